@@ -0,0 +1,137 @@
+/*
+ * The C ABI of the ffi module of the webrtc crate, built with
+ *
+ *   cargo rustc --release --features ffi --lib --crate-type staticlib
+ *
+ * Objects are made by _new or _parse, NULL when they fail, and freed by
+ * _free. A pointer and a length are a buffer of the caller borrowed for
+ * the call, NULL only with a length of 0. An object is not used from two
+ * threads at once.
+ *
+ * A NULL object is WEBRTC_ERROR_INVALID_ARGUMENT, or NULL from the
+ * functions returning a pointer. A panic of the library does not unwind
+ * into C, it is WEBRTC_ERROR_PANIC or NULL.
+ */
+
+#ifndef WEBRTC_H
+#define WEBRTC_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define WEBRTC_OK 0
+/* A NULL pointer, or an index out of range. */
+#define WEBRTC_ERROR_INVALID_ARGUMENT -1
+#define WEBRTC_ERROR_INVALID_PACKET -2
+#define WEBRTC_ERROR_BUFFER_TOO_SHORT -3
+/* SRTP protection or authentication failed. */
+#define WEBRTC_ERROR_SRTP -4
+/* A bug of the library, the object may be left in any state. */
+#define WEBRTC_ERROR_PANIC -5
+
+/* RTP */
+
+typedef struct webrtc_rtp_packet webrtc_rtp_packet;
+
+webrtc_rtp_packet *webrtc_rtp_packet_new(uint8_t payload_type, uint16_t sequence_number,
+                                         uint32_t timestamp, uint32_t ssrc, bool marker,
+                                         const uint8_t *payload, size_t payload_length);
+webrtc_rtp_packet *webrtc_rtp_packet_parse(const uint8_t *data, size_t length);
+void webrtc_rtp_packet_free(webrtc_rtp_packet *packet);
+
+/* The field, or an error. */
+int32_t webrtc_rtp_packet_get_payload_type(const webrtc_rtp_packet *packet);
+int32_t webrtc_rtp_packet_get_sequence_number(const webrtc_rtp_packet *packet);
+int32_t webrtc_rtp_packet_get_timestamp(const webrtc_rtp_packet *packet, uint32_t *timestamp);
+int32_t webrtc_rtp_packet_get_ssrc(const webrtc_rtp_packet *packet, uint32_t *ssrc);
+/* 1 with the marker, 0 without, or an error. */
+int32_t webrtc_rtp_packet_get_marker(const webrtc_rtp_packet *packet);
+/* Valid until the packet is freed. */
+const uint8_t *webrtc_rtp_packet_get_payload(const webrtc_rtp_packet *packet, size_t *length);
+/* The id is not 0 and the value is at most 255 bytes. */
+int32_t webrtc_rtp_packet_set_extension(webrtc_rtp_packet *packet, uint8_t id,
+                                        const uint8_t *value, size_t length);
+
+/* The bytes webrtc_rtp_packet_serialize writes, or an error. */
+ptrdiff_t webrtc_rtp_packet_get_length(const webrtc_rtp_packet *packet);
+/* The length written, or an error. */
+ptrdiff_t webrtc_rtp_packet_serialize(const webrtc_rtp_packet *packet, uint8_t *out,
+                                      size_t capacity);
+
+/* RTCP */
+
+typedef struct webrtc_rtcp_compound webrtc_rtcp_compound;
+
+webrtc_rtcp_compound *webrtc_rtcp_compound_new(void);
+/* NULL when a packet of it is broken. */
+webrtc_rtcp_compound *webrtc_rtcp_compound_parse(const uint8_t *data, size_t length);
+void webrtc_rtcp_compound_free(webrtc_rtcp_compound *compound);
+
+/* The packets, or an error. */
+ptrdiff_t webrtc_rtcp_compound_get_count(const webrtc_rtcp_compound *compound);
+/* 200 for a sender report, 201 for a receiver report and so on, or an error. */
+int32_t webrtc_rtcp_compound_get_packet_type(const webrtc_rtcp_compound *compound, size_t index);
+int32_t webrtc_rtcp_compound_get_ssrc(const webrtc_rtcp_compound *compound, size_t index,
+                                      uint32_t *ssrc);
+const uint8_t *webrtc_rtcp_compound_get_packet(const webrtc_rtcp_compound *compound,
+                                               size_t index, size_t *length);
+/* Valid until the next _add_ or free. */
+const uint8_t *webrtc_rtcp_compound_get_data(const webrtc_rtcp_compound *compound,
+                                             size_t *length);
+
+int32_t webrtc_rtcp_compound_add_receiver_report(webrtc_rtcp_compound *compound, uint32_t ssrc);
+int32_t webrtc_rtcp_compound_add_bye(webrtc_rtcp_compound *compound, uint32_t ssrc);
+int32_t webrtc_rtcp_compound_add_pli(webrtc_rtcp_compound *compound, uint32_t ssrc,
+                                     uint32_t media_ssrc);
+int32_t webrtc_rtcp_compound_add_nack(webrtc_rtcp_compound *compound, uint32_t ssrc,
+                                      uint32_t media_ssrc, const uint16_t *lost, size_t count);
+
+/* Session: SRTP keyed by DTLS and the RTCP interval, the time in
+ * milliseconds of a monotonic clock of the caller. */
+
+typedef struct webrtc_session webrtc_session;
+
+/* A protected packet to send. */
+#define WEBRTC_OUTPUT_TRANSMIT 0
+/* A packet received, unprotected. */
+#define WEBRTC_OUTPUT_RTP 1
+#define WEBRTC_OUTPUT_RTCP 2
+/* A compound packet is due for webrtc_session_send_rtcp, without data. */
+#define WEBRTC_OUTPUT_SENDER_REPORT 3
+#define WEBRTC_OUTPUT_RECEIVER_REPORT 4
+
+/* data is valid during the call only. */
+typedef void (*webrtc_output_callback)(void *user_data, uint32_t kind, const uint8_t *data,
+                                       size_t length);
+
+/* keying_material is the RFC 5705 export of "EXTRACTOR-dtls_srtp",
+ * 2 * (key + salt) bytes of the use_srtp profile. */
+webrtc_session *webrtc_session_new(uint16_t profile, const uint8_t *keying_material,
+                                   size_t keying_material_length, bool is_client,
+                                   uint64_t session_bandwidth, uint64_t now_ms);
+void webrtc_session_free(webrtc_session *session);
+/* NULL stops the outputs. */
+int32_t webrtc_session_set_output(webrtc_session *session, webrtc_output_callback output,
+                                  void *user_data);
+
+int32_t webrtc_session_send_rtp(webrtc_session *session, const uint8_t *packet, size_t length,
+                                uint64_t now_ms);
+int32_t webrtc_session_send_rtcp(webrtc_session *session, const uint8_t *packet, size_t length,
+                                 uint64_t now_ms);
+/* An SRTP or SRTCP packet received. */
+int32_t webrtc_session_receive(webrtc_session *session, const uint8_t *packet, size_t length,
+                               uint64_t now_ms);
+int32_t webrtc_session_process(webrtc_session *session, uint64_t now_ms);
+/* Writes when webrtc_session_process is due, -1 for never. */
+int32_t webrtc_session_poll_timeout(webrtc_session *session, int64_t *timeout_ms);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
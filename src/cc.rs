@@ -0,0 +1,105 @@
+pub mod aimd_rate_control;
+pub mod alr_detector;
+pub mod bitrate_allocator;
+pub mod bitrate_estimator;
+pub mod bwe_events;
+pub mod congestion_window;
+pub mod control;
+pub mod delay_based;
+pub mod goog_cc;
+pub mod inter_arrival;
+pub mod loss_based;
+pub mod nada;
+pub mod pacer;
+pub mod probe_bitrate_estimator;
+pub mod probe_controller;
+pub mod probe_generator;
+pub mod scream;
+pub mod transport_feedback_adapter;
+pub mod transport_feedback_generator;
+pub mod trendline;
+
+use crate::cc::bwe_events::BweEvent;
+use crate::cc::transport_feedback_adapter::TransportFeedback;
+use crate::time::Instant;
+
+/// The fate of one sent packet, as learned from transport feedback.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PacketResult {
+    pub transport_sequence_number: u16,
+    pub send_time: Instant,
+    /// `None` if the packet was reported lost.
+    pub arrival_time: Option<Instant>,
+    pub size: usize,
+    pub probe_cluster_id: Option<u32>,
+}
+
+/// Output of the overuse detector.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum BandwidthUsage {
+    Normal,
+    Underusing,
+    Overusing,
+}
+
+/// Bitrate limits given to a congestion controller at session setup.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CongestionControllerConfig {
+    pub start_bitrate: u64,
+    pub min_bitrate: u64,
+    pub max_bitrate: u64,
+}
+
+impl Default for CongestionControllerConfig {
+    fn default() -> Self {
+        CongestionControllerConfig {
+            start_bitrate: 300_000,
+            min_bitrate: 30_000,
+            max_bitrate: 10_000_000,
+        }
+    }
+}
+
+/// Send-side congestion control algorithm, fed by transport feedback.
+pub trait CongestionController {
+    fn on_transport_feedback(&mut self, feedback: &TransportFeedback);
+
+    /// ECN-CE marked bytes reported by the receiver since the last call.
+    fn on_congestion_marks(&mut self, _marked: usize, _now: Instant) {}
+
+    /// Overrides the estimate, e.g. with a successful probe.
+    fn set_bitrate(&mut self, bitrate: u64);
+
+    fn get_target_bitrate(&self) -> u64;
+
+    /// Told when the sender enters or leaves the application-limited region.
+    fn set_in_alr(&mut self, _in_alr: bool) {}
+
+    /// Returns the next diagnostics event, see `bwe_events`.
+    fn poll_event(&mut self) -> Option<BweEvent> {
+        None
+    }
+
+    /// Bytes allowed in flight, for window-based algorithms.
+    fn get_congestion_window(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CongestionControllerKind {
+    GoogCc,
+    Scream,
+    Nada,
+}
+
+pub fn new_congestion_controller(
+    kind: CongestionControllerKind,
+    config: CongestionControllerConfig,
+) -> Box<dyn CongestionController> {
+    match kind {
+        CongestionControllerKind::GoogCc => Box::new(goog_cc::GoogCcController::new(config)),
+        CongestionControllerKind::Scream => Box::new(scream::ScreamController::new(config)),
+        CongestionControllerKind::Nada => Box::new(nada::NadaController::new(config)),
+    }
+}
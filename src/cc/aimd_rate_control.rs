@@ -0,0 +1,255 @@
+// https://tools.ietf.org/html/draft-ietf-rmcat-gcc-02#section-5.5
+
+use crate::cc::BandwidthUsage;
+use crate::time::Instant;
+
+use std::time::Duration;
+
+const DEFAULT_RTT: Duration = Duration::from_millis(200);
+const BETA: f64 = 0.85;
+const MAX_INCREASE_RATE: f64 = 1.08; // per second
+const PACKET_SIZE_BITS: f64 = 1200.0 * 8.0;
+const MIN_INCREASE_BPS: f64 = 4000.0;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RateControlState {
+    Hold,
+    Increase,
+    Decrease,
+}
+
+// 検出器の出力から送信bitrateを加算的に増やし，乗算的に減らす．
+#[derive(Debug, Clone)]
+pub struct AimdRateControl {
+    min_bitrate: u64,
+    max_bitrate: u64,
+    current_bitrate: u64,
+    state: RateControlState,
+    rtt: Duration,
+    last_change: Option<Instant>,
+    initialized: bool,
+    // 直近の減少時のacked bitrateから推定したlink容量 (kbps)．
+    link_capacity: Option<f64>,
+    link_capacity_var: f64,
+    in_alr: bool,
+}
+
+impl AimdRateControl {
+    pub fn new(start_bitrate: u64, min_bitrate: u64, max_bitrate: u64) -> Self {
+        AimdRateControl {
+            min_bitrate,
+            max_bitrate,
+            current_bitrate: start_bitrate.max(min_bitrate).min(max_bitrate),
+            state: RateControlState::Hold,
+            rtt: DEFAULT_RTT,
+            last_change: None,
+            initialized: false,
+            link_capacity: None,
+            link_capacity_var: 0.4,
+            in_alr: false,
+        }
+    }
+
+    pub fn get_state(&self) -> RateControlState {
+        self.state
+    }
+
+    pub fn get_bitrate(&self) -> u64 {
+        self.current_bitrate
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: u64) {
+        self.current_bitrate = bitrate.max(self.min_bitrate).min(self.max_bitrate);
+        self.initialized = true;
+    }
+
+    pub fn set_rtt(&mut self, rtt: Duration) {
+        self.rtt = rtt;
+    }
+
+    /// While application limited the acked bitrate says nothing about the
+    /// link, so the estimate neither grows nor follows it down.
+    pub fn set_in_alr(&mut self, in_alr: bool) {
+        self.in_alr = in_alr;
+    }
+
+    pub fn set_min_bitrate(&mut self, bitrate: u64) {
+        self.min_bitrate = bitrate;
+        self.current_bitrate = self.current_bitrate.max(bitrate);
+    }
+
+    pub fn set_max_bitrate(&mut self, bitrate: u64) {
+        self.max_bitrate = bitrate;
+        self.current_bitrate = self.current_bitrate.min(bitrate);
+    }
+
+    /// Time to detect and react to overuse, used to size the additive step.
+    fn response_time(&self) -> Duration {
+        self.rtt + Duration::from_millis(100)
+    }
+
+    pub fn update(
+        &mut self,
+        usage: BandwidthUsage,
+        acked_bitrate: Option<u64>,
+        now: Instant,
+    ) -> u64 {
+        if !self.initialized {
+            match acked_bitrate {
+                // before the first overuse, take the measured throughput once.
+                Some(v) if usage == BandwidthUsage::Overusing => {
+                    self.current_bitrate = v.max(self.min_bitrate).min(self.max_bitrate);
+                    self.initialized = true;
+                }
+                Some(_) => {}
+                None => return self.current_bitrate,
+            }
+        }
+
+        self.state = match (usage, self.state) {
+            (BandwidthUsage::Normal, RateControlState::Hold) => RateControlState::Increase,
+            (BandwidthUsage::Normal, state) => state,
+            (BandwidthUsage::Overusing, _) => RateControlState::Decrease,
+            (BandwidthUsage::Underusing, _) => RateControlState::Hold,
+        };
+
+        let elapsed = self
+            .last_change
+            .map(|t| now.saturating_duration_since(t))
+            .unwrap_or_default()
+            .min(Duration::from_secs(1));
+
+        let mut bitrate = self.current_bitrate as f64;
+        match self.state {
+            RateControlState::Hold => {}
+            RateControlState::Increase if self.in_alr => {
+                self.last_change = Some(now);
+            }
+            RateControlState::Increase => {
+                if let (Some(acked), Some(capacity)) = (acked_bitrate, self.link_capacity) {
+                    // the link capacity has changed, forget the estimate.
+                    let kbps = acked as f64 / 1000.0;
+                    let std_dev = (self.link_capacity_var * capacity).sqrt();
+                    if kbps > capacity + 3.0 * std_dev {
+                        self.link_capacity = None;
+                    }
+                }
+
+                bitrate += if self.link_capacity.is_some() {
+                    self.additive_increase(elapsed)
+                } else {
+                    self.multiplicative_increase(bitrate, elapsed)
+                };
+
+                // never run far ahead of what actually gets through.
+                if let Some(acked) = acked_bitrate {
+                    bitrate = bitrate.min(1.5 * acked as f64 + 10_000.0);
+                }
+                self.last_change = Some(now);
+            }
+            RateControlState::Decrease => {
+                if let Some(acked) = acked_bitrate {
+                    bitrate = BETA * acked as f64;
+                    self.update_link_capacity(acked as f64 / 1000.0);
+                } else {
+                    bitrate *= BETA;
+                }
+                // only decrease once per overuse, then wait.
+                self.state = RateControlState::Hold;
+                self.last_change = Some(now);
+            }
+        }
+
+        self.current_bitrate = (bitrate as u64).max(self.min_bitrate).min(self.max_bitrate);
+        self.current_bitrate
+    }
+
+    fn multiplicative_increase(&self, bitrate: f64, elapsed: Duration) -> f64 {
+        let alpha = MAX_INCREASE_RATE.powf(elapsed.as_secs_f64());
+        (bitrate * (alpha - 1.0)).max(1000.0)
+    }
+
+    fn additive_increase(&self, elapsed: Duration) -> f64 {
+        // about one packet per response time.
+        let bits_per_frame = self.current_bitrate as f64 / 30.0;
+        let packets_per_frame = (bits_per_frame / PACKET_SIZE_BITS).ceil();
+        let avg_packet_bits = bits_per_frame / packets_per_frame;
+        let per_second =
+            (avg_packet_bits / self.response_time().as_secs_f64()).max(MIN_INCREASE_BPS);
+        per_second * elapsed.as_secs_f64()
+    }
+
+    fn update_link_capacity(&mut self, sample_kbps: f64) {
+        let alpha = 0.05;
+        let capacity = match self.link_capacity {
+            Some(v) => (1.0 - alpha) * v + alpha * sample_kbps,
+            None => sample_kbps,
+        };
+        let norm = capacity.max(1.0);
+        self.link_capacity_var = ((1.0 - alpha) * self.link_capacity_var
+            + alpha * (capacity - sample_kbps).powi(2) / norm)
+            .clamp(0.4, 2.5);
+        self.link_capacity = Some(capacity);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn increase_and_decrease_test() {
+        let now = Instant::now();
+        let mut aimd = AimdRateControl::new(300_000, 30_000, 2_000_000);
+        aimd.set_bitrate(300_000);
+
+        let mut t = now;
+        aimd.update(BandwidthUsage::Normal, Some(300_000), t);
+        for _ in 0..10 {
+            t += Duration::from_millis(100);
+            aimd.update(BandwidthUsage::Normal, Some(300_000), t);
+        }
+        // about 8% per second.
+        let increased = aimd.get_bitrate();
+        assert!(increased > 320_000 && increased < 330_000, "{}", increased);
+
+        t += Duration::from_millis(100);
+        let decreased = aimd.update(BandwidthUsage::Overusing, Some(400_000), t);
+        assert_eq!(decreased, 340_000);
+        assert_eq!(aimd.get_state(), RateControlState::Hold);
+    }
+
+    #[test]
+    fn capped_by_acked_bitrate_test() {
+        let mut t = Instant::now();
+        let mut aimd = AimdRateControl::new(1_000_000, 30_000, 5_000_000);
+        aimd.set_bitrate(1_000_000);
+
+        for _ in 0..20 {
+            t += Duration::from_millis(100);
+            aimd.update(BandwidthUsage::Normal, Some(100_000), t);
+        }
+        assert_eq!(aimd.get_bitrate(), 160_000);
+    }
+
+    #[test]
+    fn hold_in_alr_test() {
+        let mut t = Instant::now();
+        let mut aimd = AimdRateControl::new(1_000_000, 30_000, 5_000_000);
+        aimd.set_bitrate(1_000_000);
+        aimd.set_in_alr(true);
+
+        for _ in 0..20 {
+            t += Duration::from_millis(100);
+            aimd.update(BandwidthUsage::Normal, Some(100_000), t);
+        }
+        assert_eq!(aimd.get_bitrate(), 1_000_000);
+
+        // overuse is still reacted to.
+        t += Duration::from_millis(100);
+        assert_eq!(
+            aimd.update(BandwidthUsage::Overusing, Some(100_000), t),
+            85_000
+        );
+    }
+}
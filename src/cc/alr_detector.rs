@@ -0,0 +1,144 @@
+use crate::time::Instant;
+
+use std::time::Duration;
+
+const MAX_BUDGET_WINDOW: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlrDetectorConfig {
+    /// Fraction of the estimate the sender is expected to use.
+    pub bandwidth_usage_ratio: f64,
+    /// ALR starts when the unused budget exceeds this fraction of its max.
+    pub start_budget_level_ratio: f64,
+    /// ALR stops when the unused budget falls below this fraction.
+    pub stop_budget_level_ratio: f64,
+}
+
+impl Default for AlrDetectorConfig {
+    fn default() -> Self {
+        AlrDetectorConfig {
+            bandwidth_usage_ratio: 0.65,
+            start_budget_level_ratio: 0.80,
+            stop_budget_level_ratio: 0.50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AlrEvent {
+    Started(Instant),
+    Stopped(Instant),
+}
+
+// 推定帯域を使い切っていない区間 (application limited region) を検出する．
+#[derive(Debug, Clone)]
+pub struct AlrDetector {
+    config: AlrDetectorConfig,
+    rate: u64,   // bps
+    budget: f64, // bytes
+    last_send: Option<Instant>,
+    alr_started: Option<Instant>,
+}
+
+impl AlrDetector {
+    pub fn new(config: AlrDetectorConfig) -> Self {
+        AlrDetector {
+            config,
+            rate: 0,
+            budget: 0.0,
+            last_send: None,
+            alr_started: None,
+        }
+    }
+
+    pub fn set_estimated_bitrate(&mut self, bitrate: u64) {
+        self.rate = (bitrate as f64 * self.config.bandwidth_usage_ratio) as u64;
+        let max = self.max_budget();
+        self.budget = self.budget.clamp(-max, max);
+    }
+
+    pub fn get_alr_start_time(&self) -> Option<Instant> {
+        self.alr_started
+    }
+
+    pub fn is_in_alr(&self) -> bool {
+        self.alr_started.is_some()
+    }
+
+    /// Accounts every packet sent, media and padding alike. Call it with
+    /// 0 bytes while idle so an idle sender is detected too.
+    pub fn on_bytes_sent(&mut self, bytes: usize, now: Instant) -> Option<AlrEvent> {
+        let elapsed = self
+            .last_send
+            .map(|t| now.saturating_duration_since(t))
+            .unwrap_or_default()
+            .min(MAX_BUDGET_WINDOW);
+        self.last_send = Some(now);
+
+        let max = self.max_budget();
+        if max <= 0.0 {
+            return None;
+        }
+        self.budget += self.rate as f64 / 8.0 * elapsed.as_secs_f64();
+        self.budget -= bytes as f64;
+        self.budget = self.budget.clamp(-max, max);
+
+        let level = self.budget / max;
+        match self.alr_started {
+            None if level > self.config.start_budget_level_ratio => {
+                self.alr_started = Some(now);
+                Some(AlrEvent::Started(now))
+            }
+            Some(_) if level < self.config.stop_budget_level_ratio => {
+                self.alr_started = None;
+                Some(AlrEvent::Stopped(now))
+            }
+            _ => None,
+        }
+    }
+
+    fn max_budget(&self) -> f64 {
+        self.rate as f64 / 8.0 * MAX_BUDGET_WINDOW.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn alr_start_stop_test() {
+        let start = Instant::now();
+        let mut detector = AlrDetector::new(AlrDetectorConfig::default());
+        detector.set_estimated_bitrate(1_000_000);
+
+        // sending the full estimate, 1250 bytes per 10ms.
+        let mut t = start;
+        for _ in 0..100 {
+            t += Duration::from_millis(10);
+            assert_eq!(detector.on_bytes_sent(1250, t), None);
+        }
+
+        // the application goes quiet.
+        let mut started = None;
+        for _ in 0..100 {
+            t += Duration::from_millis(10);
+            if let Some(v) = detector.on_bytes_sent(0, t) {
+                started = Some(v);
+                break;
+            }
+        }
+        assert!(matches!(started, Some(AlrEvent::Started(_))));
+        assert!(detector.is_in_alr());
+
+        let mut stopped = None;
+        for _ in 0..100 {
+            t += Duration::from_millis(10);
+            if let Some(v) = detector.on_bytes_sent(2500, t) {
+                stopped = Some(v);
+                break;
+            }
+        }
+        assert_eq!(stopped, Some(AlrEvent::Stopped(t)));
+    }
+}
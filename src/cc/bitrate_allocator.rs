@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+/// Receives the target bitrate of one send stream, e.g. its encoder.
+pub trait BitrateAllocationObserver {
+    fn on_bitrate_updated(&mut self, bitrate: u64);
+}
+
+impl<F: FnMut(u64)> BitrateAllocationObserver for F {
+    fn on_bitrate_updated(&mut self, bitrate: u64) {
+        self(bitrate)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AllocationConfig {
+    pub min_bitrate: u64,
+    pub max_bitrate: u64,
+    /// Share of the bitrate above the minimums, relative to other streams.
+    pub priority: f64,
+    /// Keep the minimum even when the total does not cover it, instead of
+    /// pausing the stream.
+    pub enforce_min: bool,
+}
+
+impl Default for AllocationConfig {
+    fn default() -> Self {
+        AllocationConfig {
+            min_bitrate: 30_000,
+            max_bitrate: 2_500_000,
+            priority: 1.0,
+            enforce_min: true,
+        }
+    }
+}
+
+struct AllocatedStream {
+    config: AllocationConfig,
+    observer: Box<dyn BitrateAllocationObserver>,
+    allocated: Option<u64>,
+}
+
+// 輻輳制御が決めた合計bitrateを送信stream毎にmin/max/priorityで分配する．
+pub struct BitrateAllocator {
+    streams: BTreeMap<u32, AllocatedStream>,
+    total: u64,
+}
+
+impl Default for BitrateAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BitrateAllocator {
+    pub fn new() -> Self {
+        BitrateAllocator {
+            streams: BTreeMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Registers (or reconfigures) the stream `id` and reallocates.
+    pub fn add_stream(
+        &mut self,
+        id: u32,
+        config: AllocationConfig,
+        observer: Box<dyn BitrateAllocationObserver>,
+    ) {
+        self.streams.insert(
+            id,
+            AllocatedStream {
+                config,
+                observer,
+                allocated: None,
+            },
+        );
+        self.reallocate();
+    }
+
+    pub fn update_stream(&mut self, id: u32, config: AllocationConfig) {
+        if let Some(v) = self.streams.get_mut(&id) {
+            v.config = config;
+            self.reallocate();
+        }
+    }
+
+    pub fn remove_stream(&mut self, id: u32) {
+        if self.streams.remove(&id).is_some() {
+            self.reallocate();
+        }
+    }
+
+    pub fn get_allocation(&self, id: u32) -> Option<u64> {
+        self.streams.get(&id).and_then(|v| v.allocated)
+    }
+
+    /// Sum of the streams' minimums, the lowest useful target.
+    pub fn get_min_bitrate(&self) -> u64 {
+        self.streams
+            .values()
+            .filter(|v| v.config.enforce_min)
+            .map(|v| v.config.min_bitrate)
+            .sum()
+    }
+
+    pub fn get_max_bitrate(&self) -> u64 {
+        self.streams.values().map(|v| v.config.max_bitrate).sum()
+    }
+
+    /// Splits the congestion-controlled `total` and notifies every stream
+    /// whose allocation changed.
+    pub fn on_target_bitrate(&mut self, total: u64) {
+        self.total = total;
+        self.reallocate();
+    }
+
+    fn reallocate(&mut self) {
+        let allocation = allocate(
+            self.total,
+            &self
+                .streams
+                .iter()
+                .map(|(id, v)| (*id, v.config))
+                .collect::<Vec<_>>(),
+        );
+
+        for (id, bitrate) in allocation {
+            if let Some(stream) = self.streams.get_mut(&id) {
+                if stream.allocated != Some(bitrate) {
+                    stream.allocated = Some(bitrate);
+                    stream.observer.on_bitrate_updated(bitrate);
+                }
+            }
+        }
+    }
+}
+
+fn allocate(total: u64, streams: &[(u32, AllocationConfig)]) -> Vec<(u32, u64)> {
+    let sum_min: u64 = streams.iter().map(|(_, v)| v.min_bitrate).sum();
+
+    if total < sum_min {
+        // not enough for everyone, the streams that must run come first and the
+        // others get their minimum in registration order or are paused.
+        let mut remaining = total;
+        let mut out: Vec<(u32, u64)> = Vec::new();
+        for (id, config) in streams.iter().filter(|(_, v)| v.enforce_min) {
+            remaining = remaining.saturating_sub(config.min_bitrate);
+            out.push((*id, config.min_bitrate));
+        }
+        for (id, config) in streams.iter().filter(|(_, v)| !v.enforce_min) {
+            if remaining >= config.min_bitrate {
+                remaining -= config.min_bitrate;
+                out.push((*id, config.min_bitrate));
+            } else {
+                out.push((*id, 0));
+            }
+        }
+        return out;
+    }
+
+    let mut allocated: Vec<(u32, u64)> =
+        streams.iter().map(|(id, v)| (*id, v.min_bitrate)).collect();
+    let mut remaining = total - sum_min;
+
+    // priorityに比例して配り，maxを超えた分は残りのstreamで分け直す．
+    loop {
+        let open: Vec<usize> = (0..streams.len())
+            .filter(|i| allocated[*i].1 < streams[*i].1.max_bitrate)
+            .collect();
+        let priority_sum: f64 = open.iter().map(|i| streams[*i].1.priority).sum();
+        if remaining == 0 || open.is_empty() || priority_sum <= 0.0 {
+            break;
+        }
+
+        let mut distributed = 0;
+        for i in open {
+            let share = (remaining as f64 * streams[i].1.priority / priority_sum) as u64;
+            let room = streams[i].1.max_bitrate - allocated[i].1;
+            let add = share.min(room);
+            allocated[i].1 += add;
+            distributed += add;
+        }
+        if distributed == 0 {
+            break;
+        }
+        remaining -= distributed;
+    }
+
+    allocated
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn config(min_bitrate: u64, max_bitrate: u64, priority: f64) -> AllocationConfig {
+        AllocationConfig {
+            min_bitrate,
+            max_bitrate,
+            priority,
+            enforce_min: true,
+        }
+    }
+
+    #[test]
+    fn priority_allocation_test() {
+        let streams = [
+            (1, config(50_000, 500_000, 1.0)),
+            (2, config(100_000, 3_000_000, 2.0)),
+        ];
+
+        // 1.15M above the minimums, split 1:2.
+        assert_eq!(
+            allocate(1_300_000, &streams),
+            vec![(1, 433_333), (2, 866_666)]
+        );
+        // stream 1 is capped and the rest goes to stream 2.
+        assert_eq!(
+            allocate(2_100_000, &streams),
+            vec![(1, 500_000), (2, 1_600_000)]
+        );
+    }
+
+    #[test]
+    fn low_rate_allocation_test() {
+        let mut audio = config(30_000, 60_000, 1.0);
+        audio.enforce_min = true;
+        let mut video = config(200_000, 2_000_000, 1.0);
+        video.enforce_min = false;
+
+        assert_eq!(
+            allocate(100_000, &[(1, audio), (2, video)]),
+            vec![(1, 30_000), (2, 0)]
+        );
+    }
+
+    #[test]
+    fn observer_test() {
+        let updates = Rc::new(RefCell::new(Vec::new()));
+        let mut allocator = BitrateAllocator::new();
+
+        let recorded = updates.clone();
+        allocator.add_stream(
+            1,
+            config(50_000, 1_000_000, 1.0),
+            Box::new(move |v| recorded.borrow_mut().push(v)),
+        );
+        allocator.on_target_bitrate(500_000);
+        allocator.on_target_bitrate(500_000);
+        allocator.on_target_bitrate(2_000_000);
+
+        assert_eq!(*updates.borrow(), vec![50_000, 500_000, 1_000_000]);
+        assert_eq!(allocator.get_allocation(1), Some(1_000_000));
+        assert_eq!(allocator.get_max_bitrate(), 1_000_000);
+    }
+}
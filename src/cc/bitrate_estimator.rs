@@ -0,0 +1,179 @@
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// 受信統計，pacer，probeの評価で同じbyte countの方法を使うための共通の窓．
+pub trait BitrateEstimator {
+    /// Accounts `bytes` sent or received at `now`.
+    fn update(&mut self, bytes: usize, now: Instant);
+
+    /// Returns the current rate in bps, or `None` without enough samples.
+    fn get_bitrate(&mut self, now: Instant) -> Option<u64>;
+}
+
+/// Bitrate over the last `window` (e.g. 500ms or 1s).
+#[derive(Debug, Clone)]
+pub struct SlidingWindowBitrate {
+    window: Duration,
+    samples: VecDeque<(Instant, usize)>,
+    bytes: u64,
+    first_update: Option<Instant>,
+}
+
+impl SlidingWindowBitrate {
+    pub fn new(window: Duration) -> Self {
+        SlidingWindowBitrate {
+            window,
+            samples: VecDeque::new(),
+            bytes: 0,
+            first_update: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.bytes = 0;
+        self.first_update = None;
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((t, bytes)) = self.samples.front() {
+            if now.saturating_duration_since(*t) < self.window {
+                break;
+            }
+            self.bytes -= *bytes as u64;
+            self.samples.pop_front();
+        }
+    }
+}
+
+impl BitrateEstimator for SlidingWindowBitrate {
+    fn update(&mut self, bytes: usize, now: Instant) {
+        self.expire(now);
+        self.first_update.get_or_insert(now);
+        self.samples.push_back((now, bytes));
+        self.bytes += bytes as u64;
+    }
+
+    fn get_bitrate(&mut self, now: Instant) -> Option<u64> {
+        self.expire(now);
+
+        // until a full window has passed, divide by the time actually observed.
+        let observed = now.saturating_duration_since(self.first_update?);
+        let span = observed.min(self.window);
+        if span < Duration::from_millis(1) {
+            return None;
+        }
+        Some(self.bytes * 8 * 1_000_000 / span.as_micros() as u64)
+    }
+}
+
+/// Exponentially smoothed bitrate sampled over fixed `interval` buckets.
+#[derive(Debug, Clone)]
+pub struct ExponentialBitrate {
+    interval: Duration,
+    alpha: f64,
+    bucket_start: Option<Instant>,
+    bucket_bytes: u64,
+    estimate: Option<f64>,
+}
+
+impl ExponentialBitrate {
+    /// `alpha` is the weight of a new bucket, in (0, 1].
+    pub fn new(interval: Duration, alpha: f64) -> Self {
+        ExponentialBitrate {
+            interval,
+            alpha,
+            bucket_start: None,
+            bucket_bytes: 0,
+            estimate: None,
+        }
+    }
+
+    fn roll(&mut self, now: Instant) {
+        let mut start = match self.bucket_start {
+            Some(v) => v,
+            None => return,
+        };
+
+        while now.saturating_duration_since(start) >= self.interval {
+            let sample = self.bucket_bytes as f64 * 8.0 / self.interval.as_secs_f64();
+            self.estimate = Some(match self.estimate {
+                Some(v) => v * (1.0 - self.alpha) + sample * self.alpha,
+                None => sample,
+            });
+            self.bucket_bytes = 0;
+            start += self.interval;
+        }
+        self.bucket_start = Some(start);
+    }
+}
+
+impl BitrateEstimator for ExponentialBitrate {
+    fn update(&mut self, bytes: usize, now: Instant) {
+        self.bucket_start.get_or_insert(now);
+        self.roll(now);
+        self.bucket_bytes += bytes as u64;
+    }
+
+    fn get_bitrate(&mut self, now: Instant) -> Option<u64> {
+        self.roll(now);
+        self.estimate.map(|v| v as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sliding_window_test() {
+        let now = Instant::now();
+        let mut rate = SlidingWindowBitrate::new(Duration::from_millis(500));
+        assert_eq!(rate.get_bitrate(now), None);
+
+        // 1000 bytes every 10ms = 800kbps
+        for i in 0..100 {
+            rate.update(1000, now + Duration::from_millis(i * 10));
+        }
+        let t = now + Duration::from_millis(995);
+        assert_eq!(rate.get_bitrate(t), Some(800_000));
+
+        // nothing sent for a full window.
+        assert_eq!(rate.get_bitrate(t + Duration::from_millis(600)), Some(0));
+    }
+
+    #[test]
+    fn sliding_window_partial_test() {
+        let now = Instant::now();
+        let mut rate = SlidingWindowBitrate::new(Duration::from_secs(1));
+        rate.update(1000, now);
+        rate.update(1000, now + Duration::from_millis(100));
+        assert_eq!(
+            rate.get_bitrate(now + Duration::from_millis(200)),
+            Some(80_000)
+        );
+    }
+
+    #[test]
+    fn exponential_test() {
+        let now = Instant::now();
+        let mut rate = ExponentialBitrate::new(Duration::from_millis(100), 0.5);
+
+        for i in 0..10 {
+            rate.update(1000, now + Duration::from_millis(i * 10));
+        }
+        assert_eq!(rate.get_bitrate(now + Duration::from_millis(50)), None);
+        assert_eq!(
+            rate.get_bitrate(now + Duration::from_millis(100)),
+            Some(800_000)
+        );
+
+        // an empty bucket halves the estimate.
+        assert_eq!(
+            rate.get_bitrate(now + Duration::from_millis(200)),
+            Some(400_000)
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::cc::probe_bitrate_estimator::ProbeResult;
+use crate::cc::probe_generator::ProbeCluster;
+use crate::cc::BandwidthUsage;
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// What the bandwidth estimation did and why, for charting it live.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BweEvent {
+    EstimateUpdated {
+        time: Instant,
+        target_bitrate: u64,
+    },
+    /// The delay-based overuse detector changed its output.
+    UsageChanged {
+        time: Instant,
+        previous: BandwidthUsage,
+        current: BandwidthUsage,
+    },
+    LossReport {
+        time: Instant,
+        lost: usize,
+        packets: usize,
+    },
+    ProbeClusterCreated {
+        time: Instant,
+        cluster: ProbeCluster,
+    },
+    ProbeResult {
+        time: Instant,
+        result: ProbeResult,
+        /// False if the probe did not reach the ratio required to continue.
+        success: bool,
+    },
+}
+
+// pollされるまでeventを貯めておく．溢れたら古いものから捨てる．
+#[derive(Debug, Clone)]
+pub struct BweEventLog {
+    capacity: usize,
+    events: VecDeque<BweEvent>,
+    enabled: bool,
+    last_estimate: Option<u64>,
+}
+
+impl Default for BweEventLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl BweEventLog {
+    pub fn new(capacity: usize) -> Self {
+        BweEventLog {
+            capacity,
+            events: VecDeque::new(),
+            enabled: true,
+            last_estimate: None,
+        }
+    }
+
+    /// Disabled logs drop every event, for applications that do not poll.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.events.clear();
+        }
+    }
+
+    pub fn push(&mut self, event: BweEvent) {
+        if !self.enabled || self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// Pushes `EstimateUpdated` if the target differs from the last one.
+    pub fn push_estimate(&mut self, target_bitrate: u64, time: Instant) {
+        if self.last_estimate != Some(target_bitrate) {
+            self.last_estimate = Some(target_bitrate);
+            self.push(BweEvent::EstimateUpdated {
+                time,
+                target_bitrate,
+            });
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<BweEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn event_log_capacity_test() {
+        let now = Instant::now();
+        let mut log = BweEventLog::new(2);
+        for bitrate in 1..4 {
+            log.push(BweEvent::EstimateUpdated {
+                time: now,
+                target_bitrate: bitrate,
+            });
+        }
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            log.poll_event(),
+            Some(BweEvent::EstimateUpdated {
+                time: now,
+                target_bitrate: 2
+            })
+        );
+
+        log.set_enabled(false);
+        assert!(log.is_empty());
+        log.push(BweEvent::LossReport {
+            time: now,
+            lost: 1,
+            packets: 10,
+        });
+        assert_eq!(log.poll_event(), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CongestionWindowConfig {
+    /// Queuing allowed on top of one round trip worth of data.
+    pub additional_time: Duration,
+    pub min_window: usize,
+}
+
+impl Default for CongestionWindowConfig {
+    fn default() -> Self {
+        CongestionWindowConfig {
+            additional_time: Duration::from_millis(100),
+            min_window: 3000,
+        }
+    }
+}
+
+// RTTと推定bitrateから送信中に許すbyte数を決める．
+// RTTが急に短くなった時もpacerがここで止まるのでqueueが積み上がらない．
+#[derive(Debug, Clone, Copy)]
+pub struct CongestionWindow {
+    config: CongestionWindowConfig,
+    window: Option<usize>,
+}
+
+impl CongestionWindow {
+    pub fn new(config: CongestionWindowConfig) -> Self {
+        CongestionWindow {
+            config,
+            window: None,
+        }
+    }
+
+    pub fn get_window(&self) -> Option<usize> {
+        self.window
+    }
+
+    pub fn update(&mut self, target_bitrate: u64, rtt: Duration) -> usize {
+        let time = rtt + self.config.additional_time;
+        let bytes = (target_bitrate as f64 / 8.0 * time.as_secs_f64()) as usize;
+        let window = bytes.max(self.config.min_window);
+        self.window = Some(window);
+        window
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn congestion_window_test() {
+        let mut window = CongestionWindow::new(CongestionWindowConfig::default());
+        assert_eq!(window.get_window(), None);
+
+        // 1Mbps over 200ms + 100ms
+        assert_eq!(window.update(1_000_000, Duration::from_millis(200)), 37_500);
+        assert_eq!(window.update(10_000, Duration::from_millis(10)), 3000);
+    }
+}
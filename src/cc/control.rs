@@ -0,0 +1,133 @@
+/*
+    a CongestionController as a StateMachine. the target bitrate and the
+    congestion window come out when they change, with the BweEvents of the
+    controller before them.
+
+      Feedback        -> on_transport_feedback
+      CongestionMarks -> on_congestion_marks
+      InAlr           -> set_in_alr
+      SetBitrate      -> set_bitrate
+
+    it has no timer of its own, the feedback moves it.
+*/
+
+use crate::cc::bwe_events::BweEvent;
+use crate::cc::transport_feedback_adapter::TransportFeedback;
+use crate::cc::{
+    new_congestion_controller, CongestionController, CongestionControllerConfig,
+    CongestionControllerKind,
+};
+use crate::sansio::StateMachine;
+use crate::time::Instant;
+
+use std::convert::Infallible;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CongestionInput {
+    Feedback(TransportFeedback),
+    /// ECN-CE marked bytes since the last one.
+    CongestionMarks(usize),
+    InAlr(bool),
+    SetBitrate(u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CongestionOutput {
+    Bwe(BweEvent),
+    TargetBitrate(u64),
+    CongestionWindow(Option<usize>),
+}
+
+// 最後に出したtarget bitrateとcongestion window．
+pub struct CongestionControl {
+    controller: Box<dyn CongestionController>,
+    target_bitrate: Option<u64>,
+    congestion_window: Option<Option<usize>>,
+}
+
+impl CongestionControl {
+    pub fn new(controller: Box<dyn CongestionController>) -> Self {
+        CongestionControl {
+            controller,
+            target_bitrate: None,
+            congestion_window: None,
+        }
+    }
+
+    pub fn with_kind(kind: CongestionControllerKind, config: CongestionControllerConfig) -> Self {
+        CongestionControl::new(new_congestion_controller(kind, config))
+    }
+
+    pub fn get_controller(&self) -> &dyn CongestionController {
+        self.controller.as_ref()
+    }
+
+    pub fn get_target_bitrate(&self) -> u64 {
+        self.controller.get_target_bitrate()
+    }
+}
+
+impl StateMachine for CongestionControl {
+    type Input = CongestionInput;
+    type Output = CongestionOutput;
+    type Error = Infallible;
+
+    fn handle_input(&mut self, input: CongestionInput, now: Instant) -> Result<(), Infallible> {
+        match input {
+            CongestionInput::Feedback(v) => self.controller.on_transport_feedback(&v),
+            CongestionInput::CongestionMarks(v) => self.controller.on_congestion_marks(v, now),
+            CongestionInput::InAlr(v) => self.controller.set_in_alr(v),
+            CongestionInput::SetBitrate(v) => self.controller.set_bitrate(v),
+        }
+        Ok(())
+    }
+
+    fn poll_output(&mut self, _now: Instant) -> Option<CongestionOutput> {
+        if let Some(v) = self.controller.poll_event() {
+            return Some(CongestionOutput::Bwe(v));
+        }
+        let bitrate = self.controller.get_target_bitrate();
+        if self.target_bitrate != Some(bitrate) {
+            self.target_bitrate = Some(bitrate);
+            return Some(CongestionOutput::TargetBitrate(bitrate));
+        }
+        let window = self.controller.get_congestion_window();
+        if self.congestion_window != Some(window) {
+            self.congestion_window = Some(window);
+            return Some(CongestionOutput::CongestionWindow(window));
+        }
+        None
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sansio::drain_outputs;
+
+    #[test]
+    fn congestion_control_test() {
+        let now = Instant::now();
+        let config = CongestionControllerConfig::default();
+        let mut control = CongestionControl::with_kind(CongestionControllerKind::GoogCc, config);
+        let outputs = drain_outputs(&mut control, now);
+        assert!(outputs.contains(&CongestionOutput::TargetBitrate(config.start_bitrate)));
+        assert!(outputs.contains(&CongestionOutput::CongestionWindow(None)));
+        assert_eq!(drain_outputs(&mut control, now), vec![]);
+
+        control
+            .handle_input(CongestionInput::SetBitrate(500_000), now)
+            .unwrap();
+        let outputs = drain_outputs(&mut control, now);
+        assert_eq!(
+            outputs.last(),
+            Some(&CongestionOutput::TargetBitrate(500_000))
+        );
+        assert_eq!(control.get_target_bitrate(), 500_000);
+        assert_eq!(control.poll_timeout(), None);
+    }
+}
@@ -0,0 +1,217 @@
+// https://tools.ietf.org/html/draft-ietf-rmcat-gcc-02#section-5
+
+use crate::cc::aimd_rate_control::AimdRateControl;
+use crate::cc::inter_arrival::InterArrival;
+use crate::cc::trendline::TrendlineEstimator;
+use crate::cc::{BandwidthUsage, PacketResult};
+use crate::time::Instant;
+
+use std::time::Duration;
+
+const STREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct DelayBasedResult {
+    /// True when the feedback produced a new target.
+    pub updated: bool,
+    pub target_bitrate: u64,
+    pub usage: BandwidthUsage,
+    /// Set when `usage` differs from the previous feedback's.
+    pub previous_usage: Option<BandwidthUsage>,
+}
+
+// TWCC feedbackの到着時刻から遅延の傾向を求め，目標bitrateを決める送信側の推定器．
+#[derive(Debug, Clone)]
+pub struct DelayBasedBwe {
+    inter_arrival: InterArrival,
+    trendline: TrendlineEstimator,
+    rate_control: AimdRateControl,
+    usage: BandwidthUsage,
+    last_seen: Option<Instant>,
+}
+
+impl DelayBasedBwe {
+    pub fn new(start_bitrate: u64, min_bitrate: u64, max_bitrate: u64) -> Self {
+        DelayBasedBwe {
+            inter_arrival: InterArrival::new(),
+            trendline: TrendlineEstimator::default(),
+            rate_control: AimdRateControl::new(start_bitrate, min_bitrate, max_bitrate),
+            usage: BandwidthUsage::Normal,
+            last_seen: None,
+        }
+    }
+
+    pub fn get_bitrate(&self) -> u64 {
+        self.rate_control.get_bitrate()
+    }
+
+    pub fn get_usage(&self) -> BandwidthUsage {
+        self.usage
+    }
+
+    /// Overrides the estimate, e.g. with a probe result or the configured
+    /// start bitrate.
+    pub fn set_bitrate(&mut self, bitrate: u64) {
+        self.rate_control.set_bitrate(bitrate);
+    }
+
+    pub fn set_rtt(&mut self, rtt: Duration) {
+        self.rate_control.set_rtt(rtt);
+    }
+
+    pub fn set_in_alr(&mut self, in_alr: bool) {
+        self.rate_control.set_in_alr(in_alr);
+    }
+
+    pub fn set_min_bitrate(&mut self, bitrate: u64) {
+        self.rate_control.set_min_bitrate(bitrate);
+    }
+
+    pub fn set_max_bitrate(&mut self, bitrate: u64) {
+        self.rate_control.set_max_bitrate(bitrate);
+    }
+
+    /// Consumes one transport feedback worth of packet results, in send
+    /// order. `acked_bitrate` is the throughput measured from the feedback.
+    pub fn incoming_packet_feedback(
+        &mut self,
+        results: &[PacketResult],
+        acked_bitrate: Option<u64>,
+        now: Instant,
+    ) -> DelayBasedResult {
+        let previous = self.usage;
+        let mut received = false;
+
+        for result in results {
+            let arrival_time = match result.arrival_time {
+                Some(v) => v,
+                None => continue,
+            };
+            received = true;
+
+            // the stream was paused, old groups say nothing about the queue now.
+            if let Some(last) = self.last_seen {
+                if now.saturating_duration_since(last) > STREAM_TIMEOUT {
+                    self.inter_arrival.reset();
+                    self.trendline = TrendlineEstimator::default();
+                }
+            }
+            self.last_seen = Some(now);
+
+            if let Some(delta) =
+                self.inter_arrival
+                    .compute_deltas(result.send_time, arrival_time, result.size)
+            {
+                self.usage = self.trendline.update(
+                    delta.arrival_delta_ms,
+                    delta.send_delta_ms,
+                    delta.arrival_time,
+                );
+            }
+        }
+
+        if !received {
+            return DelayBasedResult {
+                updated: false,
+                target_bitrate: self.get_bitrate(),
+                usage: self.usage,
+                previous_usage: None,
+            };
+        }
+
+        let before = self.get_bitrate();
+        let target_bitrate = self.rate_control.update(self.usage, acked_bitrate, now);
+
+        DelayBasedResult {
+            updated: target_bitrate != before || self.usage == BandwidthUsage::Overusing,
+            target_bitrate,
+            usage: self.usage,
+            previous_usage: if previous != self.usage {
+                Some(previous)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn feedback(
+        base: Instant,
+        seq: &mut u16,
+        from_ms: u64,
+        count: u64,
+        extra_delay_ms: impl Fn(u64) -> u64,
+    ) -> Vec<PacketResult> {
+        (from_ms..from_ms + count)
+            .map(|i| {
+                let send_time = base + Duration::from_millis(i * 10);
+                *seq = seq.wrapping_add(1);
+                PacketResult {
+                    transport_sequence_number: *seq,
+                    send_time,
+                    arrival_time: Some(send_time + Duration::from_millis(30 + extra_delay_ms(i))),
+                    size: 1200,
+                    probe_cluster_id: None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delay_increase_causes_decrease_test() {
+        let base = Instant::now();
+        let mut seq = 0;
+        let mut bwe = DelayBasedBwe::new(1_000_000, 50_000, 5_000_000);
+        bwe.set_bitrate(1_000_000);
+
+        // 2 seconds of stable delay, 10 packets per feedback.
+        let mut i = 0;
+        while i < 200 {
+            let results = feedback(base, &mut seq, i, 10, |_| 0);
+            let now = base + Duration::from_millis((i + 10) * 10 + 30);
+            let result = bwe.incoming_packet_feedback(&results, Some(1_000_000), now);
+            assert_ne!(result.usage, BandwidthUsage::Overusing);
+            i += 10;
+        }
+        let stable = bwe.get_bitrate();
+        assert!(stable > 1_000_000, "{}", stable);
+
+        // then the queue builds up by 2ms every packet.
+        let mut changed = None;
+        while i < 400 {
+            let results = feedback(base, &mut seq, i, 10, |v| (v - 200) * 2);
+            let now = base + Duration::from_millis((i + 10) * 10 + 30);
+            let result = bwe.incoming_packet_feedback(&results, Some(1_000_000), now);
+            if result.usage == BandwidthUsage::Overusing {
+                changed = Some(result);
+                break;
+            }
+            i += 10;
+        }
+
+        let result = changed.expect("overuse not detected");
+        assert!(result.updated);
+        assert_eq!(result.previous_usage, Some(BandwidthUsage::Normal));
+        assert_eq!(result.target_bitrate, 850_000);
+    }
+
+    #[test]
+    fn lost_only_feedback_test() {
+        let now = Instant::now();
+        let mut bwe = DelayBasedBwe::new(300_000, 50_000, 5_000_000);
+        let lost = PacketResult {
+            transport_sequence_number: 1,
+            send_time: now,
+            arrival_time: None,
+            size: 1200,
+            probe_cluster_id: None,
+        };
+        let result = bwe.incoming_packet_feedback(&[lost], None, now);
+        assert!(!result.updated);
+        assert_eq!(result.target_bitrate, 300_000);
+    }
+}
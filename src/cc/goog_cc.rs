@@ -0,0 +1,171 @@
+use crate::cc::bitrate_estimator::{BitrateEstimator, SlidingWindowBitrate};
+use crate::cc::bwe_events::{BweEvent, BweEventLog};
+use crate::cc::delay_based::DelayBasedBwe;
+use crate::cc::loss_based::{LossBasedBwe, LossBasedConfig};
+use crate::cc::transport_feedback_adapter::TransportFeedback;
+use crate::cc::{CongestionController, CongestionControllerConfig};
+
+use std::time::Duration;
+
+const ACKED_BITRATE_WINDOW: Duration = Duration::from_millis(500);
+
+// 遅延ベースと損失ベースの推定を組み合わせたGCC．
+#[derive(Debug, Clone)]
+pub struct GoogCcController {
+    delay_based: DelayBasedBwe,
+    loss_based: LossBasedBwe,
+    acked_bitrate: SlidingWindowBitrate,
+    events: BweEventLog,
+}
+
+impl GoogCcController {
+    pub fn new(config: CongestionControllerConfig) -> Self {
+        GoogCcController {
+            delay_based: DelayBasedBwe::new(
+                config.start_bitrate,
+                config.min_bitrate,
+                config.max_bitrate,
+            ),
+            loss_based: LossBasedBwe::new(
+                LossBasedConfig::default(),
+                config.start_bitrate,
+                config.min_bitrate,
+                config.max_bitrate,
+            ),
+            acked_bitrate: SlidingWindowBitrate::new(ACKED_BITRATE_WINDOW),
+            events: BweEventLog::default(),
+        }
+    }
+
+    pub fn get_delay_based(&self) -> &DelayBasedBwe {
+        &self.delay_based
+    }
+
+    pub fn get_loss_based(&self) -> &LossBasedBwe {
+        &self.loss_based
+    }
+
+    pub fn get_events_mut(&mut self) -> &mut BweEventLog {
+        &mut self.events
+    }
+}
+
+impl CongestionController for GoogCcController {
+    fn on_transport_feedback(&mut self, feedback: &TransportFeedback) {
+        let received = feedback.get_received();
+        for packet in &received {
+            if let Some(t) = packet.arrival_time {
+                self.acked_bitrate.update(packet.size, t);
+            }
+        }
+        let acked_bitrate = received
+            .last()
+            .and_then(|v| v.arrival_time)
+            .and_then(|t| self.acked_bitrate.get_bitrate(t));
+
+        if let Some(rtt) = feedback.rtt {
+            self.delay_based.set_rtt(rtt);
+            self.loss_based.set_rtt(rtt);
+        }
+
+        let now = feedback.feedback_time;
+        let result =
+            self.delay_based
+                .incoming_packet_feedback(&feedback.packets, acked_bitrate, now);
+        if let Some(previous) = result.previous_usage {
+            self.events.push(BweEvent::UsageChanged {
+                time: now,
+                previous,
+                current: result.usage,
+            });
+        }
+
+        self.loss_based
+            .set_delay_based_bitrate(result.target_bitrate);
+        self.loss_based.on_packet_feedback(&feedback.packets, now);
+        let lost = feedback.get_lost_count();
+        if lost > 0 {
+            self.events.push(BweEvent::LossReport {
+                time: now,
+                lost,
+                packets: feedback.packets.len(),
+            });
+        }
+
+        let target_bitrate = self.get_target_bitrate();
+        self.events.push_estimate(target_bitrate, now);
+    }
+
+    fn set_bitrate(&mut self, bitrate: u64) {
+        self.delay_based.set_bitrate(bitrate);
+        self.loss_based.set_bitrate(bitrate);
+    }
+
+    fn set_in_alr(&mut self, in_alr: bool) {
+        self.delay_based.set_in_alr(in_alr);
+    }
+
+    fn get_target_bitrate(&self) -> u64 {
+        self.loss_based.get_target_bitrate()
+    }
+
+    fn poll_event(&mut self) -> Option<BweEvent> {
+        self.events.poll_event()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cc::{BandwidthUsage, PacketResult};
+    use std::time::Instant;
+
+    #[test]
+    fn goog_cc_events_test() {
+        let base = Instant::now();
+        let mut gcc = GoogCcController::new(CongestionControllerConfig::default());
+
+        let mut seq = 0u16;
+        let mut usage_changed = false;
+        for round in 0..40u64 {
+            let packets: Vec<PacketResult> = (0..10u64)
+                .map(|i| {
+                    let n = round * 10 + i;
+                    let send_time = base + Duration::from_millis(n * 10);
+                    // the queue grows by 2ms every packet after 2 seconds.
+                    let queue = n.saturating_sub(200) * 2;
+                    seq = seq.wrapping_add(1);
+                    PacketResult {
+                        transport_sequence_number: seq,
+                        send_time,
+                        arrival_time: if i == 0 {
+                            None
+                        } else {
+                            Some(send_time + Duration::from_millis(20 + queue))
+                        },
+                        size: 1200,
+                        probe_cluster_id: None,
+                    }
+                })
+                .collect();
+            gcc.on_transport_feedback(&TransportFeedback {
+                feedback_time: base + Duration::from_millis(round * 100 + 120),
+                packets,
+                rtt: Some(Duration::from_millis(40)),
+                prior_in_flight: 0,
+                data_in_flight: 0,
+            });
+
+            while let Some(event) = gcc.poll_event() {
+                if let BweEvent::UsageChanged { current, .. } = event {
+                    usage_changed |= current == BandwidthUsage::Overusing;
+                }
+            }
+        }
+        assert!(usage_changed);
+
+        gcc.get_events_mut().set_enabled(false);
+        gcc.set_bitrate(500_000);
+        assert_eq!(gcc.poll_event(), None);
+    }
+}
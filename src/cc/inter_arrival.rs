@@ -0,0 +1,140 @@
+use crate::time::Instant;
+
+use std::time::Duration;
+
+// https://tools.ietf.org/html/draft-ietf-rmcat-gcc-02#section-5.2
+// 送信時刻が5ms以内のpacketを一つのgroupとして扱い，group間の遅延変動を計算する．
+const GROUP_LENGTH: Duration = Duration::from_millis(5);
+const BURST_THRESHOLD: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy)]
+struct PacketGroup {
+    first_send: Instant,
+    last_send: Instant,
+    last_arrival: Instant,
+    size: usize,
+}
+
+impl PacketGroup {
+    fn new(send_time: Instant, arrival_time: Instant, size: usize) -> Self {
+        PacketGroup {
+            first_send: send_time,
+            last_send: send_time,
+            last_arrival: arrival_time,
+            size,
+        }
+    }
+}
+
+/// Send and arrival time differences between two consecutive groups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupDelta {
+    pub send_delta_ms: f64,
+    pub arrival_delta_ms: f64,
+    pub size_delta: i64,
+    /// Arrival time of the last packet of the newer group.
+    pub arrival_time: Instant,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InterArrival {
+    current: Option<PacketGroup>,
+    previous: Option<PacketGroup>,
+}
+
+impl InterArrival {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.previous = None;
+    }
+
+    /// Adds a received packet, returning the deltas when a group completes.
+    pub fn compute_deltas(
+        &mut self,
+        send_time: Instant,
+        arrival_time: Instant,
+        size: usize,
+    ) -> Option<GroupDelta> {
+        let current = match self.current {
+            Some(ref mut v) => v,
+            None => {
+                self.current = Some(PacketGroup::new(send_time, arrival_time, size));
+                return None;
+            }
+        };
+
+        // reordered packet from an older group.
+        if send_time < current.first_send {
+            return None;
+        }
+
+        if belongs_to_group(current, send_time, arrival_time) {
+            current.last_send = current.last_send.max(send_time);
+            current.last_arrival = current.last_arrival.max(arrival_time);
+            current.size += size;
+            return None;
+        }
+
+        let finished = *current;
+        let delta = self.previous.map(|previous| GroupDelta {
+            send_delta_ms: millis_between(previous.last_send, finished.last_send),
+            arrival_delta_ms: millis_between(previous.last_arrival, finished.last_arrival),
+            size_delta: finished.size as i64 - previous.size as i64,
+            arrival_time: finished.last_arrival,
+        });
+
+        self.previous = Some(finished);
+        self.current = Some(PacketGroup::new(send_time, arrival_time, size));
+
+        delta
+    }
+}
+
+fn belongs_to_group(group: &PacketGroup, send_time: Instant, arrival_time: Instant) -> bool {
+    if send_time.duration_since(group.first_send) <= GROUP_LENGTH {
+        return true;
+    }
+
+    // packets that arrive in a burst after a queue drains are merged too.
+    let arrival_delta = arrival_time.saturating_duration_since(group.last_arrival);
+    let send_delta = send_time.duration_since(group.last_send);
+    arrival_delta < BURST_THRESHOLD && arrival_delta < send_delta
+}
+
+pub(crate) fn millis_between(from: Instant, to: Instant) -> f64 {
+    if to >= from {
+        to.duration_since(from).as_secs_f64() * 1000.0
+    } else {
+        -(from.duration_since(to).as_secs_f64() * 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn group_deltas_test() {
+        let base = Instant::now();
+        let ms = |v: u64| base + Duration::from_millis(v);
+        let mut inter_arrival = InterArrival::new();
+
+        // group 1 : sent 0-2ms
+        assert_eq!(inter_arrival.compute_deltas(ms(0), ms(50), 100), None);
+        assert_eq!(inter_arrival.compute_deltas(ms(2), ms(52), 100), None);
+        // group 2 : sent 20-21ms, first group completes but has no predecessor.
+        assert_eq!(inter_arrival.compute_deltas(ms(20), ms(75), 100), None);
+        assert_eq!(inter_arrival.compute_deltas(ms(21), ms(77), 100), None);
+        // group 3 starts, group 2 completes.
+        let delta = inter_arrival.compute_deltas(ms(40), ms(100), 100).unwrap();
+
+        assert_eq!(delta.send_delta_ms, 19.0);
+        assert_eq!(delta.arrival_delta_ms, 25.0);
+        assert_eq!(delta.size_delta, 0);
+        assert_eq!(delta.arrival_time, ms(77));
+    }
+}
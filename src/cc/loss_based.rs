@@ -0,0 +1,200 @@
+// https://tools.ietf.org/html/draft-ietf-rmcat-gcc-02#section-6
+
+use crate::cc::PacketResult;
+use crate::time::Instant;
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LossBasedConfig {
+    /// Below this loss ratio the estimate increases.
+    pub low_loss: f64,
+    /// Above this loss ratio the estimate decreases.
+    pub high_loss: f64,
+    pub increase_factor: f64,
+    /// Increases happen at most this often.
+    pub increase_interval: Duration,
+    /// Loss is only evaluated over at least this many packets.
+    pub min_packets: usize,
+}
+
+impl Default for LossBasedConfig {
+    fn default() -> Self {
+        LossBasedConfig {
+            low_loss: 0.02,
+            high_loss: 0.1,
+            increase_factor: 1.05,
+            increase_interval: Duration::from_secs(1),
+            min_packets: 20,
+        }
+    }
+}
+
+// feedbackの損失率で目標bitrateを調整し，遅延ベースの推定値を上限とする．
+#[derive(Debug, Clone)]
+pub struct LossBasedBwe {
+    config: LossBasedConfig,
+    min_bitrate: u64,
+    max_bitrate: u64,
+    bitrate: u64,
+    delay_based_bitrate: Option<u64>,
+    rtt: Duration,
+    expected: usize,
+    lost: usize,
+    last_loss_ratio: f64,
+    last_increase: Option<Instant>,
+    last_decrease: Option<Instant>,
+}
+
+impl LossBasedBwe {
+    pub fn new(
+        config: LossBasedConfig,
+        start_bitrate: u64,
+        min_bitrate: u64,
+        max_bitrate: u64,
+    ) -> Self {
+        LossBasedBwe {
+            config,
+            min_bitrate,
+            max_bitrate,
+            bitrate: start_bitrate.max(min_bitrate).min(max_bitrate),
+            delay_based_bitrate: None,
+            rtt: Duration::from_millis(200),
+            expected: 0,
+            lost: 0,
+            last_loss_ratio: 0.0,
+            last_increase: None,
+            last_decrease: None,
+        }
+    }
+
+    pub fn get_loss_ratio(&self) -> f64 {
+        self.last_loss_ratio
+    }
+
+    /// Returns the combined target, never above the delay-based estimate.
+    pub fn get_target_bitrate(&self) -> u64 {
+        match self.delay_based_bitrate {
+            Some(v) => self.bitrate.min(v),
+            None => self.bitrate,
+        }
+    }
+
+    pub fn set_rtt(&mut self, rtt: Duration) {
+        self.rtt = rtt;
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: u64) {
+        self.bitrate = bitrate.max(self.min_bitrate).min(self.max_bitrate);
+    }
+
+    pub fn set_delay_based_bitrate(&mut self, bitrate: u64) {
+        self.delay_based_bitrate = Some(bitrate);
+    }
+
+    /// Accounts the packets of one transport feedback.
+    pub fn on_packet_feedback(&mut self, results: &[PacketResult], now: Instant) -> u64 {
+        self.expected += results.len();
+        self.lost += results.iter().filter(|v| v.arrival_time.is_none()).count();
+
+        if self.expected >= self.config.min_packets {
+            let ratio = self.lost as f64 / self.expected as f64;
+            self.expected = 0;
+            self.lost = 0;
+            self.on_loss_ratio(ratio, now);
+        }
+        self.get_target_bitrate()
+    }
+
+    /// Applies a loss ratio in [0, 1], e.g. an RTCP receiver report's
+    /// fraction lost divided by 256.
+    pub fn on_loss_ratio(&mut self, ratio: f64, now: Instant) -> u64 {
+        self.last_loss_ratio = ratio;
+
+        if ratio < self.config.low_loss {
+            let due = self
+                .last_increase
+                .map(|t| now.saturating_duration_since(t) >= self.config.increase_interval)
+                .unwrap_or(true);
+            if due {
+                let increased = (self.bitrate as f64 * self.config.increase_factor) as u64 + 1000;
+                self.bitrate = increased;
+                self.last_increase = Some(now);
+            }
+        } else if ratio > self.config.high_loss {
+            // one decrease per round trip, the feedback that follows still
+            // reports the loss caused by the old rate.
+            let due = self
+                .last_decrease
+                .map(|t| now.saturating_duration_since(t) >= self.rtt + Duration::from_millis(300))
+                .unwrap_or(true);
+            if due {
+                self.bitrate = (self.bitrate as f64 * (1.0 - 0.5 * ratio)) as u64;
+                self.last_decrease = Some(now);
+            }
+        }
+
+        // growing past the delay-based limit would only build a backlog.
+        if let Some(limit) = self.delay_based_bitrate {
+            self.bitrate = self.bitrate.min(limit.max(self.min_bitrate));
+        }
+        self.bitrate = self.bitrate.max(self.min_bitrate).min(self.max_bitrate);
+        self.get_target_bitrate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn results(now: Instant, count: usize, lost: usize) -> Vec<PacketResult> {
+        (0..count)
+            .map(|i| PacketResult {
+                transport_sequence_number: i as u16,
+                send_time: now,
+                arrival_time: if i < lost { None } else { Some(now) },
+                size: 1200,
+                probe_cluster_id: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loss_ratio_test() {
+        let now = Instant::now();
+        let mut bwe = LossBasedBwe::new(LossBasedConfig::default(), 1_000_000, 10_000, 10_000_000);
+
+        // low loss increases, at most once per interval.
+        assert_eq!(
+            bwe.on_packet_feedback(&results(now, 100, 1), now),
+            1_051_000
+        );
+        assert_eq!(
+            bwe.on_packet_feedback(&results(now, 100, 1), now),
+            1_051_000
+        );
+
+        // moderate loss holds.
+        let t = now + Duration::from_secs(1);
+        assert_eq!(bwe.on_packet_feedback(&results(t, 100, 5), t), 1_051_000);
+
+        // high loss decreases by half the loss ratio.
+        assert_eq!(bwe.on_packet_feedback(&results(t, 100, 20), t), 945_900);
+        assert_eq!(bwe.get_loss_ratio(), 0.2);
+    }
+
+    #[test]
+    fn delay_based_limit_test() {
+        let now = Instant::now();
+        let mut bwe = LossBasedBwe::new(LossBasedConfig::default(), 1_000_000, 10_000, 10_000_000);
+        bwe.set_delay_based_bitrate(500_000);
+        assert_eq!(bwe.get_target_bitrate(), 500_000);
+
+        bwe.on_loss_ratio(0.0, now);
+        assert_eq!(bwe.get_target_bitrate(), 500_000);
+
+        // the loss-based estimate does not run ahead while capped.
+        bwe.set_delay_based_bitrate(2_000_000);
+        assert_eq!(bwe.get_target_bitrate(), 500_000);
+    }
+}
@@ -0,0 +1,256 @@
+// https://tools.ietf.org/html/rfc8698
+
+use crate::cc::bitrate_estimator::{BitrateEstimator, SlidingWindowBitrate};
+use crate::cc::bwe_events::{BweEvent, BweEventLog};
+use crate::cc::inter_arrival::millis_between;
+use crate::cc::transport_feedback_adapter::TransportFeedback;
+use crate::cc::{CongestionController, CongestionControllerConfig};
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// RFC 8698 Figure 3 (時間はms)
+const PRIO: f64 = 1.0;
+const X_REF: f64 = 10.0;
+const KAPPA: f64 = 0.5;
+const ETA: f64 = 2.0;
+const TAU: f64 = 500.0;
+const DELTA: f64 = 100.0;
+const LOGWIN: Duration = Duration::from_millis(500);
+const QEPS: f64 = 10.0;
+const DFILTER: f64 = 120.0;
+const GAMMA_MAX: f64 = 0.5;
+const QBOUND: f64 = 50.0;
+const DLOSS: f64 = 1000.0;
+const MIN_FILTER_SAMPLES: usize = 15;
+const BASE_DELAY_WINDOW: Duration = Duration::from_secs(600);
+
+// 遅延，損失，ECNを一つの輻輳信号にまとめ，その大きさと変化量から参照rateを動かす．
+#[derive(Debug, Clone)]
+pub struct NadaController {
+    min_bitrate: f64,
+    max_bitrate: f64,
+    reference_rate: f64,
+    receive_rate: SlidingWindowBitrate,
+    base_delay: Option<(f64, Instant)>,
+    queue_delays: VecDeque<f64>,
+    // 直近LOGWINの損失と受信のpacket数．
+    losses: VecDeque<(Instant, usize, usize)>,
+    marked: usize,
+    rtt: Duration,
+    previous_signal: Option<f64>,
+    last_update: Option<Instant>,
+    last_loss: Option<Instant>,
+    events: BweEventLog,
+}
+
+impl NadaController {
+    pub fn new(config: CongestionControllerConfig) -> Self {
+        NadaController {
+            min_bitrate: config.min_bitrate as f64,
+            max_bitrate: config.max_bitrate as f64,
+            reference_rate: config.start_bitrate as f64,
+            receive_rate: SlidingWindowBitrate::new(LOGWIN),
+            base_delay: None,
+            queue_delays: VecDeque::with_capacity(MIN_FILTER_SAMPLES),
+            losses: VecDeque::new(),
+            marked: 0,
+            rtt: Duration::from_millis(100),
+            previous_signal: None,
+            last_update: None,
+            last_loss: None,
+            events: BweEventLog::default(),
+        }
+    }
+
+    /// Aggregate congestion signal `x_curr` in milliseconds.
+    pub fn get_congestion_signal(&self) -> Option<f64> {
+        self.previous_signal
+    }
+
+    fn loss_ratio(&self) -> f64 {
+        let (lost, total) = self
+            .losses
+            .iter()
+            .fold((0, 0), |(l, t), (_, lost, total)| (l + lost, t + total));
+        if total == 0 {
+            0.0
+        } else {
+            lost as f64 / total as f64
+        }
+    }
+}
+
+impl CongestionController for NadaController {
+    fn on_transport_feedback(&mut self, feedback: &TransportFeedback) {
+        let now = feedback.feedback_time;
+        if let Some(rtt) = feedback.rtt {
+            self.rtt = rtt;
+        }
+
+        let mut received = 0;
+        for packet in &feedback.get_received() {
+            let arrival = match packet.arrival_time {
+                Some(v) => v,
+                None => continue,
+            };
+            received += 1;
+            self.receive_rate.update(packet.size, arrival);
+
+            let owd = millis_between(packet.send_time, arrival);
+            let base = match self.base_delay {
+                Some((v, t))
+                    if owd >= v && now.saturating_duration_since(t) < BASE_DELAY_WINDOW =>
+                {
+                    v
+                }
+                _ => {
+                    self.base_delay = Some((owd, now));
+                    owd
+                }
+            };
+            if self.queue_delays.len() == MIN_FILTER_SAMPLES {
+                self.queue_delays.pop_front();
+            }
+            self.queue_delays.push_back(owd - base);
+        }
+
+        let lost = feedback.get_lost_count();
+        if lost > 0 {
+            self.last_loss = Some(now);
+        }
+        self.losses.push_back((now, lost, lost + received));
+        while let Some((t, _, _)) = self.losses.front() {
+            if now.saturating_duration_since(*t) <= LOGWIN {
+                break;
+            }
+            self.losses.pop_front();
+        }
+
+        // 15 samples minimum filter against delay spikes.
+        let queue_delay = match self
+            .queue_delays
+            .iter()
+            .cloned()
+            .fold(None, |acc: Option<f64>, v| {
+                Some(acc.map_or(v, |a| a.min(v)))
+            }) {
+            Some(v) => v.max(0.0),
+            None => return,
+        };
+
+        let mark_penalty = if received > 0 {
+            200.0 * (self.marked as f64 / received as f64).min(1.0)
+        } else {
+            0.0
+        };
+        self.marked = 0;
+        let signal = queue_delay + mark_penalty + DLOSS * self.loss_ratio();
+
+        let delta = self
+            .last_update
+            .map(|t| millis_between(t, now))
+            .unwrap_or(DELTA)
+            .clamp(1.0, TAU);
+        self.last_update = Some(now);
+
+        let receive_rate = self.receive_rate.get_bitrate(now).map(|v| v as f64);
+        let recent_loss = self
+            .last_loss
+            .map(|t| now.saturating_duration_since(t) < LOGWIN)
+            .unwrap_or(false);
+
+        match receive_rate {
+            // accelerated ramp-up while the queue stays empty.
+            Some(rate) if !recent_loss && queue_delay < QEPS && mark_penalty == 0.0 => {
+                let rtt = self.rtt.as_secs_f64() * 1000.0;
+                let gamma = (QBOUND / (rtt + DELTA + DFILTER)).min(GAMMA_MAX);
+                self.reference_rate = self.reference_rate.max((1.0 + gamma) * rate);
+            }
+            _ => {
+                let offset = signal - PRIO * X_REF * self.max_bitrate / self.reference_rate;
+                let diff = signal - self.previous_signal.unwrap_or(signal);
+                self.reference_rate -= KAPPA * (delta / TAU) * (offset / TAU) * self.reference_rate;
+                self.reference_rate -= KAPPA * ETA * (diff / TAU) * self.reference_rate;
+            }
+        }
+
+        self.reference_rate = self
+            .reference_rate
+            .clamp(self.min_bitrate, self.max_bitrate);
+        self.previous_signal = Some(signal);
+
+        let target_bitrate = self.get_target_bitrate();
+        self.events.push_estimate(target_bitrate, now);
+    }
+
+    fn on_congestion_marks(&mut self, marked: usize, _now: Instant) {
+        self.marked += marked;
+    }
+
+    fn set_bitrate(&mut self, bitrate: u64) {
+        self.reference_rate = (bitrate as f64).clamp(self.min_bitrate, self.max_bitrate);
+    }
+
+    fn poll_event(&mut self) -> Option<BweEvent> {
+        self.events.poll_event()
+    }
+
+    fn get_target_bitrate(&self) -> u64 {
+        self.reference_rate as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cc::PacketResult;
+
+    fn feedback(base: Instant, round: u64, queue_ms: impl Fn(u64) -> u64) -> TransportFeedback {
+        let packets = (0..10)
+            .map(|i| {
+                let n = round * 10 + i;
+                let send_time = base + Duration::from_millis(n * 10);
+                PacketResult {
+                    transport_sequence_number: n as u16,
+                    send_time,
+                    arrival_time: Some(send_time + Duration::from_millis(20 + queue_ms(n))),
+                    size: 1200,
+                    probe_cluster_id: None,
+                }
+            })
+            .collect();
+        TransportFeedback {
+            feedback_time: base + Duration::from_millis(round * 100 + 150),
+            packets,
+            rtt: Some(Duration::from_millis(50)),
+            prior_in_flight: 0,
+            data_in_flight: 0,
+        }
+    }
+
+    #[test]
+    fn nada_ramp_up_and_back_off_test() {
+        let base = Instant::now();
+        let mut nada = NadaController::new(CongestionControllerConfig {
+            start_bitrate: 500_000,
+            min_bitrate: 50_000,
+            max_bitrate: 5_000_000,
+        });
+
+        // 960kbps goes through without queuing.
+        for round in 0..10 {
+            nada.on_transport_feedback(&feedback(base, round, |_| 0));
+        }
+        let ramped = nada.get_target_bitrate();
+        assert!(ramped > 960_000, "{}", ramped);
+
+        // a standing queue of 200ms pushes the rate down.
+        for round in 10..30 {
+            nada.on_transport_feedback(&feedback(base, round, |_| 200));
+        }
+        assert!(nada.get_target_bitrate() < ramped / 2);
+        assert_eq!(nada.get_congestion_signal(), Some(200.0));
+    }
+}
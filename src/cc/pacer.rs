@@ -0,0 +1,442 @@
+use crate::rtp::packet::RtpPacket;
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const PROCESS_INTERVAL: Duration = Duration::from_millis(5);
+// budgetを貯められる上限の時間．
+const MAX_BUDGET_WINDOW: Duration = Duration::from_millis(500);
+
+/// Send order of queued packets, highest first.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum PacketPriority {
+    Audio,
+    Retransmission,
+    Video,
+    Padding,
+}
+
+const PRIORITIES: [PacketPriority; 3] = [
+    PacketPriority::Audio,
+    PacketPriority::Retransmission,
+    PacketPriority::Video,
+];
+
+/// Where the pacer releases packets to, e.g. the transport.
+pub trait PacketSender {
+    fn send_packet(&mut self, packet: RtpPacket, priority: PacketPriority, now: Instant);
+
+    /// Returns padding packets of about `bytes` in total, e.g. RTX resends
+    /// or padding-only packets.
+    fn generate_padding(&mut self, bytes: usize, now: Instant) -> Vec<RtpPacket>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PacerConfig {
+    /// Packets are sent this many times faster than the target bitrate so
+    /// encoder overshoot does not queue up.
+    pub pacing_factor: f64,
+    /// The pacing rate is raised so no packet waits longer than this.
+    pub max_queue_delay: Duration,
+    /// Audio is sent as soon as it is enqueued, ahead of every budget.
+    pub audio_fast_path: bool,
+}
+
+impl Default for PacerConfig {
+    fn default() -> Self {
+        PacerConfig {
+            pacing_factor: 2.5,
+            max_queue_delay: Duration::from_secs(2),
+            audio_fast_path: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct IntervalBudget {
+    rate: u64, // bps
+    bytes_remaining: i64,
+}
+
+impl IntervalBudget {
+    fn set_rate(&mut self, rate: u64) {
+        self.rate = rate;
+        let max = self.max_bytes();
+        self.bytes_remaining = self.bytes_remaining.min(max);
+    }
+
+    fn max_bytes(&self) -> i64 {
+        (self.rate as f64 / 8.0 * MAX_BUDGET_WINDOW.as_secs_f64()) as i64
+    }
+
+    fn increase(&mut self, elapsed: Duration) {
+        let bytes = (self.rate as f64 / 8.0 * elapsed.as_secs_f64()) as i64;
+        let max = self.max_bytes();
+        // 余ったbudgetを持ち越すと次のburstの原因になる．
+        self.bytes_remaining = if self.bytes_remaining < 0 {
+            self.bytes_remaining + bytes
+        } else {
+            bytes
+        }
+        .min(max);
+    }
+
+    fn use_budget(&mut self, bytes: usize) {
+        self.bytes_remaining = (self.bytes_remaining - bytes as i64).max(-self.max_bytes());
+    }
+
+    fn get_remaining(&self) -> usize {
+        self.bytes_remaining.max(0) as usize
+    }
+}
+
+#[derive(Debug, Clone)]
+struct QueuedPacket {
+    packet: RtpPacket,
+    enqueued_at: Instant,
+    size: usize,
+}
+
+// 送信するRTPを時間方向に均し，encoderのburstによる自己輻輳を防ぐ．
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    config: PacerConfig,
+    queues: [VecDeque<QueuedPacket>; 3],
+    queue_bytes: usize,
+    media_budget: IntervalBudget,
+    padding_budget: IntervalBudget,
+    media_rate: u64,
+    last_process: Option<Instant>,
+    paused: bool,
+    congestion_window: Option<usize>,
+    in_flight: usize,
+}
+
+impl Pacer {
+    pub fn new(config: PacerConfig) -> Self {
+        Pacer {
+            config,
+            queues: Default::default(),
+            queue_bytes: 0,
+            media_budget: IntervalBudget::default(),
+            padding_budget: IntervalBudget::default(),
+            media_rate: 0,
+            last_process: None,
+            paused: false,
+            congestion_window: None,
+            in_flight: 0,
+        }
+    }
+
+    /// `target_bitrate` comes from the estimator, the pacing rate is
+    /// `pacing_factor` times it. `padding_bitrate` fills the rest when the
+    /// queue is empty, e.g. to keep the estimate up.
+    pub fn set_pacing_rates(&mut self, target_bitrate: u64, padding_bitrate: u64) {
+        self.media_rate = (target_bitrate as f64 * self.config.pacing_factor) as u64;
+        self.media_budget.set_rate(self.media_rate);
+        self.padding_budget.set_rate(padding_bitrate);
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Limits the bytes in flight, `None` disables the limit. Media and
+    /// padding wait while the window is full, audio still goes out.
+    pub fn set_congestion_window(&mut self, window: Option<usize>) {
+        self.congestion_window = window;
+    }
+
+    /// Corrects the bytes in flight from transport feedback.
+    pub fn set_data_in_flight(&mut self, bytes: usize) {
+        self.in_flight = bytes;
+    }
+
+    pub fn get_data_in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    pub fn is_congested(&self) -> bool {
+        match self.congestion_window {
+            Some(v) => self.in_flight >= v,
+            None => false,
+        }
+    }
+
+    pub fn get_queue_size(&self) -> usize {
+        self.queue_bytes
+    }
+
+    pub fn get_queue_packets(&self) -> usize {
+        self.queues.iter().map(|v| v.len()).sum()
+    }
+
+    /// Time the oldest queued packet has waited.
+    pub fn get_oldest_queue_time(&self, now: Instant) -> Option<Duration> {
+        self.queues
+            .iter()
+            .filter_map(|v| v.front())
+            .map(|v| now.saturating_duration_since(v.enqueued_at))
+            .max()
+    }
+
+    pub fn enqueue(&mut self, packet: RtpPacket, priority: PacketPriority, now: Instant) {
+        let index = match PRIORITIES.iter().position(|v| *v == priority) {
+            Some(v) => v,
+            // padding is generated by the pacer itself.
+            None => return,
+        };
+        let size = packet.get_length();
+        self.queue_bytes += size;
+        self.queues[index].push_back(QueuedPacket {
+            packet,
+            enqueued_at: now,
+            size,
+        });
+    }
+
+    /// Returns when `process` should be called next, `None` before the
+    /// first call.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let last = self.last_process?;
+        if self.config.audio_fast_path && !self.queues[0].is_empty() {
+            Some(last)
+        } else {
+            Some(last + PROCESS_INTERVAL)
+        }
+    }
+
+    pub fn process<S: PacketSender>(&mut self, sender: &mut S, now: Instant) {
+        let elapsed = self
+            .last_process
+            .map(|t| now.saturating_duration_since(t))
+            .unwrap_or_default();
+        self.last_process = Some(now);
+
+        if self.paused {
+            return;
+        }
+
+        self.update_media_rate(now);
+        self.media_budget.increase(elapsed);
+        self.padding_budget.increase(elapsed);
+
+        if self.config.audio_fast_path {
+            while let Some(queued) = self.queues[0].pop_front() {
+                self.on_sent(queued.size);
+                sender.send_packet(queued.packet, PacketPriority::Audio, now);
+            }
+        }
+
+        while let Some(priority) = self.next_priority() {
+            if self.media_budget.get_remaining() == 0 || self.is_congested() {
+                break;
+            }
+            let index = PRIORITIES.iter().position(|v| *v == priority).unwrap_or(0);
+            if let Some(queued) = self.queues[index].pop_front() {
+                self.on_sent(queued.size);
+                sender.send_packet(queued.packet, priority, now);
+            }
+        }
+
+        if self.get_queue_packets() == 0
+            && self.media_budget.get_remaining() > 0
+            && !self.is_congested()
+        {
+            let bytes = self
+                .padding_budget
+                .get_remaining()
+                .min(self.media_budget.get_remaining());
+            if bytes > 0 {
+                for packet in sender.generate_padding(bytes, now) {
+                    let size = packet.get_length();
+                    self.media_budget.use_budget(size);
+                    self.padding_budget.use_budget(size);
+                    self.in_flight += size;
+                    sender.send_packet(packet, PacketPriority::Padding, now);
+                }
+            }
+        }
+    }
+
+    fn next_priority(&self) -> Option<PacketPriority> {
+        PRIORITIES
+            .iter()
+            .zip(self.queues.iter())
+            .find(|(_, queue)| !queue.is_empty())
+            .map(|(priority, _)| *priority)
+    }
+
+    fn on_sent(&mut self, size: usize) {
+        self.queue_bytes -= size;
+        self.in_flight += size;
+        self.media_budget.use_budget(size);
+        self.padding_budget.use_budget(size);
+    }
+
+    // 最も古いpacketがmax_queue_delay以内に出るようにpacing rateを引き上げる．
+    fn update_media_rate(&mut self, now: Instant) {
+        let mut rate = self.media_rate;
+        if let Some(oldest) = self.get_oldest_queue_time(now) {
+            let remaining = self
+                .config
+                .max_queue_delay
+                .checked_sub(oldest)
+                .unwrap_or_default()
+                .max(Duration::from_millis(1));
+            let needed = (self.queue_bytes as f64 * 8.0 / remaining.as_secs_f64()) as u64;
+            rate = rate.max(needed);
+        }
+        if rate != self.media_budget.rate {
+            self.media_budget.set_rate(rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::packet::RtpHeader;
+
+    #[derive(Default)]
+    struct Recorder {
+        sent: Vec<(PacketPriority, usize, Instant)>,
+    }
+
+    impl PacketSender for Recorder {
+        fn send_packet(&mut self, packet: RtpPacket, priority: PacketPriority, now: Instant) {
+            self.sent.push((priority, packet.get_length(), now));
+        }
+
+        fn generate_padding(&mut self, bytes: usize, _now: Instant) -> Vec<RtpPacket> {
+            let mut header = RtpHeader::new(97, 0, 0, 1);
+            header.set_padding(Some(200)).unwrap();
+            (0..bytes.div_ceil(212))
+                .map(|_| RtpPacket::new(header.clone(), vec![]))
+                .collect()
+        }
+    }
+
+    fn packet(size: usize) -> RtpPacket {
+        RtpPacket::new(RtpHeader::new(96, 0, 0, 1), vec![0; size - 12])
+    }
+
+    #[test]
+    fn pacing_rate_test() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(PacerConfig {
+            pacing_factor: 1.0,
+            ..Default::default()
+        });
+        pacer.set_pacing_rates(800_000, 0);
+        let mut sender = Recorder::default();
+
+        // a 10 packet burst at 100 bytes per ms takes about 100ms.
+        for _ in 0..10 {
+            pacer.enqueue(packet(1000), PacketPriority::Video, start);
+        }
+        pacer.process(&mut sender, start);
+        assert!(sender.sent.is_empty());
+
+        let mut t = start;
+        while pacer.get_queue_packets() > 0 {
+            t += PROCESS_INTERVAL;
+            pacer.process(&mut sender, t);
+        }
+        let elapsed = t - start;
+        assert!(
+            elapsed >= Duration::from_millis(85) && elapsed <= Duration::from_millis(100),
+            "{:?}",
+            elapsed
+        );
+        assert_eq!(sender.sent.len(), 10);
+    }
+
+    #[test]
+    fn priority_test() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(PacerConfig::default());
+        pacer.set_pacing_rates(100_000, 0);
+        let mut sender = Recorder::default();
+        pacer.process(&mut sender, start);
+
+        pacer.enqueue(packet(1000), PacketPriority::Video, start);
+        pacer.enqueue(packet(500), PacketPriority::Retransmission, start);
+        pacer.enqueue(packet(100), PacketPriority::Audio, start);
+
+        pacer.process(&mut sender, start + Duration::from_millis(40));
+        assert_eq!(sender.sent[0].0, PacketPriority::Audio);
+        assert_eq!(sender.sent[1].0, PacketPriority::Retransmission);
+    }
+
+    #[test]
+    fn padding_test() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(PacerConfig::default());
+        pacer.set_pacing_rates(1_000_000, 400_000);
+        let mut sender = Recorder::default();
+
+        pacer.process(&mut sender, start);
+        pacer.process(&mut sender, start + Duration::from_millis(10));
+        // 400kbps for 10ms = 500 bytes.
+        assert_eq!(sender.sent.len(), 3);
+        assert!(sender.sent.iter().all(|v| v.0 == PacketPriority::Padding));
+    }
+
+    #[test]
+    fn congestion_window_test() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(PacerConfig::default());
+        pacer.set_pacing_rates(10_000_000, 0);
+        pacer.set_congestion_window(Some(2500));
+        let mut sender = Recorder::default();
+        pacer.process(&mut sender, start);
+
+        for _ in 0..5 {
+            pacer.enqueue(packet(1000), PacketPriority::Video, start);
+        }
+        pacer.enqueue(packet(100), PacketPriority::Audio, start);
+
+        let t = start + Duration::from_millis(10);
+        pacer.process(&mut sender, t);
+        // audio plus video until the window fills.
+        assert_eq!(sender.sent.len(), 4);
+        assert!(pacer.is_congested());
+        pacer.process(&mut sender, t + Duration::from_millis(10));
+        assert_eq!(sender.sent.len(), 4);
+
+        // feedback acknowledged everything.
+        pacer.set_data_in_flight(0);
+        pacer.process(&mut sender, t + Duration::from_millis(20));
+        assert_eq!(sender.sent.len(), 6);
+    }
+
+    #[test]
+    fn max_queue_delay_test() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(PacerConfig {
+            pacing_factor: 1.0,
+            max_queue_delay: Duration::from_millis(100),
+            audio_fast_path: true,
+        });
+        pacer.set_pacing_rates(80_000, 0);
+        let mut sender = Recorder::default();
+
+        // 100 packets would take 10s at the pacing rate.
+        for _ in 0..100 {
+            pacer.enqueue(packet(1000), PacketPriority::Video, start);
+        }
+        pacer.process(&mut sender, start);
+        let mut t = start;
+        while pacer.get_queue_packets() > 0 {
+            t += PROCESS_INTERVAL;
+            pacer.process(&mut sender, t);
+        }
+        assert!(t - start <= Duration::from_millis(110), "{:?}", t - start);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::cc::bitrate_estimator::{BitrateEstimator, SlidingWindowBitrate};
+use crate::cc::probe_generator::ProbeCluster;
+use crate::cc::PacketResult;
+use crate::time::Instant;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+// 受信できたprobe packetがclusterの最小数のこの割合以上あれば推定する．
+const MIN_RECEIVED_PROBES_RATIO: f64 = 0.8;
+const MAX_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const CLUSTER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bitrate measured from one probe cluster.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProbeResult {
+    pub cluster_id: u32,
+    pub bitrate: u64, // bps
+}
+
+// clusterのprobe packetの送信時刻，受信時刻と大きさ．
+#[derive(Debug, Clone)]
+struct AggregatedCluster {
+    min_packets: usize,
+    packets: Vec<(Instant, Instant, usize)>,
+    last_receive: Instant,
+}
+
+/// The rate of `packets`, in the order of their times, up to `end`. The
+/// window is longer than any interval of a cluster.
+fn get_bitrate<I: Iterator<Item = (Instant, usize)>>(packets: I, end: Instant) -> Option<u64> {
+    let mut rate = SlidingWindowBitrate::new(CLUSTER_TIMEOUT);
+    for (time, size) in packets {
+        rate.update(size, time);
+    }
+    rate.get_bitrate(end)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProbeBitrateEstimator {
+    clusters: HashMap<u32, ProbeCluster>,
+    aggregated: HashMap<u32, AggregatedCluster>,
+    last_estimate: Option<ProbeResult>,
+}
+
+impl ProbeBitrateEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a cluster so its results can be evaluated.
+    pub fn add_cluster(&mut self, cluster: ProbeCluster) {
+        self.clusters.insert(cluster.id, cluster);
+    }
+
+    pub fn get_last_estimate(&self) -> Option<ProbeResult> {
+        self.last_estimate
+    }
+
+    /// Consumes the results of one feedback and returns a new estimate when
+    /// a cluster has enough received probes.
+    pub fn incoming_feedback(&mut self, results: &[PacketResult]) -> Option<ProbeResult> {
+        let mut estimate = None;
+        for result in results {
+            if let Some(v) = self.incoming_probe(result) {
+                estimate = Some(v);
+            }
+        }
+        estimate
+    }
+
+    fn incoming_probe(&mut self, result: &PacketResult) -> Option<ProbeResult> {
+        let cluster_id = result.probe_cluster_id?;
+        let arrival_time = result.arrival_time?;
+        let min_packets = self.clusters.get(&cluster_id)?.min_packets;
+
+        self.aggregated.retain(|_, v| {
+            arrival_time.saturating_duration_since(v.last_receive) < CLUSTER_TIMEOUT
+        });
+
+        let cluster = self
+            .aggregated
+            .entry(cluster_id)
+            .or_insert(AggregatedCluster {
+                min_packets,
+                packets: vec![],
+                last_receive: arrival_time,
+            });
+        cluster
+            .packets
+            .push((result.send_time, arrival_time, result.size));
+        cluster.last_receive = cluster.last_receive.max(arrival_time);
+        if (cluster.packets.len() as f64) < cluster.min_packets as f64 * MIN_RECEIVED_PROBES_RATIO {
+            return None;
+        }
+
+        let mut sent: Vec<_> = cluster.packets.iter().map(|v| (v.0, v.2)).collect();
+        let mut received: Vec<_> = cluster.packets.iter().map(|v| (v.1, v.2)).collect();
+        sent.sort_by_key(|v| v.0);
+        received.sort_by_key(|v| v.0);
+        let (first_send, last_send) = (sent[0].0, sent[sent.len() - 1].0);
+        let (first_receive, last_receive) = (received[0].0, received[received.len() - 1].0);
+        let send_interval = last_send.duration_since(first_send);
+        let receive_interval = last_receive.duration_since(first_receive);
+        if send_interval == Duration::from_secs(0)
+            || send_interval > MAX_PROBE_INTERVAL
+            || receive_interval == Duration::from_secs(0)
+            || receive_interval > MAX_PROBE_INTERVAL
+        {
+            return None;
+        }
+
+        // the last packet sent and the first packet received do not take
+        // part in their interval.
+        let send_rate = get_bitrate(sent[..sent.len() - 1].iter().copied(), last_send)?;
+        let receive_rate = get_bitrate(
+            received
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (v.0, if i == 0 { 0 } else { v.1 })),
+            last_receive,
+        )?;
+
+        let estimate = ProbeResult {
+            cluster_id,
+            bitrate: send_rate.min(receive_rate),
+        };
+        self.last_estimate = Some(estimate);
+        Some(estimate)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn probe_estimate_test() {
+        let now = Instant::now();
+        let mut estimator = ProbeBitrateEstimator::new();
+        estimator.add_cluster(ProbeCluster {
+            id: 1,
+            bitrate: 800_000,
+            duration: Duration::from_millis(15),
+            min_packets: 5,
+        });
+
+        // sent every 10ms, received every 20ms: the link is the bottleneck.
+        let results: Vec<PacketResult> = (0..5u64)
+            .map(|i| PacketResult {
+                transport_sequence_number: i as u16,
+                send_time: now + Duration::from_millis(i * 10),
+                arrival_time: Some(now + Duration::from_millis(50 + i * 20)),
+                size: 1000,
+                probe_cluster_id: Some(1),
+            })
+            .collect();
+
+        assert_eq!(estimator.incoming_feedback(&results[..3]), None);
+        assert_eq!(
+            estimator.incoming_feedback(&results[3..]),
+            Some(ProbeResult {
+                cluster_id: 1,
+                bitrate: 400_000
+            })
+        );
+    }
+}
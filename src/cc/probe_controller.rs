@@ -0,0 +1,378 @@
+use crate::cc::bwe_events::{BweEvent, BweEventLog};
+use crate::cc::probe_bitrate_estimator::ProbeResult;
+use crate::cc::probe_generator::ProbeCluster;
+use crate::time::Instant;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeControllerConfig {
+    /// Probes at session start, as multiples of the start bitrate.
+    pub first_exponential_probe_scale: f64,
+    pub second_exponential_probe_scale: f64,
+    /// Probing continues while the estimate reaches this fraction of the
+    /// last probe, with the next probe at `further_probe_scale` times it.
+    pub further_probe_threshold: f64,
+    pub further_probe_scale: f64,
+    /// An estimate below this fraction of the previous one is a large drop.
+    pub large_drop_threshold: f64,
+    /// After a large drop, probe `recovery_probe_scale` times the bitrate
+    /// before the drop once `recovery_delay` has passed, if still within
+    /// `recovery_window`.
+    pub recovery_probe_scale: f64,
+    pub recovery_delay: Duration,
+    pub recovery_window: Duration,
+    /// Probe results must reach this fraction of the target to continue.
+    pub min_success_ratio: f64,
+    pub probe_duration: Duration,
+    pub min_probe_packets: usize,
+    /// Stop waiting for a probe result after this long.
+    pub result_timeout: Duration,
+    /// While application limited, probe `alr_probe_scale` times the
+    /// estimate this often so the estimate keeps up with the link.
+    pub alr_probing_interval: Duration,
+    pub alr_probe_scale: f64,
+}
+
+impl Default for ProbeControllerConfig {
+    fn default() -> Self {
+        ProbeControllerConfig {
+            first_exponential_probe_scale: 3.0,
+            second_exponential_probe_scale: 6.0,
+            further_probe_threshold: 0.7,
+            further_probe_scale: 2.0,
+            large_drop_threshold: 0.66,
+            recovery_probe_scale: 0.85,
+            recovery_delay: Duration::from_secs(1),
+            recovery_window: Duration::from_secs(5),
+            min_success_ratio: 0.9,
+            probe_duration: Duration::from_millis(15),
+            min_probe_packets: 5,
+            result_timeout: Duration::from_secs(1),
+            alr_probing_interval: Duration::from_secs(5),
+            alr_probe_scale: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum ProbingState {
+    Init,
+    WaitingForResult,
+    Complete,
+}
+
+// probe clusterの開始時期と目標bitrateを決め，結果を評価して推定器に戻す．
+#[derive(Debug, Clone)]
+pub struct ProbeController {
+    config: ProbeControllerConfig,
+    state: ProbingState,
+    start_bitrate: u64,
+    max_bitrate: u64,
+    estimated_bitrate: u64,
+    min_bitrate_to_probe_further: Option<u64>,
+    last_probing: Option<Instant>,
+    large_drop: Option<(u64, Instant)>,
+    alr_started: Option<Instant>,
+    next_cluster_id: u32,
+    pending: HashMap<u32, ProbeCluster>,
+    events: BweEventLog,
+}
+
+impl ProbeController {
+    pub fn new(config: ProbeControllerConfig) -> Self {
+        ProbeController {
+            config,
+            state: ProbingState::Init,
+            start_bitrate: 0,
+            max_bitrate: u64::MAX,
+            estimated_bitrate: 0,
+            min_bitrate_to_probe_further: None,
+            last_probing: None,
+            large_drop: None,
+            alr_started: None,
+            next_cluster_id: 1,
+            pending: HashMap::new(),
+            events: BweEventLog::default(),
+        }
+    }
+
+    /// Returns the exponential start-up probes on the first call.
+    pub fn set_bitrates(
+        &mut self,
+        start_bitrate: u64,
+        max_bitrate: u64,
+        now: Instant,
+    ) -> Vec<ProbeCluster> {
+        self.start_bitrate = start_bitrate;
+        self.max_bitrate = max_bitrate;
+        if self.estimated_bitrate == 0 {
+            self.estimated_bitrate = start_bitrate;
+        }
+
+        if self.state != ProbingState::Init || start_bitrate == 0 {
+            return Vec::new();
+        }
+        let bitrates = [
+            (start_bitrate as f64 * self.config.first_exponential_probe_scale) as u64,
+            (start_bitrate as f64 * self.config.second_exponential_probe_scale) as u64,
+        ];
+        self.initiate_probing(&bitrates, true, now)
+    }
+
+    /// Follows a new estimate, returning further probes while the start-up
+    /// probing keeps succeeding.
+    pub fn on_estimate(&mut self, bitrate: u64, now: Instant) -> Vec<ProbeCluster> {
+        if (bitrate as f64) < self.estimated_bitrate as f64 * self.config.large_drop_threshold {
+            self.large_drop = Some((self.estimated_bitrate, now));
+        }
+        self.estimated_bitrate = bitrate;
+
+        if self.state != ProbingState::WaitingForResult {
+            return Vec::new();
+        }
+        match self.min_bitrate_to_probe_further {
+            Some(v) if bitrate > v => {
+                let next = (bitrate as f64 * self.config.further_probe_scale) as u64;
+                self.initiate_probing(&[next], true, now)
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<BweEvent> {
+        self.events.poll_event()
+    }
+
+    /// Follows the ALR detector, `None` when the sender uses its estimate.
+    pub fn set_alr_start_time(&mut self, alr_started: Option<Instant>) {
+        self.alr_started = alr_started;
+    }
+
+    /// Periodic work: result timeouts, probes after a large drop and
+    /// periodic probes in ALR.
+    pub fn process(&mut self, now: Instant) -> Vec<ProbeCluster> {
+        if self.state == ProbingState::WaitingForResult {
+            let expired = self
+                .last_probing
+                .map(|t| now.saturating_duration_since(t) > self.config.result_timeout)
+                .unwrap_or(true);
+            if expired {
+                self.state = ProbingState::Complete;
+                self.min_bitrate_to_probe_further = None;
+                self.pending.clear();
+            }
+        }
+
+        if let (Some(alr_started), ProbingState::Complete) = (self.alr_started, self.state) {
+            let since = self
+                .last_probing
+                .map(|t| t.max(alr_started))
+                .unwrap_or(alr_started);
+            if now.saturating_duration_since(since) >= self.config.alr_probing_interval {
+                let target = (self.estimated_bitrate as f64 * self.config.alr_probe_scale) as u64;
+                return self.initiate_probing(&[target], true, now);
+            }
+        }
+
+        let (before, dropped_at) = match self.large_drop {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        let elapsed = now.saturating_duration_since(dropped_at);
+        if elapsed > self.config.recovery_window {
+            self.large_drop = None;
+            return Vec::new();
+        }
+        if elapsed < self.config.recovery_delay || self.state == ProbingState::WaitingForResult {
+            return Vec::new();
+        }
+
+        self.large_drop = None;
+        let target = (before as f64 * self.config.recovery_probe_scale) as u64;
+        if target <= self.estimated_bitrate {
+            return Vec::new();
+        }
+        self.initiate_probing(&[target], false, now)
+    }
+
+    /// Evaluates a probe result and returns the bitrate to feed back into
+    /// the estimator, if the probe found more than the current estimate.
+    pub fn on_probe_result(&mut self, result: ProbeResult, now: Instant) -> Option<u64> {
+        let cluster = self.pending.remove(&result.cluster_id)?;
+
+        let success =
+            result.bitrate as f64 >= cluster.bitrate as f64 * self.config.min_success_ratio;
+        if !success {
+            // the link does not carry the probed rate, stop ramping up.
+            self.min_bitrate_to_probe_further = None;
+        }
+        self.events.push(BweEvent::ProbeResult {
+            time: now,
+            result,
+            success,
+        });
+
+        let achieved = result.bitrate.min(cluster.bitrate);
+        if achieved > self.estimated_bitrate {
+            self.estimated_bitrate = achieved;
+            Some(achieved)
+        } else {
+            None
+        }
+    }
+
+    fn initiate_probing(
+        &mut self,
+        bitrates: &[u64],
+        probe_further: bool,
+        now: Instant,
+    ) -> Vec<ProbeCluster> {
+        let mut clusters = Vec::new();
+        let mut capped = false;
+
+        for bitrate in bitrates {
+            let bitrate = if *bitrate >= self.max_bitrate {
+                capped = true;
+                self.max_bitrate
+            } else {
+                *bitrate
+            };
+
+            let cluster = ProbeCluster {
+                id: self.next_cluster_id,
+                bitrate,
+                duration: self.config.probe_duration,
+                min_packets: self.config.min_probe_packets,
+            };
+            self.next_cluster_id = self.next_cluster_id.wrapping_add(1);
+            self.pending.insert(cluster.id, cluster);
+            self.events
+                .push(BweEvent::ProbeClusterCreated { time: now, cluster });
+            clusters.push(cluster);
+
+            if capped {
+                break;
+            }
+        }
+
+        self.last_probing = Some(now);
+        self.state = ProbingState::WaitingForResult;
+        self.min_bitrate_to_probe_further = match clusters.last() {
+            Some(last) if probe_further && !capped => {
+                Some((last.bitrate as f64 * self.config.further_probe_threshold) as u64)
+            }
+            _ => None,
+        };
+
+        clusters
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cc::delay_based::DelayBasedBwe;
+
+    #[test]
+    fn exponential_probing_test() {
+        let now = Instant::now();
+        let mut controller = ProbeController::new(ProbeControllerConfig::default());
+
+        let clusters = controller.set_bitrates(300_000, 5_000_000, now);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].bitrate, 900_000);
+        assert_eq!(clusters[1].bitrate, 1_800_000);
+        assert!(controller.set_bitrates(300_000, 5_000_000, now).is_empty());
+
+        // the estimate reached 70% of the last probe, keep probing.
+        let clusters = controller.on_estimate(1_500_000, now + Duration::from_millis(100));
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].bitrate, 3_000_000);
+
+        // capped at the max bitrate, probing ends there.
+        let clusters = controller.on_estimate(2_500_000, now + Duration::from_millis(200));
+        assert_eq!(clusters[0].bitrate, 5_000_000);
+        assert!(controller
+            .on_estimate(4_000_000, now + Duration::from_millis(300))
+            .is_empty());
+    }
+
+    #[test]
+    fn probe_result_feeds_estimator_test() {
+        let now = Instant::now();
+        let mut controller = ProbeController::new(ProbeControllerConfig::default());
+        let mut bwe = DelayBasedBwe::new(300_000, 30_000, 5_000_000);
+
+        let clusters = controller.set_bitrates(300_000, 5_000_000, now);
+        let fed = controller.on_probe_result(
+            ProbeResult {
+                cluster_id: clusters[0].id,
+                bitrate: 850_000,
+            },
+            now,
+        );
+        assert_eq!(fed, Some(850_000));
+        bwe.set_bitrate(fed.unwrap());
+        assert_eq!(bwe.get_bitrate(), 850_000);
+
+        // a result below the estimate is not fed back.
+        let fed = controller.on_probe_result(
+            ProbeResult {
+                cluster_id: clusters[1].id,
+                bitrate: 600_000,
+            },
+            now,
+        );
+        assert_eq!(fed, None);
+
+        let events: Vec<BweEvent> = std::iter::from_fn(|| controller.poll_event()).collect();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0], BweEvent::ProbeClusterCreated { .. }));
+        assert!(matches!(
+            events[3],
+            BweEvent::ProbeResult { success: false, .. }
+        ));
+    }
+
+    #[test]
+    fn probe_after_large_drop_test() {
+        let now = Instant::now();
+        let mut controller = ProbeController::new(ProbeControllerConfig::default());
+        controller.set_bitrates(1_000_000, 5_000_000, now);
+        controller.process(now + Duration::from_secs(2));
+
+        let t = now + Duration::from_secs(3);
+        controller.on_estimate(2_000_000, t);
+        controller.on_estimate(1_000_000, t);
+        assert!(controller.process(t).is_empty());
+
+        let clusters = controller.process(t + Duration::from_secs(1));
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].bitrate, 1_700_000);
+        assert!(controller.process(t + Duration::from_secs(3)).is_empty());
+    }
+
+    #[test]
+    fn alr_probing_test() {
+        let now = Instant::now();
+        let mut controller = ProbeController::new(ProbeControllerConfig::default());
+        controller.set_bitrates(1_000_000, 10_000_000, now);
+        controller.process(now + Duration::from_secs(2));
+
+        let alr_started = now + Duration::from_secs(3);
+        controller.set_alr_start_time(Some(alr_started));
+        assert!(controller
+            .process(alr_started + Duration::from_secs(4))
+            .is_empty());
+
+        let clusters = controller.process(alr_started + Duration::from_secs(5));
+        assert_eq!(clusters[0].bitrate, 2_000_000);
+
+        controller.set_alr_start_time(None);
+        assert!(controller
+            .process(alr_started + Duration::from_secs(20))
+            .is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::rtp::packet::{RtpHeader, RtpPacket};
+use crate::rtp::rtx::RtxEncoder;
+use crate::rtp::transport_wide::TransportSequencer;
+use crate::rtp::Result;
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const MAX_PADDING_LENGTH: usize = 255;
+const RECENT_PACKETS: usize = 16;
+
+/// A burst of probe traffic requested by the probing controller.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProbeCluster {
+    pub id: u32,
+    pub bitrate: u64, // bps
+    pub duration: Duration,
+    pub min_packets: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ProbePacket {
+    pub packet: RtpPacket,
+    pub cluster_id: u32,
+    pub transport_sequence_number: u16,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveProbe {
+    cluster: ProbeCluster,
+    started_at: Instant,
+    bytes_sent: u64,
+    packets_sent: usize,
+}
+
+// 帯域推定のためのprobe packetを目標bitrateで生成する．
+// RTX encoderがあれば直近のmedia packetを再送として送り，無ければpadding only packetを送る．
+#[derive(Debug, Clone)]
+pub struct ProbeGenerator {
+    ssrc: u32,
+    payload_type: u8,
+    sequence_number: u16,
+    timestamp: u32,
+    transport_cc_id: u8,
+    rtx: Option<RtxEncoder>,
+    recent: VecDeque<RtpPacket>,
+    next_recent: usize,
+    active: Option<ActiveProbe>,
+}
+
+impl ProbeGenerator {
+    /// `ssrc` and `payload_type` are used for padding-only packets, usually
+    /// the RTX stream's.
+    pub fn new(ssrc: u32, payload_type: u8, transport_cc_id: u8, rtx: Option<RtxEncoder>) -> Self {
+        ProbeGenerator {
+            ssrc,
+            payload_type,
+            sequence_number: rand::random(),
+            timestamp: rand::random(),
+            transport_cc_id,
+            rtx,
+            recent: VecDeque::with_capacity(RECENT_PACKETS),
+            next_recent: 0,
+            active: None,
+        }
+    }
+
+    /// Remembers a media packet that may be resent as RTX probe payload.
+    pub fn on_media_packet(&mut self, packet: &RtpPacket) {
+        if self.recent.len() == RECENT_PACKETS {
+            self.recent.pop_front();
+        }
+        self.timestamp = packet.get_header().get_timestamp();
+        self.recent.push_back(packet.clone());
+    }
+
+    pub fn start(&mut self, cluster: ProbeCluster, now: Instant) {
+        self.active = Some(ActiveProbe {
+            cluster,
+            started_at: now,
+            bytes_sent: 0,
+            packets_sent: 0,
+        });
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    pub fn get_cluster(&self) -> Option<ProbeCluster> {
+        self.active.map(|v| v.cluster)
+    }
+
+    /// Returns when the next probe packet is due.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let active = self.active.as_ref()?;
+        if active.cluster.bitrate == 0 {
+            return Some(active.started_at);
+        }
+        let micros = active.bytes_sent * 8 * 1_000_000 / active.cluster.bitrate;
+        Some(active.started_at + Duration::from_micros(micros))
+    }
+
+    /// Emits every probe packet that is due at `now`.
+    pub fn poll(
+        &mut self,
+        sequencer: &mut TransportSequencer,
+        now: Instant,
+    ) -> Result<Vec<ProbePacket>> {
+        let mut out = Vec::new();
+
+        while let Some(active) = self.active {
+            let elapsed = now.saturating_duration_since(active.started_at);
+            if elapsed >= active.cluster.duration
+                && active.packets_sent >= active.cluster.min_packets
+            {
+                self.active = None;
+                break;
+            }
+
+            match self.poll_timeout() {
+                Some(t) if t <= now => {}
+                _ => break,
+            }
+
+            let mut packet = self.next_packet();
+            let transport_sequence_number =
+                sequencer.assign(packet.get_header_mut(), self.transport_cc_id)?;
+            let size = packet.get_length();
+
+            if let Some(ref mut v) = self.active {
+                v.bytes_sent += size as u64;
+                v.packets_sent += 1;
+            }
+
+            out.push(ProbePacket {
+                packet,
+                cluster_id: active.cluster.id,
+                transport_sequence_number,
+                size,
+            });
+        }
+
+        Ok(out)
+    }
+
+    fn next_packet(&mut self) -> RtpPacket {
+        if let Some(ref mut rtx) = self.rtx {
+            if !self.recent.is_empty() {
+                let index = self.next_recent % self.recent.len();
+                self.next_recent = self.next_recent.wrapping_add(1);
+                if let Some(v) = rtx.encapsulate(&self.recent[index]) {
+                    return v;
+                }
+            }
+        }
+
+        let mut header = RtpHeader::new(
+            self.payload_type,
+            self.sequence_number,
+            self.timestamp,
+            self.ssrc,
+        );
+        header
+            .set_padding(Some(MAX_PADDING_LENGTH as u8))
+            .expect("the padding length is not 0");
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+
+        RtpPacket::new(header, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::transport_wide::get_transport_sequence_number;
+    use std::collections::HashMap;
+
+    #[test]
+    fn padding_probe_rate_test() {
+        let now = Instant::now();
+        let mut sequencer = TransportSequencer::new(100);
+        let mut generator = ProbeGenerator::new(5000, 97, 3, None);
+
+        // about 100 bytes per millisecond.
+        generator.start(
+            ProbeCluster {
+                id: 1,
+                bitrate: 800_000,
+                duration: Duration::from_millis(15),
+                min_packets: 5,
+            },
+            now,
+        );
+
+        let first = generator.poll(&mut sequencer, now).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].transport_sequence_number, 100);
+        assert_eq!(first[0].packet.get_header().get_padding(), Some(255));
+        assert_eq!(
+            get_transport_sequence_number(first[0].packet.get_header(), 3),
+            Some(100)
+        );
+
+        let mut sent = first.len();
+        let mut t = now;
+        while generator.is_active() {
+            t += Duration::from_millis(1);
+            sent += generator.poll(&mut sequencer, t).unwrap().len();
+        }
+
+        // 15ms * 100 bytes / 275 bytes per packet
+        assert!((5..=7).contains(&sent), "sent {}", sent);
+    }
+
+    #[test]
+    fn rtx_probe_test() {
+        let now = Instant::now();
+        let mut sequencer = TransportSequencer::default();
+        let mut payload_types = HashMap::new();
+        payload_types.insert(96, 97);
+        let rtx = RtxEncoder::new(5000, payload_types, 0);
+        let mut generator = ProbeGenerator::new(5000, 97, 3, Some(rtx));
+
+        generator.on_media_packet(&RtpPacket::new(
+            RtpHeader::new(96, 10, 0, 1000),
+            vec![0; 1000],
+        ));
+        generator.start(
+            ProbeCluster {
+                id: 2,
+                bitrate: 1_000_000,
+                duration: Duration::from_millis(10),
+                min_packets: 1,
+            },
+            now,
+        );
+
+        let out = generator.poll(&mut sequencer, now).unwrap();
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].cluster_id, 2);
+        assert_eq!(out[0].packet.get_header().get_payload_type(), 97);
+        assert_eq!(out[0].packet.get_payload().len(), 1002);
+    }
+}
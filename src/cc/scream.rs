@@ -0,0 +1,247 @@
+// https://tools.ietf.org/html/rfc8298
+
+use crate::cc::bwe_events::{BweEvent, BweEventLog};
+use crate::cc::inter_arrival::millis_between;
+use crate::cc::transport_feedback_adapter::TransportFeedback;
+use crate::cc::{CongestionController, CongestionControllerConfig};
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const MSS: f64 = 1200.0;
+const MIN_CWND: f64 = 3000.0;
+const QDELAY_TARGET_MS: f64 = 100.0;
+const GAIN_UP: f64 = 1.0;
+const GAIN_DOWN: f64 = 2.0;
+const BETA_LOSS: f64 = 0.8;
+// L4S : CEの割合に比例して小さく減らす．
+const BETA_ECN: f64 = 0.9;
+const BASE_OWD_HISTORY: usize = 10;
+const BASE_OWD_INTERVAL: Duration = Duration::from_secs(60);
+const RATE_SMOOTHING: f64 = 0.25;
+
+// 片道遅延の最小値からqueue遅延を求め，目標遅延に合わせてcongestion windowを動かす．
+#[derive(Debug, Clone)]
+pub struct ScreamController {
+    min_bitrate: u64,
+    max_bitrate: u64,
+    cwnd: f64,
+    target_bitrate: f64,
+    srtt: Option<Duration>,
+    qdelay_ms: f64,
+    // 1分毎の片道遅延の最小値．
+    base_owd: VecDeque<(Instant, f64)>,
+    last_congestion_event: Option<Instant>,
+    ce_marked: usize,
+    events: BweEventLog,
+}
+
+impl ScreamController {
+    pub fn new(config: CongestionControllerConfig) -> Self {
+        ScreamController {
+            min_bitrate: config.min_bitrate,
+            max_bitrate: config.max_bitrate,
+            // one start bitrate worth of bytes over a 100ms round trip.
+            cwnd: (config.start_bitrate as f64 / 8.0 * 0.1).max(MIN_CWND),
+            target_bitrate: config.start_bitrate as f64,
+            srtt: None,
+            qdelay_ms: 0.0,
+            base_owd: VecDeque::with_capacity(BASE_OWD_HISTORY),
+            last_congestion_event: None,
+            ce_marked: 0,
+            events: BweEventLog::default(),
+        }
+    }
+
+    pub fn get_queue_delay(&self) -> Duration {
+        Duration::from_secs_f64(self.qdelay_ms.max(0.0) / 1000.0)
+    }
+
+    fn update_base_owd(&mut self, owd_ms: f64, now: Instant) {
+        match self.base_owd.back_mut() {
+            Some((t, v)) if now.saturating_duration_since(*t) < BASE_OWD_INTERVAL => {
+                *v = v.min(owd_ms);
+            }
+            _ => {
+                if self.base_owd.len() == BASE_OWD_HISTORY {
+                    self.base_owd.pop_front();
+                }
+                self.base_owd.push_back((now, owd_ms));
+            }
+        }
+    }
+
+    fn get_base_owd(&self) -> Option<f64> {
+        self.base_owd
+            .iter()
+            .map(|(_, v)| *v)
+            .fold(None, |acc: Option<f64>, v| {
+                Some(acc.map_or(v, |a| a.min(v)))
+            })
+    }
+
+    /// Congestion is reacted to at most once per round trip.
+    fn congestion_event(&mut self, beta: f64, now: Instant) {
+        let srtt = self.srtt.unwrap_or(Duration::from_millis(100));
+        let allowed = self
+            .last_congestion_event
+            .map(|t| now.saturating_duration_since(t) >= srtt)
+            .unwrap_or(true);
+        if allowed {
+            self.cwnd = (self.cwnd * beta).max(MIN_CWND);
+            self.target_bitrate *= beta;
+            self.last_congestion_event = Some(now);
+        }
+    }
+}
+
+impl CongestionController for ScreamController {
+    fn on_transport_feedback(&mut self, feedback: &TransportFeedback) {
+        let now = feedback.feedback_time;
+
+        if let Some(rtt) = feedback.rtt {
+            self.srtt = Some(match self.srtt {
+                Some(v) => (v * 7 + rtt) / 8,
+                None => rtt,
+            });
+        }
+
+        let mut acked = 0;
+        for packet in &feedback.packets {
+            if let Some(arrival) = packet.arrival_time {
+                acked += packet.size;
+                let owd = millis_between(packet.send_time, arrival);
+                self.update_base_owd(owd, now);
+                if let Some(base) = self.get_base_owd() {
+                    self.qdelay_ms = owd - base;
+                }
+            }
+        }
+
+        if feedback.get_lost_count() > 0 {
+            self.congestion_event(BETA_LOSS, now);
+        } else if self.ce_marked > 0 {
+            let fraction = (self.ce_marked as f64 / acked.max(1) as f64).min(1.0);
+            self.congestion_event(1.0 - (1.0 - BETA_ECN) * fraction * 2.0, now);
+        } else if acked > 0 {
+            let off_target = (QDELAY_TARGET_MS - self.qdelay_ms) / QDELAY_TARGET_MS;
+            let gain = if off_target > 0.0 { GAIN_UP } else { GAIN_DOWN };
+            self.cwnd += gain * off_target * acked as f64 * MSS / self.cwnd;
+            self.cwnd = self.cwnd.max(MIN_CWND);
+        }
+        self.ce_marked = 0;
+
+        // the media rate follows what the window lets through per round trip.
+        let srtt = self
+            .srtt
+            .unwrap_or(Duration::from_millis(100))
+            .as_secs_f64()
+            .max(0.01);
+        let window_rate = self.cwnd * 8.0 / srtt;
+        self.target_bitrate += RATE_SMOOTHING * (window_rate - self.target_bitrate);
+        self.target_bitrate = self
+            .target_bitrate
+            .clamp(self.min_bitrate as f64, self.max_bitrate as f64);
+
+        // the window need not grow past what the max bitrate needs.
+        let max_cwnd = (self.max_bitrate as f64 / 8.0 * srtt * 2.0).max(MIN_CWND);
+        self.cwnd = self.cwnd.min(max_cwnd);
+
+        let target_bitrate = self.get_target_bitrate();
+        self.events.push_estimate(target_bitrate, now);
+    }
+
+    fn on_congestion_marks(&mut self, marked: usize, _now: Instant) {
+        self.ce_marked += marked;
+    }
+
+    fn set_bitrate(&mut self, bitrate: u64) {
+        self.target_bitrate = bitrate.clamp(self.min_bitrate, self.max_bitrate) as f64;
+    }
+
+    fn poll_event(&mut self) -> Option<BweEvent> {
+        self.events.poll_event()
+    }
+
+    fn get_target_bitrate(&self) -> u64 {
+        self.target_bitrate as u64
+    }
+
+    fn get_congestion_window(&self) -> Option<usize> {
+        Some(self.cwnd as usize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cc::PacketResult;
+
+    fn feedback(base: Instant, round: u64, queue_ms: u64, lost: bool) -> TransportFeedback {
+        let packets = (0..10)
+            .map(|i| {
+                let send_time = base + Duration::from_millis(round * 100 + i * 10);
+                PacketResult {
+                    transport_sequence_number: (round * 10 + i) as u16,
+                    send_time,
+                    arrival_time: if lost && i == 0 {
+                        None
+                    } else {
+                        Some(send_time + Duration::from_millis(20 + queue_ms))
+                    },
+                    size: 1200,
+                    probe_cluster_id: None,
+                }
+            })
+            .collect();
+        TransportFeedback {
+            feedback_time: base + Duration::from_millis(round * 100 + 150),
+            packets,
+            rtt: Some(Duration::from_millis(50)),
+            prior_in_flight: 0,
+            data_in_flight: 0,
+        }
+    }
+
+    #[test]
+    fn scream_window_test() {
+        let base = Instant::now();
+        let mut scream = ScreamController::new(CongestionControllerConfig::default());
+
+        for round in 0..20 {
+            scream.on_transport_feedback(&feedback(base, round, 0, false));
+        }
+        let ramped = scream.get_congestion_window().unwrap();
+        assert!(ramped > 3750, "{}", ramped);
+        assert!(scream.get_target_bitrate() > 300_000);
+
+        // queue delay above the target shrinks the window.
+        scream.on_transport_feedback(&feedback(base, 20, 150, false));
+        assert!(scream.get_congestion_window().unwrap() < ramped);
+        assert_eq!(scream.get_queue_delay(), Duration::from_millis(150));
+
+        let before = scream.get_congestion_window().unwrap();
+        scream.on_transport_feedback(&feedback(base, 21, 150, true));
+        assert_eq!(
+            scream.get_congestion_window().unwrap(),
+            (before as f64 * BETA_LOSS) as usize
+        );
+    }
+
+    #[test]
+    fn selectable_backend_test() {
+        use crate::cc::{new_congestion_controller, CongestionControllerKind};
+
+        let base = Instant::now();
+        for kind in &[
+            CongestionControllerKind::GoogCc,
+            CongestionControllerKind::Scream,
+        ] {
+            let mut controller =
+                new_congestion_controller(*kind, CongestionControllerConfig::default());
+            controller.on_transport_feedback(&feedback(base, 0, 0, false));
+            assert!(controller.get_target_bitrate() >= 30_000);
+        }
+    }
+}
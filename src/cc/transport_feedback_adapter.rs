@@ -0,0 +1,270 @@
+use crate::cc::PacketResult;
+use crate::rtcp::transport_feedback::{RtcpTransportFeedbackPacket, REFERENCE_TIME_UNIT_US};
+use crate::rtp::transport_wide::SequenceUnwrapper;
+use crate::time::Instant;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const HISTORY_WINDOW: Duration = Duration::from_secs(60);
+const REFERENCE_TIME_BITS: u32 = 24;
+
+#[derive(Debug, Clone, Copy)]
+struct SentPacket {
+    transport_sequence_number: u16,
+    send_time: Instant,
+    size: usize,
+    probe_cluster_id: Option<u32>,
+    reported: bool,
+    received: bool,
+}
+
+/// Packet results of one transport-cc feedback, in send order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportFeedback {
+    pub feedback_time: Instant,
+    pub packets: Vec<PacketResult>,
+    /// Shortest time from sending a reported packet to this feedback, an
+    /// upper bound of the round trip time.
+    pub rtt: Option<Duration>,
+    pub prior_in_flight: usize,
+    pub data_in_flight: usize,
+}
+
+impl TransportFeedback {
+    /// Received packets ordered by arrival time.
+    pub fn get_received(&self) -> Vec<PacketResult> {
+        let mut received: Vec<PacketResult> = self
+            .packets
+            .iter()
+            .filter(|v| v.arrival_time.is_some())
+            .cloned()
+            .collect();
+        received.sort_by_key(|v| v.arrival_time);
+        received
+    }
+
+    pub fn get_lost_count(&self) -> usize {
+        self.packets
+            .iter()
+            .filter(|v| v.arrival_time.is_none())
+            .count()
+    }
+}
+
+// 送信したpacketの記録とTWCC feedbackを突き合わせ，推定器に渡すPacketResultを作る．
+#[derive(Debug, Clone, Default)]
+pub struct TransportFeedbackAdapter {
+    unwrapper: SequenceUnwrapper,
+    history: BTreeMap<i64, SentPacket>,
+    in_flight: usize,
+    // 受信側の時計を送信側のInstantに対応付ける基準．
+    remote_base: Option<(Instant, i64)>,
+    last_reference_time: Option<(u32, i64)>,
+}
+
+impl TransportFeedbackAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes sent and not yet reported by any feedback.
+    pub fn get_data_in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    pub fn on_packet_sent(
+        &mut self,
+        transport_sequence_number: u16,
+        size: usize,
+        probe_cluster_id: Option<u32>,
+        send_time: Instant,
+    ) {
+        let sequence = self.unwrapper.unwrap(transport_sequence_number);
+        self.history.insert(
+            sequence,
+            SentPacket {
+                transport_sequence_number,
+                send_time,
+                size,
+                probe_cluster_id,
+                reported: false,
+                received: false,
+            },
+        );
+        self.in_flight += size;
+
+        while let Some((&oldest, packet)) = self.history.iter().next() {
+            if send_time.saturating_duration_since(packet.send_time) < HISTORY_WINDOW {
+                break;
+            }
+            if !packet.reported {
+                self.in_flight -= packet.size;
+            }
+            self.history.remove(&oldest);
+        }
+    }
+
+    /// Matches `feedback` against the sent packets. Returns `None` if it
+    /// reports nothing that is still in the history.
+    pub fn process_feedback(
+        &mut self,
+        feedback: &RtcpTransportFeedbackPacket,
+        now: Instant,
+    ) -> Option<TransportFeedback> {
+        let reference_offset = self.unwrap_reference_time(feedback.get_reference_time())
+            - i64::from(feedback.get_reference_time()) * REFERENCE_TIME_UNIT_US;
+
+        let prior_in_flight = self.in_flight;
+        let mut packets = Vec::new();
+        let mut rtt: Option<Duration> = None;
+
+        for (sequence, arrival) in feedback.get_arrivals() {
+            let sequence = self.unwrapper.get_unwrapped(sequence);
+            let arrival_time = arrival.map(|v| self.get_local_time(v + reference_offset, now));
+
+            let packet = match self.history.get_mut(&sequence) {
+                Some(v) => v,
+                None => continue,
+            };
+            if packet.received || (packet.reported && arrival_time.is_none()) {
+                // already reported by an earlier feedback.
+                continue;
+            }
+            if !packet.reported {
+                self.in_flight -= packet.size;
+            }
+            packet.reported = true;
+            packet.received = arrival_time.is_some();
+
+            if arrival_time.is_some() {
+                let sample = now.saturating_duration_since(packet.send_time);
+                rtt = Some(rtt.map(|v| v.min(sample)).unwrap_or(sample));
+            }
+
+            packets.push(PacketResult {
+                transport_sequence_number: packet.transport_sequence_number,
+                send_time: packet.send_time,
+                arrival_time,
+                size: packet.size,
+                probe_cluster_id: packet.probe_cluster_id,
+            });
+        }
+
+        if packets.is_empty() {
+            return None;
+        }
+
+        Some(TransportFeedback {
+            feedback_time: now,
+            packets,
+            rtt,
+            prior_in_flight,
+            data_in_flight: self.in_flight,
+        })
+    }
+
+    /// Unwraps the 24bit reference time, returned in microseconds.
+    fn unwrap_reference_time(&mut self, reference_time: i32) -> i64 {
+        let modulo = 1i64 << REFERENCE_TIME_BITS;
+        let raw = (reference_time as u32) & (modulo as u32 - 1);
+        let unwrapped = match self.last_reference_time {
+            Some((last_raw, last)) => {
+                let mut delta = (i64::from(raw) - i64::from(last_raw)).rem_euclid(modulo);
+                if delta >= modulo / 2 {
+                    delta -= modulo;
+                }
+                last + delta
+            }
+            None => i64::from(reference_time),
+        };
+        self.last_reference_time = Some((raw, unwrapped));
+        unwrapped * REFERENCE_TIME_UNIT_US
+    }
+
+    fn get_local_time(&mut self, remote_us: i64, now: Instant) -> Instant {
+        let (base, base_us) = *self.remote_base.get_or_insert((now, remote_us));
+        let offset = remote_us - base_us;
+        if offset >= 0 {
+            base + Duration::from_micros(offset as u64)
+        } else {
+            base.checked_sub(Duration::from_micros((-offset) as u64))
+                .unwrap_or(base)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cc::delay_based::DelayBasedBwe;
+    use crate::rtcp::transport_feedback::PacketStatus;
+
+    #[test]
+    fn process_feedback_test() {
+        let now = Instant::now();
+        let mut adapter = TransportFeedbackAdapter::new();
+
+        for i in 0..4u16 {
+            let seq = 65534u16.wrapping_add(i);
+            let probe = if i == 3 { Some(7) } else { None };
+            adapter.on_packet_sent(
+                seq,
+                1000,
+                probe,
+                now + Duration::from_millis(u64::from(i) * 5),
+            );
+        }
+        assert_eq!(adapter.get_data_in_flight(), 4000);
+
+        // 65535 is lost, the others arrive 5ms apart (20 * 250us).
+        let feedback = RtcpTransportFeedbackPacket::new(
+            1,
+            2,
+            65534,
+            10,
+            0,
+            vec![
+                PacketStatus::Received(0),
+                PacketStatus::NotReceived,
+                PacketStatus::Received(40),
+                PacketStatus::Received(20),
+            ],
+        );
+        let t = now + Duration::from_millis(60);
+        let result = adapter.process_feedback(&feedback, t).unwrap();
+
+        assert_eq!(result.packets.len(), 4);
+        assert_eq!(result.get_lost_count(), 1);
+        assert_eq!(result.prior_in_flight, 4000);
+        assert_eq!(result.data_in_flight, 0);
+        assert_eq!(result.rtt, Some(Duration::from_millis(45)));
+        assert_eq!(result.packets[3].transport_sequence_number, 1);
+        assert_eq!(result.packets[3].probe_cluster_id, Some(7));
+
+        let received = result.get_received();
+        let first = received[0].arrival_time.unwrap();
+        assert_eq!(
+            received[2].arrival_time.unwrap() - first,
+            Duration::from_millis(15)
+        );
+
+        // a repeated feedback reports nothing new.
+        assert!(adapter.process_feedback(&feedback, t).is_none());
+
+        let mut bwe = DelayBasedBwe::new(300_000, 30_000, 2_000_000);
+        let estimate = bwe.incoming_packet_feedback(&result.packets, None, t);
+        assert_eq!(estimate.target_bitrate, 300_000);
+    }
+
+    #[test]
+    fn reference_time_wrap_test() {
+        let mut adapter = TransportFeedbackAdapter::new();
+        assert_eq!(adapter.unwrap_reference_time(0x7f_ffff), 0x7f_ffff * 64_000);
+        // 0x800000 is sign extended to a negative value by the parser.
+        assert_eq!(
+            adapter.unwrap_reference_time(-0x80_0000),
+            0x80_0000 * 64_000
+        );
+    }
+}
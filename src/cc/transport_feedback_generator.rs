@@ -0,0 +1,250 @@
+use crate::rtcp::packet::{RtcpPacket, RtcpPacketType};
+use crate::rtcp::transport_feedback::{
+    PacketStatus, RtcpTransportFeedbackPacket, DELTA_UNIT_US, REFERENCE_TIME_UNIT_US,
+};
+use crate::rtp::packet::RtpHeader;
+use crate::rtp::transport_wide::{get_transport_sequence_number, SequenceUnwrapper};
+use crate::time::Instant;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+const TICKS_PER_REFERENCE: i64 = REFERENCE_TIME_UNIT_US / DELTA_UNIT_US;
+const REFERENCE_TIME_MASK: i64 = 0x00ff_ffff;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TransportFeedbackGeneratorConfig {
+    /// How often feedback is sent while packets are being received.
+    pub interval: Duration,
+    /// Status count limit of one feedback packet.
+    pub max_packets_per_feedback: usize,
+}
+
+impl Default for TransportFeedbackGeneratorConfig {
+    fn default() -> Self {
+        TransportFeedbackGeneratorConfig {
+            interval: Duration::from_millis(100),
+            max_packets_per_feedback: 1000,
+        }
+    }
+}
+
+// 受信側で全SSRCのtransport-wide sequence numberの到着時刻を記録し，定期的にfeedbackを作る．
+#[derive(Debug, Clone)]
+pub struct TransportFeedbackGenerator {
+    config: TransportFeedbackGeneratorConfig,
+    sender_ssrc: u32,
+    media_ssrc: u32,
+    unwrapper: SequenceUnwrapper,
+    arrivals: BTreeMap<i64, Instant>,
+    next_sequence: Option<i64>,
+    epoch: Option<Instant>,
+    feedback_count: u8,
+    last_feedback: Option<Instant>,
+}
+
+impl TransportFeedbackGenerator {
+    pub fn new(config: TransportFeedbackGeneratorConfig, sender_ssrc: u32) -> Self {
+        TransportFeedbackGenerator {
+            config,
+            sender_ssrc,
+            media_ssrc: 0,
+            unwrapper: SequenceUnwrapper::new(),
+            arrivals: BTreeMap::new(),
+            next_sequence: None,
+            epoch: None,
+            feedback_count: 0,
+            last_feedback: None,
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.config.interval = interval;
+    }
+
+    /// Records the arrival of a packet carrying the transport-cc extension `id`.
+    pub fn on_packet(&mut self, header: &RtpHeader, id: u8, now: Instant) {
+        if let Some(sequence) = get_transport_sequence_number(header, id) {
+            self.on_arrival(sequence, header.get_ssrc(), now);
+        }
+    }
+
+    pub fn on_arrival(&mut self, transport_sequence_number: u16, media_ssrc: u32, now: Instant) {
+        let sequence = self.unwrapper.unwrap(transport_sequence_number);
+
+        // arrived after it was already reported as lost.
+        if let Some(next) = self.next_sequence {
+            if sequence < next {
+                return;
+            }
+        }
+
+        self.epoch.get_or_insert(now);
+        self.media_ssrc = media_ssrc;
+        self.arrivals.entry(sequence).or_insert(now);
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        if self.arrivals.is_empty() {
+            return None;
+        }
+        Some(match self.last_feedback {
+            Some(t) => t + self.config.interval,
+            None => *self.arrivals.values().next()?,
+        })
+    }
+
+    /// Builds the feedback for every packet received since the last call,
+    /// if the send interval has elapsed.
+    pub fn poll_feedback(&mut self, now: Instant) -> Vec<RtcpPacket> {
+        match self.poll_timeout() {
+            Some(t) if t <= now => {}
+            _ => return Vec::new(),
+        }
+        self.last_feedback = Some(now);
+
+        let mut out = Vec::new();
+        while let Some(feedback) = self.build_feedback() {
+            if let Ok(v) = feedback.to_rtpfb() {
+                out.push(RtcpPacket::new(RtcpPacketType::RTPFeedback(v)));
+            }
+        }
+        out
+    }
+
+    fn build_feedback(&mut self) -> Option<RtcpTransportFeedbackPacket> {
+        let epoch = self.epoch?;
+        let (&first, &first_arrival) = self.arrivals.iter().next()?;
+        let base = match self.next_sequence {
+            Some(v) => v.min(first),
+            None => first,
+        };
+
+        let first_ticks = ticks_since(epoch, first_arrival);
+        let reference_time = first_ticks.div_euclid(TICKS_PER_REFERENCE);
+        let mut previous_ticks = reference_time * TICKS_PER_REFERENCE;
+
+        let mut statuses = Vec::new();
+        let mut sequence = base;
+        let last = *self.arrivals.keys().next_back()?;
+
+        while sequence <= last && statuses.len() < self.config.max_packets_per_feedback {
+            match self.arrivals.get(&sequence) {
+                Some(arrival) => {
+                    let ticks = ticks_since(epoch, *arrival);
+                    let delta = ticks - previous_ticks;
+                    // deltas that do not fit go to the next feedback.
+                    if delta < i64::from(i16::MIN) || delta > i64::from(i16::MAX) {
+                        if statuses.is_empty() {
+                            // unreachable with a reference time from this packet.
+                            self.arrivals.remove(&sequence);
+                            sequence += 1;
+                            continue;
+                        }
+                        break;
+                    }
+                    statuses.push(PacketStatus::Received(delta as i32));
+                    previous_ticks = ticks;
+                }
+                None => statuses.push(PacketStatus::NotReceived),
+            }
+            sequence += 1;
+        }
+
+        // drop what this feedback covers.
+        let remaining = self.arrivals.split_off(&sequence);
+        self.arrivals = remaining;
+        self.next_sequence = Some(sequence);
+
+        if statuses.is_empty() {
+            return None;
+        }
+
+        let feedback = RtcpTransportFeedbackPacket::new(
+            self.sender_ssrc,
+            self.media_ssrc,
+            base as u16,
+            // 24bit, wraps after about 12 days.
+            ((reference_time & REFERENCE_TIME_MASK) << 8) as i32 >> 8,
+            self.feedback_count,
+            statuses,
+        );
+        self.feedback_count = self.feedback_count.wrapping_add(1);
+        Some(feedback)
+    }
+}
+
+fn ticks_since(epoch: Instant, t: Instant) -> i64 {
+    (t.saturating_duration_since(epoch).as_micros() as i64) / DELTA_UNIT_US
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtcp::transport_feedback::RtcpTransportFeedbackPacket;
+
+    fn parse(packet: &RtcpPacket) -> RtcpTransportFeedbackPacket {
+        match packet.get_packet() {
+            RtcpPacketType::RTPFeedback(v) => RtcpTransportFeedbackPacket::from_rtpfb(v).unwrap(),
+            _ => panic!("not a rtpfb packet"),
+        }
+    }
+
+    #[test]
+    fn build_feedback_test() {
+        let now = Instant::now();
+        let mut generator =
+            TransportFeedbackGenerator::new(TransportFeedbackGeneratorConfig::default(), 9);
+
+        // across ssrcs and the sequence number wrap, 65535 is lost.
+        generator.on_arrival(65533, 1, now + Duration::from_millis(70));
+        generator.on_arrival(65534, 2, now + Duration::from_millis(75));
+        generator.on_arrival(0, 1, now + Duration::from_millis(90));
+        assert!(generator.poll_feedback(now).is_empty());
+
+        let t = now + Duration::from_millis(100);
+        let out = generator.poll_feedback(t);
+        assert_eq!(out.len(), 1);
+
+        let feedback = parse(&out[0]);
+        assert_eq!(feedback.get_ssrc(), 9);
+        assert_eq!(feedback.get_media_ssrc(), 1);
+        assert_eq!(feedback.get_base_sequence_number(), 65533);
+        assert_eq!(feedback.get_feedback_count(), 0);
+        assert_eq!(
+            feedback.get_statuses(),
+            &[
+                PacketStatus::Received(0),
+                PacketStatus::Received(20),
+                PacketStatus::NotReceived,
+                PacketStatus::Received(60),
+            ]
+        );
+
+        // nothing new until the interval passes.
+        let t1 = t + Duration::from_millis(50);
+        generator.on_arrival(1, 1, t1);
+        assert!(generator.poll_feedback(t1).is_empty());
+        let out = generator.poll_feedback(t + Duration::from_millis(100));
+        let feedback = parse(&out[0]);
+        assert_eq!(feedback.get_base_sequence_number(), 1);
+        assert_eq!(feedback.get_feedback_count(), 1);
+        // 80ms from the first arrival is one 64ms reference unit plus 16ms.
+        assert_eq!(feedback.get_reference_time(), 1);
+        assert_eq!(feedback.get_statuses(), &[PacketStatus::Received(64)]);
+    }
+
+    #[test]
+    fn split_large_delta_test() {
+        let now = Instant::now();
+        let mut generator =
+            TransportFeedbackGenerator::new(TransportFeedbackGeneratorConfig::default(), 9);
+
+        generator.on_arrival(1, 1, now);
+        generator.on_arrival(2, 1, now + Duration::from_secs(10));
+        let out = generator.poll_feedback(now + Duration::from_secs(10));
+        assert_eq!(out.len(), 2);
+        assert_eq!(parse(&out[1]).get_base_sequence_number(), 2);
+        assert_eq!(parse(&out[1]).get_feedback_count(), 1);
+    }
+}
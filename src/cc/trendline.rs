@@ -0,0 +1,245 @@
+// https://tools.ietf.org/html/draft-ietf-rmcat-gcc-02#section-5.3
+// 遅延変動の累積値を最小二乗法で直線近似し，その傾きから過剰使用を検出する．
+
+use crate::cc::inter_arrival::millis_between;
+use crate::cc::BandwidthUsage;
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+
+const DEFAULT_WINDOW_SIZE: usize = 20;
+const DEFAULT_SMOOTHING: f64 = 0.9;
+const DEFAULT_THRESHOLD_GAIN: f64 = 4.0;
+
+const MAX_DELTAS: usize = 60;
+const OVERUSING_TIME_THRESHOLD_MS: f64 = 10.0;
+const MAX_ADAPT_OFFSET_MS: f64 = 15.0;
+const MAX_TIME_DELTA_MS: f64 = 100.0;
+const K_UP: f64 = 0.0087;
+const K_DOWN: f64 = 0.039;
+const MIN_THRESHOLD: f64 = 6.0;
+const MAX_THRESHOLD: f64 = 600.0;
+const INITIAL_THRESHOLD: f64 = 12.5;
+
+#[derive(Debug, Clone)]
+pub struct TrendlineEstimator {
+    window_size: usize,
+    smoothing: f64,
+    threshold_gain: f64,
+
+    num_deltas: usize,
+    first_arrival: Option<Instant>,
+    accumulated_delay: f64,
+    smoothed_delay: f64,
+    history: VecDeque<(f64, f64)>, // (arrival ms, smoothed delay ms)
+    trend: f64,
+    prev_trend: f64,
+
+    threshold: f64,
+    last_threshold_update: Option<Instant>,
+    time_over_using: Option<f64>,
+    overuse_counter: usize,
+    state: BandwidthUsage,
+}
+
+impl Default for TrendlineEstimator {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_WINDOW_SIZE,
+            DEFAULT_SMOOTHING,
+            DEFAULT_THRESHOLD_GAIN,
+        )
+    }
+}
+
+impl TrendlineEstimator {
+    pub fn new(window_size: usize, smoothing: f64, threshold_gain: f64) -> Self {
+        TrendlineEstimator {
+            window_size,
+            smoothing,
+            threshold_gain,
+            num_deltas: 0,
+            first_arrival: None,
+            accumulated_delay: 0.0,
+            smoothed_delay: 0.0,
+            history: VecDeque::with_capacity(window_size),
+            trend: 0.0,
+            prev_trend: 0.0,
+            threshold: INITIAL_THRESHOLD,
+            last_threshold_update: None,
+            time_over_using: None,
+            overuse_counter: 0,
+            state: BandwidthUsage::Normal,
+        }
+    }
+
+    pub fn get_state(&self) -> BandwidthUsage {
+        self.state
+    }
+
+    pub fn get_trend(&self) -> f64 {
+        self.trend
+    }
+
+    pub fn get_threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    pub fn update(
+        &mut self,
+        arrival_delta_ms: f64,
+        send_delta_ms: f64,
+        arrival_time: Instant,
+    ) -> BandwidthUsage {
+        let delta_ms = arrival_delta_ms - send_delta_ms;
+        self.num_deltas = (self.num_deltas + 1).min(1000);
+
+        let first_arrival = *self.first_arrival.get_or_insert(arrival_time);
+
+        self.accumulated_delay += delta_ms;
+        self.smoothed_delay =
+            self.smoothing * self.smoothed_delay + (1.0 - self.smoothing) * self.accumulated_delay;
+
+        self.history.push_back((
+            millis_between(first_arrival, arrival_time),
+            self.smoothed_delay,
+        ));
+        if self.history.len() > self.window_size {
+            self.history.pop_front();
+        }
+        if self.history.len() == self.window_size {
+            if let Some(slope) = linear_fit_slope(&self.history) {
+                self.trend = slope;
+            }
+        }
+
+        self.detect(send_delta_ms, arrival_time);
+        self.state
+    }
+
+    fn detect(&mut self, send_delta_ms: f64, now: Instant) {
+        if self.num_deltas < 2 {
+            self.state = BandwidthUsage::Normal;
+            return;
+        }
+
+        let modified_trend =
+            self.num_deltas.min(MAX_DELTAS) as f64 * self.trend * self.threshold_gain;
+
+        if modified_trend > self.threshold {
+            let time_over_using = match self.time_over_using {
+                Some(v) => v + send_delta_ms,
+                // first overuse sample, assume it started half way.
+                None => send_delta_ms / 2.0,
+            };
+            self.time_over_using = Some(time_over_using);
+            self.overuse_counter += 1;
+
+            if time_over_using > OVERUSING_TIME_THRESHOLD_MS
+                && self.overuse_counter > 1
+                && self.trend >= self.prev_trend
+            {
+                self.time_over_using = None;
+                self.overuse_counter = 0;
+                self.state = BandwidthUsage::Overusing;
+            }
+        } else if modified_trend < -self.threshold {
+            self.time_over_using = None;
+            self.overuse_counter = 0;
+            self.state = BandwidthUsage::Underusing;
+        } else {
+            self.time_over_using = None;
+            self.overuse_counter = 0;
+            self.state = BandwidthUsage::Normal;
+        }
+
+        self.prev_trend = self.trend;
+        self.update_threshold(modified_trend, now);
+    }
+
+    // https://tools.ietf.org/html/draft-ietf-rmcat-gcc-02#section-5.4
+    fn update_threshold(&mut self, modified_trend: f64, now: Instant) {
+        let last = *self.last_threshold_update.get_or_insert(now);
+
+        let abs = modified_trend.abs();
+        if abs > self.threshold + MAX_ADAPT_OFFSET_MS {
+            // spikes (e.g. a route change) should not move the threshold.
+            self.last_threshold_update = Some(now);
+            return;
+        }
+
+        let k = if abs < self.threshold { K_DOWN } else { K_UP };
+        let time_delta = millis_between(last, now).min(MAX_TIME_DELTA_MS);
+        self.threshold += k * (abs - self.threshold) * time_delta;
+        self.threshold = self.threshold.clamp(MIN_THRESHOLD, MAX_THRESHOLD);
+        self.last_threshold_update = Some(now);
+    }
+}
+
+fn linear_fit_slope(points: &VecDeque<(f64, f64)>) -> Option<f64> {
+    let n = points.len() as f64;
+    let (sum_x, sum_y) = points
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let x_avg = sum_x / n;
+    let y_avg = sum_y / n;
+
+    let (numerator, denominator) = points.iter().fold((0.0, 0.0), |(num, den), (x, y)| {
+        (
+            num + (x - x_avg) * (y - y_avg),
+            den + (x - x_avg) * (x - x_avg),
+        )
+    });
+
+    if denominator == 0.0 {
+        return None;
+    }
+    Some(numerator / denominator)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn stable_delay_test() {
+        let base = Instant::now();
+        let mut trendline = TrendlineEstimator::default();
+
+        for i in 1..100 {
+            let t = base + Duration::from_millis(i * 20);
+            assert_eq!(trendline.update(20.0, 20.0, t), BandwidthUsage::Normal);
+        }
+    }
+
+    #[test]
+    fn increasing_delay_test() {
+        let base = Instant::now();
+        let mut trendline = TrendlineEstimator::default();
+
+        let mut overusing = false;
+        for i in 1..100 {
+            // each group arrives 5ms later than it was sent apart.
+            let t = base + Duration::from_millis(i * 25);
+            if trendline.update(25.0, 20.0, t) == BandwidthUsage::Overusing {
+                overusing = true;
+                break;
+            }
+        }
+        assert!(overusing);
+    }
+
+    #[test]
+    fn decreasing_delay_test() {
+        let base = Instant::now();
+        let mut trendline = TrendlineEstimator::default();
+
+        let mut state = BandwidthUsage::Normal;
+        for i in 1..40 {
+            let t = base + Duration::from_millis(i * 15);
+            state = trendline.update(15.0, 20.0, t);
+        }
+        assert_eq!(state, BandwidthUsage::Underusing);
+    }
+}
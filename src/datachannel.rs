@@ -0,0 +1,44 @@
+use crate::sctp::SctpError;
+use failure::Fail;
+
+pub mod channel;
+pub mod dcep;
+pub mod transport;
+
+pub type Result<T> = std::result::Result<T, DataChannelError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum DataChannelError {
+    #[fail(display = "SCTP failed: {:?}", error)]
+    SctpError { error: SctpError },
+
+    #[fail(display = "DCEP message is broken.")]
+    InvalidMessage,
+
+    #[fail(display = "DCEP message type {} is not known.", message_type)]
+    UnknownMessageType { message_type: u8 },
+
+    #[fail(display = "DCEP channel type {} is not known.", channel_type)]
+    UnknownChannelType { channel_type: u8 },
+
+    #[fail(display = "Data channel parameters are invalid.")]
+    InvalidParameters,
+
+    #[fail(display = "Data channel is not open.")]
+    InvalidState,
+
+    #[fail(display = "Data channel has more than its max buffered amount queued.")]
+    BufferFull,
+
+    #[fail(display = "No stream is left for a data channel.")]
+    NoStreamAvailable,
+
+    #[fail(display = "Stream {} is used by another data channel.", id)]
+    StreamIdInUse { id: u16 },
+}
+
+impl From<SctpError> for DataChannelError {
+    fn from(error: SctpError) -> Self {
+        DataChannelError::SctpError { error }
+    }
+}
@@ -0,0 +1,276 @@
+// https://www.w3.org/TR/webrtc/#rtcdatachannel
+// https://tools.ietf.org/html/rfc8831#section-6.6
+
+/*
+    readyState   connecting -> open -> closing -> closed
+
+    the W3C callbacks are events polled from the SctpTransport:
+      onopen               Open
+      onmessage            Message
+      onbufferedamountlow  BufferedAmountLow
+      onerror              Error
+      onclosing            Closing, when the peer closed the channel
+      onclose              Close
+      ondatachannel        DataChannel, a channel the peer opened
+
+    backpressure
+      messages wait in the association until cwnd and the peer rwnd let
+      them out, counted in the buffered amount. a channel is ready to send
+      while that is below its max buffered amount, so one message more is
+      taken and the memory stays below max + the largest message.
+      past that, send fails with BufferFull, and poll_ready_to_send wakes
+      the task once the association has drained the channel again.
+
+    PPID  51 string, 53 binary, 56 and 57 an empty one sent as one byte.
+*/
+
+use crate::datachannel::dcep::{Reliability, PRIORITY_NORMAL};
+use crate::datachannel::transport::SctpTransport;
+use crate::datachannel::{DataChannelError, Result};
+use crate::time::Instant;
+
+use std::task::{Context, Poll, Waker};
+
+pub const DEFAULT_MAX_BUFFERED_AMOUNT: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum DataChannelState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+// RTCDataChannelInit．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DataChannelInit {
+    pub ordered: bool,
+    /// In milliseconds.
+    pub max_packet_life_time: Option<u16>,
+    pub max_retransmits: Option<u16>,
+    pub protocol: String,
+    /// Negotiated out of band, on the stream `id`, without DCEP.
+    pub negotiated: bool,
+    pub id: Option<u16>,
+    pub priority: u16,
+}
+
+impl Default for DataChannelInit {
+    fn default() -> Self {
+        DataChannelInit {
+            ordered: true,
+            max_packet_life_time: None,
+            max_retransmits: None,
+            protocol: String::new(),
+            negotiated: false,
+            id: None,
+            priority: PRIORITY_NORMAL,
+        }
+    }
+}
+
+impl DataChannelInit {
+    pub(crate) fn get_reliability(&self) -> Result<Reliability> {
+        match (self.max_retransmits, self.max_packet_life_time) {
+            (None, None) => Ok(Reliability::Reliable),
+            (Some(v), None) => Ok(Reliability::MaxRetransmits(u32::from(v))),
+            (None, Some(v)) => Ok(Reliability::MaxPacketLifeTime(u32::from(v))),
+            (Some(_), Some(_)) => Err(DataChannelError::InvalidParameters),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum DataChannelMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DataChannelEvent {
+    DataChannel {
+        id: u16,
+    },
+    Open {
+        id: u16,
+    },
+    Message {
+        id: u16,
+        message: DataChannelMessage,
+    },
+    BufferedAmountLow {
+        id: u16,
+    },
+    Error {
+        id: u16,
+        error: DataChannelError,
+    },
+    Closing {
+        id: u16,
+    },
+    Close {
+        id: u16,
+    },
+}
+
+// SctpTransportが持つchannelの状態．
+#[derive(Debug)]
+pub(crate) struct Channel {
+    pub label: String,
+    pub protocol: String,
+    pub ordered: bool,
+    pub reliability: Reliability,
+    pub priority: u16,
+    pub negotiated: bool,
+    pub state: DataChannelState,
+    /// Until the DCEP ACK, messages are sent ordered (RFC 8832 Section 6).
+    pub acked: bool,
+    pub buffered_amount_low_threshold: usize,
+    pub last_buffered_amount: usize,
+    pub max_buffered_amount: usize,
+    /// The task waiting in `poll_ready_to_send`.
+    pub waker: Option<Waker>,
+    pub incoming_reset: bool,
+    pub outgoing_reset: bool,
+}
+
+impl Channel {
+    pub fn is_ready_to_send(&self, buffered_amount: usize) -> bool {
+        self.state == DataChannelState::Open && buffered_amount < self.max_buffered_amount
+    }
+
+    pub fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Wakes the waiting task when `poll_ready_to_send` would be ready.
+    pub fn wake_if_ready(&mut self, buffered_amount: usize) {
+        match self.state {
+            DataChannelState::Connecting => {}
+            DataChannelState::Open if !self.is_ready_to_send(buffered_amount) => {}
+            _ => self.wake(),
+        }
+    }
+}
+
+/// A data channel of an `SctpTransport`, borrowed by its id.
+#[derive(Debug)]
+pub struct DataChannel<'a> {
+    pub(crate) transport: &'a mut SctpTransport,
+    pub(crate) id: u16,
+}
+
+impl DataChannel<'_> {
+    fn get_channel(&self) -> &Channel {
+        self.transport.get_channel(self.id)
+    }
+
+    pub fn get_id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn get_label(&self) -> &str {
+        &self.get_channel().label
+    }
+
+    pub fn get_protocol(&self) -> &str {
+        &self.get_channel().protocol
+    }
+
+    pub fn get_state(&self) -> DataChannelState {
+        self.get_channel().state
+    }
+
+    pub fn is_ordered(&self) -> bool {
+        self.get_channel().ordered
+    }
+
+    pub fn is_negotiated(&self) -> bool {
+        self.get_channel().negotiated
+    }
+
+    pub fn get_priority(&self) -> u16 {
+        self.get_channel().priority
+    }
+
+    pub fn get_max_retransmits(&self) -> Option<u16> {
+        match self.get_channel().reliability {
+            Reliability::MaxRetransmits(v) => Some(v.min(u32::from(u16::MAX)) as u16),
+            _ => None,
+        }
+    }
+
+    pub fn get_max_packet_life_time(&self) -> Option<u16> {
+        match self.get_channel().reliability {
+            Reliability::MaxPacketLifeTime(v) => Some(v.min(u32::from(u16::MAX)) as u16),
+            _ => None,
+        }
+    }
+
+    /// The bytes sent on this channel and not yet handed to the network.
+    pub fn get_buffered_amount(&self) -> usize {
+        self.transport.get_buffered_amount(self.id)
+    }
+
+    pub fn get_buffered_amount_low_threshold(&self) -> usize {
+        self.get_channel().buffered_amount_low_threshold
+    }
+
+    /// `BufferedAmountLow` is raised when the buffered amount falls to it.
+    pub fn set_buffered_amount_low_threshold(&mut self, threshold: usize) {
+        self.transport
+            .get_channel_mut(self.id)
+            .buffered_amount_low_threshold = threshold;
+    }
+
+    pub fn get_max_buffered_amount(&self) -> usize {
+        self.get_channel().max_buffered_amount
+    }
+
+    /// Sends are refused while the buffered amount is at or above it.
+    pub fn set_max_buffered_amount(&mut self, amount: usize) {
+        self.transport.get_channel_mut(self.id).max_buffered_amount = amount;
+        self.transport.wake_ready(self.id);
+    }
+
+    /// Whether the channel is open and takes another message.
+    pub fn is_ready_to_send(&self) -> bool {
+        self.get_channel()
+            .is_ready_to_send(self.get_buffered_amount())
+    }
+
+    /// `Ready(Ok)` when the channel takes another message, `Ready(Err)`
+    /// once it is closing. Otherwise `cx` is woken when that changes, as
+    /// the transport handles packets.
+    pub fn poll_ready_to_send(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let amount = self.get_buffered_amount();
+        let channel = self.transport.get_channel_mut(self.id);
+        match channel.state {
+            DataChannelState::Closing | DataChannelState::Closed => {
+                Poll::Ready(Err(DataChannelError::InvalidState))
+            }
+            _ if channel.is_ready_to_send(amount) => Poll::Ready(Ok(())),
+            _ => {
+                channel.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    pub fn send_text(&mut self, text: &str, now: Instant) -> Result<()> {
+        self.transport
+            .send(self.id, DataChannelMessage::Text(text.to_string()), now)
+    }
+
+    pub fn send_binary(&mut self, data: &[u8], now: Instant) -> Result<()> {
+        self.transport
+            .send(self.id, DataChannelMessage::Binary(data.to_vec()), now)
+    }
+
+    /// Resets the streams of the channel, after the messages already sent.
+    pub fn close(&mut self, now: Instant) {
+        self.transport.close_channel(self.id, now);
+    }
+}
@@ -0,0 +1,312 @@
+// https://tools.ietf.org/html/rfc8832#section-5
+// https://tools.ietf.org/html/rfc8831#section-8
+
+/*
+    DATA_CHANNEL_OPEN
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |  Message Type |  Channel Type |            Priority           |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                    Reliability Parameter                      |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |         Label Length          |       Protocol Length         |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    \                             Label                             \
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    \                           Protocol                            \
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    DATA_CHANNEL_ACK is the Message Type alone.
+
+    channel type   0x00 reliable                 parameter ignored
+                   0x01 partial reliable rexmit  max retransmissions
+                   0x02 partial reliable timed   lifetime in ms
+                   | 0x80 unordered
+
+    the opener sends OPEN on a stream of its parity, the DTLS client the
+    even ones, and the peer answers ACK on the same stream. Both are sent
+    ordered and reliable with the PPID 50 (Section 6).
+*/
+
+use crate::datachannel::{DataChannelError, Result};
+use crate::dtls::transport::DtlsRole;
+use crate::sctp::association::SctpMessage;
+
+pub const PPID_DCEP: u32 = 50;
+pub const PPID_STRING: u32 = 51;
+pub const PPID_BINARY: u32 = 53;
+/// An empty message is sent as one byte with these (RFC 8831 Section 6.6).
+pub const PPID_STRING_EMPTY: u32 = 56;
+pub const PPID_BINARY_EMPTY: u32 = 57;
+
+pub const PRIORITY_BELOW_NORMAL: u16 = 128;
+pub const PRIORITY_NORMAL: u16 = 256;
+pub const PRIORITY_HIGH: u16 = 512;
+pub const PRIORITY_EXTRA_HIGH: u16 = 1024;
+
+/// Stream 65535 is reserved (RFC 8831 Section 6.5).
+pub const MAX_STREAM_ID: u16 = 65534;
+
+const MESSAGE_TYPE_ACK: u8 = 0x02;
+const MESSAGE_TYPE_OPEN: u8 = 0x03;
+const CHANNEL_TYPE_RELIABLE: u8 = 0x00;
+const CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT: u8 = 0x01;
+const CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED: u8 = 0x02;
+const CHANNEL_TYPE_UNORDERED: u8 = 0x80;
+const OPEN_HEADER_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Reliability {
+    Reliable,
+    MaxRetransmits(u32),
+    /// In milliseconds.
+    MaxPacketLifeTime(u32),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DataChannelOpen {
+    pub ordered: bool,
+    pub reliability: Reliability,
+    pub priority: u16,
+    pub label: String,
+    pub protocol: String,
+}
+
+impl Default for DataChannelOpen {
+    fn default() -> Self {
+        DataChannelOpen {
+            ordered: true,
+            reliability: Reliability::Reliable,
+            priority: PRIORITY_NORMAL,
+            label: String::new(),
+            protocol: String::new(),
+        }
+    }
+}
+
+impl DataChannelOpen {
+    fn get_channel_type(&self) -> (u8, u32) {
+        let (channel_type, parameter) = match self.reliability {
+            Reliability::Reliable => (CHANNEL_TYPE_RELIABLE, 0),
+            Reliability::MaxRetransmits(v) => (CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT, v),
+            Reliability::MaxPacketLifeTime(v) => (CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED, v),
+        };
+        if self.ordered {
+            (channel_type, parameter)
+        } else {
+            (channel_type | CHANNEL_TYPE_UNORDERED, parameter)
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (channel_type, parameter) = self.get_channel_type();
+        let mut out =
+            Vec::with_capacity(OPEN_HEADER_LENGTH + self.label.len() + self.protocol.len());
+        out.push(MESSAGE_TYPE_OPEN);
+        out.push(channel_type);
+        out.extend_from_slice(&self.priority.to_be_bytes());
+        out.extend_from_slice(&parameter.to_be_bytes());
+        out.extend_from_slice(&(self.label.len() as u16).to_be_bytes());
+        out.extend_from_slice(&(self.protocol.len() as u16).to_be_bytes());
+        out.extend_from_slice(self.label.as_bytes());
+        out.extend_from_slice(self.protocol.as_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DataChannelOpen> {
+        if bytes.len() < OPEN_HEADER_LENGTH || bytes[0] != MESSAGE_TYPE_OPEN {
+            return Err(DataChannelError::InvalidMessage);
+        }
+        let parameter = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let reliability = match bytes[1] & !CHANNEL_TYPE_UNORDERED {
+            CHANNEL_TYPE_RELIABLE => Reliability::Reliable,
+            CHANNEL_TYPE_PARTIAL_RELIABLE_REXMIT => Reliability::MaxRetransmits(parameter),
+            CHANNEL_TYPE_PARTIAL_RELIABLE_TIMED => Reliability::MaxPacketLifeTime(parameter),
+            _ => {
+                return Err(DataChannelError::UnknownChannelType {
+                    channel_type: bytes[1],
+                })
+            }
+        };
+        let label_length = usize::from(u16::from_be_bytes([bytes[8], bytes[9]]));
+        let protocol_length = usize::from(u16::from_be_bytes([bytes[10], bytes[11]]));
+        if bytes.len() != OPEN_HEADER_LENGTH + label_length + protocol_length {
+            return Err(DataChannelError::InvalidMessage);
+        }
+        let (label, protocol) = bytes[OPEN_HEADER_LENGTH..].split_at(label_length);
+        let to_string =
+            |v: &[u8]| String::from_utf8(v.to_vec()).map_err(|_| DataChannelError::InvalidMessage);
+        Ok(DataChannelOpen {
+            ordered: bytes[1] & CHANNEL_TYPE_UNORDERED == 0,
+            reliability,
+            priority: u16::from_be_bytes([bytes[2], bytes[3]]),
+            label: to_string(label)?,
+            protocol: to_string(protocol)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum DcepMessage {
+    Open(DataChannelOpen),
+    Ack,
+}
+
+impl DcepMessage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            DcepMessage::Open(open) => open.to_bytes(),
+            DcepMessage::Ack => vec![MESSAGE_TYPE_ACK],
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DcepMessage> {
+        match bytes.first() {
+            Some(&MESSAGE_TYPE_OPEN) => Ok(DcepMessage::Open(DataChannelOpen::from_bytes(bytes)?)),
+            // anything after the type is ignored.
+            Some(&MESSAGE_TYPE_ACK) => Ok(DcepMessage::Ack),
+            Some(&message_type) => Err(DataChannelError::UnknownMessageType { message_type }),
+            None => Err(DataChannelError::InvalidMessage),
+        }
+    }
+
+    /// The SCTP message carrying it on `stream_id`.
+    pub fn to_sctp_message(&self, stream_id: u16) -> SctpMessage {
+        SctpMessage {
+            stream_id,
+            ppid: PPID_DCEP,
+            data: self.to_bytes(),
+            unordered: false,
+        }
+    }
+}
+
+/// Whether `stream_id` is of the parity this side opens channels on: even
+/// for the DTLS client, odd for the server.
+pub fn is_local_stream_id(role: DtlsRole, stream_id: u16) -> bool {
+    match role {
+        DtlsRole::Client => stream_id.is_multiple_of(2),
+        DtlsRole::Server => !stream_id.is_multiple_of(2),
+    }
+}
+
+/// The lowest stream of this side that is not `used`, below the `streams`
+/// negotiated.
+pub fn allocate_stream_id(role: DtlsRole, streams: u16, used: &dyn Fn(u16) -> bool) -> Option<u16> {
+    let first = if role == DtlsRole::Client { 0 } else { 1 };
+    // an exclusive end never reaches the reserved 65535.
+    (first..streams).step_by(2).find(|v| !used(*v))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dcep_open_test() {
+        // the OPEN of a browser for createDataChannel("chat").
+        let bytes = [
+            0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, b'c', b'h',
+            b'a', b't',
+        ];
+        let open = DataChannelOpen {
+            label: "chat".to_string(),
+            priority: 0,
+            ..DataChannelOpen::default()
+        };
+        assert_eq!(
+            DcepMessage::from_bytes(&bytes),
+            Ok(DcepMessage::Open(open.clone()))
+        );
+        assert_eq!(open.to_bytes(), bytes.to_vec());
+
+        for open in &[
+            DataChannelOpen {
+                ordered: false,
+                reliability: Reliability::MaxRetransmits(0),
+                priority: PRIORITY_HIGH,
+                label: "game state".to_string(),
+                protocol: "binary".to_string(),
+            },
+            DataChannelOpen {
+                reliability: Reliability::MaxPacketLifeTime(150),
+                label: "\u{30c1}\u{30e3}\u{30c3}\u{30c8}".to_string(),
+                ..DataChannelOpen::default()
+            },
+        ] {
+            assert_eq!(
+                DataChannelOpen::from_bytes(&open.to_bytes()).as_ref(),
+                Ok(open)
+            );
+        }
+        let bytes = DataChannelOpen {
+            ordered: false,
+            reliability: Reliability::MaxPacketLifeTime(150),
+            ..DataChannelOpen::default()
+        }
+        .to_bytes();
+        assert_eq!(
+            &bytes[..8],
+            &[0x03, 0x82, 0x01, 0x00, 0x00, 0x00, 0x00, 150]
+        );
+    }
+
+    #[test]
+    fn dcep_message_test() {
+        assert_eq!(DcepMessage::from_bytes(&[0x02]), Ok(DcepMessage::Ack));
+        assert_eq!(DcepMessage::Ack.to_bytes(), vec![0x02]);
+        let message = DcepMessage::Ack.to_sctp_message(3);
+        assert_eq!(message.ppid, PPID_DCEP);
+        assert!(!message.unordered);
+
+        assert_eq!(
+            DcepMessage::from_bytes(&[0x04]),
+            Err(DataChannelError::UnknownMessageType { message_type: 4 })
+        );
+        assert_eq!(
+            DcepMessage::from_bytes(&[]),
+            Err(DataChannelError::InvalidMessage)
+        );
+        let mut open = DataChannelOpen {
+            label: "x".to_string(),
+            ..DataChannelOpen::default()
+        }
+        .to_bytes();
+        assert_eq!(
+            DcepMessage::from_bytes(&open[..open.len() - 1]),
+            Err(DataChannelError::InvalidMessage)
+        );
+        open[12] = 0xff;
+        assert_eq!(
+            DcepMessage::from_bytes(&open),
+            Err(DataChannelError::InvalidMessage)
+        );
+        open[1] = 0x03;
+        assert_eq!(
+            DcepMessage::from_bytes(&open),
+            Err(DataChannelError::UnknownChannelType { channel_type: 3 })
+        );
+    }
+
+    #[test]
+    fn stream_id_test() {
+        assert!(is_local_stream_id(DtlsRole::Client, 0));
+        assert!(!is_local_stream_id(DtlsRole::Client, 1));
+        assert!(is_local_stream_id(DtlsRole::Server, 65533));
+
+        let used = |v: u16| v < 4;
+        assert_eq!(allocate_stream_id(DtlsRole::Client, 1024, &used), Some(4));
+        assert_eq!(allocate_stream_id(DtlsRole::Server, 1024, &used), Some(5));
+        assert_eq!(allocate_stream_id(DtlsRole::Server, 5, &used), None);
+        assert_eq!(
+            allocate_stream_id(DtlsRole::Server, u16::MAX, &|v| v < 65533),
+            Some(65533)
+        );
+        assert_eq!(
+            allocate_stream_id(DtlsRole::Client, u16::MAX, &|v| v < 65534),
+            Some(65534)
+        );
+    }
+}
@@ -0,0 +1,804 @@
+// https://www.w3.org/TR/webrtc/#rtcsctptransport-interface
+// https://tools.ietf.org/html/rfc8831#section-6
+// https://tools.ietf.org/html/rfc8832#section-6
+
+/*
+    createDataChannel                           (peer)
+      Connecting  -- DATA_CHANNEL_OPEN ->  DataChannel, Open
+      Open        <- DATA_CHANNEL_ACK  --
+                  <- messages          ->
+
+    a negotiated channel opens with the association, without DCEP.
+
+    DCEP messages are sent reliably, the user messages after them with
+    the reliability of the channel (RFC 8831 Section 6.1).
+
+    close
+      Closing     -- reset outgoing    ->  Closing
+                  <- reset outgoing    --
+      Closed                               Closed
+*/
+
+use crate::datachannel::channel::{
+    Channel, DataChannel, DataChannelEvent, DataChannelInit, DataChannelMessage, DataChannelState,
+    DEFAULT_MAX_BUFFERED_AMOUNT,
+};
+use crate::datachannel::dcep::{
+    allocate_stream_id, is_local_stream_id, DataChannelOpen, DcepMessage, Reliability,
+    MAX_STREAM_ID, PPID_BINARY, PPID_BINARY_EMPTY, PPID_DCEP, PPID_STRING, PPID_STRING_EMPTY,
+};
+use crate::datachannel::{DataChannelError, Result};
+use crate::dtls::transport::{DtlsBackend, DtlsRole, DtlsTransport};
+use crate::sctp::association::{
+    SctpAssociation, SctpConfig, SctpEvent, SctpMessage, SctpReliability, SctpState,
+};
+use crate::time::Instant;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+fn get_sctp_reliability(reliability: Reliability) -> SctpReliability {
+    match reliability {
+        Reliability::Reliable => SctpReliability::Reliable,
+        Reliability::MaxRetransmits(v) => SctpReliability::MaxRetransmits(v),
+        Reliability::MaxPacketLifeTime(v) => {
+            SctpReliability::MaxLifetime(Duration::from_millis(u64::from(v)))
+        }
+    }
+}
+
+// SCTP associationの上のdata channel．
+#[derive(Debug)]
+pub struct SctpTransport {
+    role: DtlsRole,
+    association: SctpAssociation,
+    channels: BTreeMap<u16, Channel>,
+    events: VecDeque<DataChannelEvent>,
+}
+
+impl SctpTransport {
+    /// `role` is the one of DTLS, which decides the parity of the streams.
+    pub fn new(role: DtlsRole, config: SctpConfig, now: Instant) -> Self {
+        SctpTransport {
+            role,
+            association: SctpAssociation::new(config, now),
+            channels: BTreeMap::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn get_association(&self) -> &SctpAssociation {
+        &self.association
+    }
+
+    pub fn get_association_mut(&mut self) -> &mut SctpAssociation {
+        &mut self.association
+    }
+
+    /// Sends the INIT. Browsers connect from both sides, which the
+    /// association resolves.
+    pub fn start(&mut self, now: Instant) {
+        self.association.connect(now);
+    }
+
+    pub fn create_data_channel(
+        &mut self,
+        label: &str,
+        init: &DataChannelInit,
+        now: Instant,
+    ) -> Result<u16> {
+        let reliability = init.get_reliability()?;
+        if label.len() > usize::from(u16::MAX) || init.protocol.len() > usize::from(u16::MAX) {
+            return Err(DataChannelError::InvalidParameters);
+        }
+        let id = if init.negotiated {
+            let id = init.id.ok_or(DataChannelError::InvalidParameters)?;
+            if id > MAX_STREAM_ID {
+                return Err(DataChannelError::InvalidParameters);
+            }
+            if self.channels.contains_key(&id) {
+                return Err(DataChannelError::StreamIdInUse { id });
+            }
+            id
+        } else {
+            let channels = &self.channels;
+            allocate_stream_id(self.role, self.association.get_outbound_streams(), &|v| {
+                channels.contains_key(&v)
+            })
+            .ok_or(DataChannelError::NoStreamAvailable)?
+        };
+        self.channels.insert(
+            id,
+            Channel {
+                label: label.to_string(),
+                protocol: init.protocol.clone(),
+                ordered: init.ordered,
+                reliability,
+                priority: init.priority,
+                negotiated: init.negotiated,
+                state: DataChannelState::Connecting,
+                acked: init.negotiated,
+                buffered_amount_low_threshold: 0,
+                last_buffered_amount: 0,
+                max_buffered_amount: DEFAULT_MAX_BUFFERED_AMOUNT,
+                waker: None,
+                incoming_reset: false,
+                outgoing_reset: false,
+            },
+        );
+        if self.association.get_state() == SctpState::Established {
+            self.open_channel(id, now);
+        }
+        Ok(id)
+    }
+
+    pub fn get_data_channel(&mut self, id: u16) -> Option<DataChannel<'_>> {
+        if self.channels.contains_key(&id) {
+            Some(DataChannel {
+                transport: self,
+                id,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The channels not closed yet.
+    pub fn get_data_channel_ids(&self) -> Vec<u16> {
+        self.channels.keys().copied().collect()
+    }
+
+    /// Handles an SCTP packet received over DTLS.
+    pub fn handle_packet(&mut self, data: &[u8], now: Instant) -> Result<()> {
+        let handled = self.association.handle_packet(data, now);
+        self.update(now);
+        Ok(handled?)
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.association.poll_timeout()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        self.association.process(now);
+        self.update(now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.association.poll_transmit()
+    }
+
+    pub fn poll_event(&mut self) -> Option<DataChannelEvent> {
+        self.events.pop_front()
+    }
+
+    /// Sends the pending SCTP packets over `dtls`.
+    pub fn flush<B: DtlsBackend>(&mut self, dtls: &mut DtlsTransport<B>) {
+        self.association.flush(dtls);
+    }
+
+    pub(crate) fn get_channel(&self, id: u16) -> &Channel {
+        &self.channels[&id]
+    }
+
+    pub(crate) fn get_channel_mut(&mut self, id: u16) -> &mut Channel {
+        self.channels.get_mut(&id).unwrap()
+    }
+
+    pub(crate) fn get_buffered_amount(&self, id: u16) -> usize {
+        self.association.get_buffered_amount(id)
+    }
+
+    pub(crate) fn send(
+        &mut self,
+        id: u16,
+        message: DataChannelMessage,
+        now: Instant,
+    ) -> Result<()> {
+        let channel = &self.channels[&id];
+        if channel.state != DataChannelState::Open {
+            return Err(DataChannelError::InvalidState);
+        }
+        if !channel.is_ready_to_send(self.association.get_buffered_amount(id)) {
+            return Err(DataChannelError::BufferFull);
+        }
+        let (ppid, data) = match message {
+            DataChannelMessage::Text(text) if text.is_empty() => (PPID_STRING_EMPTY, vec![0]),
+            DataChannelMessage::Text(text) => (PPID_STRING, text.into_bytes()),
+            DataChannelMessage::Binary(data) if data.is_empty() => (PPID_BINARY_EMPTY, vec![0]),
+            DataChannelMessage::Binary(data) => (PPID_BINARY, data),
+        };
+        let message = SctpMessage {
+            stream_id: id,
+            ppid,
+            data,
+            unordered: !channel.ordered && channel.acked,
+        };
+        self.association.send(message, now)?;
+        self.get_channel_mut(id).last_buffered_amount = self.association.get_buffered_amount(id);
+        self.update(now);
+        Ok(())
+    }
+
+    pub(crate) fn wake_ready(&mut self, id: u16) {
+        let amount = self.association.get_buffered_amount(id);
+        self.get_channel_mut(id).wake_if_ready(amount);
+    }
+
+    pub(crate) fn close_channel(&mut self, id: u16, now: Instant) {
+        let channel = self.get_channel_mut(id);
+        match channel.state {
+            DataChannelState::Closing | DataChannelState::Closed => return,
+            DataChannelState::Connecting => {
+                self.finish_close(id);
+                return;
+            }
+            DataChannelState::Open => channel.state = DataChannelState::Closing,
+        }
+        if self.association.reset_streams(&[id], now).is_err() {
+            self.finish_close(id);
+        }
+        self.update(now);
+    }
+
+    fn open_channel(&mut self, id: u16, now: Instant) {
+        let channel = &self.channels[&id];
+        if !channel.negotiated {
+            let open = DcepMessage::Open(DataChannelOpen {
+                ordered: channel.ordered,
+                reliability: channel.reliability,
+                priority: channel.priority,
+                label: channel.label.clone(),
+                protocol: channel.protocol.clone(),
+            });
+            if let Err(error) = self.association.send(open.to_sctp_message(id), now) {
+                self.events.push_back(DataChannelEvent::Error {
+                    id,
+                    error: error.into(),
+                });
+                self.finish_close(id);
+                return;
+            }
+        }
+        self.association
+            .set_reliability(id, get_sctp_reliability(channel.reliability));
+        self.get_channel_mut(id).state = DataChannelState::Open;
+        self.events.push_back(DataChannelEvent::Open { id });
+    }
+
+    fn finish_close(&mut self, id: u16) {
+        if let Some(mut channel) = self.channels.remove(&id) {
+            channel.wake();
+            self.events.push_back(DataChannelEvent::Close { id });
+        }
+    }
+
+    fn update(&mut self, now: Instant) {
+        while let Some(event) = self.association.poll_event() {
+            match event {
+                SctpEvent::StateChanged(SctpState::Established) => {
+                    let connecting: Vec<u16> = self
+                        .channels
+                        .iter()
+                        .filter(|(_, v)| v.state == DataChannelState::Connecting)
+                        .map(|(k, _)| *k)
+                        .collect();
+                    for id in connecting {
+                        self.open_channel(id, now);
+                    }
+                }
+                SctpEvent::StateChanged(SctpState::Closed) => {
+                    let ids: Vec<u16> = self.channels.keys().copied().collect();
+                    for id in ids {
+                        self.finish_close(id);
+                    }
+                }
+                SctpEvent::StateChanged(_) => {}
+                SctpEvent::IncomingStreamsReset(streams) => {
+                    let ids = if streams.is_empty() {
+                        self.channels.keys().copied().collect()
+                    } else {
+                        streams
+                    };
+                    for id in ids {
+                        self.handle_incoming_reset(id, now);
+                    }
+                }
+                SctpEvent::OutgoingStreamsReset(streams) => {
+                    for id in streams {
+                        if let Some(channel) = self.channels.get_mut(&id) {
+                            channel.outgoing_reset = true;
+                            if channel.incoming_reset {
+                                self.finish_close(id);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        while let Some(message) = self.association.poll_message() {
+            self.handle_message(message, now);
+        }
+        for (id, channel) in self.channels.iter_mut() {
+            let amount = self.association.get_buffered_amount(*id);
+            if channel.last_buffered_amount > channel.buffered_amount_low_threshold
+                && amount <= channel.buffered_amount_low_threshold
+            {
+                self.events
+                    .push_back(DataChannelEvent::BufferedAmountLow { id: *id });
+            }
+            channel.last_buffered_amount = amount;
+            channel.wake_if_ready(amount);
+        }
+    }
+
+    // the peer closed its side, this side follows (RFC 8831 Section 6.7).
+    fn handle_incoming_reset(&mut self, id: u16, now: Instant) {
+        let channel = match self.channels.get_mut(&id) {
+            Some(v) => v,
+            None => return,
+        };
+        channel.incoming_reset = true;
+        if channel.outgoing_reset {
+            self.finish_close(id);
+            return;
+        }
+        if channel.state == DataChannelState::Open {
+            channel.state = DataChannelState::Closing;
+            self.events.push_back(DataChannelEvent::Closing { id });
+            if self.association.reset_streams(&[id], now).is_err() {
+                self.finish_close(id);
+            }
+        }
+    }
+
+    fn handle_message(&mut self, message: SctpMessage, now: Instant) {
+        let id = message.stream_id;
+        if message.ppid == PPID_DCEP {
+            match DcepMessage::from_bytes(&message.data) {
+                Ok(DcepMessage::Open(open)) => self.handle_open(id, open, now),
+                Ok(DcepMessage::Ack) => {
+                    if let Some(channel) = self.channels.get_mut(&id) {
+                        channel.acked = true;
+                    }
+                }
+                Err(error) => {
+                    if self.channels.contains_key(&id) {
+                        self.events.push_back(DataChannelEvent::Error { id, error });
+                    }
+                }
+            }
+            return;
+        }
+        // a closing channel still gets what the peer sent before.
+        match self.channels.get(&id).map(|v| v.state) {
+            Some(DataChannelState::Open) | Some(DataChannelState::Closing) => {}
+            _ => return,
+        }
+        let message = match message.ppid {
+            PPID_STRING => match String::from_utf8(message.data) {
+                Ok(text) => DataChannelMessage::Text(text),
+                Err(_) => {
+                    self.events.push_back(DataChannelEvent::Error {
+                        id,
+                        error: DataChannelError::InvalidMessage,
+                    });
+                    return;
+                }
+            },
+            PPID_STRING_EMPTY => DataChannelMessage::Text(String::new()),
+            PPID_BINARY => DataChannelMessage::Binary(message.data),
+            PPID_BINARY_EMPTY => DataChannelMessage::Binary(Vec::new()),
+            _ => return,
+        };
+        self.events
+            .push_back(DataChannelEvent::Message { id, message });
+    }
+
+    fn handle_open(&mut self, id: u16, open: DataChannelOpen, now: Instant) {
+        // the peer opens on its own parity only.
+        if self.channels.contains_key(&id) || is_local_stream_id(self.role, id) {
+            return;
+        }
+        if self
+            .association
+            .send(DcepMessage::Ack.to_sctp_message(id), now)
+            .is_err()
+        {
+            return;
+        }
+        self.association
+            .set_reliability(id, get_sctp_reliability(open.reliability));
+        self.channels.insert(
+            id,
+            Channel {
+                label: open.label,
+                protocol: open.protocol,
+                ordered: open.ordered,
+                reliability: open.reliability,
+                priority: open.priority,
+                negotiated: false,
+                state: DataChannelState::Open,
+                acked: true,
+                buffered_amount_low_threshold: 0,
+                last_buffered_amount: 0,
+                max_buffered_amount: DEFAULT_MAX_BUFFERED_AMOUNT,
+                waker: None,
+                incoming_reset: false,
+                outgoing_reset: false,
+            },
+        );
+        self.events.push_back(DataChannelEvent::DataChannel { id });
+        self.events.push_back(DataChannelEvent::Open { id });
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::time::Duration;
+
+    pub(crate) fn new_transports(now: Instant) -> (SctpTransport, SctpTransport) {
+        let mut client = SctpTransport::new(DtlsRole::Client, SctpConfig::default(), now);
+        let mut server = SctpTransport::new(DtlsRole::Server, SctpConfig::default(), now);
+        client.start(now);
+        server.start(now);
+        (client, server)
+    }
+
+    /// Delivers the packets and runs the timers until both sides are idle.
+    pub(crate) fn run(a: &mut SctpTransport, b: &mut SctpTransport, mut now: Instant) -> Instant {
+        for _ in 0..100 {
+            loop {
+                let mut delivered = false;
+                while let Some(v) = a.poll_transmit() {
+                    b.handle_packet(&v, now).unwrap();
+                    delivered = true;
+                }
+                while let Some(v) = b.poll_transmit() {
+                    a.handle_packet(&v, now).unwrap();
+                    delivered = true;
+                }
+                if !delivered {
+                    break;
+                }
+            }
+            match [a.poll_timeout(), b.poll_timeout()].iter().flatten().min() {
+                Some(v) => {
+                    now = now.max(*v);
+                    a.process(now);
+                    b.process(now);
+                }
+                None => break,
+            }
+        }
+        now
+    }
+
+    pub(crate) fn iter_events(transport: &mut SctpTransport) -> Vec<DataChannelEvent> {
+        std::iter::from_fn(|| transport.poll_event()).collect()
+    }
+
+    fn text(id: u16, text: &str) -> DataChannelEvent {
+        DataChannelEvent::Message {
+            id,
+            message: DataChannelMessage::Text(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn in_band_open_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let init = DataChannelInit {
+            ordered: false,
+            max_retransmits: Some(3),
+            protocol: "chat".to_string(),
+            ..DataChannelInit::default()
+        };
+        let id = client.create_data_channel("room", &init, now).unwrap();
+        assert_eq!(id, 0);
+        assert_eq!(
+            client.get_data_channel(id).unwrap().get_state(),
+            DataChannelState::Connecting
+        );
+        let now = run(&mut client, &mut server, now);
+        assert_eq!(
+            iter_events(&mut client),
+            vec![DataChannelEvent::Open { id }]
+        );
+        assert_eq!(
+            iter_events(&mut server),
+            vec![
+                DataChannelEvent::DataChannel { id },
+                DataChannelEvent::Open { id }
+            ]
+        );
+        let channel = server.get_data_channel(id).unwrap();
+        assert_eq!(channel.get_label(), "room");
+        assert_eq!(channel.get_protocol(), "chat");
+        assert!(!channel.is_ordered());
+        assert_eq!(channel.get_max_retransmits(), Some(3));
+        assert_eq!(channel.get_max_packet_life_time(), None);
+        assert!(client.get_channel(id).acked);
+
+        let mut channel = client.get_data_channel(id).unwrap();
+        channel.send_text("hello", now).unwrap();
+        channel.send_text("", now).unwrap();
+        channel.send_binary(&[1, 2, 3], now).unwrap();
+        channel.send_binary(&[], now).unwrap();
+        let now = run(&mut client, &mut server, now);
+        let mut events = iter_events(&mut server);
+        // unordered, the order is not kept in general.
+        events.sort_by_key(|v| format!("{:?}", v));
+        assert_eq!(
+            events,
+            vec![
+                DataChannelEvent::Message {
+                    id,
+                    message: DataChannelMessage::Binary(vec![1, 2, 3])
+                },
+                DataChannelEvent::Message {
+                    id,
+                    message: DataChannelMessage::Binary(vec![])
+                },
+                text(id, ""),
+                text(id, "hello"),
+            ]
+        );
+
+        // the server opens on the odd streams.
+        let id = server
+            .create_data_channel("back", &DataChannelInit::default(), now)
+            .unwrap();
+        assert_eq!(id, 1);
+        run(&mut client, &mut server, now);
+        assert_eq!(
+            iter_events(&mut client),
+            vec![
+                DataChannelEvent::DataChannel { id },
+                DataChannelEvent::Open { id }
+            ]
+        );
+        assert_eq!(client.get_data_channel_ids(), vec![0, 1]);
+    }
+
+    #[test]
+    fn negotiated_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let init = DataChannelInit {
+            negotiated: true,
+            id: Some(5),
+            ..DataChannelInit::default()
+        };
+        client.create_data_channel("a", &init, now).unwrap();
+        server.create_data_channel("a", &init, now).unwrap();
+        assert_eq!(
+            client.create_data_channel("b", &init, now),
+            Err(DataChannelError::StreamIdInUse { id: 5 })
+        );
+        let now = run(&mut client, &mut server, now);
+        assert_eq!(
+            iter_events(&mut client),
+            vec![DataChannelEvent::Open { id: 5 }]
+        );
+        assert_eq!(
+            iter_events(&mut server),
+            vec![DataChannelEvent::Open { id: 5 }]
+        );
+
+        client
+            .get_data_channel(5)
+            .unwrap()
+            .send_text("one", now)
+            .unwrap();
+        server
+            .get_data_channel(5)
+            .unwrap()
+            .send_text("two", now)
+            .unwrap();
+        run(&mut client, &mut server, now);
+        assert_eq!(iter_events(&mut server), vec![text(5, "one")]);
+        assert_eq!(iter_events(&mut client), vec![text(5, "two")]);
+
+        for init in &[
+            DataChannelInit {
+                negotiated: true,
+                ..DataChannelInit::default()
+            },
+            DataChannelInit {
+                max_retransmits: Some(0),
+                max_packet_life_time: Some(100),
+                ..DataChannelInit::default()
+            },
+            DataChannelInit {
+                negotiated: true,
+                id: Some(65535),
+                ..DataChannelInit::default()
+            },
+        ] {
+            assert_eq!(
+                client.create_data_channel("x", init, now),
+                Err(DataChannelError::InvalidParameters)
+            );
+        }
+        let mut channel = client.get_data_channel(5).unwrap();
+        channel.close(now);
+        assert_eq!(
+            channel.send_text("late", now),
+            Err(DataChannelError::InvalidState)
+        );
+    }
+
+    #[test]
+    fn buffered_amount_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let id = client
+            .create_data_channel("file", &DataChannelInit::default(), now)
+            .unwrap();
+        let now = run(&mut client, &mut server, now);
+        iter_events(&mut client);
+        let mut channel = client.get_data_channel(id).unwrap();
+        channel.set_buffered_amount_low_threshold(1000);
+        let data = vec![7; 100_000];
+        channel.send_binary(&data, now).unwrap();
+        let buffered = channel.get_buffered_amount();
+        assert!(buffered > 90_000);
+        assert!(buffered < 100_000);
+        assert_eq!(client.get_channel(id).last_buffered_amount, buffered);
+
+        run(&mut client, &mut server, now);
+        assert_eq!(
+            client.get_data_channel(id).unwrap().get_buffered_amount(),
+            0
+        );
+        assert_eq!(
+            iter_events(&mut client),
+            vec![DataChannelEvent::BufferedAmountLow { id }]
+        );
+        assert!(
+            iter_events(&mut server).contains(&DataChannelEvent::Message {
+                id,
+                message: DataChannelMessage::Binary(data)
+            })
+        );
+    }
+
+    #[test]
+    fn partial_reliability_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let init = DataChannelInit {
+            max_retransmits: Some(0),
+            ..DataChannelInit::default()
+        };
+        let id = client.create_data_channel("game", &init, now).unwrap();
+        let now = run(&mut client, &mut server, now);
+        iter_events(&mut server);
+        let mut channel = client.get_data_channel(id).unwrap();
+        channel.send_text("lost", now).unwrap();
+        client.poll_transmit().unwrap();
+        let mut channel = client.get_data_channel(id).unwrap();
+        channel.send_text("state", now).unwrap();
+        // skipped after the retransmission timeout, with FORWARD TSN.
+        run(&mut client, &mut server, now);
+        assert_eq!(iter_events(&mut server), vec![text(id, "state")]);
+    }
+
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn backpressure_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let id = client
+            .create_data_channel("file", &DataChannelInit::default(), now)
+            .unwrap();
+        let now = run(&mut client, &mut server, now);
+        iter_events(&mut client);
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut channel = client.get_data_channel(id).unwrap();
+        assert_eq!(
+            channel.get_max_buffered_amount(),
+            DEFAULT_MAX_BUFFERED_AMOUNT
+        );
+        channel.set_max_buffered_amount(10_000);
+        assert!(channel.is_ready_to_send());
+        channel.send_binary(&[1; 20_000], now).unwrap();
+        assert!(!channel.is_ready_to_send());
+        assert_eq!(
+            channel.send_binary(&[2; 10], now),
+            Err(DataChannelError::BufferFull)
+        );
+        assert_eq!(channel.poll_ready_to_send(&mut cx), Poll::Pending);
+        assert_eq!(count.0.load(Ordering::SeqCst), 0);
+
+        // the SACKs open cwnd and the buffer drains.
+        let now = run(&mut client, &mut server, now);
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        let mut channel = client.get_data_channel(id).unwrap();
+        assert_eq!(channel.poll_ready_to_send(&mut cx), Poll::Ready(Ok(())));
+        channel.send_binary(&[3; 10], now).unwrap();
+
+        channel.set_max_buffered_amount(0);
+        assert_eq!(channel.poll_ready_to_send(&mut cx), Poll::Pending);
+        channel.close(now);
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            client
+                .get_data_channel(id)
+                .unwrap()
+                .poll_ready_to_send(&mut cx),
+            Poll::Ready(Err(DataChannelError::InvalidState))
+        );
+    }
+
+    #[test]
+    fn close_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let id = client
+            .create_data_channel("chat", &DataChannelInit::default(), now)
+            .unwrap();
+        let now = run(&mut client, &mut server, now);
+        iter_events(&mut client);
+        iter_events(&mut server);
+
+        let mut channel = client.get_data_channel(id).unwrap();
+        channel.send_text("last", now).unwrap();
+        channel.close(now);
+        assert_eq!(channel.get_state(), DataChannelState::Closing);
+        let now = run(&mut client, &mut server, now);
+        assert_eq!(
+            iter_events(&mut client),
+            vec![DataChannelEvent::Close { id }]
+        );
+        assert_eq!(
+            iter_events(&mut server),
+            vec![
+                text(id, "last"),
+                DataChannelEvent::Closing { id },
+                DataChannelEvent::Close { id }
+            ]
+        );
+        assert!(client.get_data_channel(id).is_none());
+        assert!(server.get_data_channel(id).is_none());
+
+        // the stream is free again, and starts over at SSN 0.
+        let id = client
+            .create_data_channel("again", &DataChannelInit::default(), now)
+            .unwrap();
+        assert_eq!(id, 0);
+        let now = run(&mut client, &mut server, now);
+        client
+            .get_data_channel(id)
+            .unwrap()
+            .send_text("hi", now)
+            .unwrap();
+        let now = run(&mut client, &mut server, now);
+        assert_eq!(iter_events(&mut server).last(), Some(&text(id, "hi")));
+
+        // an association that goes away closes every channel.
+        client.get_association_mut().abort();
+        run(&mut client, &mut server, now + Duration::from_secs(1));
+        assert_eq!(
+            iter_events(&mut server),
+            vec![DataChannelEvent::Close { id }]
+        );
+        assert_eq!(client.get_association().get_state(), SctpState::Closed);
+    }
+}
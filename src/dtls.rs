@@ -0,0 +1,46 @@
+mod asn1;
+pub mod certificate;
+pub mod exporter;
+pub mod fingerprint;
+pub mod transport;
+pub mod use_srtp;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, DtlsError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum DtlsError {
+    #[fail(display = "a=fingerprint attribute is broken.")]
+    InvalidFingerprint,
+
+    #[fail(display = "Fingerprint hash function {} is not supported.", name)]
+    UnsupportedHashFunction { name: String },
+
+    #[fail(display = "Peer certificate does not match the signaled fingerprint.")]
+    FingerprintMismatch,
+
+    #[fail(display = "DTLS handshake failed: {}", reason)]
+    HandshakeFailed { reason: String },
+
+    #[fail(display = "DTLS is not connected.")]
+    NotConnected,
+
+    #[fail(display = "Certificate or private key is broken.")]
+    InvalidCertificate,
+
+    #[fail(display = "RSA key size {} is not supported.", bits)]
+    UnsupportedKeySize { bits: usize },
+
+    #[fail(display = "use_srtp extension is broken.")]
+    InvalidUseSrtp,
+
+    #[fail(display = "No SRTP protection profile is shared with the peer.")]
+    NoSharedSrtpProfile,
+
+    #[fail(display = "Peer selected an SRTP protection profile that was not offered.")]
+    SrtpProfileMismatch,
+
+    #[fail(display = "Keying material exporter label, context or length is invalid.")]
+    InvalidExporterParameters,
+}
@@ -0,0 +1,278 @@
+// https://www.itu.int/rec/T-REC-X.690
+
+/*
+    DER: tag | length | content
+
+    length < 128: 1 byte
+    otherwise:    0x80 | number of bytes, the length in big endian
+*/
+
+use crate::dtls::{DtlsError, Result};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+#[cfg(test)]
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// `[n]`, constructed.
+pub fn context_tag(n: u8) -> u8 {
+    0xa0 | n
+}
+
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let length = content.len();
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let skip = bytes.iter().take_while(|v| **v == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+pub fn encode_sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    encode(TAG_SEQUENCE, &items.concat())
+}
+
+/// A non-negative INTEGER from its big endian bytes.
+pub fn encode_integer(value: &[u8]) -> Vec<u8> {
+    let skip = value.iter().take_while(|v| **v == 0).count();
+    let value = &value[skip..];
+    let mut content = Vec::with_capacity(value.len() + 1);
+    // the high bit would make it negative.
+    if value.first().is_none_or(|v| v & 0x80 != 0) {
+        content.push(0);
+    }
+    content.extend_from_slice(value);
+    encode(TAG_INTEGER, &content)
+}
+
+pub fn encode_bit_string(value: &[u8]) -> Vec<u8> {
+    let mut content = vec![0];
+    content.extend_from_slice(value);
+    encode(TAG_BIT_STRING, &content)
+}
+
+/// UTCTime until 2049, GeneralizedTime after (RFC 5280 Section 4.1.2.5).
+pub fn encode_time(time: SystemTime) -> Vec<u8> {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |v| v.as_secs());
+    let (year, month, day) = get_civil_date((seconds / 86400) as i64);
+    let rest = seconds % 86400;
+    let (hour, minute, second) = (rest / 3600, rest / 60 % 60, rest % 60);
+    let clock = format!(
+        "{:02}{:02}{:02}{:02}{:02}Z",
+        month, day, hour, minute, second
+    );
+    if (1950..2050).contains(&year) {
+        let value = format!("{:02}{}", year % 100, clock);
+        encode(TAG_UTC_TIME, value.as_bytes())
+    } else {
+        let value = format!("{:04}{}", year, clock);
+        encode(TAG_GENERALIZED_TIME, value.as_bytes())
+    }
+}
+
+pub fn decode_time(tag: u8, content: &[u8]) -> Result<SystemTime> {
+    let value = std::str::from_utf8(content).map_err(|_| DtlsError::InvalidCertificate)?;
+    let value = value
+        .strip_suffix('Z')
+        .ok_or(DtlsError::InvalidCertificate)?;
+    let (year, rest) = match tag {
+        TAG_UTC_TIME if value.len() == 12 => {
+            let year = parse_digits(&value[..2])?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                &value[2..],
+            )
+        }
+        TAG_GENERALIZED_TIME if value.len() == 14 => (parse_digits(&value[..4])?, &value[4..]),
+        _ => return Err(DtlsError::InvalidCertificate),
+    };
+    let field = |i: usize| parse_digits(&rest[i * 2..i * 2 + 2]);
+    let (month, day) = (field(0)?, field(1)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(DtlsError::InvalidCertificate);
+    }
+    let days = get_days_from_civil(year, month, day);
+    let seconds = days * 86400 + field(2)? * 3600 + field(3)? * 60 + field(4)?;
+    if seconds < 0 {
+        return Err(DtlsError::InvalidCertificate);
+    }
+    Ok(UNIX_EPOCH + Duration::from_secs(seconds as u64))
+}
+
+fn parse_digits(value: &str) -> Result<i64> {
+    if !value.bytes().all(|v| v.is_ascii_digit()) {
+        return Err(DtlsError::InvalidCertificate);
+    }
+    value.parse().map_err(|_| DtlsError::InvalidCertificate)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn get_civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn get_days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// DERを先頭から読む．
+#[derive(Debug, Clone, Copy)]
+pub struct DerReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> DerReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        DerReader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// The next element, its tag and its content.
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8])> {
+        let invalid = DtlsError::InvalidCertificate;
+        let (tag, first) = match self.data {
+            [tag, first, ..] => (*tag, *first),
+            _ => return Err(invalid),
+        };
+        let (length, header) = if first < 0x80 {
+            (usize::from(first), 2)
+        } else {
+            let count = usize::from(first & 0x7f);
+            if count == 0 || count > 4 || self.data.len() < 2 + count {
+                return Err(invalid);
+            }
+            let length = self.data[2..2 + count]
+                .iter()
+                .fold(0, |v, b| (v << 8) | usize::from(*b));
+            (length, 2 + count)
+        };
+        if self.data.len() - header < length {
+            return Err(invalid);
+        }
+        let content = &self.data[header..header + length];
+        self.data = &self.data[header + length..];
+        Ok((tag, content))
+    }
+
+    /// The next element with its tag and length, e.g. to hash or sign it.
+    pub fn read_element(&mut self) -> Result<&'a [u8]> {
+        let data = self.data;
+        self.read_any()?;
+        Ok(&data[..data.len() - self.data.len()])
+    }
+
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8]> {
+        match self.read_any()? {
+            (v, content) if v == tag => Ok(content),
+            _ => Err(DtlsError::InvalidCertificate),
+        }
+    }
+
+    /// The big endian bytes of a non-negative INTEGER.
+    pub fn read_integer(&mut self) -> Result<&'a [u8]> {
+        let value = self.read(TAG_INTEGER)?;
+        match value {
+            [] => Err(DtlsError::InvalidCertificate),
+            [v, ..] if v & 0x80 != 0 => Err(DtlsError::InvalidCertificate),
+            [0, rest @ ..] => Ok(rest),
+            _ => Ok(value),
+        }
+    }
+
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8]> {
+        match self.read(TAG_BIT_STRING)? {
+            [0, rest @ ..] => Ok(rest),
+            _ => Err(DtlsError::InvalidCertificate),
+        }
+    }
+
+    pub fn read_sequence(&mut self) -> Result<DerReader<'a>> {
+        self.read(TAG_SEQUENCE).map(DerReader::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn der_test() {
+        let long = vec![0xab; 300];
+        let encoded = encode_sequence(&[
+            encode_integer(&[0x00, 0x80]),
+            encode(TAG_OCTET_STRING, &long),
+            encode_bit_string(&[1, 2]),
+        ]);
+        assert_eq!(&encoded[..4], &[0x30, 0x82, 0x01, 0x39]);
+        assert_eq!(&encoded[4..8], &[0x02, 0x02, 0x00, 0x80]);
+
+        let mut reader = DerReader::new(&encoded);
+        let mut sequence = reader.read_sequence().unwrap();
+        assert!(reader.is_empty());
+        assert_eq!(sequence.read_integer().unwrap(), &[0x80]);
+        assert_eq!(sequence.read(TAG_OCTET_STRING).unwrap(), &long[..]);
+        assert_eq!(sequence.read_bit_string().unwrap(), &[1, 2]);
+        assert!(sequence.is_empty());
+        assert!(sequence.read_any().is_err());
+
+        assert_eq!(encode_integer(&[0, 0, 1]), vec![0x02, 0x01, 0x01]);
+        assert_eq!(encode_integer(&[]), vec![0x02, 0x01, 0x00]);
+        assert!(DerReader::new(&[0x30, 0x05, 0x00]).read_any().is_err());
+    }
+
+    #[test]
+    fn time_test() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_735_689_600 + 3723);
+        let encoded = encode_time(time);
+        assert_eq!(&encoded[2..], b"250101010203Z");
+        let mut reader = DerReader::new(&encoded);
+        let (tag, content) = reader.read_any().unwrap();
+        assert_eq!(decode_time(tag, content), Ok(time));
+
+        let time = UNIX_EPOCH + Duration::from_secs(2_556_144_000);
+        let encoded = encode_time(time);
+        assert_eq!(encoded[0], TAG_GENERALIZED_TIME);
+        assert_eq!(&encoded[2..], b"20510101000000Z");
+        assert_eq!(decode_time(encoded[0], &encoded[2..]), Ok(time));
+
+        assert!(decode_time(TAG_UTC_TIME, b"251301000000Z").is_err());
+        assert!(decode_time(TAG_UTC_TIME, b"2501010000Z").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+// https://tools.ietf.org/html/rfc5705#section-4
+// https://tools.ietf.org/html/rfc5246#section-5
+// https://tools.ietf.org/html/rfc8446#section-7.5
+// https://tools.ietf.org/html/rfc9147#section-5.9
+
+/*
+    DTLS 1.2
+      PRF(master_secret, label, client_random | server_random
+          [| context_length(2) | context])
+
+    DTLS 1.3
+      HKDF-Expand-Label(Derive-Secret(exporter_master_secret, label, ""),
+                        "exporter", Hash(context), length)
+      the labels are prefixed with "dtls13", not "tls13 ".
+      no context and an empty context are the same.
+*/
+
+use crate::dtls::{DtlsError, Result};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha384};
+
+/// The labels the PRF of TLS itself uses (RFC 5705 Section 4).
+const RESERVED_LABELS: [&str; 5] = [
+    "client finished",
+    "server finished",
+    "master secret",
+    "extended master secret",
+    "key expansion",
+];
+
+const DTLS13_LABEL_PREFIX: &[u8] = b"dtls13";
+
+/// The hash of the cipher suite.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum PrfHash {
+    Sha256,
+    Sha384,
+}
+
+impl PrfHash {
+    pub fn get_length(self) -> usize {
+        match self {
+            PrfHash::Sha256 => 32,
+            PrfHash::Sha384 => 48,
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            PrfHash::Sha256 => Sha256::digest(data).to_vec(),
+            PrfHash::Sha384 => Sha384::digest(data).to_vec(),
+        }
+    }
+
+    fn hmac(self, key: &[u8], data: &[&[u8]]) -> Vec<u8> {
+        match self {
+            PrfHash::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+                data.iter().for_each(|v| mac.update(v));
+                mac.finalize().into_bytes().to_vec()
+            }
+            PrfHash::Sha384 => {
+                let mut mac = Hmac::<Sha384>::new_from_slice(key).unwrap();
+                data.iter().for_each(|v| mac.update(v));
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+}
+
+/// Checks what an application asks to export, before any backend sees it.
+pub fn validate_exporter_parameters(
+    label: &str,
+    context: Option<&[u8]>,
+    length: usize,
+) -> Result<()> {
+    if label.is_empty()
+        || RESERVED_LABELS.contains(&label)
+        || context.is_some_and(|v| v.len() > usize::from(u16::MAX))
+        || length == 0
+        || length > usize::from(u16::MAX)
+    {
+        return Err(DtlsError::InvalidExporterParameters);
+    }
+    Ok(())
+}
+
+// RFC 5246 Section 5, P_hash
+fn prf(hash: PrfHash, secret: &[u8], label: &[u8], seed: &[u8], length: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(length + hash.get_length());
+    let mut a = hash.hmac(secret, &[label, seed]);
+    while out.len() < length {
+        out.extend(hash.hmac(secret, &[&a, label, seed]));
+        a = hash.hmac(secret, &[&a]);
+    }
+    out.truncate(length);
+    out
+}
+
+/// The RFC 5705 exporter of DTLS 1.2.
+pub fn export_dtls12(
+    hash: PrfHash,
+    master_secret: &[u8],
+    client_random: &[u8],
+    server_random: &[u8],
+    label: &str,
+    context: Option<&[u8]>,
+    length: usize,
+) -> Vec<u8> {
+    let mut seed = Vec::with_capacity(client_random.len() + server_random.len());
+    seed.extend_from_slice(client_random);
+    seed.extend_from_slice(server_random);
+    if let Some(context) = context {
+        seed.extend_from_slice(&(context.len() as u16).to_be_bytes());
+        seed.extend_from_slice(context);
+    }
+    prf(hash, master_secret, label.as_bytes(), &seed, length)
+}
+
+// RFC 5869 Section 2.3
+fn hkdf_expand(hash: PrfHash, prk: &[u8], info: &[u8], length: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(length + hash.get_length());
+    let mut t = Vec::new();
+    let mut i = 1u8;
+    while out.len() < length {
+        t = hash.hmac(prk, &[&t, info, &[i]]);
+        out.extend_from_slice(&t);
+        i = i.wrapping_add(1);
+    }
+    out.truncate(length);
+    out
+}
+
+fn hkdf_expand_label(
+    hash: PrfHash,
+    secret: &[u8],
+    label: &[u8],
+    context: &[u8],
+    length: usize,
+) -> Vec<u8> {
+    let mut info = (length as u16).to_be_bytes().to_vec();
+    info.push((DTLS13_LABEL_PREFIX.len() + label.len()) as u8);
+    info.extend_from_slice(DTLS13_LABEL_PREFIX);
+    info.extend_from_slice(label);
+    info.push(context.len() as u8);
+    info.extend_from_slice(context);
+    hkdf_expand(hash, secret, &info, length)
+}
+
+/// The exporter of DTLS 1.3. HKDF-Expand limits `length` to 255 times the
+/// hash length, and the label to 249 bytes.
+pub fn export_dtls13(
+    hash: PrfHash,
+    exporter_master_secret: &[u8],
+    label: &str,
+    context: Option<&[u8]>,
+    length: usize,
+) -> Result<Vec<u8>> {
+    if length > 255 * hash.get_length() || label.len() > 255 - DTLS13_LABEL_PREFIX.len() {
+        return Err(DtlsError::InvalidExporterParameters);
+    }
+    let secret = hkdf_expand_label(
+        hash,
+        exporter_master_secret,
+        label.as_bytes(),
+        &hash.digest(b""),
+        hash.get_length(),
+    );
+    let context = hash.digest(context.unwrap_or(&[]));
+    Ok(hkdf_expand_label(
+        hash,
+        &secret,
+        b"exporter",
+        &context,
+        length,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn from_hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn prf_test() {
+        let secret = from_hex("9bbe436ba940f017b17652849a71db35");
+        let seed = from_hex("a0ba9f936cda311827a6f796ffd5198c");
+        let out = prf(PrfHash::Sha256, &secret, b"test label", &seed, 100);
+        assert_eq!(
+            out,
+            from_hex(
+                "e3f229ba727be17b8d122620557cd453c2aab21d07c3d495329b52d4e61edb5a\
+                 6b301791e90d35c9c9a46b4e14baf9af0fa022f7077def17abfd3797c0564bab\
+                 4fbc91666e9def9b97fce34f796789baa48082d122ee42c5a72e5a5110fff701\
+                 87347b66"
+            )
+        );
+    }
+
+    #[test]
+    fn export_dtls12_test() {
+        let master_secret: Vec<u8> = (0..48).collect();
+        let export = |hash, context, length| {
+            export_dtls12(
+                hash,
+                &master_secret,
+                &[1; 32],
+                &[2; 32],
+                "EXPERIMENTAL test",
+                context,
+                length,
+            )
+        };
+        assert_eq!(
+            export(PrfHash::Sha256, Some(&b"ctx"[..]), 32),
+            from_hex("2a52c043ab4d9c872c3f75e0c9d2ee14d14f27bb108a1c999b3a7d81e5144dcb")
+        );
+        assert_eq!(
+            export(PrfHash::Sha256, None, 32),
+            from_hex("a9f3a3f8fe17c6bfac564b2546a48b921b4efe62dc3719aa8b054a2ce48ba1aa")
+        );
+        // an empty context is not the same as none in DTLS 1.2.
+        assert_ne!(
+            export(PrfHash::Sha256, Some(&[]), 32),
+            export(PrfHash::Sha256, None, 32)
+        );
+        assert_eq!(
+            export(PrfHash::Sha384, None, 16),
+            from_hex("ce02d6ceba9dcb254faf478e8c46da59")
+        );
+    }
+
+    #[test]
+    fn export_dtls13_test() {
+        let secret: Vec<u8> = (0..32).collect();
+        let export = |context, length| {
+            export_dtls13(
+                PrfHash::Sha256,
+                &secret,
+                "EXPERIMENTAL test",
+                context,
+                length,
+            )
+        };
+        assert_eq!(
+            export(Some(&b"ctx"[..]), 32).unwrap(),
+            from_hex("1d1d2d56504a2b306e6bbe7f47c6fb57dc225d9401fef0d3a3ba11164ee012d8")
+        );
+        let none = export(None, 32).unwrap();
+        assert_eq!(
+            none,
+            from_hex("84d99e9bc3d338b1e7bba7c4f93454037deddc7d4a310f6a2315348380cada1e")
+        );
+        assert_eq!(export(Some(&[]), 32).unwrap(), none);
+        assert_eq!(export(None, 255 * 32).unwrap().len(), 255 * 32);
+        assert_eq!(
+            export(None, 255 * 32 + 1),
+            Err(DtlsError::InvalidExporterParameters)
+        );
+    }
+
+    #[test]
+    fn validate_exporter_parameters_test() {
+        assert_eq!(
+            validate_exporter_parameters("EXTRACTOR-dtls_srtp", None, 60),
+            Ok(())
+        );
+        for (label, context, length) in &[
+            ("", None, 16),
+            ("key expansion", None, 16),
+            ("EXPERIMENTAL", None, 0),
+            ("EXPERIMENTAL", None, 65536),
+        ] {
+            assert_eq!(
+                validate_exporter_parameters(label, *context, *length),
+                Err(DtlsError::InvalidExporterParameters)
+            );
+        }
+        let context = vec![0; 65536];
+        assert_eq!(
+            validate_exporter_parameters("EXPERIMENTAL", Some(&context), 16),
+            Err(DtlsError::InvalidExporterParameters)
+        );
+    }
+}
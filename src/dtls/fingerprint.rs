@@ -0,0 +1,221 @@
+// https://tools.ietf.org/html/rfc8122#section-5
+// https://tools.ietf.org/html/rfc8842#section-5
+
+/*
+    a=fingerprint:<hash-func> <fingerprint>
+
+    e.g.
+    a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC
+*/
+
+use crate::dtls::{DtlsError, Result};
+
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use std::fmt;
+use subtle::ConstantTimeEq;
+
+/// Only the hash functions a peer may still use are supported, md2 and md5
+/// are rejected.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum HashFunction {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashFunction {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            HashFunction::Sha1 => "sha-1",
+            HashFunction::Sha224 => "sha-224",
+            HashFunction::Sha256 => "sha-256",
+            HashFunction::Sha384 => "sha-384",
+            HashFunction::Sha512 => "sha-512",
+        }
+    }
+
+    /// The name is case insensitive.
+    pub fn from_name(name: &str) -> Option<HashFunction> {
+        match name.to_ascii_lowercase().as_str() {
+            "sha-1" => Some(HashFunction::Sha1),
+            "sha-224" => Some(HashFunction::Sha224),
+            "sha-256" => Some(HashFunction::Sha256),
+            "sha-384" => Some(HashFunction::Sha384),
+            "sha-512" => Some(HashFunction::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn get_length(self) -> usize {
+        match self {
+            HashFunction::Sha1 => 20,
+            HashFunction::Sha224 => 28,
+            HashFunction::Sha256 => 32,
+            HashFunction::Sha384 => 48,
+            HashFunction::Sha512 => 64,
+        }
+    }
+
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashFunction::Sha1 => Sha1::digest(data).to_vec(),
+            HashFunction::Sha224 => Sha224::digest(data).to_vec(),
+            HashFunction::Sha256 => Sha256::digest(data).to_vec(),
+            HashFunction::Sha384 => Sha384::digest(data).to_vec(),
+            HashFunction::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+// 証明書(DER)のfingerprint．SDPで相手に通知した値とDTLSで受け取った証明書を照合する．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CertificateFingerprint {
+    pub algorithm: HashFunction,
+    pub value: Vec<u8>,
+}
+
+impl CertificateFingerprint {
+    pub fn from_certificate(algorithm: HashFunction, der: &[u8]) -> Self {
+        CertificateFingerprint {
+            algorithm,
+            value: algorithm.digest(der),
+        }
+    }
+
+    /// Parses the value of an `a=fingerprint` line, with or without the
+    /// `a=fingerprint:` prefix.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix("a=").unwrap_or(value);
+        let value = value.strip_prefix("fingerprint:").unwrap_or(value);
+
+        let mut fields = value.split_whitespace();
+        let name = fields.next().ok_or(DtlsError::InvalidFingerprint)?;
+        let algorithm =
+            HashFunction::from_name(name).ok_or_else(|| DtlsError::UnsupportedHashFunction {
+                name: name.to_string(),
+            })?;
+        let hex = fields.next().ok_or(DtlsError::InvalidFingerprint)?;
+        if fields.next().is_some() {
+            return Err(DtlsError::InvalidFingerprint);
+        }
+
+        let value = hex
+            .split(':')
+            .map(|v| {
+                if v.len() != 2 {
+                    return Err(DtlsError::InvalidFingerprint);
+                }
+                u8::from_str_radix(v, 16).map_err(|_| DtlsError::InvalidFingerprint)
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        if value.len() != algorithm.get_length() {
+            return Err(DtlsError::InvalidFingerprint);
+        }
+
+        Ok(CertificateFingerprint { algorithm, value })
+    }
+
+    /// Checks the peer's DER certificate in constant time.
+    pub fn verify(&self, der: &[u8]) -> Result<()> {
+        let digest = self.algorithm.digest(der);
+        if bool::from(digest.ct_eq(&self.value)) {
+            Ok(())
+        } else {
+            Err(DtlsError::FingerprintMismatch)
+        }
+    }
+}
+
+impl fmt::Display for CertificateFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ", self.algorithm.get_name())?;
+        for (i, v) in self.value.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            write!(f, "{:02X}", v)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the peer certificate against the signaled fingerprints; any
+/// match is enough when several are given (RFC 8122 Section 5).
+pub fn verify_fingerprints(fingerprints: &[CertificateFingerprint], der: &[u8]) -> Result<()> {
+    if fingerprints.is_empty() {
+        return Err(DtlsError::InvalidFingerprint);
+    }
+    let mut matched = false;
+    for fingerprint in fingerprints {
+        // no short circuit, so the time does not depend on which one matched.
+        matched |= fingerprint.verify(der).is_ok();
+    }
+    if matched {
+        Ok(())
+    } else {
+        Err(DtlsError::FingerprintMismatch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_fingerprint_test() {
+        let line = "a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC";
+        let fingerprint = CertificateFingerprint::parse(line).unwrap();
+        assert_eq!(fingerprint.algorithm, HashFunction::Sha256);
+        assert_eq!(&fingerprint.value[..3], &[0x6b, 0x8b, 0x5d]);
+        assert_eq!(fingerprint.to_string(), &line["a=fingerprint:".len()..]);
+
+        let lower = CertificateFingerprint::parse(&line.to_ascii_lowercase()).unwrap();
+        assert_eq!(lower, fingerprint);
+
+        assert_eq!(
+            CertificateFingerprint::parse("md5 00:11"),
+            Err(DtlsError::UnsupportedHashFunction {
+                name: "md5".to_string()
+            })
+        );
+        for value in &["sha-256", "sha-256 6B:8B", "sha-1 6B8B", "sha-1 ZZ"] {
+            assert_eq!(
+                CertificateFingerprint::parse(value),
+                Err(DtlsError::InvalidFingerprint)
+            );
+        }
+    }
+
+    #[test]
+    fn verify_fingerprint_test() {
+        let certificate = b"not really a DER certificate";
+        let sha256 = CertificateFingerprint::from_certificate(HashFunction::Sha256, certificate);
+        assert_eq!(sha256.value.len(), 32);
+        assert_eq!(sha256.verify(certificate), Ok(()));
+        assert_eq!(
+            sha256.verify(b"another certificate"),
+            Err(DtlsError::FingerprintMismatch)
+        );
+
+        let parsed = CertificateFingerprint::parse(&sha256.to_string()).unwrap();
+        assert_eq!(parsed.verify(certificate), Ok(()));
+
+        let sha1 = CertificateFingerprint::from_certificate(HashFunction::Sha1, b"other");
+        assert_eq!(
+            verify_fingerprints(&[sha1.clone(), sha256], certificate),
+            Ok(())
+        );
+        assert_eq!(
+            verify_fingerprints(&[sha1], certificate),
+            Err(DtlsError::FingerprintMismatch)
+        );
+        assert_eq!(
+            verify_fingerprints(&[], certificate),
+            Err(DtlsError::InvalidFingerprint)
+        );
+    }
+}
@@ -0,0 +1,853 @@
+// https://tools.ietf.org/html/rfc8842
+// https://tools.ietf.org/html/rfc7983#section-7
+
+/*
+    the first byte of a packet on the selected pair (RFC 7983)
+      0..3      STUN, handled by the ICE agent
+      20..63    DTLS
+      128..191  RTP and RTCP
+
+    DtlsTransport                         DtlsBackend
+      start          -- profiles     ->   set_srtp_profiles, start
+      handle_packet  -- DTLS record  ->   handle_record
+      poll_transmit  <- DTLS record  --   poll_record
+      poll_event     <- Connected    --   handshake complete, the peer
+                                          certificate matches a signalled
+                                          fingerprint, and use_srtp agreed
+                                          on one of the profiles
+      send           -- app data     ->   send_data
+      poll_data      <- app data     --   poll_data     (SCTP)
+      export_keying_material          ->  export_keying_material (SRTP)
+
+    a=setup:active is the client, a=setup:passive the server.
+*/
+
+use crate::dtls::exporter::validate_exporter_parameters;
+use crate::dtls::fingerprint::{verify_fingerprints, CertificateFingerprint};
+use crate::dtls::use_srtp::DEFAULT_SRTP_PROFILES;
+use crate::dtls::{DtlsError, Result};
+use crate::ice::agent::IceAgent;
+use crate::sansio::StateMachine;
+use crate::srtp::dtls_srtp::{DtlsSrtpKeys, KeyingMaterialExporter};
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+
+/// Tells a DTLS record apart from STUN, RTP and RTCP (RFC 7983).
+pub fn is_dtls_packet(data: &[u8]) -> bool {
+    data.first().is_some_and(|v| (20..=63).contains(v))
+}
+
+/// The same role keys SRTP, so it is shared with the SRTP layer.
+pub use crate::srtp::dtls_srtp::DtlsRole;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum DtlsState {
+    New,
+    Connecting,
+    Connected,
+    Closed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum DtlsEvent {
+    StateChanged(DtlsState),
+}
+
+/// What the transport has ready as a StateMachine.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum DtlsOutput {
+    /// Records to send on the selected pair.
+    Transmit(Vec<u8>),
+    /// Application data received, for SCTP.
+    Data(Vec<u8>),
+    Event(DtlsEvent),
+    /// The keys of SRTP, once after Connected.
+    SrtpKeys(DtlsSrtpKeys),
+}
+
+// DTLSの実装を差し替えられるようにする．socketは持たず，recordを受け渡す．
+pub trait DtlsBackend {
+    /// The use_srtp profiles a client offers, or a server selects from, in
+    /// order of preference. Called before `start`.
+    fn set_srtp_profiles(&mut self, profiles: &[ProtectionProfile]);
+
+    /// Starts the handshake. A client sends the ClientHello, a server waits
+    /// for it.
+    fn start(&mut self, role: DtlsRole, now: Instant) -> Result<()>;
+
+    /// Handles a datagram of DTLS records from the peer.
+    fn handle_record(&mut self, data: &[u8], now: Instant) -> Result<()>;
+
+    /// The next datagram of records to send.
+    fn poll_record(&mut self) -> Option<Vec<u8>>;
+
+    /// When the flight has to be retransmitted.
+    fn poll_timeout(&self) -> Option<Instant>;
+
+    fn process(&mut self, now: Instant) -> Result<()>;
+
+    fn is_handshake_complete(&self) -> bool;
+
+    /// The DER certificate the peer sent in the handshake.
+    fn get_peer_certificate(&self) -> Option<Vec<u8>>;
+
+    /// The protection profile id use_srtp agreed on.
+    fn get_selected_srtp_profile(&self) -> Option<u16>;
+
+    /// RFC 5705 (or RFC 8446 Section 7.5) exporter, once the handshake is
+    /// complete. The parameters are already validated.
+    fn export_keying_material(
+        &self,
+        label: &str,
+        context: Option<&[u8]>,
+        length: usize,
+    ) -> Result<Vec<u8>>;
+
+    fn send_data(&mut self, data: &[u8]) -> Result<()>;
+
+    fn poll_data(&mut self) -> Option<Vec<u8>>;
+
+    /// Sends close_notify.
+    fn close(&mut self);
+}
+
+// ICEで選ばれたpair上でDTLSのhandshakeを行い，SCTPにdataを渡す．
+#[derive(Debug)]
+pub struct DtlsTransport<B: DtlsBackend> {
+    backend: B,
+    role: DtlsRole,
+    state: DtlsState,
+    remote_fingerprints: Vec<CertificateFingerprint>,
+    srtp_profiles: Vec<ProtectionProfile>,
+    srtp_profile: Option<ProtectionProfile>,
+    transmits: VecDeque<Vec<u8>>,
+    events: VecDeque<DtlsEvent>,
+    srtp_keys_exported: bool,
+}
+
+impl<B: DtlsBackend> DtlsTransport<B> {
+    pub fn new(backend: B, role: DtlsRole) -> Self {
+        DtlsTransport {
+            backend,
+            role,
+            state: DtlsState::New,
+            remote_fingerprints: Vec::new(),
+            srtp_profiles: DEFAULT_SRTP_PROFILES.to_vec(),
+            srtp_profile: None,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+            srtp_keys_exported: false,
+        }
+    }
+
+    pub fn get_role(&self) -> DtlsRole {
+        self.role
+    }
+
+    pub fn get_state(&self) -> DtlsState {
+        self.state
+    }
+
+    pub fn get_backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn get_backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    pub fn get_remote_fingerprints(&self) -> &[CertificateFingerprint] {
+        &self.remote_fingerprints
+    }
+
+    /// The `a=fingerprint` values of the remote description.
+    pub fn set_remote_fingerprints(&mut self, fingerprints: Vec<CertificateFingerprint>) {
+        self.remote_fingerprints = fingerprints;
+    }
+
+    pub fn get_srtp_profiles(&self) -> &[ProtectionProfile] {
+        &self.srtp_profiles
+    }
+
+    /// In order of preference, before `start`.
+    pub fn set_srtp_profiles(&mut self, profiles: Vec<ProtectionProfile>) {
+        self.srtp_profiles = profiles;
+    }
+
+    /// The profile agreed on, once connected.
+    pub fn get_srtp_profile(&self) -> Option<ProtectionProfile> {
+        self.srtp_profile
+    }
+
+    pub fn start(&mut self, now: Instant) -> Result<()> {
+        if self.state != DtlsState::New {
+            return Ok(());
+        }
+        self.set_state(DtlsState::Connecting);
+        self.backend.set_srtp_profiles(&self.srtp_profiles);
+        let started = self.backend.start(self.role, now);
+        self.handle_result(started)
+    }
+
+    /// Handles a packet received on the selected pair. Returns `false` when
+    /// it is not DTLS, to be handed to SRTP.
+    pub fn handle_packet(&mut self, data: &[u8], now: Instant) -> Result<bool> {
+        if !is_dtls_packet(data) {
+            return Ok(false);
+        }
+        if self.state != DtlsState::Connecting && self.state != DtlsState::Connected {
+            return Ok(true);
+        }
+        let handled = self.backend.handle_record(data, now);
+        self.handle_result(handled)?;
+        Ok(true)
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        match self.state {
+            DtlsState::Connecting | DtlsState::Connected => self.backend.poll_timeout(),
+            _ => None,
+        }
+    }
+
+    pub fn process(&mut self, now: Instant) -> Result<()> {
+        if self.state != DtlsState::Connecting && self.state != DtlsState::Connected {
+            return Ok(());
+        }
+        let processed = self.backend.process(now);
+        self.handle_result(processed)
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.collect_records();
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<DtlsEvent> {
+        self.events.pop_front()
+    }
+
+    /// Sends the pending records on the selected pair of `ice`. They are
+    /// kept while ICE has no selected pair.
+    pub fn flush(&mut self, ice: &mut IceAgent, now: Instant) {
+        while let Some(record) = self.poll_transmit() {
+            if ice.send(&record, now).is_err() {
+                self.transmits.push_front(record);
+                return;
+            }
+        }
+    }
+
+    /// Sends application data, once the handshake is complete.
+    pub fn send(&mut self, data: &[u8]) -> Result<()> {
+        if self.state != DtlsState::Connected {
+            return Err(DtlsError::NotConnected);
+        }
+        self.backend.send_data(data)
+    }
+
+    /// The application data received, for SCTP.
+    pub fn poll_data(&mut self) -> Option<Vec<u8>> {
+        self.backend.poll_data()
+    }
+
+    /// Keying material both sides derive from the handshake, e.g. with the
+    /// `EXTRACTOR-dtls_srtp` label for SRTP.
+    pub fn export_keying_material(
+        &self,
+        label: &str,
+        context: Option<&[u8]>,
+        length: usize,
+    ) -> Result<Vec<u8>> {
+        if self.state != DtlsState::Connected {
+            return Err(DtlsError::NotConnected);
+        }
+        validate_exporter_parameters(label, context, length)?;
+        self.backend.export_keying_material(label, context, length)
+    }
+
+    pub fn close(&mut self) {
+        if self.state == DtlsState::Connecting || self.state == DtlsState::Connected {
+            self.backend.close();
+            self.collect_records();
+        }
+        self.set_state(DtlsState::Closed);
+    }
+
+    fn handle_result(&mut self, result: Result<()>) -> Result<()> {
+        if let Err(e) = result {
+            self.fail();
+            return Err(e);
+        }
+        self.collect_records();
+        if self.state == DtlsState::Connecting && self.backend.is_handshake_complete() {
+            // the certificate is only trusted through the signalled fingerprint.
+            let verified = match self.backend.get_peer_certificate() {
+                Some(der) => verify_fingerprints(&self.remote_fingerprints, &der),
+                None => Err(DtlsError::FingerprintMismatch),
+            };
+            match verified.and_then(|_| self.get_selected_srtp_profile()) {
+                Ok(profile) => self.srtp_profile = Some(profile),
+                Err(e) => {
+                    self.fail();
+                    return Err(e);
+                }
+            }
+            self.set_state(DtlsState::Connected);
+        }
+        Ok(())
+    }
+
+    // WebRTC has no media without SRTP, so DTLS without use_srtp fails too.
+    fn get_selected_srtp_profile(&self) -> Result<ProtectionProfile> {
+        let id = self
+            .backend
+            .get_selected_srtp_profile()
+            .ok_or(DtlsError::NoSharedSrtpProfile)?;
+        ProtectionProfile::from_id(id)
+            .filter(|v| self.srtp_profiles.contains(v))
+            .ok_or(DtlsError::SrtpProfileMismatch)
+    }
+
+    fn fail(&mut self) {
+        self.backend.close();
+        self.transmits.clear();
+        while self.backend.poll_record().is_some() {}
+        self.set_state(DtlsState::Failed);
+    }
+
+    fn collect_records(&mut self) {
+        while let Some(record) = self.backend.poll_record() {
+            self.transmits.push_back(record);
+        }
+    }
+
+    fn set_state(&mut self, state: DtlsState) {
+        if self.state != state {
+            self.state = state;
+            self.events.push_back(DtlsEvent::StateChanged(state));
+        }
+    }
+}
+
+// SRTPの鍵を直接作れるようにする．
+impl<B: DtlsBackend> KeyingMaterialExporter for DtlsTransport<B> {
+    fn export_keying_material(
+        &self,
+        label: &str,
+        context: Option<&[u8]>,
+        length: usize,
+    ) -> Option<Vec<u8>> {
+        DtlsTransport::export_keying_material(self, label, context, length).ok()
+    }
+
+    fn get_selected_srtp_profile(&self) -> Option<u16> {
+        self.srtp_profile.map(|v| v.get_id())
+    }
+}
+
+// DTLSでないpacketは受けず，SRTPの鍵もoutputにする．
+impl<B: DtlsBackend> StateMachine for DtlsTransport<B> {
+    type Input = Vec<u8>;
+    type Output = DtlsOutput;
+    type Error = DtlsError;
+
+    fn handle_input(&mut self, input: Vec<u8>, now: Instant) -> Result<()> {
+        self.handle_packet(&input, now).map(|_| ())
+    }
+
+    fn poll_output(&mut self, now: Instant) -> Option<DtlsOutput> {
+        if DtlsTransport::poll_timeout(self).is_some_and(|v| v <= now) {
+            // a failure is told by the Failed state.
+            let _ = self.process(now);
+        }
+        if let Some(v) = self.poll_transmit() {
+            return Some(DtlsOutput::Transmit(v));
+        }
+        if let Some(v) = self.poll_data() {
+            return Some(DtlsOutput::Data(v));
+        }
+        if let Some(v) = self.poll_event() {
+            return Some(DtlsOutput::Event(v));
+        }
+        if self.state == DtlsState::Connected && !self.srtp_keys_exported {
+            self.srtp_keys_exported = true;
+            return DtlsSrtpKeys::export(self, self.role)
+                .ok()
+                .map(DtlsOutput::SrtpKeys);
+        }
+        None
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        DtlsTransport::poll_timeout(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::dtls::exporter::{export_dtls12, PrfHash};
+    use crate::dtls::fingerprint::HashFunction;
+    use crate::dtls::use_srtp::UseSrtp;
+    use crate::ice::agent::test::{exchange, is_connected, new_agent, run_until};
+    use crate::ice::agent::{IceConfig, IceRole};
+    use crate::sansio::drain_outputs;
+    use crate::srtp::context::ContextConfig;
+    use crate::srtp::dtls_srtp::{DtlsSrtpSession, DTLS_SRTP_EXPORTER_LABEL};
+    use sha2::{Digest, Sha256};
+
+    const HANDSHAKE: u8 = 22;
+    const APPLICATION_DATA: u8 = 23;
+    const ALERT: u8 = 21;
+
+    /// A handshake of one flight each way: the hello carries the
+    /// certificate and the use_srtp extension.
+    #[derive(Debug, Default)]
+    pub(crate) struct FakeBackend {
+        pub certificate: Vec<u8>,
+        pub role: Option<DtlsRole>,
+        pub srtp_profiles: Vec<ProtectionProfile>,
+        pub use_srtp: Option<UseSrtp>,
+        pub selected_srtp_profile: Option<u16>,
+        pub peer_certificate: Option<Vec<u8>>,
+        pub complete: bool,
+        pub closed: bool,
+        pub records: VecDeque<Vec<u8>>,
+        pub data: VecDeque<Vec<u8>>,
+    }
+
+    impl FakeBackend {
+        pub(crate) fn new(certificate: &[u8]) -> Self {
+            FakeBackend {
+                certificate: certificate.to_vec(),
+                ..FakeBackend::default()
+            }
+        }
+
+        fn hello(&self, use_srtp: &UseSrtp) -> Vec<u8> {
+            let mut record = vec![HANDSHAKE, self.certificate.len() as u8];
+            record.extend_from_slice(&self.certificate);
+            record.extend(use_srtp.encode());
+            record
+        }
+    }
+
+    impl DtlsBackend for FakeBackend {
+        fn set_srtp_profiles(&mut self, profiles: &[ProtectionProfile]) {
+            self.srtp_profiles = profiles.to_vec();
+        }
+
+        fn start(&mut self, role: DtlsRole, _now: Instant) -> Result<()> {
+            self.role = Some(role);
+            if role == DtlsRole::Client {
+                let offer = UseSrtp::offer(&self.srtp_profiles);
+                let hello = self.hello(&offer);
+                self.records.push_back(hello);
+                self.use_srtp = Some(offer);
+            }
+            Ok(())
+        }
+
+        fn handle_record(&mut self, data: &[u8], _now: Instant) -> Result<()> {
+            match data[0] {
+                HANDSHAKE if !self.complete => {
+                    let length = usize::from(data[1]);
+                    let use_srtp = UseSrtp::decode(&data[2 + length..])?;
+                    let profile = match &self.use_srtp {
+                        // the server answers the hello of the client.
+                        None => {
+                            let (profile, answer) = use_srtp.select(&self.srtp_profiles)?;
+                            let hello = self.hello(&answer);
+                            self.records.push_back(hello);
+                            profile
+                        }
+                        Some(offer) => offer.validate(&use_srtp)?,
+                    };
+                    self.selected_srtp_profile = Some(profile.get_id());
+                    self.peer_certificate = Some(data[2..2 + length].to_vec());
+                    self.complete = true;
+                    Ok(())
+                }
+                APPLICATION_DATA if self.complete => {
+                    self.data.push_back(data[1..].to_vec());
+                    Ok(())
+                }
+                ALERT => {
+                    self.closed = true;
+                    Ok(())
+                }
+                _ => Err(DtlsError::HandshakeFailed {
+                    reason: "unexpected record".to_string(),
+                }),
+            }
+        }
+
+        fn poll_record(&mut self) -> Option<Vec<u8>> {
+            self.records.pop_front()
+        }
+
+        fn poll_timeout(&self) -> Option<Instant> {
+            None
+        }
+
+        fn process(&mut self, _now: Instant) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_handshake_complete(&self) -> bool {
+            self.complete
+        }
+
+        fn get_peer_certificate(&self) -> Option<Vec<u8>> {
+            self.peer_certificate.clone()
+        }
+
+        fn get_selected_srtp_profile(&self) -> Option<u16> {
+            self.selected_srtp_profile
+        }
+
+        /// A master secret from both certificates, client first.
+        fn export_keying_material(
+            &self,
+            label: &str,
+            context: Option<&[u8]>,
+            length: usize,
+        ) -> Result<Vec<u8>> {
+            let peer = self
+                .peer_certificate
+                .as_ref()
+                .ok_or(DtlsError::NotConnected)?;
+            let mut certificates = [&self.certificate[..], &peer[..]];
+            if self.role == Some(DtlsRole::Server) {
+                certificates.reverse();
+            }
+            let master_secret = Sha256::digest(certificates.concat());
+            Ok(export_dtls12(
+                PrfHash::Sha256,
+                &master_secret,
+                &[1; 32],
+                &[2; 32],
+                label,
+                context,
+                length,
+            ))
+        }
+
+        fn send_data(&mut self, data: &[u8]) -> Result<()> {
+            let mut record = vec![APPLICATION_DATA];
+            record.extend_from_slice(data);
+            self.records.push_back(record);
+            Ok(())
+        }
+
+        fn poll_data(&mut self) -> Option<Vec<u8>> {
+            self.data.pop_front()
+        }
+
+        fn close(&mut self) {
+            if !self.closed {
+                self.closed = true;
+                self.records.push_back(vec![ALERT]);
+            }
+        }
+    }
+
+    pub(crate) fn new_transports(
+        now: Instant,
+    ) -> (DtlsTransport<FakeBackend>, DtlsTransport<FakeBackend>) {
+        let mut client = DtlsTransport::new(FakeBackend::new(b"client"), DtlsRole::Client);
+        let mut server = DtlsTransport::new(FakeBackend::new(b"server"), DtlsRole::Server);
+        client.set_remote_fingerprints(vec![CertificateFingerprint::from_certificate(
+            HashFunction::Sha256,
+            b"server",
+        )]);
+        server.set_remote_fingerprints(vec![CertificateFingerprint::from_certificate(
+            HashFunction::Sha256,
+            b"client",
+        )]);
+        client.start(now).unwrap();
+        server.start(now).unwrap();
+        (client, server)
+    }
+
+    fn deliver<A: DtlsBackend, B: DtlsBackend>(
+        a: &mut DtlsTransport<A>,
+        b: &mut DtlsTransport<B>,
+        now: Instant,
+    ) {
+        loop {
+            let mut delivered = false;
+            while let Some(v) = a.poll_transmit() {
+                assert!(b.handle_packet(&v, now).unwrap());
+                delivered = true;
+            }
+            while let Some(v) = b.poll_transmit() {
+                assert!(a.handle_packet(&v, now).unwrap());
+                delivered = true;
+            }
+            if !delivered {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn dtls_transport_test() {
+        assert!(is_dtls_packet(&[22, 254, 253]));
+        assert!(!is_dtls_packet(&[0, 1]));
+        assert!(!is_dtls_packet(&[128, 96]));
+        assert!(!is_dtls_packet(&[]));
+
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        assert_eq!(client.get_state(), DtlsState::Connecting);
+        assert_eq!(server.poll_transmit(), None);
+        assert_eq!(client.send(b"early"), Err(DtlsError::NotConnected));
+
+        deliver(&mut client, &mut server, now);
+        assert_eq!(client.get_state(), DtlsState::Connected);
+        assert_eq!(server.get_state(), DtlsState::Connected);
+        assert_eq!(
+            client.get_srtp_profile(),
+            Some(ProtectionProfile::AeadAes128Gcm)
+        );
+        assert_eq!(server.get_srtp_profile(), client.get_srtp_profile());
+        assert_eq!(
+            iter_events(&mut client),
+            vec![
+                DtlsEvent::StateChanged(DtlsState::Connecting),
+                DtlsEvent::StateChanged(DtlsState::Connected)
+            ]
+        );
+        assert!(!server.handle_packet(&[128, 96, 0, 1], now).unwrap());
+
+        client.send(b"sctp").unwrap();
+        deliver(&mut client, &mut server, now);
+        assert_eq!(server.poll_data(), Some(b"sctp".to_vec()));
+
+        client.close();
+        deliver(&mut client, &mut server, now);
+        assert_eq!(client.get_state(), DtlsState::Closed);
+        assert!(server.get_backend().closed);
+    }
+
+    #[test]
+    fn state_machine_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let mut outputs = [vec![], vec![]];
+        loop {
+            let mut delivered = false;
+            for output in drain_outputs(&mut client, now) {
+                match output {
+                    DtlsOutput::Transmit(v) => server.handle_input(v, now).unwrap(),
+                    v => outputs[0].push(v),
+                }
+                delivered = true;
+            }
+            for output in drain_outputs(&mut server, now) {
+                match output {
+                    DtlsOutput::Transmit(v) => client.handle_input(v, now).unwrap(),
+                    v => outputs[1].push(v),
+                }
+                delivered = true;
+            }
+            if !delivered {
+                break;
+            }
+        }
+        let connected = DtlsOutput::Event(DtlsEvent::StateChanged(DtlsState::Connected));
+        let keys: Vec<&DtlsSrtpKeys> = outputs
+            .iter()
+            .map(|v| match &v[..] {
+                [DtlsOutput::Event(_), event, DtlsOutput::SrtpKeys(keys)]
+                    if *event == connected =>
+                {
+                    keys
+                }
+                v => panic!("{:?}", v),
+            })
+            .collect();
+        assert_eq!(keys[0].local_master_key, keys[1].remote_master_key);
+        assert_eq!(keys[0].remote_master_salt, keys[1].local_master_salt);
+        assert_eq!(keys[0].profile, ProtectionProfile::AeadAes128Gcm);
+
+        client.send(b"sctp").unwrap();
+        let records = drain_outputs(&mut client, now);
+        for output in records {
+            if let DtlsOutput::Transmit(v) = output {
+                server.handle_input(v, now).unwrap();
+            }
+        }
+        assert_eq!(
+            drain_outputs(&mut server, now),
+            vec![DtlsOutput::Data(b"sctp".to_vec())]
+        );
+    }
+
+    fn iter_events<B: DtlsBackend>(transport: &mut DtlsTransport<B>) -> Vec<DtlsEvent> {
+        std::iter::from_fn(|| transport.poll_event()).collect()
+    }
+
+    #[test]
+    fn fingerprint_mismatch_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        client.set_remote_fingerprints(vec![CertificateFingerprint::from_certificate(
+            HashFunction::Sha256,
+            b"someone else",
+        )]);
+        let hello = client.poll_transmit().unwrap();
+        server.handle_packet(&hello, now).unwrap();
+        let answer = server.poll_transmit().unwrap();
+        assert_eq!(
+            client.handle_packet(&answer, now),
+            Err(DtlsError::FingerprintMismatch)
+        );
+        assert_eq!(client.get_state(), DtlsState::Failed);
+        assert_eq!(client.poll_transmit(), None);
+        assert_eq!(client.send(b"data"), Err(DtlsError::NotConnected));
+        // nothing is handled anymore.
+        assert!(client.handle_packet(&answer, now).unwrap());
+    }
+
+    #[test]
+    fn srtp_profile_test() {
+        let now = Instant::now();
+        let (mut client, _) = new_transports(now);
+        // the server only has the 80bit profile.
+        let mut server = DtlsTransport::new(FakeBackend::new(b"server"), DtlsRole::Server);
+        server.set_remote_fingerprints(vec![CertificateFingerprint::from_certificate(
+            HashFunction::Sha256,
+            b"client",
+        )]);
+        server.set_srtp_profiles(vec![ProtectionProfile::Aes128CmHmacSha1_80]);
+        server.start(now).unwrap();
+        deliver(&mut client, &mut server, now);
+        assert_eq!(
+            client.get_srtp_profile(),
+            Some(ProtectionProfile::Aes128CmHmacSha1_80)
+        );
+        assert_eq!(server.get_state(), DtlsState::Connected);
+
+        // nothing shared.
+        let (mut client, mut server) = new_transports(now);
+        server.get_backend_mut().srtp_profiles = vec![ProtectionProfile::Aes128CmHmacSha1_32];
+        let hello = client.poll_transmit().unwrap();
+        assert_eq!(
+            server.handle_packet(&hello, now),
+            Err(DtlsError::NoSharedSrtpProfile)
+        );
+        assert_eq!(server.get_state(), DtlsState::Failed);
+
+        // the backend agreed on a profile the transport does not allow.
+        let (mut client, mut server) = new_transports(now);
+        client.set_srtp_profiles(vec![ProtectionProfile::Aes128CmHmacSha1_80]);
+        let hello = client.poll_transmit().unwrap();
+        server.handle_packet(&hello, now).unwrap();
+        let answer = server.poll_transmit().unwrap();
+        assert_eq!(
+            client.handle_packet(&answer, now),
+            Err(DtlsError::SrtpProfileMismatch)
+        );
+        assert_eq!(client.get_srtp_profile(), None);
+    }
+
+    #[test]
+    fn export_keying_material_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        assert_eq!(
+            client.export_keying_material("EXPERIMENTAL", None, 16),
+            Err(DtlsError::NotConnected)
+        );
+        deliver(&mut client, &mut server, now);
+
+        let material = client
+            .export_keying_material("EXPERIMENTAL", None, 16)
+            .unwrap();
+        assert_eq!(material.len(), 16);
+        assert_eq!(
+            server.export_keying_material("EXPERIMENTAL", None, 16),
+            Ok(material.clone())
+        );
+        assert_ne!(
+            client.export_keying_material("EXPERIMENTAL", Some(b"ctx"), 16),
+            Ok(material)
+        );
+        assert_eq!(
+            client.export_keying_material("master secret", None, 16),
+            Err(DtlsError::InvalidExporterParameters)
+        );
+        assert_eq!(
+            KeyingMaterialExporter::get_selected_srtp_profile(&client),
+            Some(0x0007)
+        );
+
+        // SRTP keyed from the transports.
+        let config = ContextConfig::default();
+        let mut sender = DtlsSrtpSession::new(&client, client.get_role(), config).unwrap();
+        let mut receiver = DtlsSrtpSession::new(&server, server.get_role(), config).unwrap();
+        let packet = vec![0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0x04, 0xd2, 1, 2, 3];
+        let protected = sender.get_send_context().protect_rtp(&packet).unwrap();
+        assert_eq!(
+            receiver
+                .get_receive_context()
+                .unprotect_rtp(&protected)
+                .unwrap(),
+            packet
+        );
+        assert_eq!(
+            KeyingMaterialExporter::export_keying_material(
+                &client,
+                DTLS_SRTP_EXPORTER_LABEL,
+                None,
+                0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn dtls_over_ice_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        let (mut client, mut server) = new_transports(now);
+        // the hello waits for the selected pair.
+        client.flush(&mut a, now);
+        assert!(client.transmits.len() == 1);
+
+        exchange(&mut a, &mut b, now);
+        let now = run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        for _ in 0..2 {
+            client.flush(&mut a, now);
+            server.flush(&mut b, now);
+            crate::ice::agent::test::deliver(&mut a, &mut b, now);
+            while let Some(v) = b.poll_receive() {
+                server.handle_packet(&v, now).unwrap();
+            }
+            while let Some(v) = a.poll_receive() {
+                client.handle_packet(&v, now).unwrap();
+            }
+        }
+        assert_eq!(client.get_state(), DtlsState::Connected);
+        assert_eq!(server.get_state(), DtlsState::Connected);
+    }
+}
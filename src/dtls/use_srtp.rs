@@ -0,0 +1,185 @@
+// https://tools.ietf.org/html/rfc5764#section-4.1.1
+// https://tools.ietf.org/html/rfc7714#section-14.2
+
+/*
+    extension_type: use_srtp(14)
+
+    struct {
+        SRTPProtectionProfile profiles<2..2^16-1>;   uint8[2] each
+        opaque srtp_mki<0..255>;
+    } UseSRTPData;
+
+    ClientHello  all the profiles the client supports
+    ServerHello  exactly one of them, and the MKI of the client or none
+*/
+
+use crate::dtls::{DtlsError, Result};
+use crate::srtp::protection_profile::ProtectionProfile;
+
+pub const USE_SRTP_EXTENSION_TYPE: u16 = 14;
+
+/// WebRTC offers the AEAD profiles first and keeps AES128_CM_SHA1_80 for
+/// the peers without them (RFC 8827 Section 6.5).
+pub const DEFAULT_SRTP_PROFILES: [ProtectionProfile; 3] = [
+    ProtectionProfile::AeadAes128Gcm,
+    ProtectionProfile::AeadAes256Gcm,
+    ProtectionProfile::Aes128CmHmacSha1_80,
+];
+
+// use_srtpのextension_data．知らないprofileのidもそのまま持つ．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UseSrtp {
+    pub profiles: Vec<u16>,
+    pub mki: Vec<u8>,
+}
+
+impl UseSrtp {
+    /// The extension of a ClientHello, `profiles` in order of preference.
+    pub fn offer(profiles: &[ProtectionProfile]) -> Self {
+        UseSrtp {
+            profiles: profiles.iter().map(|v| v.get_id()).collect(),
+            mki: Vec::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(3 + self.profiles.len() * 2 + self.mki.len());
+        out.extend_from_slice(&((self.profiles.len() * 2) as u16).to_be_bytes());
+        for id in &self.profiles {
+            out.extend_from_slice(&id.to_be_bytes());
+        }
+        out.push(self.mki.len() as u8);
+        out.extend_from_slice(&self.mki);
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let invalid = DtlsError::InvalidUseSrtp;
+        if data.len() < 2 {
+            return Err(invalid);
+        }
+        let length = usize::from(u16::from_be_bytes([data[0], data[1]]));
+        if length == 0 || length % 2 != 0 || data.len() < 2 + length + 1 {
+            return Err(invalid);
+        }
+        let profiles = data[2..2 + length]
+            .chunks(2)
+            .map(|v| u16::from_be_bytes([v[0], v[1]]))
+            .collect();
+        let mki_length = usize::from(data[2 + length]);
+        let mki = &data[3 + length..];
+        if mki.len() != mki_length {
+            return Err(invalid);
+        }
+        Ok(UseSrtp {
+            profiles,
+            mki: mki.to_vec(),
+        })
+    }
+
+    /// The server side: the extension of the ServerHello with the first of
+    /// `supported` the client offered. The MKI is echoed.
+    pub fn select(&self, supported: &[ProtectionProfile]) -> Result<(ProtectionProfile, UseSrtp)> {
+        let profile = supported
+            .iter()
+            .find(|v| self.profiles.contains(&v.get_id()))
+            .copied()
+            .ok_or(DtlsError::NoSharedSrtpProfile)?;
+        let answer = UseSrtp {
+            profiles: vec![profile.get_id()],
+            mki: self.mki.clone(),
+        };
+        Ok((profile, answer))
+    }
+
+    /// The client side: checks the answer of the server to this offer.
+    pub fn validate(&self, answer: &UseSrtp) -> Result<ProtectionProfile> {
+        let id = match answer.profiles[..] {
+            [id] => id,
+            _ => return Err(DtlsError::InvalidUseSrtp),
+        };
+        // a nonzero MKI different from the offer aborts the handshake.
+        if !answer.mki.is_empty() && answer.mki != self.mki {
+            return Err(DtlsError::InvalidUseSrtp);
+        }
+        if !self.profiles.contains(&id) {
+            return Err(DtlsError::SrtpProfileMismatch);
+        }
+        ProtectionProfile::from_id(id).ok_or(DtlsError::SrtpProfileMismatch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn use_srtp_codec_test() {
+        let offer = UseSrtp::offer(&DEFAULT_SRTP_PROFILES);
+        let encoded = offer.encode();
+        assert_eq!(encoded, vec![0, 6, 0, 7, 0, 8, 0, 1, 0]);
+        assert_eq!(UseSrtp::decode(&encoded), Ok(offer));
+
+        let with_mki = UseSrtp {
+            profiles: vec![0x0001, 0x0002],
+            mki: vec![0xaa, 0xbb],
+        };
+        assert_eq!(UseSrtp::decode(&with_mki.encode()), Ok(with_mki));
+
+        for broken in &[
+            &[0, 0, 0][..],
+            &[0, 3, 0, 1, 0, 0],
+            &[0, 2, 0, 1],
+            &[0, 2, 0, 1, 2, 0xaa],
+            &[0, 2, 0, 1, 0, 0xaa],
+        ] {
+            assert_eq!(UseSrtp::decode(broken), Err(DtlsError::InvalidUseSrtp));
+        }
+    }
+
+    #[test]
+    fn use_srtp_negotiation_test() {
+        // a client offering an unknown profile first.
+        let offer = UseSrtp {
+            profiles: vec![0x0100, 0x0001, 0x0008],
+            mki: vec![1],
+        };
+        let (profile, answer) = offer.select(&DEFAULT_SRTP_PROFILES).unwrap();
+        assert_eq!(profile, ProtectionProfile::AeadAes256Gcm);
+        assert_eq!(answer.profiles, vec![0x0008]);
+        assert_eq!(answer.mki, vec![1]);
+        assert_eq!(offer.validate(&answer), Ok(profile));
+
+        let (profile, _) = offer
+            .select(&[ProtectionProfile::Aes128CmHmacSha1_80])
+            .unwrap();
+        assert_eq!(profile, ProtectionProfile::Aes128CmHmacSha1_80);
+        assert_eq!(
+            offer.select(&[ProtectionProfile::AeadAes128Gcm]),
+            Err(DtlsError::NoSharedSrtpProfile)
+        );
+
+        let answer = |profiles: Vec<u16>, mki: Vec<u8>| UseSrtp { profiles, mki };
+        assert_eq!(
+            offer.validate(&answer(vec![0x0001], vec![])),
+            Ok(ProtectionProfile::Aes128CmHmacSha1_80)
+        );
+        assert_eq!(
+            offer.validate(&answer(vec![0x0007], vec![])),
+            Err(DtlsError::SrtpProfileMismatch)
+        );
+        // offered, but not a profile this side knows.
+        assert_eq!(
+            offer.validate(&answer(vec![0x0100], vec![])),
+            Err(DtlsError::SrtpProfileMismatch)
+        );
+        assert_eq!(
+            offer.validate(&answer(vec![0x0001, 0x0008], vec![])),
+            Err(DtlsError::InvalidUseSrtp)
+        );
+        assert_eq!(
+            offer.validate(&answer(vec![0x0001], vec![2])),
+            Err(DtlsError::InvalidUseSrtp)
+        );
+    }
+}
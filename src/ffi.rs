@@ -0,0 +1,79 @@
+/*
+    a C ABI of the packet codecs and of an SRTP session, for the media
+    servers of C and C++ taking the crate in a part at a time. the
+    declarations are the ones of include/webrtc.h, built as a library of
+    C with the ffi feature:
+
+      cargo rustc --release --features ffi --lib --crate-type staticlib
+
+      packet    webrtc_rtp_packet_*      an RTP packet, new or parsed
+                webrtc_rtcp_compound_*   a compound RTCP packet, parsed or
+                                         built a packet at a time
+      session   webrtc_session_*         SRTP keyed with the keying
+                                         material of DTLS and the RTCP
+                                         interval, its packets given to a
+                                         callback
+
+    the objects are made by _new or _parse and freed by _free, NULL when
+    they fail. a pointer and a length are of a buffer of the caller,
+    borrowed for the call, NULL only with length 0. the functions of an
+    object are not called from two threads at once.
+
+    a function returns WEBRTC_OK or one of the errors below, a negative
+    length for the ones returning the length written, NULL for the ones
+    returning a pointer. a NULL object is WEBRTC_ERROR_INVALID_ARGUMENT,
+    and a panic does not unwind into C, it is WEBRTC_ERROR_PANIC.
+*/
+
+// the safety of every function is the one above.
+#![allow(clippy::missing_safety_doc)]
+
+pub mod packet;
+pub mod session;
+
+use crate::octets::Octets;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+pub const WEBRTC_OK: i32 = 0;
+/// A NULL pointer, or an index out of range.
+pub const WEBRTC_ERROR_INVALID_ARGUMENT: i32 = -1;
+pub const WEBRTC_ERROR_INVALID_PACKET: i32 = -2;
+pub const WEBRTC_ERROR_BUFFER_TOO_SHORT: i32 = -3;
+/// SRTP protection or authentication failed.
+pub const WEBRTC_ERROR_SRTP: i32 = -4;
+/// A bug of the crate, the object may be left in any state.
+pub const WEBRTC_ERROR_PANIC: i32 = -5;
+
+/// The result of `f`, `error` when it panics.
+pub(crate) fn guard<T, F: FnOnce() -> T>(error: T, f: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(error)
+}
+
+/// The buffer of `data` and `length`, None when NULL with a length.
+pub(crate) unsafe fn as_slice<'a>(data: *const u8, length: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        return if length == 0 { Some(&[]) } else { None };
+    }
+    Some(slice::from_raw_parts(data, length))
+}
+
+/// Writes `length` bytes of `f` to the buffer of `out` and `capacity`, and
+/// returns the length or an error.
+pub(crate) unsafe fn write_to<E, F>(out: *mut u8, capacity: usize, length: usize, f: F) -> isize
+where
+    F: FnOnce(&mut Octets) -> Result<(), E>,
+{
+    if out.is_null() {
+        return WEBRTC_ERROR_INVALID_ARGUMENT as isize;
+    }
+    if capacity < length {
+        return WEBRTC_ERROR_BUFFER_TOO_SHORT as isize;
+    }
+    let mut octets = Octets::with_slice(slice::from_raw_parts_mut(out, capacity));
+    match f(&mut octets) {
+        Ok(()) => octets.off() as isize,
+        Err(_) => WEBRTC_ERROR_INVALID_PACKET as isize,
+    }
+}
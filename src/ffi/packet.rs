@@ -0,0 +1,593 @@
+/*
+    webrtc_rtp_packet     an RtpPacket, its header by the getters
+    webrtc_rtcp_compound  the bytes of a compound RTCP packet and where
+                          each packet of it is. a parsed one keeps a copy
+                          of what it was given, a new one the packets
+                          _add_ wrote
+*/
+
+use crate::ffi::{
+    as_slice, guard, write_to, WEBRTC_ERROR_INVALID_ARGUMENT, WEBRTC_ERROR_INVALID_PACKET,
+    WEBRTC_ERROR_PANIC, WEBRTC_OK,
+};
+use crate::octets::Octets;
+use crate::rtcp::good_bye::RtcpGoodByePacket;
+use crate::rtcp::packet::{parse_ref, RtcpPacket, RtcpPacketRef, RtcpPacketType};
+use crate::rtcp::payload_specific_feedback::RtcpPayloadSpecificFeedbackPacket;
+use crate::rtcp::receiver_report::RtcpReceiverReportPacket;
+use crate::rtcp::rtp_feedback::RtcpRtpFeedbackPacket;
+use crate::rtp::packet::{RtpHeader, RtpPacket, RtpPacketRef};
+
+use std::ops::Range;
+use std::ptr;
+
+const PLI_FORMAT: u8 = 1;
+const NACK_FORMAT: u8 = 1;
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_new(
+    payload_type: u8,
+    sequence_number: u16,
+    timestamp: u32,
+    ssrc: u32,
+    marker: bool,
+    payload: *const u8,
+    payload_length: usize,
+) -> *mut RtpPacket {
+    guard(ptr::null_mut(), || {
+        let payload = match as_slice(payload, payload_length) {
+            Some(v) => v,
+            None => return ptr::null_mut(),
+        };
+        let mut header = RtpHeader::new(payload_type, sequence_number, timestamp, ssrc);
+        header.set_marker(marker);
+        Box::into_raw(Box::new(RtpPacket::new(header, payload.to_vec())))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_parse(data: *const u8, length: usize) -> *mut RtpPacket {
+    guard(ptr::null_mut(), || {
+        match as_slice(data, length).and_then(|v| RtpPacketRef::from_slice(v).ok()) {
+            Some(v) => Box::into_raw(Box::new(v.to_owned())),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_free(packet: *mut RtpPacket) {
+    guard((), || {
+        if !packet.is_null() {
+            drop(Box::from_raw(packet));
+        }
+    })
+}
+
+/// The header field of `f`, or an error.
+unsafe fn get_field<F: FnOnce(&RtpHeader) -> i32>(packet: *const RtpPacket, f: F) -> i32 {
+    guard(WEBRTC_ERROR_PANIC, || match packet.as_ref() {
+        Some(v) => f(v.get_header()),
+        None => WEBRTC_ERROR_INVALID_ARGUMENT,
+    })
+}
+
+/// Writes the header field of `f` to `out`.
+unsafe fn write_field<F: FnOnce(&RtpHeader) -> u32>(
+    packet: *const RtpPacket,
+    out: *mut u32,
+    f: F,
+) -> i32 {
+    get_field(packet, |header| match out.as_mut() {
+        Some(out) => {
+            *out = f(header);
+            WEBRTC_OK
+        }
+        None => WEBRTC_ERROR_INVALID_ARGUMENT,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_payload_type(packet: *const RtpPacket) -> i32 {
+    get_field(packet, |v| i32::from(v.get_payload_type()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_sequence_number(packet: *const RtpPacket) -> i32 {
+    get_field(packet, |v| i32::from(v.get_sequence_number()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_timestamp(
+    packet: *const RtpPacket,
+    timestamp: *mut u32,
+) -> i32 {
+    write_field(packet, timestamp, |v| v.get_timestamp())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_ssrc(
+    packet: *const RtpPacket,
+    ssrc: *mut u32,
+) -> i32 {
+    write_field(packet, ssrc, |v| v.get_ssrc())
+}
+
+/// 1 with the marker, 0 without.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_marker(packet: *const RtpPacket) -> i32 {
+    get_field(packet, |v| i32::from(v.get_marker()))
+}
+
+/// The payload, valid until the packet is freed.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_payload(
+    packet: *const RtpPacket,
+    length: *mut usize,
+) -> *const u8 {
+    guard(ptr::null(), || {
+        let payload = match packet.as_ref() {
+            Some(v) => v.get_payload(),
+            None => return ptr::null(),
+        };
+        if !length.is_null() {
+            *length = payload.len();
+        }
+        payload.as_ptr()
+    })
+}
+
+/// Sets the header extension `id` of one-byte or two-byte elements.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_set_extension(
+    packet: *mut RtpPacket,
+    id: u8,
+    value: *const u8,
+    length: usize,
+) -> i32 {
+    guard(WEBRTC_ERROR_PANIC, || {
+        match (packet.as_mut(), as_slice(value, length)) {
+            (Some(packet), Some(v)) => match packet.get_header_mut().set_extension(id, v) {
+                Ok(()) => WEBRTC_OK,
+                Err(_) => WEBRTC_ERROR_INVALID_ARGUMENT,
+            },
+            _ => WEBRTC_ERROR_INVALID_ARGUMENT,
+        }
+    })
+}
+
+/// The bytes webrtc_rtp_packet_serialize writes.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_get_length(packet: *const RtpPacket) -> isize {
+    guard(WEBRTC_ERROR_PANIC as isize, || match packet.as_ref() {
+        Some(v) => v.serialized_len() as isize,
+        None => WEBRTC_ERROR_INVALID_ARGUMENT as isize,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtp_packet_serialize(
+    packet: *const RtpPacket,
+    out: *mut u8,
+    capacity: usize,
+) -> isize {
+    guard(WEBRTC_ERROR_PANIC as isize, || {
+        let packet = match packet.as_ref() {
+            Some(v) => v,
+            None => return WEBRTC_ERROR_INVALID_ARGUMENT as isize,
+        };
+        write_to(out, capacity, packet.serialized_len(), |out| {
+            packet.to_bytes(out)
+        })
+    })
+}
+
+// compound packetのbytesとその中の各packetの範囲．
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RtcpCompound {
+    data: Vec<u8>,
+    packets: Vec<Range<usize>>,
+}
+
+impl RtcpCompound {
+    fn get(&self, index: usize) -> Option<RtcpPacketRef<'_>> {
+        let range = self.packets.get(index)?.clone();
+        RtcpPacketRef::from_slice(&self.data[range]).ok()
+    }
+
+    fn add(&mut self, packet: RtcpPacketType) -> i32 {
+        let packet = RtcpPacket::new(packet);
+        let start = self.data.len();
+        let mut buf = Vec::with_capacity(packet.serialized_len());
+        if packet.to_bytes(&mut Octets::with_vec(&mut buf)).is_err() {
+            return WEBRTC_ERROR_INVALID_PACKET;
+        }
+        self.data.extend_from_slice(&buf);
+        self.packets.push(start..self.data.len());
+        WEBRTC_OK
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn webrtc_rtcp_compound_new() -> *mut RtcpCompound {
+    guard(ptr::null_mut(), || Box::into_raw(Box::default()))
+}
+
+/// NULL when a packet of it is broken.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_parse(
+    data: *const u8,
+    length: usize,
+) -> *mut RtcpCompound {
+    guard(ptr::null_mut(), || {
+        let data = match as_slice(data, length) {
+            Some(v) => v,
+            None => return ptr::null_mut(),
+        };
+        let mut compound = RtcpCompound {
+            data: data.to_vec(),
+            packets: vec![],
+        };
+        let mut start = 0;
+        for packet in parse_ref(data) {
+            match packet {
+                Ok(v) => {
+                    compound.packets.push(start..start + v.get_length());
+                    start += v.get_length();
+                }
+                Err(_) => return ptr::null_mut(),
+            }
+        }
+        Box::into_raw(Box::new(compound))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_free(compound: *mut RtcpCompound) {
+    guard((), || {
+        if !compound.is_null() {
+            drop(Box::from_raw(compound));
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_get_count(compound: *const RtcpCompound) -> isize {
+    guard(WEBRTC_ERROR_PANIC as isize, || match compound.as_ref() {
+        Some(v) => v.packets.len() as isize,
+        None => WEBRTC_ERROR_INVALID_ARGUMENT as isize,
+    })
+}
+
+/// 200 for a sender report, 201 for a receiver report and so on.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_get_packet_type(
+    compound: *const RtcpCompound,
+    index: usize,
+) -> i32 {
+    guard(WEBRTC_ERROR_PANIC, || {
+        match compound.as_ref().and_then(|v| v.get(index)) {
+            Some(v) => i32::from(v.get_packet_type()),
+            None => WEBRTC_ERROR_INVALID_ARGUMENT,
+        }
+    })
+}
+
+/// The SSRC of the sender of the packet, the first word of its payload.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_get_ssrc(
+    compound: *const RtcpCompound,
+    index: usize,
+    ssrc: *mut u32,
+) -> i32 {
+    guard(WEBRTC_ERROR_PANIC, || {
+        match compound.as_ref().and_then(|v| v.get(index)) {
+            Some(v) if !ssrc.is_null() => match v.get_ssrc() {
+                Some(v) => {
+                    *ssrc = v;
+                    WEBRTC_OK
+                }
+                None => WEBRTC_ERROR_INVALID_PACKET,
+            },
+            _ => WEBRTC_ERROR_INVALID_ARGUMENT,
+        }
+    })
+}
+
+/// The bytes of the packet `index`, NULL when out of range.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_get_packet(
+    compound: *const RtcpCompound,
+    index: usize,
+    length: *mut usize,
+) -> *const u8 {
+    guard(ptr::null(), || {
+        let compound = match compound.as_ref() {
+            Some(v) => v,
+            None => return ptr::null(),
+        };
+        match compound.packets.get(index) {
+            Some(v) => {
+                if !length.is_null() {
+                    *length = v.len();
+                }
+                compound.data[v.clone()].as_ptr()
+            }
+            None => ptr::null(),
+        }
+    })
+}
+
+/// The bytes of the compound packet, valid until the next _add_ or free.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_get_data(
+    compound: *const RtcpCompound,
+    length: *mut usize,
+) -> *const u8 {
+    guard(ptr::null(), || {
+        let compound = match compound.as_ref() {
+            Some(v) => v,
+            None => return ptr::null(),
+        };
+        if !length.is_null() {
+            *length = compound.data.len();
+        }
+        compound.data.as_ptr()
+    })
+}
+
+/// Adds the packet of `f` to `compound`.
+unsafe fn add<F: FnOnce() -> Option<RtcpPacketType>>(compound: *mut RtcpCompound, f: F) -> i32 {
+    guard(WEBRTC_ERROR_PANIC, || match (compound.as_mut(), f()) {
+        (Some(compound), Some(packet)) => compound.add(packet),
+        _ => WEBRTC_ERROR_INVALID_ARGUMENT,
+    })
+}
+
+/// A receiver report without report blocks.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_add_receiver_report(
+    compound: *mut RtcpCompound,
+    ssrc: u32,
+) -> i32 {
+    add(compound, || {
+        let report = RtcpReceiverReportPacket::new(ssrc, vec![]);
+        Some(RtcpPacketType::ReceiverReport(report))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_add_bye(
+    compound: *mut RtcpCompound,
+    ssrc: u32,
+) -> i32 {
+    add(compound, || {
+        Some(RtcpPacketType::Goodbye(RtcpGoodByePacket(vec![ssrc])))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_add_pli(
+    compound: *mut RtcpCompound,
+    ssrc: u32,
+    media_ssrc: u32,
+) -> i32 {
+    add(compound, || {
+        let pli = RtcpPayloadSpecificFeedbackPacket::new(PLI_FORMAT, ssrc, media_ssrc, vec![]);
+        Some(RtcpPacketType::PayloadSpecificFeedback(pli))
+    })
+}
+
+/// A generic NACK of the `count` sequence numbers of `lost`.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_rtcp_compound_add_nack(
+    compound: *mut RtcpCompound,
+    ssrc: u32,
+    media_ssrc: u32,
+    lost: *const u16,
+    count: usize,
+) -> i32 {
+    add(compound, || {
+        if lost.is_null() {
+            return None;
+        }
+        let lost = std::slice::from_raw_parts(lost, count).to_vec();
+        let nack = RtcpRtpFeedbackPacket::new(NACK_FORMAT, ssrc, media_ssrc, Some(lost));
+        Some(RtcpPacketType::RTPFeedback(nack))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ffi::WEBRTC_ERROR_BUFFER_TOO_SHORT;
+    use std::slice;
+
+    #[test]
+    fn rtp_packet_test() {
+        unsafe {
+            let payload = [1, 2, 3];
+            let packet = webrtc_rtp_packet_new(96, 1000, 3000, 0x1234, true, payload.as_ptr(), 3);
+            assert_eq!(
+                webrtc_rtp_packet_set_extension(packet, 1, [7].as_ptr(), 1),
+                WEBRTC_OK
+            );
+            assert_eq!(
+                webrtc_rtp_packet_set_extension(packet, 0, ptr::null(), 0),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtp_packet_set_extension(packet, 2, [0; 256].as_ptr(), 256),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            let length = webrtc_rtp_packet_get_length(packet) as usize;
+            let mut buf = vec![0; length];
+            assert_eq!(
+                webrtc_rtp_packet_serialize(packet, buf.as_mut_ptr(), length - 1),
+                WEBRTC_ERROR_BUFFER_TOO_SHORT as isize
+            );
+            assert_eq!(
+                webrtc_rtp_packet_serialize(packet, buf.as_mut_ptr(), length),
+                length as isize
+            );
+            webrtc_rtp_packet_free(packet);
+
+            let packet = webrtc_rtp_packet_parse(buf.as_ptr(), buf.len());
+            assert_eq!(webrtc_rtp_packet_get_payload_type(packet), 96);
+            assert_eq!(webrtc_rtp_packet_get_sequence_number(packet), 1000);
+            let (mut timestamp, mut ssrc) = (0, 0);
+            assert_eq!(
+                webrtc_rtp_packet_get_timestamp(packet, &mut timestamp),
+                WEBRTC_OK
+            );
+            assert_eq!(timestamp, 3000);
+            assert_eq!(webrtc_rtp_packet_get_ssrc(packet, &mut ssrc), WEBRTC_OK);
+            assert_eq!(ssrc, 0x1234);
+            assert_eq!(webrtc_rtp_packet_get_marker(packet), 1);
+            let mut length = 0;
+            let data = webrtc_rtp_packet_get_payload(packet, &mut length);
+            assert_eq!(slice::from_raw_parts(data, length), &payload);
+            assert_eq!((*packet).get_header().get_extension(1), Some(vec![7]));
+            webrtc_rtp_packet_free(packet);
+
+            assert!(webrtc_rtp_packet_parse(buf.as_ptr(), 4).is_null());
+            assert!(webrtc_rtp_packet_parse(ptr::null(), 12).is_null());
+        }
+    }
+
+    #[test]
+    fn rtcp_compound_test() {
+        unsafe {
+            let compound = webrtc_rtcp_compound_new();
+            assert_eq!(
+                webrtc_rtcp_compound_add_receiver_report(compound, 1),
+                WEBRTC_OK
+            );
+            assert_eq!(webrtc_rtcp_compound_add_pli(compound, 1, 2), WEBRTC_OK);
+            let lost = [10, 12];
+            assert_eq!(
+                webrtc_rtcp_compound_add_nack(compound, 1, 2, lost.as_ptr(), 2),
+                WEBRTC_OK
+            );
+            assert_eq!(webrtc_rtcp_compound_add_bye(compound, 1), WEBRTC_OK);
+            let mut length = 0;
+            let data = webrtc_rtcp_compound_get_data(compound, &mut length);
+            let data = slice::from_raw_parts(data, length).to_vec();
+            webrtc_rtcp_compound_free(compound);
+
+            let compound = webrtc_rtcp_compound_parse(data.as_ptr(), data.len());
+            assert_eq!(webrtc_rtcp_compound_get_count(compound), 4);
+            let types: Vec<i32> = (0..4)
+                .map(|i| webrtc_rtcp_compound_get_packet_type(compound, i))
+                .collect();
+            assert_eq!(types, vec![201, 206, 205, 203]);
+            assert_eq!(
+                webrtc_rtcp_compound_get_packet_type(compound, 4),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            let mut ssrc = 0;
+            assert_eq!(
+                webrtc_rtcp_compound_get_ssrc(compound, 3, &mut ssrc),
+                WEBRTC_OK
+            );
+            assert_eq!(ssrc, 1);
+
+            let mut length = 0;
+            let packet = webrtc_rtcp_compound_get_packet(compound, 0, &mut length);
+            assert_eq!(slice::from_raw_parts(packet, length), &data[..length]);
+            assert!(webrtc_rtcp_compound_get_packet(compound, 4, &mut length).is_null());
+            webrtc_rtcp_compound_free(compound);
+
+            assert!(webrtc_rtcp_compound_parse(data.as_ptr(), data.len() - 1).is_null());
+        }
+    }
+
+    #[test]
+    fn null_test() {
+        unsafe {
+            let mut value = 0;
+            let mut length = 0;
+            let packet: *mut RtpPacket = ptr::null_mut();
+            assert_eq!(
+                webrtc_rtp_packet_get_payload_type(packet),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtp_packet_get_sequence_number(packet),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtp_packet_get_timestamp(packet, &mut value),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtp_packet_get_ssrc(packet, &mut value),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtp_packet_get_marker(packet),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert!(webrtc_rtp_packet_get_payload(packet, &mut length).is_null());
+            assert_eq!(
+                webrtc_rtp_packet_set_extension(packet, 1, [7].as_ptr(), 1),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtp_packet_get_length(packet),
+                WEBRTC_ERROR_INVALID_ARGUMENT as isize
+            );
+            assert_eq!(
+                webrtc_rtp_packet_serialize(packet, [0; 4].as_mut_ptr(), 4),
+                WEBRTC_ERROR_INVALID_ARGUMENT as isize
+            );
+
+            let packet = webrtc_rtp_packet_new(96, 1, 2, 3, false, ptr::null(), 0);
+            assert_eq!(
+                webrtc_rtp_packet_get_ssrc(packet, ptr::null_mut()),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            webrtc_rtp_packet_free(packet);
+
+            let compound: *mut RtcpCompound = ptr::null_mut();
+            assert_eq!(
+                webrtc_rtcp_compound_get_count(compound),
+                WEBRTC_ERROR_INVALID_ARGUMENT as isize
+            );
+            assert_eq!(
+                webrtc_rtcp_compound_get_packet_type(compound, 0),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtcp_compound_get_ssrc(compound, 0, &mut value),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert!(webrtc_rtcp_compound_get_packet(compound, 0, &mut length).is_null());
+            assert!(webrtc_rtcp_compound_get_data(compound, &mut length).is_null());
+            assert_eq!(
+                webrtc_rtcp_compound_add_receiver_report(compound, 1),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtcp_compound_add_bye(compound, 1),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtcp_compound_add_pli(compound, 1, 2),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_rtcp_compound_add_nack(compound, 1, 2, [10].as_ptr(), 1),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            webrtc_rtp_packet_free(ptr::null_mut());
+            webrtc_rtcp_compound_free(compound);
+        }
+
+        // a panic is an error, not an unwind into C.
+        assert_eq!(
+            guard(WEBRTC_ERROR_PANIC, || panic!("bug")),
+            WEBRTC_ERROR_PANIC
+        );
+    }
+}
@@ -0,0 +1,458 @@
+// https://tools.ietf.org/html/rfc5764#section-4.2
+// https://tools.ietf.org/html/rfc5761#section-4
+
+/*
+    webrtc_session, a DtlsSrtpSession and an RtcpScheduler driven by the
+    caller: its DTLS stack gives the keying material, its sockets the
+    packets and its event loop the time, milliseconds of a clock of its own.
+
+      send_rtp, send_rtcp   protected, then WEBRTC_OUTPUT_TRANSMIT
+      receive               unprotected, then WEBRTC_OUTPUT_RTP or
+                            WEBRTC_OUTPUT_RTCP by the second byte
+      process               WEBRTC_OUTPUT_SENDER_REPORT or
+                            WEBRTC_OUTPUT_RECEIVER_REPORT when a compound
+                            packet is due, for send_rtcp
+      poll_timeout          when process is due, -1 for never
+
+    the outputs are given to the callback during the call.
+*/
+
+use crate::ffi::{
+    as_slice, guard, WEBRTC_ERROR_INVALID_ARGUMENT, WEBRTC_ERROR_INVALID_PACKET,
+    WEBRTC_ERROR_PANIC, WEBRTC_ERROR_SRTP, WEBRTC_OK,
+};
+use crate::rtcp::packet::parse_ref;
+use crate::rtcp::scheduler::{RtcpInput, RtcpOutput, RtcpScheduler, RtcpSchedulerConfig};
+use crate::rtp::packet::RtpPacketRef;
+use crate::sansio::StateMachine;
+use crate::srtp::context::ContextConfig;
+use crate::srtp::dtls_srtp::{DtlsRole, DtlsSrtpKeys, DtlsSrtpSession};
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::time::{Duration, Instant};
+
+use std::os::raw::c_void;
+use std::ptr;
+
+/// A protected packet to send.
+pub const WEBRTC_OUTPUT_TRANSMIT: u32 = 0;
+/// An RTP packet received, unprotected.
+pub const WEBRTC_OUTPUT_RTP: u32 = 1;
+pub const WEBRTC_OUTPUT_RTCP: u32 = 2;
+/// A compound packet with a sender report is due, without data.
+pub const WEBRTC_OUTPUT_SENDER_REPORT: u32 = 3;
+pub const WEBRTC_OUTPUT_RECEIVER_REPORT: u32 = 4;
+
+// RTCPの間隔はIPv4とUDPのheaderを含めた大きさで決まる．
+const UDP_IP_OVERHEAD: usize = 28;
+const RTCP_BYE: u8 = 203;
+
+/// The data is valid during the call only.
+pub type OutputCallback =
+    extern "C" fn(user_data: *mut c_void, kind: u32, data: *const u8, length: usize);
+
+pub struct Session {
+    srtp: DtlsSrtpSession,
+    scheduler: RtcpScheduler,
+    // now_msの原点．
+    origin: Instant,
+    origin_ms: u64,
+    output: Option<OutputCallback>,
+    user_data: *mut c_void,
+}
+
+impl Session {
+    fn get_instant(&self, now_ms: u64) -> Instant {
+        self.origin + Duration::from_millis(now_ms.saturating_sub(self.origin_ms))
+    }
+
+    fn get_millis(&self, instant: Instant) -> u64 {
+        let elapsed = instant.saturating_duration_since(self.origin);
+        self.origin_ms + elapsed.as_nanos().div_ceil(1_000_000) as u64
+    }
+
+    fn output(&self, kind: u32, data: &[u8]) {
+        if let Some(output) = self.output {
+            let pointer = if data.is_empty() {
+                ptr::null()
+            } else {
+                data.as_ptr()
+            };
+            output(self.user_data, kind, pointer, data.len());
+        }
+    }
+
+    fn receive_rtcp(&mut self, packet: &[u8], now: Instant) -> i32 {
+        let packet = match self.srtp.get_receive_context().unprotect_rtcp(packet) {
+            Ok(v) => v,
+            Err(_) => return WEBRTC_ERROR_SRTP,
+        };
+        for v in parse_ref(&packet) {
+            let v = match v {
+                Ok(v) => v,
+                Err(_) => return WEBRTC_ERROR_INVALID_PACKET,
+            };
+            let ssrc = match v.get_ssrc() {
+                Some(v) => v,
+                None => continue,
+            };
+            let input = if v.get_packet_type() == RTCP_BYE {
+                RtcpInput::Bye { ssrc }
+            } else {
+                RtcpInput::RtcpReceived {
+                    ssrc,
+                    size: v.get_length() + UDP_IP_OVERHEAD,
+                }
+            };
+            let _ = self.scheduler.handle_input(input, now);
+        }
+        self.output(WEBRTC_OUTPUT_RTCP, &packet);
+        WEBRTC_OK
+    }
+
+    fn receive_rtp(&mut self, packet: &[u8], now: Instant) -> i32 {
+        let packet = match self.srtp.get_receive_context().unprotect_rtp(packet) {
+            Ok(v) => v,
+            Err(_) => return WEBRTC_ERROR_SRTP,
+        };
+        let ssrc = match RtpPacketRef::from_slice(&packet) {
+            Ok(v) => v.get_ssrc(),
+            Err(_) => return WEBRTC_ERROR_INVALID_PACKET,
+        };
+        let _ = self
+            .scheduler
+            .handle_input(RtcpInput::RtpReceived { ssrc }, now);
+        self.output(WEBRTC_OUTPUT_RTP, &packet);
+        WEBRTC_OK
+    }
+}
+
+/// `keying_material` is the RFC 5705 export of "EXTRACTOR-dtls_srtp" for
+/// the profile, 2 * (key + salt) bytes. NULL when they do not agree.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_new(
+    profile: u16,
+    keying_material: *const u8,
+    keying_material_length: usize,
+    is_client: bool,
+    session_bandwidth: u64,
+    now_ms: u64,
+) -> *mut Session {
+    guard(ptr::null_mut(), || {
+        let material = match as_slice(keying_material, keying_material_length) {
+            Some(v) => v,
+            None => return ptr::null_mut(),
+        };
+        let profile = match ProtectionProfile::from_id(profile) {
+            Some(v) => v,
+            None => return ptr::null_mut(),
+        };
+        let role = if is_client {
+            DtlsRole::Client
+        } else {
+            DtlsRole::Server
+        };
+        let srtp = match DtlsSrtpKeys::from_keying_material(profile, material, role)
+            .and_then(|v| DtlsSrtpSession::from_keys(&v, ContextConfig::default()))
+        {
+            Ok(v) => v,
+            Err(_) => return ptr::null_mut(),
+        };
+        let config = RtcpSchedulerConfig {
+            session_bandwidth,
+            ..RtcpSchedulerConfig::default()
+        };
+        let origin = Instant::now();
+        Box::into_raw(Box::new(Session {
+            srtp,
+            scheduler: RtcpScheduler::new(config, origin),
+            origin,
+            origin_ms: now_ms,
+            output: None,
+            user_data: ptr::null_mut(),
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_free(session: *mut Session) {
+    guard((), || {
+        if !session.is_null() {
+            drop(Box::from_raw(session));
+        }
+    })
+}
+
+/// The result of `f` on the session, or an error.
+unsafe fn with_session<F: FnOnce(&mut Session) -> i32>(session: *mut Session, f: F) -> i32 {
+    guard(WEBRTC_ERROR_PANIC, || match session.as_mut() {
+        Some(v) => f(v),
+        None => WEBRTC_ERROR_INVALID_ARGUMENT,
+    })
+}
+
+/// `user_data` is given back to `output` as it is, NULL stops the outputs.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_set_output(
+    session: *mut Session,
+    output: Option<OutputCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    with_session(session, |session| {
+        session.output = output;
+        session.user_data = user_data;
+        WEBRTC_OK
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_send_rtp(
+    session: *mut Session,
+    packet: *const u8,
+    length: usize,
+    now_ms: u64,
+) -> i32 {
+    with_session(session, |session| {
+        let packet = match as_slice(packet, length) {
+            Some(v) => v,
+            None => return WEBRTC_ERROR_INVALID_ARGUMENT,
+        };
+        let now = session.get_instant(now_ms);
+        match session.srtp.get_send_context().protect_rtp(packet) {
+            Ok(v) => {
+                let _ = session.scheduler.handle_input(RtcpInput::RtpSent, now);
+                session.output(WEBRTC_OUTPUT_TRANSMIT, &v);
+                WEBRTC_OK
+            }
+            Err(_) => WEBRTC_ERROR_SRTP,
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_send_rtcp(
+    session: *mut Session,
+    packet: *const u8,
+    length: usize,
+    now_ms: u64,
+) -> i32 {
+    with_session(session, |session| {
+        let packet = match as_slice(packet, length) {
+            Some(v) => v,
+            None => return WEBRTC_ERROR_INVALID_ARGUMENT,
+        };
+        let now = session.get_instant(now_ms);
+        match session.srtp.get_send_context().protect_rtcp(packet) {
+            Ok(v) => {
+                let size = packet.len() + UDP_IP_OVERHEAD;
+                let _ = session
+                    .scheduler
+                    .handle_input(RtcpInput::RtcpSent { size }, now);
+                session.output(WEBRTC_OUTPUT_TRANSMIT, &v);
+                WEBRTC_OK
+            }
+            Err(_) => WEBRTC_ERROR_SRTP,
+        }
+    })
+}
+
+/// An SRTP or SRTCP packet received.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_receive(
+    session: *mut Session,
+    packet: *const u8,
+    length: usize,
+    now_ms: u64,
+) -> i32 {
+    with_session(session, |session| {
+        let packet = match as_slice(packet, length) {
+            Some(v) if v.len() >= 2 => v,
+            Some(_) => return WEBRTC_ERROR_INVALID_PACKET,
+            None => return WEBRTC_ERROR_INVALID_ARGUMENT,
+        };
+        let now = session.get_instant(now_ms);
+        match packet[1] {
+            192..=223 => session.receive_rtcp(packet, now),
+            _ => session.receive_rtp(packet, now),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_process(session: *mut Session, now_ms: u64) -> i32 {
+    with_session(session, |session| {
+        let now = session.get_instant(now_ms);
+        session.srtp.get_send_context().process(now);
+        session.srtp.get_receive_context().process(now);
+        while let Some(RtcpOutput::Send { sender }) = session.scheduler.poll_output(now) {
+            let kind = if sender {
+                WEBRTC_OUTPUT_SENDER_REPORT
+            } else {
+                WEBRTC_OUTPUT_RECEIVER_REPORT
+            };
+            session.output(kind, &[]);
+        }
+        WEBRTC_OK
+    })
+}
+
+/// Writes when process is due to `timeout_ms`, -1 for never.
+#[no_mangle]
+pub unsafe extern "C" fn webrtc_session_poll_timeout(
+    session: *mut Session,
+    timeout_ms: *mut i64,
+) -> i32 {
+    with_session(session, |session| {
+        let out = match timeout_ms.as_mut() {
+            Some(v) => v,
+            None => return WEBRTC_ERROR_INVALID_ARGUMENT,
+        };
+        let timeout = [
+            session.scheduler.poll_timeout(),
+            session.srtp.get_send_context().poll_timeout(),
+            session.srtp.get_receive_context().poll_timeout(),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied();
+        *out = match timeout {
+            Some(v) => session.get_millis(v) as i64,
+            None => -1,
+        };
+        WEBRTC_OK
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ffi::packet::{
+        webrtc_rtcp_compound_add_receiver_report, webrtc_rtcp_compound_free,
+        webrtc_rtcp_compound_get_data, webrtc_rtcp_compound_new,
+    };
+    use std::slice;
+
+    type Outputs = Vec<(u32, Vec<u8>)>;
+
+    extern "C" fn collect(user_data: *mut c_void, kind: u32, data: *const u8, length: usize) {
+        let outputs = unsafe { &mut *(user_data as *mut Outputs) };
+        let data = if data.is_null() {
+            vec![]
+        } else {
+            unsafe { slice::from_raw_parts(data, length).to_vec() }
+        };
+        outputs.push((kind, data));
+    }
+
+    const RTP: [u8; 16] = [
+        0x80, 0x60, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0xca, 0xfe, 0xba, 0xbe, 1, 2, 3, 4,
+    ];
+
+    #[test]
+    fn session_test() {
+        unsafe {
+            // AES_CM_128_HMAC_SHA1_80, 2 * (16 + 14) bytes.
+            let material: Vec<u8> = (0..60).collect();
+            let mut outputs = [Outputs::new(), Outputs::new()];
+            let sessions: Vec<*mut Session> = (0..2)
+                .map(|i| {
+                    let session =
+                        webrtc_session_new(1, material.as_ptr(), 60, i == 0, 64_000, 1000);
+                    let user_data = &mut outputs[i] as *mut Outputs as *mut c_void;
+                    webrtc_session_set_output(session, Some(collect), user_data);
+                    session
+                })
+                .collect();
+            assert!(webrtc_session_new(1, material.as_ptr(), 59, true, 0, 0).is_null());
+
+            assert_eq!(
+                webrtc_session_send_rtp(sessions[0], RTP.as_ptr(), RTP.len(), 1000),
+                WEBRTC_OK
+            );
+            let (kind, srtp) = outputs[0].pop().unwrap();
+            assert_eq!(kind, WEBRTC_OUTPUT_TRANSMIT);
+            assert_eq!(srtp.len(), RTP.len() + 10);
+            assert_eq!(
+                webrtc_session_receive(sessions[1], srtp.as_ptr(), srtp.len(), 1010),
+                WEBRTC_OK
+            );
+            assert_eq!(outputs[1].pop(), Some((WEBRTC_OUTPUT_RTP, RTP.to_vec())));
+            assert_eq!(
+                webrtc_session_receive(sessions[1], srtp.as_ptr(), srtp.len(), 1010),
+                WEBRTC_ERROR_SRTP
+            );
+
+            // the first compound packet is due in 2.5s * [0.5, 1.5] / 1.21828,
+            // reconsidered when it is.
+            let mut timeout = 1000;
+            while outputs[0].is_empty() {
+                assert_eq!(
+                    webrtc_session_poll_timeout(sessions[0], &mut timeout),
+                    WEBRTC_OK
+                );
+                assert!(timeout < 1000 + 2 * 3079);
+                webrtc_session_process(sessions[0], timeout as u64);
+            }
+            assert_eq!(
+                outputs[0].pop(),
+                Some((WEBRTC_OUTPUT_SENDER_REPORT, vec![]))
+            );
+
+            let compound = webrtc_rtcp_compound_new();
+            webrtc_rtcp_compound_add_receiver_report(compound, 0xcafe_babe);
+            let mut length = 0;
+            let data = webrtc_rtcp_compound_get_data(compound, &mut length);
+            let rtcp = slice::from_raw_parts(data, length).to_vec();
+            webrtc_rtcp_compound_free(compound);
+            let now = timeout as u64;
+            assert_eq!(
+                webrtc_session_send_rtcp(sessions[0], rtcp.as_ptr(), rtcp.len(), now),
+                WEBRTC_OK
+            );
+            let (_, srtcp) = outputs[0].pop().unwrap();
+            assert_eq!(
+                webrtc_session_receive(sessions[1], srtcp.as_ptr(), srtcp.len(), now),
+                WEBRTC_OK
+            );
+            assert_eq!(outputs[1].pop(), Some((WEBRTC_OUTPUT_RTCP, rtcp)));
+            assert_eq!((*sessions[1]).scheduler.get_members(), 2);
+
+            for session in sessions {
+                webrtc_session_free(session);
+            }
+        }
+    }
+
+    #[test]
+    fn null_session_test() {
+        unsafe {
+            let session = ptr::null_mut();
+            let mut timeout = 0;
+            assert_eq!(
+                webrtc_session_set_output(session, Some(collect), ptr::null_mut()),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_session_send_rtp(session, RTP.as_ptr(), RTP.len(), 0),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_session_send_rtcp(session, RTP.as_ptr(), RTP.len(), 0),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_session_receive(session, RTP.as_ptr(), RTP.len(), 0),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_session_process(session, 0),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                webrtc_session_poll_timeout(session, &mut timeout),
+                WEBRTC_ERROR_INVALID_ARGUMENT
+            );
+            webrtc_session_free(session);
+        }
+    }
+}
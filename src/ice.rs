@@ -0,0 +1,24 @@
+pub mod agent;
+pub mod candidate;
+pub mod checklist;
+pub mod gatherer;
+pub mod mdns;
+pub mod network;
+pub mod policy;
+pub mod tcp;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, IceError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum IceError {
+    #[fail(display = "ICE candidate attribute is broken: {}", reason)]
+    InvalidCandidate { reason: String },
+
+    #[fail(display = "ICE has no selected candidate pair.")]
+    NoSelectedPair,
+
+    #[fail(display = "mDNS message is broken.")]
+    InvalidMdnsMessage,
+}
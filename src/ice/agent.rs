@@ -0,0 +1,2391 @@
+// https://tools.ietf.org/html/rfc8445#section-7
+
+/*
+    Binding request of a connectivity check
+
+      USERNAME           remote ufrag ":" local ufrag
+      PRIORITY           priority as a peer reflexive candidate
+      ICE-CONTROLLING /  tie-breaker
+      ICE-CONTROLLED
+      USE-CANDIDATE      only when the controlling agent nominates
+      NOMINATION         the nomination count, with renomination
+      MESSAGE-INTEGRITY  short-term credential with the remote password
+      FINGERPRINT
+
+    A check succeeds when a success response arrives from the address the
+    request was sent to, on the base it was sent from. The pair becomes
+    valid. The controlling agent nominates
+      regular:    the best valid pair of each component once no better pair
+                  may still succeed, with a check carrying USE-CANDIDATE
+      aggressive: every pair, so the best pair that succeeds is selected
+
+    With renomination the controlling agent nominates again when a better
+    pair becomes valid, and the controlled agent follows the nomination
+    with the highest count instead of the pair with the highest priority.
+
+    https://tools.ietf.org/html/rfc8838
+    With trickle ICE the candidates are added while the checks run, and
+    the checklist cannot fail or complete before both sides have signalled
+    end-of-candidates.
+
+    An ICE restart starts over with new credentials, candidates and
+    checklist. The selected pairs of the previous session keep carrying
+    data until every component has a new one.
+
+    https://tools.ietf.org/html/rfc8445#section-2.5
+    A lite agent has host candidates only, is always controlled and never
+    sends checks. A pair is valid once a check arrives on it, and selected
+    once the full agent nominates it.
+
+    An ICE-TCP pair of an active or simultaneous-open local candidate asks
+    the application to connect with an event before it is checked. The
+    checks and the data on a connection are framed as RFC 4571.
+
+    https://tools.ietf.org/html/rfc8445#section-7.3.1.1
+    Both agents may take the same role. The agent with the larger
+    tie-breaker keeps it: a request carrying the same role attribute is
+    answered with 487 (Role Conflict) by that agent, and the other one
+    switches its role, on the request or on the 487, and checks the pair
+    again. The checks and the selected pairs so far are kept.
+
+    https://tools.ietf.org/html/rfc7675
+    Consent freshness: a check without USE-CANDIDATE is sent on every
+    selected pair every 4 to 6 seconds. Without a response for a while the
+    state becomes disconnected, and after 30 seconds the consent expires,
+    nothing is sent anymore and the state becomes failed.
+
+    https://tools.ietf.org/html/rfc8445#section-11
+    Keepalive: a Binding indication with FINGERPRINT on each selected pair
+    nothing was sent on for the keepalive interval (Tr). Data and consent
+    checks keep the NAT bindings alive too and postpone it, so in practice
+    the lite agent, which sends no consent checks, is the one sending it.
+*/
+
+use crate::ice::candidate::{
+    compute_priority, CandidateType, IceCandidate, TcpType, TransportProtocol, COMPONENT_RTP,
+};
+use crate::ice::checklist::{CandidatePair, Checklist, PairState};
+use crate::ice::gatherer::{
+    CandidateGatherer, GatherConfig, GatherEvent, GatherState, LocalCandidate,
+};
+use crate::ice::mdns::{is_mdns_hostname, MdnsConfig, MdnsConnection, MdnsEvent, MdnsMode};
+use crate::ice::network::{NetworkChange, NetworkProvider, Transmit, TransmitQueue};
+use crate::ice::tcp::{frame_packet, TcpFramer};
+use crate::ice::{IceError, Result};
+use crate::sansio::StateMachine;
+use crate::stun::attribute::{
+    ATTR_ICE_CONTROLLED, ATTR_ICE_CONTROLLING, ATTR_NOMINATION, ATTR_PRIORITY, ATTR_USERNAME,
+    ATTR_USE_CANDIDATE, ATTR_XOR_MAPPED_ADDRESS, ERROR_BAD_REQUEST, ERROR_ROLE_CONFLICT,
+    ERROR_UNAUTHENTICATED,
+};
+use crate::stun::integrity::{get_short_term_key, verify_fingerprint, verify_message_integrity};
+use crate::stun::message::{
+    is_stun_message, StunClass, StunMessage, TransactionId, METHOD_BINDING,
+};
+use crate::stun::transaction::{TransactionConfig, TransactionEvent, TransactionManager};
+use crate::time::Instant;
+
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::io;
+use std::iter;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+// TCPでは再送せず，RFC 5389の39.5秒待つ．
+const TCP_TRANSACTION_TIMEOUT: Duration = Duration::from_millis(39500);
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IceCredentials {
+    pub ufrag: String,
+    pub pwd: String,
+}
+
+impl IceCredentials {
+    pub fn new(ufrag: &str, pwd: &str) -> Self {
+        IceCredentials {
+            ufrag: ufrag.to_string(),
+            pwd: pwd.to_string(),
+        }
+    }
+
+    pub fn generate() -> Self {
+        let mut rng = thread_rng();
+        let ufrag: String = iter::repeat(())
+            .map(|()| rng.sample(Alphanumeric))
+            .take(4)
+            .collect();
+        let pwd: String = iter::repeat(())
+            .map(|()| rng.sample(Alphanumeric))
+            .take(22)
+            .collect();
+        IceCredentials { ufrag, pwd }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum IceRole {
+    Controlling,
+    Controlled,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum IceConnectionState {
+    New,
+    Checking,
+    Connected,
+    Completed,
+    Disconnected,
+    Failed,
+    Closed,
+}
+
+/// What the agent has ready as a StateMachine.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum IceOutput {
+    Transmit(Transmit),
+    /// Application data received on a pair.
+    Data(Vec<u8>),
+    Event(IceEvent),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum IceEvent {
+    LocalCandidate(IceCandidate),
+    GatheringComplete,
+    StateChanged(IceConnectionState),
+    SelectedPairChanged {
+        component: u16,
+        local: IceCandidate,
+        remote: IceCandidate,
+    },
+    /// Connect a TCP stream from the IP address of `local` (from its port
+    /// too unless it is 9) to `remote`, and report the result with
+    /// `handle_tcp_connected` or `handle_tcp_connect_failed`.
+    TcpConnect {
+        local: SocketAddr,
+        remote: SocketAddr,
+    },
+    /// The role was switched to repair a role conflict.
+    RoleChanged(IceRole),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum NominationMode {
+    Regular,
+    Aggressive,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IceConfig {
+    pub gather: GatherConfig,
+    /// Ta, the pacing of the checks.
+    pub check_interval: Duration,
+    pub nomination: NominationMode,
+    pub renomination: bool,
+    /// ICE-lite, for servers on a public address.
+    pub lite: bool,
+    /// The mean interval of the consent checks, randomized by +-20%.
+    pub consent_interval: Duration,
+    /// Disconnected after this long without consent.
+    pub disconnected_timeout: Duration,
+    /// Failed after this long without consent.
+    pub consent_timeout: Duration,
+    /// Tr, no keepalives with `None`.
+    pub keepalive_interval: Option<Duration>,
+    pub transaction: TransactionConfig,
+    pub mdns: MdnsConfig,
+}
+
+impl Default for IceConfig {
+    fn default() -> Self {
+        IceConfig {
+            gather: GatherConfig::default(),
+            check_interval: Duration::from_millis(50),
+            nomination: NominationMode::Regular,
+            renomination: false,
+            lite: false,
+            consent_interval: Duration::from_secs(5),
+            disconnected_timeout: Duration::from_secs(10),
+            consent_timeout: Duration::from_secs(30),
+            keepalive_interval: Some(Duration::from_secs(15)),
+            transaction: TransactionConfig::default(),
+            mdns: MdnsConfig::default(),
+        }
+    }
+}
+
+// restart前のsession．新しいpairが選ばれるまでdataを流し続ける．
+#[derive(Debug, Clone)]
+struct PreviousSession {
+    gatherer: CandidateGatherer,
+    selected: HashMap<u16, CandidatePair>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Check {
+    pair: usize,
+    nominate: bool,
+    consent: bool,
+    // 送ったrole．487を受けたときに切り替えるかどうかに使う．
+    role: IceRole,
+}
+
+// ICEのagent．socketは持たず，Transmitを返し受信したdatagramを渡してもらう．
+#[derive(Debug, Clone)]
+pub struct IceAgent {
+    config: IceConfig,
+    role: IceRole,
+    tie_breaker: u64,
+    local_credentials: IceCredentials,
+    remote_credentials: Option<IceCredentials>,
+    gatherer: CandidateGatherer,
+    remote_candidates: Vec<IceCandidate>,
+    remote_end_of_candidates: bool,
+    checklist: Checklist,
+    // 送信元ごとにtransactionを分ける．
+    transactions: HashMap<SocketAddr, TransactionManager>,
+    checks: HashMap<TransactionId, Check>,
+    // controllingがnominateしている，componentごとのpair．
+    nominating: HashMap<u16, usize>,
+    // 送ったNOMINATIONの値と，componentごとに受け入れた最後の値．
+    nomination_count: u32,
+    nominations: HashMap<u16, u32>,
+    // USE-CANDIDATEを受けたが，まだcheckが成功していないpair．
+    nominate_on_success: HashSet<usize>,
+    selected: HashMap<u16, usize>,
+    previous: Option<PreviousSession>,
+    mdns: Option<MdnsConnection>,
+    state: IceConnectionState,
+    next_check_at: Option<Instant>,
+    transmits: VecDeque<Transmit>,
+    events: VecDeque<IceEvent>,
+    received: VecDeque<Vec<u8>>,
+    // ICE-TCP: 接続ごとのframer，listenしているbase，接続待ちのpair．
+    tcp_streams: HashMap<(SocketAddr, SocketAddr), TcpFramer>,
+    tcp_listeners: HashSet<SocketAddr>,
+    tcp_connecting: HashMap<(SocketAddr, SocketAddr), usize>,
+    // componentごとに最後にconsentを得た時刻．
+    consent_at: HashMap<u16, Instant>,
+    consent_stale: bool,
+    next_consent_at: Option<Instant>,
+    // componentごとに選ばれたpairで最後に送った時刻．
+    sent_at: HashMap<u16, Instant>,
+}
+
+impl IceAgent {
+    /// A lite agent is controlled whatever `role` is.
+    pub fn new(config: IceConfig, role: IceRole) -> Self {
+        let mut gather = config.gather.clone();
+        let policy = gather.policy;
+        let role = if config.lite {
+            gather.stun_servers.clear();
+            gather.turn_servers.clear();
+            IceRole::Controlled
+        } else {
+            role
+        };
+        IceAgent {
+            gatherer: CandidateGatherer::new(gather),
+            config,
+            role,
+            tie_breaker: rand::random(),
+            local_credentials: IceCredentials::generate(),
+            remote_credentials: None,
+            remote_candidates: Vec::new(),
+            remote_end_of_candidates: false,
+            checklist: Checklist::with_policy(policy),
+            transactions: HashMap::new(),
+            checks: HashMap::new(),
+            nominating: HashMap::new(),
+            nomination_count: 0,
+            nominations: HashMap::new(),
+            nominate_on_success: HashSet::new(),
+            selected: HashMap::new(),
+            previous: None,
+            mdns: None,
+            state: IceConnectionState::New,
+            next_check_at: None,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+            received: VecDeque::new(),
+            tcp_streams: HashMap::new(),
+            tcp_listeners: HashSet::new(),
+            tcp_connecting: HashMap::new(),
+            consent_at: HashMap::new(),
+            consent_stale: false,
+            next_consent_at: None,
+            sent_at: HashMap::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &IceConfig {
+        &self.config
+    }
+
+    pub fn get_role(&self) -> IceRole {
+        self.role
+    }
+
+    /// The role taken from the offer/answer, before the checks start. A
+    /// lite agent stays controlled.
+    pub fn set_role(&mut self, role: IceRole) {
+        if !self.config.lite {
+            self.role = role;
+            self.checklist.set_controlling(role == IceRole::Controlling);
+        }
+    }
+
+    /// The peer is a lite agent, so this full agent takes the controlling
+    /// role.
+    pub fn set_remote_lite(&mut self) {
+        if !self.config.lite && self.role != IceRole::Controlling {
+            self.role = IceRole::Controlling;
+            self.checklist.set_controlling(true);
+        }
+    }
+
+    pub fn get_state(&self) -> IceConnectionState {
+        self.state
+    }
+
+    pub fn get_local_credentials(&self) -> &IceCredentials {
+        &self.local_credentials
+    }
+
+    pub fn get_remote_credentials(&self) -> Option<&IceCredentials> {
+        self.remote_credentials.as_ref()
+    }
+
+    pub fn set_remote_credentials(&mut self, credentials: IceCredentials, now: Instant) {
+        self.remote_credentials = Some(credentials);
+        self.schedule_check(now);
+    }
+
+    pub fn get_gatherer(&self) -> &CandidateGatherer {
+        &self.gatherer
+    }
+
+    pub fn get_local_candidates(&self) -> Vec<IceCandidate> {
+        self.gatherer
+            .get_local_candidates()
+            .iter()
+            .map(|v| v.candidate.clone())
+            .collect()
+    }
+
+    pub fn get_remote_candidates(&self) -> &[IceCandidate] {
+        &self.remote_candidates
+    }
+
+    pub fn get_checklist(&self) -> &Checklist {
+        &self.checklist
+    }
+
+    /// The pair carrying data. During a restart it is the one of the
+    /// previous session until a new one is selected.
+    pub fn get_selected_pair(&self, component: u16) -> Option<&CandidatePair> {
+        match self.selected.get(&component) {
+            Some(v) => Some(self.checklist.get_pair(*v)),
+            None => self
+                .previous
+                .as_ref()
+                .and_then(|v| v.selected.get(&component)),
+        }
+    }
+
+    pub fn is_restarting(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    /// Restarts ICE with new local credentials and a new gathering. The
+    /// remote credentials and candidates have to be set again.
+    pub fn restart<P: NetworkProvider + ?Sized>(
+        &mut self,
+        provider: &mut P,
+        now: Instant,
+    ) -> io::Result<()> {
+        let gatherer = CandidateGatherer::new(self.config.gather.clone());
+        let gatherer = std::mem::replace(&mut self.gatherer, gatherer);
+        if !self.selected.is_empty() {
+            let selected = self
+                .selected
+                .iter()
+                .map(|(k, v)| (*k, self.checklist.get_pair(*v).clone()))
+                .collect();
+            self.previous = Some(PreviousSession { gatherer, selected });
+        }
+
+        self.local_credentials = IceCredentials::generate();
+        self.remote_credentials = None;
+        self.remote_candidates.clear();
+        self.remote_end_of_candidates = false;
+        self.checklist = Checklist::with_policy(self.config.gather.policy);
+        self.transactions.clear();
+        self.checks.clear();
+        self.nominating.clear();
+        self.nomination_count = 0;
+        self.nominations.clear();
+        self.nominate_on_success.clear();
+        self.selected.clear();
+        self.next_check_at = None;
+        self.consent_at.clear();
+        self.consent_stale = false;
+        self.next_consent_at = None;
+        if self.state == IceConnectionState::Completed {
+            self.set_state(IceConnectionState::Connected);
+        }
+        self.gather(provider, now)
+    }
+
+    /// Drains the address changes `provider` watched. ICE restarts when an
+    /// address a local candidate is based on went away or a usable one
+    /// appeared, and `true` is returned: the new credentials and candidates
+    /// have to be signalled.
+    pub fn handle_network_changes<P: NetworkProvider + ?Sized>(
+        &mut self,
+        provider: &mut P,
+        now: Instant,
+    ) -> io::Result<bool> {
+        let mut changed = false;
+        while let Some(change) = provider.poll_change() {
+            changed |= match change {
+                NetworkChange::AddressAdded { interface, address } => {
+                    let config = self.gatherer.get_config();
+                    !address.is_unspecified()
+                        && (!interface.is_loopback || config.include_loopback)
+                        && config.policy.allows_address(address)
+                }
+                NetworkChange::AddressRemoved { address, .. } => self
+                    .gatherer
+                    .get_local_candidates()
+                    .iter()
+                    .any(|v| v.base.ip() == address),
+            };
+        }
+        if !changed || self.state == IceConnectionState::Closed {
+            return Ok(false);
+        }
+        self.restart(provider, now)?;
+        Ok(true)
+    }
+
+    /// Starts gathering. The candidates are emitted as events.
+    pub fn gather<P: NetworkProvider + ?Sized>(
+        &mut self,
+        provider: &mut P,
+        now: Instant,
+    ) -> io::Result<()> {
+        if self.config.gather.mdns != MdnsMode::Disabled && self.mdns.is_none() {
+            let local = provider.bind_mdns()?;
+            self.mdns = Some(MdnsConnection::new(local, self.config.mdns));
+        }
+        self.gatherer.gather(provider, now)?;
+        if let Some(mdns) = self.mdns.as_mut() {
+            for (name, address) in self.gatherer.get_mdns_hostnames() {
+                mdns.register(name, *address);
+            }
+        }
+        self.handle_gather_events(now);
+        Ok(())
+    }
+
+    pub fn get_mdns(&self) -> Option<&MdnsConnection> {
+        self.mdns.as_ref()
+    }
+
+    /// Sets all the remote candidates of a peer that does not trickle, and
+    /// pairs them with the local ones.
+    pub fn set_remote_candidates(&mut self, candidates: Vec<IceCandidate>, now: Instant) {
+        for candidate in candidates {
+            self.add_remote(candidate, now);
+        }
+        self.set_remote_end_of_candidates();
+    }
+
+    /// Adds a trickled remote candidate. The checks already running go on.
+    /// Returns false for a candidate that is already known.
+    pub fn add_remote_candidate(&mut self, candidate: IceCandidate, now: Instant) -> bool {
+        self.add_remote(candidate, now)
+    }
+
+    pub fn has_remote_end_of_candidates(&self) -> bool {
+        self.remote_end_of_candidates
+    }
+
+    /// The peer signalled end-of-candidates.
+    pub fn set_remote_end_of_candidates(&mut self) {
+        self.remote_end_of_candidates = true;
+        self.update_state();
+    }
+
+    pub fn poll_event(&mut self) -> Option<IceEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        while let Some(mut v) = self.transmits.pop_front() {
+            if self.tcp_streams.contains_key(&(v.source, v.destination)) {
+                // a datagram too large for the length prefix is dropped.
+                v.data = match frame_packet(&v.data) {
+                    Some(data) => data,
+                    None => continue,
+                };
+            }
+            return Some(v);
+        }
+        if let Some(v) = self.gatherer.poll_transmit() {
+            return Some(v);
+        }
+        if let Some(v) = self.mdns.as_mut().and_then(|v| v.poll_transmit()) {
+            return Some(v);
+        }
+        self.previous.as_mut()?.gatherer.poll_transmit()
+    }
+
+    /// Application data received on any pair.
+    pub fn poll_receive(&mut self) -> Option<Vec<u8>> {
+        self.received.pop_front()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let transactions = self.transactions.values().filter_map(|v| v.poll_timeout());
+        let previous = self
+            .previous
+            .as_ref()
+            .and_then(|v| v.gatherer.poll_timeout());
+        let mdns = self.mdns.as_ref().and_then(|v| v.poll_timeout());
+        self.gatherer
+            .poll_timeout()
+            .into_iter()
+            .chain(previous)
+            .chain(mdns)
+            .chain(transactions)
+            .chain(self.next_check_at)
+            .chain(self.next_consent_at)
+            .chain(self.get_keepalive_at())
+            .min()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        self.gatherer.process(now);
+        self.handle_gather_events(now);
+        if let Some(previous) = self.previous.as_mut() {
+            previous.gatherer.process(now);
+            while previous.gatherer.poll_event().is_some() {}
+        }
+        if let Some(mdns) = self.mdns.as_mut() {
+            mdns.process(now);
+        }
+        self.handle_mdns_events(now);
+
+        let mut timed_out = Vec::new();
+        for (base, transactions) in self.transactions.iter_mut() {
+            let mut transport = TransmitQueue {
+                source: *base,
+                queue: &mut self.transmits,
+            };
+            for event in transactions.process(now, &mut transport) {
+                if let TransactionEvent::TimedOut { transaction_id, .. } = event {
+                    timed_out.push(transaction_id);
+                }
+            }
+        }
+        for transaction_id in timed_out {
+            match self.checks.remove(&transaction_id) {
+                Some(check) if !check.consent => self.fail_check(check),
+                _ => {}
+            }
+        }
+
+        if self.next_check_at.is_some_and(|v| v <= now) {
+            self.next_check_at = if self.send_next_check(now) {
+                Some(now + self.config.check_interval)
+            } else {
+                None
+            };
+        }
+        if self.next_consent_at.is_some_and(|v| v <= now) {
+            self.refresh_consent(now);
+        }
+        if self.get_keepalive_at().is_some_and(|v| v <= now) {
+            self.send_keepalives(now);
+        }
+
+        self.nominate(now);
+        self.update_state();
+        self.flush_relayed(now);
+    }
+
+    /// Handles a datagram received on the socket bound to `local` (or data
+    /// of a TURN stream connected from it).
+    pub fn handle_receive(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) {
+        if let Some(mdns) = self
+            .mdns
+            .as_mut()
+            .filter(|v| v.get_local_address() == local)
+        {
+            if mdns.handle_receive(source, data).is_ok() {
+                self.handle_mdns_events(now);
+            }
+            self.flush_relayed(now);
+            return;
+        }
+        if self.tcp_streams.contains_key(&(local, source)) || self.tcp_listeners.contains(&local) {
+            self.handle_tcp_data(local, source, data, now);
+            self.update_state();
+            self.flush_relayed(now);
+            return;
+        }
+        if is_stun_message(data) {
+            if let Ok(message) = StunMessage::from_bytes(data) {
+                if self.gatherer.handle_stun(local, source, message, now) {
+                    self.handle_gather_events(now);
+                    self.flush_relayed(now);
+                    return;
+                }
+            }
+        }
+
+        if self.gatherer.is_relay_server(local, source) {
+            let received = self.gatherer.handle_relay_packet(local, source, data, now);
+            for (relayed, peer, data) in received {
+                self.handle_datagram(relayed, peer, &data, now);
+            }
+            self.handle_gather_events(now);
+        } else if let Some(previous) = self
+            .previous
+            .as_mut()
+            .filter(|v| v.gatherer.is_relay_server(local, source))
+        {
+            let received = previous
+                .gatherer
+                .handle_relay_packet(local, source, data, now);
+            for (_, _, data) in received {
+                self.received.push_back(data);
+            }
+        } else {
+            self.handle_datagram(local, source, data, now);
+        }
+        self.update_state();
+        self.flush_relayed(now);
+    }
+
+    /// Sends application data on the selected pair of the RTP component.
+    pub fn send(&mut self, data: &[u8], now: Instant) -> Result<()> {
+        self.send_component(COMPONENT_RTP, data, now)
+    }
+
+    pub fn send_component(&mut self, component: u16, data: &[u8], now: Instant) -> Result<()> {
+        let pair = self
+            .get_selected_pair(component)
+            .ok_or(IceError::NoSelectedPair)?;
+        let transmit = Transmit::new(pair.local.base, pair.remote_address, data.to_vec());
+        self.transmits.push_back(transmit);
+        self.sent_at.insert(component, now);
+        self.flush_relayed(now);
+        Ok(())
+    }
+
+    /// The connection asked with `IceEvent::TcpConnect` from `base` to
+    /// `remote` is established from `local`.
+    pub fn handle_tcp_connected(
+        &mut self,
+        base: SocketAddr,
+        remote: SocketAddr,
+        local: SocketAddr,
+        now: Instant,
+    ) {
+        if let Some(index) = self.tcp_connecting.remove(&(base, remote)) {
+            self.tcp_streams.insert((local, remote), TcpFramer::new());
+            self.checklist.get_pair_mut(index).local.base = local;
+            self.start_tcp_check(index, now);
+        }
+    }
+
+    pub fn handle_tcp_connect_failed(&mut self, base: SocketAddr, remote: SocketAddr) {
+        if let Some(index) = self.tcp_connecting.remove(&(base, remote)) {
+            self.checklist.set_state(index, PairState::Failed);
+            self.update_state();
+        }
+    }
+
+    /// The TCP connection from `local` to `remote` was closed, so its pairs
+    /// cannot carry anything anymore.
+    pub fn handle_tcp_closed(&mut self, local: SocketAddr, remote: SocketAddr) {
+        if self.tcp_streams.remove(&(local, remote)).is_none() {
+            return;
+        }
+        if let Some(index) = self.checklist.find_pair(local, remote) {
+            let pair = self.checklist.get_pair_mut(index);
+            pair.valid = false;
+            let component = pair.get_component();
+            self.checklist.set_state(index, PairState::Failed);
+            if self.selected.get(&component) == Some(&index) {
+                self.selected.remove(&component);
+            }
+        }
+        self.update_state();
+    }
+
+    pub fn close(&mut self) {
+        self.checks.clear();
+        self.transactions.clear();
+        self.tcp_streams.clear();
+        self.tcp_connecting.clear();
+        self.next_check_at = None;
+        self.next_consent_at = None;
+        self.set_state(IceConnectionState::Closed);
+    }
+
+    fn handle_gather_events(&mut self, now: Instant) {
+        while let Some(event) = self.gatherer.poll_event() {
+            match event {
+                GatherEvent::Candidate(local) => {
+                    self.events
+                        .push_back(IceEvent::LocalCandidate(local.candidate.clone()));
+                    if let Some(TcpType::Passive) | Some(TcpType::SimultaneousOpen) =
+                        local.candidate.tcp_type
+                    {
+                        self.tcp_listeners.insert(local.base);
+                    }
+                    for remote in self.remote_candidates.clone() {
+                        self.add_pair(&local, &remote, now);
+                    }
+                }
+                GatherEvent::Complete => self.events.push_back(IceEvent::GatheringComplete),
+            }
+        }
+    }
+
+    fn add_remote(&mut self, candidate: IceCandidate, now: Instant) -> bool {
+        if self.remote_candidates.contains(&candidate) {
+            return false;
+        }
+        for local in self.gatherer.get_local_candidates().to_vec() {
+            self.add_pair(&local, &candidate, now);
+        }
+        let hostname =
+            candidate.get_socket_addr().is_none() && is_mdns_hostname(&candidate.address);
+        if let Some(mdns) = self.mdns.as_mut().filter(|_| hostname) {
+            mdns.query(&candidate.address, now);
+        }
+        self.remote_candidates.push(candidate);
+        self.handle_mdns_events(now);
+        true
+    }
+
+    /// Pairs the remote candidates with a hostname that was resolved.
+    fn handle_mdns_events(&mut self, now: Instant) {
+        while let Some(event) = self.mdns.as_mut().and_then(|v| v.poll_event()) {
+            if let MdnsEvent::Resolved { name, .. } = event {
+                let remotes: Vec<IceCandidate> = self
+                    .remote_candidates
+                    .iter()
+                    .filter(|v| v.address.eq_ignore_ascii_case(&name))
+                    .cloned()
+                    .collect();
+                for remote in remotes {
+                    for local in self.gatherer.get_local_candidates().to_vec() {
+                        self.add_pair(&local, &remote, now);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The address of a remote candidate, or what its hostname resolved to.
+    fn get_remote_address(&self, remote: &IceCandidate) -> Option<SocketAddr> {
+        remote.get_socket_addr().or_else(|| {
+            let address = self.mdns.as_ref()?.get_address(&remote.address)?;
+            Some(SocketAddr::new(address, remote.port))
+        })
+    }
+
+    fn add_pair(
+        &mut self,
+        local: &LocalCandidate,
+        remote: &IceCandidate,
+        now: Instant,
+    ) -> Option<usize> {
+        let controlling = self.role == IceRole::Controlling;
+        let remote_address = self.get_remote_address(remote)?;
+        let index = self
+            .checklist
+            .add_resolved_pair(local, remote, remote_address, controlling)?;
+        self.schedule_check(now);
+        Some(index)
+    }
+
+    fn schedule_check(&mut self, now: Instant) {
+        let closed = self.state == IceConnectionState::Closed;
+        if self.config.lite || closed {
+            return;
+        }
+        if self.next_check_at.is_none() && self.remote_credentials.is_some() {
+            self.next_check_at = Some(now);
+        }
+    }
+
+    fn send_next_check(&mut self, now: Instant) -> bool {
+        if self.remote_credentials.is_none() {
+            return false;
+        }
+        let index = match self.checklist.next_check() {
+            Some(v) => v,
+            None => return false,
+        };
+        let pair = self.checklist.get_pair(index).clone();
+        match self.state {
+            IceConnectionState::New
+            | IceConnectionState::Disconnected
+            | IceConnectionState::Failed => self.set_state(IceConnectionState::Checking),
+            _ => {}
+        }
+        let key = (pair.local.base, pair.remote_address);
+        let connects = pair
+            .local
+            .candidate
+            .tcp_type
+            .is_some_and(|v| v != TcpType::Passive);
+        if connects && !self.tcp_streams.contains_key(&key) {
+            // the check waits for the connection.
+            self.checklist.set_state(index, PairState::InProgress);
+            self.tcp_connecting.insert(key, index);
+            self.events.push_back(IceEvent::TcpConnect {
+                local: key.0,
+                remote: key.1,
+            });
+            return true;
+        }
+        let nominate = self.role == IceRole::Controlling
+            && (self.config.nomination == NominationMode::Aggressive
+                || self.nominating.get(&pair.get_component()) == Some(&index));
+        let state = if self.start_check(index, nominate, false, now) {
+            PairState::InProgress
+        } else {
+            PairState::Failed
+        };
+        self.checklist.set_state(index, state);
+        true
+    }
+
+    /// Sends a Binding request on the pair, returns false if it cannot be.
+    fn start_check(&mut self, index: usize, nominate: bool, consent: bool, now: Instant) -> bool {
+        let remote_credentials = match self.remote_credentials {
+            Some(ref v) => v.clone(),
+            None => return false,
+        };
+        let pair = self.checklist.get_pair(index).clone();
+        let mut request = StunMessage::request(METHOD_BINDING);
+        let username = format!(
+            "{}:{}",
+            remote_credentials.ufrag, self.local_credentials.ufrag
+        );
+        request.add_attribute(ATTR_USERNAME, username.into_bytes());
+        let local_preference = ((pair.local.candidate.priority >> 8) & 0xffff) as u16;
+        let priority = compute_priority(
+            CandidateType::PeerReflexive,
+            local_preference,
+            pair.get_component(),
+        );
+        request.add_attribute(ATTR_PRIORITY, priority.to_be_bytes().to_vec());
+        let role_attribute = match self.role {
+            IceRole::Controlling => ATTR_ICE_CONTROLLING,
+            IceRole::Controlled => ATTR_ICE_CONTROLLED,
+        };
+        request.add_attribute(role_attribute, self.tie_breaker.to_be_bytes().to_vec());
+        if nominate {
+            request.add_attribute(ATTR_USE_CANDIDATE, vec![]);
+            if self.config.renomination {
+                self.nomination_count = (self.nomination_count + 1) & 0xff_ffff;
+                let count = self.nomination_count.to_be_bytes().to_vec();
+                request.add_attribute(ATTR_NOMINATION, count);
+            }
+        }
+        request.add_message_integrity(&get_short_term_key(&remote_credentials.pwd));
+        request.add_fingerprint();
+
+        let config = if pair.local.candidate.transport == TransportProtocol::Tcp {
+            TransactionConfig {
+                rto: TCP_TRANSACTION_TIMEOUT,
+                max_requests: 1,
+                last_wait_multiplier: 1,
+            }
+        } else {
+            self.config.transaction
+        };
+        let mut transport = TransmitQueue {
+            source: pair.local.base,
+            queue: &mut self.transmits,
+        };
+        let started = self
+            .transactions
+            .entry(pair.local.base)
+            .or_insert_with(|| TransactionManager::new(config))
+            .start(&request, pair.remote_address, now, &mut transport);
+        match started {
+            Ok(transaction_id) => {
+                let check = Check {
+                    pair: index,
+                    nominate,
+                    consent,
+                    role: self.role,
+                };
+                self.checks.insert(transaction_id, check);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Sends the consent checks, and gives up the pairs whose consent
+    /// expired.
+    fn refresh_consent(&mut self, now: Instant) {
+        let mut stale = false;
+        let mut expired = false;
+        for at in self.consent_at.values() {
+            let elapsed = now.saturating_duration_since(*at);
+            stale |= elapsed >= self.config.disconnected_timeout;
+            expired |= elapsed >= self.config.consent_timeout;
+        }
+        if expired {
+            for index in self.selected.values() {
+                self.checklist.get_pair_mut(*index).valid = false;
+            }
+            for index in self.selected.values().copied().collect::<Vec<_>>() {
+                self.checklist.set_state(index, PairState::Failed);
+            }
+            self.selected.clear();
+            self.consent_at.clear();
+            self.consent_stale = false;
+            self.next_consent_at = None;
+            self.set_state(IceConnectionState::Failed);
+            return;
+        }
+
+        self.consent_stale = stale;
+        for index in self.selected.values().copied().collect::<Vec<_>>() {
+            if self.start_check(index, false, true, now) {
+                let component = self.checklist.get_pair(index).get_component();
+                self.sent_at.insert(component, now);
+            }
+        }
+        let interval = self
+            .config
+            .consent_interval
+            .mul_f64(thread_rng().gen_range(0.8, 1.2));
+        self.next_consent_at = Some(now + interval);
+    }
+
+    fn get_keepalive_at(&self) -> Option<Instant> {
+        let interval = self.config.keepalive_interval?;
+        if self.state == IceConnectionState::Closed {
+            return None;
+        }
+        (1..=self.config.gather.components.max(1))
+            .filter(|v| self.get_selected_pair(*v).is_some())
+            .filter_map(|v| self.sent_at.get(&v))
+            .map(|v| *v + interval)
+            .min()
+    }
+
+    fn send_keepalives(&mut self, now: Instant) {
+        let interval = match self.config.keepalive_interval {
+            Some(v) => v,
+            None => return,
+        };
+        for component in 1..=self.config.gather.components.max(1) {
+            let (base, remote) = match self.get_selected_pair(component) {
+                Some(v) => (v.local.base, v.remote_address),
+                None => continue,
+            };
+            if self
+                .sent_at
+                .get(&component)
+                .is_some_and(|v| now < *v + interval)
+            {
+                continue;
+            }
+            let mut indication =
+                StunMessage::new(METHOD_BINDING, StunClass::Indication, TransactionId::new());
+            indication.add_fingerprint();
+            self.transmits
+                .push_back(Transmit::new(base, remote, indication.to_bytes()));
+            self.sent_at.insert(component, now);
+        }
+        self.flush_relayed(now);
+    }
+
+    fn start_tcp_check(&mut self, index: usize, now: Instant) {
+        self.checklist.set_state(index, PairState::Waiting);
+        self.checklist.push_triggered(index);
+        self.schedule_check(now);
+    }
+
+    fn handle_tcp_data(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) {
+        let framer = self.tcp_streams.entry((local, source)).or_default();
+        framer.push(data);
+        let frames: Vec<Vec<u8>> = iter::from_fn(|| framer.pop()).collect();
+        // the remote side of a simultaneous open connected first.
+        if let Some(index) = self.tcp_connecting.remove(&(local, source)) {
+            self.start_tcp_check(index, now);
+        }
+        for frame in frames {
+            self.handle_datagram(local, source, &frame, now);
+        }
+    }
+
+    fn handle_datagram(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) {
+        if !is_stun_message(data) {
+            self.received.push_back(data.to_vec());
+            return;
+        }
+        if verify_fingerprint(data).is_err() {
+            return;
+        }
+        let message = match StunMessage::from_bytes(data) {
+            Ok(v) if v.get_method() == METHOD_BINDING => v,
+            _ => return,
+        };
+        match message.get_class() {
+            StunClass::Request => self.handle_binding_request(local, source, message, data, now),
+            StunClass::SuccessResponse | StunClass::ErrorResponse => {
+                self.handle_check_response(local, source, message, data, now)
+            }
+            StunClass::Indication => {}
+        }
+    }
+
+    fn handle_binding_request(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        request: StunMessage,
+        data: &[u8],
+        now: Instant,
+    ) {
+        // an active TCP candidate is sent from the connection.
+        let connected = self.checklist.get_pairs().iter().map(|v| &v.local);
+        let local_candidate = match self
+            .gatherer
+            .get_local_candidates()
+            .iter()
+            .chain(connected)
+            .find(|v| v.base == local)
+        {
+            Some(v) => v.clone(),
+            None => return,
+        };
+
+        let prefix = format!("{}:", self.local_credentials.ufrag);
+        let authenticated = match request.get_string_attribute(ATTR_USERNAME) {
+            Ok(Some(v)) if v.starts_with(&prefix) => {
+                verify_message_integrity(data, &get_short_term_key(&self.local_credentials.pwd))
+                    .is_ok()
+            }
+            _ => false,
+        };
+        if !authenticated {
+            self.send_error_response(local, source, &request, ERROR_UNAUTHENTICATED);
+            return;
+        }
+        let priority = match request.get_u32_attribute(ATTR_PRIORITY) {
+            Ok(Some(v)) => v,
+            _ => {
+                self.send_error_response(local, source, &request, ERROR_BAD_REQUEST);
+                return;
+            }
+        };
+        if self.has_role_conflict(&request, now) {
+            self.send_error_response(local, source, &request, ERROR_ROLE_CONFLICT);
+            return;
+        }
+
+        let mut response = StunMessage::response(&request, StunClass::SuccessResponse);
+        response.add_xor_address(ATTR_XOR_MAPPED_ADDRESS, &source);
+        response.add_message_integrity(&get_short_term_key(&self.local_credentials.pwd));
+        response.add_fingerprint();
+        self.transmits
+            .push_back(Transmit::new(local, source, response.to_bytes()));
+
+        // an unknown source is a peer reflexive candidate.
+        let known = self
+            .remote_candidates
+            .iter()
+            .any(|v| self.get_remote_address(v) == Some(source));
+        if !known {
+            let foundation = format!("prflx{}", self.remote_candidates.len() + 1);
+            let mut candidate = IceCandidate::new(
+                &foundation,
+                local_candidate.candidate.component,
+                local_candidate.candidate.transport,
+                priority,
+                source,
+                CandidateType::PeerReflexive,
+            );
+            candidate.tcp_type = match local_candidate.candidate.tcp_type {
+                Some(TcpType::Active) => Some(TcpType::Passive),
+                Some(TcpType::Passive) => Some(TcpType::Active),
+                v => v,
+            };
+            self.add_remote(candidate, now);
+        }
+
+        let index = match self.checklist.find_pair(local, source) {
+            Some(v) => v,
+            None => return,
+        };
+        let use_candidate = self.role == IceRole::Controlled
+            && request.has_attribute(ATTR_USE_CANDIDATE)
+            && self.accept_nomination(index, &request);
+        if self.config.lite {
+            // nothing is checked the other way around.
+            self.checklist.get_pair_mut(index).valid = true;
+            self.checklist.set_state(index, PairState::Succeeded);
+            if self.state == IceConnectionState::New {
+                self.set_state(IceConnectionState::Checking);
+            }
+            if use_candidate {
+                self.select(index, now);
+            }
+            return;
+        }
+        let pair = self.checklist.get_pair(index);
+        if pair.state == PairState::Succeeded && pair.valid {
+            if use_candidate {
+                self.select(index, now);
+            }
+            return;
+        }
+        if use_candidate {
+            self.nominate_on_success.insert(index);
+        }
+        if pair.state != PairState::InProgress {
+            self.checklist.set_state(index, PairState::Waiting);
+            self.checklist.push_triggered(index);
+            self.schedule_check(now);
+        }
+    }
+
+    fn send_error_response(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        request: &StunMessage,
+        code: u16,
+    ) {
+        let mut response = StunMessage::response(request, StunClass::ErrorResponse);
+        response.add_error_code(code, "");
+        // the checker only accepts a 487 with the integrity of a response.
+        if code == ERROR_ROLE_CONFLICT {
+            response.add_message_integrity(&get_short_term_key(&self.local_credentials.pwd));
+        }
+        response.add_fingerprint();
+        self.transmits
+            .push_back(Transmit::new(local, source, response.to_bytes()));
+    }
+
+    fn handle_check_response(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        response: StunMessage,
+        data: &[u8],
+        now: Instant,
+    ) {
+        let transaction_id = response.get_transaction_id();
+        let check = match self.checks.get(&transaction_id) {
+            Some(v) => *v,
+            None => return,
+        };
+        // the addresses have to be symmetric.
+        let pair = self.checklist.get_pair(check.pair);
+        if pair.local.base != local || pair.remote_address != source {
+            return;
+        }
+        let key = match self.remote_credentials {
+            Some(ref v) => get_short_term_key(&v.pwd),
+            None => return,
+        };
+        if verify_message_integrity(data, &key).is_err() {
+            return;
+        }
+        let response = match self
+            .transactions
+            .get_mut(&local)
+            .and_then(|v| v.handle_response(response, source, now))
+        {
+            Some(TransactionEvent::Response { message, .. }) => message,
+            _ => return,
+        };
+        self.checks.remove(&transaction_id);
+
+        let conflict = response.get_class() == StunClass::ErrorResponse
+            && response.get_error_code().ok().flatten().map(|v| v.code)
+                == Some(ERROR_ROLE_CONFLICT);
+        if conflict && !self.config.lite {
+            // switched already if a request of the peer came first.
+            if self.role == check.role {
+                let role = match check.role {
+                    IceRole::Controlling => IceRole::Controlled,
+                    IceRole::Controlled => IceRole::Controlling,
+                };
+                self.switch_role(role, now);
+            }
+            if !check.consent {
+                self.checklist.set_state(check.pair, PairState::Waiting);
+                self.checklist.push_triggered(check.pair);
+                self.schedule_check(now);
+            }
+            return;
+        }
+
+        if check.consent {
+            if response.get_class() == StunClass::SuccessResponse {
+                let component = self.checklist.get_pair(check.pair).get_component();
+                self.consent_at.insert(component, now);
+                let timeout = self.config.disconnected_timeout;
+                self.consent_stale = self
+                    .consent_at
+                    .values()
+                    .any(|v| now.saturating_duration_since(*v) >= timeout);
+            }
+            return;
+        }
+        if response.get_class() == StunClass::ErrorResponse {
+            self.fail_check(check);
+            return;
+        }
+
+        self.checklist.get_pair_mut(check.pair).valid = true;
+        self.checklist.set_state(check.pair, PairState::Succeeded);
+        if check.nominate || self.nominate_on_success.remove(&check.pair) {
+            self.select(check.pair, now);
+        }
+        if check.nominate {
+            let component = self.checklist.get_pair(check.pair).get_component();
+            if self.nominating.get(&component) == Some(&check.pair) {
+                self.nominating.remove(&component);
+            }
+        }
+        self.nominate(now);
+    }
+
+    /// RFC 8445 Section 7.3.1.1: whether the request has to be answered
+    /// with 487. Switches the role when the peer wins the tie-breaker.
+    fn has_role_conflict(&mut self, request: &StunMessage, now: Instant) -> bool {
+        let (attribute, role) = match self.role {
+            IceRole::Controlling => (ATTR_ICE_CONTROLLING, IceRole::Controlled),
+            IceRole::Controlled => (ATTR_ICE_CONTROLLED, IceRole::Controlling),
+        };
+        let tie_breaker = match request.get_u64_attribute(attribute) {
+            Ok(Some(v)) => v,
+            _ => return false,
+        };
+        // a lite agent never controls, so the full one has to.
+        if self.config.lite {
+            return true;
+        }
+        let wins = self.tie_breaker >= tie_breaker;
+        match self.role {
+            IceRole::Controlling if wins => true,
+            IceRole::Controlled if !wins => true,
+            _ => {
+                self.switch_role(role, now);
+                false
+            }
+        }
+    }
+
+    fn switch_role(&mut self, role: IceRole, now: Instant) {
+        self.role = role;
+        self.checklist.set_controlling(role == IceRole::Controlling);
+        // the nominations of the other role do not carry over.
+        self.nominating.clear();
+        self.nominate_on_success.clear();
+        self.nominations.clear();
+        self.events.push_back(IceEvent::RoleChanged(role));
+        self.nominate(now);
+    }
+
+    /// Whether a USE-CANDIDATE from the controlling agent is newer than the
+    /// nomination in use. Without renomination every nomination is.
+    fn accept_nomination(&mut self, index: usize, request: &StunMessage) -> bool {
+        if !self.config.renomination {
+            return true;
+        }
+        let component = self.checklist.get_pair(index).get_component();
+        if let Ok(Some(count)) = request.get_u32_attribute(ATTR_NOMINATION) {
+            let count = count & 0xff_ffff;
+            if self
+                .nominations
+                .get(&component)
+                .is_some_and(|v| *v >= count)
+            {
+                return false;
+            }
+            self.nominations.insert(component, count);
+        }
+        // an older nomination that has not succeeded yet is superseded.
+        let pairs = &self.checklist;
+        self.nominate_on_success
+            .retain(|v| pairs.get_pair(*v).get_component() != component);
+        true
+    }
+
+    fn fail_check(&mut self, check: Check) {
+        self.checklist.set_state(check.pair, PairState::Failed);
+        let component = self.checklist.get_pair(check.pair).get_component();
+        if self.nominating.get(&component) == Some(&check.pair) {
+            self.nominating.remove(&component);
+        }
+    }
+
+    /// Regular nomination: once no better pair may succeed, the best valid
+    /// pair is checked again with USE-CANDIDATE. With renomination a better
+    /// pair than the selected one is nominated too.
+    fn nominate(&mut self, now: Instant) {
+        if self.role != IceRole::Controlling || self.config.nomination != NominationMode::Regular {
+            return;
+        }
+        for component in 1..=self.config.gather.components.max(1) {
+            if self.nominating.contains_key(&component) {
+                continue;
+            }
+            let index = match self.checklist.get_best_valid(component) {
+                Some(v) => v,
+                None => continue,
+            };
+            let priority = self.checklist.get_pair(index).priority;
+            if let Some(current) = self.selected.get(&component) {
+                let better = self.checklist.get_pair(*current).priority < priority;
+                if !self.config.renomination || !better {
+                    continue;
+                }
+            }
+            if self.checklist.has_better_pending(component, priority) {
+                continue;
+            }
+            self.nominating.insert(component, index);
+            self.checklist.push_triggered(index);
+            self.schedule_check(now);
+        }
+    }
+
+    fn select(&mut self, index: usize, now: Instant) {
+        let pair = self.checklist.get_pair_mut(index);
+        pair.nominated = true;
+        let component = pair.get_component();
+        let priority = pair.priority;
+
+        // the controlled agent follows the latest nomination with
+        // renomination, and the best nominated pair otherwise.
+        let latest = self.config.renomination && self.role == IceRole::Controlled;
+        if let Some(current) = self.selected.get(&component) {
+            let better = self.checklist.get_pair(*current).priority > priority;
+            if *current == index || (better && !latest) {
+                return;
+            }
+        }
+        self.selected.insert(component, index);
+        self.sent_at.insert(component, now);
+        // a lite agent sends no checks, so no consent ones either.
+        if !self.config.lite {
+            self.consent_at.insert(component, now);
+            if self.next_consent_at.is_none() {
+                self.next_consent_at = Some(now + self.config.consent_interval);
+            }
+        }
+        let components = self.config.gather.components.max(1);
+        if (1..=components).all(|v| self.selected.contains_key(&v)) {
+            self.previous = None;
+        }
+        let pair = self.checklist.get_pair(index);
+        self.events.push_back(IceEvent::SelectedPairChanged {
+            component,
+            local: pair.local.candidate.clone(),
+            remote: pair.remote.clone(),
+        });
+    }
+
+    fn update_state(&mut self) {
+        if self.state == IceConnectionState::Closed {
+            return;
+        }
+        let components = self.config.gather.components.max(1);
+        let connected = (1..=components).all(|v| self.selected.contains_key(&v));
+        let finished = self.checklist.is_finished()
+            && self.checks.values().all(|v| v.consent)
+            && self.remote_end_of_candidates
+            && self.gatherer.get_state() == GatherState::Complete;
+
+        let state = if connected && self.consent_stale {
+            IceConnectionState::Disconnected
+        } else if connected && (finished || self.config.lite) {
+            IceConnectionState::Completed
+        } else if connected {
+            IceConnectionState::Connected
+        } else if finished
+            && !self.config.lite
+            && (1..=components).any(|v| self.checklist.get_best_valid(v).is_none())
+        {
+            IceConnectionState::Failed
+        } else {
+            return;
+        };
+        self.set_state(state);
+    }
+
+    fn set_state(&mut self, state: IceConnectionState) {
+        if self.state != state {
+            self.state = state;
+            self.events.push_back(IceEvent::StateChanged(state));
+        }
+    }
+
+    /// Hands what is sent from a relayed candidate to its allocation.
+    fn flush_relayed(&mut self, now: Instant) {
+        if self.transmits.is_empty() {
+            return;
+        }
+        let transmits: Vec<Transmit> = self.transmits.drain(..).collect();
+        for transmit in transmits {
+            let (source, destination) = (transmit.source, transmit.destination);
+            if self.gatherer.get_turn_client(source).is_some() {
+                self.gatherer
+                    .send_relayed(source, destination, &transmit.data, now);
+            } else if let Some(previous) = self
+                .previous
+                .as_mut()
+                .filter(|v| v.gatherer.get_turn_client(source).is_some())
+            {
+                previous
+                    .gatherer
+                    .send_relayed(source, destination, &transmit.data, now);
+            } else {
+                self.transmits.push_back(transmit);
+            }
+        }
+    }
+}
+
+// 受けたdatagramのdestinationはローカルアドレス．
+impl StateMachine for IceAgent {
+    type Input = Transmit;
+    type Output = IceOutput;
+    type Error = Infallible;
+
+    fn handle_input(
+        &mut self,
+        input: Transmit,
+        now: Instant,
+    ) -> std::result::Result<(), Infallible> {
+        self.handle_receive(input.destination, input.source, &input.data, now);
+        Ok(())
+    }
+
+    fn poll_output(&mut self, now: Instant) -> Option<IceOutput> {
+        if IceAgent::poll_timeout(self).is_some_and(|v| v <= now) {
+            self.process(now);
+        }
+        if let Some(v) = self.poll_transmit() {
+            return Some(IceOutput::Transmit(v));
+        }
+        if let Some(v) = self.poll_receive() {
+            return Some(IceOutput::Data(v));
+        }
+        self.poll_event().map(IceOutput::Event)
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        IceAgent::poll_timeout(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::ice::gatherer::test::FakeNetwork;
+    use crate::ice::network::NetworkInterface;
+    use crate::sansio::drain_outputs;
+
+    pub(crate) fn new_agent(
+        config: IceConfig,
+        role: IceRole,
+        addresses: &[&str],
+        now: Instant,
+    ) -> IceAgent {
+        let addresses = addresses.iter().map(|v| v.parse().unwrap()).collect();
+        let mut network = FakeNetwork::new(vec![NetworkInterface::new("eth0", 2, addresses)]);
+        let mut agent = IceAgent::new(config, role);
+        agent.gather(&mut network, now).unwrap();
+        agent
+    }
+
+    pub(crate) fn exchange(a: &mut IceAgent, b: &mut IceAgent, now: Instant) {
+        a.set_remote_credentials(b.get_local_credentials().clone(), now);
+        b.set_remote_credentials(a.get_local_credentials().clone(), now);
+        a.set_remote_candidates(b.get_local_candidates(), now);
+        b.set_remote_candidates(a.get_local_candidates(), now);
+    }
+
+    /// Delivers everything both agents send to each other.
+    pub(crate) fn deliver(a: &mut IceAgent, b: &mut IceAgent, now: Instant) {
+        // multicast reaches the mDNS socket.
+        let route = |to: &IceAgent, v: &Transmit| {
+            if v.destination.ip().is_multicast() {
+                to.get_mdns().map(|v| v.get_local_address())
+            } else {
+                Some(v.destination)
+            }
+        };
+        loop {
+            let mut delivered = false;
+            while let Some(v) = a.poll_transmit() {
+                if let Some(local) = route(b, &v) {
+                    b.handle_receive(local, v.source, &v.data, now);
+                }
+                delivered = true;
+            }
+            while let Some(v) = b.poll_transmit() {
+                if let Some(local) = route(a, &v) {
+                    a.handle_receive(local, v.source, &v.data, now);
+                }
+                delivered = true;
+            }
+            if !delivered {
+                break;
+            }
+        }
+    }
+
+    /// Runs both agents until `done` holds, and returns the time.
+    pub(crate) fn run_until<F: Fn(&IceAgent, &IceAgent) -> bool>(
+        a: &mut IceAgent,
+        b: &mut IceAgent,
+        mut now: Instant,
+        done: F,
+    ) -> Instant {
+        for _ in 0..10_000 {
+            deliver(a, b, now);
+            if done(a, b) {
+                return now;
+            }
+            let next = [a.poll_timeout(), b.poll_timeout()]
+                .iter()
+                .flatten()
+                .min()
+                .copied();
+            match next {
+                Some(v) => now = now.max(v),
+                None => return now,
+            }
+            a.process(now);
+            b.process(now);
+        }
+        now
+    }
+
+    pub(crate) fn is_connected(agent: &IceAgent) -> bool {
+        agent.get_state() == IceConnectionState::Connected
+            || agent.get_state() == IceConnectionState::Completed
+    }
+
+    #[test]
+    fn connectivity_check_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        exchange(&mut a, &mut b, now);
+
+        run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        assert!(is_connected(&a));
+        assert!(is_connected(&b));
+
+        let pair = a.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert!(pair.valid && pair.nominated);
+        assert_eq!(pair.remote_address, "10.0.0.2:50000".parse().unwrap());
+        let pair = b.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(pair.remote_address, "10.0.0.1:50000".parse().unwrap());
+
+        let events: Vec<IceEvent> = iter::from_fn(|| a.poll_event()).collect();
+        assert!(events.contains(&IceEvent::StateChanged(IceConnectionState::Checking)));
+        assert!(events.iter().any(|v| match v {
+            IceEvent::SelectedPairChanged { component, .. } => *component == COMPONENT_RTP,
+            _ => false,
+        }));
+
+        a.send(b"hello", now).unwrap();
+        deliver(&mut a, &mut b, now);
+        assert_eq!(b.poll_receive(), Some(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn state_machine_test() {
+        let mut now = Instant::now();
+        let mut agents = [
+            new_agent(
+                IceConfig::default(),
+                IceRole::Controlling,
+                &["10.0.0.1"],
+                now,
+            ),
+            new_agent(
+                IceConfig::default(),
+                IceRole::Controlled,
+                &["10.0.0.2"],
+                now,
+            ),
+        ];
+        let [a, b] = &mut agents;
+        exchange(a, b, now);
+        let mut received = vec![];
+        let mut sent = false;
+        for _ in 0..10_000 {
+            let mut busy = false;
+            for i in 0..2 {
+                for output in drain_outputs(&mut agents[i], now) {
+                    match output {
+                        IceOutput::Transmit(v) if !v.destination.ip().is_multicast() => {
+                            agents[1 - i].handle_input(v, now).unwrap();
+                        }
+                        IceOutput::Data(v) => received.push(v),
+                        _ => {}
+                    }
+                    busy = true;
+                }
+            }
+            if agents.iter().all(is_connected) && !sent {
+                agents[0].send(b"hello", now).unwrap();
+                sent = true;
+            } else if !busy && sent {
+                break;
+            } else if !busy {
+                let timeouts = agents.iter().map(StateMachine::poll_timeout);
+                now = now.max(timeouts.flatten().min().unwrap());
+            }
+        }
+        assert_eq!(received, vec![b"hello".to_vec()]);
+    }
+
+    #[test]
+    fn check_request_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        let mut remote = b.get_local_candidates();
+        let mut second = remote[0].clone();
+        second.port = 50001;
+        second.priority -= 1;
+        remote.push(second);
+        a.set_remote_credentials(b.get_local_credentials().clone(), now);
+        a.set_remote_candidates(remote, now);
+        assert_eq!(a.poll_timeout(), Some(now));
+
+        // one check every Ta.
+        a.process(now);
+        let request = a.poll_transmit().unwrap();
+        assert_eq!(a.poll_transmit(), None);
+        assert_eq!(a.get_state(), IceConnectionState::Checking);
+        a.process(now + Duration::from_millis(50));
+        assert!(a.poll_transmit().is_some());
+
+        let message = StunMessage::from_bytes(&request.data).unwrap();
+        let username = format!(
+            "{}:{}",
+            b.get_local_credentials().ufrag,
+            a.get_local_credentials().ufrag
+        );
+        assert_eq!(
+            message.get_string_attribute(ATTR_USERNAME),
+            Ok(Some(username))
+        );
+        assert!(message.has_attribute(ATTR_ICE_CONTROLLING));
+        assert!(message.has_attribute(ATTR_PRIORITY));
+        assert!(!message.has_attribute(ATTR_USE_CANDIDATE));
+        assert!(verify_fingerprint(&request.data).is_ok());
+        assert!(verify_message_integrity(
+            &request.data,
+            &get_short_term_key(&b.get_local_credentials().pwd)
+        )
+        .is_ok());
+
+        // a request with wrong credentials is refused.
+        let mut wrong = StunMessage::request(METHOD_BINDING);
+        wrong.add_attribute(ATTR_USERNAME, b"nobody:x".to_vec());
+        wrong.add_message_integrity(b"wrong");
+        wrong.add_fingerprint();
+        b.handle_receive(request.destination, request.source, &wrong.to_bytes(), now);
+        let response = b.poll_transmit().unwrap();
+        let response = StunMessage::from_bytes(&response.data).unwrap();
+        assert_eq!(response.get_class(), StunClass::ErrorResponse);
+        assert_eq!(
+            response.get_error_code().unwrap().unwrap().code,
+            ERROR_UNAUTHENTICATED
+        );
+    }
+
+    #[test]
+    fn aggressive_nomination_test() {
+        let now = Instant::now();
+        let config = IceConfig {
+            nomination: NominationMode::Aggressive,
+            ..IceConfig::default()
+        };
+        let mut a = new_agent(config, IceRole::Controlling, &["10.0.0.1"], now);
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        exchange(&mut a, &mut b, now);
+
+        a.process(now);
+        let request = a.poll_transmit().unwrap();
+        let message = StunMessage::from_bytes(&request.data).unwrap();
+        assert!(message.has_attribute(ATTR_USE_CANDIDATE));
+        assert!(!message.has_attribute(ATTR_NOMINATION));
+        b.handle_receive(request.destination, request.source, &request.data, now);
+
+        run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        assert!(is_connected(&a));
+        assert!(is_connected(&b));
+        assert_eq!(
+            b.get_selected_pair(COMPONENT_RTP).unwrap().remote_address,
+            request.source
+        );
+    }
+
+    #[test]
+    fn renomination_test() {
+        let now = Instant::now();
+        let config = IceConfig {
+            renomination: true,
+            ..IceConfig::default()
+        };
+        let mut a = new_agent(config.clone(), IceRole::Controlling, &["10.0.0.1"], now);
+        let mut b = new_agent(config, IceRole::Controlled, &["10.0.0.2", "10.0.0.4"], now);
+        a.set_remote_credentials(b.get_local_credentials().clone(), now);
+        b.set_remote_credentials(a.get_local_credentials().clone(), now);
+        let candidates = b.get_local_candidates();
+        let (better, worse) = (candidates[0].clone(), candidates[1].clone());
+        let (better, worse) = (better.get_socket_addr(), worse.get_socket_addr());
+
+        // only the worse candidate is known at first.
+        a.add_remote_candidate(candidates[1].clone(), now);
+        let now = run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        let selected = |agent: &IceAgent| agent.get_selected_pair(COMPONENT_RTP).cloned();
+        assert_eq!(selected(&a).map(|v| v.remote_address), worse);
+        assert_eq!(selected(&b).map(|v| Some(v.local.base)), Some(worse));
+
+        // the better pair is nominated again, and the controlled agent follows.
+        a.add_remote_candidate(candidates[0].clone(), now);
+        run_until(&mut a, &mut b, now, |a, b| {
+            selected(a).map(|v| Some(v.remote_address)) == Some(better)
+                && selected(b).map(|v| Some(v.local.base)) == Some(better)
+        });
+        assert_eq!(selected(&a).map(|v| v.remote_address), better);
+        assert_eq!(selected(&b).map(|v| v.local.base), better);
+        assert!(is_connected(&b));
+    }
+
+    #[test]
+    fn trickle_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        a.set_remote_credentials(b.get_local_credentials().clone(), now);
+        b.set_remote_credentials(a.get_local_credentials().clone(), now);
+
+        // an unreachable candidate fails, but more may come.
+        let unreachable =
+            IceCandidate::parse("candidate:9 1 udp 2130706431 10.0.0.9 9 typ host").unwrap();
+        assert!(a.add_remote_candidate(unreachable.clone(), now));
+        assert!(!a.add_remote_candidate(unreachable, now));
+        let mut t = now;
+        while let Some(v) = a.poll_timeout() {
+            t = v;
+            a.process(t);
+            while a.poll_transmit().is_some() {}
+        }
+        assert_eq!(a.get_checklist().get_pair(0).state, PairState::Failed);
+        assert_eq!(a.get_state(), IceConnectionState::Checking);
+
+        for candidate in b.get_local_candidates() {
+            a.add_remote_candidate(candidate, t);
+        }
+        let t = run_until(&mut a, &mut b, t, |a, b| is_connected(a) && is_connected(b));
+        assert_eq!(a.get_state(), IceConnectionState::Connected);
+        assert_eq!(a.get_checklist().get_pairs().len(), 2);
+
+        a.set_remote_end_of_candidates();
+        a.process(t);
+        assert_eq!(a.get_state(), IceConnectionState::Completed);
+        assert!(a.has_remote_end_of_candidates());
+        assert!(!b.has_remote_end_of_candidates());
+    }
+
+    #[test]
+    fn restart_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        exchange(&mut a, &mut b, now);
+        let now = run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        let old = a.get_selected_pair(COMPONENT_RTP).unwrap().clone();
+        let credentials = a.get_local_credentials().clone();
+        while a.poll_event().is_some() {}
+
+        let interfaces = |address: &str| {
+            let mut network = FakeNetwork::new(vec![NetworkInterface::new(
+                "eth0",
+                2,
+                vec![address.parse().unwrap()],
+            )]);
+            network.next_port = 51000;
+            network
+        };
+        a.restart(&mut interfaces("10.0.0.1"), now).unwrap();
+        b.restart(&mut interfaces("10.0.0.2"), now).unwrap();
+        assert_ne!(a.get_local_credentials(), &credentials);
+        assert!(a.is_restarting());
+        assert!(a.get_checklist().is_empty());
+        assert_eq!(a.get_remote_credentials(), None);
+
+        // the previous pair keeps carrying data.
+        assert_eq!(a.get_selected_pair(COMPONENT_RTP), Some(&old));
+        a.send(b"before", now).unwrap();
+        deliver(&mut a, &mut b, now);
+        assert_eq!(b.poll_receive(), Some(b"before".to_vec()));
+
+        exchange(&mut a, &mut b, now);
+        let now = run_until(&mut a, &mut b, now, |a, b| {
+            !a.is_restarting() && !b.is_restarting()
+        });
+        let new = a.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(new.local.base, "10.0.0.1:51000".parse().unwrap());
+        assert_eq!(new.remote_address, "10.0.0.2:51000".parse().unwrap());
+        let events: Vec<IceEvent> = iter::from_fn(|| a.poll_event()).collect();
+        assert!(events
+            .iter()
+            .any(|v| matches!(v, IceEvent::SelectedPairChanged { .. })));
+        assert!(!events.contains(&IceEvent::StateChanged(IceConnectionState::Checking)));
+        assert!(is_connected(&a));
+
+        a.send(b"after", now).unwrap();
+        let transmit = a.poll_transmit().unwrap();
+        assert_eq!(transmit.source, "10.0.0.1:51000".parse().unwrap());
+    }
+
+    #[test]
+    fn network_change_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        exchange(&mut a, &mut b, now);
+        let now = run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        let credentials = a.get_local_credentials().clone();
+
+        let eth0 = NetworkInterface::new("eth0", 2, vec!["10.0.0.1".parse().unwrap()]);
+        let mut lo = NetworkInterface::new("lo", 1, vec!["127.0.0.1".parse().unwrap()]);
+        lo.is_loopback = true;
+        let mut network = FakeNetwork::new(vec![eth0.clone()]);
+        network.next_port = 51000;
+        // neither of them is gathered.
+        network.changes.push_back(NetworkChange::AddressAdded {
+            interface: lo.clone(),
+            address: "127.0.0.1".parse().unwrap(),
+        });
+        network.changes.push_back(NetworkChange::AddressRemoved {
+            interface: eth0.clone(),
+            address: "10.0.0.9".parse().unwrap(),
+        });
+        assert!(!a.handle_network_changes(&mut network, now).unwrap());
+        assert!(network.changes.is_empty());
+        assert_eq!(a.get_local_credentials(), &credentials);
+
+        network.changes.push_back(NetworkChange::AddressRemoved {
+            interface: eth0,
+            address: "10.0.0.1".parse().unwrap(),
+        });
+        assert!(a.handle_network_changes(&mut network, now).unwrap());
+        assert!(a.is_restarting());
+        assert_ne!(a.get_local_credentials(), &credentials);
+        assert_eq!(
+            a.get_gatherer().get_local_candidates()[0].base,
+            "10.0.0.1:51000".parse().unwrap()
+        );
+
+        a.close();
+        network.changes.push_back(NetworkChange::AddressAdded {
+            interface: NetworkInterface::new("wlan0", 3, vec![]),
+            address: "10.0.1.1".parse().unwrap(),
+        });
+        assert!(!a.handle_network_changes(&mut network, now).unwrap());
+    }
+
+    #[test]
+    fn lite_test() {
+        let now = Instant::now();
+        let mut config = IceConfig {
+            lite: true,
+            ..IceConfig::default()
+        };
+        config.gather.stun_servers = vec!["192.0.2.1:3478".parse().unwrap()];
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(config, IceRole::Controlling, &["10.0.0.2"], now);
+        assert_eq!(b.get_role(), IceRole::Controlled);
+        assert_eq!(b.get_local_candidates().len(), 1);
+        assert_eq!(b.get_gatherer().get_state(), GatherState::Complete);
+
+        a.set_remote_lite();
+        assert_eq!(a.get_role(), IceRole::Controlling);
+        exchange(&mut a, &mut b, now);
+
+        // the lite agent only answers.
+        b.process(now);
+        assert_eq!(b.poll_transmit(), None);
+        assert_eq!(b.poll_timeout(), None);
+
+        run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        assert!(is_connected(&a));
+        assert_eq!(b.get_state(), IceConnectionState::Completed);
+        let pair = b.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(pair.remote_address, "10.0.0.1:50000".parse().unwrap());
+        assert!(pair.valid && pair.nominated);
+
+        b.send(b"lite", now).unwrap();
+        deliver(&mut a, &mut b, now);
+        assert_eq!(a.poll_receive(), Some(b"lite".to_vec()));
+    }
+
+    #[test]
+    fn mdns_test() {
+        let now = Instant::now();
+        let mut config = IceConfig::default();
+        config.gather.mdns = MdnsMode::QueryAndGather;
+        let mut a = new_agent(config.clone(), IceRole::Controlling, &["10.0.0.1"], now);
+        let mut b = new_agent(config, IceRole::Controlled, &["10.0.0.2"], now);
+        let candidate = &a.get_local_candidates()[0];
+        assert!(is_mdns_hostname(&candidate.address));
+        assert_eq!(candidate.get_socket_addr(), None);
+        assert!(a.get_mdns().is_some());
+        exchange(&mut a, &mut b, now);
+
+        run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        let pair = a.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(pair.remote_address, "10.0.0.2:50000".parse().unwrap());
+        assert_eq!(pair.remote, b.get_local_candidates()[0]);
+        assert_eq!(b.get_checklist().get_pairs().len(), 1);
+        assert_eq!(
+            b.get_checklist().get_pair(0).remote.candidate_type,
+            CandidateType::Host
+        );
+    }
+
+    fn tcp_agents(local: TcpType, remote: TcpType, now: Instant) -> (IceAgent, IceAgent) {
+        let config = |tcp_type| {
+            let mut config = IceConfig::default();
+            config.gather.tcp_types = vec![tcp_type];
+            config
+        };
+        let mut a = new_agent(config(local), IceRole::Controlling, &["10.0.0.1"], now);
+        let mut b = new_agent(config(remote), IceRole::Controlled, &["10.0.0.2"], now);
+        a.set_remote_credentials(b.get_local_credentials().clone(), now);
+        b.set_remote_credentials(a.get_local_credentials().clone(), now);
+        let tcp = |agent: &IceAgent| {
+            agent
+                .get_local_candidates()
+                .into_iter()
+                .filter(|v| v.transport == TransportProtocol::Tcp)
+                .collect()
+        };
+        let (candidates_a, candidates_b) = (tcp(&a), tcp(&b));
+        a.set_remote_candidates(candidates_b, now);
+        b.set_remote_candidates(candidates_a, now);
+        (a, b)
+    }
+
+    fn poll_tcp_connect(agent: &mut IceAgent) -> Option<(SocketAddr, SocketAddr)> {
+        iter::from_fn(|| agent.poll_event()).find_map(|v| match v {
+            IceEvent::TcpConnect { local, remote } => Some((local, remote)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn tcp_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = tcp_agents(TcpType::Active, TcpType::Passive, now);
+        let active = &a.get_local_candidates()[1];
+        assert_eq!(active.tcp_type, Some(TcpType::Active));
+        assert_eq!(active.port, 9);
+        assert!(active.priority < a.get_local_candidates()[0].priority);
+        // the passive side waits for the connection.
+        assert!(b.get_checklist().is_empty());
+
+        a.process(now);
+        assert_eq!(a.poll_transmit(), None);
+        let (base, remote) = poll_tcp_connect(&mut a).unwrap();
+        assert_eq!(base, "10.0.0.1:9".parse().unwrap());
+        assert_eq!(remote, "10.0.0.2:50001".parse().unwrap());
+        let stream: SocketAddr = "10.0.0.1:60000".parse().unwrap();
+        a.handle_tcp_connected(base, remote, stream, now);
+
+        let now = run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        let pair = a.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(pair.local.base, stream);
+        let pair = b.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(pair.remote.candidate_type, CandidateType::PeerReflexive);
+        assert_eq!(pair.remote.tcp_type, Some(TcpType::Active));
+
+        a.send(b"hello", now).unwrap();
+        let transmit = a.poll_transmit().unwrap();
+        assert_eq!(&transmit.data[..2], &[0, 5]);
+        // a frame may arrive in pieces.
+        b.handle_receive(
+            transmit.destination,
+            transmit.source,
+            &transmit.data[..3],
+            now,
+        );
+        assert_eq!(b.poll_receive(), None);
+        b.handle_receive(
+            transmit.destination,
+            transmit.source,
+            &transmit.data[3..],
+            now,
+        );
+        assert_eq!(b.poll_receive(), Some(b"hello".to_vec()));
+
+        // a datagram the length prefix cannot carry is dropped, not truncated.
+        a.send(&[0; 65536], now).unwrap();
+        a.send(b"next", now).unwrap();
+        let transmit = a.poll_transmit().unwrap();
+        assert_eq!(&transmit.data[..], b"\x00\x04next");
+        assert_eq!(a.poll_transmit(), None);
+
+        a.handle_tcp_closed(stream, remote);
+        assert_eq!(a.send(b"closed", now), Err(IceError::NoSelectedPair));
+    }
+
+    #[test]
+    fn tcp_simultaneous_open_test() {
+        let now = Instant::now();
+        let so = TcpType::SimultaneousOpen;
+        let (mut a, mut b) = tcp_agents(so, so, now);
+        a.process(now);
+        b.process(now);
+        let (base_a, remote_a) = poll_tcp_connect(&mut a).unwrap();
+        let (base_b, remote_b) = poll_tcp_connect(&mut b).unwrap();
+        assert_eq!((base_a, remote_a), (remote_b, base_b));
+
+        // only the connection of a is established, b accepts it instead.
+        a.handle_tcp_connected(base_a, remote_a, base_a, now);
+        run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        assert!(is_connected(&a) && is_connected(&b));
+        b.handle_tcp_connect_failed(base_b, remote_b);
+        let pair = b.get_selected_pair(COMPONENT_RTP).unwrap();
+        assert_eq!(pair.local.base, base_b);
+        assert_eq!(pair.remote.tcp_type, Some(so));
+    }
+
+    #[test]
+    fn consent_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(
+            IceConfig::default(),
+            IceRole::Controlled,
+            &["10.0.0.2"],
+            now,
+        );
+        exchange(&mut a, &mut b, now);
+        let connected = run_until(&mut a, &mut b, now, |a, b| {
+            a.get_state() == IceConnectionState::Completed && is_connected(b)
+        });
+
+        // consent checks every 4 to 6 seconds, answered by b.
+        let mut t = connected;
+        let mut last = None;
+        for _ in 0..5 {
+            let request = loop {
+                t = a.poll_timeout().unwrap();
+                a.process(t);
+                b.process(t);
+                if let Some(v) = a.poll_transmit() {
+                    break v;
+                }
+            };
+            let message = StunMessage::from_bytes(&request.data).unwrap();
+            assert!(!message.has_attribute(ATTR_USE_CANDIDATE));
+            if let Some(last) = last {
+                let interval = t - last;
+                assert!(interval >= Duration::from_secs(4) && interval <= Duration::from_secs(6));
+            }
+            last = Some(t);
+            b.handle_receive(request.destination, request.source, &request.data, t);
+            deliver(&mut a, &mut b, t);
+            assert_eq!(a.get_state(), IceConnectionState::Completed);
+        }
+
+        // b stops answering.
+        while a.poll_event().is_some() {}
+        let mut states = vec![];
+        while a.get_state() != IceConnectionState::Failed {
+            t = a.poll_timeout().unwrap();
+            a.process(t);
+            while a.poll_transmit().is_some() {}
+            states.extend(iter::from_fn(|| a.poll_event()));
+        }
+        assert_eq!(
+            states,
+            vec![
+                IceEvent::StateChanged(IceConnectionState::Disconnected),
+                IceEvent::StateChanged(IceConnectionState::Failed)
+            ]
+        );
+        assert!(t - last.unwrap() >= Duration::from_secs(25));
+        assert_eq!(a.send(b"data", t), Err(IceError::NoSelectedPair));
+    }
+
+    #[test]
+    fn keepalive_test() {
+        let now = Instant::now();
+        let config = IceConfig {
+            lite: true,
+            ..IceConfig::default()
+        };
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        let mut b = new_agent(config, IceRole::Controlled, &["10.0.0.2"], now);
+        a.set_remote_lite();
+        exchange(&mut a, &mut b, now);
+        let connected = run_until(&mut a, &mut b, now, |a, b| {
+            is_connected(a) && b.get_state() == IceConnectionState::Completed
+        });
+
+        // the lite agent keeps the binding alive with indications.
+        assert_eq!(b.poll_timeout(), Some(connected + Duration::from_secs(15)));
+        b.send(b"data", connected + Duration::from_secs(5)).unwrap();
+        while b.poll_transmit().is_some() {}
+        let t = b.poll_timeout().unwrap();
+        assert_eq!(t, connected + Duration::from_secs(20));
+        b.process(t);
+        let keepalive = b.poll_transmit().unwrap();
+        assert_eq!(keepalive.source, "10.0.0.2:50000".parse().unwrap());
+        assert_eq!(keepalive.destination, "10.0.0.1:50000".parse().unwrap());
+        let message = StunMessage::from_bytes(&keepalive.data).unwrap();
+        assert_eq!(message.get_class(), StunClass::Indication);
+        assert!(verify_fingerprint(&keepalive.data).is_ok());
+        assert_eq!(b.poll_transmit(), None);
+        assert_eq!(b.poll_timeout(), Some(t + Duration::from_secs(15)));
+        a.handle_receive(keepalive.destination, keepalive.source, &keepalive.data, t);
+        assert_eq!(a.poll_receive(), None);
+
+        // the consent checks of the full agent postpone its keepalives.
+        let mut t = connected;
+        while t < connected + Duration::from_secs(60) {
+            t = a.poll_timeout().unwrap();
+            a.process(t);
+            while let Some(v) = a.poll_transmit() {
+                let message = StunMessage::from_bytes(&v.data).unwrap();
+                assert_eq!(message.get_class(), StunClass::Request);
+                b.handle_receive(v.destination, v.source, &v.data, t);
+            }
+            deliver(&mut a, &mut b, t);
+        }
+        assert!(is_connected(&a));
+    }
+
+    #[test]
+    fn role_conflict_test() {
+        for role in [IceRole::Controlling, IceRole::Controlled].iter() {
+            let now = Instant::now();
+            let mut a = new_agent(IceConfig::default(), *role, &["10.0.0.1"], now);
+            let mut b = new_agent(IceConfig::default(), *role, &["10.0.0.2"], now);
+            a.tie_breaker = 1;
+            b.tie_breaker = 2;
+            exchange(&mut a, &mut b, now);
+
+            // the first check of a is answered with 487 by b when both
+            // control, and switches b when both are controlled.
+            a.process(now);
+            let request = a.poll_transmit().unwrap();
+            b.handle_receive(request.destination, request.source, &request.data, now);
+            let response = StunMessage::from_bytes(&b.poll_transmit().unwrap().data).unwrap();
+            if *role == IceRole::Controlling {
+                assert_eq!(response.get_class(), StunClass::ErrorResponse);
+                assert_eq!(
+                    response.get_error_code().unwrap().unwrap().code,
+                    ERROR_ROLE_CONFLICT
+                );
+                let data = response.to_bytes();
+                a.handle_receive(request.source, request.destination, &data, now);
+                assert_eq!(a.get_role(), IceRole::Controlled);
+                assert_eq!(a.get_checklist().get_pair(0).state, PairState::Waiting);
+            } else {
+                assert_eq!(response.get_class(), StunClass::SuccessResponse);
+                assert_eq!(b.get_role(), IceRole::Controlling);
+                let data = response.to_bytes();
+                a.handle_receive(request.source, request.destination, &data, now);
+            }
+
+            run_until(&mut a, &mut b, now, |a, b| {
+                is_connected(a) && is_connected(b)
+            });
+            assert!(is_connected(&a) && is_connected(&b));
+            assert_eq!(a.get_role(), IceRole::Controlled);
+            assert_eq!(b.get_role(), IceRole::Controlling);
+            let pair = a.get_selected_pair(COMPONENT_RTP).unwrap();
+            assert!(pair.valid && pair.nominated);
+            let (a_events, b_events): (Vec<_>, Vec<_>) = (
+                iter::from_fn(|| a.poll_event()).collect(),
+                iter::from_fn(|| b.poll_event()).collect(),
+            );
+            let changed = |events: &[IceEvent]| {
+                events
+                    .iter()
+                    .filter(|v| matches!(v, IceEvent::RoleChanged(_)))
+                    .count()
+            };
+            if *role == IceRole::Controlling {
+                assert_eq!(changed(&a_events), 1);
+                assert_eq!(changed(&b_events), 0);
+            } else {
+                assert_eq!(changed(&a_events), 0);
+                assert_eq!(changed(&b_events), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn check_failure_test() {
+        let now = Instant::now();
+        let mut a = new_agent(
+            IceConfig::default(),
+            IceRole::Controlling,
+            &["10.0.0.1"],
+            now,
+        );
+        a.set_remote_credentials(IceCredentials::new("ufrag", "password"), now);
+        a.set_remote_candidates(
+            vec![IceCandidate::parse("candidate:1 1 udp 2130706431 10.0.0.9 9 typ host").unwrap()],
+            now,
+        );
+        let mut t = now;
+        while let Some(v) = a.poll_timeout() {
+            t = v;
+            a.process(t);
+            while a.poll_transmit().is_some() {}
+        }
+        assert!(t > now);
+        assert_eq!(a.get_state(), IceConnectionState::Failed);
+        assert_eq!(a.get_checklist().get_pair(0).state, PairState::Failed);
+        assert_eq!(a.send(b"data", t), Err(IceError::NoSelectedPair));
+    }
+}
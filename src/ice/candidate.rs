@@ -0,0 +1,375 @@
+// https://tools.ietf.org/html/rfc8839#section-5.1
+// https://tools.ietf.org/html/rfc8445#section-5.1.2
+
+/*
+    candidate-attribute = "candidate" ":" foundation SP component-id SP
+                          transport SP priority SP
+                          connection-address SP port SP
+                          "typ" SP cand-type
+                          [SP "raddr" SP connection-address]
+                          [SP "rport" SP port]
+                          [SP "tcptype" SP tcp-type]
+                          *(SP extension-att-name SP extension-att-value)
+
+    priority = (2^24) * type preference
+             + (2^8)  * local preference
+             + (2^0)  * (256 - component ID)
+*/
+
+use crate::ice::{IceError, Result};
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+pub const COMPONENT_RTP: u16 = 1;
+pub const COMPONENT_RTCP: u16 = 2;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CandidateType {
+    Host,
+    ServerReflexive,
+    PeerReflexive,
+    Relay,
+}
+
+impl CandidateType {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            CandidateType::Host => "host",
+            CandidateType::ServerReflexive => "srflx",
+            CandidateType::PeerReflexive => "prflx",
+            CandidateType::Relay => "relay",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<CandidateType> {
+        match name {
+            "host" => Some(CandidateType::Host),
+            "srflx" => Some(CandidateType::ServerReflexive),
+            "prflx" => Some(CandidateType::PeerReflexive),
+            "relay" => Some(CandidateType::Relay),
+            _ => None,
+        }
+    }
+
+    /// The recommended type preference of RFC 8445 Section 5.1.2.2.
+    pub fn get_preference(self) -> u32 {
+        match self {
+            CandidateType::Host => 126,
+            CandidateType::PeerReflexive => 110,
+            CandidateType::ServerReflexive => 100,
+            CandidateType::Relay => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum TransportProtocol {
+    Udp,
+    Tcp,
+}
+
+impl TransportProtocol {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            TransportProtocol::Udp => "udp",
+            TransportProtocol::Tcp => "tcp",
+        }
+    }
+}
+
+/// RFC 6544 Section 4.5
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum TcpType {
+    Active,
+    Passive,
+    SimultaneousOpen,
+}
+
+impl TcpType {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            TcpType::Active => "active",
+            TcpType::Passive => "passive",
+            TcpType::SimultaneousOpen => "so",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TcpType> {
+        match name {
+            "active" => Some(TcpType::Active),
+            "passive" => Some(TcpType::Passive),
+            "so" => Some(TcpType::SimultaneousOpen),
+            _ => None,
+        }
+    }
+}
+
+pub fn compute_priority(
+    candidate_type: CandidateType,
+    local_preference: u16,
+    component: u16,
+) -> u32 {
+    (candidate_type.get_preference() << 24)
+        + (u32::from(local_preference) << 8)
+        + (256 - u32::from(component.clamp(1, 256)))
+}
+
+// SDPのa=candidate．addressはmDNSのhostnameの場合もあるので文字列で持つ．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IceCandidate {
+    pub foundation: String,
+    pub component: u16,
+    pub transport: TransportProtocol,
+    pub priority: u32,
+    pub address: String,
+    pub port: u16,
+    pub candidate_type: CandidateType,
+    pub related_address: Option<String>,
+    pub related_port: Option<u16>,
+    pub tcp_type: Option<TcpType>,
+    pub extensions: Vec<(String, String)>,
+}
+
+impl IceCandidate {
+    pub fn new(
+        foundation: &str,
+        component: u16,
+        transport: TransportProtocol,
+        priority: u32,
+        address: SocketAddr,
+        candidate_type: CandidateType,
+    ) -> Self {
+        IceCandidate {
+            foundation: foundation.to_string(),
+            component,
+            transport,
+            priority,
+            address: address.ip().to_string(),
+            port: address.port(),
+            candidate_type,
+            related_address: None,
+            related_port: None,
+            tcp_type: None,
+            extensions: vec![],
+        }
+    }
+
+    /// Returns the address, or `None` for a hostname.
+    pub fn get_socket_addr(&self) -> Option<SocketAddr> {
+        self.address
+            .parse::<IpAddr>()
+            .ok()
+            .map(|ip| SocketAddr::new(ip, self.port))
+    }
+
+    pub fn get_related_socket_addr(&self) -> Option<SocketAddr> {
+        let ip = self.related_address.as_ref()?.parse::<IpAddr>().ok()?;
+        Some(SocketAddr::new(ip, self.related_port?))
+    }
+
+    pub fn set_related_address(&mut self, addr: SocketAddr) {
+        self.related_address = Some(addr.ip().to_string());
+        self.related_port = Some(addr.port());
+    }
+
+    pub fn get_extension(&self, name: &str) -> Option<&str> {
+        self.extensions
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parses a candidate attribute, with or without the `a=candidate:` or
+    /// `candidate:` prefix.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = |reason: &str| IceError::InvalidCandidate {
+            reason: reason.to_string(),
+        };
+
+        let value = value.trim();
+        let value = value.strip_prefix("a=").unwrap_or(value);
+        let value = value.strip_prefix("candidate:").unwrap_or(value);
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        if fields.len() < 8 {
+            return Err(invalid("too few fields"));
+        }
+
+        let foundation = fields[0];
+        if foundation.is_empty() || foundation.len() > 32 {
+            return Err(invalid("foundation"));
+        }
+        let component = fields[1]
+            .parse()
+            .ok()
+            .filter(|v| (1..=256).contains(v))
+            .ok_or_else(|| invalid("component"))?;
+        let transport = match fields[2].to_ascii_lowercase().as_str() {
+            "udp" => TransportProtocol::Udp,
+            "tcp" => TransportProtocol::Tcp,
+            _ => return Err(invalid("transport")),
+        };
+        let priority = fields[3].parse().map_err(|_| invalid("priority"))?;
+        let address = fields[4].to_string();
+        let port = fields[5].parse().map_err(|_| invalid("port"))?;
+        if fields[6] != "typ" {
+            return Err(invalid("typ"));
+        }
+        let candidate_type = CandidateType::from_name(fields[7]).ok_or_else(|| invalid("type"))?;
+
+        let mut candidate = IceCandidate {
+            foundation: foundation.to_string(),
+            component,
+            transport,
+            priority,
+            address,
+            port,
+            candidate_type,
+            related_address: None,
+            related_port: None,
+            tcp_type: None,
+            extensions: vec![],
+        };
+
+        let mut rest = fields[8..].chunks(2);
+        for pair in &mut rest {
+            let (name, value) = match pair {
+                [name, value] => (*name, *value),
+                _ => return Err(invalid("extension without a value")),
+            };
+            match name {
+                "raddr" => candidate.related_address = Some(value.to_string()),
+                "rport" => {
+                    candidate.related_port = Some(value.parse().map_err(|_| invalid("rport"))?)
+                }
+                "tcptype" => {
+                    candidate.tcp_type =
+                        Some(TcpType::from_name(value).ok_or_else(|| invalid("tcptype"))?)
+                }
+                _ => candidate
+                    .extensions
+                    .push((name.to_string(), value.to_string())),
+            }
+        }
+
+        Ok(candidate)
+    }
+}
+
+impl fmt::Display for IceCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "candidate:{} {} {} {} {} {} typ {}",
+            self.foundation,
+            self.component,
+            self.transport.get_name(),
+            self.priority,
+            self.address,
+            self.port,
+            self.candidate_type.get_name()
+        )?;
+        if let Some(ref v) = self.related_address {
+            write!(f, " raddr {}", v)?;
+        }
+        if let Some(v) = self.related_port {
+            write!(f, " rport {}", v)?;
+        }
+        if let Some(v) = self.tcp_type {
+            write!(f, " tcptype {}", v.get_name())?;
+        }
+        for (name, value) in &self.extensions {
+            write!(f, " {} {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_candidate_test() {
+        let cases = vec![
+            "candidate:2665802302 1 udp 2122262783 2a02:a03f:3eb0:e000:b0aa:d60a:cff2:933c 38475 typ host generation 0 network-id 2 network-cost 10",
+            "candidate:3496416974 1 tcp 1518283007 192.168.99.58 9 typ host tcptype active generation 0",
+            "candidate:842163049 1 udp 1677729535 203.0.113.7 60769 typ srflx raddr 192.168.99.58 rport 45076 generation 0",
+            "candidate:1 1 udp 2122260223 2a7b4c28-b7d8-414e-9ab3-c6e9df1a9c86.local 53411 typ host",
+        ];
+        for line in cases {
+            let candidate = IceCandidate::parse(line).unwrap();
+            assert_eq!(candidate.to_string(), line);
+        }
+
+        let candidate = IceCandidate::parse(
+            "a=candidate:842163049 1 UDP 1677729535 203.0.113.7 60769 typ srflx raddr 192.168.99.58 rport 45076 generation 0",
+        )
+        .unwrap();
+        assert_eq!(candidate.transport, TransportProtocol::Udp);
+        assert_eq!(candidate.candidate_type, CandidateType::ServerReflexive);
+        assert_eq!(
+            candidate.get_socket_addr(),
+            Some("203.0.113.7:60769".parse().unwrap())
+        );
+        assert_eq!(
+            candidate.get_related_socket_addr(),
+            Some("192.168.99.58:45076".parse().unwrap())
+        );
+        assert_eq!(candidate.get_extension("generation"), Some("0"));
+
+        let mdns = IceCandidate::parse(
+            "candidate:1 1 udp 2122260223 2a7b4c28-b7d8-414e-9ab3-c6e9df1a9c86.local 53411 typ host",
+        )
+        .unwrap();
+        assert_eq!(mdns.get_socket_addr(), None);
+
+        for line in &[
+            "candidate:1 1 udp 1 1.2.3.4 5 host",
+            "candidate:1 0 udp 1 1.2.3.4 5 typ host",
+            "candidate:1 1 sctp 1 1.2.3.4 5 typ host",
+            "candidate:1 1 udp 1 1.2.3.4 5 typ unknown",
+            "candidate:1 1 udp 1 1.2.3.4 5 typ host generation",
+            "candidate:1 1 tcp 1 1.2.3.4 5 typ host tcptype wrong",
+        ] {
+            assert!(IceCandidate::parse(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn priority_test() {
+        assert_eq!(
+            compute_priority(CandidateType::Host, 65535, 1),
+            2_130_706_431
+        );
+        assert_eq!(
+            compute_priority(CandidateType::Host, 65535, 2),
+            2_130_706_430
+        );
+        assert_eq!(
+            compute_priority(CandidateType::ServerReflexive, 65535, 1),
+            1_694_498_815
+        );
+        assert_eq!(compute_priority(CandidateType::Relay, 65535, 1), 16_777_215);
+
+        let addr = "10.0.0.1:5000".parse().unwrap();
+        let priority = compute_priority(CandidateType::Host, 65535, COMPONENT_RTP);
+        let mut candidate = IceCandidate::new(
+            "1",
+            COMPONENT_RTP,
+            TransportProtocol::Udp,
+            priority,
+            addr,
+            CandidateType::Host,
+        );
+        assert_eq!(
+            candidate.to_string(),
+            "candidate:1 1 udp 2130706431 10.0.0.1 5000 typ host"
+        );
+        candidate.set_related_address("10.0.0.2:6000".parse().unwrap());
+        assert_eq!(
+            candidate.get_related_socket_addr(),
+            Some("10.0.0.2:6000".parse().unwrap())
+        );
+    }
+}
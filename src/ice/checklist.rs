@@ -0,0 +1,441 @@
+// https://tools.ietf.org/html/rfc8445#section-6.1.2
+
+/*
+    pair priority = 2^32 * MIN(G, D) + 2 * MAX(G, D) + (G > D ? 1 : 0)
+        G: priority of the controlling agent's candidate
+        D: priority of the controlled agent's candidate
+
+    Local candidates the candidate policy does not allow, and remote
+    addresses of a family it does not allow, are not paired. A link-local
+    address is only paired with a link-local one, on the scope of the
+    local base.
+
+    Frozen -> Waiting -> In-Progress -> Succeeded
+                                     -> Failed
+
+    A check is sent every Ta: a triggered check if there is one, otherwise
+    the highest priority Waiting pair, otherwise a Frozen one is unfrozen.
+*/
+
+use crate::ice::candidate::{CandidateType, IceCandidate, TcpType, TransportProtocol};
+use crate::ice::gatherer::LocalCandidate;
+use crate::ice::network::is_link_local;
+use crate::ice::policy::CandidatePolicy;
+use crate::ice::tcp::is_tcp_pairable;
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+pub fn compute_pair_priority(controlling: u32, controlled: u32) -> u64 {
+    let (g, d) = (u64::from(controlling), u64::from(controlled));
+    (g.min(d) << 32) + 2 * g.max(d) + if g > d { 1 } else { 0 }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum PairState {
+    Frozen,
+    Waiting,
+    InProgress,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CandidatePair {
+    pub local: LocalCandidate,
+    pub remote: IceCandidate,
+    /// The resolved address of the remote candidate.
+    pub remote_address: SocketAddr,
+    pub state: PairState,
+    pub priority: u64,
+    /// A check on this pair succeeded, so it can carry data.
+    pub valid: bool,
+    pub nominated: bool,
+}
+
+impl CandidatePair {
+    pub fn get_component(&self) -> u16 {
+        self.local.candidate.component
+    }
+
+    pub fn get_foundation(&self) -> (&str, &str) {
+        (&self.local.candidate.foundation, &self.remote.foundation)
+    }
+
+    fn compute_priority(&self, controlling: bool) -> u64 {
+        let (local, remote) = (self.local.candidate.priority, self.remote.priority);
+        if controlling {
+            compute_pair_priority(local, remote)
+        } else {
+            compute_pair_priority(remote, local)
+        }
+    }
+}
+
+// ローカルとリモートの候補のペアと，checkを送る順番を管理する．
+#[derive(Debug, Clone, Default)]
+pub struct Checklist {
+    pairs: Vec<CandidatePair>,
+    triggered: VecDeque<usize>,
+    policy: CandidatePolicy,
+}
+
+impl Checklist {
+    pub fn new() -> Self {
+        Checklist::default()
+    }
+
+    pub fn with_policy(policy: CandidatePolicy) -> Self {
+        Checklist {
+            policy,
+            ..Checklist::default()
+        }
+    }
+
+    pub fn get_policy(&self) -> &CandidatePolicy {
+        &self.policy
+    }
+
+    pub fn get_pairs(&self) -> &[CandidatePair] {
+        &self.pairs
+    }
+
+    pub fn get_pair(&self, index: usize) -> &CandidatePair {
+        &self.pairs[index]
+    }
+
+    pub fn get_pair_mut(&mut self, index: usize) -> &mut CandidatePair {
+        &mut self.pairs[index]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn find_pair(&self, local_base: SocketAddr, remote: SocketAddr) -> Option<usize> {
+        self.pairs
+            .iter()
+            .position(|v| v.local.base == local_base && v.remote_address == remote)
+    }
+
+    /// Pairs `local` with `remote` if they can reach each other. A server
+    /// reflexive local candidate is replaced with its base, and a pair with
+    /// the same base and remote address as an existing one is pruned.
+    /// Returns the index of the new or the existing pair.
+    pub fn add_pair(
+        &mut self,
+        local: &LocalCandidate,
+        remote: &IceCandidate,
+        controlling: bool,
+    ) -> Option<usize> {
+        let remote_address = remote.get_socket_addr()?;
+        self.add_resolved_pair(local, remote, remote_address, controlling)
+    }
+
+    /// Same as `add_pair`, for a remote candidate with a hostname resolved
+    /// to `remote_address`.
+    pub fn add_resolved_pair(
+        &mut self,
+        local: &LocalCandidate,
+        remote: &IceCandidate,
+        remote_address: SocketAddr,
+        controlling: bool,
+    ) -> Option<usize> {
+        if local.candidate.component != remote.component
+            || local.candidate.transport != remote.transport
+            || local.base.is_ipv4() != remote_address.is_ipv4()
+            || !self.policy.allows_type(local.candidate.candidate_type)
+            || !self.policy.allows_address(remote_address.ip())
+            || is_link_local(local.base.ip()) != is_link_local(remote_address.ip())
+        {
+            return None;
+        }
+        let remote_address = match (remote_address, local.base) {
+            (SocketAddr::V6(mut remote), SocketAddr::V6(base)) if remote.scope_id() == 0 => {
+                if remote.ip().is_unicast_link_local() {
+                    remote.set_scope_id(base.scope_id());
+                }
+                SocketAddr::V6(remote)
+            }
+            (v, _) => v,
+        };
+        if local.candidate.transport == TransportProtocol::Tcp {
+            let (local_type, remote_type) = (local.candidate.tcp_type?, remote.tcp_type?);
+            // an active remote candidate cannot be connected to, so the pair
+            // is made once its connection arrives.
+            let connected = remote.candidate_type == CandidateType::PeerReflexive;
+            if !is_tcp_pairable(local_type, remote_type)
+                || (local_type == TcpType::Passive && !connected)
+            {
+                return None;
+            }
+        }
+        if let Some(i) = self.find_pair(local.base, remote_address) {
+            // a signalled candidate replaces the peer reflexive one learned
+            // from a check.
+            let pair = &mut self.pairs[i];
+            if pair.remote.candidate_type == CandidateType::PeerReflexive
+                && remote.candidate_type != CandidateType::PeerReflexive
+            {
+                pair.remote = remote.clone();
+                pair.priority = pair.compute_priority(controlling);
+            }
+            return Some(i);
+        }
+
+        let mut local = local.clone();
+        if local.candidate.candidate_type == CandidateType::ServerReflexive {
+            // checks are sent from the base, so the pair is the host one.
+            local.candidate.candidate_type = CandidateType::Host;
+            local.candidate.address = local.base.ip().to_string();
+            local.candidate.port = local.base.port();
+            local.candidate.related_address = None;
+            local.candidate.related_port = None;
+        }
+        let mut pair = CandidatePair {
+            local,
+            remote: remote.clone(),
+            remote_address,
+            state: PairState::Frozen,
+            priority: 0,
+            valid: false,
+            nominated: false,
+        };
+        pair.priority = pair.compute_priority(controlling);
+
+        // the first pair of a foundation is checked first, and a pair
+        // trickled in after its foundation succeeded right away.
+        let foundation = pair.get_foundation();
+        let mut same = self
+            .pairs
+            .iter()
+            .filter(|v| v.get_foundation() == foundation && v.state != PairState::Failed)
+            .peekable();
+        if same.peek().is_none() || same.any(|v| v.state == PairState::Succeeded) {
+            pair.state = PairState::Waiting;
+        }
+        self.pairs.push(pair);
+        Some(self.pairs.len() - 1)
+    }
+
+    /// Recomputes the pair priorities after a role change.
+    pub fn set_controlling(&mut self, controlling: bool) {
+        for pair in self.pairs.iter_mut() {
+            pair.priority = pair.compute_priority(controlling);
+        }
+    }
+
+    pub fn set_state(&mut self, index: usize, state: PairState) {
+        self.pairs[index].state = state;
+        if state == PairState::Succeeded {
+            self.unfreeze_foundation(index);
+        }
+    }
+
+    /// Queues a triggered check, which is sent before the ordinary ones.
+    pub fn push_triggered(&mut self, index: usize) {
+        if !self.triggered.contains(&index) {
+            self.triggered.push_back(index);
+        }
+    }
+
+    /// The pair to check next, if there is any.
+    pub fn next_check(&mut self) -> Option<usize> {
+        while let Some(i) = self.triggered.pop_front() {
+            if self.pairs[i].state != PairState::InProgress {
+                return Some(i);
+            }
+        }
+        if let Some(i) = self.get_best(PairState::Waiting) {
+            return Some(i);
+        }
+        self.get_best(PairState::Frozen)
+    }
+
+    /// The valid pair with the highest priority for `component`.
+    pub fn get_best_valid(&self, component: u16) -> Option<usize> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.valid && v.get_component() == component)
+            .max_by_key(|(_, v)| v.priority)
+            .map(|(i, _)| i)
+    }
+
+    /// Whether a pair of `component` that may still succeed has a higher
+    /// priority than `priority`.
+    pub fn has_better_pending(&self, component: u16, priority: u64) -> bool {
+        self.pairs.iter().any(|v| {
+            v.get_component() == component
+                && v.priority > priority
+                && !v.valid
+                && v.state != PairState::Failed
+        })
+    }
+
+    /// Whether every pair was checked.
+    pub fn is_finished(&self) -> bool {
+        self.triggered.is_empty()
+            && self
+                .pairs
+                .iter()
+                .all(|v| v.state == PairState::Succeeded || v.state == PairState::Failed)
+    }
+
+    fn get_best(&self, state: PairState) -> Option<usize> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.state == state)
+            .max_by_key(|(_, v)| v.priority)
+            .map(|(i, _)| i)
+    }
+
+    fn unfreeze_foundation(&mut self, index: usize) {
+        let foundation = self.pairs[index].get_foundation();
+        let foundation = (foundation.0.to_string(), foundation.1.to_string());
+        for pair in self.pairs.iter_mut() {
+            let (local, remote) = pair.get_foundation();
+            if pair.state == PairState::Frozen && local == foundation.0 && remote == foundation.1 {
+                pair.state = PairState::Waiting;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ice::candidate::{compute_priority, TransportProtocol};
+    use crate::ice::policy::{CandidateTypePolicy, IpFamilyPolicy};
+
+    fn local(foundation: &str, addr: &str, candidate_type: CandidateType) -> LocalCandidate {
+        let addr: SocketAddr = addr.parse().unwrap();
+        LocalCandidate {
+            candidate: IceCandidate::new(
+                foundation,
+                1,
+                TransportProtocol::Udp,
+                compute_priority(candidate_type, 65535, 1),
+                addr,
+                candidate_type,
+            ),
+            base: addr,
+        }
+    }
+
+    fn remote(foundation: &str, addr: &str, candidate_type: CandidateType) -> IceCandidate {
+        local(foundation, addr, candidate_type).candidate
+    }
+
+    #[test]
+    fn pair_priority_test() {
+        assert_eq!(
+            compute_pair_priority(2_130_706_431, 1_694_498_815),
+            (1_694_498_815u64 << 32) + 2 * 2_130_706_431 + 1
+        );
+        assert_eq!(
+            compute_pair_priority(1_694_498_815, 2_130_706_431),
+            (1_694_498_815u64 << 32) + 2 * 2_130_706_431
+        );
+    }
+
+    #[test]
+    fn checklist_policy_test() {
+        let mut checklist = Checklist::with_policy(CandidatePolicy {
+            types: CandidateTypePolicy::NoHost,
+            family: IpFamilyPolicy::Ipv4Only,
+            ports: None,
+        });
+        let host = local("1", "192.168.1.10:50000", CandidateType::Host);
+        let mut srflx = local("2", "203.0.113.7:60769", CandidateType::ServerReflexive);
+        srflx.base = host.base;
+        let remote_host = remote("1", "192.168.1.20:40000", CandidateType::Host);
+
+        assert_eq!(checklist.add_pair(&host, &remote_host, true), None);
+        // checked from the host base all the same.
+        assert_eq!(checklist.add_pair(&srflx, &remote_host, true), Some(0));
+        assert_eq!(checklist.get_pair(0).local.base, host.base);
+
+        let mut checklist = Checklist::with_policy(CandidatePolicy {
+            family: IpFamilyPolicy::Ipv4Only,
+            ..CandidatePolicy::default()
+        });
+        let host_v6 = local("3", "[2001:db8::10]:50000", CandidateType::Host);
+        let remote_v6 = remote("3", "[2001:db8::1]:40000", CandidateType::Host);
+        assert_eq!(checklist.add_pair(&host_v6, &remote_v6, true), None);
+        assert_eq!(checklist.add_pair(&host, &remote_host, true), Some(0));
+    }
+
+    #[test]
+    fn link_local_test() {
+        let mut checklist = Checklist::new();
+        let mut link_local = local("1", "[fe80::10]:50000", CandidateType::Host);
+        link_local.base = "[fe80::10%2]:50000".parse().unwrap();
+        let global = local("2", "[2001:db8::10]:50000", CandidateType::Host);
+        let remote_link_local = remote("1", "[fe80::20]:40000", CandidateType::Host);
+        let remote_global = remote("2", "[2001:db8::20]:40000", CandidateType::Host);
+
+        assert_eq!(checklist.add_pair(&global, &remote_link_local, true), None);
+        assert_eq!(checklist.add_pair(&link_local, &remote_global, true), None);
+        // sent on the interface of the base.
+        assert_eq!(
+            checklist.add_pair(&link_local, &remote_link_local, true),
+            Some(0)
+        );
+        let remote_address = checklist.get_pair(0).remote_address;
+        assert_eq!(remote_address, "[fe80::20%2]:40000".parse().unwrap());
+        assert_eq!(
+            checklist.find_pair(link_local.base, remote_address),
+            Some(0)
+        );
+        assert_eq!(checklist.add_pair(&global, &remote_global, true), Some(1));
+    }
+
+    #[test]
+    fn checklist_test() {
+        let mut checklist = Checklist::new();
+        let host = local("1", "192.168.1.10:50000", CandidateType::Host);
+        let mut srflx = local("2", "203.0.113.7:60769", CandidateType::ServerReflexive);
+        srflx.base = host.base;
+        let remote_host = remote("1", "192.168.1.20:40000", CandidateType::Host);
+        let remote_srflx = remote("2", "198.51.100.9:41000", CandidateType::ServerReflexive);
+        let remote_v6 = remote("3", "[2001:db8::1]:40000", CandidateType::Host);
+
+        assert_eq!(checklist.add_pair(&host, &remote_host, true), Some(0));
+        assert_eq!(checklist.add_pair(&host, &remote_srflx, true), Some(1));
+        assert_eq!(checklist.add_pair(&host, &remote_v6, true), None);
+        // the srflx candidate is replaced with its base, which is redundant.
+        assert_eq!(checklist.add_pair(&srflx, &remote_host, true), Some(0));
+        assert_eq!(checklist.get_pairs().len(), 2);
+        let prflx = remote("4", "192.168.1.30:40000", CandidateType::PeerReflexive);
+        let signalled = remote("5", "192.168.1.30:40000", CandidateType::Host);
+        assert_eq!(checklist.add_pair(&host, &prflx, true), Some(2));
+        assert_eq!(checklist.add_pair(&host, &signalled, true), Some(2));
+        assert_eq!(checklist.get_pair(2).remote, signalled);
+        checklist.set_state(2, PairState::Failed);
+
+        // the host pair is checked first, the other foundation too.
+        assert_eq!(checklist.get_pair(0).state, PairState::Waiting);
+        assert_eq!(checklist.get_pair(1).state, PairState::Waiting);
+        assert_eq!(checklist.next_check(), Some(0));
+        checklist.set_state(0, PairState::InProgress);
+        assert_eq!(checklist.next_check(), Some(1));
+        checklist.set_state(1, PairState::InProgress);
+        checklist.push_triggered(0);
+        assert_eq!(checklist.next_check(), None);
+
+        checklist.get_pair_mut(1).valid = true;
+        checklist.set_state(1, PairState::Succeeded);
+        assert_eq!(checklist.get_best_valid(1), Some(1));
+        assert!(checklist.has_better_pending(1, checklist.get_pair(1).priority));
+        checklist.set_state(0, PairState::Failed);
+        assert!(!checklist.has_better_pending(1, checklist.get_pair(1).priority));
+        assert!(checklist.is_finished());
+
+        // controlled side priorities are computed the other way around.
+        let before = checklist.get_pair(1).priority;
+        checklist.set_controlling(false);
+        assert_ne!(checklist.get_pair(1).priority, before);
+    }
+}
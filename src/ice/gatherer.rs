@@ -0,0 +1,1106 @@
+// https://tools.ietf.org/html/rfc8445#section-5.1.1
+// https://tools.ietf.org/html/rfc8445#section-5.1.1.2
+
+/*
+    interface -> address -> UDP socket per component -> host candidate
+    host socket -> Binding request -> STUN server -> XOR-MAPPED-ADDRESS -> srflx
+    host socket -> Allocate -> TURN server -> XOR-RELAYED-ADDRESS -> relay
+
+    foundation: same type, base IP, server IP and transport => same foundation
+    redundant:  same transport address and same base as a kept candidate
+
+    The candidate policy filters the addresses and the candidate types,
+    and restricts the ports the sockets are bound to.
+
+    https://tools.ietf.org/html/rfc8421#section-4
+    local preference: the addresses of the preferred family and of the
+    other one alternate, so a check of the other family follows each one
+    of the preferred family. Link-local addresses come last, are bound
+    with the scope of their interface and reach no server.
+*/
+
+use crate::ice::candidate::{
+    compute_priority, CandidateType, IceCandidate, TcpType, TransportProtocol,
+};
+use crate::ice::mdns::{generate_mdns_hostname, MdnsMode};
+use crate::ice::network::{is_link_local, NetworkProvider, Transmit, TransmitQueue};
+use crate::ice::policy::{CandidatePolicy, PortRange};
+use crate::ice::tcp::{compute_tcp_local_preference, TCP_ACTIVE_PORT};
+use crate::stun::message::{StunClass, StunMessage, TransactionId, METHOD_BINDING};
+use crate::stun::transaction::{TransactionEvent, TransactionManager};
+use crate::time::Instant;
+use crate::turn::client::{TurnClient, TurnConfig, TurnEvent, TurnState};
+use crate::turn::uri::TurnTransport;
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV6};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct GatherConfig {
+    /// 1 with rtcp-mux, 2 for separate RTP and RTCP.
+    pub components: u16,
+    pub include_loopback: bool,
+    pub stun_servers: Vec<SocketAddr>,
+    pub turn_servers: Vec<TurnConfig>,
+    pub mdns: MdnsMode,
+    /// The ICE-TCP host candidates gathered next to the UDP ones.
+    pub tcp_types: Vec<TcpType>,
+    pub policy: CandidatePolicy,
+    pub family_preference: FamilyPreference,
+}
+
+impl Default for GatherConfig {
+    fn default() -> Self {
+        GatherConfig {
+            components: 1,
+            include_loopback: false,
+            stun_servers: vec![],
+            turn_servers: vec![],
+            mdns: MdnsMode::Disabled,
+            tcp_types: vec![],
+            policy: CandidatePolicy::default(),
+            family_preference: FamilyPreference::Any,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum FamilyPreference {
+    /// The addresses in the order of the interfaces.
+    Any,
+    Ipv4,
+    Ipv6,
+}
+
+/// A local candidate and the address of the socket it is sent from.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct LocalCandidate {
+    pub candidate: IceCandidate,
+    pub base: SocketAddr,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum GatherEvent {
+    /// Emitted as soon as the candidate is found, to be trickled.
+    Candidate(LocalCandidate),
+    Complete,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum GatherState {
+    New,
+    Gathering,
+    Complete,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+struct FoundationKey {
+    candidate_type: CandidateType,
+    base: IpAddr,
+    server: Option<IpAddr>,
+    transport: TransportProtocol,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SrflxRequest {
+    base: SocketAddr,
+    component: u16,
+    local_preference: u16,
+    server: SocketAddr,
+}
+
+#[derive(Debug, Clone)]
+struct Relay {
+    client: TurnClient,
+    component: u16,
+    local_preference: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct CandidateGatherer {
+    config: GatherConfig,
+    state: GatherState,
+    candidates: Vec<LocalCandidate>,
+    foundations: HashMap<FoundationKey, String>,
+    events: VecDeque<GatherEvent>,
+    // 送信元ごとにtransactionを分ける．
+    transactions: HashMap<SocketAddr, TransactionManager>,
+    requests: HashMap<TransactionId, SrflxRequest>,
+    transmits: VecDeque<Transmit>,
+    relays: Vec<Relay>,
+    // host candidateのIPアドレスを隠すmDNSの名前．
+    hostnames: Vec<(String, IpAddr)>,
+}
+
+impl CandidateGatherer {
+    pub fn new(config: GatherConfig) -> Self {
+        CandidateGatherer {
+            config,
+            state: GatherState::New,
+            candidates: Vec::new(),
+            foundations: HashMap::new(),
+            events: VecDeque::new(),
+            transactions: HashMap::new(),
+            requests: HashMap::new(),
+            transmits: VecDeque::new(),
+            relays: Vec::new(),
+            hostnames: Vec::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &GatherConfig {
+        &self.config
+    }
+
+    pub fn get_state(&self) -> GatherState {
+        self.state
+    }
+
+    pub fn get_local_candidates(&self) -> &[LocalCandidate] {
+        &self.candidates
+    }
+
+    /// The names the host candidates are sent with, to be answered by an
+    /// mDNS responder.
+    pub fn get_mdns_hostnames(&self) -> &[(String, IpAddr)] {
+        &self.hostnames
+    }
+
+    /// Binds the sockets, gathers the host candidates and starts the Binding
+    /// requests and allocations for the server-reflexive and relayed ones.
+    /// An address that cannot be bound is skipped.
+    pub fn gather<P: NetworkProvider + ?Sized>(
+        &mut self,
+        provider: &mut P,
+        now: Instant,
+    ) -> io::Result<()> {
+        let interfaces = provider.get_interfaces()?;
+        self.state = GatherState::Gathering;
+
+        let mut addresses: Vec<SocketAddr> = Vec::new();
+        for interface in &interfaces {
+            if interface.is_loopback && !self.config.include_loopback {
+                continue;
+            }
+            for address in &interface.addresses {
+                if address.is_unspecified()
+                    || addresses.iter().any(|v| v.ip() == *address)
+                    || !self.config.policy.allows_address(*address)
+                {
+                    continue;
+                }
+                let address = match address {
+                    IpAddr::V6(v) if v.is_unicast_link_local() => {
+                        SocketAddr::V6(SocketAddrV6::new(*v, 0, 0, interface.index))
+                    }
+                    v => SocketAddr::new(*v, 0),
+                };
+                addresses.push(address);
+            }
+        }
+        let addresses = order_addresses(addresses, self.config.family_preference);
+
+        let policy = self.config.policy;
+        let stun_servers = if policy.allows_type(CandidateType::ServerReflexive) {
+            self.config.stun_servers.clone()
+        } else {
+            vec![]
+        };
+        for (i, address) in addresses.iter().enumerate() {
+            let local_preference = u16::MAX - i.min(usize::from(u16::MAX)) as u16;
+            for component in 1..=self.config.components.max(1) {
+                let base = match bind_port(*address, policy.ports, |v| provider.bind_udp(v)) {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+                // without host candidates the socket is only the base of
+                // the others.
+                if policy.allows_type(CandidateType::Host) {
+                    let foundation = self.get_foundation(
+                        CandidateType::Host,
+                        base.ip(),
+                        None,
+                        TransportProtocol::Udp,
+                    );
+                    let mut candidate = IceCandidate::new(
+                        &foundation,
+                        component,
+                        TransportProtocol::Udp,
+                        compute_priority(CandidateType::Host, local_preference, component),
+                        base,
+                        CandidateType::Host,
+                    );
+                    if self.config.mdns == MdnsMode::QueryAndGather {
+                        candidate.address = self.get_hostname(address.ip());
+                    }
+                    if !self.add_candidate(LocalCandidate { candidate, base }) {
+                        continue;
+                    }
+                }
+                if is_link_local(base.ip()) {
+                    continue;
+                }
+                for server in stun_servers.iter().copied() {
+                    if server.is_ipv4() == base.is_ipv4() {
+                        self.start_srflx_request(
+                            SrflxRequest {
+                                base,
+                                component,
+                                local_preference,
+                                server,
+                            },
+                            now,
+                        );
+                    }
+                }
+                for server in self.config.turn_servers.clone() {
+                    if server.server.is_ipv4() == base.is_ipv4() {
+                        let local = if server.transport.is_stream() {
+                            match provider.connect_tcp(
+                                SocketAddr::new(base.ip(), 0),
+                                server.server,
+                                server.transport == TurnTransport::Tls,
+                            ) {
+                                Ok(v) => v,
+                                Err(_) => continue,
+                            }
+                        } else {
+                            base
+                        };
+                        let mut client = TurnClient::new(local, server);
+                        client.allocate(now);
+                        self.relays.push(Relay {
+                            client,
+                            component,
+                            local_preference,
+                        });
+                    }
+                }
+            }
+        }
+
+        let tcp_types = if policy.allows_type(CandidateType::Host) {
+            self.config.tcp_types.clone()
+        } else {
+            vec![]
+        };
+        for (i, address) in addresses.iter().enumerate() {
+            let other_preference = 0x1fff - i.min(0x1fff) as u16;
+            for component in 1..=self.config.components.max(1) {
+                for tcp_type in tcp_types.iter().copied() {
+                    self.gather_tcp(provider, *address, tcp_type, other_preference, component);
+                }
+            }
+        }
+
+        self.complete();
+        Ok(())
+    }
+
+    pub fn poll_event(&mut self) -> Option<GatherEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        if let Some(v) = self.transmits.pop_front() {
+            return Some(v);
+        }
+        self.relays
+            .iter_mut()
+            .find_map(|v| v.client.poll_transmit())
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let relays = self.relays.iter().filter_map(|v| v.client.poll_timeout());
+        self.transactions
+            .values()
+            .filter_map(|v| v.poll_timeout())
+            .chain(relays)
+            .min()
+    }
+
+    /// The allocation behind a relayed candidate.
+    pub fn get_turn_client(&self, relayed: SocketAddr) -> Option<&TurnClient> {
+        self.relays
+            .iter()
+            .map(|v| &v.client)
+            .find(|v| v.get_relayed_address() == Some(relayed))
+    }
+
+    pub fn get_turn_client_mut(&mut self, relayed: SocketAddr) -> Option<&mut TurnClient> {
+        self.relays
+            .iter_mut()
+            .map(|v| &mut v.client)
+            .find(|v| v.get_relayed_address() == Some(relayed))
+    }
+
+    pub fn is_relay_server(&self, local: SocketAddr, source: SocketAddr) -> bool {
+        self.relays
+            .iter()
+            .any(|v| v.client.get_local_address() == local && v.client.get_server() == source)
+    }
+
+    /// Handles a datagram (or stream data for TCP and TLS) received from a
+    /// TURN server on the socket bound to `local`. Returns the relayed
+    /// address the data arrived on, the peer and the data.
+    pub fn handle_relay_packet(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) -> Vec<(SocketAddr, SocketAddr, Vec<u8>)> {
+        let relay = match self
+            .relays
+            .iter_mut()
+            .find(|v| v.client.get_local_address() == local && v.client.get_server() == source)
+        {
+            Some(v) => v,
+            None => return vec![],
+        };
+        let received = if relay.client.get_config().transport.is_stream() {
+            match relay.client.handle_stream_data(data, now) {
+                Ok(v) => v,
+                // the stream cannot be recovered.
+                Err(_) => {
+                    relay.client.close(now);
+                    vec![]
+                }
+            }
+        } else {
+            relay
+                .client
+                .handle_receive(source, data, now)
+                .into_iter()
+                .collect()
+        };
+        let relayed = relay.client.get_relayed_address();
+        self.handle_relay_events();
+        self.complete();
+        match relayed {
+            Some(relayed) => received
+                .into_iter()
+                .map(|(peer, data)| (relayed, peer, data))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Sends `data` to `peer` through the allocation of `relayed`.
+    pub fn send_relayed(
+        &mut self,
+        relayed: SocketAddr,
+        peer: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) -> bool {
+        match self.get_turn_client_mut(relayed) {
+            Some(v) => v.send_to(peer, data, now).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Retransmits the Binding requests, and gives up on the servers that
+    /// did not answer.
+    pub fn process(&mut self, now: Instant) {
+        let mut timed_out = Vec::new();
+        for (base, transactions) in self.transactions.iter_mut() {
+            let mut transport = TransmitQueue {
+                source: *base,
+                queue: &mut self.transmits,
+            };
+            for event in transactions.process(now, &mut transport) {
+                if let TransactionEvent::TimedOut { transaction_id, .. } = event {
+                    timed_out.push(transaction_id);
+                }
+            }
+        }
+        for transaction_id in timed_out {
+            self.requests.remove(&transaction_id);
+        }
+        for relay in self.relays.iter_mut() {
+            relay.client.process(now);
+        }
+        self.handle_relay_events();
+        self.complete();
+    }
+
+    /// Handles a STUN message received on the socket bound to `local`.
+    /// Returns `false` when it is not a response to our Binding requests.
+    pub fn handle_stun(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        message: StunMessage,
+        now: Instant,
+    ) -> bool {
+        let transaction_id = message.get_transaction_id();
+        let request = match self.requests.get(&transaction_id) {
+            Some(v) if v.base == local && v.server == source => *v,
+            _ => return false,
+        };
+        let event = match self.transactions.get_mut(&local) {
+            Some(v) => v.handle_response(message, source, now),
+            None => None,
+        };
+        let message = match event {
+            Some(TransactionEvent::Response { message, .. }) => message,
+            _ => return false,
+        };
+        self.requests.remove(&transaction_id);
+
+        if message.get_class() == StunClass::SuccessResponse {
+            if let Ok(Some(mapped)) = message.get_mapped_address() {
+                self.add_srflx_candidate(&request, mapped);
+            }
+        }
+        self.complete();
+        true
+    }
+
+    fn start_srflx_request(&mut self, request: SrflxRequest, now: Instant) {
+        let message = StunMessage::request(METHOD_BINDING);
+        let mut transport = TransmitQueue {
+            source: request.base,
+            queue: &mut self.transmits,
+        };
+        let started = self.transactions.entry(request.base).or_default().start(
+            &message,
+            request.server,
+            now,
+            &mut transport,
+        );
+        if let Ok(transaction_id) = started {
+            self.requests.insert(transaction_id, request);
+        }
+    }
+
+    fn add_srflx_candidate(&mut self, request: &SrflxRequest, mapped: SocketAddr) {
+        let foundation = self.get_foundation(
+            CandidateType::ServerReflexive,
+            request.base.ip(),
+            Some(request.server.ip()),
+            TransportProtocol::Udp,
+        );
+        let mut candidate = IceCandidate::new(
+            &foundation,
+            request.component,
+            TransportProtocol::Udp,
+            compute_priority(
+                CandidateType::ServerReflexive,
+                request.local_preference,
+                request.component,
+            ),
+            mapped,
+            CandidateType::ServerReflexive,
+        );
+        let hidden = self.config.mdns == MdnsMode::QueryAndGather
+            || !self.config.policy.allows_type(CandidateType::Host);
+        if hidden {
+            // the base would reveal the hidden host address.
+            candidate.set_related_address(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0));
+        } else {
+            candidate.set_related_address(request.base);
+        }
+        self.add_candidate(LocalCandidate {
+            candidate,
+            base: request.base,
+        });
+    }
+
+    fn gather_tcp<P: NetworkProvider + ?Sized>(
+        &mut self,
+        provider: &mut P,
+        address: SocketAddr,
+        tcp_type: TcpType,
+        other_preference: u16,
+        component: u16,
+    ) {
+        // an active candidate has no socket until it connects.
+        let base = if tcp_type == TcpType::Active {
+            let mut base = address;
+            base.set_port(TCP_ACTIVE_PORT);
+            base
+        } else {
+            match bind_port(address, self.config.policy.ports, |v| {
+                provider.listen_tcp(v)
+            }) {
+                Ok(v) => v,
+                Err(_) => return,
+            }
+        };
+        let foundation = self.get_foundation(
+            CandidateType::Host,
+            address.ip(),
+            None,
+            TransportProtocol::Tcp,
+        );
+        let local_preference =
+            compute_tcp_local_preference(CandidateType::Host, tcp_type, other_preference);
+        let mut candidate = IceCandidate::new(
+            &foundation,
+            component,
+            TransportProtocol::Tcp,
+            compute_priority(CandidateType::Host, local_preference, component),
+            base,
+            CandidateType::Host,
+        );
+        candidate.tcp_type = Some(tcp_type);
+        if self.config.mdns == MdnsMode::QueryAndGather {
+            candidate.address = self.get_hostname(address.ip());
+        }
+        self.add_candidate(LocalCandidate { candidate, base });
+    }
+
+    fn get_hostname(&mut self, address: IpAddr) -> String {
+        if let Some((name, _)) = self.hostnames.iter().find(|v| v.1 == address) {
+            return name.clone();
+        }
+        let name = generate_mdns_hostname();
+        self.hostnames.push((name.clone(), address));
+        name
+    }
+
+    fn handle_relay_events(&mut self) {
+        let mut allocated = Vec::new();
+        for relay in self.relays.iter_mut() {
+            while let Some(event) = relay.client.poll_event() {
+                if let TurnEvent::Allocated { relayed, mapped } = event {
+                    allocated.push((
+                        relay.component,
+                        relay.local_preference,
+                        relay.client.get_server(),
+                        relayed,
+                        mapped,
+                    ));
+                }
+            }
+        }
+        for (component, local_preference, server, relayed, mapped) in allocated {
+            let foundation = self.get_foundation(
+                CandidateType::Relay,
+                relayed.ip(),
+                Some(server.ip()),
+                TransportProtocol::Udp,
+            );
+            let mut candidate = IceCandidate::new(
+                &foundation,
+                component,
+                TransportProtocol::Udp,
+                compute_priority(CandidateType::Relay, local_preference, component),
+                relayed,
+                CandidateType::Relay,
+            );
+            if let Some(v) = mapped {
+                candidate.set_related_address(v);
+            }
+            self.add_candidate(LocalCandidate {
+                candidate,
+                base: relayed,
+            });
+        }
+    }
+
+    fn complete(&mut self) {
+        let allocating = self.relays.iter().any(|v| {
+            v.client.get_state() == TurnState::New || v.client.get_state() == TurnState::Allocating
+        });
+        if self.state == GatherState::Gathering && self.requests.is_empty() && !allocating {
+            self.state = GatherState::Complete;
+            self.events.push_back(GatherEvent::Complete);
+        }
+    }
+
+    fn get_foundation(
+        &mut self,
+        candidate_type: CandidateType,
+        base: IpAddr,
+        server: Option<IpAddr>,
+        transport: TransportProtocol,
+    ) -> String {
+        let key = FoundationKey {
+            candidate_type,
+            base,
+            server,
+            transport,
+        };
+        let next = self.foundations.len() + 1;
+        self.foundations
+            .entry(key)
+            .or_insert_with(|| next.to_string())
+            .clone()
+    }
+
+    /// Keeps `local` unless it is redundant, and emits it.
+    fn add_candidate(&mut self, local: LocalCandidate) -> bool {
+        let redundant = self.candidates.iter().any(|v| {
+            v.base == local.base
+                && v.candidate.component == local.candidate.component
+                && v.candidate.transport == local.candidate.transport
+                && v.candidate.address == local.candidate.address
+                && v.candidate.port == local.candidate.port
+        });
+        if redundant {
+            return false;
+        }
+        self.events.push_back(GatherEvent::Candidate(local.clone()));
+        self.candidates.push(local);
+        true
+    }
+}
+
+/// Binds `address` to the first free port of `ports`, or to any port
+/// without a range.
+fn bind_port<F>(
+    address: SocketAddr,
+    ports: Option<PortRange>,
+    mut bind: F,
+) -> io::Result<SocketAddr>
+where
+    F: FnMut(SocketAddr) -> io::Result<SocketAddr>,
+{
+    let ports = match ports {
+        Some(v) => v,
+        None => return bind(address),
+    };
+    let mut error = io::Error::new(io::ErrorKind::AddrInUse, "no port is free in the range");
+    for port in ports.min..=ports.max {
+        let mut address = address;
+        address.set_port(port);
+        match bind(address) {
+            Ok(v) => return Ok(v),
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// The addresses in the order of their local preference.
+fn order_addresses(addresses: Vec<SocketAddr>, preference: FamilyPreference) -> Vec<SocketAddr> {
+    let (link_local, addresses): (Vec<_>, Vec<_>) =
+        addresses.into_iter().partition(|v| is_link_local(v.ip()));
+    let mut ordered = match preference {
+        FamilyPreference::Any => addresses,
+        FamilyPreference::Ipv4 | FamilyPreference::Ipv6 => {
+            let ipv6 = preference == FamilyPreference::Ipv6;
+            let (preferred, other): (Vec<_>, Vec<_>) =
+                addresses.into_iter().partition(|v| v.is_ipv6() == ipv6);
+            let mut ordered = Vec::with_capacity(preferred.len() + other.len());
+            let (mut preferred, mut other) = (preferred.into_iter(), other.into_iter());
+            loop {
+                match (preferred.next(), other.next()) {
+                    (None, None) => break,
+                    (a, b) => ordered.extend(a.into_iter().chain(b)),
+                }
+            }
+            ordered
+        }
+    };
+    ordered.extend(link_local);
+    ordered
+}
+
+impl Default for CandidateGatherer {
+    fn default() -> Self {
+        CandidateGatherer::new(GatherConfig::default())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::ice::mdns::MDNS_PORT;
+    use crate::ice::network::{NetworkChange, NetworkInterface};
+    use crate::ice::policy::{CandidateTypePolicy, IpFamilyPolicy};
+    use std::iter;
+
+    #[derive(Default)]
+    pub(crate) struct FakeNetwork {
+        pub interfaces: Vec<NetworkInterface>,
+        pub bound: Vec<SocketAddr>,
+        pub next_port: u16,
+        pub changes: VecDeque<NetworkChange>,
+    }
+
+    impl FakeNetwork {
+        pub(crate) fn new(interfaces: Vec<NetworkInterface>) -> Self {
+            FakeNetwork {
+                interfaces,
+                bound: vec![],
+                next_port: 50000,
+                changes: VecDeque::new(),
+            }
+        }
+    }
+
+    impl NetworkProvider for FakeNetwork {
+        fn get_interfaces(&mut self) -> io::Result<Vec<NetworkInterface>> {
+            Ok(self.interfaces.clone())
+        }
+
+        fn bind_udp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+            if addr.port() != 0 {
+                if self.bound.contains(&addr) {
+                    return Err(io::ErrorKind::AddrInUse.into());
+                }
+                self.bound.push(addr);
+                return Ok(addr);
+            }
+            let mut addr = addr;
+            addr.set_port(self.next_port);
+            self.next_port += 1;
+            self.bound.push(addr);
+            Ok(addr)
+        }
+
+        fn listen_tcp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+            self.bind_udp(addr)
+        }
+
+        fn bind_mdns(&mut self) -> io::Result<SocketAddr> {
+            let address = self.interfaces[0].addresses[0];
+            Ok(SocketAddr::new(address, MDNS_PORT))
+        }
+
+        fn poll_change(&mut self) -> Option<NetworkChange> {
+            self.changes.pop_front()
+        }
+    }
+
+    #[test]
+    fn gather_host_test() {
+        let mut loopback = NetworkInterface::new("lo", 1, vec!["127.0.0.1".parse().unwrap()]);
+        loopback.is_loopback = true;
+        let mut network = FakeNetwork::new(vec![
+            loopback,
+            NetworkInterface::new(
+                "eth0",
+                2,
+                vec![
+                    "192.168.1.10".parse().unwrap(),
+                    "2001:db8::10".parse().unwrap(),
+                ],
+            ),
+            // the same address on another interface is redundant.
+            NetworkInterface::new("eth1", 3, vec!["192.168.1.10".parse().unwrap()]),
+        ]);
+
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            components: 2,
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, Instant::now()).unwrap();
+        assert_eq!(gatherer.get_state(), GatherState::Complete);
+        assert_eq!(network.bound.len(), 4);
+
+        let mut events = vec![];
+        while let Some(v) = gatherer.poll_event() {
+            events.push(v);
+        }
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[4], GatherEvent::Complete);
+
+        let candidates = gatherer.get_local_candidates();
+        assert_eq!(candidates.len(), 4);
+        for (i, local) in candidates.iter().enumerate() {
+            assert_eq!(events[i], GatherEvent::Candidate(local.clone()));
+            assert_eq!(local.candidate.get_socket_addr(), Some(local.base));
+            assert_eq!(local.candidate.candidate_type, CandidateType::Host);
+        }
+
+        // both components of an address share the foundation.
+        assert_eq!(candidates[0].candidate.foundation, "1");
+        assert_eq!(candidates[1].candidate.foundation, "1");
+        assert_eq!(candidates[2].candidate.foundation, "2");
+        assert_eq!(candidates[0].candidate.component, 1);
+        assert_eq!(candidates[1].candidate.component, 2);
+        assert_eq!(candidates[0].candidate.priority, 2_130_706_431);
+        assert_eq!(candidates[1].candidate.priority, 2_130_706_430);
+        assert!(candidates[2].candidate.priority < candidates[0].candidate.priority);
+    }
+
+    #[test]
+    fn gather_dual_stack_test() {
+        let server: SocketAddr = "[2001:db8::1]:3478".parse().unwrap();
+        let mut network = FakeNetwork::new(vec![NetworkInterface::new(
+            "eth0",
+            2,
+            vec![
+                "fe80::10".parse().unwrap(),
+                "192.168.1.10".parse().unwrap(),
+                "192.168.1.11".parse().unwrap(),
+                "2001:db8::10".parse().unwrap(),
+                "2001:db8::11".parse().unwrap(),
+            ],
+        )]);
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            stun_servers: vec![server],
+            family_preference: FamilyPreference::Ipv6,
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, Instant::now()).unwrap();
+
+        let mut candidates = gatherer.get_local_candidates().to_vec();
+        candidates.sort_by_key(|v| std::cmp::Reverse(v.candidate.priority));
+        let addresses: Vec<_> = candidates.iter().map(|v| v.base.ip()).collect();
+        let expected: Vec<IpAddr> = [
+            "2001:db8::10",
+            "192.168.1.10",
+            "2001:db8::11",
+            "192.168.1.11",
+            "fe80::10",
+        ]
+        .iter()
+        .map(|v| v.parse().unwrap())
+        .collect();
+        assert_eq!(addresses, expected);
+        match candidates[4].base {
+            SocketAddr::V6(v) => assert_eq!(v.scope_id(), 2),
+            v => panic!("{}", v),
+        }
+
+        // the link-local socket cannot reach the server.
+        let sources: Vec<_> = iter::from_fn(|| gatherer.poll_transmit())
+            .map(|v| v.source.ip())
+            .collect();
+        assert_eq!(sources, vec![expected[0], expected[2]]);
+
+        let addresses = vec![
+            "192.168.1.10:0".parse().unwrap(),
+            "[2001:db8::10]:0".parse().unwrap(),
+            "192.168.1.11:0".parse().unwrap(),
+        ];
+        assert_eq!(
+            order_addresses(addresses.clone(), FamilyPreference::Ipv4),
+            vec![addresses[0], addresses[1], addresses[2]]
+        );
+        assert_eq!(
+            order_addresses(addresses.clone(), FamilyPreference::Ipv6),
+            vec![addresses[1], addresses[0], addresses[2]]
+        );
+        assert_eq!(
+            order_addresses(addresses.clone(), FamilyPreference::Any),
+            addresses
+        );
+    }
+
+    fn respond(request: &Transmit, mapped: &SocketAddr) -> StunMessage {
+        let request = StunMessage::from_bytes(&request.data).unwrap();
+        let mut response = StunMessage::response(&request, StunClass::SuccessResponse);
+        response.add_xor_address(crate::stun::attribute::ATTR_XOR_MAPPED_ADDRESS, mapped);
+        response
+    }
+
+    #[test]
+    fn gather_srflx_test() {
+        let now = Instant::now();
+        let server: SocketAddr = "198.51.100.1:3478".parse().unwrap();
+        let mut network = FakeNetwork::new(vec![NetworkInterface::new(
+            "eth0",
+            2,
+            vec![
+                "192.168.1.10".parse().unwrap(),
+                "2001:db8::10".parse().unwrap(),
+            ],
+        )]);
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            stun_servers: vec![server],
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, now).unwrap();
+        assert_eq!(gatherer.get_state(), GatherState::Gathering);
+
+        // only the IPv4 socket can reach the server.
+        let request = gatherer.poll_transmit().unwrap();
+        assert_eq!(request.source, "192.168.1.10:50000".parse().unwrap());
+        assert_eq!(request.destination, server);
+        assert_eq!(gatherer.poll_transmit(), None);
+
+        let mapped: SocketAddr = "203.0.113.7:60769".parse().unwrap();
+        let response = respond(&request, &mapped);
+        assert!(!gatherer.handle_stun(request.source, mapped, response.clone(), now));
+        assert!(gatherer.handle_stun(request.source, server, response, now));
+        assert_eq!(gatherer.get_state(), GatherState::Complete);
+
+        let srflx = gatherer.get_local_candidates()[2].clone();
+        assert_eq!(srflx.base, request.source);
+        assert_eq!(
+            srflx.candidate.candidate_type,
+            CandidateType::ServerReflexive
+        );
+        assert_eq!(srflx.candidate.get_socket_addr(), Some(mapped));
+        assert_eq!(
+            srflx.candidate.get_related_socket_addr(),
+            Some(request.source)
+        );
+        assert_eq!(srflx.candidate.priority, 1_694_498_815);
+        assert_eq!(srflx.candidate.foundation, "3");
+
+        let events: Vec<GatherEvent> = std::iter::from_fn(|| gatherer.poll_event()).collect();
+        assert_eq!(
+            &events[2..],
+            &[GatherEvent::Candidate(srflx), GatherEvent::Complete][..]
+        );
+    }
+
+    #[test]
+    fn gather_policy_test() {
+        let now = Instant::now();
+        let server: SocketAddr = "198.51.100.1:3478".parse().unwrap();
+        let interfaces = vec![NetworkInterface::new(
+            "eth0",
+            2,
+            vec![
+                "192.168.1.10".parse().unwrap(),
+                "2001:db8::10".parse().unwrap(),
+            ],
+        )];
+        let mut network = FakeNetwork::new(interfaces.clone());
+        network.bound.push("192.168.1.10:40000".parse().unwrap());
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            components: 2,
+            stun_servers: vec![server],
+            tcp_types: vec![TcpType::Passive],
+            policy: CandidatePolicy {
+                types: CandidateTypePolicy::NoHost,
+                family: IpFamilyPolicy::Ipv4Only,
+                ports: Some(PortRange::new(40000, 40002)),
+            },
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, now).unwrap();
+
+        // the sockets are bound in the range, without host candidates.
+        assert_eq!(
+            network.bound,
+            vec![
+                "192.168.1.10:40000".parse().unwrap(),
+                "192.168.1.10:40001".parse().unwrap(),
+                "192.168.1.10:40002".parse().unwrap(),
+            ]
+        );
+        assert!(gatherer.get_local_candidates().is_empty());
+        let request = gatherer.poll_transmit().unwrap();
+        assert_eq!(request.source, "192.168.1.10:40001".parse().unwrap());
+        let mapped: SocketAddr = "203.0.113.7:60769".parse().unwrap();
+        assert!(gatherer.handle_stun(request.source, server, respond(&request, &mapped), now));
+        let srflx = &gatherer.get_local_candidates()[0];
+        assert_eq!(srflx.base, request.source);
+        assert_eq!(
+            srflx.candidate.get_related_socket_addr(),
+            Some("0.0.0.0:0".parse().unwrap())
+        );
+
+        // no port is left in the range.
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            policy: CandidatePolicy {
+                ports: Some(PortRange::new(40000, 40002)),
+                ..CandidatePolicy::default()
+            },
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, now).unwrap();
+        let candidates = gatherer.get_local_candidates();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(
+            candidates[0].base.ip(),
+            "2001:db8::10".parse::<IpAddr>().unwrap()
+        );
+
+        let mut network = FakeNetwork::new(interfaces);
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            stun_servers: vec![server],
+            policy: CandidatePolicy {
+                types: CandidateTypePolicy::RelayOnly,
+                ..CandidatePolicy::default()
+            },
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, now).unwrap();
+        assert_eq!(gatherer.poll_transmit(), None);
+        assert_eq!(gatherer.get_state(), GatherState::Complete);
+        assert!(gatherer.get_local_candidates().is_empty());
+    }
+
+    #[test]
+    fn gather_srflx_failure_test() {
+        let now = Instant::now();
+        let servers: Vec<SocketAddr> = vec![
+            "198.51.100.1:3478".parse().unwrap(),
+            "198.51.100.2:3478".parse().unwrap(),
+        ];
+        let mut network = FakeNetwork::new(vec![NetworkInterface::new(
+            "eth0",
+            2,
+            vec!["203.0.113.7".parse().unwrap()],
+        )]);
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            stun_servers: servers.clone(),
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, now).unwrap();
+
+        // without a NAT, the srflx candidate is redundant with the host one.
+        let request = gatherer.poll_transmit().unwrap();
+        let response = respond(&request, &request.source);
+        assert!(gatherer.handle_stun(request.source, request.destination, response, now));
+        assert_eq!(gatherer.get_local_candidates().len(), 1);
+        assert_eq!(gatherer.get_state(), GatherState::Gathering);
+
+        // the other server never answers.
+        while let Some(t) = gatherer.poll_timeout() {
+            gatherer.process(t);
+        }
+        assert_eq!(gatherer.get_state(), GatherState::Complete);
+        assert_eq!(gatherer.get_local_candidates().len(), 1);
+    }
+
+    #[test]
+    fn gather_relay_test() {
+        use crate::turn::client::test::{serve, MAPPED, RELAYED};
+
+        let now = Instant::now();
+        let server: SocketAddr = "192.0.2.1:3478".parse().unwrap();
+        let mut network = FakeNetwork::new(vec![NetworkInterface::new(
+            "eth0",
+            2,
+            vec!["192.168.1.10".parse().unwrap()],
+        )]);
+        let mut gatherer = CandidateGatherer::new(GatherConfig {
+            turn_servers: vec![TurnConfig::new(server, "user", "pass")],
+            ..Default::default()
+        });
+        gatherer.gather(&mut network, now).unwrap();
+        assert_eq!(gatherer.get_state(), GatherState::Gathering);
+
+        while let Some(request) = gatherer.poll_transmit() {
+            assert!(gatherer.is_relay_server(request.source, request.destination));
+            let response = serve(&request);
+            assert_eq!(
+                gatherer.handle_relay_packet(request.source, request.destination, &response, now),
+                vec![]
+            );
+        }
+        assert_eq!(gatherer.get_state(), GatherState::Complete);
+
+        let relayed: SocketAddr = RELAYED.parse().unwrap();
+        let relay = gatherer.get_local_candidates()[1].clone();
+        assert_eq!(relay.base, relayed);
+        assert_eq!(relay.candidate.candidate_type, CandidateType::Relay);
+        assert_eq!(relay.candidate.get_socket_addr(), Some(relayed));
+        assert_eq!(
+            relay.candidate.get_related_socket_addr(),
+            Some(MAPPED.parse().unwrap())
+        );
+        assert_eq!(relay.candidate.priority, 16_777_215);
+
+        let peer: SocketAddr = "203.0.113.50:40000".parse().unwrap();
+        assert!(gatherer.send_relayed(relayed, peer, b"check", now));
+        assert!(!gatherer.send_relayed(peer, peer, b"check", now));
+        assert_eq!(
+            gatherer.poll_transmit().map(|v| v.destination),
+            Some(server)
+        );
+    }
+}
@@ -0,0 +1,557 @@
+// https://tools.ietf.org/html/draft-ietf-mmusic-mdns-ice-candidates-03
+// https://tools.ietf.org/html/rfc6762
+
+/*
+    A host candidate hides its IP address behind a random name:
+
+      candidate:1 1 udp 2122260223 2a7b4c28-b7d8-414e-9ab3-c6e9df1a9c86.local 53411 typ host
+
+    Query and response are DNS messages multicast to 224.0.0.251:5353 or
+    [ff02::fb]:5353.
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |               ID              |Q|        flags                |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |            QDCOUNT            |            ANCOUNT            |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |            NSCOUNT            |            ARCOUNT            |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | question: name, type (A / AAAA), class                        |
+    | answer:   name, type, class (cache-flush), TTL, length, address|
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::ice::network::Transmit;
+use crate::ice::{IceError, Result};
+use crate::time::Instant;
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+pub const MDNS_PORT: u16 = 5353;
+pub const MDNS_IPV4_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_IPV6_GROUP: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+pub const RECORD_TYPE_A: u16 = 1;
+pub const RECORD_TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+const FLAG_RESPONSE: u16 = 0x8400;
+const DNS_HEADER_LENGTH: usize = 12;
+
+/// A random name as browsers generate, a UUID version 4 in the .local
+/// domain.
+pub fn generate_mdns_hostname() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|v| format!("{:02x}", v)).collect();
+    format!(
+        "{}-{}-{}-{}-{}.local",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+pub fn is_mdns_hostname(address: &str) -> bool {
+    address.len() > ".local".len() && address.to_ascii_lowercase().ends_with(".local")
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MdnsMode {
+    Disabled,
+    /// Resolves the remote .local candidates.
+    QueryOnly,
+    /// Also hides the local host addresses behind .local names.
+    QueryAndGather,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MdnsQuestion {
+    pub name: String,
+    pub record_type: u16,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MdnsAnswer {
+    pub name: String,
+    pub address: IpAddr,
+    pub ttl: u32,
+}
+
+// A/AAAA以外のrecordは読み飛ばす．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MdnsMessage {
+    pub id: u16,
+    pub is_response: bool,
+    pub questions: Vec<MdnsQuestion>,
+    pub answers: Vec<MdnsAnswer>,
+}
+
+impl MdnsMessage {
+    pub fn query(name: &str) -> Self {
+        MdnsMessage {
+            id: 0,
+            is_response: false,
+            questions: vec![
+                MdnsQuestion {
+                    name: name.to_string(),
+                    record_type: RECORD_TYPE_A,
+                },
+                MdnsQuestion {
+                    name: name.to_string(),
+                    record_type: RECORD_TYPE_AAAA,
+                },
+            ],
+            answers: vec![],
+        }
+    }
+
+    pub fn response(answers: Vec<MdnsAnswer>) -> Self {
+        MdnsMessage {
+            id: 0,
+            is_response: true,
+            questions: vec![],
+            answers,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&self.id.to_be_bytes());
+        let flags = if self.is_response { FLAG_RESPONSE } else { 0 };
+        out.extend_from_slice(&flags.to_be_bytes());
+        out.extend_from_slice(&(self.questions.len() as u16).to_be_bytes());
+        out.extend_from_slice(&(self.answers.len() as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        for question in &self.questions {
+            encode_name(&mut out, &question.name);
+            out.extend_from_slice(&question.record_type.to_be_bytes());
+            out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        }
+        for answer in &self.answers {
+            encode_name(&mut out, &answer.name);
+            let (record_type, data) = match answer.address {
+                IpAddr::V4(v) => (RECORD_TYPE_A, v.octets().to_vec()),
+                IpAddr::V6(v) => (RECORD_TYPE_AAAA, v.octets().to_vec()),
+            };
+            out.extend_from_slice(&record_type.to_be_bytes());
+            out.extend_from_slice(&(CLASS_IN | CLASS_CACHE_FLUSH).to_be_bytes());
+            out.extend_from_slice(&answer.ttl.to_be_bytes());
+            out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            out.extend_from_slice(&data);
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.len() < DNS_HEADER_LENGTH {
+            return Err(IceError::InvalidMdnsMessage);
+        }
+        let get_u16 = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let id = get_u16(0);
+        let is_response = get_u16(2) & 0x8000 != 0;
+        let question_count = get_u16(4);
+        let record_count =
+            usize::from(get_u16(6)) + usize::from(get_u16(8)) + usize::from(get_u16(10));
+
+        let mut offset = DNS_HEADER_LENGTH;
+        let mut questions = Vec::new();
+        for _ in 0..question_count {
+            let name = decode_name(data, &mut offset)?;
+            let fields = data
+                .get(offset..offset + 4)
+                .ok_or(IceError::InvalidMdnsMessage)?;
+            questions.push(MdnsQuestion {
+                name,
+                record_type: u16::from_be_bytes([fields[0], fields[1]]),
+            });
+            offset += 4;
+        }
+
+        // the authority and additional records are read as answers too.
+        let mut answers = Vec::new();
+        for _ in 0..record_count {
+            let name = decode_name(data, &mut offset)?;
+            let fields = data
+                .get(offset..offset + 10)
+                .ok_or(IceError::InvalidMdnsMessage)?;
+            let record_type = u16::from_be_bytes([fields[0], fields[1]]);
+            let ttl = u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]);
+            let length = usize::from(u16::from_be_bytes([fields[8], fields[9]]));
+            offset += 10;
+            let rdata = data
+                .get(offset..offset + length)
+                .ok_or(IceError::InvalidMdnsMessage)?;
+            offset += length;
+            let address = match (record_type, length) {
+                (RECORD_TYPE_A, 4) => IpAddr::from([rdata[0], rdata[1], rdata[2], rdata[3]]),
+                (RECORD_TYPE_AAAA, 16) => {
+                    let mut octets = [0; 16];
+                    octets.copy_from_slice(rdata);
+                    IpAddr::from(octets)
+                }
+                _ => continue,
+            };
+            answers.push(MdnsAnswer { name, address, ttl });
+        }
+
+        Ok(MdnsMessage {
+            id,
+            is_response,
+            questions,
+            answers,
+        })
+    }
+}
+
+fn encode_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|v| !v.is_empty()) {
+        out.push(label.len().min(63) as u8);
+        out.extend_from_slice(&label.as_bytes()[..label.len().min(63)]);
+    }
+    out.push(0);
+}
+
+fn decode_name(data: &[u8], offset: &mut usize) -> Result<String> {
+    let mut labels = Vec::new();
+    let mut position = *offset;
+    let mut jumped = false;
+    // a loop of compression pointers is broken.
+    for _ in 0..128 {
+        let length = *data.get(position).ok_or(IceError::InvalidMdnsMessage)?;
+        match length & 0xc0 {
+            0x00 if length == 0 => {
+                if !jumped {
+                    *offset = position + 1;
+                }
+                return Ok(labels.join("."));
+            }
+            0x00 => {
+                let start = position + 1;
+                let label = data
+                    .get(start..start + usize::from(length))
+                    .ok_or(IceError::InvalidMdnsMessage)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position = start + usize::from(length);
+            }
+            0xc0 => {
+                let low = *data.get(position + 1).ok_or(IceError::InvalidMdnsMessage)?;
+                if !jumped {
+                    *offset = position + 2;
+                }
+                jumped = true;
+                position = (usize::from(length & 0x3f) << 8) | usize::from(low);
+            }
+            _ => return Err(IceError::InvalidMdnsMessage),
+        }
+    }
+    Err(IceError::InvalidMdnsMessage)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum MdnsEvent {
+    Resolved { name: String, address: IpAddr },
+    Unresolved { name: String },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct MdnsConfig {
+    pub retransmit_interval: Duration,
+    /// How long a query is retried before the name is given up.
+    pub timeout: Duration,
+    /// TTL of the answers for the registered names.
+    pub ttl: u32,
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        MdnsConfig {
+            retransmit_interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+            ttl: 120,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Query {
+    next_at: Instant,
+    interval: Duration,
+    deadline: Instant,
+}
+
+// 自分の名前への問い合わせに答え，相手の名前を問い合わせる．socketは
+// multicast groupに参加した`local`を使う．
+#[derive(Debug, Clone)]
+pub struct MdnsConnection {
+    local: SocketAddr,
+    config: MdnsConfig,
+    names: HashMap<String, IpAddr>,
+    cache: HashMap<String, IpAddr>,
+    queries: HashMap<String, Query>,
+    transmits: VecDeque<Transmit>,
+    events: VecDeque<MdnsEvent>,
+}
+
+impl MdnsConnection {
+    pub fn new(local: SocketAddr, config: MdnsConfig) -> Self {
+        MdnsConnection {
+            local,
+            config,
+            names: HashMap::new(),
+            cache: HashMap::new(),
+            queries: HashMap::new(),
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn get_local_address(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub fn get_group_address(&self) -> SocketAddr {
+        match self.local {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(MDNS_IPV4_GROUP), MDNS_PORT),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(MDNS_IPV6_GROUP), MDNS_PORT),
+        }
+    }
+
+    /// Answers the queries for `name` with `address`.
+    pub fn register(&mut self, name: &str, address: IpAddr) {
+        self.names.insert(name.to_ascii_lowercase(), address);
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.names.remove(&name.to_ascii_lowercase());
+    }
+
+    /// The resolved address of `name`, if it is known.
+    pub fn get_address(&self, name: &str) -> Option<IpAddr> {
+        self.cache.get(&name.to_ascii_lowercase()).copied()
+    }
+
+    /// Starts resolving `name`. The result is emitted as an event.
+    pub fn query(&mut self, name: &str, now: Instant) {
+        let name = name.to_ascii_lowercase();
+        if let Some(address) = self.cache.get(&name) {
+            self.events.push_back(MdnsEvent::Resolved {
+                name,
+                address: *address,
+            });
+            return;
+        }
+        if self.queries.contains_key(&name) {
+            return;
+        }
+        self.send_query(&name);
+        let interval = self.config.retransmit_interval;
+        self.queries.insert(
+            name,
+            Query {
+                next_at: now + interval,
+                interval,
+                deadline: now + self.config.timeout,
+            },
+        );
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<MdnsEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.queries
+            .values()
+            .map(|v| v.next_at.min(v.deadline))
+            .min()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        let mut expired = Vec::new();
+        let mut retransmits = Vec::new();
+        for (name, query) in self.queries.iter_mut() {
+            if query.deadline <= now {
+                expired.push(name.clone());
+            } else if query.next_at <= now {
+                query.interval *= 2;
+                query.next_at = now + query.interval;
+                retransmits.push(name.clone());
+            }
+        }
+        for name in retransmits {
+            self.send_query(&name);
+        }
+        for name in expired {
+            self.queries.remove(&name);
+            self.events.push_back(MdnsEvent::Unresolved { name });
+        }
+    }
+
+    /// Handles a datagram received on the multicast socket.
+    pub fn handle_receive(&mut self, _source: SocketAddr, data: &[u8]) -> Result<()> {
+        let message = MdnsMessage::from_bytes(data)?;
+        if message.is_response {
+            for answer in message.answers {
+                let name = answer.name.to_ascii_lowercase();
+                self.cache.insert(name.clone(), answer.address);
+                if self.queries.remove(&name).is_some() {
+                    self.events.push_back(MdnsEvent::Resolved {
+                        name,
+                        address: answer.address,
+                    });
+                }
+            }
+            return Ok(());
+        }
+
+        let answers: Vec<MdnsAnswer> = message
+            .questions
+            .iter()
+            .filter_map(|question| {
+                let name = question.name.to_ascii_lowercase();
+                let address = *self.names.get(&name)?;
+                let record_type = if address.is_ipv4() {
+                    RECORD_TYPE_A
+                } else {
+                    RECORD_TYPE_AAAA
+                };
+                if question.record_type != record_type {
+                    return None;
+                }
+                Some(MdnsAnswer {
+                    name: question.name.clone(),
+                    address,
+                    ttl: self.config.ttl,
+                })
+            })
+            .collect();
+        if !answers.is_empty() {
+            let response = MdnsMessage::response(answers);
+            let group = self.get_group_address();
+            self.transmits
+                .push_back(Transmit::new(self.local, group, response.to_bytes()));
+        }
+        Ok(())
+    }
+
+    fn send_query(&mut self, name: &str) {
+        let query = MdnsMessage::query(name);
+        let group = self.get_group_address();
+        self.transmits
+            .push_back(Transmit::new(self.local, group, query.to_bytes()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hostname_test() {
+        let name = generate_mdns_hostname();
+        assert_eq!(name.len(), 36 + ".local".len());
+        assert_eq!(&name[14..15], "4");
+        assert!(is_mdns_hostname(&name));
+        assert!(is_mdns_hostname("Host.LOCAL"));
+        assert!(!is_mdns_hostname(".local"));
+        assert!(!is_mdns_hostname("192.168.1.10"));
+        assert_ne!(name, generate_mdns_hostname());
+    }
+
+    #[test]
+    fn message_test() {
+        let query = MdnsMessage::query("a.local");
+        assert_eq!(MdnsMessage::from_bytes(&query.to_bytes()), Ok(query));
+
+        let response = MdnsMessage::response(vec![MdnsAnswer {
+            name: "a.local".to_string(),
+            address: "2001:db8::1".parse().unwrap(),
+            ttl: 120,
+        }]);
+        assert_eq!(MdnsMessage::from_bytes(&response.to_bytes()), Ok(response));
+
+        // a compressed name points back to the question.
+        let mut data = MdnsMessage::query("b.local").to_bytes();
+        data[7] = 1;
+        data.extend_from_slice(&[0xc0, 12, 0, 1, 0x80, 1, 0, 0, 0, 120, 0, 4, 10, 0, 0, 1]);
+        let message = MdnsMessage::from_bytes(&data).unwrap();
+        assert_eq!(message.answers[0].name, "b.local");
+        assert_eq!(message.answers[0].address, IpAddr::from([10, 0, 0, 1]));
+
+        assert_eq!(
+            MdnsMessage::from_bytes(&data[..data.len() - 1]),
+            Err(IceError::InvalidMdnsMessage)
+        );
+        let mut looped = MdnsMessage::query("b.local").to_bytes();
+        looped.truncate(DNS_HEADER_LENGTH);
+        looped.extend_from_slice(&[0xc0, 12]);
+        assert_eq!(
+            MdnsMessage::from_bytes(&looped),
+            Err(IceError::InvalidMdnsMessage)
+        );
+    }
+
+    #[test]
+    fn connection_test() {
+        let now = Instant::now();
+        let mut responder =
+            MdnsConnection::new("10.0.0.2:5353".parse().unwrap(), MdnsConfig::default());
+        let mut querier =
+            MdnsConnection::new("10.0.0.1:5353".parse().unwrap(), MdnsConfig::default());
+        let name = generate_mdns_hostname();
+        responder.register(&name, "10.0.0.2".parse().unwrap());
+
+        querier.query(&name, now);
+        let query = querier.poll_transmit().unwrap();
+        assert_eq!(query.destination, "224.0.0.251:5353".parse().unwrap());
+        responder.handle_receive(query.source, &query.data).unwrap();
+        let response = responder.poll_transmit().unwrap();
+        querier
+            .handle_receive(response.source, &response.data)
+            .unwrap();
+        assert_eq!(
+            querier.poll_event(),
+            Some(MdnsEvent::Resolved {
+                name: name.clone(),
+                address: "10.0.0.2".parse().unwrap()
+            })
+        );
+        assert_eq!(
+            querier.get_address(&name.to_uppercase()),
+            Some("10.0.0.2".parse().unwrap())
+        );
+        assert_eq!(querier.poll_timeout(), None);
+
+        // an unknown name is retried, then given up.
+        querier.query("unknown.local", now);
+        let query = querier.poll_transmit().unwrap();
+        responder.handle_receive(query.source, &query.data).unwrap();
+        assert_eq!(responder.poll_transmit(), None);
+        let mut sent = 1;
+        while let Some(t) = querier.poll_timeout() {
+            querier.process(t);
+            while querier.poll_transmit().is_some() {
+                sent += 1;
+            }
+        }
+        assert_eq!(sent, 3);
+        assert_eq!(
+            querier.poll_event(),
+            Some(MdnsEvent::Unresolved {
+                name: "unknown.local".to_string()
+            })
+        );
+    }
+}
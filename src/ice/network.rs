@@ -0,0 +1,215 @@
+pub mod system;
+
+use crate::stun::transaction::DatagramTransport;
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+/// 169.254.0.0/16 and fe80::/10, which only reach the same link.
+pub fn is_link_local(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v) => v.is_link_local(),
+        IpAddr::V6(v) => v.is_unicast_link_local(),
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub index: u32,
+    pub addresses: Vec<IpAddr>,
+    pub is_loopback: bool,
+}
+
+impl NetworkInterface {
+    pub fn new(name: &str, index: u32, addresses: Vec<IpAddr>) -> Self {
+        NetworkInterface {
+            name: name.to_string(),
+            index,
+            addresses,
+            is_loopback: false,
+        }
+    }
+}
+
+// interfaceの列挙とsocketの作成を差し替えられるようにする．socket自体はproviderが持ち，
+// agentはローカルアドレスで送信元を指定する．
+pub trait NetworkProvider {
+    fn get_interfaces(&mut self) -> io::Result<Vec<NetworkInterface>>;
+
+    /// Binds a UDP socket and returns its actual local address (the port is
+    /// chosen by the system when `addr` has port 0).
+    fn bind_udp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr>;
+
+    /// Connects a TCP stream, wrapped in TLS when `tls` is set, and returns
+    /// its local address. A transmit from that address to `remote` is
+    /// written to the stream.
+    fn connect_tcp(
+        &mut self,
+        _local: SocketAddr,
+        _remote: SocketAddr,
+        _tls: bool,
+    ) -> io::Result<SocketAddr> {
+        Err(io::Error::other("TCP is not supported by this provider"))
+    }
+
+    /// Listens for TCP connections and returns the local address. Data
+    /// received on an accepted connection is handed over with this address
+    /// as the local one, and a transmit to the remote end is written to it.
+    fn listen_tcp(&mut self, _addr: SocketAddr) -> io::Result<SocketAddr> {
+        Err(io::Error::other("TCP is not supported by this provider"))
+    }
+
+    /// Binds a UDP socket on port 5353 joined to the mDNS multicast group
+    /// and returns its local address.
+    fn bind_mdns(&mut self) -> io::Result<SocketAddr> {
+        Err(io::Error::other("mDNS is not supported by this provider"))
+    }
+
+    /// The next change of the interface addresses, for a provider that
+    /// watches them.
+    fn poll_change(&mut self) -> Option<NetworkChange> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum NetworkChange {
+    AddressAdded {
+        interface: NetworkInterface,
+        address: IpAddr,
+    },
+    AddressRemoved {
+        interface: NetworkInterface,
+        address: IpAddr,
+    },
+}
+
+// 前回列挙したinterfaceとの差分を変化として返す．
+#[derive(Debug, Clone, Default)]
+pub struct NetworkWatcher {
+    interfaces: Option<Vec<NetworkInterface>>,
+}
+
+impl NetworkWatcher {
+    pub fn new() -> Self {
+        NetworkWatcher::default()
+    }
+
+    pub fn get_interfaces(&self) -> Option<&[NetworkInterface]> {
+        self.interfaces.as_deref()
+    }
+
+    /// Compares `interfaces` with the previous ones. The first update has
+    /// nothing to compare with and returns no change.
+    pub fn update(&mut self, interfaces: Vec<NetworkInterface>) -> Vec<NetworkChange> {
+        let previous = match self.interfaces.replace(interfaces) {
+            Some(v) => v,
+            None => return vec![],
+        };
+        let current = self.interfaces.as_deref().unwrap_or_default();
+        let has = |interfaces: &[NetworkInterface], name: &str, address: &IpAddr| {
+            interfaces
+                .iter()
+                .any(|v| v.name == name && v.addresses.contains(address))
+        };
+        let mut changes = Vec::new();
+        for interface in &previous {
+            for address in &interface.addresses {
+                if !has(current, &interface.name, address) {
+                    changes.push(NetworkChange::AddressRemoved {
+                        interface: interface.clone(),
+                        address: *address,
+                    });
+                }
+            }
+        }
+        for interface in current {
+            for address in &interface.addresses {
+                if !has(&previous, &interface.name, address) {
+                    changes.push(NetworkChange::AddressAdded {
+                        interface: interface.clone(),
+                        address: *address,
+                    });
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// A datagram to send from the socket bound to `source`, or data to write
+/// to the stream connected from `source` to `destination`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Transmit {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+impl Transmit {
+    pub fn new(source: SocketAddr, destination: SocketAddr, data: Vec<u8>) -> Self {
+        Transmit {
+            source,
+            destination,
+            data,
+        }
+    }
+}
+
+/// Queues what a STUN transaction sends as transmits from `source`.
+pub(crate) struct TransmitQueue<'a> {
+    pub source: SocketAddr,
+    pub queue: &'a mut VecDeque<Transmit>,
+}
+
+impl<'a> DatagramTransport for TransmitQueue<'a> {
+    fn send_to(&mut self, data: &[u8], destination: SocketAddr) -> io::Result<usize> {
+        self.queue
+            .push_back(Transmit::new(self.source, destination, data.to_vec()));
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn network_watcher_test() {
+        let eth0 = NetworkInterface::new("eth0", 2, vec!["192.168.1.10".parse().unwrap()]);
+        let mut watcher = NetworkWatcher::new();
+        assert_eq!(watcher.update(vec![eth0.clone()]), vec![]);
+        assert_eq!(watcher.update(vec![eth0.clone()]), vec![]);
+
+        // the address moved to another interface.
+        let wlan0 = NetworkInterface::new(
+            "wlan0",
+            3,
+            vec![
+                "192.168.1.10".parse().unwrap(),
+                "2001:db8::10".parse().unwrap(),
+            ],
+        );
+        let changes = watcher.update(vec![wlan0.clone()]);
+        assert_eq!(
+            changes,
+            vec![
+                NetworkChange::AddressRemoved {
+                    interface: eth0,
+                    address: "192.168.1.10".parse().unwrap(),
+                },
+                NetworkChange::AddressAdded {
+                    interface: wlan0.clone(),
+                    address: "192.168.1.10".parse().unwrap(),
+                },
+                NetworkChange::AddressAdded {
+                    interface: wlan0.clone(),
+                    address: "2001:db8::10".parse().unwrap(),
+                },
+            ]
+        );
+        assert_eq!(watcher.get_interfaces(), Some(&[wlan0][..]));
+    }
+}
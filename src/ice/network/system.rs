@@ -0,0 +1,389 @@
+// https://man7.org/linux/man-pages/man3/getifaddrs.3.html
+
+/*
+    get_interfaces  getifaddrs(3) on unix, the source addresses of the
+                    default routes elsewhere
+    bind_udp        a non-blocking UDP socket
+    listen_tcp      a non-blocking TCP listener, accepted in poll_event
+    connect_tcp     a TCP stream from the address the system chooses,
+                    without TLS
+    bind_mdns       a UDP socket on 0.0.0.0:5353 joined to 224.0.0.251
+    poll_change     the interfaces enumerated again every watch interval
+*/
+
+use crate::ice::mdns::{MDNS_IPV4_GROUP, MDNS_PORT};
+use crate::ice::network::{
+    NetworkChange, NetworkInterface, NetworkProvider, NetworkWatcher, Transmit,
+};
+use crate::time::Instant;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::time::Duration;
+
+const RECEIVE_BUFFER_SIZE: usize = 65536;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum SystemEvent {
+    /// A datagram, or data read from a TCP stream, received on `local`.
+    Received {
+        local: SocketAddr,
+        source: SocketAddr,
+        data: Vec<u8>,
+    },
+    /// A TCP stream from `remote` was accepted by the listener on `local`.
+    Accepted {
+        local: SocketAddr,
+        remote: SocketAddr,
+    },
+    /// A TCP stream was closed by the peer or failed.
+    Closed {
+        local: SocketAddr,
+        remote: SocketAddr,
+    },
+}
+
+// OSのsocketによるNetworkProvider．applicationがpoll_eventとsendを回す．
+#[derive(Debug)]
+pub struct SystemNetwork {
+    udp: HashMap<SocketAddr, UdpSocket>,
+    listeners: HashMap<SocketAddr, TcpListener>,
+    // 受け入れたstreamはlistenerのアドレスをローカルとする．
+    streams: HashMap<(SocketAddr, SocketAddr), TcpStream>,
+    watcher: NetworkWatcher,
+    watch_interval: Duration,
+    watched_at: Option<Instant>,
+    changes: VecDeque<NetworkChange>,
+    buffer: Vec<u8>,
+}
+
+impl SystemNetwork {
+    pub fn new() -> Self {
+        SystemNetwork {
+            udp: HashMap::new(),
+            listeners: HashMap::new(),
+            streams: HashMap::new(),
+            watcher: NetworkWatcher::new(),
+            watch_interval: Duration::from_secs(2),
+            watched_at: None,
+            changes: VecDeque::new(),
+            buffer: vec![0; RECEIVE_BUFFER_SIZE],
+        }
+    }
+
+    pub fn get_watch_interval(&self) -> Duration {
+        self.watch_interval
+    }
+
+    pub fn set_watch_interval(&mut self, interval: Duration) {
+        self.watch_interval = interval;
+    }
+
+    /// Sends a transmit of the ICE agent from the socket or the stream it
+    /// names.
+    pub fn send(&mut self, transmit: &Transmit) -> io::Result<()> {
+        let key = (transmit.source, transmit.destination);
+        if let Some(stream) = self.streams.get_mut(&key) {
+            // a partial write would break the framing.
+            stream.set_nonblocking(false)?;
+            let written = stream.write_all(&transmit.data);
+            stream.set_nonblocking(true)?;
+            return written;
+        }
+        match self.udp.get(&transmit.source) {
+            Some(socket) => socket
+                .send_to(&transmit.data, transmit.destination)
+                .map(|_| ()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no socket is bound to the source address",
+            )),
+        }
+    }
+
+    /// Accepts a TCP stream or reads what arrived on a socket, without
+    /// blocking. `None` when nothing is ready.
+    pub fn poll_event(&mut self) -> io::Result<Option<SystemEvent>> {
+        for (local, listener) in &self.listeners {
+            match listener.accept() {
+                Ok((stream, remote)) => {
+                    stream.set_nonblocking(true)?;
+                    self.streams.insert((*local, remote), stream);
+                    return Ok(Some(SystemEvent::Accepted {
+                        local: *local,
+                        remote,
+                    }));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for (local, socket) in &self.udp {
+            match socket.recv_from(&mut self.buffer) {
+                Ok((length, source)) => {
+                    return Ok(Some(SystemEvent::Received {
+                        local: *local,
+                        source,
+                        data: self.buffer[..length].to_vec(),
+                    }));
+                }
+                // the ICMP error of an earlier datagram.
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::ConnectionReset => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let mut closed = None;
+        for (key, stream) in self.streams.iter_mut() {
+            match stream.read(&mut self.buffer) {
+                Ok(0) => {
+                    closed = Some(*key);
+                    break;
+                }
+                Ok(length) => {
+                    return Ok(Some(SystemEvent::Received {
+                        local: key.0,
+                        source: key.1,
+                        data: self.buffer[..length].to_vec(),
+                    }));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(_) => {
+                    closed = Some(*key);
+                    break;
+                }
+            }
+        }
+        Ok(closed.map(|(local, remote)| {
+            self.streams.remove(&(local, remote));
+            SystemEvent::Closed { local, remote }
+        }))
+    }
+
+    /// Closes the socket, the listener or the streams bound to `local`.
+    pub fn close(&mut self, local: SocketAddr) {
+        self.udp.remove(&local);
+        self.listeners.remove(&local);
+        self.streams.retain(|k, _| k.0 != local);
+    }
+}
+
+impl Default for SystemNetwork {
+    fn default() -> Self {
+        SystemNetwork::new()
+    }
+}
+
+impl NetworkProvider for SystemNetwork {
+    fn get_interfaces(&mut self) -> io::Result<Vec<NetworkInterface>> {
+        let interfaces = get_system_interfaces()?;
+        // the first enumeration is what the changes are watched from.
+        if self.watcher.get_interfaces().is_none() {
+            self.watcher.update(interfaces.clone());
+            self.watched_at = Some(Instant::now());
+        }
+        Ok(interfaces)
+    }
+
+    fn bind_udp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        let local = socket.local_addr()?;
+        self.udp.insert(local, socket);
+        Ok(local)
+    }
+
+    fn connect_tcp(
+        &mut self,
+        _local: SocketAddr,
+        remote: SocketAddr,
+        tls: bool,
+    ) -> io::Result<SocketAddr> {
+        if tls {
+            return Err(io::Error::other("TLS is not supported by this provider"));
+        }
+        let stream = TcpStream::connect_timeout(&remote, CONNECT_TIMEOUT)?;
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        let local = stream.local_addr()?;
+        self.streams.insert((local, remote), stream);
+        Ok(local)
+    }
+
+    fn listen_tcp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local = listener.local_addr()?;
+        self.listeners.insert(local, listener);
+        Ok(local)
+    }
+
+    fn bind_mdns(&mut self) -> io::Result<SocketAddr> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, MDNS_PORT))?;
+        socket.join_multicast_v4(&MDNS_IPV4_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+        socket.set_nonblocking(true)?;
+        let local = socket.local_addr()?;
+        self.udp.insert(local, socket);
+        Ok(local)
+    }
+
+    fn poll_change(&mut self) -> Option<NetworkChange> {
+        if let Some(v) = self.changes.pop_front() {
+            return Some(v);
+        }
+        let now = Instant::now();
+        let interval = self.watch_interval;
+        if self
+            .watched_at
+            .is_some_and(|v| now.saturating_duration_since(v) < interval)
+        {
+            return None;
+        }
+        self.watched_at = Some(now);
+        let interfaces = get_system_interfaces().ok()?;
+        self.changes.extend(self.watcher.update(interfaces));
+        self.changes.pop_front()
+    }
+}
+
+#[cfg(unix)]
+fn get_system_interfaces() -> io::Result<Vec<NetworkInterface>> {
+    use std::ffi::CStr;
+    use std::net::{IpAddr, Ipv6Addr};
+
+    let mut interfaces: Vec<NetworkInterface> = Vec::new();
+    let mut list: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut list) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut current = list;
+    while !current.is_null() {
+        // the list stays valid until freeifaddrs.
+        let entry = unsafe { &*current };
+        current = entry.ifa_next;
+        if entry.ifa_addr.is_null() || entry.ifa_flags & libc::IFF_UP as libc::c_uint == 0 {
+            continue;
+        }
+        let address = match i32::from(unsafe { (*entry.ifa_addr).sa_family }) {
+            libc::AF_INET => {
+                let v = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in) };
+                IpAddr::from(Ipv4Addr::from(u32::from_be(v.sin_addr.s_addr)))
+            }
+            libc::AF_INET6 => {
+                let v = unsafe { &*(entry.ifa_addr as *const libc::sockaddr_in6) };
+                IpAddr::from(Ipv6Addr::from(v.sin6_addr.s6_addr))
+            }
+            _ => continue,
+        };
+        let name = unsafe { CStr::from_ptr(entry.ifa_name) }
+            .to_string_lossy()
+            .into_owned();
+        match interfaces.iter_mut().find(|v| v.name == name) {
+            Some(v) => v.addresses.push(address),
+            None => {
+                let index = unsafe { libc::if_nametoindex(entry.ifa_name) };
+                let mut interface = NetworkInterface::new(&name, index, vec![address]);
+                interface.is_loopback = entry.ifa_flags & libc::IFF_LOOPBACK as libc::c_uint != 0;
+                interfaces.push(interface);
+            }
+        }
+    }
+    unsafe { libc::freeifaddrs(list) };
+    Ok(interfaces)
+}
+
+#[cfg(not(unix))]
+fn get_system_interfaces() -> io::Result<Vec<NetworkInterface>> {
+    // connecting a UDP socket picks the source address and sends nothing.
+    let mut addresses = Vec::new();
+    for (bind, probe) in [("0.0.0.0:0", "192.0.2.1:9"), ("[::]:0", "[2001:db8::1]:9")].iter() {
+        let socket = match UdpSocket::bind(bind) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if let Ok(local) = socket.connect(probe).and_then(|_| socket.local_addr()) {
+            addresses.push(local.ip());
+        }
+    }
+    Ok(vec![NetworkInterface::new("default", 0, addresses)])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::IpAddr;
+
+    fn wait_event(network: &mut SystemNetwork) -> SystemEvent {
+        for _ in 0..1000 {
+            if let Some(v) = network.poll_event().unwrap() {
+                return v;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("no event");
+    }
+
+    #[test]
+    fn system_network_test() {
+        let mut network = SystemNetwork::new();
+        let interfaces = network.get_interfaces().unwrap();
+        let loopback: IpAddr = Ipv4Addr::LOCALHOST.into();
+        assert!(interfaces
+            .iter()
+            .any(|v| v.is_loopback && v.addresses.contains(&loopback)));
+        assert_eq!(network.poll_change(), None);
+
+        let a = network.bind_udp(SocketAddr::new(loopback, 0)).unwrap();
+        let b = network.bind_udp(SocketAddr::new(loopback, 0)).unwrap();
+        assert_ne!(a.port(), 0);
+        network
+            .send(&Transmit::new(a, b, b"datagram".to_vec()))
+            .unwrap();
+        assert_eq!(
+            wait_event(&mut network),
+            SystemEvent::Received {
+                local: b,
+                source: a,
+                data: b"datagram".to_vec(),
+            }
+        );
+
+        let listener = network.listen_tcp(SocketAddr::new(loopback, 0)).unwrap();
+        let local = network.connect_tcp(a, listener, false).unwrap();
+        assert_eq!(
+            wait_event(&mut network),
+            SystemEvent::Accepted {
+                local: listener,
+                remote: local,
+            }
+        );
+        network
+            .send(&Transmit::new(local, listener, b"stream".to_vec()))
+            .unwrap();
+        assert_eq!(
+            wait_event(&mut network),
+            SystemEvent::Received {
+                local: listener,
+                source: local,
+                data: b"stream".to_vec(),
+            }
+        );
+        assert!(network.connect_tcp(a, listener, true).is_err());
+
+        network.close(local);
+        assert_eq!(
+            wait_event(&mut network),
+            SystemEvent::Closed {
+                local: listener,
+                remote: local,
+            }
+        );
+        assert!(network
+            .send(&Transmit::new(a, b, vec![]))
+            .and_then(|_| network.send(&Transmit::new(local, listener, vec![])))
+            .is_err());
+    }
+}
@@ -0,0 +1,120 @@
+// https://www.w3.org/TR/webrtc/#rtcicetransportpolicy-enum
+// https://tools.ietf.org/html/rfc8828
+
+/*
+    types       gathered and paired local candidates
+    all         host, srflx, relay
+    no-host     srflx, relay    the host sockets are bound as their bases
+    relay-only  relay           nothing reveals the local addresses
+
+    family      the local addresses gathered, and the remote ones paired
+    ports       the local ports the sockets are bound to
+*/
+
+use crate::ice::candidate::CandidateType;
+
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CandidateTypePolicy {
+    All,
+    NoHost,
+    RelayOnly,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum IpFamilyPolicy {
+    Any,
+    Ipv4Only,
+    Ipv6Only,
+}
+
+/// Inclusive on both ends.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct PortRange {
+    pub min: u16,
+    pub max: u16,
+}
+
+impl PortRange {
+    pub fn new(min: u16, max: u16) -> Self {
+        PortRange { min, max }
+    }
+
+    pub fn contains(&self, port: u16) -> bool {
+        self.min <= port && port <= self.max
+    }
+}
+
+// どの候補を集めてpairにするかの制限．
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CandidatePolicy {
+    pub types: CandidateTypePolicy,
+    pub family: IpFamilyPolicy,
+    /// Any port when `None`.
+    pub ports: Option<PortRange>,
+}
+
+impl CandidatePolicy {
+    pub fn allows_type(&self, candidate_type: CandidateType) -> bool {
+        match self.types {
+            CandidateTypePolicy::All => true,
+            CandidateTypePolicy::NoHost => candidate_type != CandidateType::Host,
+            CandidateTypePolicy::RelayOnly => candidate_type == CandidateType::Relay,
+        }
+    }
+
+    pub fn allows_address(&self, address: IpAddr) -> bool {
+        match self.family {
+            IpFamilyPolicy::Any => true,
+            IpFamilyPolicy::Ipv4Only => address.is_ipv4(),
+            IpFamilyPolicy::Ipv6Only => address.is_ipv6(),
+        }
+    }
+
+    pub fn allows_port(&self, port: u16) -> bool {
+        self.ports.is_none_or(|v| v.contains(port))
+    }
+}
+
+impl Default for CandidatePolicy {
+    fn default() -> Self {
+        CandidatePolicy {
+            types: CandidateTypePolicy::All,
+            family: IpFamilyPolicy::Any,
+            ports: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn candidate_policy_test() {
+        let policy = CandidatePolicy::default();
+        assert!(policy.allows_type(CandidateType::Host));
+        assert!(policy.allows_address("2001:db8::1".parse().unwrap()));
+        assert!(policy.allows_port(1));
+
+        let policy = CandidatePolicy {
+            types: CandidateTypePolicy::NoHost,
+            family: IpFamilyPolicy::Ipv4Only,
+            ports: Some(PortRange::new(40000, 40009)),
+        };
+        assert!(!policy.allows_type(CandidateType::Host));
+        assert!(policy.allows_type(CandidateType::ServerReflexive));
+        assert!(policy.allows_address("192.0.2.1".parse().unwrap()));
+        assert!(!policy.allows_address("2001:db8::1".parse().unwrap()));
+        assert!(policy.allows_port(40000) && policy.allows_port(40009));
+        assert!(!policy.allows_port(40010));
+
+        let policy = CandidatePolicy {
+            types: CandidateTypePolicy::RelayOnly,
+            ..CandidatePolicy::default()
+        };
+        assert!(!policy.allows_type(CandidateType::ServerReflexive));
+        assert!(policy.allows_type(CandidateType::Relay));
+    }
+}
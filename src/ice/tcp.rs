@@ -0,0 +1,154 @@
+// https://tools.ietf.org/html/rfc6544
+// https://tools.ietf.org/html/rfc4571
+
+/*
+    local     remote
+    active  -> passive   the active side connects
+    passive <- active    paired once the connection arrives (peer reflexive)
+    so     <-> so        both sides connect at once, either connection wins
+
+    On an established connection every STUN message and every packet is
+    framed with its length:
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |             LENGTH            |  STUN or application data    |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+    |                                                               |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::ice::candidate::{CandidateType, TcpType};
+
+/// The discard port an active candidate is signalled with.
+pub const TCP_ACTIVE_PORT: u16 = 9;
+const FRAME_HEADER_LENGTH: usize = 2;
+
+/// RFC 6544 Section 4.2: the direction preference fills the top 3 bits of
+/// the local preference, `other_preference` the other 13.
+pub fn compute_tcp_local_preference(
+    candidate_type: CandidateType,
+    tcp_type: TcpType,
+    other_preference: u16,
+) -> u16 {
+    let direction = match (candidate_type, tcp_type) {
+        (CandidateType::Host, TcpType::Active) | (CandidateType::Relay, TcpType::Active) => 6,
+        (CandidateType::Host, TcpType::Passive) | (CandidateType::Relay, TcpType::Passive) => 4,
+        (CandidateType::Host, TcpType::SimultaneousOpen)
+        | (CandidateType::Relay, TcpType::SimultaneousOpen) => 2,
+        (_, TcpType::SimultaneousOpen) => 6,
+        (_, TcpType::Active) => 4,
+        (_, TcpType::Passive) => 2,
+    };
+    (direction << 13) | (other_preference & 0x1fff)
+}
+
+/// Whether a local candidate of `local` type can be checked with a remote
+/// one of `remote` type.
+pub fn is_tcp_pairable(local: TcpType, remote: TcpType) -> bool {
+    matches!(
+        (local, remote),
+        (TcpType::Active, TcpType::Passive)
+            | (TcpType::Passive, TcpType::Active)
+            | (TcpType::SimultaneousOpen, TcpType::SimultaneousOpen)
+    )
+}
+
+/// Prefixes `data` with its length, `None` if it does not fit in 16 bits.
+pub fn frame_packet(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() > usize::from(u16::MAX) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(FRAME_HEADER_LENGTH + data.len());
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+    Some(out)
+}
+
+// 接続ごとに受け取ったbyte列をframeに切り分ける．
+#[derive(Debug, Clone, Default)]
+pub struct TcpFramer {
+    buffer: Vec<u8>,
+}
+
+impl TcpFramer {
+    pub fn new() -> Self {
+        TcpFramer::default()
+    }
+
+    pub fn get_buffered_length(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete frame without its length.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        if self.buffer.len() < FRAME_HEADER_LENGTH {
+            return None;
+        }
+        let length = usize::from(u16::from_be_bytes([self.buffer[0], self.buffer[1]]));
+        if self.buffer.len() < FRAME_HEADER_LENGTH + length {
+            return None;
+        }
+        let frame = self.buffer[FRAME_HEADER_LENGTH..FRAME_HEADER_LENGTH + length].to_vec();
+        self.buffer.drain(..FRAME_HEADER_LENGTH + length);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tcp_local_preference_test() {
+        let active = compute_tcp_local_preference(CandidateType::Host, TcpType::Active, 8191);
+        let passive = compute_tcp_local_preference(CandidateType::Host, TcpType::Passive, 8191);
+        let so = compute_tcp_local_preference(CandidateType::Host, TcpType::SimultaneousOpen, 8191);
+        assert_eq!(active, 0xdfff);
+        assert!(active > passive && passive > so);
+        let srflx_so = compute_tcp_local_preference(
+            CandidateType::ServerReflexive,
+            TcpType::SimultaneousOpen,
+            0,
+        );
+        assert_eq!(srflx_so, 6 << 13);
+
+        assert!(is_tcp_pairable(TcpType::Active, TcpType::Passive));
+        assert!(is_tcp_pairable(
+            TcpType::SimultaneousOpen,
+            TcpType::SimultaneousOpen
+        ));
+        assert!(!is_tcp_pairable(TcpType::Active, TcpType::Active));
+        assert!(!is_tcp_pairable(
+            TcpType::Passive,
+            TcpType::SimultaneousOpen
+        ));
+    }
+
+    #[test]
+    fn tcp_framer_test() {
+        let mut stream = frame_packet(b"hello").unwrap();
+        stream.extend_from_slice(&frame_packet(b"").unwrap());
+        stream.extend_from_slice(&frame_packet(b"world").unwrap());
+        assert_eq!(&stream[..2], &[0, 5]);
+        assert_eq!(frame_packet(&[0; 65535]).map(|v| v.len()), Some(65537));
+        assert_eq!(frame_packet(&[0; 65536]), None);
+
+        // fed one byte at a time, as TCP may deliver it.
+        let mut framer = TcpFramer::new();
+        let mut frames = vec![];
+        for byte in &stream {
+            framer.push(&[*byte]);
+            while let Some(frame) = framer.pop() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, vec![b"hello".to_vec(), vec![], b"world".to_vec()]);
+        assert_eq!(framer.get_buffered_length(), 0);
+    }
+}
@@ -0,0 +1,124 @@
+// https://tools.ietf.org/html/rfc8829
+// https://www.w3.org/TR/webrtc/#rtcsignalingstate-enum
+
+/*
+                 setLocal(offer)                setRemote(pranswer)
+      Stable  ------------------>  HaveLocalOffer  -------->  HaveRemotePranswer
+              <------------------                  <--------
+               setRemote(answer)                   setRemote(answer), to Stable
+
+                 setRemote(offer)               setLocal(pranswer)
+      Stable  ------------------>  HaveRemoteOffer  ------->  HaveLocalPranswer
+              <------------------                  <--------
+               setLocal(answer)                    setLocal(answer), to Stable
+
+    a rollback goes back to Stable from an offer.
+*/
+
+pub mod bundle;
+pub mod capabilities;
+pub mod codec;
+pub mod dtls;
+pub mod extmap;
+pub mod fmtp;
+pub mod ice;
+pub mod msid;
+pub mod receiver;
+pub mod rtcp_mux;
+pub mod sender;
+pub mod session;
+pub mod signaling;
+pub mod simulcast;
+pub mod ssrc;
+pub mod track;
+pub mod transceiver;
+
+use crate::sdp::SdpError;
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, JsepError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum JsepError {
+    #[fail(display = "SDP failed: {:?}", error)]
+    SdpError { error: SdpError },
+
+    #[fail(display = "{:?} can't be set in {:?}.", sdp_type, state)]
+    InvalidState {
+        state: SignalingState,
+        sdp_type: SdpType,
+    },
+
+    #[fail(display = "Session description is invalid: {}", reason)]
+    InvalidDescription { reason: String },
+
+    #[fail(display = "Local description is not the one created last.")]
+    InvalidModification,
+
+    #[fail(display = "Capability is invalid: {}", reason)]
+    InvalidCapability { reason: String },
+
+    #[fail(display = "Track is invalid: {}", reason)]
+    InvalidTrack { reason: String },
+}
+
+impl From<SdpError> for JsepError {
+    fn from(error: SdpError) -> Self {
+        JsepError::SdpError { error }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SdpType {
+    Offer,
+    Pranswer,
+    Answer,
+    Rollback,
+}
+
+impl SdpType {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            SdpType::Offer => "offer",
+            SdpType::Pranswer => "pranswer",
+            SdpType::Answer => "answer",
+            SdpType::Rollback => "rollback",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SdpType> {
+        match name {
+            "offer" => Some(SdpType::Offer),
+            "pranswer" => Some(SdpType::Pranswer),
+            "answer" => Some(SdpType::Answer),
+            "rollback" => Some(SdpType::Rollback),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SignalingState {
+    Stable,
+    HaveLocalOffer,
+    HaveRemoteOffer,
+    HaveLocalPranswer,
+    HaveRemotePranswer,
+    Closed,
+}
+
+// RTCSessionDescription．rollbackのsdpは空．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Description {
+    pub sdp_type: SdpType,
+    pub sdp: String,
+}
+
+impl Description {
+    pub fn new(sdp_type: SdpType, sdp: &str) -> Self {
+        Description {
+            sdp_type,
+            sdp: sdp.to_string(),
+        }
+    }
+}
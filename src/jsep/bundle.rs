@@ -0,0 +1,85 @@
+// https://tools.ietf.org/html/rfc8843
+// https://tools.ietf.org/html/rfc8829#section-4.1.1
+// https://www.w3.org/TR/webrtc/#rtcbundlepolicy-enum
+
+/*
+    a=group:BUNDLE <tag mid> <mid> ...
+
+    the m= sections of a group share the transport of the first one, the
+    tagged m= section. they keep their ICE and DTLS attributes as browsers
+    write them, a peer leaving them out is taken too.
+
+    the initial offer marks m= sections bundle-only, port 0 and
+    a=bundle-only, so a peer without BUNDLE rejects them:
+      balanced     all but the first of each kind
+      max-compat   none
+      max-bundle   all but the first
+    an answer to an offer without BUNDLE takes only the first m= section
+    with max-bundle.
+*/
+
+use crate::sdp::media::MediaKind;
+use crate::sdp::session::SessionDescription;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum BundlePolicy {
+    #[default]
+    Balanced,
+    MaxCompat,
+    MaxBundle,
+}
+
+impl BundlePolicy {
+    /// Whether the m= section of `kind` is bundle-only in an initial offer,
+    /// after the `previous` kinds.
+    pub fn is_bundle_only(self, kind: MediaKind, previous: &[MediaKind]) -> bool {
+        match self {
+            BundlePolicy::Balanced => previous.contains(&kind),
+            BundlePolicy::MaxCompat => false,
+            BundlePolicy::MaxBundle => !previous.is_empty(),
+        }
+    }
+}
+
+pub fn get_bundle_groups(session: &SessionDescription) -> Vec<Vec<String>> {
+    session
+        .get_groups("BUNDLE")
+        .into_iter()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.into_iter().map(|v| v.to_string()).collect())
+        .collect()
+}
+
+/// The tagged mid of the group with `mid`.
+pub fn get_bundle_tag<'a>(groups: &'a [Vec<String>], mid: &str) -> Option<&'a str> {
+    groups
+        .iter()
+        .find(|v| v.iter().any(|v| v == mid))
+        .map(|v| v[0].as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdp::session::test::CHROME_OFFER;
+
+    #[test]
+    fn bundle_policy_test() {
+        let kinds = [MediaKind::Audio, MediaKind::Video];
+        assert!(!BundlePolicy::Balanced.is_bundle_only(MediaKind::Audio, &[]));
+        assert!(!BundlePolicy::Balanced.is_bundle_only(MediaKind::Video, &kinds[..1]));
+        assert!(BundlePolicy::Balanced.is_bundle_only(MediaKind::Video, &kinds));
+        assert!(!BundlePolicy::MaxCompat.is_bundle_only(MediaKind::Video, &kinds));
+        assert!(!BundlePolicy::MaxBundle.is_bundle_only(MediaKind::Audio, &[]));
+        assert!(BundlePolicy::MaxBundle.is_bundle_only(MediaKind::Video, &kinds[..1]));
+    }
+
+    #[test]
+    fn bundle_groups_test() {
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        let groups = get_bundle_groups(&session);
+        assert_eq!(groups, vec![vec!["0", "1", "2"]]);
+        assert_eq!(get_bundle_tag(&groups, "2"), Some("0"));
+        assert_eq!(get_bundle_tag(&groups, "3"), None);
+    }
+}
@@ -0,0 +1,315 @@
+// https://www.w3.org/TR/webrtc/#dom-rtcrtpsender-getcapabilities
+// https://tools.ietf.org/html/rfc8829#section-5.2.1
+
+/*
+    the codecs and header extensions of each kind, with whether they are
+    sent, received or both. a new transceiver takes all of them, an m=
+    section is written with the ones its direction can use:
+
+      sendrecv    sent and received
+      sendonly    sent
+      recvonly    received
+      inactive    either
+
+    codecs of dynamic payload types 96 to 127 and extensions can be added,
+    for the transceivers added after. the codec preferences of a transceiver
+    are codecs of the registry, not only ones that repair the media.
+*/
+
+use crate::jsep::codec::{get_default_audio_codecs, get_default_video_codecs, Codec};
+use crate::jsep::extmap::{get_default_audio_extmaps, get_default_video_extmaps, Extmap};
+use crate::jsep::fmtp::is_compatible;
+use crate::jsep::{JsepError, Result};
+use crate::sdp::media::{Direction, MediaKind};
+
+const DYNAMIC_PAYLOAD_TYPES: std::ops::RangeInclusive<u8> = 96..=127;
+
+fn invalid(reason: String) -> JsepError {
+    JsepError::InvalidCapability { reason }
+}
+
+// RTXは直すcodecと一緒に残す．
+fn without_unpaired_rtx(codecs: &[&Codec]) -> Vec<Codec> {
+    codecs
+        .iter()
+        .filter(|v| {
+            !v.is_rtx()
+                || codecs
+                    .iter()
+                    .any(|c| Some(c.payload_type) == v.get_apt() && !c.is_rtx())
+        })
+        .map(|v| (*v).clone())
+        .collect()
+}
+
+// RTCRtpCapabilities．
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RtpCapabilities {
+    pub codecs: Vec<Codec>,
+    pub extmaps: Vec<Extmap>,
+}
+
+// 一つのkindのcodecと，それを送るか受けるか．
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct KindCapabilities {
+    codecs: Vec<(Codec, Direction)>,
+    extmaps: Vec<Extmap>,
+}
+
+impl KindCapabilities {
+    fn new(codecs: Vec<Codec>, extmaps: Vec<Extmap>) -> Self {
+        KindCapabilities {
+            codecs: codecs
+                .into_iter()
+                .map(|v| (v, Direction::SendRecv))
+                .collect(),
+            extmaps,
+        }
+    }
+
+    fn get(&self, send: bool, recv: bool) -> RtpCapabilities {
+        let codecs: Vec<&Codec> = self
+            .codecs
+            .iter()
+            .filter(|(_, v)| (!send || v.is_send()) && (!recv || v.is_recv()))
+            .map(|(v, _)| v)
+            .collect();
+        let extmaps = self
+            .extmaps
+            .iter()
+            .filter(|v| {
+                let direction = v.direction.unwrap_or(Direction::SendRecv);
+                (!send || direction.is_send()) && (!recv || direction.is_recv())
+            })
+            .cloned()
+            .collect();
+        RtpCapabilities {
+            codecs: without_unpaired_rtx(&codecs),
+            extmaps,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CapabilityRegistry {
+    audio: KindCapabilities,
+    video: KindCapabilities,
+}
+
+impl Default for CapabilityRegistry {
+    fn default() -> Self {
+        CapabilityRegistry {
+            audio: KindCapabilities::new(get_default_audio_codecs(), get_default_audio_extmaps()),
+            video: KindCapabilities::new(get_default_video_codecs(), get_default_video_extmaps()),
+        }
+    }
+}
+
+impl CapabilityRegistry {
+    pub fn new() -> Self {
+        CapabilityRegistry::default()
+    }
+
+    fn get(&self, kind: MediaKind) -> Option<&KindCapabilities> {
+        match kind {
+            MediaKind::Audio => Some(&self.audio),
+            MediaKind::Video => Some(&self.video),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, kind: MediaKind) -> Result<&mut KindCapabilities> {
+        match kind {
+            MediaKind::Audio => Ok(&mut self.audio),
+            MediaKind::Video => Ok(&mut self.video),
+            _ => Err(invalid(format!("no codec for {:?}", kind))),
+        }
+    }
+
+    /// What can be sent, empty for application.
+    pub fn get_sender_capabilities(&self, kind: MediaKind) -> RtpCapabilities {
+        self.get(kind)
+            .map(|v| v.get(true, false))
+            .unwrap_or_default()
+    }
+
+    pub fn get_receiver_capabilities(&self, kind: MediaKind) -> RtpCapabilities {
+        self.get(kind)
+            .map(|v| v.get(false, true))
+            .unwrap_or_default()
+    }
+
+    /// The codecs and extensions of a direction, see the top.
+    pub fn get_capabilities(&self, kind: MediaKind, direction: Direction) -> RtpCapabilities {
+        let (send, recv) = match direction {
+            Direction::Inactive => (false, false),
+            direction => (direction.is_send(), direction.is_recv()),
+        };
+        self.get(kind)
+            .map(|v| v.get(send, recv))
+            .unwrap_or_default()
+    }
+
+    /// Adds a codec of a dynamic payload type not used by the kind.
+    /// `direction` tells whether it is sent, received or both.
+    pub fn add_codec(&mut self, kind: MediaKind, codec: Codec, direction: Direction) -> Result<()> {
+        let capabilities = self.get_mut(kind)?;
+        if !DYNAMIC_PAYLOAD_TYPES.contains(&codec.payload_type) {
+            return Err(invalid(format!(
+                "payload type {} is not dynamic",
+                codec.payload_type
+            )));
+        }
+        if capabilities
+            .codecs
+            .iter()
+            .any(|(v, _)| v.payload_type == codec.payload_type)
+        {
+            return Err(invalid(format!(
+                "payload type {} is used",
+                codec.payload_type
+            )));
+        }
+        capabilities.codecs.push((codec, direction));
+        Ok(())
+    }
+
+    /// Adds a header extension of an id and URI not used by the kind.
+    pub fn add_extmap(&mut self, kind: MediaKind, extmap: Extmap) -> Result<()> {
+        let capabilities = self.get_mut(kind)?;
+        if capabilities
+            .extmaps
+            .iter()
+            .any(|v| v.id == extmap.id || v.uri == extmap.uri)
+        {
+            return Err(invalid(format!("extmap {} is used", extmap)));
+        }
+        capabilities.extmaps.push(extmap);
+        Ok(())
+    }
+
+    /// Whether the codecs can be the preferences of a transceiver of the
+    /// kind. An empty one resets them.
+    pub fn check_codec_preferences(&self, kind: MediaKind, codecs: &[Codec]) -> Result<()> {
+        let capabilities = self.get_capabilities(kind, Direction::Inactive);
+        if let Some(codec) = codecs
+            .iter()
+            .find(|v| !capabilities.codecs.iter().any(|c| is_compatible(c, v)))
+        {
+            return Err(invalid(format!(
+                "{}/{} is not supported",
+                codec.name, codec.clock_rate
+            )));
+        }
+        let repairing = ["rtx", "red", "ulpfec", "flexfec-03"];
+        if !codecs.is_empty()
+            && codecs
+                .iter()
+                .all(|v| repairing.iter().any(|r| v.name.eq_ignore_ascii_case(r)))
+        {
+            return Err(invalid("no codec of media".to_string()));
+        }
+        Ok(())
+    }
+
+    /// The codecs of `codecs` a direction can use, in their order.
+    pub fn filter_codecs(
+        &self,
+        kind: MediaKind,
+        codecs: &[Codec],
+        direction: Direction,
+    ) -> Vec<Codec> {
+        let capabilities = self.get_capabilities(kind, direction);
+        let codecs: Vec<&Codec> = codecs
+            .iter()
+            .filter(|v| capabilities.codecs.iter().any(|c| is_compatible(c, v)))
+            .collect();
+        without_unpaired_rtx(&codecs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn capabilities_test() {
+        let mut registry = CapabilityRegistry::new();
+        let names =
+            |v: RtpCapabilities| -> Vec<String> { v.codecs.into_iter().map(|v| v.name).collect() };
+        assert_eq!(
+            names(registry.get_sender_capabilities(MediaKind::Video)),
+            vec!["VP8", "rtx", "H264", "rtx"]
+        );
+        assert!(registry
+            .get_sender_capabilities(MediaKind::Application)
+            .codecs
+            .is_empty());
+
+        let vp9 = Codec::new(98, "VP9", 90000, None).with_parameters("profile-id=0");
+        registry
+            .add_codec(MediaKind::Video, vp9.clone(), Direction::RecvOnly)
+            .unwrap();
+        let rtx = Codec::new(99, "rtx", 90000, None).with_parameters("apt=98");
+        registry
+            .add_codec(MediaKind::Video, rtx, Direction::SendRecv)
+            .unwrap();
+        assert_eq!(
+            names(registry.get_receiver_capabilities(MediaKind::Video)),
+            vec!["VP8", "rtx", "H264", "rtx", "VP9", "rtx"]
+        );
+        // the RTX of VP9 is not sent either.
+        assert_eq!(
+            names(registry.get_sender_capabilities(MediaKind::Video)),
+            vec!["VP8", "rtx", "H264", "rtx"]
+        );
+        assert!(registry
+            .add_codec(MediaKind::Video, vp9.clone(), Direction::SendRecv)
+            .is_err());
+        assert!(registry
+            .add_codec(
+                MediaKind::Video,
+                Codec::new(8, "x", 90000, None),
+                Direction::SendRecv
+            )
+            .is_err());
+
+        let codecs = registry.get_receiver_capabilities(MediaKind::Video).codecs;
+        let filtered = registry.filter_codecs(MediaKind::Video, &codecs, Direction::SendRecv);
+        assert_eq!(filtered.len(), 4);
+        let filtered = registry.filter_codecs(MediaKind::Video, &codecs, Direction::RecvOnly);
+        assert_eq!(filtered.len(), 6);
+
+        let vp8 = Codec::new(96, "VP8", 90000, None);
+        let rtx = Codec::new(97, "rtx", 90000, None).with_parameters("apt=96");
+        assert!(registry
+            .check_codec_preferences(MediaKind::Video, &[vp8.clone(), rtx.clone()])
+            .is_ok());
+        assert!(registry
+            .check_codec_preferences(MediaKind::Video, &[rtx])
+            .is_err());
+        assert!(registry
+            .check_codec_preferences(MediaKind::Audio, &[vp8])
+            .is_err());
+
+        let extmap = Extmap::new(5, "urn:example").with_direction(Direction::SendOnly);
+        registry.add_extmap(MediaKind::Audio, extmap).unwrap();
+        assert!(registry
+            .add_extmap(MediaKind::Audio, Extmap::new(5, "urn:other"))
+            .is_err());
+        assert_eq!(
+            registry
+                .get_sender_capabilities(MediaKind::Audio)
+                .extmaps
+                .len(),
+            5
+        );
+        assert_eq!(
+            registry
+                .get_receiver_capabilities(MediaKind::Audio)
+                .extmaps
+                .len(),
+            4
+        );
+    }
+}
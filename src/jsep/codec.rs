@@ -0,0 +1,316 @@
+// https://tools.ietf.org/html/rfc8866#section-6.6
+// https://tools.ietf.org/html/rfc8829#section-5.3.1
+
+/*
+    a=rtpmap:<payload type> <encoding name>/<clock rate>[/<channels>]
+    a=fmtp:<payload type> <parameters>
+    a=rtcp-fb:<payload type> <feedback>      payload type * for all
+
+    an answer uses the payload types of the offer, for the codecs both
+    sides have, in the order of the offer. the fmtp tells whether two codecs
+    of a name are the same, see fmtp.rs.
+*/
+
+use crate::jsep::fmtp::is_compatible;
+use crate::jsep::{JsepError, Result};
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::MediaDescription;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Codec {
+    pub payload_type: u8,
+    pub name: String,
+    pub clock_rate: u32,
+    pub channels: Option<u16>,
+    /// The a=fmtp value, after the payload type.
+    pub parameters: Option<String>,
+    /// The a=rtcp-fb values, after the payload type.
+    pub feedbacks: Vec<String>,
+}
+
+impl Codec {
+    pub fn new(payload_type: u8, name: &str, clock_rate: u32, channels: Option<u16>) -> Self {
+        Codec {
+            payload_type,
+            name: name.to_string(),
+            clock_rate,
+            channels,
+            parameters: None,
+            feedbacks: vec![],
+        }
+    }
+
+    pub fn with_parameters(mut self, parameters: &str) -> Self {
+        self.parameters = Some(parameters.to_string());
+        self
+    }
+
+    pub fn with_feedback(mut self, feedback: &str) -> Self {
+        self.feedbacks.push(feedback.to_string());
+        self
+    }
+
+    /// RTX (RFC 4588) names the codec it repairs with `apt`.
+    pub fn is_rtx(&self) -> bool {
+        self.name.eq_ignore_ascii_case("rtx")
+    }
+
+    pub fn get_parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .as_deref()?
+            .split(';')
+            .filter_map(|v| {
+                let mut kv = v.trim().splitn(2, '=');
+                Some((kv.next()?, kv.next()?))
+            })
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_apt(&self) -> Option<u8> {
+        self.get_parameter("apt")?.parse().ok()
+    }
+
+    /// Same encoding name, clock rate and channels, one channel when none
+    /// is written.
+    pub fn is_same_codec(&self, other: &Codec) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+            && self.clock_rate == other.clock_rate
+            && self.channels.unwrap_or(1) == other.channels.unwrap_or(1)
+    }
+
+    fn get_rtpmap(&self) -> String {
+        match self.channels {
+            Some(v) => format!(
+                "{} {}/{}/{}",
+                self.payload_type, self.name, self.clock_rate, v
+            ),
+            None => format!("{} {}/{}", self.payload_type, self.name, self.clock_rate),
+        }
+    }
+}
+
+pub fn get_default_audio_codecs() -> Vec<Codec> {
+    vec![
+        Codec::new(111, "opus", 48000, Some(2))
+            .with_parameters("minptime=10;useinbandfec=1")
+            .with_feedback("transport-cc"),
+        Codec::new(0, "PCMU", 8000, None),
+        Codec::new(8, "PCMA", 8000, None),
+    ]
+}
+
+pub fn get_default_video_codecs() -> Vec<Codec> {
+    let feedbacks = |codec: Codec| {
+        codec
+            .with_feedback("goog-remb")
+            .with_feedback("transport-cc")
+            .with_feedback("ccm fir")
+            .with_feedback("nack")
+            .with_feedback("nack pli")
+    };
+    vec![
+        feedbacks(Codec::new(96, "VP8", 90000, None)),
+        Codec::new(97, "rtx", 90000, None).with_parameters("apt=96"),
+        feedbacks(Codec::new(102, "H264", 90000, None)).with_parameters(
+            "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f",
+        ),
+        Codec::new(103, "rtx", 90000, None).with_parameters("apt=102"),
+    ]
+}
+
+/// The codecs of the formats with an a=rtpmap, in the order of the m= line.
+pub fn get_codecs(media: &MediaDescription) -> Result<Vec<Codec>> {
+    let invalid = |reason: &str| JsepError::InvalidDescription {
+        reason: reason.to_string(),
+    };
+    let mut codecs: HashMap<u8, Codec> = HashMap::new();
+    for value in media.get_attribute_values("rtpmap") {
+        let (payload_type, encoding) =
+            split_payload_type(value).ok_or_else(|| invalid("rtpmap"))?;
+        let payload_type = payload_type.parse::<u8>().map_err(|_| invalid("rtpmap"))?;
+        let fields: Vec<&str> = encoding.split('/').collect();
+        if fields.len() < 2 || fields.len() > 3 || fields[0].is_empty() {
+            return Err(invalid("rtpmap"));
+        }
+        let clock_rate = fields[1].parse::<u32>().map_err(|_| invalid("rtpmap"))?;
+        let channels = match fields.get(2) {
+            Some(v) => Some(v.parse::<u16>().map_err(|_| invalid("rtpmap"))?),
+            None => None,
+        };
+        codecs.insert(
+            payload_type,
+            Codec::new(payload_type, fields[0], clock_rate, channels),
+        );
+    }
+    for value in media.get_attribute_values("fmtp") {
+        let (payload_type, parameters) =
+            split_payload_type(value).ok_or_else(|| invalid("fmtp"))?;
+        let codec = payload_type
+            .parse::<u8>()
+            .ok()
+            .and_then(|v| codecs.get_mut(&v));
+        if let Some(codec) = codec {
+            codec.parameters = Some(parameters.to_string());
+        }
+    }
+    for value in media.get_attribute_values("rtcp-fb") {
+        let (payload_type, feedback) =
+            split_payload_type(value).ok_or_else(|| invalid("rtcp-fb"))?;
+        if payload_type == "*" {
+            for codec in codecs.values_mut() {
+                codec.feedbacks.push(feedback.to_string());
+            }
+        } else if let Some(codec) = payload_type
+            .parse::<u8>()
+            .ok()
+            .and_then(|v| codecs.get_mut(&v))
+        {
+            codec.feedbacks.push(feedback.to_string());
+        }
+    }
+    Ok(media
+        .formats
+        .iter()
+        .filter_map(|v| v.parse::<u8>().ok())
+        .filter_map(|v| codecs.remove(&v))
+        .collect())
+}
+
+fn split_payload_type(value: &str) -> Option<(&str, &str)> {
+    let i = value.find(' ')?;
+    Some((&value[..i], value[i + 1..].trim_start()))
+}
+
+/// Sets the formats of the m= line and writes their attributes.
+pub fn add_codecs(media: &mut MediaDescription, codecs: &[Codec]) {
+    media.formats = codecs.iter().map(|v| v.payload_type.to_string()).collect();
+    for codec in codecs {
+        media
+            .attributes
+            .push(Attribute::new("rtpmap", &codec.get_rtpmap()));
+        for feedback in &codec.feedbacks {
+            media.attributes.push(Attribute::new(
+                "rtcp-fb",
+                &format!("{} {}", codec.payload_type, feedback),
+            ));
+        }
+        if let Some(ref parameters) = codec.parameters {
+            media.attributes.push(Attribute::new(
+                "fmtp",
+                &format!("{} {}", codec.payload_type, parameters),
+            ));
+        }
+    }
+}
+
+/// The offered codecs `local` also has, with the payload types of the
+/// offer and the parameters and feedbacks of `local`. RTX is kept when the
+/// codec it repairs is.
+pub fn answer_codecs(offered: &[Codec], local: &[Codec]) -> Vec<Codec> {
+    let mut answer: Vec<Codec> = vec![];
+    for codec in offered.iter().filter(|v| !v.is_rtx()) {
+        if let Some(v) = local
+            .iter()
+            .find(|v| !v.is_rtx() && is_compatible(v, codec))
+        {
+            answer.push(Codec {
+                payload_type: codec.payload_type,
+                name: codec.name.clone(),
+                clock_rate: codec.clock_rate,
+                channels: codec.channels,
+                parameters: v.parameters.clone(),
+                feedbacks: v
+                    .feedbacks
+                    .iter()
+                    .filter(|f| codec.feedbacks.contains(f))
+                    .cloned()
+                    .collect(),
+            });
+        }
+    }
+    let has_rtx = local.iter().any(|v| v.is_rtx());
+    for codec in offered.iter().filter(|v| v.is_rtx() && has_rtx) {
+        let apt = codec.get_apt();
+        if answer.iter().any(|v| Some(v.payload_type) == apt) {
+            answer.push(codec.clone());
+        }
+    }
+    // RTX after the codec it repairs, as in the offer.
+    answer.sort_by_key(|v| {
+        offered
+            .iter()
+            .position(|o| o.payload_type == v.payload_type)
+    });
+    answer
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdp::session::test::CHROME_OFFER;
+    use crate::sdp::session::SessionDescription;
+
+    #[test]
+    fn get_codecs_test() {
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        let codecs = get_codecs(&session.media[0]).unwrap();
+        assert_eq!(codecs.len(), 8);
+        assert_eq!(
+            codecs[0],
+            Codec::new(111, "opus", 48000, Some(2))
+                .with_parameters("minptime=10;useinbandfec=1")
+                .with_feedback("transport-cc")
+        );
+        assert_eq!(codecs[1].get_parameter("minptime"), None);
+        assert_eq!(codecs[1].parameters.as_deref(), Some("111/111"));
+
+        let codecs = get_codecs(&session.media[1]).unwrap();
+        assert_eq!(codecs.len(), 4);
+        assert_eq!(codecs[0].feedbacks.len(), 5);
+        assert!(codecs[1].is_rtx());
+        assert_eq!(codecs[1].get_apt(), Some(96));
+        assert_eq!(codecs[2].get_parameter("packetization-mode"), Some("1"));
+
+        let mut media = session.media[1].clone();
+        media.attributes.clear();
+        add_codecs(&mut media, &codecs);
+        assert_eq!(media.formats, vec!["96", "97", "102", "103"]);
+        assert_eq!(get_codecs(&media).unwrap(), codecs);
+    }
+
+    #[test]
+    fn answer_codecs_test() {
+        let offered = vec![
+            Codec::new(100, "VP9", 90000, None),
+            Codec::new(101, "rtx", 90000, None).with_parameters("apt=100"),
+            Codec::new(120, "vp8", 90000, None)
+                .with_feedback("nack")
+                .with_feedback("goog-lntf"),
+            Codec::new(121, "rtx", 90000, None).with_parameters("apt=120"),
+        ];
+        let answer = answer_codecs(&offered, &get_default_video_codecs());
+        assert_eq!(
+            answer,
+            vec![
+                Codec::new(120, "vp8", 90000, None).with_feedback("nack"),
+                Codec::new(121, "rtx", 90000, None).with_parameters("apt=120"),
+            ]
+        );
+
+        let offered = vec![Codec::new(102, "H264", 90000, None)
+            .with_parameters("packetization-mode=0;profile-level-id=42e01f")];
+        assert!(answer_codecs(&offered, &get_default_video_codecs()).is_empty());
+
+        let offered = vec![Codec::new(109, "opus", 48000, Some(2))];
+        let answer = answer_codecs(&offered, &get_default_audio_codecs());
+        assert_eq!(answer[0].payload_type, 109);
+        assert_eq!(
+            answer[0].parameters.as_deref(),
+            Some("minptime=10;useinbandfec=1")
+        );
+        assert!(answer_codecs(&offered, &get_default_video_codecs()).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+// https://tools.ietf.org/html/rfc8842#section-5
+// https://tools.ietf.org/html/rfc8122#section-5
+
+/*
+    a=fingerprint:<hash function> <fingerprint>
+    a=setup:<actpass|active|passive|holdconn>
+
+          offer        answer       DTLS client
+          actpass      active       answerer
+          actpass      passive      offerer
+          active       passive      offerer
+          passive      active       answerer
+
+    offers are actpass. an answer to actpass keeps the role the transport
+    has, or is active for a new one. actpass and holdconn are not answers.
+*/
+
+use crate::dtls::transport::DtlsRole;
+use crate::sdp::media::MediaDescription;
+use crate::sdp::session::SessionDescription;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Setup {
+    ActPass,
+    Active,
+    Passive,
+    HoldConn,
+}
+
+impl Setup {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Setup::ActPass => "actpass",
+            Setup::Active => "active",
+            Setup::Passive => "passive",
+            Setup::HoldConn => "holdconn",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Setup> {
+        match name {
+            "actpass" => Some(Setup::ActPass),
+            "active" => Some(Setup::Active),
+            "passive" => Some(Setup::Passive),
+            "holdconn" => Some(Setup::HoldConn),
+            _ => None,
+        }
+    }
+
+    pub fn from_role(role: DtlsRole) -> Setup {
+        match role {
+            DtlsRole::Client => Setup::Active,
+            DtlsRole::Server => Setup::Passive,
+        }
+    }
+
+    /// The setup answering this one, `previous` for the role the transport
+    /// has. None for holdconn.
+    pub fn answer(self, previous: Option<DtlsRole>) -> Option<Setup> {
+        match self {
+            Setup::ActPass => Some(previous.map_or(Setup::Active, Setup::from_role)),
+            Setup::Active => Some(Setup::Passive),
+            Setup::Passive => Some(Setup::Active),
+            Setup::HoldConn => None,
+        }
+    }
+}
+
+/// The a=setup of the m= section, or of the session. Active when none is
+/// written, RFC 4145 Section 4.
+pub fn get_setup(session: &SessionDescription, media: &MediaDescription) -> Option<Setup> {
+    let value = media
+        .get_attribute("setup")
+        .or_else(|| session.get_attribute("setup"))
+        .map(|v| v.value.as_deref().unwrap_or_default());
+    match value {
+        Some(v) => Setup::from_name(v),
+        None => Some(Setup::Active),
+    }
+}
+
+/// The role of this side, by its setup and the one of the peer.
+pub fn get_dtls_role(local: Setup, remote: Setup) -> Option<DtlsRole> {
+    match (local, remote) {
+        (Setup::Active, _) => Some(DtlsRole::Client),
+        (Setup::Passive, _) => Some(DtlsRole::Server),
+        (Setup::ActPass, Setup::Active) => Some(DtlsRole::Server),
+        (Setup::ActPass, Setup::Passive) => Some(DtlsRole::Client),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn setup_test() {
+        assert_eq!(Setup::ActPass.answer(None), Some(Setup::Active));
+        assert_eq!(
+            Setup::ActPass.answer(Some(DtlsRole::Server)),
+            Some(Setup::Passive)
+        );
+        assert_eq!(Setup::Active.answer(None), Some(Setup::Passive));
+        assert_eq!(
+            Setup::Passive.answer(Some(DtlsRole::Server)),
+            Some(Setup::Active)
+        );
+        assert_eq!(Setup::HoldConn.answer(None), None);
+
+        assert_eq!(
+            get_dtls_role(Setup::ActPass, Setup::Active),
+            Some(DtlsRole::Server)
+        );
+        assert_eq!(
+            get_dtls_role(Setup::ActPass, Setup::Passive),
+            Some(DtlsRole::Client)
+        );
+        assert_eq!(
+            get_dtls_role(Setup::Active, Setup::ActPass),
+            Some(DtlsRole::Client)
+        );
+        assert_eq!(get_dtls_role(Setup::ActPass, Setup::ActPass), None);
+    }
+}
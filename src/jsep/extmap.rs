@@ -0,0 +1,267 @@
+// https://tools.ietf.org/html/rfc8285#section-8
+// https://tools.ietf.org/html/rfc6904#section-4
+// https://tools.ietf.org/html/rfc8829#section-5.3.1
+
+/*
+    a=extmap:<id>[/<direction>] [urn:ietf:params:rtp-hdrext:encrypt] <URI> <attributes>
+
+    an answer takes the offered extensions both sides have, with the ids of
+    the offer. ids 4096 - 4351 are left to the answerer and get a free one.
+    an encrypted extension is only taken by an encrypted one of the same
+    URI, a plain one by a plain one.
+*/
+
+use crate::jsep::{JsepError, Result};
+use crate::rtp::audio_level::AUDIO_LEVEL_URI;
+use crate::rtp::header_extension::{
+    HeaderExtensionMap, ABS_SEND_TIME_URI, MID_URI, REPAIRED_RID_URI, RID_URI, TOFFSET_URI,
+};
+use crate::rtp::transport_wide::TRANSPORT_WIDE_CC_URI;
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::{Direction, MediaDescription};
+
+use std::fmt;
+
+pub const ENCRYPT_URI: &str = "urn:ietf:params:rtp-hdrext:encrypt";
+
+const ANSWERER_IDS: std::ops::RangeInclusive<u16> = 4096..=4351;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Extmap {
+    pub id: u16,
+    pub direction: Option<Direction>,
+    pub encrypted: bool,
+    pub uri: String,
+    pub attributes: Option<String>,
+}
+
+impl Extmap {
+    pub fn new(id: u16, uri: &str) -> Self {
+        Extmap {
+            id,
+            direction: None,
+            encrypted: false,
+            uri: uri.to_string(),
+            attributes: None,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    pub fn with_encryption(mut self) -> Self {
+        self.encrypted = true;
+        self
+    }
+
+    /// Parses the value of an a=extmap.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || JsepError::InvalidDescription {
+            reason: format!("extmap:{}", value),
+        };
+        let mut fields = value.splitn(2, ' ');
+        let mut key = fields.next().unwrap_or_default().splitn(2, '/');
+        let id = key
+            .next()
+            .and_then(|v| v.parse::<u16>().ok())
+            .filter(|v| *v >= 1 && (*v <= 255 || ANSWERER_IDS.contains(v)))
+            .ok_or_else(invalid)?;
+        let direction = match key.next() {
+            Some(v) => Some(Direction::from_name(v).ok_or_else(invalid)?),
+            None => None,
+        };
+        let rest = fields.next().ok_or_else(invalid)?.trim_start();
+        let mut fields = rest.splitn(2, ' ');
+        let mut uri = fields.next().ok_or_else(invalid)?;
+        let mut attributes = fields.next();
+        let encrypted = uri == ENCRYPT_URI;
+        if encrypted {
+            let mut fields = attributes.ok_or_else(invalid)?.trim_start().splitn(2, ' ');
+            uri = fields.next().ok_or_else(invalid)?;
+            attributes = fields.next();
+        }
+        if uri.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Extmap {
+            id,
+            direction,
+            encrypted,
+            uri: uri.to_string(),
+            attributes: attributes
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Extmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(direction) = self.direction {
+            write!(f, "/{}", direction.get_name())?;
+        }
+        if self.encrypted {
+            write!(f, " {}", ENCRYPT_URI)?;
+        }
+        write!(f, " {}", self.uri)?;
+        if let Some(ref attributes) = self.attributes {
+            write!(f, " {}", attributes)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn get_default_audio_extmaps() -> Vec<Extmap> {
+    vec![
+        Extmap::new(1, AUDIO_LEVEL_URI),
+        Extmap::new(2, ABS_SEND_TIME_URI),
+        Extmap::new(3, TRANSPORT_WIDE_CC_URI),
+        Extmap::new(4, MID_URI),
+    ]
+}
+
+pub fn get_default_video_extmaps() -> Vec<Extmap> {
+    vec![
+        Extmap::new(2, ABS_SEND_TIME_URI),
+        Extmap::new(3, TRANSPORT_WIDE_CC_URI),
+        Extmap::new(4, MID_URI),
+        Extmap::new(10, RID_URI),
+        Extmap::new(11, REPAIRED_RID_URI),
+        Extmap::new(14, TOFFSET_URI),
+    ]
+}
+
+pub fn get_extmaps(media: &MediaDescription) -> Result<Vec<Extmap>> {
+    media
+        .get_attribute_values("extmap")
+        .map(Extmap::parse)
+        .collect()
+}
+
+pub fn add_extmaps(media: &mut MediaDescription, extmaps: &[Extmap]) {
+    for extmap in extmaps {
+        media
+            .attributes
+            .push(Attribute::new("extmap", &extmap.to_string()));
+    }
+}
+
+/// The offered extensions `local` also has, with the ids of the offer and
+/// the reverse of the offered direction.
+pub fn answer_extmaps(offered: &[Extmap], local: &[Extmap]) -> Vec<Extmap> {
+    let mut answer: Vec<Extmap> = vec![];
+    for extmap in offered {
+        let v = match local
+            .iter()
+            .find(|v| v.uri == extmap.uri && v.encrypted == extmap.encrypted)
+        {
+            Some(v) => v,
+            None => continue,
+        };
+        if answer.iter().any(|v| v.uri == extmap.uri) {
+            continue;
+        }
+        let id = if ANSWERER_IDS.contains(&extmap.id) {
+            match (1..=255).find(|id| !offered.iter().chain(&answer).any(|v| v.id == *id)) {
+                Some(id) => id,
+                None => continue,
+            }
+        } else {
+            extmap.id
+        };
+        answer.push(Extmap {
+            id,
+            direction: extmap.direction.map(|v| v.reverse()),
+            encrypted: extmap.encrypted,
+            uri: extmap.uri.clone(),
+            attributes: v.attributes.clone(),
+        });
+    }
+    answer
+}
+
+/// The map for the RTP packets, without the inactive extensions.
+pub fn get_header_extension_map(extmaps: &[Extmap]) -> HeaderExtensionMap {
+    let mut map = HeaderExtensionMap::new();
+    for extmap in extmaps {
+        if extmap.id <= 255 && extmap.direction != Some(Direction::Inactive) {
+            map.insert(extmap.id as u8, &extmap.uri);
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdp::session::test::{CHROME_OFFER, FIREFOX_OFFER};
+    use crate::sdp::session::SessionDescription;
+
+    #[test]
+    fn extmap_test() {
+        let extmap =
+            Extmap::parse("2/recvonly urn:ietf:params:rtp-hdrext:csrc-audio-level").unwrap();
+        assert_eq!(
+            extmap,
+            Extmap::new(2, "urn:ietf:params:rtp-hdrext:csrc-audio-level")
+                .with_direction(Direction::RecvOnly)
+        );
+        assert_eq!(
+            extmap.to_string(),
+            "2/recvonly urn:ietf:params:rtp-hdrext:csrc-audio-level"
+        );
+
+        let value = "3/sendonly urn:ietf:params:rtp-hdrext:encrypt urn:ietf:params:rtp-hdrext:smpte-tc 25@600/24";
+        let extmap = Extmap::parse(value).unwrap();
+        assert!(extmap.encrypted);
+        assert_eq!(extmap.uri, "urn:ietf:params:rtp-hdrext:smpte-tc");
+        assert_eq!(extmap.attributes.as_deref(), Some("25@600/24"));
+        assert_eq!(extmap.to_string(), value);
+
+        assert!(Extmap::parse("0 urn:x").is_err());
+        assert!(Extmap::parse("256 urn:x").is_err());
+        assert!(Extmap::parse("1/sideways urn:x").is_err());
+        assert!(Extmap::parse("1").is_err());
+        assert!(Extmap::parse(&format!("1 {}", ENCRYPT_URI)).is_err());
+        assert_eq!(Extmap::parse("4096 urn:x").unwrap().id, 4096);
+
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        assert_eq!(get_extmaps(&session.media[0]).unwrap().len(), 4);
+        let session = SessionDescription::parse(FIREFOX_OFFER).unwrap();
+        let extmaps = get_extmaps(&session.media[0]).unwrap();
+        assert_eq!(extmaps[1].direction, Some(Direction::RecvOnly));
+    }
+
+    #[test]
+    fn answer_extmaps_test() {
+        let offered = vec![
+            Extmap::new(1, AUDIO_LEVEL_URI).with_direction(Direction::SendOnly),
+            Extmap::new(2, TOFFSET_URI).with_encryption(),
+            Extmap::new(4096, MID_URI),
+            Extmap::new(5, "urn:x"),
+        ];
+        let answer = answer_extmaps(&offered, &get_default_audio_extmaps());
+        assert_eq!(
+            answer,
+            vec![
+                Extmap::new(1, AUDIO_LEVEL_URI).with_direction(Direction::RecvOnly),
+                Extmap::new(3, MID_URI),
+            ]
+        );
+
+        let local = vec![Extmap::new(9, TOFFSET_URI).with_encryption()];
+        let answer = answer_extmaps(&offered, &local);
+        assert_eq!(answer, vec![Extmap::new(2, TOFFSET_URI).with_encryption()]);
+
+        let map = get_header_extension_map(&[
+            Extmap::new(3, MID_URI),
+            Extmap::new(4, RID_URI).with_direction(Direction::Inactive),
+        ]);
+        assert_eq!(map.get_id(MID_URI), Some(3));
+        assert_eq!(map.get_id(RID_URI), None);
+    }
+}
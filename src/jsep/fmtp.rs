@@ -0,0 +1,274 @@
+// https://tools.ietf.org/html/rfc6184#section-8.1
+// https://tools.ietf.org/html/draft-ietf-payload-vp9-16#section-6
+// https://tools.ietf.org/html/rfc7587#section-6.1
+// https://tools.ietf.org/html/rfc4588#section-8.6
+// https://tools.ietf.org/html/rfc2198#section-5
+
+/*
+    a=fmtp:102 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f
+    a=fmtp:98 profile-id=0
+    a=fmtp:111 minptime=10;useinbandfec=1;stereo=1
+    a=fmtp:97 apt=96;rtx-time=3000
+    a=fmtp:63 111/111                     RED of opus
+
+    profile-level-id is profile_idc, profile-iop and level_idc in hex. the
+    profile is told by profile_idc and the constraint bits of profile-iop.
+
+    the same codec of different fmtp is still another codec for:
+      H264    profile and packetization-mode, the level may differ
+      VP9     profile-id
+*/
+
+use crate::jsep::codec::Codec;
+
+use std::str::FromStr;
+
+fn get_parameter<T: FromStr>(codec: &Codec, name: &str) -> Option<T> {
+    codec.get_parameter(name)?.parse().ok()
+}
+
+fn get_flag(codec: &Codec, name: &str) -> bool {
+    codec.get_parameter(name) == Some("1")
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum H264Profile {
+    ConstrainedBaseline,
+    Baseline,
+    Main,
+    ConstrainedHigh,
+    High,
+    PredictiveHigh444,
+}
+
+// profile_idc, profile-iopのmaskと値．上から順に照合する．
+#[rustfmt::skip]
+const H264_PROFILES: [(u8, u8, u8, H264Profile); 9] = [
+    (0x42, 0b0100_1111, 0b0100_0000, H264Profile::ConstrainedBaseline),
+    (0x4d, 0b1000_1111, 0b1000_0000, H264Profile::ConstrainedBaseline),
+    (0x58, 0b1100_1111, 0b1100_0000, H264Profile::ConstrainedBaseline),
+    (0x42, 0b0100_1111, 0b0000_0000, H264Profile::Baseline),
+    (0x58, 0b1100_1111, 0b1000_0000, H264Profile::Baseline),
+    (0x4d, 0b1010_1111, 0b0000_0000, H264Profile::Main),
+    (0x64, 0b1111_1111, 0b0000_0000, H264Profile::High),
+    (0x64, 0b1111_1111, 0b0000_1100, H264Profile::ConstrainedHigh),
+    (0xf4, 0b1111_1111, 0b0000_0000, H264Profile::PredictiveHigh444),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProfileLevelId {
+    pub profile: H264Profile,
+    pub level: u8,
+}
+
+impl ProfileLevelId {
+    pub fn parse(value: &str) -> Option<Self> {
+        if value.len() != 6 {
+            return None;
+        }
+        let value = u32::from_str_radix(value, 16).ok()?;
+        let (idc, iop, level) = ((value >> 16) as u8, (value >> 8) as u8, value as u8);
+        let profile = H264_PROFILES
+            .iter()
+            .find(|(v, mask, bits, _)| *v == idc && iop & mask == *bits)?
+            .3;
+        Some(ProfileLevelId { profile, level })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct H264Parameters {
+    pub profile_level_id: ProfileLevelId,
+    pub packetization_mode: u8,
+    pub level_asymmetry_allowed: bool,
+}
+
+impl H264Parameters {
+    /// Baseline level 1 when no profile-level-id is written. None for a
+    /// profile-level-id of no known profile.
+    pub fn from_codec(codec: &Codec) -> Option<Self> {
+        let profile_level_id = codec.get_parameter("profile-level-id").unwrap_or("42000a");
+        Some(H264Parameters {
+            profile_level_id: ProfileLevelId::parse(profile_level_id)?,
+            packetization_mode: get_parameter(codec, "packetization-mode").unwrap_or(0),
+            level_asymmetry_allowed: get_flag(codec, "level-asymmetry-allowed"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Vp9Parameters {
+    pub profile_id: u8,
+}
+
+impl Vp9Parameters {
+    pub fn from_codec(codec: &Codec) -> Self {
+        Vp9Parameters {
+            profile_id: get_parameter(codec, "profile-id").unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct OpusParameters {
+    pub minptime: Option<u16>,
+    pub maxplaybackrate: Option<u32>,
+    pub maxaveragebitrate: Option<u32>,
+    pub stereo: bool,
+    pub sprop_stereo: bool,
+    pub useinbandfec: bool,
+    pub usedtx: bool,
+}
+
+impl OpusParameters {
+    pub fn from_codec(codec: &Codec) -> Self {
+        OpusParameters {
+            minptime: get_parameter(codec, "minptime"),
+            maxplaybackrate: get_parameter(codec, "maxplaybackrate"),
+            maxaveragebitrate: get_parameter(codec, "maxaveragebitrate"),
+            stereo: get_flag(codec, "stereo"),
+            sprop_stereo: get_flag(codec, "sprop-stereo"),
+            useinbandfec: get_flag(codec, "useinbandfec"),
+            usedtx: get_flag(codec, "usedtx"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct RtxParameters {
+    pub apt: u8,
+    /// How long the sender keeps packets to retransmit, in ms.
+    pub rtx_time: Option<u32>,
+}
+
+impl RtxParameters {
+    pub fn from_codec(codec: &Codec) -> Option<Self> {
+        Some(RtxParameters {
+            apt: codec.get_apt()?,
+            rtx_time: get_parameter(codec, "rtx-time"),
+        })
+    }
+}
+
+// REDの各blockのpayload type．fmtpは"111/111"のように書く．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RedParameters {
+    pub payload_types: Vec<u8>,
+}
+
+impl RedParameters {
+    pub fn from_codec(codec: &Codec) -> Option<Self> {
+        let payload_types = codec
+            .parameters
+            .as_deref()?
+            .trim()
+            .split('/')
+            .map(|v| v.parse::<u8>().ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(RedParameters { payload_types })
+    }
+}
+
+/// Whether two codecs of the same name also agree on the fmtp that makes
+/// them different codecs. ULPFEC, RED, RTX and opus have none.
+pub fn is_compatible(a: &Codec, b: &Codec) -> bool {
+    if !a.is_same_codec(b) {
+        return false;
+    }
+    if a.name.eq_ignore_ascii_case("H264") {
+        return match (H264Parameters::from_codec(a), H264Parameters::from_codec(b)) {
+            (Some(a), Some(b)) => {
+                a.profile_level_id.profile == b.profile_level_id.profile
+                    && a.packetization_mode == b.packetization_mode
+            }
+            _ => false,
+        };
+    }
+    if a.name.eq_ignore_ascii_case("VP9") {
+        return Vp9Parameters::from_codec(a) == Vp9Parameters::from_codec(b);
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn h264(parameters: &str) -> Codec {
+        Codec::new(102, "H264", 90000, None).with_parameters(parameters)
+    }
+
+    #[test]
+    fn h264_test() {
+        let profile = |v| ProfileLevelId::parse(v).map(|v| v.profile);
+        assert_eq!(profile("42e01f"), Some(H264Profile::ConstrainedBaseline));
+        assert_eq!(profile("42001f"), Some(H264Profile::Baseline));
+        assert_eq!(profile("4d001f"), Some(H264Profile::Main));
+        assert_eq!(profile("640c1f"), Some(H264Profile::ConstrainedHigh));
+        assert_eq!(profile("640032"), Some(H264Profile::High));
+        assert_eq!(ProfileLevelId::parse("42e01f").unwrap().level, 0x1f);
+        assert_eq!(profile("ff001f"), None);
+        assert_eq!(profile("42e0"), None);
+
+        let parameters = H264Parameters::from_codec(&h264(
+            "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f",
+        ))
+        .unwrap();
+        assert_eq!(parameters.packetization_mode, 1);
+        assert!(parameters.level_asymmetry_allowed);
+        let parameters = H264Parameters::from_codec(&Codec::new(102, "H264", 90000, None)).unwrap();
+        assert_eq!(parameters.profile_level_id.profile, H264Profile::Baseline);
+        assert_eq!(parameters.packetization_mode, 0);
+
+        let a = h264("packetization-mode=1;profile-level-id=42e01f");
+        assert!(is_compatible(
+            &a,
+            &h264("packetization-mode=1;profile-level-id=42e034")
+        ));
+        assert!(!is_compatible(
+            &a,
+            &h264("packetization-mode=0;profile-level-id=42e01f")
+        ));
+        assert!(!is_compatible(
+            &a,
+            &h264("packetization-mode=1;profile-level-id=640c1f")
+        ));
+    }
+
+    #[test]
+    fn parameters_test() {
+        let vp9 = |v| Codec::new(98, "VP9", 90000, None).with_parameters(v);
+        assert_eq!(
+            Vp9Parameters::from_codec(&vp9("profile-id=2")).profile_id,
+            2
+        );
+        assert!(is_compatible(
+            &vp9("profile-id=0"),
+            &Codec::new(100, "vp9", 90000, None)
+        ));
+        assert!(!is_compatible(&vp9("profile-id=0"), &vp9("profile-id=2")));
+
+        let opus = Codec::new(111, "opus", 48000, Some(2))
+            .with_parameters("minptime=10;useinbandfec=1;stereo=1;maxaveragebitrate=128000");
+        let parameters = OpusParameters::from_codec(&opus);
+        assert_eq!(parameters.minptime, Some(10));
+        assert_eq!(parameters.maxaveragebitrate, Some(128000));
+        assert!(parameters.useinbandfec && parameters.stereo);
+        assert!(!parameters.usedtx);
+
+        let rtx = Codec::new(97, "rtx", 90000, None).with_parameters("apt=96;rtx-time=3000");
+        assert_eq!(
+            RtxParameters::from_codec(&rtx),
+            Some(RtxParameters {
+                apt: 96,
+                rtx_time: Some(3000)
+            })
+        );
+        let red = Codec::new(63, "red", 48000, Some(2)).with_parameters("111/111");
+        assert_eq!(
+            RedParameters::from_codec(&red).unwrap().payload_types,
+            vec![111, 111]
+        );
+        assert_eq!(RedParameters::from_codec(&opus), None);
+    }
+}
@@ -0,0 +1,106 @@
+// https://tools.ietf.org/html/rfc8839#section-5
+// https://tools.ietf.org/html/rfc8840#section-8
+
+/*
+    a=ice-lite                      session level only
+    a=ice-ufrag:<ufrag>
+    a=ice-pwd:<pwd>
+    a=ice-options:trickle ...
+    a=candidate:<candidate>
+    a=end-of-candidates
+
+    the candidates of a transport are written to the m= section of its mid,
+    the ones trickled after are added to the remote description too.
+*/
+
+use crate::ice::agent::IceAgent;
+use crate::ice::candidate::IceCandidate;
+use crate::jsep::session::RemoteTransport;
+use crate::jsep::{JsepError, Result};
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::MediaDescription;
+use crate::time::Instant;
+
+pub const TRICKLE_OPTION: &str = "trickle";
+
+pub fn get_candidates(media: &MediaDescription) -> Result<Vec<IceCandidate>> {
+    media
+        .get_attribute_values("candidate")
+        .map(|v| {
+            IceCandidate::parse(v).map_err(|e| JsepError::InvalidDescription {
+                reason: format!("candidate:{}: {}", v, e),
+            })
+        })
+        .collect()
+}
+
+pub fn is_end_of_candidates(media: &MediaDescription) -> bool {
+    media.get_attribute("end-of-candidates").is_some()
+}
+
+pub fn add_candidates(
+    media: &mut MediaDescription,
+    candidates: &[IceCandidate],
+    end_of_candidates: bool,
+) {
+    for candidate in candidates {
+        let value = candidate.to_string();
+        media.attributes.push(Attribute::new(
+            "candidate",
+            value.strip_prefix("candidate:").unwrap_or(&value),
+        ));
+    }
+    if end_of_candidates {
+        media
+            .attributes
+            .push(Attribute::new_property("end-of-candidates"));
+    }
+}
+
+/// Gives the agent the credentials and candidates of the remote description.
+/// The candidates it has are skipped, so it can be applied again after each
+/// trickled one.
+pub fn apply_remote_transport(agent: &mut IceAgent, transport: &RemoteTransport, now: Instant) {
+    if agent.get_remote_credentials() != Some(&transport.credentials) {
+        agent.set_remote_credentials(transport.credentials.clone(), now);
+    }
+    if transport.ice_lite {
+        agent.set_remote_lite();
+    }
+    for candidate in &transport.candidates {
+        agent.add_remote_candidate(candidate.clone(), now);
+    }
+    if transport.end_of_candidates && !agent.has_remote_end_of_candidates() {
+        agent.set_remote_end_of_candidates();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdp::session::test::CHROME_OFFER;
+    use crate::sdp::session::SessionDescription;
+
+    #[test]
+    fn candidates_test() {
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        let mut media = session.media[0].clone();
+        media.attributes.clear();
+        let candidates = vec![
+            IceCandidate::parse("candidate:842163049 1 udp 1677729535 203.0.113.7 60769 typ srflx raddr 192.168.99.58 rport 45076").unwrap(),
+            IceCandidate::parse("candidate:1 1 udp 2122260223 192.168.99.58 45076 typ host").unwrap(),
+        ];
+        add_candidates(&mut media, &candidates, true);
+        assert_eq!(
+            media.get_attribute_values("candidate").nth(1),
+            Some("1 1 udp 2122260223 192.168.99.58 45076 typ host")
+        );
+        assert_eq!(get_candidates(&media).unwrap(), candidates);
+        assert!(is_end_of_candidates(&media));
+
+        media
+            .attributes
+            .push(Attribute::new("candidate", "1 1 udp"));
+        assert!(get_candidates(&media).is_err());
+    }
+}
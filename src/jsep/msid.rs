@@ -0,0 +1,158 @@
+// https://tools.ietf.org/html/rfc8830#section-2
+// https://tools.ietf.org/html/rfc8829#section-5.2.1
+
+/*
+    a=msid:<stream id> <track id>
+    a=msid:- <track id>                    a track of no stream
+
+    one a=msid for each stream the track of the m= section is in, written
+    while it is sent. older browsers write it to the SSRC instead:
+
+    a=ssrc:<ssrc> msid:<stream id> <track id>
+*/
+
+use crate::jsep::{JsepError, Result};
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::MediaDescription;
+
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::fmt;
+use std::iter;
+
+const NO_STREAM: &str = "-";
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Msid {
+    pub stream_id: Option<String>,
+    pub track_id: Option<String>,
+}
+
+impl Msid {
+    pub fn new(stream_id: Option<&str>, track_id: &str) -> Self {
+        Msid {
+            stream_id: stream_id.map(|v| v.to_string()),
+            track_id: Some(track_id.to_string()),
+        }
+    }
+
+    /// Parses the value of an a=msid.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut fields = value.split_whitespace();
+        let stream_id = fields.next().ok_or_else(|| JsepError::InvalidDescription {
+            reason: format!("msid:{}", value),
+        })?;
+        let track_id = fields.next();
+        if fields.next().is_some() {
+            return Err(JsepError::InvalidDescription {
+                reason: format!("msid:{}", value),
+            });
+        }
+        Ok(Msid {
+            stream_id: Some(stream_id)
+                .filter(|v| *v != NO_STREAM)
+                .map(|v| v.to_string()),
+            track_id: track_id.map(|v| v.to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Msid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.stream_id.as_deref().unwrap_or(NO_STREAM))?;
+        if let Some(ref track_id) = self.track_id {
+            write!(f, " {}", track_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// A stream or track id for a local track.
+pub fn generate_id() -> String {
+    let mut rng = thread_rng();
+    iter::repeat(())
+        .map(|()| rng.sample(Alphanumeric))
+        .take(36)
+        .collect()
+}
+
+/// The a=msid lines, or the msid of the a=ssrc lines without them.
+pub fn get_msids(media: &MediaDescription) -> Result<Vec<Msid>> {
+    let msids = media
+        .get_attribute_values("msid")
+        .map(Msid::parse)
+        .collect::<Result<Vec<_>>>()?;
+    if !msids.is_empty() {
+        return Ok(msids);
+    }
+    let mut msids: Vec<Msid> = vec![];
+    for value in media.get_attribute_values("ssrc") {
+        let msid = value
+            .split_once(' ')
+            .and_then(|(_, v)| v.strip_prefix("msid:"))
+            .map(Msid::parse)
+            .transpose()?;
+        if let Some(msid) = msid.filter(|v| !msids.contains(v)) {
+            msids.push(msid);
+        }
+    }
+    Ok(msids)
+}
+
+/// One msid for each of `stream_ids`, "-" for none.
+pub fn get_local_msids(stream_ids: &[String], track_id: &str) -> Vec<Msid> {
+    if stream_ids.is_empty() {
+        vec![Msid::new(None, track_id)]
+    } else {
+        stream_ids
+            .iter()
+            .map(|v| Msid::new(Some(v), track_id))
+            .collect()
+    }
+}
+
+pub fn add_msids(media: &mut MediaDescription, msids: &[Msid]) {
+    for msid in msids {
+        media
+            .attributes
+            .push(Attribute::new("msid", &msid.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdp::session::test::{CHROME_OFFER, FIREFOX_OFFER};
+    use crate::sdp::session::SessionDescription;
+
+    #[test]
+    fn msid_test() {
+        let msid = Msid::parse("stream track").unwrap();
+        assert_eq!(msid, Msid::new(Some("stream"), "track"));
+        assert_eq!(msid.to_string(), "stream track");
+        let msid = Msid::parse("- track").unwrap();
+        assert_eq!(msid.stream_id, None);
+        assert_eq!(msid.to_string(), "- track");
+        assert_eq!(Msid::parse("stream").unwrap().track_id, None);
+        assert!(Msid::parse("").is_err());
+        assert!(Msid::parse("a b c").is_err());
+        assert_eq!(generate_id().len(), 36);
+
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        let msids = get_msids(&session.media[1]).unwrap();
+        assert_eq!(
+            msids,
+            vec![Msid::new(
+                Some("3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a"),
+                "7b5c1b5e-0d3a-4f0e-9d8e-4e1f0f3d2c1b"
+            )]
+        );
+        // from a=ssrc
+        let mut media = session.media[1].clone();
+        media.attributes.retain(|v| v.name != "msid");
+        assert_eq!(get_msids(&media).unwrap(), msids);
+
+        let session = SessionDescription::parse(FIREFOX_OFFER).unwrap();
+        assert_eq!(get_msids(&session.media[0]).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,55 @@
+// https://www.w3.org/TR/webrtc/#rtcrtpreceiver-interface
+
+/*
+    the receiving half of a transceiver. the track is there from the
+    start, the streams and SSRCs of the peer come with its descriptions.
+*/
+
+use crate::jsep::msid::Msid;
+use crate::jsep::ssrc::SsrcLayer;
+use crate::jsep::track::MediaStreamTrack;
+use crate::sdp::media::MediaKind;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RtpReceiver {
+    pub(crate) track: MediaStreamTrack,
+    pub(crate) remote_msids: Vec<Msid>,
+    pub(crate) remote_ssrcs: Vec<SsrcLayer>,
+    pub(crate) remote_cname: Option<String>,
+}
+
+impl RtpReceiver {
+    pub(crate) fn new(kind: MediaKind) -> Self {
+        RtpReceiver {
+            track: MediaStreamTrack::new(kind),
+            remote_msids: vec![],
+            remote_ssrcs: vec![],
+            remote_cname: None,
+        }
+    }
+
+    pub fn get_track(&self) -> &MediaStreamTrack {
+        &self.track
+    }
+
+    /// The streams the remote track is in, from the last remote description.
+    pub fn get_remote_stream_ids(&self) -> Vec<&str> {
+        self.remote_msids
+            .iter()
+            .filter_map(|v| v.stream_id.as_deref())
+            .collect()
+    }
+
+    pub fn get_remote_track_id(&self) -> Option<&str> {
+        self.remote_msids.iter().find_map(|v| v.track_id.as_deref())
+    }
+
+    /// The SSRCs the peer sends with, from the last remote description.
+    pub fn get_remote_ssrcs(&self) -> &[SsrcLayer] {
+        &self.remote_ssrcs
+    }
+
+    pub fn get_remote_cname(&self) -> Option<&str> {
+        self.remote_cname.as_deref()
+    }
+}
@@ -0,0 +1,30 @@
+// https://tools.ietf.org/html/rfc5761#section-5.1.1
+// https://tools.ietf.org/html/rfc8858
+// https://www.w3.org/TR/webrtc/#rtcrtcpmuxpolicy-enum
+
+/*
+    a=rtcp-mux         RTCP on the port of RTP
+    a=rtcp-mux-only    the offerer takes no answer without a=rtcp-mux
+
+    require      offers rtcp-mux-only, gathers one ICE component and
+                 rejects a remote m= section without rtcp-mux
+    negotiate    offers rtcp-mux, gathers the second component for RTCP
+                 until the answer says whether it is muxed
+*/
+
+use crate::sdp::media::MediaDescription;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum RtcpMuxPolicy {
+    Negotiate,
+    #[default]
+    Require,
+}
+
+pub fn is_rtcp_mux(media: &MediaDescription) -> bool {
+    media.get_attribute("rtcp-mux").is_some()
+}
+
+pub fn is_rtcp_mux_only(media: &MediaDescription) -> bool {
+    media.get_attribute("rtcp-mux-only").is_some()
+}
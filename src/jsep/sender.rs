@@ -0,0 +1,91 @@
+// https://www.w3.org/TR/webrtc/#rtcrtpsender-interface
+
+/*
+    the sending half of a transceiver. without a track it sends nothing,
+    but is still written with its msid and SSRCs while the direction sends.
+*/
+
+use crate::jsep::msid::generate_id;
+use crate::jsep::ssrc::SsrcLayer;
+use crate::jsep::track::MediaStreamTrack;
+use crate::jsep::{JsepError, Result};
+use crate::sdp::media::MediaKind;
+
+use rand::{thread_rng, Rng};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RtpSender {
+    pub(crate) kind: MediaKind,
+    pub(crate) track: Option<MediaStreamTrack>,
+    pub(crate) stream_ids: Vec<String>,
+    pub(crate) track_id: String,
+    pub(crate) ssrcs: Vec<SsrcLayer>,
+    /// The direction of an answer has sent.
+    pub(crate) has_sent: bool,
+}
+
+impl RtpSender {
+    pub(crate) fn new(kind: MediaKind, rtx: bool) -> Self {
+        let ssrcs = match kind {
+            MediaKind::Audio | MediaKind::Video => {
+                let mut rng = thread_rng();
+                let rtx_ssrc = Some(rng.gen::<u32>()).filter(|_| rtx);
+                vec![SsrcLayer::new(rng.gen(), rtx_ssrc)]
+            }
+            _ => vec![],
+        };
+        RtpSender {
+            kind,
+            track: None,
+            stream_ids: vec![],
+            track_id: generate_id(),
+            ssrcs,
+            has_sent: false,
+        }
+    }
+
+    pub fn get_track(&self) -> Option<&MediaStreamTrack> {
+        self.track.as_ref()
+    }
+
+    /// Replaces the track, without negotiation. The msid takes the id of
+    /// the track from the next offer or answer.
+    pub fn set_track(&mut self, track: Option<MediaStreamTrack>) -> Result<()> {
+        if let Some(ref track) = track {
+            if track.get_kind() != self.kind {
+                return Err(JsepError::InvalidTrack {
+                    reason: format!("{:?} track for {:?}", track.get_kind(), self.kind),
+                });
+            }
+            self.track_id = track.get_id().to_string();
+        }
+        self.track = track;
+        Ok(())
+    }
+
+    /// The streams the sent track is in, written as a=msid.
+    pub fn get_stream_ids(&self) -> &[String] {
+        &self.stream_ids
+    }
+
+    pub fn set_stream_ids(&mut self, stream_ids: Vec<String>) {
+        self.stream_ids = stream_ids;
+    }
+
+    pub fn get_track_id(&self) -> &str {
+        &self.track_id
+    }
+
+    pub fn set_track_id(&mut self, track_id: &str) {
+        self.track_id = track_id.to_string();
+    }
+
+    /// The SSRCs to send with, a=ssrc-group:SIM when more than one layer.
+    pub fn get_ssrcs(&self) -> &[SsrcLayer] {
+        &self.ssrcs
+    }
+
+    pub fn set_ssrcs(&mut self, ssrcs: Vec<SsrcLayer>) {
+        self.ssrcs = ssrcs;
+    }
+}
@@ -0,0 +1,2319 @@
+// https://tools.ietf.org/html/rfc8829#section-5
+// https://tools.ietf.org/html/rfc3264#section-6
+
+/*
+    create_offer     the m= sections of the last description in their
+                     order, then the transceivers added since. a stopped
+                     transceiver is written with port 0.
+    create_answer    an m= section for each of the remote offer, with port
+                     0 when no transceiver takes it or no codec is common.
+
+    set_local_description and set_remote_description check the type
+    against the signaling state. the offer associates the m= sections with
+    transceivers by mid, the answer fixes their directions and codecs.
+    negotiation is needed while a transceiver is not in the current local
+    description as it is now: added, stopped, of another direction or of
+    other streams. it is checked in Stable, after the operations and on
+    poll_event, and NegotiationNeeded is queued once until it is done.
+
+    the state an offer changes is saved when it leaves Stable, and a
+    rollback puts it back: the transceivers created for a remote offer are
+    removed, the others lose the mids and remote tracks of the offer.
+
+    the remote ICE parameters, candidates and fingerprints of each m=
+    section are kept for the transports, looked up by mid. the DTLS role of
+    a transport is told by the setups of the current local and remote
+    descriptions. the m= sections of a negotiated
+    BUNDLE group have the transport of the tagged one, with a second ICE
+    component when RTCP is not muxed.
+*/
+
+use crate::dtls::fingerprint::CertificateFingerprint;
+use crate::dtls::transport::DtlsRole;
+use crate::ice::agent::IceCredentials;
+use crate::ice::candidate::IceCandidate;
+use crate::jsep::bundle::{get_bundle_groups, get_bundle_tag, BundlePolicy};
+use crate::jsep::capabilities::CapabilityRegistry;
+use crate::jsep::codec::{add_codecs, answer_codecs, get_codecs, Codec};
+use crate::jsep::dtls::{get_dtls_role, get_setup, Setup};
+use crate::jsep::extmap::{
+    add_extmaps, answer_extmaps, get_extmaps, get_header_extension_map, Extmap,
+};
+use crate::jsep::ice::{add_candidates, get_candidates, is_end_of_candidates, TRICKLE_OPTION};
+use crate::jsep::msid::{add_msids, generate_id, get_local_msids, get_msids};
+use crate::jsep::rtcp_mux::{is_rtcp_mux, is_rtcp_mux_only, RtcpMuxPolicy};
+use crate::jsep::simulcast::{
+    add_simulcast, answer_simulcast, get_rids, get_simulcast, offer_simulcast,
+};
+use crate::jsep::ssrc::{
+    add_ssrcs, get_cname, get_ssrc_attributes, get_ssrc_groups, get_ssrc_layers,
+};
+use crate::jsep::track::MediaStreamTrack;
+use crate::jsep::transceiver::RtpTransceiver;
+use crate::jsep::{Description, JsepError, Result, SdpType, SignalingState};
+use crate::rtp::demuxer::RtpDemuxer;
+use crate::sctp::association::{SctpConfig, DEFAULT_SCTP_PORT};
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::{Direction, MediaDescription, MediaKind};
+use crate::sdp::session::{Connection, Origin, SessionDescription};
+
+use rand::{thread_rng, Rng};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub const RTP_PROTOCOL: &str = "UDP/TLS/RTP/SAVPF";
+pub const SCTP_PROTOCOL: &str = "UDP/DTLS/SCTP";
+pub const SCTP_FORMAT: &str = "webrtc-datachannel";
+
+fn invalid(reason: &str) -> JsepError {
+    JsepError::InvalidDescription {
+        reason: reason.to_string(),
+    }
+}
+
+// 相手のm= sectionから取り出したtransportの設定．
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RemoteTransport {
+    pub credentials: IceCredentials,
+    pub ice_options: Vec<String>,
+    pub ice_lite: bool,
+    pub candidates: Vec<IceCandidate>,
+    pub end_of_candidates: bool,
+    pub fingerprints: Vec<CertificateFingerprint>,
+    pub setup: Setup,
+}
+
+impl RemoteTransport {
+    /// Attributes of the m= section, or of the session when it has none.
+    fn parse(session: &SessionDescription, media: &MediaDescription) -> Result<Self> {
+        let get = |name: &str| {
+            media
+                .get_attribute(name)
+                .or_else(|| session.get_attribute(name))
+                .and_then(|v| v.value.as_deref())
+        };
+        let credentials = match (get("ice-ufrag"), get("ice-pwd")) {
+            (Some(ufrag), Some(pwd)) => IceCredentials::new(ufrag, pwd),
+            _ => return Err(invalid("no ICE credentials")),
+        };
+        let mut values: Vec<&str> = media.get_attribute_values("fingerprint").collect();
+        if values.is_empty() {
+            values = session.get_attribute_values("fingerprint").collect();
+        }
+        let fingerprints = values
+            .into_iter()
+            .map(|v| CertificateFingerprint::parse(v).map_err(|_| invalid("fingerprint")))
+            .collect::<Result<Vec<_>>>()?;
+        if fingerprints.is_empty() {
+            return Err(invalid("no fingerprint"));
+        }
+        Ok(RemoteTransport {
+            credentials,
+            ice_options: get("ice-options")
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|v| v.to_string())
+                .collect(),
+            ice_lite: session.get_attribute("ice-lite").is_some(),
+            candidates: get_candidates(media)?,
+            end_of_candidates: is_end_of_candidates(media),
+            fingerprints,
+            setup: get_setup(session, media).ok_or_else(|| invalid("setup"))?,
+        })
+    }
+}
+
+// 相手のapplication m= sectionのSCTPの設定．
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SctpParameters {
+    pub port: u16,
+    /// 0 when any size is taken.
+    pub max_message_size: usize,
+}
+
+impl SctpParameters {
+    /// 5000 and 64 KiB when not written (RFC 8841 Section 5 and 6).
+    fn parse(media: &MediaDescription) -> Result<Self> {
+        let port = match media.get_attribute("sctp-port") {
+            Some(v) => v
+                .value
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| invalid("sctp-port"))?,
+            None => DEFAULT_SCTP_PORT,
+        };
+        let max_message_size = match media.get_attribute("max-message-size") {
+            Some(v) => v
+                .value
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .ok_or_else(|| invalid("max-message-size"))?,
+            None => 64 * 1024,
+        };
+        Ok(SctpParameters {
+            port,
+            max_message_size,
+        })
+    }
+}
+
+fn get_media_direction(session: &SessionDescription, media: &MediaDescription) -> Direction {
+    media
+        .get_direction()
+        .or_else(|| session.get_direction())
+        .unwrap_or(Direction::SendRecv)
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum JsepEvent {
+    NegotiationNeeded,
+}
+
+// offerでStableを離れる前の状態．rollbackで戻す．
+#[derive(Debug, Clone)]
+struct Snapshot {
+    transceivers: Vec<RtpTransceiver>,
+    remote_transports: HashMap<String, RemoteTransport>,
+    remote_sctp: Option<SctpParameters>,
+}
+
+#[derive(Debug)]
+pub struct JsepSession {
+    state: SignalingState,
+    session_id: u64,
+    session_version: u64,
+    credentials: IceCredentials,
+    fingerprints: Vec<CertificateFingerprint>,
+    cname: String,
+    ice_lite: bool,
+    local_candidates: HashMap<String, Vec<IceCandidate>>,
+    local_end_of_candidates: HashSet<String>,
+    max_message_size: usize,
+    bundle_policy: BundlePolicy,
+    rtcp_mux_policy: RtcpMuxPolicy,
+    capabilities: CapabilityRegistry,
+    /// From the last answer.
+    bundle_groups: Vec<Vec<String>>,
+    transceivers: Vec<RtpTransceiver>,
+    current_local: Option<SessionDescription>,
+    pending_local: Option<SessionDescription>,
+    current_remote: Option<SessionDescription>,
+    pending_remote: Option<SessionDescription>,
+    last_offer: Option<String>,
+    last_answer: Option<String>,
+    remote_transports: HashMap<String, RemoteTransport>,
+    remote_sctp: Option<SctpParameters>,
+    snapshot: Option<Snapshot>,
+    /// The current local description is an offer.
+    local_offerer: bool,
+    negotiation_needed: bool,
+    events: VecDeque<JsepEvent>,
+}
+
+impl JsepSession {
+    pub fn new(credentials: IceCredentials, fingerprints: Vec<CertificateFingerprint>) -> Self {
+        JsepSession {
+            state: SignalingState::Stable,
+            // at most 63 bits, RFC 8829 Section 5.2.1.
+            session_id: thread_rng().gen::<u64>() >> 1,
+            session_version: 0,
+            credentials,
+            fingerprints,
+            cname: generate_id(),
+            ice_lite: false,
+            local_candidates: HashMap::new(),
+            local_end_of_candidates: HashSet::new(),
+            max_message_size: SctpConfig::default().max_message_size,
+            bundle_policy: BundlePolicy::default(),
+            rtcp_mux_policy: RtcpMuxPolicy::default(),
+            capabilities: CapabilityRegistry::default(),
+            bundle_groups: vec![],
+            transceivers: vec![],
+            current_local: None,
+            pending_local: None,
+            current_remote: None,
+            pending_remote: None,
+            last_offer: None,
+            last_answer: None,
+            remote_transports: HashMap::new(),
+            remote_sctp: None,
+            snapshot: None,
+            local_offerer: false,
+            negotiation_needed: false,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn get_signaling_state(&self) -> SignalingState {
+        self.state
+    }
+
+    pub fn is_negotiation_needed(&self) -> bool {
+        self.negotiation_needed
+    }
+
+    /// Checks for negotiation first, for the transceivers changed since.
+    pub fn poll_event(&mut self) -> Option<JsepEvent> {
+        self.update_negotiation_needed();
+        self.events.pop_front()
+    }
+
+    pub fn get_local_credentials(&self) -> &IceCredentials {
+        &self.credentials
+    }
+
+    pub fn get_bundle_policy(&self) -> BundlePolicy {
+        self.bundle_policy
+    }
+
+    pub fn set_bundle_policy(&mut self, policy: BundlePolicy) {
+        self.bundle_policy = policy;
+    }
+
+    pub fn get_capabilities(&self) -> &CapabilityRegistry {
+        &self.capabilities
+    }
+
+    /// The codecs and extensions added are taken by the transceivers added
+    /// after.
+    pub fn get_capabilities_mut(&mut self) -> &mut CapabilityRegistry {
+        &mut self.capabilities
+    }
+
+    pub fn is_ice_lite(&self) -> bool {
+        self.ice_lite
+    }
+
+    /// Writes a=ice-lite, for an agent with IceConfig::lite.
+    pub fn set_ice_lite(&mut self, lite: bool) {
+        self.ice_lite = lite;
+    }
+
+    /// A gathered candidate of the transport of `mid`, None when gathering
+    /// is done. Written to the descriptions created after.
+    pub fn add_local_candidate(&mut self, mid: &str, candidate: Option<IceCandidate>) {
+        match candidate {
+            Some(v) => self
+                .local_candidates
+                .entry(mid.to_string())
+                .or_default()
+                .push(v),
+            None => {
+                self.local_end_of_candidates.insert(mid.to_string());
+            }
+        }
+    }
+
+    /// A trickled candidate of the peer, None for end-of-candidates. Kept
+    /// by the transport of `mid` and added to the remote description.
+    pub fn add_remote_candidate(
+        &mut self,
+        mid: &str,
+        candidate: Option<IceCandidate>,
+    ) -> Result<()> {
+        let transport_mid = self.get_transport_mid(mid).to_string();
+        let transport = match self.remote_transports.get_mut(&transport_mid) {
+            Some(v) => v,
+            None => return Err(invalid("no remote transport of the mid")),
+        };
+        let media = self
+            .pending_remote
+            .as_mut()
+            .or(self.current_remote.as_mut())
+            .and_then(|v| {
+                v.media
+                    .iter_mut()
+                    .find(|v| v.get_mid() == Some(transport_mid.as_str()))
+            });
+        match candidate {
+            Some(candidate) => {
+                if let Some(media) = media {
+                    add_candidates(media, std::slice::from_ref(&candidate), false);
+                }
+                if !transport.candidates.contains(&candidate) {
+                    transport.candidates.push(candidate);
+                }
+            }
+            None => {
+                if let Some(media) = media.filter(|v| !is_end_of_candidates(v)) {
+                    add_candidates(media, &[], true);
+                }
+                transport.end_of_candidates = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// The RTCP CNAME of the a=ssrc lines.
+    pub fn get_cname(&self) -> &str {
+        &self.cname
+    }
+
+    pub fn set_cname(&mut self, cname: &str) {
+        self.cname = cname.to_string();
+    }
+
+    pub fn get_rtcp_mux_policy(&self) -> RtcpMuxPolicy {
+        self.rtcp_mux_policy
+    }
+
+    pub fn set_rtcp_mux_policy(&mut self, policy: RtcpMuxPolicy) {
+        self.rtcp_mux_policy = policy;
+    }
+
+    /// The ICE components to gather for the transport of `mid`, 2 for RTCP
+    /// while it may not be muxed.
+    pub fn get_ice_components(&self, mid: &str) -> u16 {
+        let transceiver = match self.get_transceiver_by_mid(self.get_transport_mid(mid)) {
+            Some(v) => v,
+            None => return 1,
+        };
+        let muxed = match transceiver.current_direction {
+            Some(_) => transceiver.rtcp_mux,
+            None => self.rtcp_mux_policy == RtcpMuxPolicy::Require,
+        };
+        if transceiver.kind == MediaKind::Application || transceiver.stopped || muxed {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// A demuxer for the RTP packets of the transport of `mid`, with the
+    /// remote SSRCs, payload types and header extensions of its m= sections.
+    pub fn create_demuxer(&self, mid: &str) -> RtpDemuxer {
+        let transport = self.get_transport_mid(mid);
+        let transceivers: Vec<(&str, &RtpTransceiver)> = self
+            .transceivers
+            .iter()
+            .filter(|v| !v.stopped && v.current_direction.is_some())
+            .filter_map(|v| Some((v.get_mid()?, v)))
+            .filter(|(mid, _)| self.get_transport_mid(mid) == transport)
+            .collect();
+        let mut demuxer = RtpDemuxer::new();
+        let mut extmaps = vec![];
+        for (mid, transceiver) in transceivers {
+            for layer in &transceiver.receiver.remote_ssrcs {
+                demuxer.add_ssrc(layer.ssrc, mid);
+                if let Some(rtx_ssrc) = layer.rtx_ssrc {
+                    demuxer.add_ssrc(rtx_ssrc, mid);
+                }
+            }
+            for codec in &transceiver.negotiated_codecs {
+                demuxer.add_payload_type(codec.payload_type, mid);
+            }
+            extmaps.extend(transceiver.negotiated_extmaps.iter().cloned());
+        }
+        demuxer.set_header_extension_map(get_header_extension_map(&extmaps));
+        demuxer
+    }
+
+    /// The BUNDLE groups of the last answer, the tagged mid first.
+    pub fn get_bundle_groups(&self) -> &[Vec<String>] {
+        &self.bundle_groups
+    }
+
+    /// The mid whose transport the m= section of `mid` uses.
+    pub fn get_transport_mid<'a>(&'a self, mid: &'a str) -> &'a str {
+        get_bundle_tag(&self.bundle_groups, mid).unwrap_or(mid)
+    }
+
+    /// One mid for each transport to create, in the order of the m= lines.
+    pub fn get_transport_mids(&self) -> Vec<&str> {
+        let mut transceivers: Vec<&RtpTransceiver> = self
+            .transceivers
+            .iter()
+            .filter(|v| !v.stopped && v.mline_index.is_some())
+            .collect();
+        transceivers.sort_by_key(|v| v.mline_index);
+        let mut mids: Vec<&str> = vec![];
+        for transceiver in transceivers {
+            let mid = self.get_transport_mid(transceiver.get_mid().unwrap_or_default());
+            if !mids.contains(&mid) {
+                mids.push(mid);
+            }
+        }
+        mids
+    }
+
+    pub fn add_transceiver(&mut self, kind: MediaKind, direction: Direction) -> usize {
+        let index = self.create_transceiver(kind, direction);
+        self.update_negotiation_needed();
+        index
+    }
+
+    // remote offerのm= sectionのためにも作る．
+    fn create_transceiver(&mut self, kind: MediaKind, direction: Direction) -> usize {
+        self.transceivers
+            .push(RtpTransceiver::new(kind, direction, &self.capabilities));
+        self.transceivers.len() - 1
+    }
+
+    /// Sends the track on a transceiver of its kind that has never sent and
+    /// has no track, or on a new sendrecv one. The index of the transceiver.
+    pub fn add_track(&mut self, track: MediaStreamTrack, stream_ids: Vec<String>) -> Result<usize> {
+        let id = track.get_id();
+        if self
+            .transceivers
+            .iter()
+            .any(|v| !v.stopped && v.sender.get_track().is_some_and(|v| v.get_id() == id))
+        {
+            return Err(JsepError::InvalidTrack {
+                reason: format!("{} has a sender", id),
+            });
+        }
+        let kind = track.get_kind();
+        let index = match self.transceivers.iter().position(|v| {
+            v.kind == kind && !v.stopped && v.sender.track.is_none() && !v.sender.has_sent
+        }) {
+            Some(i) => {
+                let transceiver = &mut self.transceivers[i];
+                transceiver.direction = Direction::new(true, transceiver.direction.is_recv());
+                i
+            }
+            None => self.create_transceiver(kind, Direction::SendRecv),
+        };
+        let sender = &mut self.transceivers[index].sender;
+        sender.set_track(Some(track))?;
+        sender.set_stream_ids(stream_ids);
+        self.update_negotiation_needed();
+        Ok(index)
+    }
+
+    /// Stops sending the track of the transceiver, sendrecv becomes recvonly
+    /// and sendonly becomes inactive.
+    pub fn remove_track(&mut self, index: usize) {
+        let transceiver = match self.transceivers.get_mut(index) {
+            Some(v) if !v.stopped && v.sender.track.is_some() => v,
+            _ => return,
+        };
+        transceiver.sender.track = None;
+        transceiver.direction = Direction::new(false, transceiver.direction.is_recv());
+        self.update_negotiation_needed();
+    }
+
+    /// The application m= section, one for all the data channels.
+    pub fn add_data_channel(&mut self) -> usize {
+        match self
+            .transceivers
+            .iter()
+            .position(|v| v.kind == MediaKind::Application && !v.stopped)
+        {
+            Some(i) => i,
+            None => self.add_transceiver(MediaKind::Application, Direction::SendRecv),
+        }
+    }
+
+    pub fn get_transceivers(&self) -> &[RtpTransceiver] {
+        &self.transceivers
+    }
+
+    pub fn get_transceiver_mut(&mut self, index: usize) -> Option<&mut RtpTransceiver> {
+        self.transceivers.get_mut(index)
+    }
+
+    /// The codecs the transceiver offers, in order, and answers with. An
+    /// empty one is all the codecs of the capabilities. From the next offer
+    /// or answer.
+    pub fn set_codec_preferences(&mut self, index: usize, codecs: Vec<Codec>) -> Result<()> {
+        let kind = match self.transceivers.get(index) {
+            Some(v) => v.kind,
+            None => return Ok(()),
+        };
+        self.capabilities.check_codec_preferences(kind, &codecs)?;
+        let codecs = if codecs.is_empty() {
+            self.capabilities
+                .get_capabilities(kind, Direction::Inactive)
+                .codecs
+        } else {
+            codecs
+        };
+        self.transceivers[index].codecs = codecs;
+        Ok(())
+    }
+
+    pub fn get_transceiver_by_mid(&self, mid: &str) -> Option<&RtpTransceiver> {
+        self.transceivers.iter().find(|v| v.get_mid() == Some(mid))
+    }
+
+    /// The pending description, or the current one.
+    pub fn get_local_description(&self) -> Option<&SessionDescription> {
+        self.pending_local.as_ref().or(self.current_local.as_ref())
+    }
+
+    pub fn get_remote_description(&self) -> Option<&SessionDescription> {
+        self.pending_remote
+            .as_ref()
+            .or(self.current_remote.as_ref())
+    }
+
+    pub fn get_remote_transport(&self, mid: &str) -> Option<&RemoteTransport> {
+        self.remote_transports
+            .get(self.get_transport_mid(mid))
+            .or_else(|| self.remote_transports.get(mid))
+    }
+
+    /// The role of the transport of the mid, by the setups last negotiated.
+    /// None before the first answer.
+    pub fn get_dtls_role(&self, mid: &str) -> Option<DtlsRole> {
+        let mid = self.get_transport_mid(mid);
+        let setup = |session: Option<&SessionDescription>| {
+            let session = session?;
+            let media = session.media.iter().find(|v| v.get_mid() == Some(mid))?;
+            get_setup(session, media)
+        };
+        get_dtls_role(
+            setup(self.current_local.as_ref())?,
+            setup(self.current_remote.as_ref())?,
+        )
+    }
+
+    pub fn get_remote_sctp_parameters(&self) -> Option<SctpParameters> {
+        self.remote_sctp
+    }
+
+    pub fn close(&mut self) {
+        self.state = SignalingState::Closed;
+    }
+
+    fn check_state(&self, sdp_type: SdpType, states: &[SignalingState]) -> Result<()> {
+        if states.contains(&self.state) {
+            Ok(())
+        } else {
+            Err(JsepError::InvalidState {
+                state: self.state,
+                sdp_type,
+            })
+        }
+    }
+
+    // 最後に合意したdescription．m= sectionはこの順で並び，減らせない．
+    fn get_current_description(&self) -> Option<&SessionDescription> {
+        self.current_local.as_ref()
+    }
+
+    fn allocate_mid(&self) -> String {
+        let mids: HashSet<&str> = self
+            .transceivers
+            .iter()
+            .filter_map(|v| v.get_mid())
+            .collect();
+        (0..)
+            .map(|v: u32| v.to_string())
+            .find(|v| !mids.contains(v.as_str()))
+            .unwrap()
+    }
+
+    fn create_session(&self) -> SessionDescription {
+        let mut session =
+            SessionDescription::new(Origin::new(self.session_id, self.session_version));
+        if self.ice_lite {
+            session.attributes.push(Attribute::new_property("ice-lite"));
+        }
+        session
+            .attributes
+            .push(Attribute::new("msid-semantic", "WMS *"));
+        session
+    }
+
+    fn add_transport_attributes(&self, media: &mut MediaDescription, mid: &str, setup: Setup) {
+        media.connections.push(Connection::new("IP4", "0.0.0.0"));
+        let attributes = &mut media.attributes;
+        attributes.push(Attribute::new("ice-ufrag", &self.credentials.ufrag));
+        attributes.push(Attribute::new("ice-pwd", &self.credentials.pwd));
+        attributes.push(Attribute::new("ice-options", TRICKLE_OPTION));
+        for fingerprint in &self.fingerprints {
+            attributes.push(Attribute::new("fingerprint", &fingerprint.to_string()));
+        }
+        attributes.push(Attribute::new("setup", setup.get_name()));
+        add_candidates(
+            media,
+            self.local_candidates
+                .get(mid)
+                .map(|v| v.as_slice())
+                .unwrap_or_default(),
+            self.local_end_of_candidates.contains(mid),
+        );
+    }
+
+    fn create_media(
+        &self,
+        transceiver: &RtpTransceiver,
+        setup: Setup,
+        direction: Direction,
+        codecs: &[Codec],
+        extmaps: &[Extmap],
+        rtcp_mux: &[&str],
+    ) -> MediaDescription {
+        let mid = transceiver.get_mid().unwrap_or_default();
+        if transceiver.kind == MediaKind::Application {
+            let mut media = MediaDescription::new(
+                MediaKind::Application,
+                9,
+                SCTP_PROTOCOL,
+                vec![SCTP_FORMAT.to_string()],
+            );
+            self.add_transport_attributes(&mut media, mid, setup);
+            media.attributes.push(Attribute::new("mid", mid));
+            media
+                .attributes
+                .push(Attribute::new("sctp-port", &DEFAULT_SCTP_PORT.to_string()));
+            media.attributes.push(Attribute::new(
+                "max-message-size",
+                &self.max_message_size.to_string(),
+            ));
+            return media;
+        }
+        let mut media = MediaDescription::new(transceiver.kind, 9, RTP_PROTOCOL, vec![]);
+        // no candidate is known yet, RFC 8829 Section 5.2.1.
+        media
+            .attributes
+            .push(Attribute::new("rtcp", "9 IN IP4 0.0.0.0"));
+        self.add_transport_attributes(&mut media, mid, setup);
+        media.attributes.push(Attribute::new("mid", mid));
+        add_extmaps(&mut media, extmaps);
+        media.set_direction(direction);
+        let msids = get_local_msids(&transceiver.sender.stream_ids, &transceiver.sender.track_id);
+        if direction.is_send() {
+            add_msids(&mut media, &msids);
+        }
+        for name in rtcp_mux {
+            media.attributes.push(Attribute::new_property(name));
+        }
+        add_codecs(&mut media, codecs);
+        // RIDs tell the layers instead.
+        if direction.is_send() && transceiver.rids.is_empty() {
+            add_ssrcs(&mut media, &transceiver.sender.ssrcs, &self.cname, &msids);
+        }
+        media
+    }
+
+    fn create_rejected_media(offer: &MediaDescription) -> MediaDescription {
+        let mut media = MediaDescription {
+            port: 0,
+            port_count: None,
+            information: None,
+            connections: vec![Connection::new("IP4", "0.0.0.0")],
+            bandwidths: vec![],
+            key: None,
+            attributes: vec![],
+            ..offer.clone()
+        };
+        if let Some(mid) = offer.get_mid() {
+            media.attributes.push(Attribute::new("mid", mid));
+        }
+        media
+    }
+
+    pub fn create_offer(&mut self) -> Result<Description> {
+        self.check_state(
+            SdpType::Offer,
+            &[SignalingState::Stable, SignalingState::HaveLocalOffer],
+        )?;
+        // the m= sections keep their index, new ones are added after them.
+        let mut order: Vec<usize> = (0..self.transceivers.len())
+            .filter(|&i| self.transceivers[i].mline_index.is_some())
+            .collect();
+        order.sort_by_key(|&i| self.transceivers[i].mline_index);
+        // the m= sections rejected by both sides, a new transceiver takes one.
+        let mut recyclable: Vec<usize> = (0..order.len())
+            .filter(|&i| {
+                let transceiver = &self.transceivers[order[i]];
+                transceiver.stopped && transceiver.current_direction.is_none()
+            })
+            .filter(|&i| {
+                let rejected = |session: Option<&SessionDescription>| {
+                    session
+                        .and_then(|v| v.media.get(i))
+                        .is_some_and(|v| v.is_rejected())
+                };
+                rejected(self.current_local.as_ref()) && rejected(self.current_remote.as_ref())
+            })
+            .rev()
+            .collect();
+        for i in 0..self.transceivers.len() {
+            let transceiver = &self.transceivers[i];
+            if transceiver.mline_index.is_none() && !transceiver.stopped {
+                if transceiver.mid.is_none() {
+                    self.transceivers[i].mid = Some(self.allocate_mid());
+                }
+                match recyclable.pop() {
+                    Some(index) => order[index] = i,
+                    None => order.push(i),
+                }
+            }
+        }
+
+        let mut session = self.create_session();
+        let initial = self.get_current_description().is_none();
+        let mut kinds = vec![];
+        let mut bundle = vec![];
+        let rtcp_mux: &[&str] = match self.rtcp_mux_policy {
+            RtcpMuxPolicy::Negotiate => &["rtcp-mux"],
+            RtcpMuxPolicy::Require => &["rtcp-mux", "rtcp-mux-only"],
+        };
+        for i in order {
+            let transceiver = &self.transceivers[i];
+            let direction = if transceiver.stopped {
+                Direction::Inactive
+            } else {
+                transceiver.direction
+            };
+            let codecs =
+                self.capabilities
+                    .filter_codecs(transceiver.kind, &transceiver.codecs, direction);
+            let mut media = self.create_media(
+                transceiver,
+                Setup::ActPass,
+                direction,
+                &codecs,
+                &transceiver.extmaps,
+                rtcp_mux,
+            );
+            add_simulcast(
+                &mut media,
+                &transceiver.rids,
+                &offer_simulcast(&transceiver.rids),
+            );
+            if transceiver.stopped {
+                media.port = 0;
+            } else {
+                if initial && self.bundle_policy.is_bundle_only(transceiver.kind, &kinds) {
+                    media.port = 0;
+                    media
+                        .attributes
+                        .push(Attribute::new_property("bundle-only"));
+                }
+                kinds.push(transceiver.kind);
+                bundle.push(transceiver.get_mid().unwrap_or_default());
+            }
+            session.media.push(media);
+        }
+        if !bundle.is_empty() {
+            session.attributes.push(Attribute::new(
+                "group",
+                &format!("BUNDLE {}", bundle.join(" ")),
+            ));
+        }
+        let sdp = session.to_string();
+        self.last_offer = Some(sdp.clone());
+        Ok(Description::new(SdpType::Offer, &sdp))
+    }
+
+    pub fn create_answer(&mut self) -> Result<Description> {
+        self.check_state(
+            SdpType::Answer,
+            &[
+                SignalingState::HaveRemoteOffer,
+                SignalingState::HaveLocalPranswer,
+            ],
+        )?;
+        let offer = self.pending_remote.as_ref().unwrap();
+        let groups = get_bundle_groups(offer);
+        let mut session = self.create_session();
+        let mut accepted: Vec<&str> = vec![];
+        for offered in &offer.media {
+            let single = groups.is_empty()
+                && self.bundle_policy == BundlePolicy::MaxBundle
+                && !accepted.is_empty();
+            let transceiver = offered
+                .get_mid()
+                .and_then(|mid| self.get_transceiver_by_mid(mid))
+                .filter(|v| !v.stopped && !offered.is_rejected() && !single);
+            let transceiver = match transceiver {
+                Some(v) => v,
+                None => {
+                    session
+                        .media
+                        .push(JsepSession::create_rejected_media(offered));
+                    continue;
+                }
+            };
+            // validate has rejected holdconn.
+            let mid = offered.get_mid().unwrap_or_default();
+            let setup = self
+                .remote_transports
+                .get(get_bundle_tag(&groups, mid).unwrap_or(mid))
+                .and_then(|v| v.setup.answer(self.get_dtls_role(mid)))
+                .unwrap_or(Setup::Active);
+            let media = if transceiver.kind == MediaKind::Application {
+                self.create_media(transceiver, setup, Direction::SendRecv, &[], &[], &[])
+            } else {
+                let direction = get_media_direction(offer, offered);
+                let direction = Direction::new(
+                    transceiver.direction.is_send() && direction.is_recv(),
+                    transceiver.direction.is_recv() && direction.is_send(),
+                );
+                let local = self.capabilities.filter_codecs(
+                    transceiver.kind,
+                    &transceiver.codecs,
+                    direction,
+                );
+                let codecs = answer_codecs(&get_codecs(offered)?, &local);
+                let rtcp_mux: &[&str] = if is_rtcp_mux(offered) {
+                    &["rtcp-mux"]
+                } else {
+                    &[]
+                };
+                if codecs.is_empty()
+                    || (rtcp_mux.is_empty() && self.rtcp_mux_policy == RtcpMuxPolicy::Require)
+                {
+                    session
+                        .media
+                        .push(JsepSession::create_rejected_media(offered));
+                    continue;
+                }
+                let extmaps = answer_extmaps(&get_extmaps(offered)?, &transceiver.extmaps);
+                let mut media =
+                    self.create_media(transceiver, setup, direction, &codecs, &extmaps, rtcp_mux);
+                if let Some(simulcast) = get_simulcast(offered)? {
+                    let (rids, simulcast) = answer_simulcast(
+                        &simulcast,
+                        &get_rids(offered)?,
+                        &transceiver.rids,
+                        &codecs,
+                        direction.is_send(),
+                        direction.is_recv(),
+                    );
+                    add_simulcast(&mut media, &rids, &simulcast);
+                }
+                media.protocol = offered.protocol.clone();
+                media
+            };
+            accepted.push(offered.get_mid().unwrap_or_default());
+            session.media.push(media);
+        }
+        // the offered groups without the rejected m= sections.
+        for group in groups {
+            let mids: Vec<&str> = group
+                .iter()
+                .map(|v| v.as_str())
+                .filter(|v| accepted.contains(v))
+                .collect();
+            if !mids.is_empty() {
+                session.attributes.push(Attribute::new(
+                    "group",
+                    &format!("BUNDLE {}", mids.join(" ")),
+                ));
+            }
+        }
+        let sdp = session.to_string();
+        self.last_answer = Some(sdp.clone());
+        Ok(Description::new(SdpType::Answer, &sdp))
+    }
+
+    pub fn set_local_description(&mut self, description: &Description) -> Result<()> {
+        match description.sdp_type {
+            SdpType::Rollback => return self.rollback(SignalingState::HaveLocalOffer),
+            SdpType::Offer => {
+                self.check_state(
+                    SdpType::Offer,
+                    &[SignalingState::Stable, SignalingState::HaveLocalOffer],
+                )?;
+                if self.last_offer.as_ref() != Some(&description.sdp) {
+                    return Err(JsepError::InvalidModification);
+                }
+                let session = SessionDescription::parse(&description.sdp)?;
+                self.save_snapshot();
+                self.associate(&session);
+                self.pending_local = Some(session);
+                self.state = SignalingState::HaveLocalOffer;
+            }
+            sdp_type => {
+                self.check_state(
+                    sdp_type,
+                    &[
+                        SignalingState::HaveRemoteOffer,
+                        SignalingState::HaveLocalPranswer,
+                    ],
+                )?;
+                if self.last_answer.as_ref() != Some(&description.sdp) {
+                    return Err(JsepError::InvalidModification);
+                }
+                let session = SessionDescription::parse(&description.sdp)?;
+                self.apply_answer(&session, false);
+                if sdp_type == SdpType::Answer {
+                    self.current_local = Some(session);
+                    self.pending_local = None;
+                    self.current_remote = self.pending_remote.take();
+                    self.snapshot = None;
+                    self.local_offerer = false;
+                    self.state = SignalingState::Stable;
+                    self.stable_negotiation_needed();
+                } else {
+                    self.pending_local = Some(session);
+                    self.state = SignalingState::HaveLocalPranswer;
+                }
+            }
+        }
+        self.session_version += 1;
+        Ok(())
+    }
+
+    pub fn set_remote_description(&mut self, description: &Description) -> Result<()> {
+        let sdp_type = description.sdp_type;
+        if sdp_type == SdpType::Rollback {
+            return self.rollback(SignalingState::HaveRemoteOffer);
+        }
+        let states: &[SignalingState] = if sdp_type == SdpType::Offer {
+            &[SignalingState::Stable, SignalingState::HaveRemoteOffer]
+        } else {
+            &[
+                SignalingState::HaveLocalOffer,
+                SignalingState::HaveRemotePranswer,
+            ]
+        };
+        self.check_state(sdp_type, states)?;
+        let session = SessionDescription::parse(&description.sdp)?;
+        let transports = self.validate(&session, sdp_type)?;
+        let sctp = session
+            .media
+            .iter()
+            .find(|v| v.get_kind() == Some(MediaKind::Application) && !v.is_rejected())
+            .map(SctpParameters::parse)
+            .transpose()?;
+
+        if sdp_type == SdpType::Offer {
+            self.save_snapshot();
+            self.associate(&session);
+            self.apply_remote_tracks(&session);
+            self.pending_remote = Some(session);
+            self.state = SignalingState::HaveRemoteOffer;
+        } else {
+            self.apply_answer(&session, true);
+            self.apply_remote_tracks(&session);
+            if sdp_type == SdpType::Answer {
+                self.current_remote = Some(session);
+                self.pending_remote = None;
+                self.current_local = self.pending_local.take();
+                self.snapshot = None;
+                self.local_offerer = true;
+                self.state = SignalingState::Stable;
+                self.stable_negotiation_needed();
+            } else {
+                self.pending_remote = Some(session);
+                self.state = SignalingState::HaveRemotePranswer;
+            }
+        }
+        self.remote_transports.extend(transports);
+        if sctp.is_some() {
+            self.remote_sctp = sctp;
+        }
+        Ok(())
+    }
+
+    /// Checks the mids against the last description and reads the
+    /// transports of the m= sections not rejected.
+    fn validate(
+        &self,
+        session: &SessionDescription,
+        sdp_type: SdpType,
+    ) -> Result<Vec<(String, RemoteTransport)>> {
+        let mut mids = HashSet::new();
+        for media in &session.media {
+            let mid = media.get_mid().ok_or_else(|| invalid("no mid"))?;
+            if !mids.insert(mid) {
+                return Err(invalid("mid is not unique"));
+            }
+            get_simulcast(media)?;
+            get_msids(media)?;
+            get_ssrc_attributes(media)?;
+            get_ssrc_groups(media)?;
+        }
+        let groups = get_bundle_groups(session);
+        for mid in groups.iter().flatten() {
+            if !mids.contains(mid.as_str()) {
+                return Err(invalid("BUNDLE group has an unknown mid"));
+            }
+        }
+        if sdp_type != SdpType::Offer {
+            let offered = self
+                .pending_local
+                .as_ref()
+                .map(get_bundle_groups)
+                .unwrap_or_default();
+            if groups
+                .iter()
+                .flatten()
+                .any(|mid| get_bundle_tag(&offered, mid).is_none())
+            {
+                return Err(invalid("BUNDLE group has a mid not offered in one"));
+            }
+            let offer = self.pending_local.as_ref().unwrap();
+            let unmuxed = offer
+                .media
+                .iter()
+                .zip(&session.media)
+                .any(|(o, a)| is_rtcp_mux_only(o) && !a.is_rejected() && !is_rtcp_mux(a));
+            if unmuxed {
+                return Err(invalid("rtcp-mux-only is answered without rtcp-mux"));
+            }
+        }
+
+        let mut transports = vec![];
+        for media in session.media.iter().filter(|v| !v.is_rejected()) {
+            let mid = media.get_mid().unwrap();
+            match RemoteTransport::parse(session, media) {
+                Ok(v) => transports.push((mid.to_string(), v)),
+                // the tagged m= section has the transport.
+                Err(_) if get_bundle_tag(&groups, mid).is_some_and(|v| v != mid) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        for (_, transport) in &transports {
+            let answerable = match transport.setup {
+                Setup::HoldConn => false,
+                Setup::ActPass => sdp_type == SdpType::Offer,
+                _ => true,
+            };
+            if !answerable {
+                return Err(invalid("setup"));
+            }
+        }
+        let previous = if sdp_type == SdpType::Offer {
+            self.get_current_description()
+        } else {
+            self.pending_local.as_ref()
+        };
+        if let Some(previous) = previous {
+            let count = previous.media.len();
+            // an offer may recycle a rejected m= section with another mid.
+            let matches = session.media.len() >= count
+                && (sdp_type == SdpType::Offer || session.media.len() == count)
+                && previous.media.iter().zip(&session.media).all(|(a, b)| {
+                    a.get_mid() == b.get_mid() || (sdp_type == SdpType::Offer && a.is_rejected())
+                });
+            if !matches {
+                return Err(invalid("m= sections differ from the last description"));
+            }
+        }
+        Ok(transports)
+    }
+
+    // offerのm= sectionとtransceiverを対応付ける．無ければrecvonlyで作る．
+    fn associate(&mut self, session: &SessionDescription) {
+        for (i, media) in session.media.iter().enumerate() {
+            let mid = media.get_mid().unwrap_or_default();
+            if let Some(t) = self
+                .transceivers
+                .iter_mut()
+                .find(|v| v.get_mid() == Some(mid))
+            {
+                t.mline_index = Some(i);
+                continue;
+            }
+            let kind = match media.get_kind() {
+                Some(v) if !media.is_rejected() => v,
+                _ => continue,
+            };
+            let index = match self.transceivers.iter().position(|v| {
+                v.kind == kind && v.mid.is_none() && v.mline_index.is_none() && !v.stopped
+            }) {
+                Some(v) => v,
+                None if kind == MediaKind::Application => {
+                    self.create_transceiver(kind, Direction::SendRecv)
+                }
+                None => self.create_transceiver(kind, Direction::RecvOnly),
+            };
+            let transceiver = &mut self.transceivers[index];
+            transceiver.mid = Some(mid.to_string());
+            transceiver.mline_index = Some(i);
+        }
+        // the stopped ones whose m= sections are recycled by another mid.
+        for transceiver in &mut self.transceivers {
+            let recycled = transceiver.mline_index.is_some_and(|i| {
+                session.media.get(i).and_then(|v| v.get_mid()) != transceiver.get_mid()
+            });
+            if recycled {
+                transceiver.mline_index = None;
+            }
+        }
+    }
+
+    fn apply_answer(&mut self, session: &SessionDescription, remote: bool) {
+        self.bundle_groups = get_bundle_groups(session);
+        for media in &session.media {
+            let transceiver = match media.get_mid().and_then(|mid| {
+                self.transceivers
+                    .iter_mut()
+                    .find(|v| v.get_mid() == Some(mid))
+            }) {
+                Some(v) => v,
+                None => continue,
+            };
+            if media.is_rejected() {
+                transceiver.stopped = true;
+                transceiver.current_direction = None;
+                transceiver.negotiated_codecs.clear();
+                continue;
+            }
+            let direction = get_media_direction(session, media);
+            let direction = if remote {
+                direction.reverse()
+            } else {
+                direction
+            };
+            transceiver.current_direction = Some(direction);
+            transceiver.sender.has_sent |= direction.is_send();
+            transceiver.negotiated_codecs = get_codecs(media).unwrap_or_default();
+            transceiver.negotiated_extmaps = get_extmaps(media).unwrap_or_default();
+            transceiver.rtcp_mux = is_rtcp_mux(media);
+            transceiver.simulcast =
+                get_simulcast(media)
+                    .unwrap_or_default()
+                    .map(|v| if remote { v.reverse() } else { v });
+        }
+    }
+
+    /// The streams and SSRCs of the remote tracks, none while the peer does
+    /// not send.
+    fn apply_remote_tracks(&mut self, session: &SessionDescription) {
+        for media in &session.media {
+            let transceiver = match media.get_mid().and_then(|mid| {
+                self.transceivers
+                    .iter_mut()
+                    .find(|v| v.get_mid() == Some(mid))
+            }) {
+                Some(v) => v,
+                None => continue,
+            };
+            if get_media_direction(session, media).is_send() {
+                transceiver.receiver.remote_msids = get_msids(media).unwrap_or_default();
+                transceiver.receiver.remote_ssrcs = get_ssrc_layers(media).unwrap_or_default();
+                transceiver.receiver.remote_cname = get_cname(media);
+            } else {
+                transceiver.receiver.remote_msids.clear();
+                transceiver.receiver.remote_ssrcs.clear();
+                transceiver.receiver.remote_cname = None;
+            }
+        }
+    }
+
+    // 二度目のofferでは最初の状態を残す．
+    fn save_snapshot(&mut self) {
+        if self.state != SignalingState::Stable {
+            return;
+        }
+        self.snapshot = Some(Snapshot {
+            transceivers: self.transceivers.clone(),
+            remote_transports: self.remote_transports.clone(),
+            remote_sctp: self.remote_sctp,
+        });
+    }
+
+    /// Puts back what the offer changed. The directions, codecs and the
+    /// other settings of the transceivers are kept, as are the ones added
+    /// while in the offer.
+    fn rollback(&mut self, state: SignalingState) -> Result<()> {
+        self.check_state(SdpType::Rollback, &[state])?;
+        if let Some(snapshot) = self.snapshot.take() {
+            let count = snapshot.transceivers.len();
+            // the ones after that have an m= section were created for it.
+            let added: Vec<RtpTransceiver> = self
+                .transceivers
+                .drain(count..)
+                .filter(|v| v.mline_index.is_none())
+                .collect();
+            for (transceiver, saved) in self.transceivers.iter_mut().zip(snapshot.transceivers) {
+                transceiver.mid = saved.mid;
+                transceiver.mline_index = saved.mline_index;
+                transceiver.receiver = saved.receiver;
+            }
+            self.transceivers.extend(added);
+            // a mid not negotiated yet is allocated again by the next offer.
+            for transceiver in &mut self.transceivers {
+                if transceiver.mline_index.is_none() {
+                    transceiver.mid = None;
+                }
+            }
+            self.remote_transports = snapshot.remote_transports;
+            self.remote_sctp = snapshot.remote_sctp;
+        }
+        self.pending_local = None;
+        self.pending_remote = None;
+        self.state = SignalingState::Stable;
+        self.stable_negotiation_needed();
+        Ok(())
+    }
+
+    /// Queues NegotiationNeeded when it was not needed before. Nothing is
+    /// checked out of Stable.
+    fn update_negotiation_needed(&mut self) {
+        if self.state != SignalingState::Stable {
+            return;
+        }
+        let needed = self.check_negotiation_needed();
+        if needed && !self.negotiation_needed {
+            self.events.push_back(JsepEvent::NegotiationNeeded);
+        }
+        self.negotiation_needed = needed;
+    }
+
+    // Stableに戻った時，まだ必要なら改めて知らせる．
+    fn stable_negotiation_needed(&mut self) {
+        let needed = self.negotiation_needed;
+        self.update_negotiation_needed();
+        if needed && self.negotiation_needed {
+            self.events.push_back(JsepEvent::NegotiationNeeded);
+        }
+    }
+
+    // https://www.w3.org/TR/webrtc/#dfn-check-if-negotiation-is-needed
+    fn check_negotiation_needed(&self) -> bool {
+        fn find<'a>(
+            session: Option<&'a SessionDescription>,
+            mid: Option<&str>,
+        ) -> Option<(Direction, &'a MediaDescription)> {
+            let session = session?;
+            let media = session.media.iter().find(|v| v.get_mid() == mid)?;
+            Some((get_media_direction(session, media), media))
+        }
+        for transceiver in &self.transceivers {
+            let local = find(self.current_local.as_ref(), transceiver.get_mid())
+                .filter(|_| transceiver.mline_index.is_some());
+            let (direction, media) = match local {
+                _ if transceiver.stopped => {
+                    if local.is_some_and(|(_, media)| !media.is_rejected()) {
+                        return true;
+                    }
+                    continue;
+                }
+                Some(v) => v,
+                None => return true,
+            };
+            if transceiver.kind == MediaKind::Application {
+                continue;
+            }
+            if direction.is_send() && transceiver.direction.is_send() {
+                let mut streams: Vec<Option<String>> = get_msids(media)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|v| v.stream_id)
+                    .collect();
+                let mut sent: Vec<Option<String>> = transceiver
+                    .sender
+                    .stream_ids
+                    .iter()
+                    .map(|v| Some(v.clone()))
+                    .collect();
+                if sent.is_empty() {
+                    sent.push(None);
+                }
+                streams.sort();
+                sent.sort();
+                if streams != sent {
+                    return true;
+                }
+            }
+            let remote = find(self.current_remote.as_ref(), transceiver.get_mid())
+                .map(|(v, _)| v)
+                .unwrap_or(Direction::Inactive);
+            let changed = if self.local_offerer {
+                direction != transceiver.direction && remote.reverse() != transceiver.direction
+            } else {
+                direction
+                    != Direction::new(
+                        transceiver.direction.is_send() && remote.is_recv(),
+                        transceiver.direction.is_recv() && remote.is_send(),
+                    )
+            };
+            if changed {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::ice::agent::{IceAgent, IceConfig, IceRole};
+    use crate::jsep::ice::apply_remote_transport;
+    use crate::jsep::simulcast::{Rid, RidDirection};
+    use crate::jsep::ssrc::SsrcLayer;
+    use crate::rtp::audio_level::AUDIO_LEVEL_URI;
+    use crate::rtp::header_extension::MID_URI;
+    use crate::rtp::packet::RtpHeader;
+    use crate::sdp::session::test::CHROME_OFFER;
+
+    use std::time::Instant;
+
+    pub(crate) fn new_session(ufrag: &str) -> JsepSession {
+        let fingerprint = CertificateFingerprint::parse(
+            "sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC",
+        )
+        .unwrap();
+        JsepSession::new(
+            IceCredentials::new(ufrag, "6vKVOvTOf0kZgvhMAnLDRQI0"),
+            vec![fingerprint],
+        )
+    }
+
+    /// Offers from `offerer` and answers from `answerer`.
+    pub(crate) fn negotiate(offerer: &mut JsepSession, answerer: &mut JsepSession) {
+        let offer = offerer.create_offer().unwrap();
+        offerer.set_local_description(&offer).unwrap();
+        answerer.set_remote_description(&offer).unwrap();
+        let answer = answerer.create_answer().unwrap();
+        answerer.set_local_description(&answer).unwrap();
+        offerer.set_remote_description(&answer).unwrap();
+    }
+
+    #[test]
+    fn offer_answer_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Video, Direction::SendOnly);
+        a.add_data_channel();
+        assert_eq!(a.add_data_channel(), 2);
+
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert_eq!(session.media.len(), 3);
+        assert_eq!(session.media[0].get_mid(), Some("0"));
+        assert_eq!(session.media[1].get_direction(), Some(Direction::SendOnly));
+        assert_eq!(session.media[2].formats, vec![SCTP_FORMAT]);
+        assert_eq!(
+            session.media[2]
+                .get_attribute("sctp-port")
+                .unwrap()
+                .value
+                .as_deref(),
+            Some("5000")
+        );
+
+        a.set_local_description(&offer).unwrap();
+        assert_eq!(a.get_signaling_state(), SignalingState::HaveLocalOffer);
+        assert_eq!(a.get_transceivers()[1].get_mline_index(), Some(1));
+        b.set_remote_description(&offer).unwrap();
+        assert_eq!(b.get_signaling_state(), SignalingState::HaveRemoteOffer);
+        assert_eq!(b.get_transceivers().len(), 3);
+        assert_eq!(b.get_transceivers()[1].get_direction(), Direction::RecvOnly);
+        let transport = b.get_remote_transport("2").unwrap();
+        assert_eq!(transport.credentials.ufrag, "aaaa");
+        assert_eq!(
+            b.get_remote_sctp_parameters(),
+            Some(SctpParameters {
+                port: 5000,
+                max_message_size: 262144
+            })
+        );
+
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+        assert_eq!(b.get_signaling_state(), SignalingState::Stable);
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.get_signaling_state(), SignalingState::Stable);
+        assert_eq!(
+            a.get_remote_transport("0").unwrap().credentials.ufrag,
+            "bbbb"
+        );
+
+        // audio sendrecv and recvonly answers recvonly, video sendonly as well.
+        let audio = &a.get_transceivers()[0];
+        assert_eq!(audio.get_current_direction(), Some(Direction::SendOnly));
+        assert_eq!(audio.get_negotiated_codecs().len(), 3);
+        assert_eq!(audio.get_negotiated_codecs()[0].name, "opus");
+        assert_eq!(
+            b.get_transceivers()[0].get_current_direction(),
+            Some(Direction::RecvOnly)
+        );
+        assert_eq!(
+            a.get_transceivers()[1].get_current_direction(),
+            Some(Direction::SendOnly)
+        );
+        assert_eq!(b.get_transceivers()[1].get_negotiated_codecs().len(), 4);
+        assert_eq!(
+            a.get_transceivers()[2].get_current_direction(),
+            Some(Direction::SendRecv)
+        );
+
+        // renegotiation keeps the order and adds at the end.
+        b.get_transceiver_mut(0)
+            .unwrap()
+            .set_direction(Direction::SendRecv);
+        b.add_transceiver(MediaKind::Audio, Direction::SendOnly);
+        negotiate(&mut b, &mut a);
+        let mids: Vec<_> = b
+            .get_local_description()
+            .unwrap()
+            .media
+            .iter()
+            .map(|v| v.get_mid().unwrap().to_string())
+            .collect();
+        assert_eq!(mids, vec!["0", "1", "2", "3"]);
+        assert_eq!(
+            a.get_transceivers()[0].get_current_direction(),
+            Some(Direction::SendRecv)
+        );
+        assert_eq!(
+            a.get_transceiver_by_mid("3")
+                .unwrap()
+                .get_current_direction(),
+            Some(Direction::RecvOnly)
+        );
+        assert_eq!(a.get_local_description().unwrap().origin.session_version, 1);
+    }
+
+    #[test]
+    fn chrome_offer_test() {
+        let mut session = new_session("aaaa");
+        session.add_transceiver(MediaKind::Video, Direction::RecvOnly);
+        let offer = Description::new(SdpType::Offer, CHROME_OFFER);
+        session.set_remote_description(&offer).unwrap();
+        // the video transceiver takes mid 1, audio and data are created.
+        let transceivers = session.get_transceivers();
+        assert_eq!(transceivers.len(), 3);
+        assert_eq!(transceivers[0].get_mid(), Some("1"));
+        assert_eq!(transceivers[1].get_kind(), MediaKind::Audio);
+        assert_eq!(transceivers[2].get_kind(), MediaKind::Application);
+
+        let answer = session.create_answer().unwrap();
+        let answer = SessionDescription::parse(&answer.sdp).unwrap();
+        let audio = &answer.media[0];
+        assert_eq!(audio.formats, vec!["111", "0", "8"]);
+        assert_eq!(audio.get_direction(), Some(Direction::RecvOnly));
+        let video = &answer.media[1];
+        // H264 42001f is Baseline, not the local Constrained Baseline.
+        assert_eq!(video.formats, vec!["96", "97"]);
+        assert_eq!(video.get_attribute_values("fmtp").next(), Some("97 apt=96"));
+        assert_eq!(answer.media[2].get_mid(), Some("2"));
+        assert_eq!(
+            answer.media[2]
+                .get_attribute("setup")
+                .unwrap()
+                .value
+                .as_deref(),
+            Some("active")
+        );
+    }
+
+    #[test]
+    fn reject_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        b.get_transceiver_mut(1).unwrap().stop();
+        // no codec in common.
+        b.get_transceiver_mut(0).unwrap().set_codecs(vec![]);
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        for transceiver in a.get_transceivers() {
+            assert!(transceiver.is_stopped());
+            assert_eq!(transceiver.get_current_direction(), None);
+        }
+        let answer = SessionDescription::parse(&answer.sdp).unwrap();
+        assert_eq!(answer.media[0].port, 0);
+        assert_eq!(answer.media[1].get_mid(), Some("1"));
+
+        // a stopped transceiver keeps its m= section, with port 0.
+        let offer = a.create_offer().unwrap();
+        let offer = SessionDescription::parse(&offer.sdp).unwrap();
+        assert_eq!(offer.media.len(), 2);
+        assert_eq!(offer.media[1].port, 0);
+    }
+
+    #[test]
+    fn add_track_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let track = MediaStreamTrack::new(MediaKind::Audio);
+        let index = a.add_track(track.clone(), vec!["s".to_string()]).unwrap();
+        assert_eq!(index, 0);
+        assert!(a.add_track(track.clone(), vec![]).is_err());
+        let sender = a.get_transceivers()[0].get_sender();
+        assert_eq!(sender.get_track_id(), track.get_id());
+        assert!(a
+            .get_transceiver_mut(0)
+            .unwrap()
+            .get_sender_mut()
+            .set_track(Some(MediaStreamTrack::new(MediaKind::Video)))
+            .is_err());
+        negotiate(&mut a, &mut b);
+        assert_eq!(
+            b.get_transceivers()[0].get_receiver().get_remote_track_id(),
+            Some(track.get_id())
+        );
+
+        // the recvonly transceiver of the offer is made sendrecv.
+        let video = MediaStreamTrack::new(MediaKind::Video);
+        let index = a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        negotiate(&mut a, &mut b);
+        assert_eq!(b.add_track(video, vec![]).unwrap(), index);
+        assert_eq!(
+            b.get_transceivers()[index].get_direction(),
+            Direction::SendRecv
+        );
+        negotiate(&mut b, &mut a);
+        // a transceiver that has sent is not taken again.
+        b.remove_track(index);
+        assert_eq!(
+            b.get_transceivers()[index].get_direction(),
+            Direction::RecvOnly
+        );
+        let video = MediaStreamTrack::new(MediaKind::Video);
+        assert_eq!(b.add_track(video, vec![]).unwrap(), 2);
+
+        a.remove_track(0);
+        assert_eq!(a.get_transceivers()[0].get_direction(), Direction::RecvOnly);
+        assert!(a.get_transceivers()[0].get_sender().get_track().is_none());
+    }
+
+    #[test]
+    fn recycle_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        negotiate(&mut a, &mut b);
+        a.get_transceiver_mut(1).unwrap().stop();
+        negotiate(&mut a, &mut b);
+        assert_eq!(b.get_transceivers()[1].get_current_direction(), None);
+
+        // the rejected m= section is taken by the new transceiver.
+        let index = a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert_eq!(session.media.len(), 2);
+        assert_eq!(session.media[1].get_mid(), Some("2"));
+        assert_eq!(session.media[1].get_kind(), Some(MediaKind::Audio));
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.get_transceivers()[index].get_mline_index(), Some(1));
+        assert_eq!(a.get_transceivers()[1].get_mline_index(), None);
+        let transceiver = b.get_transceiver_by_mid("2").unwrap();
+        assert_eq!(transceiver.get_mline_index(), Some(1));
+        assert_eq!(
+            transceiver.get_current_direction(),
+            Some(Direction::RecvOnly)
+        );
+        assert_eq!(b.get_transceivers()[1].get_mline_index(), None);
+    }
+
+    // 2つ目以降の行を消す．
+    fn remove_repeated(sdp: &str, line: &str) -> String {
+        let i = sdp.find(line).unwrap() + line.len();
+        format!("{}{}", &sdp[..i], sdp[i..].replace(line, ""))
+    }
+
+    #[test]
+    fn bundle_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert_eq!(session.get_groups("BUNDLE"), vec![vec!["0", "1", "2"]]);
+        assert!(!session.media[0].is_bundle_only());
+        assert_eq!(session.media[1].port, 0);
+        assert!(session.media[1].is_bundle_only());
+        assert!(!session.media[2].is_bundle_only());
+
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        // the bundle-only m= section is taken, not rejected.
+        assert_eq!(b.get_transceivers().len(), 3);
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert_eq!(session.media[1].port, 9);
+
+        // a peer writing the transport only in the tagged m= section.
+        let sdp = remove_repeated(&answer.sdp, "a=ice-ufrag:bbbb\r\n");
+        let sdp = remove_repeated(&sdp, "a=ice-pwd:6vKVOvTOf0kZgvhMAnLDRQI0\r\n");
+        a.set_remote_description(&Description::new(SdpType::Answer, &sdp))
+            .unwrap();
+        assert_eq!(a.get_bundle_groups(), b.get_bundle_groups());
+        assert_eq!(a.get_bundle_groups(), &[vec!["0", "1", "2"]]);
+        assert_eq!(a.get_transport_mid("2"), "0");
+        assert_eq!(a.get_transport_mids(), vec!["0"]);
+        assert_eq!(b.get_transport_mids(), vec!["0"]);
+        assert_eq!(
+            a.get_remote_transport("1").unwrap().credentials.ufrag,
+            "bbbb"
+        );
+        assert_eq!(
+            a.get_transceivers()[1].get_current_direction(),
+            Some(Direction::SendOnly)
+        );
+
+        // a renegotiation has no bundle-only.
+        b.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let offer = b.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert!(session.media.iter().all(|v| !v.is_bundle_only()));
+        assert_eq!(session.get_groups("BUNDLE"), vec![vec!["0", "1", "2", "3"]]);
+    }
+
+    #[test]
+    fn bundle_reject_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.set_bundle_policy(BundlePolicy::MaxCompat);
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_data_channel();
+        let offer = a.create_offer().unwrap();
+        assert!(!offer.sdp.contains("bundle-only"));
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        b.get_transceiver_mut(0).unwrap().stop();
+        let answer = b.create_answer().unwrap();
+        // the tag moves to the first m= section taken.
+        assert!(answer.sdp.contains("a=group:BUNDLE 1 2\r\n"));
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.get_transport_mids(), vec!["1"]);
+        assert_eq!(
+            a.get_remote_transport("2").unwrap().credentials.ufrag,
+            "bbbb"
+        );
+        assert_eq!(a.get_remote_sctp_parameters().unwrap().port, 5000);
+
+        // an answer can't bundle what was not offered bundled.
+        let mut c = new_session("cccc");
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer).unwrap();
+        let without = offer.sdp.replace("a=group:BUNDLE 1 2\r\n", "");
+        c.set_remote_description(&Description::new(SdpType::Offer, &without))
+            .unwrap();
+        let answer = c.create_answer().unwrap();
+        assert!(!answer.sdp.contains("a=group"));
+        let bundled = answer
+            .sdp
+            .replace("s=-\r\nt=0 0\r\n", "s=-\r\nt=0 0\r\na=group:BUNDLE 1 3\r\n");
+        assert!(a
+            .set_remote_description(&Description::new(SdpType::Answer, &bundled))
+            .is_err());
+    }
+
+    #[test]
+    fn max_bundle_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        let sdp = offer.sdp.replace("a=group:BUNDLE 0 1\r\n", "");
+        b.set_bundle_policy(BundlePolicy::MaxBundle);
+        b.set_remote_description(&Description::new(SdpType::Offer, &sdp))
+            .unwrap();
+        let answer = b.create_answer().unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert_eq!(session.media[0].port, 9);
+        assert!(session.media[1].is_rejected());
+        assert!(session.get_groups("BUNDLE").is_empty());
+
+        a.set_bundle_policy(BundlePolicy::MaxBundle);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert!(session.media[1].is_bundle_only());
+    }
+
+    #[test]
+    fn capabilities_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        let vp9 = Codec::new(98, "VP9", 90000, None).with_parameters("profile-id=0");
+        a.get_capabilities_mut()
+            .add_codec(MediaKind::Video, vp9.clone(), Direction::RecvOnly)
+            .unwrap();
+        b.get_capabilities_mut()
+            .add_codec(MediaKind::Video, vp9, Direction::SendRecv)
+            .unwrap();
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        a.add_transceiver(MediaKind::Video, Direction::RecvOnly);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        // VP9 is only received.
+        assert_eq!(session.media[0].formats, vec!["96", "97", "102", "103"]);
+        assert_eq!(
+            session.media[1].formats,
+            vec!["96", "97", "102", "103", "98"]
+        );
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        let answer = b.create_answer().unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert!(session.media[1].formats.contains(&"98".to_string()));
+    }
+
+    #[test]
+    fn codec_preferences_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        let index = a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let codecs = a
+            .get_capabilities()
+            .get_sender_capabilities(MediaKind::Video)
+            .codecs;
+        // H264 and its RTX first, VP8 without RTX.
+        let preferences = vec![codecs[2].clone(), codecs[3].clone(), codecs[0].clone()];
+        a.set_codec_preferences(index, preferences).unwrap();
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert_eq!(session.media[0].formats, vec!["102", "103", "96"]);
+        assert!(a
+            .set_codec_preferences(index, vec![codecs[1].clone()])
+            .is_err());
+        assert!(a
+            .set_codec_preferences(index, vec![Codec::new(111, "opus", 48000, Some(2))])
+            .is_err());
+
+        // the answer has only the preferred codecs of the offer.
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        b.set_codec_preferences(0, vec![codecs[0].clone()]).unwrap();
+        let answer = b.create_answer().unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert_eq!(session.media[0].formats, vec!["96"]);
+
+        a.set_codec_preferences(index, vec![]).unwrap();
+        assert_eq!(a.get_transceivers()[index].get_codecs(), &codecs[..]);
+    }
+
+    #[test]
+    fn extmap_test() {
+        let mut session = new_session("aaaa");
+        let offer = Description::new(SdpType::Offer, CHROME_OFFER);
+        session.set_remote_description(&offer).unwrap();
+        let answer = session.create_answer().unwrap();
+        session.set_local_description(&answer).unwrap();
+        let transceivers = session.get_transceivers();
+        // in the order of the offer.
+        let ids: Vec<u16> = transceivers[1]
+            .get_negotiated_extmaps()
+            .iter()
+            .map(|v| v.id)
+            .collect();
+        assert_eq!(ids, vec![14, 2, 3, 4, 10, 11]);
+        let map = transceivers[0].get_header_extension_map();
+        assert_eq!(map.get_id(AUDIO_LEVEL_URI), Some(1));
+        assert_eq!(map.get_id(MID_URI), Some(4));
+
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        let index = a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.get_transceiver_mut(index)
+            .unwrap()
+            .set_extmaps(vec![Extmap::new(7, MID_URI), Extmap::new(8, "urn:x")]);
+        negotiate(&mut a, &mut b);
+        let map = a.get_transceivers()[0].get_header_extension_map();
+        assert_eq!(map, b.get_transceivers()[0].get_header_extension_map());
+        assert_eq!(map.get_id(MID_URI), Some(7));
+        assert_eq!(map.get_uri(8), None);
+    }
+
+    #[test]
+    fn simulcast_test() {
+        let mut session = new_session("aaaa");
+        session.add_transceiver(MediaKind::Video, Direction::RecvOnly);
+        let sdp = CHROME_OFFER.replace(
+            "a=fmtp:103 apt=102\r\n",
+            "a=fmtp:103 apt=102\r\na=rid:q send pt=96;max-width=320\r\na=rid:h send\r\na=simulcast:send q;~h\r\n",
+        );
+        let offer = Description::new(SdpType::Offer, &sdp);
+        session.set_remote_description(&offer).unwrap();
+        let answer = session.create_answer().unwrap();
+        let parsed = SessionDescription::parse(&answer.sdp).unwrap();
+        let video = &parsed.media[1];
+        let rids: Vec<&str> = video.get_attribute_values("rid").collect();
+        assert_eq!(rids, vec!["q recv pt=96;max-width=320", "h recv"]);
+        assert_eq!(
+            video.get_attribute_values("simulcast").next(),
+            Some("recv q;~h")
+        );
+        session.set_local_description(&answer).unwrap();
+        let simulcast = session.get_transceivers()[0].get_simulcast().unwrap();
+        assert_eq!(simulcast.get_rids(RidDirection::Recv), vec!["q", "h"]);
+
+        // a layer without its a=rid
+        let mut session = new_session("aaaa");
+        let sdp = sdp.replace("a=rid:h send\r\n", "");
+        assert!(session
+            .set_remote_description(&Description::new(SdpType::Offer, &sdp))
+            .is_err());
+
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        let index = a.add_transceiver(MediaKind::Video, Direction::SendOnly);
+        a.get_transceiver_mut(index).unwrap().set_rids(vec![
+            Rid::new("q", RidDirection::Send).with_restriction("max-height", "180"),
+            Rid::new("f", RidDirection::Send),
+        ]);
+        negotiate(&mut a, &mut b);
+        let simulcast = a.get_transceivers()[0].get_simulcast().unwrap();
+        assert_eq!(simulcast.get_rids(RidDirection::Send), vec!["q", "f"]);
+        let simulcast = b.get_transceivers()[0].get_simulcast().unwrap();
+        assert_eq!(simulcast.get_rids(RidDirection::Recv), vec!["q", "f"]);
+    }
+
+    #[test]
+    fn msid_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        let index = a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let transceiver = a.get_transceiver_mut(index).unwrap();
+        transceiver
+            .get_sender_mut()
+            .set_stream_ids(vec!["s1".to_string(), "s2".to_string()]);
+        transceiver.get_sender_mut().set_track_id("t1");
+        a.add_transceiver(MediaKind::Video, Direction::RecvOnly);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        let msids: Vec<&str> = session.media[0].get_attribute_values("msid").collect();
+        assert_eq!(msids, vec!["s1 t1", "s2 t1"]);
+        assert_eq!(session.media[1].get_attribute("msid"), None);
+
+        negotiate(&mut a, &mut b);
+        let transceivers = b.get_transceivers();
+        assert_eq!(
+            transceivers[0].get_receiver().get_remote_stream_ids(),
+            vec!["s1", "s2"]
+        );
+        assert_eq!(
+            transceivers[0].get_receiver().get_remote_track_id(),
+            Some("t1")
+        );
+        assert_eq!(transceivers[1].get_receiver().get_remote_track_id(), None);
+        // b only receives until it sends video.
+        assert_eq!(
+            a.get_transceivers()[0].get_receiver().get_remote_track_id(),
+            None
+        );
+        b.get_transceiver_mut(1)
+            .unwrap()
+            .set_direction(Direction::SendOnly);
+        negotiate(&mut b, &mut a);
+        let transceivers = a.get_transceivers();
+        assert_eq!(
+            transceivers[1].get_receiver().get_remote_track_id(),
+            Some(b.get_transceivers()[1].get_sender().get_track_id())
+        );
+        assert!(transceivers[1]
+            .get_receiver()
+            .get_remote_stream_ids()
+            .is_empty());
+
+        let mut session = new_session("aaaa");
+        let offer = Description::new(SdpType::Offer, CHROME_OFFER);
+        session.set_remote_description(&offer).unwrap();
+        let transceiver = &session.get_transceivers()[1];
+        assert_eq!(
+            transceiver.get_receiver().get_remote_stream_ids(),
+            vec!["3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a"]
+        );
+    }
+
+    #[test]
+    fn ssrc_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let index = a.add_transceiver(MediaKind::Video, Direction::SendOnly);
+        let layers = vec![SsrcLayer::new(1, Some(2)), SsrcLayer::new(3, Some(4))];
+        a.get_transceiver_mut(index)
+            .unwrap()
+            .get_sender_mut()
+            .set_ssrcs(layers.clone());
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        let groups: Vec<&str> = session.media[1]
+            .get_attribute_values("ssrc-group")
+            .collect();
+        assert_eq!(groups, vec!["SIM 1 3", "FID 1 2", "FID 3 4"]);
+        assert!(session.media[0].get_attribute("ssrc-group").is_none());
+
+        negotiate(&mut a, &mut b);
+        let transceivers = b.get_transceivers();
+        assert_eq!(
+            transceivers[1].get_receiver().get_remote_ssrcs(),
+            &layers[..]
+        );
+        assert_eq!(
+            transceivers[1].get_receiver().get_remote_cname(),
+            Some(a.get_cname())
+        );
+        // b only receives.
+        assert!(a.get_transceivers()[1]
+            .get_receiver()
+            .get_remote_ssrcs()
+            .is_empty());
+
+        let audio_ssrc = a.get_transceivers()[0].get_sender().get_ssrcs()[0].ssrc;
+        let mut demuxer = b.create_demuxer("1");
+        assert_eq!(demuxer.demux(&RtpHeader::new(96, 1, 0, 4)), Some("1"));
+        assert_eq!(
+            demuxer.demux(&RtpHeader::new(0, 1, 0, audio_ssrc)),
+            Some("0")
+        );
+
+        // the layers are told by RIDs.
+        a.get_transceiver_mut(index)
+            .unwrap()
+            .set_rids(vec![Rid::new("q", RidDirection::Send)]);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert!(session.media[1].get_attribute("ssrc").is_none());
+    }
+
+    #[test]
+    fn ice_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.set_ice_lite(true);
+        let host = IceCandidate::parse("candidate:1 1 udp 2122260223 192.168.99.58 45076 typ host")
+            .unwrap();
+        a.add_local_candidate("0", Some(host.clone()));
+        a.add_local_candidate("0", None);
+        negotiate(&mut a, &mut b);
+        let transport = b.get_remote_transport("0").unwrap();
+        assert!(transport.ice_lite);
+        assert_eq!(transport.ice_options, vec![TRICKLE_OPTION]);
+        assert_eq!(transport.candidates, vec![host]);
+        assert!(transport.end_of_candidates);
+
+        let mut agent = IceAgent::new(IceConfig::default(), IceRole::Controlled);
+        apply_remote_transport(&mut agent, transport, Instant::now());
+        assert_eq!(agent.get_remote_credentials(), Some(&transport.credentials));
+        assert_eq!(agent.get_role(), IceRole::Controlling);
+        assert_eq!(agent.get_remote_candidates().len(), 1);
+        assert!(agent.has_remote_end_of_candidates());
+
+        // trickled from b
+        let transport = a.get_remote_transport("0").unwrap();
+        assert!(transport.candidates.is_empty() && !transport.end_of_candidates);
+        let srflx = IceCandidate::parse("candidate:842163049 1 udp 1677729535 203.0.113.7 60769 typ srflx raddr 192.168.99.58 rport 45076").unwrap();
+        a.add_remote_candidate("0", Some(srflx.clone())).unwrap();
+        a.add_remote_candidate("0", None).unwrap();
+        let transport = a.get_remote_transport("0").unwrap();
+        assert_eq!(transport.candidates, vec![srflx]);
+        assert!(transport.end_of_candidates);
+        let remote = a.get_remote_description().unwrap();
+        assert_eq!(remote.media[0].get_attribute_values("candidate").count(), 1);
+        assert!(remote.media[0].get_attribute("end-of-candidates").is_some());
+        assert!(a.add_remote_candidate("5", None).is_err());
+    }
+
+    #[test]
+    fn setup_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        assert_eq!(b.get_remote_transport("0").unwrap().setup, Setup::ActPass);
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+
+        // actpass does not answer.
+        let actpass = Description::new(
+            SdpType::Answer,
+            &answer.sdp.replace("setup:active", "setup:actpass"),
+        );
+        assert!(a.set_remote_description(&actpass).is_err());
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.get_dtls_role("0"), Some(DtlsRole::Server));
+        assert_eq!(b.get_dtls_role("0"), Some(DtlsRole::Client));
+
+        // b keeps the role when a offers again, and also when it offers.
+        negotiate(&mut a, &mut b);
+        assert_eq!(b.get_dtls_role("0"), Some(DtlsRole::Client));
+        negotiate(&mut b, &mut a);
+        assert_eq!(a.get_dtls_role("0"), Some(DtlsRole::Server));
+        assert_eq!(b.get_dtls_role("0"), Some(DtlsRole::Client));
+
+        let mut c = new_session("cccc");
+        let offer = a.create_offer().unwrap();
+        let passive = Description::new(
+            SdpType::Offer,
+            &offer.sdp.replace("setup:actpass", "setup:passive"),
+        );
+        c.set_remote_description(&passive).unwrap();
+        let answer = c.create_answer().unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert_eq!(get_setup(&session, &session.media[0]), Some(Setup::Active));
+        let holdconn = Description::new(
+            SdpType::Offer,
+            &offer.sdp.replace("setup:actpass", "setup:holdconn"),
+        );
+        assert!(new_session("dddd")
+            .set_remote_description(&holdconn)
+            .is_err());
+    }
+
+    #[test]
+    fn rtcp_mux_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert!(is_rtcp_mux_only(&session.media[0]));
+        assert_eq!(a.get_ice_components("0"), 1);
+
+        // a non-mux answer to rtcp-mux-only
+        a.set_local_description(&offer).unwrap();
+        b.set_remote_description(&offer).unwrap();
+        let answer = b.create_answer().unwrap();
+        let sdp = answer.sdp.replace("a=rtcp-mux\r\n", "");
+        assert!(a
+            .set_remote_description(&Description::new(SdpType::Answer, &sdp))
+            .is_err());
+        a.set_remote_description(&answer).unwrap();
+        assert!(a.get_transceivers()[0].is_rtcp_mux());
+
+        // negotiate gathers RTCP until an answer without rtcp-mux
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.set_rtcp_mux_policy(RtcpMuxPolicy::Negotiate);
+        b.set_rtcp_mux_policy(RtcpMuxPolicy::Negotiate);
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        let session = SessionDescription::parse(&offer.sdp).unwrap();
+        assert!(is_rtcp_mux(&session.media[0]));
+        assert!(!is_rtcp_mux_only(&session.media[0]));
+        assert_eq!(a.get_ice_components("0"), 2);
+        a.set_local_description(&offer).unwrap();
+        let sdp = offer.sdp.replace("a=rtcp-mux\r\n", "");
+        b.set_remote_description(&Description::new(SdpType::Offer, &sdp))
+            .unwrap();
+        let answer = b.create_answer().unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert_eq!(session.media[0].port, 9);
+        assert!(!is_rtcp_mux(&session.media[0]));
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        assert!(!a.get_transceivers()[0].is_rtcp_mux());
+        assert_eq!(a.get_ice_components("0"), 2);
+        assert_eq!(b.get_ice_components("0"), 2);
+
+        // require rejects a non-mux offer
+        let mut b = new_session("bbbb");
+        b.set_remote_description(&Description::new(SdpType::Offer, &sdp))
+            .unwrap();
+        let answer = b.create_answer().unwrap();
+        let session = SessionDescription::parse(&answer.sdp).unwrap();
+        assert!(session.media[0].is_rejected());
+    }
+
+    #[test]
+    fn signaling_state_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        assert_eq!(
+            a.create_answer(),
+            Err(JsepError::InvalidState {
+                state: SignalingState::Stable,
+                sdp_type: SdpType::Answer
+            })
+        );
+        let offer = a.create_offer().unwrap();
+        assert_eq!(
+            a.set_remote_description(&Description::new(SdpType::Answer, &offer.sdp)),
+            Err(JsepError::InvalidState {
+                state: SignalingState::Stable,
+                sdp_type: SdpType::Answer
+            })
+        );
+        let modified = Description::new(SdpType::Offer, &offer.sdp.replace("trickle", "x"));
+        assert_eq!(
+            a.set_local_description(&modified),
+            Err(JsepError::InvalidModification)
+        );
+        a.set_local_description(&offer).unwrap();
+        assert!(a
+            .set_remote_description(&Description::new(SdpType::Offer, &offer.sdp))
+            .is_err());
+
+        // pranswer then answer.
+        b.set_remote_description(&offer).unwrap();
+        let mut answer = b.create_answer().unwrap();
+        answer.sdp_type = SdpType::Pranswer;
+        b.set_local_description(&answer).unwrap();
+        assert_eq!(b.get_signaling_state(), SignalingState::HaveLocalPranswer);
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.get_signaling_state(), SignalingState::HaveRemotePranswer);
+        answer.sdp_type = SdpType::Answer;
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.get_signaling_state(), SignalingState::Stable);
+
+        // rollback of a local offer.
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer).unwrap();
+        let rollback = Description::new(SdpType::Rollback, "");
+        assert!(a.set_remote_description(&rollback).is_err());
+        a.set_local_description(&rollback).unwrap();
+        assert_eq!(a.get_signaling_state(), SignalingState::Stable);
+
+        a.close();
+        assert_eq!(
+            a.create_offer(),
+            Err(JsepError::InvalidState {
+                state: SignalingState::Closed,
+                sdp_type: SdpType::Offer
+            })
+        );
+    }
+
+    #[test]
+    fn rollback_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        negotiate(&mut a, &mut b);
+
+        // glare: b rolls its offer back and takes the one of a.
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let index = b.add_transceiver(MediaKind::Video, Direction::SendOnly);
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer).unwrap();
+        let glare = b.create_offer().unwrap();
+        b.set_local_description(&glare).unwrap();
+        assert_eq!(b.get_transceivers()[index].get_mid(), Some("1"));
+        assert!(b.set_remote_description(&offer).is_err());
+        b.set_local_description(&Description::new(SdpType::Rollback, ""))
+            .unwrap();
+        assert_eq!(b.get_signaling_state(), SignalingState::Stable);
+        assert_eq!(b.get_transceivers()[index].get_mid(), None);
+        b.set_remote_description(&offer).unwrap();
+        assert_eq!(b.get_transceivers().len(), 2);
+        assert_eq!(b.get_transceivers()[index].get_mid(), Some("1"));
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(
+            a.get_transceivers()[1].get_current_direction(),
+            Some(Direction::RecvOnly)
+        );
+
+        // the transceivers and transports of a remote offer are removed.
+        let mut c = new_session("cccc");
+        let offer = a.create_offer().unwrap();
+        c.set_remote_description(&offer).unwrap();
+        assert_eq!(c.get_transceivers().len(), 2);
+        assert!(c.get_remote_transport("0").is_some());
+        c.set_remote_description(&Description::new(SdpType::Rollback, ""))
+            .unwrap();
+        assert!(c.get_transceivers().is_empty());
+        assert!(c.get_remote_transport("0").is_none());
+        assert!(c.get_remote_description().is_none());
+        c.set_remote_description(&offer).unwrap();
+        assert_eq!(c.get_transceivers().len(), 2);
+        let answer = c.create_answer().unwrap();
+        c.set_local_description(&answer).unwrap();
+    }
+
+    #[test]
+    fn negotiation_needed_test() {
+        let mut a = new_session("aaaa");
+        let mut b = new_session("bbbb");
+        assert_eq!(a.poll_event(), None);
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        a.add_data_channel();
+        // once for both.
+        assert_eq!(a.poll_event(), Some(JsepEvent::NegotiationNeeded));
+        assert_eq!(a.poll_event(), None);
+        assert!(a.is_negotiation_needed());
+        negotiate(&mut a, &mut b);
+        assert!(!a.is_negotiation_needed() && !b.is_negotiation_needed());
+        assert_eq!(a.poll_event(), None);
+        assert_eq!(b.poll_event(), None);
+
+        // sendonly is what b has answered.
+        a.get_transceiver_mut(0)
+            .unwrap()
+            .set_direction(Direction::SendOnly);
+        assert_eq!(a.poll_event(), None);
+        a.get_transceiver_mut(0)
+            .unwrap()
+            .set_direction(Direction::Inactive);
+        assert_eq!(a.poll_event(), Some(JsepEvent::NegotiationNeeded));
+        negotiate(&mut a, &mut b);
+        a.get_transceiver_mut(0)
+            .unwrap()
+            .set_direction(Direction::SendRecv);
+        assert_eq!(a.poll_event(), Some(JsepEvent::NegotiationNeeded));
+        negotiate(&mut a, &mut b);
+        assert_eq!(a.poll_event(), None);
+        b.get_transceiver_mut(0)
+            .unwrap()
+            .get_sender_mut()
+            .set_stream_ids(vec!["s".to_string()]);
+        assert_eq!(b.poll_event(), None);
+        b.get_transceiver_mut(0)
+            .unwrap()
+            .set_direction(Direction::SendRecv);
+        assert_eq!(b.poll_event(), Some(JsepEvent::NegotiationNeeded));
+        negotiate(&mut b, &mut a);
+        assert_eq!(b.poll_event(), None);
+
+        // fired again when still needed after the offer.
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer).unwrap();
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        assert_eq!(a.poll_event(), None);
+        b.set_remote_description(&offer).unwrap();
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer).unwrap();
+        a.set_remote_description(&answer).unwrap();
+        assert_eq!(a.poll_event(), Some(JsepEvent::NegotiationNeeded));
+        negotiate(&mut a, &mut b);
+
+        b.get_transceiver_mut(1).unwrap().stop();
+        assert_eq!(b.poll_event(), Some(JsepEvent::NegotiationNeeded));
+        negotiate(&mut b, &mut a);
+        assert!(!a.is_negotiation_needed() && !b.is_negotiation_needed());
+    }
+
+    #[test]
+    fn invalid_description_test() {
+        let mut a = new_session("aaaa");
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let offer = a.create_offer().unwrap();
+        let cases = [
+            offer.sdp.replace("a=mid:0\r\n", ""),
+            offer
+                .sdp
+                .replace("a=ice-pwd:6vKVOvTOf0kZgvhMAnLDRQI0\r\n", ""),
+            offer
+                .sdp
+                .replace("fingerprint:sha-256 6B", "fingerprint:sha-256 6"),
+            offer.sdp.replace("v=0", "v=1"),
+        ];
+        for sdp in cases.iter() {
+            let mut b = new_session("bbbb");
+            assert!(b
+                .set_remote_description(&Description::new(SdpType::Offer, sdp))
+                .is_err());
+            assert_eq!(b.get_signaling_state(), SignalingState::Stable);
+        }
+
+        // an answer must have the m= sections of the offer.
+        let mut b = new_session("bbbb");
+        a.set_local_description(&offer).unwrap();
+        b.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        b.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        let other = b.create_offer().unwrap();
+        assert!(a
+            .set_remote_description(&Description::new(SdpType::Answer, &other.sdp))
+            .is_err());
+        assert_eq!(a.get_signaling_state(), SignalingState::HaveLocalOffer);
+    }
+}
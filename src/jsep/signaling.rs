@@ -0,0 +1,166 @@
+// https://www.w3.org/TR/webrtc/#dom-rtcsessiondescriptioninit
+// https://www.w3.org/TR/webrtc/#dom-rtcicecandidateinit
+
+/*
+    the JSON the browsers send and take for signaling.
+
+    {"type": "offer", "sdp": "v=0\r\n..."}
+    {"candidate": "candidate:1 1 udp ...", "sdpMid": "0", "sdpMLineIndex": 0,
+     "usernameFragment": "EsAw"}
+
+    a candidate of "" is the end of candidates of the m= section.
+*/
+
+use crate::ice::candidate::IceCandidate;
+use crate::ice::IceError;
+use crate::jsep::{Description, SdpType};
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+// SdpTypeはJSONでは"offer"等の名前で表す．
+mod sdp_type {
+    use crate::jsep::SdpType;
+
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S: Serializer>(sdp_type: &SdpType, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(sdp_type.get_name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SdpType, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        SdpType::from_name(&name).ok_or_else(|| {
+            de::Error::unknown_variant(&name, &["offer", "pranswer", "answer", "rollback"])
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "SessionDescriptionJson")]
+pub struct SessionDescriptionInit {
+    #[serde(rename = "type", with = "sdp_type")]
+    pub sdp_type: SdpType,
+    pub sdp: String,
+}
+
+// 受信したJSON．rollbackのsdpは省略できる．
+#[derive(Deserialize)]
+struct SessionDescriptionJson {
+    #[serde(rename = "type", with = "sdp_type")]
+    sdp_type: SdpType,
+    #[serde(default)]
+    sdp: Option<String>,
+}
+
+impl TryFrom<SessionDescriptionJson> for SessionDescriptionInit {
+    type Error = &'static str;
+
+    fn try_from(json: SessionDescriptionJson) -> Result<Self, Self::Error> {
+        let sdp = match json.sdp {
+            Some(v) => v,
+            None if json.sdp_type == SdpType::Rollback => String::new(),
+            None => return Err("missing field `sdp`"),
+        };
+        Ok(SessionDescriptionInit {
+            sdp_type: json.sdp_type,
+            sdp,
+        })
+    }
+}
+
+impl From<Description> for SessionDescriptionInit {
+    fn from(description: Description) -> Self {
+        SessionDescriptionInit {
+            sdp_type: description.sdp_type,
+            sdp: description.sdp,
+        }
+    }
+}
+
+impl From<SessionDescriptionInit> for Description {
+    fn from(init: SessionDescriptionInit) -> Self {
+        Description {
+            sdp_type: init.sdp_type,
+            sdp: init.sdp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IceCandidateInit {
+    /// The a=candidate line without "a=", empty for the end of candidates.
+    #[serde(default)]
+    pub candidate: String,
+    #[serde(default)]
+    pub sdp_mid: Option<String>,
+    #[serde(rename = "sdpMLineIndex", default)]
+    pub sdp_mline_index: Option<u16>,
+    #[serde(default)]
+    pub username_fragment: Option<String>,
+}
+
+impl IceCandidateInit {
+    /// None is the end of candidates.
+    pub fn new(candidate: Option<&IceCandidate>, sdp_mid: &str, sdp_mline_index: u16) -> Self {
+        IceCandidateInit {
+            candidate: candidate.map(|v| v.to_string()).unwrap_or_default(),
+            sdp_mid: Some(sdp_mid.to_string()),
+            sdp_mline_index: Some(sdp_mline_index),
+            username_fragment: None,
+        }
+    }
+
+    /// None for the end of candidates.
+    pub fn get_candidate(&self) -> Result<Option<IceCandidate>, IceError> {
+        if self.candidate.trim().is_empty() {
+            return Ok(None);
+        }
+        IceCandidate::parse(&self.candidate).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_description_test() {
+        let init: SessionDescriptionInit =
+            serde_json::from_str(r#"{"type":"offer","sdp":"v=0\r\n"}"#).unwrap();
+        assert_eq!(init.sdp_type, SdpType::Offer);
+        assert_eq!(init.sdp, "v=0\r\n");
+        assert_eq!(
+            serde_json::to_string(&init).unwrap(),
+            r#"{"type":"offer","sdp":"v=0\r\n"}"#
+        );
+        let description: Description = init.into();
+        assert_eq!(description, Description::new(SdpType::Offer, "v=0\r\n"));
+
+        let init: SessionDescriptionInit = serde_json::from_str(r#"{"type":"rollback"}"#).unwrap();
+        assert_eq!(init.sdp, "");
+        assert!(serde_json::from_str::<SessionDescriptionInit>(r#"{"type":"answer"}"#).is_err());
+        assert!(
+            serde_json::from_str::<SessionDescriptionInit>(r#"{"type":"x","sdp":""}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn ice_candidate_test() {
+        let json = r#"{"candidate":"candidate:1 1 udp 2122260223 192.168.99.58 45076 typ host","sdpMid":"0","sdpMLineIndex":0,"usernameFragment":null}"#;
+        let init: IceCandidateInit = serde_json::from_str(json).unwrap();
+        assert_eq!(init.sdp_mid.as_deref(), Some("0"));
+        assert_eq!(init.sdp_mline_index, Some(0));
+        let candidate = init.get_candidate().unwrap().unwrap();
+        assert_eq!(IceCandidateInit::new(Some(&candidate), "0", 0), init);
+        assert_eq!(serde_json::to_string(&init).unwrap(), json);
+
+        let init: IceCandidateInit =
+            serde_json::from_str(r#"{"candidate":"","sdpMid":"1"}"#).unwrap();
+        assert_eq!(init.get_candidate(), Ok(None));
+        assert_eq!(init.sdp_mline_index, None);
+        assert!(serde_json::from_str::<IceCandidateInit>(r#"{"sdpMLineIndex":-1}"#).is_err());
+    }
+}
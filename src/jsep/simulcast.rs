@@ -0,0 +1,471 @@
+// https://tools.ietf.org/html/rfc8851#section-4
+// https://tools.ietf.org/html/rfc8853#section-5
+
+/*
+    a=rid:<rid-id> <send|recv> [pt=<fmt>,...;<restriction>=<value>;...]
+    a=simulcast:<send|recv> <list> [<recv|send> <list>]
+
+    a list is ";" separated layers, each "," separated alternatives of
+    rid-ids, "~" in front of a paused one.
+
+    e.g.
+    a=rid:q send max-width=320;max-height=180
+    a=rid:h send
+    a=rid:f send
+    a=simulcast:send q;h;f
+
+    an answer receives the offered send layers as recv layers, with the
+    rids reversed, and sends the offered recv layers it has rids for.
+*/
+
+use crate::jsep::codec::Codec;
+use crate::jsep::{JsepError, Result};
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::MediaDescription;
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RidDirection {
+    Send,
+    Recv,
+}
+
+impl RidDirection {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            RidDirection::Send => "send",
+            RidDirection::Recv => "recv",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RidDirection> {
+        match name {
+            "send" => Some(RidDirection::Send),
+            "recv" => Some(RidDirection::Recv),
+            _ => None,
+        }
+    }
+
+    pub fn reverse(self) -> RidDirection {
+        match self {
+            RidDirection::Send => RidDirection::Recv,
+            RidDirection::Recv => RidDirection::Send,
+        }
+    }
+}
+
+fn invalid(name: &str, value: &str) -> JsepError {
+    JsepError::InvalidDescription {
+        reason: format!("{}:{}", name, value),
+    }
+}
+
+fn is_rid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// a=ridの一行．restrictionsはmax-widthなど，書かれた順．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Rid {
+    pub id: String,
+    pub direction: RidDirection,
+    pub payload_types: Vec<u8>,
+    pub restrictions: Vec<(String, String)>,
+}
+
+impl Rid {
+    pub fn new(id: &str, direction: RidDirection) -> Self {
+        Rid {
+            id: id.to_string(),
+            direction,
+            payload_types: vec![],
+            restrictions: vec![],
+        }
+    }
+
+    pub fn with_restriction(mut self, name: &str, value: &str) -> Self {
+        self.restrictions
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn get_restriction(&self, name: &str) -> Option<&str> {
+        self.restrictions
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Parses the value of an a=rid.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut fields = value.split_whitespace();
+        let id = fields
+            .next()
+            .filter(|v| is_rid_id(v))
+            .ok_or_else(|| invalid("rid", value))?;
+        let direction = fields
+            .next()
+            .and_then(RidDirection::from_name)
+            .ok_or_else(|| invalid("rid", value))?;
+        let mut rid = Rid::new(id, direction);
+        if let Some(params) = fields.next() {
+            for param in params.split(';').filter(|v| !v.is_empty()) {
+                let mut kv = param.splitn(2, '=');
+                let name = kv.next().unwrap_or_default();
+                let v = kv.next().ok_or_else(|| invalid("rid", value))?;
+                if name == "pt" {
+                    rid.payload_types = v
+                        .split(',')
+                        .map(|v| v.parse::<u8>())
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|_| invalid("rid", value))?;
+                } else {
+                    rid.restrictions.push((name.to_string(), v.to_string()));
+                }
+            }
+        }
+        if fields.next().is_some() {
+            return Err(invalid("rid", value));
+        }
+        Ok(rid)
+    }
+
+    /// Whether the layer can use one of `codecs`, any when no pt is written.
+    pub fn is_compatible(&self, codecs: &[Codec]) -> bool {
+        self.payload_types.is_empty()
+            || codecs
+                .iter()
+                .any(|v| self.payload_types.contains(&v.payload_type))
+    }
+}
+
+impl fmt::Display for Rid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.id, self.direction.get_name())?;
+        let mut params = vec![];
+        if !self.payload_types.is_empty() {
+            let pts: Vec<String> = self.payload_types.iter().map(|v| v.to_string()).collect();
+            params.push(format!("pt={}", pts.join(",")));
+        }
+        for (name, value) in &self.restrictions {
+            params.push(format!("{}={}", name, value));
+        }
+        if !params.is_empty() {
+            write!(f, " {}", params.join(";"))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SimulcastLayer {
+    pub rid: String,
+    pub paused: bool,
+}
+
+impl SimulcastLayer {
+    pub fn new(rid: &str) -> Self {
+        SimulcastLayer {
+            rid: rid.to_string(),
+            paused: false,
+        }
+    }
+}
+
+// 外側がlayer，内側がその代替．
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Simulcast {
+    pub send: Vec<Vec<SimulcastLayer>>,
+    pub recv: Vec<Vec<SimulcastLayer>>,
+}
+
+impl Simulcast {
+    pub fn is_empty(&self) -> bool {
+        self.send.is_empty() && self.recv.is_empty()
+    }
+
+    /// The send and recv layers swapped, as the peer sees them.
+    pub fn reverse(self) -> Simulcast {
+        Simulcast {
+            send: self.recv,
+            recv: self.send,
+        }
+    }
+
+    /// The first alternative of each layer in `direction`.
+    pub fn get_rids(&self, direction: RidDirection) -> Vec<&str> {
+        let layers = match direction {
+            RidDirection::Send => &self.send,
+            RidDirection::Recv => &self.recv,
+        };
+        layers
+            .iter()
+            .filter_map(|v| v.first())
+            .map(|v| v.rid.as_str())
+            .collect()
+    }
+
+    /// Parses the value of an a=simulcast.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut simulcast = Simulcast::default();
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        if fields.is_empty() || !fields.len().is_multiple_of(2) || fields.len() > 4 {
+            return Err(invalid("simulcast", value));
+        }
+        for pair in fields.chunks(2) {
+            let layers = match RidDirection::from_name(pair[0]) {
+                Some(RidDirection::Send) => &mut simulcast.send,
+                Some(RidDirection::Recv) => &mut simulcast.recv,
+                None => return Err(invalid("simulcast", value)),
+            };
+            if !layers.is_empty() {
+                return Err(invalid("simulcast", value));
+            }
+            for alternatives in pair[1].split(';') {
+                let mut layer = vec![];
+                for id in alternatives.split(',') {
+                    let paused = id.starts_with('~');
+                    let id = id.trim_start_matches('~');
+                    if !is_rid_id(id) {
+                        return Err(invalid("simulcast", value));
+                    }
+                    layer.push(SimulcastLayer {
+                        rid: id.to_string(),
+                        paused,
+                    });
+                }
+                layers.push(layer);
+            }
+        }
+        Ok(simulcast)
+    }
+}
+
+fn fmt_layers(layers: &[Vec<SimulcastLayer>]) -> String {
+    layers
+        .iter()
+        .map(|v| {
+            v.iter()
+                .map(|v| format!("{}{}", if v.paused { "~" } else { "" }, v.rid))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+impl fmt::Display for Simulcast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields = vec![];
+        if !self.send.is_empty() {
+            fields.push(format!("send {}", fmt_layers(&self.send)));
+        }
+        if !self.recv.is_empty() {
+            fields.push(format!("recv {}", fmt_layers(&self.recv)));
+        }
+        write!(f, "{}", fields.join(" "))
+    }
+}
+
+pub fn get_rids(media: &MediaDescription) -> Result<Vec<Rid>> {
+    media.get_attribute_values("rid").map(Rid::parse).collect()
+}
+
+/// The a=simulcast of the m= section, checked against its a=rid lines.
+pub fn get_simulcast(media: &MediaDescription) -> Result<Option<Simulcast>> {
+    let value = match media.get_attribute_values("simulcast").next() {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let simulcast = Simulcast::parse(value)?;
+    let rids = get_rids(media)?;
+    for (direction, layers) in &[
+        (RidDirection::Send, &simulcast.send),
+        (RidDirection::Recv, &simulcast.recv),
+    ] {
+        let known = layers.iter().flatten().all(|layer| {
+            rids.iter()
+                .any(|v| v.id == layer.rid && v.direction == *direction)
+        });
+        if !known {
+            return Err(invalid("simulcast", value));
+        }
+    }
+    Ok(Some(simulcast))
+}
+
+pub fn add_simulcast(media: &mut MediaDescription, rids: &[Rid], simulcast: &Simulcast) {
+    if simulcast.is_empty() {
+        return;
+    }
+    for rid in rids {
+        media
+            .attributes
+            .push(Attribute::new("rid", &rid.to_string()));
+    }
+    media
+        .attributes
+        .push(Attribute::new("simulcast", &simulcast.to_string()));
+}
+
+/// The simulcast of an offer with the local `rids`, one layer each.
+pub fn offer_simulcast(rids: &[Rid]) -> Simulcast {
+    let layers = |direction: RidDirection| {
+        rids.iter()
+            .filter(|v| v.direction == direction)
+            .map(|v| vec![SimulcastLayer::new(&v.id)])
+            .collect()
+    };
+    Simulcast {
+        send: layers(RidDirection::Send),
+        recv: layers(RidDirection::Recv),
+    }
+}
+
+/// The rids and simulcast answering `offered`. The offered send layers
+/// are received when `recv`, with the pt of `codecs`. The offered recv
+/// layers are sent for the `local` send rids.
+pub fn answer_simulcast(
+    offered: &Simulcast,
+    offered_rids: &[Rid],
+    local: &[Rid],
+    codecs: &[Codec],
+    send: bool,
+    recv: bool,
+) -> (Vec<Rid>, Simulcast) {
+    let mut rids: Vec<Rid> = vec![];
+    let mut simulcast = Simulcast::default();
+    if recv {
+        for alternatives in &offered.send {
+            let layer: Vec<SimulcastLayer> = alternatives
+                .iter()
+                .filter(|layer| {
+                    offered_rids.iter().any(|v| {
+                        v.id == layer.rid
+                            && v.direction == RidDirection::Send
+                            && v.is_compatible(codecs)
+                    })
+                })
+                .cloned()
+                .collect();
+            for layer in &layer {
+                let offered = offered_rids
+                    .iter()
+                    .find(|v| v.id == layer.rid && v.direction == RidDirection::Send)
+                    .unwrap();
+                rids.push(Rid {
+                    id: offered.id.clone(),
+                    direction: RidDirection::Recv,
+                    payload_types: offered
+                        .payload_types
+                        .iter()
+                        .filter(|pt| codecs.iter().any(|v| v.payload_type == **pt))
+                        .cloned()
+                        .collect(),
+                    restrictions: offered.restrictions.clone(),
+                });
+            }
+            if !layer.is_empty() {
+                simulcast.recv.push(layer);
+            }
+        }
+    }
+    if send {
+        for alternatives in &offered.recv {
+            let layer: Vec<SimulcastLayer> = alternatives
+                .iter()
+                .filter(|layer| {
+                    local
+                        .iter()
+                        .any(|v| v.id == layer.rid && v.direction == RidDirection::Send)
+                })
+                .cloned()
+                .collect();
+            for layer in &layer {
+                rids.extend(
+                    local
+                        .iter()
+                        .filter(|v| v.id == layer.rid && v.direction == RidDirection::Send)
+                        .cloned(),
+                );
+            }
+            if !layer.is_empty() {
+                simulcast.send.push(layer);
+            }
+        }
+    }
+    (rids, simulcast)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rid_test() {
+        let rid = Rid::parse("q send pt=96,97;max-width=320;max-height=180").unwrap();
+        assert_eq!(rid.id, "q");
+        assert_eq!(rid.direction, RidDirection::Send);
+        assert_eq!(rid.payload_types, vec![96, 97]);
+        assert_eq!(rid.get_restriction("max-width"), Some("320"));
+        assert_eq!(
+            rid.to_string(),
+            "q send pt=96,97;max-width=320;max-height=180"
+        );
+        assert_eq!(
+            Rid::parse("f recv").unwrap(),
+            Rid::new("f", RidDirection::Recv)
+        );
+        assert!(Rid::parse("q").is_err());
+        assert!(Rid::parse("q sendrecv").is_err());
+        assert!(Rid::parse("q! send").is_err());
+        assert!(Rid::parse("q send pt=x").is_err());
+        assert!(Rid::parse("q send max-width").is_err());
+    }
+
+    #[test]
+    fn simulcast_test() {
+        let simulcast = Simulcast::parse("send q;~h,h2;f recv r").unwrap();
+        assert_eq!(simulcast.send.len(), 3);
+        assert!(simulcast.send[1][0].paused);
+        assert_eq!(simulcast.send[1][1].rid, "h2");
+        assert_eq!(simulcast.get_rids(RidDirection::Send), vec!["q", "h", "f"]);
+        assert_eq!(simulcast.get_rids(RidDirection::Recv), vec!["r"]);
+        assert_eq!(simulcast.to_string(), "send q;~h,h2;f recv r");
+        let reversed = simulcast.reverse();
+        assert_eq!(reversed.to_string(), "send r recv q;~h,h2;f");
+
+        assert!(Simulcast::parse("send").is_err());
+        assert!(Simulcast::parse("send q send h").is_err());
+        assert!(Simulcast::parse("both q").is_err());
+        assert!(Simulcast::parse("send q;;h").is_err());
+    }
+
+    #[test]
+    fn answer_simulcast_test() {
+        let offered = Simulcast::parse("send q;h,h2;f recv r").unwrap();
+        let offered_rids = vec![
+            Rid::parse("q send pt=96;max-width=320").unwrap(),
+            Rid::parse("h send pt=120").unwrap(),
+            Rid::parse("h2 send").unwrap(),
+            Rid::parse("f send").unwrap(),
+            Rid::parse("r recv").unwrap(),
+        ];
+        let codecs = vec![Codec::new(96, "VP8", 90000, None)];
+        let (rids, simulcast) =
+            answer_simulcast(&offered, &offered_rids, &[], &codecs, false, true);
+        assert_eq!(simulcast.to_string(), "recv q;h2;f");
+        assert_eq!(rids[0].to_string(), "q recv pt=96;max-width=320");
+        assert_eq!(rids.len(), 3);
+
+        let local = vec![Rid::new("r", RidDirection::Send)];
+        let (rids, simulcast) =
+            answer_simulcast(&offered, &offered_rids, &local, &codecs, true, false);
+        assert_eq!(simulcast.to_string(), "send r");
+        assert_eq!(rids, local);
+    }
+}
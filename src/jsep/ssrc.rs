@@ -0,0 +1,264 @@
+// https://tools.ietf.org/html/rfc5576#section-4
+// https://tools.ietf.org/html/rfc8829#section-5.2.1
+
+/*
+    a=ssrc-group:SIM <ssrc> <ssrc> ...          legacy simulcast, low to high
+    a=ssrc-group:FID <ssrc> <rtx ssrc>          RTX of the first
+    a=ssrc:<ssrc> cname:<cname>
+    a=ssrc:<ssrc> msid:<stream id> <track id>
+
+    the SSRCs of the sender are written while it sends without RIDs. the
+    ones of the peer are kept to demux its packets.
+*/
+
+use crate::jsep::msid::Msid;
+use crate::jsep::{JsepError, Result};
+use crate::sdp::attribute::Attribute;
+use crate::sdp::media::MediaDescription;
+
+use std::fmt;
+
+fn invalid(name: &str, value: &str) -> JsepError {
+    JsepError::InvalidDescription {
+        reason: format!("{}:{}", name, value),
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SsrcAttribute {
+    pub ssrc: u32,
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl SsrcAttribute {
+    pub fn new(ssrc: u32, name: &str, value: Option<&str>) -> Self {
+        SsrcAttribute {
+            ssrc,
+            name: name.to_string(),
+            value: value.map(|v| v.to_string()),
+        }
+    }
+
+    /// Parses the value of an a=ssrc.
+    pub fn parse(value: &str) -> Result<Self> {
+        let (ssrc, attribute) = value
+            .split_once(' ')
+            .ok_or_else(|| invalid("ssrc", value))?;
+        let ssrc = ssrc.parse::<u32>().map_err(|_| invalid("ssrc", value))?;
+        let (name, v) = match attribute.trim_start().split_once(':') {
+            Some((name, v)) => (name, Some(v)),
+            None => (attribute.trim_start(), None),
+        };
+        if name.is_empty() {
+            return Err(invalid("ssrc", value));
+        }
+        Ok(SsrcAttribute::new(ssrc, name, v))
+    }
+}
+
+impl fmt::Display for SsrcAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.ssrc, self.name)?;
+        if let Some(ref value) = self.value {
+            write!(f, ":{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SsrcGroup {
+    pub semantics: String,
+    pub ssrcs: Vec<u32>,
+}
+
+impl SsrcGroup {
+    pub fn new(semantics: &str, ssrcs: Vec<u32>) -> Self {
+        SsrcGroup {
+            semantics: semantics.to_string(),
+            ssrcs,
+        }
+    }
+
+    /// Parses the value of an a=ssrc-group.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut fields = value.split_whitespace();
+        let semantics = fields.next().ok_or_else(|| invalid("ssrc-group", value))?;
+        let ssrcs = fields
+            .map(|v| v.parse::<u32>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| invalid("ssrc-group", value))?;
+        if ssrcs.is_empty() {
+            return Err(invalid("ssrc-group", value));
+        }
+        Ok(SsrcGroup::new(semantics, ssrcs))
+    }
+}
+
+impl fmt::Display for SsrcGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.semantics)?;
+        for ssrc in &self.ssrcs {
+            write!(f, " {}", ssrc)?;
+        }
+        Ok(())
+    }
+}
+
+// 一つのlayerのSSRCと，そのRTXのSSRC．
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SsrcLayer {
+    pub ssrc: u32,
+    pub rtx_ssrc: Option<u32>,
+}
+
+impl SsrcLayer {
+    pub fn new(ssrc: u32, rtx_ssrc: Option<u32>) -> Self {
+        SsrcLayer { ssrc, rtx_ssrc }
+    }
+}
+
+pub fn get_ssrc_attributes(media: &MediaDescription) -> Result<Vec<SsrcAttribute>> {
+    media
+        .get_attribute_values("ssrc")
+        .map(SsrcAttribute::parse)
+        .collect()
+}
+
+pub fn get_ssrc_groups(media: &MediaDescription) -> Result<Vec<SsrcGroup>> {
+    media
+        .get_attribute_values("ssrc-group")
+        .map(SsrcGroup::parse)
+        .collect()
+}
+
+/// The layers of the SIM group, or each SSRC not an RTX one. The RTX SSRCs
+/// are from the FID groups.
+pub fn get_ssrc_layers(media: &MediaDescription) -> Result<Vec<SsrcLayer>> {
+    let groups = get_ssrc_groups(media)?;
+    let fids: Vec<(u32, u32)> = groups
+        .iter()
+        .filter(|v| v.semantics == "FID" && v.ssrcs.len() == 2)
+        .map(|v| (v.ssrcs[0], v.ssrcs[1]))
+        .collect();
+    let mut ssrcs: Vec<u32> = match groups.iter().find(|v| v.semantics == "SIM") {
+        Some(v) => v.ssrcs.clone(),
+        None => vec![],
+    };
+    if ssrcs.is_empty() {
+        for attribute in get_ssrc_attributes(media)? {
+            let ssrc = attribute.ssrc;
+            if !ssrcs.contains(&ssrc) && !fids.iter().any(|(_, rtx)| *rtx == ssrc) {
+                ssrcs.push(ssrc);
+            }
+        }
+    }
+    Ok(ssrcs
+        .into_iter()
+        .map(|ssrc| {
+            let rtx_ssrc = fids.iter().find(|(v, _)| *v == ssrc).map(|(_, v)| *v);
+            SsrcLayer::new(ssrc, rtx_ssrc)
+        })
+        .collect())
+}
+
+/// The cname of the first SSRC with one.
+pub fn get_cname(media: &MediaDescription) -> Option<String> {
+    get_ssrc_attributes(media)
+        .ok()?
+        .into_iter()
+        .find(|v| v.name == "cname")?
+        .value
+}
+
+/// Writes the groups, then the cname and msids of each SSRC.
+pub fn add_ssrcs(media: &mut MediaDescription, layers: &[SsrcLayer], cname: &str, msids: &[Msid]) {
+    if layers.len() > 1 {
+        let ssrcs = layers.iter().map(|v| v.ssrc).collect();
+        media.attributes.push(Attribute::new(
+            "ssrc-group",
+            &SsrcGroup::new("SIM", ssrcs).to_string(),
+        ));
+    }
+    for layer in layers {
+        if let Some(rtx_ssrc) = layer.rtx_ssrc {
+            media.attributes.push(Attribute::new(
+                "ssrc-group",
+                &SsrcGroup::new("FID", vec![layer.ssrc, rtx_ssrc]).to_string(),
+            ));
+        }
+    }
+    let ssrcs = layers
+        .iter()
+        .flat_map(|v| Some(v.ssrc).into_iter().chain(v.rtx_ssrc));
+    for ssrc in ssrcs {
+        let mut attributes = vec![SsrcAttribute::new(ssrc, "cname", Some(cname))];
+        for msid in msids {
+            attributes.push(SsrcAttribute::new(ssrc, "msid", Some(&msid.to_string())));
+        }
+        for attribute in attributes {
+            media
+                .attributes
+                .push(Attribute::new("ssrc", &attribute.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sdp::session::test::CHROME_OFFER;
+    use crate::sdp::session::SessionDescription;
+
+    #[test]
+    fn ssrc_test() {
+        let attribute = SsrcAttribute::parse("1045175675 cname:nkLsHqZl0yPvNv4B").unwrap();
+        assert_eq!(
+            attribute,
+            SsrcAttribute::new(1045175675, "cname", Some("nkLsHqZl0yPvNv4B"))
+        );
+        assert_eq!(attribute.to_string(), "1045175675 cname:nkLsHqZl0yPvNv4B");
+        assert_eq!(SsrcAttribute::parse("1 x").unwrap().value, None);
+        assert!(SsrcAttribute::parse("x cname:a").is_err());
+        assert!(SsrcAttribute::parse("1").is_err());
+
+        let group = SsrcGroup::parse("FID 1045175675 3822520862").unwrap();
+        assert_eq!(group.ssrcs, vec![1045175675, 3822520862]);
+        assert_eq!(group.to_string(), "FID 1045175675 3822520862");
+        assert!(SsrcGroup::parse("FID").is_err());
+        assert!(SsrcGroup::parse("FID x").is_err());
+
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        assert_eq!(
+            get_ssrc_layers(&session.media[0]).unwrap(),
+            vec![SsrcLayer::new(2228110218, None)]
+        );
+        assert_eq!(
+            get_ssrc_layers(&session.media[1]).unwrap(),
+            vec![SsrcLayer::new(1045175675, Some(3822520862))]
+        );
+        assert_eq!(
+            get_cname(&session.media[1]).as_deref(),
+            Some("nkLsHqZl0yPvNv4B")
+        );
+    }
+
+    #[test]
+    fn add_ssrcs_test() {
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        let mut media = session.media[1].clone();
+        media.attributes.clear();
+        let layers = vec![
+            SsrcLayer::new(1, Some(2)),
+            SsrcLayer::new(3, Some(4)),
+            SsrcLayer::new(5, None),
+        ];
+        add_ssrcs(&mut media, &layers, "cname", &[Msid::new(Some("s"), "t")]);
+        let groups: Vec<&str> = media.get_attribute_values("ssrc-group").collect();
+        assert_eq!(groups, vec!["SIM 1 3 5", "FID 1 2", "FID 3 4"]);
+        assert_eq!(media.get_attribute_values("ssrc").count(), 10);
+        assert_eq!(get_ssrc_layers(&media).unwrap(), layers);
+        assert_eq!(get_cname(&media).as_deref(), Some("cname"));
+    }
+}
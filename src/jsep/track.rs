@@ -0,0 +1,37 @@
+// https://www.w3.org/TR/mediacapture-streams/#mediastreamtrack
+
+/*
+    a track is what a sender sends and a receiver gives. the media of it
+    are from the application, only the id and kind are negotiated.
+*/
+
+use crate::jsep::msid::generate_id;
+use crate::sdp::media::MediaKind;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MediaStreamTrack {
+    id: String,
+    kind: MediaKind,
+}
+
+impl MediaStreamTrack {
+    pub fn new(kind: MediaKind) -> Self {
+        MediaStreamTrack::with_id(kind, &generate_id())
+    }
+
+    pub fn with_id(kind: MediaKind, id: &str) -> Self {
+        MediaStreamTrack {
+            id: id.to_string(),
+            kind,
+        }
+    }
+
+    /// The track id of a=msid.
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_kind(&self) -> MediaKind {
+        self.kind
+    }
+}
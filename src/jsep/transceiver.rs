@@ -0,0 +1,174 @@
+// https://tools.ietf.org/html/rfc8829#section-3.4.1
+
+/*
+    a transceiver is one m= section, created by add_transceiver, add_track
+    or by a remote offer with an m= line none is associated with. it is a
+    sender and a receiver of the same mid.
+
+    mid           set by create_offer, or from the remote offer
+    mline_index   set when a description with it is applied
+    direction     what the application wants
+    current       what the last answer says, None before
+
+    add_track takes a transceiver of the kind that has never sent and has
+    no track, and makes it send. the m= section of a stopped transceiver
+    rejected by both sides is recycled by a new one in the next offer.
+*/
+
+use crate::jsep::capabilities::CapabilityRegistry;
+use crate::jsep::codec::Codec;
+use crate::jsep::extmap::{get_header_extension_map, Extmap};
+use crate::jsep::receiver::RtpReceiver;
+use crate::jsep::sender::RtpSender;
+use crate::jsep::simulcast::{Rid, Simulcast};
+use crate::rtp::header_extension::HeaderExtensionMap;
+use crate::sdp::media::{Direction, MediaKind};
+
+// m= sectionごとの状態．applicationはdata channelで，codecを持たない．
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RtpTransceiver {
+    pub(crate) mid: Option<String>,
+    pub(crate) mline_index: Option<usize>,
+    pub(crate) kind: MediaKind,
+    pub(crate) direction: Direction,
+    pub(crate) current_direction: Option<Direction>,
+    pub(crate) codecs: Vec<Codec>,
+    pub(crate) negotiated_codecs: Vec<Codec>,
+    pub(crate) extmaps: Vec<Extmap>,
+    pub(crate) negotiated_extmaps: Vec<Extmap>,
+    pub(crate) rids: Vec<Rid>,
+    pub(crate) sender: RtpSender,
+    pub(crate) receiver: RtpReceiver,
+    pub(crate) simulcast: Option<Simulcast>,
+    pub(crate) rtcp_mux: bool,
+    pub(crate) stopped: bool,
+}
+
+impl RtpTransceiver {
+    /// All the codecs and extensions of the kind, sent or received.
+    pub(crate) fn new(
+        kind: MediaKind,
+        direction: Direction,
+        capabilities: &CapabilityRegistry,
+    ) -> Self {
+        let capabilities = capabilities.get_capabilities(kind, Direction::Inactive);
+        let (codecs, extmaps) = (capabilities.codecs, capabilities.extmaps);
+        let rtx = codecs.iter().any(|v| v.is_rtx());
+        RtpTransceiver {
+            mid: None,
+            mline_index: None,
+            kind,
+            direction,
+            current_direction: None,
+            codecs,
+            negotiated_codecs: vec![],
+            extmaps,
+            negotiated_extmaps: vec![],
+            rids: vec![],
+            sender: RtpSender::new(kind, rtx),
+            receiver: RtpReceiver::new(kind),
+            simulcast: None,
+            rtcp_mux: false,
+            stopped: false,
+        }
+    }
+
+    pub fn get_mid(&self) -> Option<&str> {
+        self.mid.as_deref()
+    }
+
+    pub fn get_mline_index(&self) -> Option<usize> {
+        self.mline_index
+    }
+
+    pub fn get_kind(&self) -> MediaKind {
+        self.kind
+    }
+
+    pub fn get_direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Applies from the next offer or answer.
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn get_current_direction(&self) -> Option<Direction> {
+        self.current_direction
+    }
+
+    /// The codecs offered or answered with, in order of preference.
+    pub fn get_codecs(&self) -> &[Codec] {
+        &self.codecs
+    }
+
+    pub fn set_codecs(&mut self, codecs: Vec<Codec>) {
+        self.codecs = codecs;
+    }
+
+    /// The codecs of the last answer, with the payload types to send with.
+    pub fn get_negotiated_codecs(&self) -> &[Codec] {
+        &self.negotiated_codecs
+    }
+
+    /// The header extensions offered or answered with.
+    pub fn get_extmaps(&self) -> &[Extmap] {
+        &self.extmaps
+    }
+
+    pub fn set_extmaps(&mut self, extmaps: Vec<Extmap>) {
+        self.extmaps = extmaps;
+    }
+
+    /// The header extensions of the last answer.
+    pub fn get_negotiated_extmaps(&self) -> &[Extmap] {
+        &self.negotiated_extmaps
+    }
+
+    /// The ids of the last answer for the RTP packets of this m= section.
+    pub fn get_header_extension_map(&self) -> HeaderExtensionMap {
+        get_header_extension_map(&self.negotiated_extmaps)
+    }
+
+    pub fn get_sender(&self) -> &RtpSender {
+        &self.sender
+    }
+
+    pub fn get_sender_mut(&mut self) -> &mut RtpSender {
+        &mut self.sender
+    }
+
+    pub fn get_receiver(&self) -> &RtpReceiver {
+        &self.receiver
+    }
+
+    /// The simulcast layers, offered as a=rid and a=simulcast. An answer
+    /// sends the ones the offer asks for.
+    pub fn get_rids(&self) -> &[Rid] {
+        &self.rids
+    }
+
+    pub fn set_rids(&mut self, rids: Vec<Rid>) {
+        self.rids = rids;
+    }
+
+    /// The layers of the last answer, send the ones this side sends.
+    pub fn get_simulcast(&self) -> Option<&Simulcast> {
+        self.simulcast.as_ref()
+    }
+
+    /// Whether the last answer muxes RTCP with RTP.
+    pub fn is_rtcp_mux(&self) -> bool {
+        self.rtcp_mux
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// The m= section is rejected in the next offer or answer.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+}
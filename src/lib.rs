@@ -1,15 +1,64 @@
+// failure_derive expands `impl Fail` inside a named const item.
+#![allow(non_local_definitions)]
+// the packet codecs need only core and alloc.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 use failure::Fail;
 
+#[cfg(feature = "std")]
+pub mod cc;
+#[cfg(feature = "std")]
+pub mod datachannel;
+#[cfg(feature = "std")]
+pub mod dtls;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod ice;
+#[cfg(feature = "std")]
+pub mod jsep;
 pub mod octets;
+#[cfg(feature = "std")]
+pub mod ortc;
 pub mod rtcp;
 pub mod rtp;
+#[cfg(feature = "std")]
+pub mod roq;
+#[cfg(feature = "std")]
+pub mod runtime;
+#[cfg(feature = "std")]
+pub mod sansio;
+#[cfg(feature = "std")]
+pub mod sctp;
+#[cfg(feature = "std")]
 pub mod sdp;
+#[cfg(feature = "std")]
+pub mod sframe;
+#[cfg(feature = "std")]
+pub mod sfu;
+#[cfg(feature = "std")]
+pub mod srtp;
+#[cfg(feature = "std")]
+pub mod stun;
+#[cfg(feature = "std")]
+pub mod time;
+#[cfg(feature = "std")]
+pub mod turn;
+#[cfg(feature = "std")]
+pub mod whep;
+#[cfg(feature = "std")]
+pub mod whip;
 
+#[cfg(feature = "std")]
 pub mod rtcpeerconnection;
 
-pub type Result<T> = std::result::Result<T, OctetsError>;
+pub type Result<T> = core::result::Result<T, OctetsError>;
 //pub type Result<T> = std::result::Result<T, WebrtcError>;
 
+#[cfg(feature = "std")]
 #[derive(Fail, Debug)]
 pub enum WebrtcError {
     #[fail(display = "Octets manipulate failed: {:?}", error)]
@@ -18,26 +67,155 @@ pub enum WebrtcError {
     RtpError { error: rtp::RtpError },
     #[fail(display = "RTCP failed: {:?}", error)]
     RtcpError { error: rtcp::RtcpError },
+    #[fail(display = "DTLS failed: {:?}", error)]
+    DtlsError { error: dtls::DtlsError },
+    #[fail(display = "SRTP failed: {:?}", error)]
+    SrtpError { error: srtp::SrtpError },
+    #[fail(display = "STUN failed: {:?}", error)]
+    StunError { error: stun::StunError },
+    #[fail(display = "ICE failed: {:?}", error)]
+    IceError { error: ice::IceError },
+    #[fail(display = "TURN failed: {:?}", error)]
+    TurnError { error: turn::TurnError },
+    #[fail(display = "SFrame failed: {:?}", error)]
+    SframeError { error: sframe::SframeError },
+    #[fail(display = "SCTP failed: {:?}", error)]
+    SctpError { error: sctp::SctpError },
+    #[fail(display = "Data channel failed: {:?}", error)]
+    DataChannelError { error: datachannel::DataChannelError },
+    #[fail(display = "SDP failed: {:?}", error)]
+    SdpError { error: sdp::SdpError },
+    #[fail(display = "JSEP failed: {:?}", error)]
+    JsepError { error: jsep::JsepError },
+    #[fail(display = "ORTC failed: {:?}", error)]
+    OrtcError { error: ortc::OrtcError },
+    #[fail(display = "RoQ failed: {:?}", error)]
+    RoqError { error: roq::RoqError },
+    #[fail(display = "WHIP failed: {:?}", error)]
+    WhipError { error: whip::WhipError },
+    #[fail(display = "I/O failed: {}", error)]
+    IoError { error: std::io::Error },
 }
 
+#[cfg(feature = "std")]
 impl From<OctetsError> for WebrtcError {
     fn from(error: OctetsError) -> Self {
         WebrtcError::OctetsError { error }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<rtp::RtpError> for WebrtcError {
     fn from(error: rtp::RtpError) -> Self {
         WebrtcError::RtpError { error }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<rtcp::RtcpError> for WebrtcError {
     fn from(error: rtcp::RtcpError) -> Self {
         WebrtcError::RtcpError { error }
     }
 }
 
+#[cfg(feature = "std")]
+impl From<dtls::DtlsError> for WebrtcError {
+    fn from(error: dtls::DtlsError) -> Self {
+        WebrtcError::DtlsError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<srtp::SrtpError> for WebrtcError {
+    fn from(error: srtp::SrtpError) -> Self {
+        WebrtcError::SrtpError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<stun::StunError> for WebrtcError {
+    fn from(error: stun::StunError) -> Self {
+        WebrtcError::StunError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ice::IceError> for WebrtcError {
+    fn from(error: ice::IceError) -> Self {
+        WebrtcError::IceError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<turn::TurnError> for WebrtcError {
+    fn from(error: turn::TurnError) -> Self {
+        WebrtcError::TurnError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<sframe::SframeError> for WebrtcError {
+    fn from(error: sframe::SframeError) -> Self {
+        WebrtcError::SframeError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<sctp::SctpError> for WebrtcError {
+    fn from(error: sctp::SctpError) -> Self {
+        WebrtcError::SctpError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<datachannel::DataChannelError> for WebrtcError {
+    fn from(error: datachannel::DataChannelError) -> Self {
+        WebrtcError::DataChannelError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<sdp::SdpError> for WebrtcError {
+    fn from(error: sdp::SdpError) -> Self {
+        WebrtcError::SdpError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<jsep::JsepError> for WebrtcError {
+    fn from(error: jsep::JsepError) -> Self {
+        WebrtcError::JsepError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ortc::OrtcError> for WebrtcError {
+    fn from(error: ortc::OrtcError) -> Self {
+        WebrtcError::OrtcError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<roq::RoqError> for WebrtcError {
+    fn from(error: roq::RoqError) -> Self {
+        WebrtcError::RoqError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<whip::WhipError> for WebrtcError {
+    fn from(error: whip::WhipError) -> Self {
+        WebrtcError::WhipError { error }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for WebrtcError {
+    fn from(error: std::io::Error) -> Self {
+        WebrtcError::IoError { error }
+    }
+}
+
 /// A Octets error.
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(C)]
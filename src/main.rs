@@ -1,6 +1,3 @@
-use std::io::Write;
-use std::net::{TcpListener, TcpStream};
-
 //use webrtc::packet::{RtpPacket};
 
 fn main() {
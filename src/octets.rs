@@ -26,13 +26,18 @@
 // SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 /// Zero-copy abstraction for parsing and constructing network packets.
-use std::mem;
-use std::ptr;
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr;
 
 //use crate::Error;
 use crate::OctetsError;
 use crate::Result;
-use std::convert::AsMut;
+use core::convert::AsMut;
+
+#[cfg(feature = "std")]
+pub mod pool;
 
 macro_rules! peek_u {
     ($b:expr, $ty:ty, $len:expr) => {{
@@ -67,6 +72,8 @@ macro_rules! get_u {
 
 macro_rules! put_u {
     ($b:expr, $ty:ty, $v:expr, $len:expr) => {{
+        $b.reserve($len);
+
         let dst = &mut $b.buf[$b.off..];
 
         if dst.len() < $len {
@@ -86,6 +93,39 @@ macro_rules! put_u {
     }};
 }
 
+// with_sliceの固定長のsliceか，with_vecの書くと伸びるVec．
+#[derive(Debug)]
+enum Buffer<'a> {
+    Slice(&'a mut [u8]),
+    Vec(&'a mut Vec<u8>),
+}
+
+impl<'a> Deref for Buffer<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Buffer::Slice(v) => v,
+            Buffer::Vec(v) => v,
+        }
+    }
+}
+
+impl<'a> DerefMut for Buffer<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Buffer::Slice(v) => v,
+            Buffer::Vec(v) => v,
+        }
+    }
+}
+
+impl<'a> PartialEq for Buffer<'a> {
+    fn eq(&self, other: &Buffer<'a>) -> bool {
+        **self == **other
+    }
+}
+
 /// A zero-copy mutable byte buffer.
 ///
 /// `Octets` wraps an in-memory buffer of bytes and provides utility functions
@@ -96,9 +136,12 @@ macro_rules! put_u {
 /// Additionally, an offset (initially set to the start of the buffer) is
 /// incremented as bytes are read from / written to the buffer, to allow for
 /// sequential operations.
+///
+/// An `Octets` created with `with_vec` grows its `Vec` when written past the
+/// end instead, so the length needs not be known beforehand.
 #[derive(Debug, PartialEq)]
 pub struct Octets<'a> {
-    buf: &'a mut [u8],
+    buf: Buffer<'a>,
     off: usize,
 }
 
@@ -107,8 +150,35 @@ impl<'a> Octets<'a> {
     ///
     /// Since there's no copy, the input slice needs to be mutable to allow
     /// modifications.
-    pub fn with_slice(buf: &'a mut [u8]) -> Octets {
-        Octets { buf, off: 0 }
+    pub fn with_slice(buf: &'a mut [u8]) -> Octets<'a> {
+        Octets {
+            buf: Buffer::Slice(buf),
+            off: 0,
+        }
+    }
+
+    /// Creates a growable `Octets` from the given vector. Writing past its
+    /// end appends to it, an empty vector ends up with what was written.
+    pub fn with_vec(buf: &'a mut Vec<u8>) -> Octets<'a> {
+        Octets {
+            buf: Buffer::Vec(buf),
+            off: 0,
+        }
+    }
+
+    /// Whether it grows when written past the end.
+    pub fn is_growable(&self) -> bool {
+        matches!(self.buf, Buffer::Vec(_))
+    }
+
+    /// Makes room for `len` bytes from the current offset, when growable.
+    fn reserve(&mut self, len: usize) {
+        if let Buffer::Vec(ref mut v) = self.buf {
+            let end = self.off.saturating_add(len);
+            if v.len() < end {
+                v.resize(end, 0);
+            }
+        }
     }
 
     /// Reads an unsigned 8-bit integer from the current offset and advances
@@ -215,6 +285,8 @@ impl<'a> Octets<'a> {
     /// Writes an unsigned variable-length integer of the specified length, in
     /// network byte-order at the current offset and advances the buffer.
     pub fn put_varint_with_len(&mut self, v: u64, len: usize) -> Result<()> {
+        self.reserve(len);
+
         if self.cap() < len {
             return Err(OctetsError::BufferTooShort);
         }
@@ -247,15 +319,12 @@ impl<'a> Octets<'a> {
 
     /// Reads `len` bytes from the current offset without copying and advances
     /// the buffer.
-    pub fn get_bytes(&mut self, len: usize) -> Result<Octets> {
+    pub fn get_bytes(&mut self, len: usize) -> Result<Octets<'_>> {
         if self.cap() < len {
             return Err(OctetsError::BufferTooShort);
         }
 
-        let out = Octets {
-            buf: &mut self.buf[self.off..self.off + len],
-            off: 0,
-        };
+        let out = Octets::with_slice(&mut self.buf[self.off..self.off + len]);
 
         self.off += len;
 
@@ -264,7 +333,7 @@ impl<'a> Octets<'a> {
 
     /// Reads `len` bytes from the current offset without copying and advances
     /// the buffer, where `len` is an unsigned 8-bit integer prefix.
-    pub fn get_bytes_with_u8_length(&mut self) -> Result<Octets> {
+    pub fn get_bytes_with_u8_length(&mut self) -> Result<Octets<'_>> {
         let len = self.get_u8()?;
         self.get_bytes(len as usize)
     }
@@ -272,7 +341,7 @@ impl<'a> Octets<'a> {
     /// Reads `len` bytes from the current offset without copying and advances
     /// the buffer, where `len` is an unsigned 16-bit integer prefix in network
     /// byte-order.
-    pub fn get_bytes_with_u16_length(&mut self) -> Result<Octets> {
+    pub fn get_bytes_with_u16_length(&mut self) -> Result<Octets<'_>> {
         let len = self.get_u16()?;
         self.get_bytes(len as usize)
     }
@@ -280,22 +349,19 @@ impl<'a> Octets<'a> {
     /// Reads `len` bytes from the current offset without copying and advances
     /// the buffer, where `len` is an unsigned variable-length integer prefix
     /// in network byte-order.
-    pub fn get_bytes_with_varint_length(&mut self) -> Result<Octets> {
+    pub fn get_bytes_with_varint_length(&mut self) -> Result<Octets<'_>> {
         let len = self.get_varint()?;
         self.get_bytes(len as usize)
     }
 
     /// Reads `len` bytes from the current offset without copying and without
     /// advancing the buffer.
-    pub fn peek_bytes(&mut self, len: usize) -> Result<Octets> {
+    pub fn peek_bytes(&mut self, len: usize) -> Result<Octets<'_>> {
         if self.cap() < len {
             return Err(OctetsError::BufferTooShort);
         }
 
-        let out = Octets {
-            buf: &mut self.buf[self.off..self.off + len],
-            off: 0,
-        };
+        let out = Octets::with_slice(&mut self.buf[self.off..self.off + len]);
 
         Ok(out)
     }
@@ -305,6 +371,8 @@ impl<'a> Octets<'a> {
     pub fn put_bytes(&mut self, v: &[u8]) -> Result<()> {
         let len = v.len();
 
+        self.reserve(len);
+
         if self.cap() < len {
             return Err(OctetsError::BufferTooShort);
         }
@@ -321,22 +389,24 @@ impl<'a> Octets<'a> {
     }
 
     /// Splits the buffer in two at the given absolute offset.
-    pub fn split_at(&mut self, off: usize) -> Result<(Octets, Octets)> {
+    pub fn split_at(&mut self, off: usize) -> Result<(Octets<'_>, Octets<'_>)> {
         if self.len() < off {
             return Err(OctetsError::BufferTooShort);
         }
 
         let (left, right) = self.buf.split_at_mut(off);
 
-        let first = Octets { buf: left, off: 0 };
+        let first = Octets::with_slice(left);
 
-        let last = Octets { buf: right, off: 0 };
+        let last = Octets::with_slice(right);
 
         Ok((first, last))
     }
 
     /// Returns a slice of `len` elements from the current offset.
     pub fn slice(&'a mut self, len: usize) -> Result<&'a mut [u8]> {
+        self.reserve(len);
+
         if len > self.cap() {
             return Err(OctetsError::BufferTooShort);
         }
@@ -364,6 +434,11 @@ impl<'a> Octets<'a> {
         self.buf.len()
     }
 
+    /// Returns `true` if the buffer has zero length.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
     /// Returns the current offset of the buffer.
     pub fn off(&self) -> usize {
         self.off
@@ -615,6 +690,7 @@ mod tests {
 
     #[test]
     #[should_panic]
+    #[allow(clippy::legacy_numeric_constants)]
     fn varint_too_large() {
         let mut d = [0; 3];
         let mut b = Octets::with_slice(&mut d);
@@ -812,4 +888,42 @@ mod tests {
             assert!(b.slice_last(11).is_err());
         }
     }
+
+    #[test]
+    fn with_vec() {
+        let mut d = Vec::new();
+
+        {
+            let mut b = Octets::with_vec(&mut d);
+            assert!(b.is_growable());
+            assert_eq!(b.cap(), 0);
+
+            assert!(b.put_u8(1).is_ok());
+            assert!(b.put_u16(0x203).is_ok());
+            assert!(b.put_u24(0x40506).is_ok());
+            assert!(b.put_varint(151_288_809_941_952_652).is_ok());
+            assert!(b.put_bytes(b"hello").is_ok());
+            assert_eq!(b.off(), 19);
+            assert_eq!(b.cap(), 0);
+            assert_eq!(b.slice(2), Ok(&mut [0, 0][..]));
+        }
+        assert_eq!(d.len(), 21);
+
+        // what is already in it is written over.
+        let mut b = Octets::with_vec(&mut d);
+        assert_eq!(b.get_u8(), Ok(1));
+        assert_eq!(b.get_u16(), Ok(0x203));
+        assert!(b.put_u32(0).is_ok());
+        assert_eq!(b.len(), 21);
+        assert_eq!(&d[..4], &[1, 2, 3, 0]);
+
+        let mut e = Vec::new();
+        let mut b = Octets::with_vec(&mut e);
+        assert_eq!(b.get_u8(), Err(OctetsError::BufferTooShort));
+        assert_eq!(
+            b.peek_bytes(1).map(|v| v.len()),
+            Err(OctetsError::BufferTooShort)
+        );
+        assert_ne!(b, Octets::with_slice(&mut [0][..]));
+    }
 }
@@ -0,0 +1,231 @@
+/*
+    buffers of the packets serialized and received, kept to be used again
+    so a packet of the hot paths is not a heap allocation of its own:
+
+      write(length, f)   a PooledBuffer of what f puts in length bytes,
+                         given back to the pool when it is dropped
+      take(data)         a Vec of the pool with a copy of data, for the
+                         Transmits received
+      recycle(buffer)    gives a Vec taken back
+
+    the pool keeps max_buffers of them at most, of buffer_size bytes or
+    more. PoolStats tell how many were allocated and how many were in use
+    at once, to size it: max_in_use buffers of it are the ones a pool of
+    max_buffers never allocates again.
+*/
+
+use crate::octets::Octets;
+
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// About a datagram of Ethernet.
+pub const DEFAULT_BUFFER_SIZE: usize = 1500;
+pub const DEFAULT_MAX_BUFFERS: usize = 64;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct PoolStats {
+    /// The buffers not in the pool when asked for.
+    pub allocated: u64,
+    pub reused: u64,
+    pub recycled: u64,
+    /// Given back while the pool was full, or smaller than buffer_size.
+    pub discarded: u64,
+    pub in_use: usize,
+    pub max_in_use: usize,
+}
+
+// 空いているbufferと統計．
+#[derive(Debug, Default)]
+struct PoolState {
+    free: Vec<Vec<u8>>,
+    stats: PoolStats,
+}
+
+// cloneしたものは同じbufferを共有する．
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    buffer_size: usize,
+    max_buffers: usize,
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl BufferPool {
+    pub fn new(buffer_size: usize, max_buffers: usize) -> Self {
+        BufferPool {
+            buffer_size,
+            max_buffers,
+            state: Arc::new(Mutex::new(PoolState::default())),
+        }
+    }
+
+    pub fn get_buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn get_max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+
+    /// The buffers in the pool, not in use.
+    pub fn get_free(&self) -> usize {
+        self.state.lock().unwrap().free.len()
+    }
+
+    pub fn get_stats(&self) -> PoolStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// An empty Vec of `capacity` bytes at least.
+    fn alloc(&self, capacity: usize) -> Vec<u8> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        state.stats.in_use += 1;
+        state.stats.max_in_use = state.stats.max_in_use.max(state.stats.in_use);
+        match state.free.iter().rposition(|v| v.capacity() >= capacity) {
+            Some(i) => {
+                state.stats.reused += 1;
+                state.free.swap_remove(i)
+            }
+            None => {
+                state.stats.allocated += 1;
+                Vec::with_capacity(capacity.max(self.buffer_size))
+            }
+        }
+    }
+
+    /// A buffer of what `f` puts in the first `length` bytes of it.
+    pub fn write<E, F>(&self, length: usize, f: F) -> Result<PooledBuffer, E>
+    where
+        F: FnOnce(&mut Octets) -> Result<(), E>,
+    {
+        let mut buffer = PooledBuffer {
+            buffer: self.alloc(length),
+            pool: self.clone(),
+        };
+        buffer.buffer.resize(length, 0);
+        let mut out = Octets::with_slice(&mut buffer.buffer);
+        f(&mut out)?;
+        let written = out.off();
+        buffer.buffer.truncate(written);
+        Ok(buffer)
+    }
+
+    /// A copy of `data` in a Vec of the pool, to be recycled.
+    pub fn take(&self, data: &[u8]) -> Vec<u8> {
+        let mut buffer = self.alloc(data.len());
+        buffer.extend_from_slice(data);
+        buffer
+    }
+
+    /// Gives back a Vec taken from the pool. An empty one of no capacity
+    /// is not of it.
+    pub fn recycle(&self, mut buffer: Vec<u8>) {
+        if buffer.capacity() == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.stats.in_use = state.stats.in_use.saturating_sub(1);
+        if state.free.len() >= self.max_buffers || buffer.capacity() < self.buffer_size {
+            state.stats.discarded += 1;
+            return;
+        }
+        buffer.clear();
+        state.free.push(buffer);
+        state.stats.recycled += 1;
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        BufferPool::new(DEFAULT_BUFFER_SIZE, DEFAULT_MAX_BUFFERS)
+    }
+}
+
+/// A buffer of a BufferPool, given back when it is dropped.
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: BufferPool,
+}
+
+impl PooledBuffer {
+    /// Takes the Vec out of it, to be recycled by the one it is given to.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        mem::take(&mut self.buffer)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buffer
+    }
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.recycle(mem::take(&mut self.buffer));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OctetsError;
+
+    #[test]
+    fn buffer_pool_test() {
+        let pool = BufferPool::new(16, 2);
+        let buffer = pool.write(8, |out| out.put_u32(1).map(|_| ())).unwrap();
+        assert_eq!(&buffer[..], &[0, 0, 0, 1]);
+        assert_eq!(pool.get_stats().in_use, 1);
+        drop(buffer);
+        assert_eq!(pool.get_free(), 1);
+
+        // the one given back is used again.
+        for _ in 0..10 {
+            let buffer = pool.write(4, |out| out.put_u32(2).map(|_| ())).unwrap();
+            assert_eq!(&buffer[..], &[0, 0, 0, 2]);
+        }
+        let stats = pool.get_stats();
+        assert_eq!(stats.allocated, 1);
+        assert_eq!(stats.reused, 10);
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.max_in_use, 1);
+
+        // a failed write gives it back too.
+        assert_eq!(
+            pool.write(2, |out| out.put_u32(3).map(|_| ())).unwrap_err(),
+            OctetsError::BufferTooShort
+        );
+        assert_eq!(pool.get_free(), 1);
+
+        // no more than max_buffers are kept.
+        let taken: Vec<Vec<u8>> = (0..3).map(|_| pool.take(&[1; 20])).collect();
+        assert!(taken.iter().all(|v| v[..] == [1; 20]));
+        assert_eq!(pool.get_stats().max_in_use, 3);
+        for v in taken {
+            pool.recycle(v);
+        }
+        assert_eq!(pool.get_free(), 2);
+        let stats = pool.get_stats();
+        assert_eq!(stats.discarded, 2);
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.allocated, 4);
+    }
+}
@@ -0,0 +1,280 @@
+// https://draft.ortc.org/
+// https://www.w3.org/TR/webrtc/#rtcicetransport
+
+/*
+    the transports and the tracks of RtcPeerConnection without SDP, wired
+    with the parameters of ORTC, for a signaling of its own or the pipeline
+    of a server:
+
+      RtcIceTransport    an IceAgent, started with the RtcIceParameters
+                         and the candidates of the peer
+      RtcDtlsTransport   DTLS and SRTP over the RtcIceTransport it owns,
+                         started with the RtcDtlsParameters of the peer.
+                         the RTP packets received are the Rtp event of the
+                         mid of a receiver
+      RtcRtpSender       the packetizer of the first codec of the
+                         RtcRtpParameters it can write, on the SSRC of the
+                         first encoding
+      RtcRtpReceiver     the frames of the packets of its RtcRtpParameters
+
+    the peers exchange the parameters of get_local_parameters and the
+    candidates of the LocalCandidate events, the senders of one and the
+    receivers of the other given the same RtcRtpParameters:
+
+      ice.gather(network, now)
+      ice.start(&remote_ice, role, now)    one side controlling
+      dtls.start(&remote_dtls, now)        a role of Auto is the client on
+                                           the controlled side
+      receiver.receive(&mut dtls, parameters)
+      sender.send(parameters)
+
+    sans-IO like RtcPeerConnection, the senders and the receivers are given
+    the RtcDtlsTransport on each call.
+*/
+
+pub mod dtls_transport;
+pub mod ice_transport;
+pub mod rtp_receiver;
+pub mod rtp_sender;
+
+use crate::dtls::fingerprint::CertificateFingerprint;
+use crate::jsep::codec::Codec;
+use crate::jsep::extmap::Extmap;
+
+use failure::Fail;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum OrtcError {
+    #[fail(display = "Parameters are invalid: {}", reason)]
+    InvalidParameters { reason: String },
+
+    #[fail(display = "Already started.")]
+    AlreadyStarted,
+
+    #[fail(display = "Not started.")]
+    NotStarted,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtcIceParameters {
+    pub username_fragment: String,
+    pub password: String,
+    pub ice_lite: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RtcDtlsRole {
+    /// The client on the controlled side of ICE.
+    Auto,
+    Client,
+    Server,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtcDtlsParameters {
+    pub role: RtcDtlsRole,
+    pub fingerprints: Vec<CertificateFingerprint>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct RtcRtpEncodingParameters {
+    /// A random one for a sender, any for a receiver with None.
+    pub ssrc: Option<u32>,
+}
+
+// SDPのm= sectionの代わりに，senderとreceiverで揃える値．
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RtcRtpParameters {
+    /// In the MID header extension when it is in `header_extensions`.
+    pub mid: Option<String>,
+    pub codecs: Vec<Codec>,
+    pub header_extensions: Vec<Extmap>,
+    pub encodings: Vec<RtcRtpEncodingParameters>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::dtls_transport::{DtlsTransportEvent, RtcDtlsTransport};
+    use super::ice_transport::RtcIceTransport;
+    use super::rtp_receiver::RtcRtpReceiver;
+    use super::rtp_sender::RtcRtpSender;
+    use super::*;
+    use crate::dtls::fingerprint::HashFunction;
+    use crate::dtls::transport::test::FakeBackend;
+    use crate::dtls::transport::{DtlsRole, DtlsState};
+    use crate::ice::agent::{IceConfig, IceEvent, IceRole};
+    use crate::jsep::track::MediaStreamTrack;
+    use crate::rtcpeerconnection::test::new_network;
+    use crate::rtcpeerconnection::track::RTP_MTU;
+    use crate::rtp::header_extension::MID_URI;
+    use crate::sdp::media::MediaKind;
+    use crate::srtp::context::ContextConfig;
+    use crate::time::Instant;
+    use crate::WebrtcError;
+    use std::time::Duration;
+
+    type Transport = RtcDtlsTransport<FakeBackend>;
+
+    fn new_transport(certificate: &[u8]) -> Transport {
+        let fingerprint =
+            CertificateFingerprint::from_certificate(HashFunction::Sha256, certificate);
+        RtcDtlsTransport::new(
+            RtcIceTransport::new(IceConfig::default()),
+            FakeBackend::new(certificate),
+            vec![fingerprint],
+            ContextConfig::default(),
+        )
+    }
+
+    /// Delivers the packets and the candidates of `from`.
+    fn pump(
+        from: &mut Transport,
+        to: &mut Transport,
+        events: &mut Vec<DtlsTransportEvent>,
+        now: Instant,
+    ) -> bool {
+        let mut busy = false;
+        while let Some(v) = from.poll_transmit() {
+            to.handle_receive(v.destination, v.source, &v.data, now);
+            busy = true;
+        }
+        while let Some(event) = from.poll_event() {
+            let ice = to.get_ice_transport_mut();
+            match event {
+                DtlsTransportEvent::Ice(IceEvent::LocalCandidate(v)) => {
+                    ice.add_remote_candidate(Some(v), now)
+                }
+                DtlsTransportEvent::Ice(IceEvent::GatheringComplete) => {
+                    ice.add_remote_candidate(None, now)
+                }
+                event => events.push(event),
+            }
+            busy = true;
+        }
+        busy
+    }
+
+    fn run_until<F: Fn(&[DtlsTransportEvent], &[DtlsTransportEvent]) -> bool>(
+        a: &mut Transport,
+        b: &mut Transport,
+        events: &mut [Vec<DtlsTransportEvent>; 2],
+        mut now: Instant,
+        done: F,
+    ) -> Instant {
+        for _ in 0..10_000 {
+            let [a_events, b_events] = events;
+            while pump(a, b, a_events, now) | pump(b, a, b_events, now) {}
+            if done(a_events, b_events) {
+                return now;
+            }
+            let next = [a.poll_timeout(), b.poll_timeout()]
+                .iter()
+                .flatten()
+                .min()
+                .copied();
+            match next {
+                Some(v) => now = now.max(v),
+                None => return now,
+            }
+            a.process(now);
+            b.process(now);
+        }
+        panic!("not done");
+    }
+
+    fn is_connected(events: &[DtlsTransportEvent]) -> bool {
+        events.contains(&DtlsTransportEvent::StateChange(DtlsState::Connected))
+    }
+
+    #[test]
+    fn ortc_test() {
+        let now = Instant::now();
+        let mut a = new_transport(b"a");
+        let mut b = new_transport(b"b");
+        let mut events = [vec![], vec![]];
+        a.get_ice_transport_mut()
+            .gather(&mut new_network("10.0.0.1"), now)
+            .unwrap();
+        b.get_ice_transport_mut()
+            .gather(&mut new_network("10.0.0.2"), now)
+            .unwrap();
+
+        // signaled by the application.
+        let (a_ice, a_dtls) = (
+            a.get_ice_transport().get_local_parameters(),
+            a.get_local_parameters(),
+        );
+        let (b_ice, b_dtls) = (
+            b.get_ice_transport().get_local_parameters(),
+            b.get_local_parameters(),
+        );
+        a.get_ice_transport_mut()
+            .start(&b_ice, IceRole::Controlling, now)
+            .unwrap();
+        b.get_ice_transport_mut()
+            .start(&a_ice, IceRole::Controlled, now)
+            .unwrap();
+        a.start(&b_dtls, now).unwrap();
+        b.start(&a_dtls, now).unwrap();
+        assert_eq!(a.get_role(), Some(DtlsRole::Server));
+        assert_eq!(b.get_role(), Some(DtlsRole::Client));
+        assert_eq!(
+            a.start(&b_dtls, now).unwrap_err().to_string(),
+            WebrtcError::from(OrtcError::AlreadyStarted).to_string()
+        );
+
+        let parameters = RtcRtpParameters {
+            mid: Some("video".to_string()),
+            codecs: vec![Codec::new(96, "VP8", 90000, None)],
+            header_extensions: vec![Extmap::new(1, MID_URI)],
+            encodings: vec![RtcRtpEncodingParameters { ssrc: Some(1234) }],
+        };
+        let mut sender = RtcRtpSender::new(MediaStreamTrack::new(MediaKind::Video));
+        let frame = vec![7; 3000];
+        assert!(sender
+            .write_frame(&mut a, &frame, Duration::from_millis(33), now)
+            .is_err());
+        assert!(sender.send(RtcRtpParameters::default()).is_err());
+        sender.send(parameters.clone()).unwrap();
+        assert_eq!(sender.get_ssrc(), Some(1234));
+
+        // the SSRC is not known to the receiver, the MID tells the stream.
+        let mut receiver = RtcRtpReceiver::new(MediaKind::Video);
+        let remote = RtcRtpParameters {
+            encodings: vec![],
+            ..parameters
+        };
+        receiver.receive(&mut b, remote).unwrap();
+        assert_eq!(receiver.get_mid(), Some("video"));
+
+        let now = run_until(&mut a, &mut b, &mut events, now, |a, b| {
+            is_connected(a) && is_connected(b)
+        });
+        sender
+            .write_frame(&mut a, &frame, Duration::from_millis(33), now)
+            .unwrap();
+        run_until(&mut a, &mut b, &mut events, now, |_, b| {
+            b.iter()
+                .any(|v| matches!(v, DtlsTransportEvent::Rtp { .. }))
+        });
+        let mut count = 0;
+        for event in events[1].drain(..) {
+            if let DtlsTransportEvent::Rtp { mid, packet } = event {
+                assert!(packet.len() <= RTP_MTU);
+                assert!(receiver.handle_packet(&mid, &packet));
+                count += 1;
+            }
+        }
+        assert_eq!(count, 3);
+        let received = receiver.poll_frame().unwrap();
+        assert_eq!(received.ssrc, 1234);
+        assert_eq!(received.data, frame);
+        assert!(!receiver.handle_packet("audio", &[]));
+
+        b.send_rtcp(&[0x80, 201, 0, 1, 0, 0, 0, 1], now).unwrap();
+        run_until(&mut a, &mut b, &mut events, now, |a, _| {
+            a.iter().any(|v| matches!(v, DtlsTransportEvent::Rtcp(_)))
+        });
+        assert!(events[0].contains(&DtlsTransportEvent::Rtcp(vec![0x80, 201, 0, 1, 0, 0, 0, 1])));
+    }
+}
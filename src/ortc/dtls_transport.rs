@@ -0,0 +1,249 @@
+// https://draft.ortc.org/#rtcdtlstransport*
+
+use crate::dtls::fingerprint::CertificateFingerprint;
+use crate::dtls::transport::{
+    is_dtls_packet, DtlsBackend, DtlsEvent, DtlsRole, DtlsState, DtlsTransport,
+};
+use crate::dtls::DtlsError;
+use crate::ice::agent::{IceEvent, IceRole};
+use crate::ice::network::Transmit;
+use crate::jsep::extmap::{get_header_extension_map, Extmap};
+use crate::ortc::ice_transport::RtcIceTransport;
+use crate::ortc::{OrtcError, RtcDtlsParameters, RtcDtlsRole, RtcRtpParameters};
+use crate::rtcpeerconnection::{is_rtcp_packet, Result};
+use crate::rtp::demuxer::RtpDemuxer;
+use crate::rtp::packet::RtpPacketRef;
+use crate::srtp::context::ContextConfig;
+use crate::srtp::dtls_srtp::DtlsSrtpSession;
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+#[derive(Debug, PartialEq)]
+pub enum DtlsTransportEvent {
+    Ice(IceEvent),
+    StateChange(DtlsState),
+    /// An RTP packet of the receiver of `mid`, unprotected.
+    Rtp {
+        mid: String,
+        packet: Vec<u8>,
+    },
+    /// A compound RTCP packet, unprotected.
+    Rtcp(Vec<u8>),
+}
+
+// RtcIceTransportの上のDTLSとSRTP，受けたRTPをreceiverのmidに分ける．
+pub struct RtcDtlsTransport<B: DtlsBackend> {
+    ice: RtcIceTransport,
+    fingerprints: Vec<CertificateFingerprint>,
+    config: ContextConfig,
+    backend: Option<B>,
+    dtls: Option<DtlsTransport<B>>,
+    srtp: Option<DtlsSrtpSession>,
+    demuxer: RtpDemuxer,
+    extmaps: Vec<Extmap>,
+    events: VecDeque<DtlsTransportEvent>,
+}
+
+impl<B: DtlsBackend> RtcDtlsTransport<B> {
+    /// `fingerprints` are the ones of the certificate of `backend`.
+    pub fn new(
+        ice: RtcIceTransport,
+        backend: B,
+        fingerprints: Vec<CertificateFingerprint>,
+        config: ContextConfig,
+    ) -> Self {
+        RtcDtlsTransport {
+            ice,
+            fingerprints,
+            config,
+            backend: Some(backend),
+            dtls: None,
+            srtp: None,
+            demuxer: RtpDemuxer::new(),
+            extmaps: vec![],
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn get_ice_transport(&self) -> &RtcIceTransport {
+        &self.ice
+    }
+
+    pub fn get_ice_transport_mut(&mut self) -> &mut RtcIceTransport {
+        &mut self.ice
+    }
+
+    pub fn get_state(&self) -> DtlsState {
+        self.dtls
+            .as_ref()
+            .map(|v| v.get_state())
+            .unwrap_or(DtlsState::New)
+    }
+
+    /// The role taken by start.
+    pub fn get_role(&self) -> Option<DtlsRole> {
+        self.dtls.as_ref().map(|v| v.get_role())
+    }
+
+    pub fn get_local_parameters(&self) -> RtcDtlsParameters {
+        RtcDtlsParameters {
+            role: RtcDtlsRole::Auto,
+            fingerprints: self.fingerprints.clone(),
+        }
+    }
+
+    /// Starts the handshake with the peer, after the RtcIceTransport is
+    /// started for the role of Auto.
+    pub fn start(&mut self, remote: &RtcDtlsParameters, now: Instant) -> Result<()> {
+        let backend = self.backend.take().ok_or(OrtcError::AlreadyStarted)?;
+        let role = match remote.role {
+            RtcDtlsRole::Client => DtlsRole::Server,
+            RtcDtlsRole::Server => DtlsRole::Client,
+            RtcDtlsRole::Auto if self.ice.get_role() == IceRole::Controlling => DtlsRole::Server,
+            RtcDtlsRole::Auto => DtlsRole::Client,
+        };
+        let mut dtls = DtlsTransport::new(backend, role);
+        dtls.set_remote_fingerprints(remote.fingerprints.clone());
+        dtls.start(now)?;
+        self.dtls = Some(dtls);
+        self.update(now);
+        Ok(())
+    }
+
+    /// Demuxes the packets of the SSRCs, the payload types and the MID of
+    /// `parameters` to `mid`.
+    pub(crate) fn add_receiver(&mut self, mid: &str, parameters: &RtcRtpParameters) {
+        for ssrc in parameters.encodings.iter().filter_map(|v| v.ssrc) {
+            self.demuxer.add_ssrc(ssrc, mid);
+        }
+        for codec in &parameters.codecs {
+            self.demuxer.add_payload_type(codec.payload_type, mid);
+        }
+        self.extmaps
+            .extend(parameters.header_extensions.iter().cloned());
+        self.demuxer
+            .set_header_extension_map(get_header_extension_map(&self.extmaps));
+    }
+
+    /// Sends an RTP packet, once DTLS is connected.
+    pub fn send_rtp(&mut self, packet: &[u8], now: Instant) -> Result<()> {
+        let srtp = self.srtp.as_mut().ok_or(DtlsError::NotConnected)?;
+        let packet = srtp.get_send_context().protect_rtp(packet)?;
+        self.ice.get_ice_agent_mut().send(&packet, now)?;
+        Ok(())
+    }
+
+    pub fn send_rtcp(&mut self, packet: &[u8], now: Instant) -> Result<()> {
+        let srtp = self.srtp.as_mut().ok_or(DtlsError::NotConnected)?;
+        let packet = srtp.get_send_context().protect_rtcp(packet)?;
+        self.ice.get_ice_agent_mut().send(&packet, now)?;
+        Ok(())
+    }
+
+    /// Handles a datagram received on the socket bound to `local`.
+    pub fn handle_receive(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) {
+        self.ice.handle_receive(local, source, data, now);
+        self.update(now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.ice.poll_transmit()
+    }
+
+    pub fn poll_event(&mut self) -> Option<DtlsTransportEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        [
+            self.ice.poll_timeout(),
+            self.dtls.as_ref().and_then(|v| v.poll_timeout()),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        self.ice.process(now);
+        if let Some(dtls) = self.dtls.as_mut() {
+            // a failure is told by the state.
+            let _ = dtls.process(now);
+        }
+        if let Some(srtp) = self.srtp.as_mut() {
+            srtp.get_send_context().process(now);
+            srtp.get_receive_context().process(now);
+        }
+        self.update(now);
+    }
+
+    /// Closes DTLS with an alert, and ICE.
+    pub fn stop(&mut self, now: Instant) {
+        if let Some(dtls) = self.dtls.as_mut() {
+            dtls.close();
+            dtls.flush(self.ice.get_ice_agent_mut(), now);
+        }
+        self.ice.stop();
+        self.srtp = None;
+    }
+
+    fn handle_packet(&mut self, data: &[u8], now: Instant) {
+        if is_dtls_packet(data) {
+            if let Some(dtls) = self.dtls.as_mut() {
+                let _ = dtls.handle_packet(data, now);
+            }
+            return;
+        }
+        let srtp = match self.srtp.as_mut() {
+            Some(v) if data.first().is_some_and(|v| (128..=191).contains(v)) => v,
+            _ => return,
+        };
+        if is_rtcp_packet(data) {
+            if let Ok(packet) = srtp.get_receive_context().unprotect_rtcp(data) {
+                self.events.push_back(DtlsTransportEvent::Rtcp(packet));
+            }
+            return;
+        }
+        let packet = match srtp.get_receive_context().unprotect_rtp(data) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let mid = match RtpPacketRef::from_slice(&packet) {
+            Ok(parsed) => match self.demuxer.demux_ref(&parsed) {
+                Some(v) => v.to_string(),
+                None => return,
+            },
+            Err(_) => return,
+        };
+        self.events
+            .push_back(DtlsTransportEvent::Rtp { mid, packet });
+    }
+
+    fn update(&mut self, now: Instant) {
+        while let Some(event) = self.ice.poll_event() {
+            self.events.push_back(DtlsTransportEvent::Ice(event));
+        }
+        while let Some(data) = self.ice.get_ice_agent_mut().poll_receive() {
+            self.handle_packet(&data, now);
+        }
+        if let Some(dtls) = self.dtls.as_mut() {
+            while let Some(DtlsEvent::StateChanged(state)) = dtls.poll_event() {
+                if state == DtlsState::Connected {
+                    self.srtp = DtlsSrtpSession::new(dtls, dtls.get_role(), self.config).ok();
+                }
+                self.events
+                    .push_back(DtlsTransportEvent::StateChange(state));
+            }
+            dtls.flush(self.ice.get_ice_agent_mut(), now);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// https://draft.ortc.org/#rtcicetransport*
+
+use crate::ice::agent::{
+    IceAgent, IceConfig, IceConnectionState, IceCredentials, IceEvent, IceRole,
+};
+use crate::ice::candidate::IceCandidate;
+use crate::ice::network::{NetworkProvider, Transmit};
+use crate::ortc::{OrtcError, RtcIceParameters};
+use crate::rtcpeerconnection::Result;
+use crate::time::Instant;
+
+use std::net::SocketAddr;
+
+// 相手のparametersで始めるIceAgent．
+pub struct RtcIceTransport {
+    agent: IceAgent,
+    remote: Option<RtcIceParameters>,
+}
+
+impl RtcIceTransport {
+    pub fn new(config: IceConfig) -> Self {
+        RtcIceTransport {
+            agent: IceAgent::new(config, IceRole::Controlled),
+            remote: None,
+        }
+    }
+
+    pub fn get_ice_agent(&self) -> &IceAgent {
+        &self.agent
+    }
+
+    /// For what the parameters have no place for, the TCP candidates and
+    /// the restarts.
+    pub fn get_ice_agent_mut(&mut self) -> &mut IceAgent {
+        &mut self.agent
+    }
+
+    pub fn get_role(&self) -> IceRole {
+        self.agent.get_role()
+    }
+
+    pub fn get_state(&self) -> IceConnectionState {
+        self.agent.get_state()
+    }
+
+    pub fn get_local_parameters(&self) -> RtcIceParameters {
+        let credentials = self.agent.get_local_credentials();
+        RtcIceParameters {
+            username_fragment: credentials.ufrag.clone(),
+            password: credentials.pwd.clone(),
+            ice_lite: self.agent.get_config().lite,
+        }
+    }
+
+    pub fn get_remote_parameters(&self) -> Option<&RtcIceParameters> {
+        self.remote.as_ref()
+    }
+
+    pub fn get_local_candidates(&self) -> Vec<IceCandidate> {
+        self.agent.get_local_candidates()
+    }
+
+    /// Gathers the candidates, each a LocalCandidate event to signal.
+    pub fn gather<P: NetworkProvider + ?Sized>(
+        &mut self,
+        network: &mut P,
+        now: Instant,
+    ) -> Result<()> {
+        self.agent.gather(network, now)?;
+        Ok(())
+    }
+
+    /// Checks with the peer, controlled anyway when this side is ICE-lite
+    /// and controlling when the peer is.
+    pub fn start(&mut self, remote: &RtcIceParameters, role: IceRole, now: Instant) -> Result<()> {
+        if self.remote.is_some() {
+            return Err(OrtcError::AlreadyStarted.into());
+        }
+        self.agent.set_role(role);
+        if remote.ice_lite {
+            self.agent.set_remote_lite();
+        }
+        self.agent.set_remote_credentials(
+            IceCredentials::new(&remote.username_fragment, &remote.password),
+            now,
+        );
+        self.remote = Some(remote.clone());
+        Ok(())
+    }
+
+    /// A candidate of the peer, None at the end of them.
+    pub fn add_remote_candidate(&mut self, candidate: Option<IceCandidate>, now: Instant) {
+        match candidate {
+            Some(v) => {
+                self.agent.add_remote_candidate(v, now);
+            }
+            None => self.agent.set_remote_end_of_candidates(),
+        }
+    }
+
+    pub fn handle_receive(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) {
+        self.agent.handle_receive(local, source, data, now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.agent.poll_transmit()
+    }
+
+    pub fn poll_event(&mut self) -> Option<IceEvent> {
+        self.agent.poll_event()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.agent.poll_timeout()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        self.agent.process(now);
+    }
+
+    pub fn stop(&mut self) {
+        self.agent.close();
+    }
+}
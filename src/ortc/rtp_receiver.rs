@@ -0,0 +1,100 @@
+// https://draft.ortc.org/#rtcrtpreceiver-interface*
+
+use crate::dtls::transport::DtlsBackend;
+use crate::jsep::capabilities::{CapabilityRegistry, RtpCapabilities};
+use crate::jsep::track::MediaStreamTrack;
+use crate::ortc::dtls_transport::RtcDtlsTransport;
+use crate::ortc::{OrtcError, RtcRtpParameters};
+use crate::rtcpeerconnection::track::TrackReceiver;
+use crate::rtcpeerconnection::Result;
+use crate::rtp::frame_transformer::{EncodedFrame, FrameTransformPipeline, FrameTransformer};
+use crate::rtp::packet::RtpPacketRef;
+use crate::sdp::media::MediaKind;
+
+// RtcRtpParametersで受けるtrack．
+pub struct RtcRtpReceiver {
+    track: MediaStreamTrack,
+    parameters: Option<RtcRtpParameters>,
+    receiver: Option<TrackReceiver>,
+    transforms: FrameTransformPipeline,
+}
+
+impl RtcRtpReceiver {
+    pub fn new(kind: MediaKind) -> Self {
+        RtcRtpReceiver {
+            track: MediaStreamTrack::new(kind),
+            parameters: None,
+            receiver: None,
+            transforms: FrameTransformPipeline::new(),
+        }
+    }
+
+    /// What a receiver of `kind` can read, for the parameters.
+    pub fn get_capabilities(kind: MediaKind) -> RtpCapabilities {
+        CapabilityRegistry::new().get_receiver_capabilities(kind)
+    }
+
+    pub fn get_track(&self) -> &MediaStreamTrack {
+        &self.track
+    }
+
+    pub fn get_parameters(&self) -> Option<&RtcRtpParameters> {
+        self.parameters.as_ref()
+    }
+
+    /// The mid of the Rtp events of the transport, the id of the track
+    /// when the parameters have none.
+    pub fn get_mid(&self) -> Option<&str> {
+        self.receiver.as_ref().map(|v| v.get_mid())
+    }
+
+    /// Takes the packets of the SSRCs, the payload types and the MID of
+    /// `parameters` received on `transport`. Receiving again adds to them.
+    pub fn receive<B: DtlsBackend>(
+        &mut self,
+        transport: &mut RtcDtlsTransport<B>,
+        parameters: RtcRtpParameters,
+    ) -> Result<()> {
+        if parameters.codecs.is_empty() {
+            return Err(OrtcError::InvalidParameters {
+                reason: "no codec to read".to_string(),
+            }
+            .into());
+        }
+        let mid = match (self.receiver.as_ref(), parameters.mid.as_ref()) {
+            (Some(receiver), _) => receiver.get_mid().to_string(),
+            (None, Some(mid)) => mid.clone(),
+            (None, None) => self.track.get_id().to_string(),
+        };
+        transport.add_receiver(&mid, &parameters);
+        match self.receiver.as_mut() {
+            Some(receiver) => receiver.set_codecs(&parameters.codecs),
+            None => self.receiver = Some(TrackReceiver::new(&mid, &parameters.codecs)),
+        }
+        self.parameters = Some(parameters);
+        Ok(())
+    }
+
+    /// Whether the packet of an Rtp event of `mid` is taken.
+    pub fn handle_packet(&mut self, mid: &str, packet: &[u8]) -> bool {
+        let receiver = match self.receiver.as_mut() {
+            Some(v) if v.get_mid() == mid => v,
+            _ => return false,
+        };
+        RtpPacketRef::from_slice(packet).is_ok_and(|v| receiver.handle_packet(&v))
+    }
+
+    /// The transform of the assembled frames.
+    pub fn set_receive_transform(&mut self, transformer: Option<Box<dyn FrameTransformer>>) {
+        self.transforms.set_receive_transform(transformer);
+    }
+
+    /// A whole frame received, in the order of the packets.
+    pub fn poll_frame(&mut self) -> Option<EncodedFrame> {
+        self.receiver.as_mut()?.poll_frame(&mut self.transforms)
+    }
+
+    pub fn stop(&mut self) {
+        self.receiver = None;
+    }
+}
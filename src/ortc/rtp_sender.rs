@@ -0,0 +1,133 @@
+// https://draft.ortc.org/#rtcrtpsender-interface*
+
+use crate::dtls::transport::DtlsBackend;
+use crate::jsep::capabilities::{CapabilityRegistry, RtpCapabilities};
+use crate::jsep::codec::Codec;
+use crate::jsep::extmap::get_header_extension_map;
+use crate::jsep::track::MediaStreamTrack;
+use crate::octets::Octets;
+use crate::ortc::dtls_transport::RtcDtlsTransport;
+use crate::ortc::{OrtcError, RtcRtpParameters};
+use crate::rtcpeerconnection::track::{pack_frame, select_codec, RTP_MTU};
+use crate::rtcpeerconnection::Result;
+use crate::rtp::frame_transformer::{FrameTransformPipeline, FrameTransformer};
+use crate::rtp::header_extension::{HeaderExtensionMap, MID_URI};
+use crate::rtp::packetizer::RtpPacketizer;
+use crate::rtp::payloader::new_payloader;
+use crate::time::Instant;
+
+use std::time::Duration;
+
+// RtcRtpParametersで送るtrack．
+pub struct RtcRtpSender {
+    track: MediaStreamTrack,
+    parameters: Option<RtcRtpParameters>,
+    codec: Option<Codec>,
+    packetizer: Option<RtpPacketizer>,
+    extensions: HeaderExtensionMap,
+    transforms: FrameTransformPipeline,
+}
+
+impl RtcRtpSender {
+    pub fn new(track: MediaStreamTrack) -> Self {
+        RtcRtpSender {
+            track,
+            parameters: None,
+            codec: None,
+            packetizer: None,
+            extensions: HeaderExtensionMap::new(),
+            transforms: FrameTransformPipeline::new(),
+        }
+    }
+
+    /// What a sender of `track` can write in, for the parameters.
+    pub fn get_capabilities(track: &MediaStreamTrack) -> RtpCapabilities {
+        CapabilityRegistry::new().get_sender_capabilities(track.get_kind())
+    }
+
+    pub fn get_track(&self) -> &MediaStreamTrack {
+        &self.track
+    }
+
+    pub fn get_parameters(&self) -> Option<&RtcRtpParameters> {
+        self.parameters.as_ref()
+    }
+
+    /// The codec the frames are written in.
+    pub fn get_codec(&self) -> Option<&Codec> {
+        self.codec.as_ref()
+    }
+
+    pub fn get_ssrc(&self) -> Option<u32> {
+        self.packetizer.as_ref().map(|v| v.get_ssrc())
+    }
+
+    /// Starts sending, the stream goes on with parameters of the same SSRC.
+    pub fn send(&mut self, parameters: RtcRtpParameters) -> Result<()> {
+        let codec = select_codec(&parameters.codecs).cloned().ok_or_else(|| {
+            OrtcError::InvalidParameters {
+                reason: "no codec to write".to_string(),
+            }
+        })?;
+        let payloader = new_payloader(&codec.name).unwrap();
+        let ssrc = parameters.encodings.first().and_then(|v| v.ssrc);
+        let extensions = get_header_extension_map(&parameters.header_extensions);
+        match self.packetizer.as_mut() {
+            Some(packetizer) if ssrc.is_none() || ssrc == Some(packetizer.get_ssrc()) => {
+                packetizer.set_codec(codec.payload_type, codec.clock_rate, payloader);
+            }
+            _ => {
+                let mtu = match parameters.mid.as_ref() {
+                    // the one-byte header of RFC 8285 and the padding.
+                    Some(mid) if extensions.get_id(MID_URI).is_some() => {
+                        RTP_MTU - 4 - (mid.len() + 4) / 4 * 4
+                    }
+                    _ => RTP_MTU,
+                };
+                self.packetizer = Some(RtpPacketizer::new(
+                    mtu,
+                    codec.payload_type,
+                    ssrc.unwrap_or_else(rand::random),
+                    codec.clock_rate,
+                    payloader,
+                ));
+            }
+        }
+        self.extensions = extensions;
+        self.codec = Some(codec);
+        self.parameters = Some(parameters);
+        Ok(())
+    }
+
+    /// The transform of the frames before they are packetized.
+    pub fn set_send_transform(&mut self, transformer: Option<Box<dyn FrameTransformer>>) {
+        self.transforms.set_send_transform(transformer);
+    }
+
+    /// Sends an encoded frame lasting `duration` on `transport`.
+    pub fn write_frame<B: DtlsBackend>(
+        &mut self,
+        transport: &mut RtcDtlsTransport<B>,
+        frame: &[u8],
+        duration: Duration,
+        now: Instant,
+    ) -> Result<()> {
+        let packetizer = self.packetizer.as_mut().ok_or(OrtcError::NotStarted)?;
+        let mid = self.parameters.as_ref().and_then(|v| v.mid.as_ref());
+        for mut packet in pack_frame(packetizer, &mut self.transforms, frame, duration) {
+            if let Some(mid) = mid {
+                self.extensions
+                    .set_extension(packet.get_header_mut(), MID_URI, mid.as_bytes())?;
+            }
+            let mut data = vec![0; packet.get_length()];
+            packet.to_bytes(&mut Octets::with_slice(&mut data))?;
+            transport.send_rtp(&data, now)?;
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.packetizer = None;
+        self.codec = None;
+    }
+}
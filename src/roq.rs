@@ -0,0 +1,41 @@
+// https://datatracker.ietf.org/doc/draft-ietf-avtcore-rtp-over-quic/
+
+/*
+    RTP over QUIC, experimental as the draft is. the QUIC connection is the
+    one of the application, negotiated with the ALPN below; RoqTransport
+    maps the RTP and RTCP packets of each flow onto it:
+
+      datagram   flow id (varint) | packet
+      stream     flow id (varint) | length (varint) | packet | length ...
+                 a unidirectional stream for a flow, the packets of it in
+                 order until it is finished
+
+    RTP and RTCP share the flow id, told apart as RFC 5761 does. the flow
+    ids are agreed on out of band, by the signaling.
+
+    QUIC does the congestion control of the streams. the datagrams acked or
+    lost as the QUIC stack tells are the TransportFeedback of a
+    CongestionController of cc, the time the acknowledgement is told as
+    the arrival.
+*/
+
+pub mod transport;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, RoqError>;
+
+/// The ALPN of RTP over QUIC.
+pub const ALPN: &[u8] = b"rtp-mux-quic";
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum RoqError {
+    #[fail(display = "RoQ flow id {} is over 2^62.", flow_id)]
+    InvalidFlowId { flow_id: u64 },
+
+    #[fail(display = "RoQ datagram is broken.")]
+    InvalidDatagram,
+
+    #[fail(display = "RoQ stream {} is broken.", stream)]
+    InvalidStream { stream: u64 },
+}
@@ -0,0 +1,529 @@
+use crate::cc::transport_feedback_adapter::TransportFeedback;
+use crate::cc::{CongestionController, PacketResult};
+use crate::octets::{varint_parse_len, Octets};
+use crate::roq::{Result, RoqError};
+use crate::rtcpeerconnection::is_rtcp_packet;
+use crate::time::Instant;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+
+const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// Longer ones are not RTP packets of a UDP datagram either.
+const MAX_PACKET_LENGTH: u64 = 65535;
+
+/// The datagrams not told acked or lost after this many are forgotten,
+/// as the transport sequence numbers wrap.
+const MAX_SENT_DATAGRAMS: u64 = 1 << 15;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RoqMode {
+    Datagram,
+    Stream,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct RoqConfig {
+    pub mode: RoqMode,
+    /// The largest payload of a DATAGRAM frame, a longer packet goes on a
+    /// stream of its own.
+    pub max_datagram_size: usize,
+    /// How long the datagrams acked or lost are batched for the controller.
+    pub feedback_interval: Duration,
+}
+
+impl Default for RoqConfig {
+    fn default() -> Self {
+        RoqConfig {
+            mode: RoqMode::Datagram,
+            max_datagram_size: 1200,
+            feedback_interval: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RoqTransmit {
+    /// The payload of a DATAGRAM frame, `id` to tell back with
+    /// handle_datagram_acked or handle_datagram_lost.
+    Datagram { id: u64, data: Vec<u8> },
+    /// Bytes of the local unidirectional stream `stream`, opened by the
+    /// first ones and finished with `fin`. The QUIC stream id is the one
+    /// the application opens for it.
+    Stream {
+        stream: u64,
+        data: Vec<u8>,
+        fin: bool,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RoqEvent {
+    Rtp { flow_id: u64, packet: Vec<u8> },
+    Rtcp { flow_id: u64, packet: Vec<u8> },
+}
+
+// 送ったdatagramのsequenceと時刻と大きさ．
+struct SentDatagram {
+    sequence: u16,
+    send_time: Instant,
+    size: usize,
+}
+
+// 受信中のstreamの，読み残しとflow id．
+#[derive(Default)]
+struct ReceiveStream {
+    flow_id: Option<u64>,
+    buffer: Vec<u8>,
+}
+
+/// The varint at the start of `data` and its length, None when it is not
+/// all there.
+fn get_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let len = varint_parse_len(*data.first()?);
+    let bytes = data.get(..len)?;
+    let value = bytes[1..]
+        .iter()
+        .fold(u64::from(bytes[0] & 0x3f), |v, b| v << 8 | u64::from(*b));
+    Some((value, len))
+}
+
+fn is_rtp_or_rtcp(packet: &[u8]) -> bool {
+    packet.len() >= 4 && (128..=191).contains(&packet[0])
+}
+
+// RTPとRTCPのpacketとQUICのdatagram，streamの対応．
+pub struct RoqTransport {
+    config: RoqConfig,
+    controller: Box<dyn CongestionController>,
+    next_datagram: u64,
+    sent: BTreeMap<u64, SentDatagram>,
+    in_flight: usize,
+    results: Vec<PacketResult>,
+    prior_in_flight: usize,
+    rtt: Option<Duration>,
+    feedback_due: Option<Instant>,
+    next_stream: u64,
+    send_streams: HashMap<u64, u64>,
+    receive_streams: HashMap<u64, ReceiveStream>,
+    transmits: VecDeque<RoqTransmit>,
+    events: VecDeque<RoqEvent>,
+}
+
+impl RoqTransport {
+    pub fn new(config: RoqConfig, controller: Box<dyn CongestionController>) -> Self {
+        RoqTransport {
+            config,
+            controller,
+            next_datagram: 0,
+            sent: BTreeMap::new(),
+            in_flight: 0,
+            results: vec![],
+            prior_in_flight: 0,
+            rtt: None,
+            feedback_due: None,
+            next_stream: 0,
+            send_streams: HashMap::new(),
+            receive_streams: HashMap::new(),
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &RoqConfig {
+        &self.config
+    }
+
+    pub fn get_controller(&self) -> &dyn CongestionController {
+        self.controller.as_ref()
+    }
+
+    pub fn get_target_bitrate(&self) -> u64 {
+        self.controller.get_target_bitrate()
+    }
+
+    /// Bytes of the datagrams not yet told acked or lost.
+    pub fn get_data_in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Sends an RTP or RTCP packet of `flow_id` in the mode of the config.
+    pub fn send(&mut self, flow_id: u64, packet: &[u8], now: Instant) -> Result<()> {
+        if flow_id > MAX_VARINT {
+            return Err(RoqError::InvalidFlowId { flow_id });
+        }
+        if self.config.mode == RoqMode::Stream {
+            let (stream, opened) = match self.send_streams.get(&flow_id) {
+                Some(v) => (*v, false),
+                None => {
+                    let stream = self.open_stream();
+                    self.send_streams.insert(flow_id, stream);
+                    (stream, true)
+                }
+            };
+            let data = Self::write_stream(opened.then_some(flow_id), packet);
+            self.transmits.push_back(RoqTransmit::Stream {
+                stream,
+                data,
+                fin: false,
+            });
+            return Ok(());
+        }
+
+        let mut data = vec![];
+        let mut out = Octets::with_vec(&mut data);
+        out.put_varint(flow_id).unwrap();
+        out.put_bytes(packet).unwrap();
+        if data.len() > self.config.max_datagram_size {
+            let stream = self.open_stream();
+            self.transmits.push_back(RoqTransmit::Stream {
+                stream,
+                data: Self::write_stream(Some(flow_id), packet),
+                fin: true,
+            });
+            return Ok(());
+        }
+
+        let id = self.next_datagram;
+        self.next_datagram += 1;
+        self.sent.insert(
+            id,
+            SentDatagram {
+                sequence: id as u16,
+                send_time: now,
+                size: data.len(),
+            },
+        );
+        self.in_flight += data.len();
+        if let Some(forgotten) = id.checked_sub(MAX_SENT_DATAGRAMS) {
+            if let Some(v) = self.sent.remove(&forgotten) {
+                self.in_flight -= v.size;
+            }
+        }
+        self.transmits.push_back(RoqTransmit::Datagram { id, data });
+        Ok(())
+    }
+
+    /// Finishes the stream of `flow_id`, the next packet opens another. A
+    /// stream for each frame keeps a lost one from holding the next back.
+    pub fn finish_stream(&mut self, flow_id: u64) {
+        if let Some(stream) = self.send_streams.remove(&flow_id) {
+            self.transmits.push_back(RoqTransmit::Stream {
+                stream,
+                data: vec![],
+                fin: true,
+            });
+        }
+    }
+
+    /// Handles the payload of a DATAGRAM frame received.
+    pub fn handle_datagram(&mut self, data: &[u8]) -> Result<()> {
+        let (flow_id, len) = get_varint(data).ok_or(RoqError::InvalidDatagram)?;
+        let packet = &data[len..];
+        if !is_rtp_or_rtcp(packet) {
+            return Err(RoqError::InvalidDatagram);
+        }
+        self.push_packet(flow_id, packet.to_vec());
+        Ok(())
+    }
+
+    /// Handles the bytes read from the unidirectional stream `stream` of
+    /// the peer, `fin` with the last ones. A broken stream is dropped.
+    pub fn handle_stream(&mut self, stream: u64, data: &[u8], fin: bool) -> Result<()> {
+        let mut receive = self.receive_streams.remove(&stream).unwrap_or_default();
+        receive.buffer.extend_from_slice(data);
+        let mut off = 0;
+        let broken = loop {
+            let rest = &receive.buffer[off..];
+            let flow_id = match receive.flow_id {
+                Some(v) => v,
+                None => match get_varint(rest) {
+                    Some((v, len)) => {
+                        receive.flow_id = Some(v);
+                        off += len;
+                        continue;
+                    }
+                    None => break false,
+                },
+            };
+            let (length, len) = match get_varint(rest) {
+                Some(v) => v,
+                None => break false,
+            };
+            if length > MAX_PACKET_LENGTH {
+                break true;
+            }
+            let end = len + length as usize;
+            if rest.len() < end {
+                break false;
+            }
+            let packet = &rest[len..end];
+            if !is_rtp_or_rtcp(packet) {
+                break true;
+            }
+            let packet = packet.to_vec();
+            self.push_packet(flow_id, packet);
+            off += end;
+        };
+        if broken || (fin && off < receive.buffer.len()) {
+            return Err(RoqError::InvalidStream { stream });
+        }
+        if !fin {
+            receive.buffer.drain(..off);
+            self.receive_streams.insert(stream, receive);
+        }
+        Ok(())
+    }
+
+    /// The datagram `id` was acknowledged by the peer.
+    pub fn handle_datagram_acked(&mut self, id: u64, now: Instant) {
+        self.report(id, Some(now), now);
+    }
+
+    /// The datagram `id` was declared lost by the QUIC stack.
+    pub fn handle_datagram_lost(&mut self, id: u64, now: Instant) {
+        self.report(id, None, now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<RoqTransmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<RoqEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.feedback_due
+    }
+
+    /// Gives the datagrams acked or lost to the controller when the
+    /// feedback interval is over.
+    pub fn process(&mut self, now: Instant) {
+        if self.feedback_due.is_none_or(|v| v > now) {
+            return;
+        }
+        self.feedback_due = None;
+        let feedback = TransportFeedback {
+            feedback_time: now,
+            packets: std::mem::take(&mut self.results),
+            rtt: self.rtt.take(),
+            prior_in_flight: self.prior_in_flight,
+            data_in_flight: self.in_flight,
+        };
+        self.controller.on_transport_feedback(&feedback);
+    }
+
+    fn open_stream(&mut self) -> u64 {
+        let stream = self.next_stream;
+        self.next_stream += 1;
+        stream
+    }
+
+    /// A packet on a stream, after the flow id when it opens it.
+    fn write_stream(flow_id: Option<u64>, packet: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut out = Octets::with_vec(&mut data);
+        if let Some(flow_id) = flow_id {
+            out.put_varint(flow_id).unwrap();
+        }
+        out.put_varint(packet.len() as u64).unwrap();
+        out.put_bytes(packet).unwrap();
+        data
+    }
+
+    fn push_packet(&mut self, flow_id: u64, packet: Vec<u8>) {
+        self.events.push_back(if is_rtcp_packet(&packet) {
+            RoqEvent::Rtcp { flow_id, packet }
+        } else {
+            RoqEvent::Rtp { flow_id, packet }
+        });
+    }
+
+    fn report(&mut self, id: u64, arrival_time: Option<Instant>, now: Instant) {
+        let sent = match self.sent.remove(&id) {
+            Some(v) => v,
+            None => return,
+        };
+        if self.results.is_empty() {
+            self.prior_in_flight = self.in_flight;
+            self.feedback_due = Some(now + self.config.feedback_interval);
+        }
+        self.in_flight -= sent.size;
+        if arrival_time.is_some() {
+            let sample = now.saturating_duration_since(sent.send_time);
+            self.rtt = Some(self.rtt.map_or(sample, |v| v.min(sample)));
+        }
+        self.results.push(PacketResult {
+            transport_sequence_number: sent.sequence,
+            send_time: sent.send_time,
+            arrival_time,
+            size: sent.size,
+            probe_cluster_id: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    // 受けたfeedbackを残すcontroller．
+    struct FakeController(Arc<Mutex<Vec<TransportFeedback>>>);
+
+    impl CongestionController for FakeController {
+        fn on_transport_feedback(&mut self, feedback: &TransportFeedback) {
+            self.0.lock().unwrap().push(feedback.clone());
+        }
+
+        fn set_bitrate(&mut self, _bitrate: u64) {}
+
+        fn get_target_bitrate(&self) -> u64 {
+            300_000
+        }
+    }
+
+    fn new_transport(mode: RoqMode) -> (RoqTransport, Arc<Mutex<Vec<TransportFeedback>>>) {
+        let feedbacks = Arc::new(Mutex::new(vec![]));
+        let config = RoqConfig {
+            mode,
+            ..RoqConfig::default()
+        };
+        let transport = RoqTransport::new(config, Box::new(FakeController(feedbacks.clone())));
+        (transport, feedbacks)
+    }
+
+    const RTP: [u8; 12] = [0x80, 96, 0, 1, 0, 0, 0, 1, 0, 0, 0, 2];
+    const RTCP: [u8; 8] = [0x80, 201, 0, 1, 0, 0, 0, 2];
+
+    #[test]
+    fn roq_datagram_test() {
+        let now = Instant::now();
+        let (mut a, feedbacks) = new_transport(RoqMode::Datagram);
+        let (mut b, _) = new_transport(RoqMode::Datagram);
+        a.send(64, &RTP, now).unwrap();
+        a.send(64, &RTCP, now).unwrap();
+        assert_eq!(
+            a.send(1 << 62, &RTP, now),
+            Err(RoqError::InvalidFlowId { flow_id: 1 << 62 })
+        );
+        let mut ids = vec![];
+        while let Some(v) = a.poll_transmit() {
+            match v {
+                RoqTransmit::Datagram { id, data } => {
+                    assert_eq!(&data[..2], &[0x40, 64]);
+                    b.handle_datagram(&data).unwrap();
+                    ids.push(id);
+                }
+                v => panic!("{:?}", v),
+            }
+        }
+        assert_eq!(
+            b.poll_event(),
+            Some(RoqEvent::Rtp {
+                flow_id: 64,
+                packet: RTP.to_vec()
+            })
+        );
+        assert_eq!(
+            b.poll_event(),
+            Some(RoqEvent::Rtcp {
+                flow_id: 64,
+                packet: RTCP.to_vec()
+            })
+        );
+        assert_eq!(b.handle_datagram(&[0]), Err(RoqError::InvalidDatagram));
+        assert_eq!(a.get_data_in_flight(), 14 + 10);
+
+        // the acks and the losses are batched for the controller.
+        let later = now + Duration::from_millis(10);
+        a.handle_datagram_acked(ids[0], later);
+        a.handle_datagram_lost(ids[1], later);
+        assert_eq!(a.get_data_in_flight(), 0);
+        assert_eq!(a.poll_timeout(), Some(later + Duration::from_millis(50)));
+        a.process(later);
+        assert!(feedbacks.lock().unwrap().is_empty());
+        a.process(later + Duration::from_millis(50));
+        let feedbacks = feedbacks.lock().unwrap();
+        assert_eq!(feedbacks.len(), 1);
+        let feedback = &feedbacks[0];
+        assert_eq!(feedback.prior_in_flight, 24);
+        assert_eq!(feedback.rtt, Some(Duration::from_millis(10)));
+        assert_eq!(feedback.packets[0].arrival_time, Some(later));
+        assert_eq!(feedback.get_lost_count(), 1);
+        assert_eq!(a.poll_timeout(), None);
+
+        // too long for a datagram.
+        a.send(1, &[0x80; 1300], now).unwrap();
+        match a.poll_transmit() {
+            Some(RoqTransmit::Stream { stream, data, fin }) => {
+                assert!(fin);
+                b.handle_stream(stream, &data, fin).unwrap();
+            }
+            v => panic!("{:?}", v),
+        }
+        assert!(matches!(
+            b.poll_event(),
+            Some(RoqEvent::Rtp { flow_id: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn roq_stream_test() {
+        let now = Instant::now();
+        let (mut a, _) = new_transport(RoqMode::Stream);
+        let (mut b, _) = new_transport(RoqMode::Stream);
+        a.send(3, &RTP, now).unwrap();
+        a.send(3, &RTCP, now).unwrap();
+        a.finish_stream(3);
+        a.send(3, &RTP, now).unwrap();
+        let mut data = vec![];
+        let mut streams = vec![];
+        while let Some(v) = a.poll_transmit() {
+            match v {
+                RoqTransmit::Stream {
+                    stream: 0,
+                    data: v,
+                    fin,
+                } => {
+                    // finished without data of its own.
+                    assert_eq!(fin, v.is_empty());
+                    data.extend(v);
+                }
+                RoqTransmit::Stream { stream, .. } => streams.push(stream),
+                v => panic!("{:?}", v),
+            }
+        }
+        assert_eq!(streams, vec![1]);
+        assert_eq!(&data[..2], &[3, 12]);
+
+        // read a byte at a time.
+        for (i, v) in data.iter().enumerate() {
+            b.handle_stream(0, &[*v], i + 1 == data.len()).unwrap();
+        }
+        assert_eq!(
+            b.poll_event(),
+            Some(RoqEvent::Rtp {
+                flow_id: 3,
+                packet: RTP.to_vec()
+            })
+        );
+        assert!(matches!(
+            b.poll_event(),
+            Some(RoqEvent::Rtcp { flow_id: 3, .. })
+        ));
+        assert_eq!(b.poll_event(), None);
+
+        // cut in a packet.
+        assert_eq!(
+            b.handle_stream(5, &data[..4], true),
+            Err(RoqError::InvalidStream { stream: 5 })
+        );
+        assert_eq!(
+            b.handle_stream(6, &[1, 4, 0, 0, 0, 0], false),
+            Err(RoqError::InvalidStream { stream: 6 })
+        );
+    }
+}
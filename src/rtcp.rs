@@ -1,4 +1,5 @@
 use crate::OctetsError;
+#[cfg(feature = "std")]
 use failure::Fail;
 
 pub mod packet;
@@ -7,48 +8,62 @@ pub mod report_block;
 pub mod good_bye;
 pub mod payload_specific_feedback;
 pub mod receiver_report;
+pub mod remb;
 pub mod rtp_feedback;
+#[cfg(feature = "std")]
+pub mod scheduler;
 pub mod sender_report;
 pub mod source_description;
+pub mod transport_feedback;
 
-pub type Result<T> = std::result::Result<T, RtcpError>;
+pub type Result<T> = core::result::Result<T, RtcpError>;
 
-#[derive(Fail, Debug, PartialEq)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "std", derive(Fail))]
 pub enum RtcpError {
-    #[fail(display = "Octets manipulate failed: {:?}", error)]
+    #[cfg_attr(feature = "std", fail(display = "Octets manipulate failed: {:?}", error))]
     OctetsError { error: OctetsError },
 
     /// The provided packet cannot be parsed because its version is unknown.
-    #[fail(display = "This RTP packet version is not 2.")]
+    #[cfg_attr(feature = "std", fail(display = "This RTP packet version is not 2."))]
     UnknownVersion,
 
     /// The provided packet cannot be parsed because its version is unknown.
-    #[fail(display = "Unknown Packet Type.")]
+    #[cfg_attr(feature = "std", fail(display = "Unknown Packet Type."))]
     UnknownPacketType,
 
     /// InvalidPacketHeader
-    #[fail(display = "Invalid packet header")]
+    #[cfg_attr(feature = "std", fail(display = "Invalid packet header"))]
     InvalidPacketHeader,
 
-    #[fail(display = "Packet header size is too short.")]
+    #[cfg_attr(feature = "std", fail(display = "Packet header size is too short."))]
     PacketHeaderTooShort,
 
-    #[fail(display = "Packet extension is broken.")]
+    #[cfg_attr(feature = "std", fail(display = "Packet extension is broken."))]
     InvalidPacketHeaderExtensionSize,
 
-    #[fail(display = "padding size calculation is failed.")]
+    #[cfg_attr(feature = "std", fail(display = "padding size calculation is failed."))]
     InvalidPaddingSize,
 
-    #[fail(display = "Packet size is invalid.")]
+    #[cfg_attr(feature = "std", fail(display = "SDES item is longer than 255 bytes."))]
+    InvalidSdesItemLength,
+
+    #[cfg_attr(feature = "std", fail(display = "Packet size is invalid."))]
     InvalidPacketLength,
 
-    #[fail(display = "RTCP payload-specific feedback length is invalid")]
+    #[cfg_attr(feature = "std", fail(display = "RTCP payload-specific feedback length is invalid"))]
     InvalidPsfbPacketLength,
 
-    #[fail(display = "RTCP receiver report length is invalid")]
+    #[cfg_attr(feature = "std", fail(display = "RTCP receiver report length is invalid"))]
     InvalidRrPacketLength,
 
-    #[fail(display = "Not implemented.")]
+    #[cfg_attr(feature = "std", fail(display = "RTCP REMB packet is invalid"))]
+    InvalidRembPacket,
+
+    #[cfg_attr(feature = "std", fail(display = "RTCP transport-cc feedback packet is invalid"))]
+    InvalidTransportFeedbackPacket,
+
+    #[cfg_attr(feature = "std", fail(display = "Not implemented."))]
     NotImplemented,
 }
 
@@ -22,6 +22,8 @@ use crate::rtcp::{Result, RtcpError};
 //use crate::{Result,Error};
 use crate::octets;
 
+use alloc::vec::Vec;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct RtcpGoodByePacket(pub Vec<u32>);
 
@@ -6,10 +6,12 @@ use crate::rtcp::payload_specific_feedback::RtcpPayloadSpecificFeedbackPacket;
 use crate::rtcp::receiver_report::RtcpReceiverReportPacket;
 use crate::rtcp::rtp_feedback::RtcpRtpFeedbackPacket;
 use crate::rtcp::sender_report::RtcpSenderReportPacket;
-use crate::rtcp::source_description::RtcpSourceDescriptionPacket;
+use crate::rtcp::source_description::{RtcpSourceDescriptionPacket, RtcpSourceDescriptionRef};
 
 use crate::octets;
 
+use alloc::vec::Vec;
+
 // https://www.geekpage.jp/technology/rtp/rtcp.php
 // http://www.ttc.or.jp/files/6112/8763/7422/2007_3Q_01.pdf
 
@@ -97,6 +99,29 @@ pub struct RtcpPacket {
 }
 
 impl RtcpPacket {
+    pub fn new(packet: RtcpPacketType) -> Self {
+        RtcpPacket { version: 2, packet }
+    }
+
+    pub fn get_packet(&self) -> &RtcpPacketType {
+        &self.packet
+    }
+
+    /// The bytes to_bytes writes, the header too. 0 for ApplicationDefined,
+    /// which it cannot write.
+    pub fn serialized_len(&self) -> usize {
+        let length = match &self.packet {
+            RtcpPacketType::SenderReport(v) => v.get_length(),
+            RtcpPacketType::ReceiverReport(v) => v.get_length(),
+            RtcpPacketType::SourceDescription(v) => v.get_length(),
+            RtcpPacketType::Goodbye(v) => v.get_length(),
+            RtcpPacketType::ApplicationDefined => return 0,
+            RtcpPacketType::RTPFeedback(v) => v.get_length(),
+            RtcpPacketType::PayloadSpecificFeedback(v) => v.get_length(),
+        };
+        4 + length as usize
+    }
+
     pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
         pack_rtcp_packet(&self.packet, out)?;
         Ok(())
@@ -163,10 +188,7 @@ impl RtcpPacket {
             _ => return Err(RtcpError::UnknownPacketType),
         };
 
-        Ok(RtcpPacket {
-            version,
-            packet: packet,
-        })
+        Ok(RtcpPacket { version, packet })
     }
 }
 
@@ -182,6 +204,11 @@ pub fn parse(bytes: &mut octets::Octets) -> Result<RtcpPacketList> {
     Ok(packet_list)
 }
 
+/// The bytes of the compound packet serialize writes.
+pub fn serialized_len(packets: &[RtcpPacket]) -> usize {
+    packets.iter().map(|v| v.serialized_len()).sum()
+}
+
 pub fn serialize(packets: RtcpPacketList, out: &mut octets::Octets) -> Result<()> {
     for packet in &packets {
         packet.to_bytes(out)?;
@@ -189,7 +216,99 @@ pub fn serialize(packets: RtcpPacketList, out: &mut octets::Octets) -> Result<()
     Ok(())
 }
 
+// 受けたbufferのRTCP packetを参照する．payloadはpaddingを除いたもの．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtcpPacketRef<'a> {
+    buf: &'a [u8],
+    payload: &'a [u8],
+}
+
+impl<'a> RtcpPacketRef<'a> {
+    /// The first packet of `buf`, which may be a compound packet.
+    pub fn from_slice(buf: &'a [u8]) -> Result<RtcpPacketRef<'a>> {
+        if buf.len() < 4 {
+            return Err(RtcpError::PacketHeaderTooShort);
+        }
+        if buf[0] >> 6 != 2 {
+            return Err(RtcpError::UnknownVersion);
+        }
+        let length = u16::from_be_bytes([buf[2], buf[3]]) as usize * 4;
+        if buf.len() < 4 + length {
+            return Err(RtcpError::InvalidPacketLength);
+        }
+        let buf = &buf[..4 + length];
+        let mut payload = &buf[4..];
+        if buf[0] & 0b00100000 > 0 {
+            let padding_length = *payload.last().ok_or(RtcpError::InvalidPaddingSize)? as usize;
+            if padding_length == 0 || padding_length > payload.len() {
+                return Err(RtcpError::InvalidPaddingSize);
+            }
+            payload = &payload[..payload.len() - padding_length];
+        }
+        Ok(RtcpPacketRef { buf, payload })
+    }
+
+    /// The packet, padding included.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn get_length(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn get_packet_type(&self) -> u8 {
+        self.buf[1]
+    }
+
+    /// The reports, the chunks or the sources, the format of the feedback.
+    pub fn get_count(&self) -> u8 {
+        self.buf[0] & 0b00011111
+    }
+
+    pub fn get_payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// The SSRC of the sender, of the first chunk or source, the first word
+    /// of the payload.
+    pub fn get_ssrc(&self) -> Option<u32> {
+        let v = self.payload.get(..4)?;
+        Some(u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    pub fn get_source_description(&self) -> Option<RtcpSourceDescriptionRef<'a>> {
+        if self.get_packet_type() != RTCP_SDES {
+            return None;
+        }
+        Some(RtcpSourceDescriptionRef::new(self.payload, self.get_count()))
+    }
+
+    /// The RtcpPacket parsed from a copy of it.
+    pub fn to_owned(&self) -> Result<RtcpPacket> {
+        let mut buf = self.buf.to_vec();
+        RtcpPacket::from_bytes(&mut octets::Octets::with_slice(&mut buf))
+    }
+}
+
+/// The packets of a compound packet, until one is broken.
+pub fn parse_ref(buf: &[u8]) -> impl Iterator<Item = Result<RtcpPacketRef<'_>>> {
+    let mut rest = buf;
+    core::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let packet = RtcpPacketRef::from_slice(rest);
+        rest = match packet {
+            Ok(ref v) => &rest[v.get_length()..],
+            Err(_) => &[],
+        };
+        Some(packet)
+    })
+}
+
 #[cfg(test)]
+#[allow(unused_imports)]
 mod test {
     use super::*;
     use crate::octets;
@@ -456,10 +575,10 @@ mod test {
                 RtcpSourceDescriptionPacket::new(
                     vec![RtcpSourceDescriptionChunk::new(
                         1831097322, 
-                        vec![RtcpSourceDescriptionItem {
-                            item_type: 1,
-                            data: "{63f459ea-41fe-4474-9d33-9707c9ee79d1}".as_bytes().to_vec(),
-                        }]
+                        vec![RtcpSourceDescriptionItem::new(
+                            1,
+                            "{63f459ea-41fe-4474-9d33-9707c9ee79d1}".as_bytes(),
+                        ).unwrap()]
                     )]
                 )
             )
@@ -473,6 +592,11 @@ mod test {
         assert!(sdes.to_bytes(&mut ser).is_ok());
         assert_eq!(raw_octet, ser);
         assert_eq!(raw_packet[..], buf[..]); //長さを消すために，この様に書いている
+
+        assert_eq!(
+            RtcpSourceDescriptionItem::new(1, &[0; 256]),
+            Err(RtcpError::InvalidSdesItemLength)
+        );
     }
 
     #[test]
@@ -493,5 +617,62 @@ mod test {
         let parse_sdes = RtcpPacket::from_bytes(&mut raw_octet);
 
         assert_eq!(parse_sdes, Err(RtcpError::OctetsError { error: OctetsError::BufferTooShort}));
+
+        let packet = RtcpPacketRef::from_slice(&raw_packet).unwrap();
+        let mut chunks = packet.get_source_description().unwrap().get_chunks();
+        assert_eq!(
+            chunks.next(),
+            Some(Err(RtcpError::OctetsError { error: OctetsError::BufferTooShort }))
+        );
+        assert_eq!(chunks.next(), None);
+    }
+
+    #[test]
+    fn rtcp_packet_ref_test() {
+        let sdes = [
+            0x81, 0xCA, 0x00, 0x0C, 0x6D, 0x24, 0x53, 0xEA, 0x01, 0x26, 0x7B, 0x36, 0x33, 0x66,
+            0x34, 0x35, 0x39, 0x65, 0x61, 0x2D, 0x34, 0x31, 0x66, 0x65, 0x2D, 0x34, 0x34, 0x37,
+            0x34, 0x2D, 0x39, 0x64, 0x33, 0x33, 0x2D, 0x39, 0x37, 0x30, 0x37, 0x63, 0x39, 0x65,
+            0x65, 0x37, 0x39, 0x64, 0x31, 0x7D, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let pli = [
+            0x81, 0xCE, 0x00, 0x02, 0x54, 0x50, 0x62, 0x65, 0x23, 0x01, 0x3F, 0xB9,
+        ];
+        let mut compound = sdes.to_vec();
+        compound.extend_from_slice(&pli);
+
+        let packets = parse_ref(&compound).collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].get_packet_type(), RTCP_SDES);
+        assert_eq!(packets[0].get_ssrc(), Some(1831097322));
+        assert_eq!(packets[1].as_bytes(), &pli[..]);
+        assert_eq!(packets[1].get_count(), 1);
+        assert_eq!(packets[1].get_source_description(), None);
+
+        // the data of the item is in the buffer.
+        let description = packets[0].get_source_description().unwrap();
+        let chunk = description.get_chunks().next().unwrap().unwrap();
+        assert_eq!(chunk.get_ssrc(), 1831097322);
+        let items: Vec<_> = chunk.get_items().collect();
+        assert_eq!(items, vec![(1, &compound[10..48])]);
+        assert_eq!(items[0].1.as_ptr(), compound[10..].as_ptr());
+
+        let mut buf = compound.clone();
+        let owned = parse(&mut octets::Octets::with_slice(&mut buf)).unwrap();
+        assert_eq!(packets[0].to_owned().unwrap(), owned[0]);
+        assert_eq!(packets[1].to_owned().unwrap(), owned[1]);
+        assert_eq!(
+            owned[0].get_packet(),
+            &RtcpPacketType::SourceDescription(description.to_owned().unwrap())
+        );
+
+        assert_eq!(serialized_len(&owned), compound.len());
+        let mut buf = Vec::new();
+        serialize(owned, &mut octets::Octets::with_vec(&mut buf)).unwrap();
+        assert_eq!(buf, compound);
+
+        let truncated: Vec<_> = parse_ref(&compound[..compound.len() - 1]).collect();
+        assert_eq!(truncated.len(), 2);
+        assert_eq!(truncated[1], Err(RtcpError::InvalidPacketLength));
     }
 }
@@ -36,6 +36,17 @@ use crate::rtcp::{Result, RtcpError};
 //use crate::{Result,Error};
 use crate::octets;
 
+use alloc::vec::Vec;
+
+// https://tools.ietf.org/html/rfc4585#section-6.3
+pub const PSFB_PLI: u8 = 1;
+pub const PSFB_SLI: u8 = 2;
+pub const PSFB_RPSI: u8 = 3;
+// https://tools.ietf.org/html/rfc5104#section-4.3
+pub const PSFB_FIR: u8 = 4;
+// Application layer feedback (REMB)
+pub const PSFB_AFB: u8 = 15;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct RtcpPayloadSpecificFeedbackPacket {
     format: u8,
@@ -62,6 +73,18 @@ impl RtcpPayloadSpecificFeedbackPacket {
         self.format
     }
 
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn get_media_ssrc(&self) -> u32 {
+        self.media_ssrc
+    }
+
+    pub fn get_fci(&self) -> &[u8] {
+        &self.fci
+    }
+
     pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
         out.put_u32(self.ssrc)?;
         out.put_u32(self.media_ssrc)?;
@@ -37,8 +37,9 @@ use crate::rtcp::{Result, RtcpError};
 //use crate::{Result,Error};
 use crate::octets;
 
+use alloc::vec::Vec;
+
 const RTCP_HEADER_LENGTH: usize = 4; // ssrc size
-const RTCP_SR_INFO_LENGTH: usize = 20;
 const RTCP_REPORT_BLOCK_LENGTH: usize = 24;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
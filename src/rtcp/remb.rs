@@ -0,0 +1,159 @@
+// https://tools.ietf.org/html/draft-alvestrand-rmcat-remb-03
+
+/*
+    0                   1                   2                   3
+    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |V=2|P| FMT=15  |   PT=206      |             length            |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                  SSRC of packet sender                        |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                  SSRC of media source                         |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |  Unique identifier 'R' 'E' 'M' 'B'                            |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |  Num SSRC     | BR Exp    |  BR Mantissa                      |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |   SSRC feedback                                               |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |  ...                                                          |
+*/
+
+use crate::octets;
+use crate::rtcp::payload_specific_feedback::{RtcpPayloadSpecificFeedbackPacket, PSFB_AFB};
+use crate::rtcp::{Result, RtcpError};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+const REMB_IDENTIFIER: u32 = 0x5245_4d42; // "REMB"
+const REMB_MANTISSA_MAX: u64 = 0x3ffff; // 18bit
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtcpRembPacket {
+    ssrc: u32,
+    bitrate: u64, // bps
+    ssrcs: Vec<u32>,
+}
+
+impl RtcpRembPacket {
+    pub fn new(ssrc: u32, bitrate: u64, ssrcs: Vec<u32>) -> Self {
+        RtcpRembPacket {
+            ssrc,
+            bitrate,
+            ssrcs,
+        }
+    }
+
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn get_bitrate(&self) -> u64 {
+        self.bitrate
+    }
+
+    pub fn get_ssrcs(&self) -> &[u32] {
+        &self.ssrcs
+    }
+
+    pub fn get_length(&self) -> u32 {
+        4 + 4 + self.ssrcs.len() as u32 * 4
+    }
+
+    pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
+        let mut exp = 0u32;
+        let mut mantissa = self.bitrate;
+        while mantissa > REMB_MANTISSA_MAX {
+            mantissa >>= 1;
+            exp += 1;
+        }
+
+        out.put_u32(REMB_IDENTIFIER)?;
+        out.put_u8(self.ssrcs.len() as u8)?;
+        out.put_u24((exp << 18) | mantissa as u32)?;
+        for ssrc in &self.ssrcs {
+            out.put_u32(*ssrc)?;
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(bytes: &mut octets::Octets, ssrc: u32) -> Result<RtcpRembPacket> {
+        if bytes.cap() < 8 || bytes.get_u32()? != REMB_IDENTIFIER {
+            return Err(RtcpError::InvalidRembPacket);
+        }
+
+        let count = bytes.get_u8()?;
+        let b = bytes.get_u24()?;
+        let exp = b >> 18;
+        let mantissa = u64::from(b & REMB_MANTISSA_MAX as u32);
+
+        if bytes.cap() < count as usize * 4 {
+            return Err(RtcpError::InvalidRembPacket);
+        }
+        let ssrcs = (0..count)
+            .map(|_| bytes.get_u32())
+            .collect::<core::result::Result<Vec<_>, _>>()?;
+
+        Ok(RtcpRembPacket {
+            ssrc,
+            bitrate: mantissa << exp,
+            ssrcs,
+        })
+    }
+
+    pub fn from_psfb(packet: &RtcpPayloadSpecificFeedbackPacket) -> Result<RtcpRembPacket> {
+        if packet.get_format() != PSFB_AFB {
+            return Err(RtcpError::InvalidRembPacket);
+        }
+        let mut fci = packet.get_fci().to_vec();
+        let mut b = octets::Octets::with_slice(&mut fci);
+        RtcpRembPacket::from_bytes(&mut b, packet.get_ssrc())
+    }
+
+    pub fn to_psfb(&self) -> Result<RtcpPayloadSpecificFeedbackPacket> {
+        let mut fci = vec![0u8; self.get_length() as usize];
+        {
+            let mut b = octets::Octets::with_slice(&mut fci);
+            self.to_bytes(&mut b)?;
+        }
+        Ok(RtcpPayloadSpecificFeedbackPacket::new(
+            PSFB_AFB, self.ssrc, 0, fci,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtcp::packet::{RtcpPacket, RtcpPacketType};
+
+    #[test]
+    fn rtcp_remb_test() {
+        let mut raw_packet = [
+            0x8f, 0xce, 0x00, 0x05, // header
+            0x00, 0x00, 0x00, 0x01, // sender ssrc
+            0x00, 0x00, 0x00, 0x00, // media ssrc
+            0x52, 0x45, 0x4d, 0x42, // REMB
+            0x01, 0x1a, 0x20, 0xdb, // count, exp, mantissa
+            0x00, 0x00, 0x12, 0x34, // ssrc feedback
+        ];
+
+        let mut raw_octet = octets::Octets::with_slice(&mut raw_packet);
+        let packet = RtcpPacket::from_bytes(&mut raw_octet).unwrap();
+
+        let remb = match packet.get_packet() {
+            RtcpPacketType::PayloadSpecificFeedback(v) => RtcpRembPacket::from_psfb(v).unwrap(),
+            _ => panic!("not a psfb packet"),
+        };
+        assert_eq!(remb, RtcpRembPacket::new(1, 8926912, vec![0x1234]));
+
+        let out = RtcpPacket::new(RtcpPacketType::PayloadSpecificFeedback(
+            remb.to_psfb().unwrap(),
+        ));
+        let mut buf = [0u8; 24];
+        let mut ser = octets::Octets::with_slice(&mut buf);
+        assert!(out.to_bytes(&mut ser).is_ok());
+        assert_eq!(raw_packet, buf);
+    }
+}
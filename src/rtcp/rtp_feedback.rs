@@ -33,15 +33,44 @@ use crate::rtcp::{Result, RtcpError};
 //use crate::{Result,Error};
 use crate::octets;
 
+use alloc::vec::Vec;
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct RtcpRtpFeedbackPacket {
     format: u8,      // 1bytes
     ssrc: u32,       // 4bytes
     media_ssrc: u32, // 4bytes
     lost: Option<Vec<u16>>,
+    fci: Vec<u8>, // NACK以外のformatのFCI
 }
 
+// https://tools.ietf.org/html/rfc4585#section-6.2
+pub const RTPFB_NACK: u8 = 1;
+// https://tools.ietf.org/html/draft-holmer-rmcat-transport-wide-cc-extensions-01
+pub const RTPFB_TWCC: u8 = 15;
+
 impl RtcpRtpFeedbackPacket {
+    pub fn new(format: u8, ssrc: u32, media_ssrc: u32, lost: Option<Vec<u16>>) -> Self {
+        RtcpRtpFeedbackPacket {
+            format,
+            ssrc,
+            media_ssrc,
+            lost,
+            fci: Vec::new(),
+        }
+    }
+
+    /// Feedback whose FCI is not a NACK list, e.g. transport-cc.
+    pub fn with_fci(format: u8, ssrc: u32, media_ssrc: u32, fci: Vec<u8>) -> Self {
+        RtcpRtpFeedbackPacket {
+            format,
+            ssrc,
+            media_ssrc,
+            lost: None,
+            fci,
+        }
+    }
+
     pub fn get_length(&self) -> u32 {
         let mut b_length = 4 + 4;
 
@@ -61,13 +90,32 @@ impl RtcpRtpFeedbackPacket {
             b_length += 4
         }
 
-        b_length
+        b_length + self.fci.len() as u32
     }
 
     pub fn get_format(&self) -> u8 {
         self.format
     }
 
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn get_media_ssrc(&self) -> u32 {
+        self.media_ssrc
+    }
+
+    pub fn get_lost(&self) -> &[u16] {
+        match self.lost {
+            Some(ref v) => v,
+            None => &[],
+        }
+    }
+
+    pub fn get_fci(&self) -> &[u8] {
+        &self.fci
+    }
+
     pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
         out.put_u32(self.ssrc)?;
         out.put_u32(self.media_ssrc)?;
@@ -89,6 +137,7 @@ impl RtcpRtpFeedbackPacket {
             out.put_u16(pid)?;
             out.put_u16(blp)?;
         }
+        out.put_bytes(&self.fci)?;
 
         Ok(())
     }
@@ -97,13 +146,22 @@ impl RtcpRtpFeedbackPacket {
         // 8bytes = ssrc + media_ssrc
         // packet length = 8 + 4 * k [bytes]
         // k is feedback control information counts
-        if bytes.len() < 8 || bytes.len() % 4 != 0 {
+        if bytes.len() < 8 || !bytes.len().is_multiple_of(4) {
             return Err(RtcpError::InvalidPacketLength);
         }
 
         let ssrc = bytes.get_u32()?;
         let media_ssrc = bytes.get_u32()?;
 
+        if format != RTPFB_NACK {
+            return Ok(RtcpRtpFeedbackPacket::with_fci(
+                format,
+                ssrc,
+                media_ssrc,
+                bytes.to_vec(),
+            ));
+        }
+
         let fci_count = (bytes.len() - 8) / 4;
 
         let lost = if fci_count > 0 {
@@ -131,6 +189,7 @@ impl RtcpRtpFeedbackPacket {
             ssrc,
             media_ssrc,
             lost,
+            fci: Vec::new(),
         })
     }
 }
@@ -0,0 +1,345 @@
+// https://tools.ietf.org/html/rfc3550#section-6.3
+// https://tools.ietf.org/html/rfc3550#appendix-A.7
+
+/*
+    the interval of compound RTCP packets, 5% of the session bandwidth
+    shared by the members, a quarter of it by the senders while they are a
+    quarter of the members or less:
+
+      T = max(avg_rtcp_size * n / rtcp_bw, Tmin) * [0.5, 1.5] / (e - 3/2)
+
+    Tmin is 5s, 360 / session bandwidth in kbps with the reduced minimum,
+    and half of it before the first packet. the next packet is
+    reconsidered when it is due (timer reconsideration) and moved closer
+    when members leave (reverse reconsideration). a sender is not one
+    after 2 intervals without RTP, a member is removed after 5 without
+    RTCP.
+
+    the sizes are of the packets with the UDP and IP headers.
+*/
+
+use crate::sansio::StateMachine;
+use crate::time::Instant;
+
+use rand::Rng;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+
+const COMPENSATION: f64 = std::f64::consts::E - 1.5;
+const INITIAL_RTCP_SIZE: f64 = 128.0;
+const SENDER_TIMEOUT_INTERVALS: u32 = 2;
+const MEMBER_TIMEOUT_INTERVALS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RtcpSchedulerConfig {
+    /// The bandwidth of the session in bps, RTP and RTCP.
+    pub session_bandwidth: u64,
+    /// The part of it for RTCP.
+    pub rtcp_fraction: f64,
+    pub min_interval: Duration,
+    /// 360 / session bandwidth in kbps instead of min_interval.
+    pub reduced_minimum: bool,
+}
+
+impl Default for RtcpSchedulerConfig {
+    fn default() -> Self {
+        RtcpSchedulerConfig {
+            session_bandwidth: 1_000_000,
+            rtcp_fraction: 0.05,
+            min_interval: Duration::from_secs(5),
+            reduced_minimum: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RtcpInput {
+    RtpSent,
+    RtpReceived { ssrc: u32 },
+    RtcpSent { size: usize },
+    RtcpReceived { ssrc: u32, size: usize },
+    Bye { ssrc: u32 },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RtcpOutput {
+    /// Send a compound packet, a sender report when `sender`.
+    Send { sender: bool },
+}
+
+// 他のmemberが最後にRTCPとRTPを送った時刻．
+#[derive(Debug, Clone, Copy)]
+struct Member {
+    rtcp_at: Instant,
+    rtp_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RtcpScheduler {
+    config: RtcpSchedulerConfig,
+    members: HashMap<u32, Member>,
+    pmembers: usize,
+    rtp_sent_at: Option<Instant>,
+    avg_rtcp_size: f64,
+    initial: bool,
+    tp: Instant,
+    tn: Instant,
+}
+
+impl RtcpScheduler {
+    pub fn new(config: RtcpSchedulerConfig, now: Instant) -> Self {
+        let mut scheduler = RtcpScheduler {
+            config,
+            members: HashMap::new(),
+            pmembers: 1,
+            rtp_sent_at: None,
+            avg_rtcp_size: INITIAL_RTCP_SIZE,
+            initial: true,
+            tp: now,
+            tn: now,
+        };
+        scheduler.tn = now + scheduler.get_interval(true);
+        scheduler
+    }
+
+    /// The members with us.
+    pub fn get_members(&self) -> usize {
+        self.members.len() + 1
+    }
+
+    pub fn get_senders(&self) -> usize {
+        let senders = self.members.values().filter(|v| v.rtp_at.is_some()).count();
+        senders + usize::from(self.is_sender())
+    }
+
+    pub fn is_sender(&self) -> bool {
+        self.rtp_sent_at.is_some()
+    }
+
+    pub fn get_avg_rtcp_size(&self) -> f64 {
+        self.avg_rtcp_size
+    }
+
+    /// When the next packet is due, before it is reconsidered.
+    pub fn get_next_time(&self) -> Instant {
+        self.tn
+    }
+
+    /// T of the top, the deterministic one without `randomized`.
+    fn get_interval(&self, randomized: bool) -> Duration {
+        let mut min_interval = if self.config.reduced_minimum {
+            let kbps = (self.config.session_bandwidth as f64 / 1000.0).max(1.0);
+            Duration::from_secs_f64(360.0 / kbps).min(self.config.min_interval)
+        } else {
+            self.config.min_interval
+        };
+        if self.initial {
+            min_interval /= 2;
+        }
+        let members = self.get_members() as f64;
+        let senders = self.get_senders() as f64;
+        let mut rtcp_bw = self.config.session_bandwidth as f64 / 8.0 * self.config.rtcp_fraction;
+        let mut n = members;
+        if senders <= members * 0.25 {
+            if self.is_sender() {
+                rtcp_bw *= 0.25;
+                n = senders;
+            } else {
+                rtcp_bw *= 0.75;
+                n -= senders;
+            }
+        }
+        let interval = if rtcp_bw > 0.0 {
+            Duration::from_secs_f64(self.avg_rtcp_size * n / rtcp_bw).max(min_interval)
+        } else {
+            min_interval
+        };
+        if !randomized {
+            return interval;
+        }
+        interval.mul_f64(rand::thread_rng().gen_range(0.5, 1.5) / COMPENSATION)
+    }
+
+    fn update_avg_rtcp_size(&mut self, size: usize) {
+        self.avg_rtcp_size = size as f64 / 16.0 + self.avg_rtcp_size * 15.0 / 16.0;
+    }
+
+    fn remove_timed_out(&mut self, now: Instant) {
+        let interval = self.get_interval(false);
+        let sender_timeout = interval * SENDER_TIMEOUT_INTERVALS;
+        let member_timeout = interval * MEMBER_TIMEOUT_INTERVALS;
+        let timed_out = |v: Instant, timeout: Duration| now.saturating_duration_since(v) > timeout;
+        if self
+            .rtp_sent_at
+            .is_some_and(|v| timed_out(v, sender_timeout))
+        {
+            self.rtp_sent_at = None;
+        }
+        for member in self.members.values_mut() {
+            if member.rtp_at.is_some_and(|v| timed_out(v, sender_timeout)) {
+                member.rtp_at = None;
+            }
+        }
+        self.members
+            .retain(|_, v| !timed_out(v.rtcp_at, member_timeout));
+        self.reconsider_reverse(now);
+    }
+
+    fn reconsider_reverse(&mut self, now: Instant) {
+        let members = self.get_members();
+        if members >= self.pmembers {
+            return;
+        }
+        let ratio = members as f64 / self.pmembers as f64;
+        self.tn = now + self.tn.saturating_duration_since(now).mul_f64(ratio);
+        self.tp = now - now.saturating_duration_since(self.tp).mul_f64(ratio);
+        self.pmembers = members;
+    }
+
+    fn get_member(&mut self, ssrc: u32, now: Instant) -> &mut Member {
+        self.members.entry(ssrc).or_insert(Member {
+            rtcp_at: now,
+            rtp_at: None,
+        })
+    }
+}
+
+impl StateMachine for RtcpScheduler {
+    type Input = RtcpInput;
+    type Output = RtcpOutput;
+    type Error = Infallible;
+
+    fn handle_input(&mut self, input: RtcpInput, now: Instant) -> Result<(), Infallible> {
+        match input {
+            RtcpInput::RtpSent => self.rtp_sent_at = Some(now),
+            RtcpInput::RtpReceived { ssrc } => self.get_member(ssrc, now).rtp_at = Some(now),
+            RtcpInput::RtcpSent { size } => self.update_avg_rtcp_size(size),
+            RtcpInput::RtcpReceived { ssrc, size } => {
+                self.get_member(ssrc, now).rtcp_at = now;
+                self.update_avg_rtcp_size(size);
+            }
+            RtcpInput::Bye { ssrc } => {
+                self.members.remove(&ssrc);
+                self.reconsider_reverse(now);
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_output(&mut self, now: Instant) -> Option<RtcpOutput> {
+        if now < self.tn {
+            return None;
+        }
+        self.remove_timed_out(now);
+        self.pmembers = self.get_members();
+        let tn = self.tp + self.get_interval(true);
+        if tn > now {
+            self.tn = tn;
+            return None;
+        }
+        self.tp = now;
+        self.initial = false;
+        self.tn = now + self.get_interval(true);
+        Some(RtcpOutput::Send {
+            sender: self.is_sender(),
+        })
+    }
+
+    fn poll_timeout(&self) -> Option<Instant> {
+        Some(self.tn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn randomized(interval: f64) -> std::ops::RangeInclusive<Duration> {
+        Duration::from_secs_f64(interval * 0.5 / COMPENSATION)
+            ..=Duration::from_secs_f64(interval * 1.5 / COMPENSATION)
+    }
+
+    // RtcpOutputが出るまで時計を進める．
+    fn run(scheduler: &mut RtcpScheduler, mut now: Instant) -> (Instant, RtcpOutput) {
+        loop {
+            now = scheduler.poll_timeout().unwrap().max(now);
+            if let Some(v) = scheduler.poll_output(now) {
+                return (now, v);
+            }
+        }
+    }
+
+    #[test]
+    fn interval_test() {
+        let start = Instant::now();
+        let mut scheduler = RtcpScheduler::new(RtcpSchedulerConfig::default(), start);
+        assert!(randomized(2.5).contains(&(scheduler.get_next_time() - start)));
+        assert_eq!(scheduler.poll_output(start), None);
+
+        let (now, output) = run(&mut scheduler, start);
+        assert_eq!(output, RtcpOutput::Send { sender: false });
+        assert!(now - start <= *randomized(2.5).end());
+        assert!(randomized(5.0).contains(&(scheduler.get_next_time() - now)));
+
+        scheduler.handle_input(RtcpInput::RtpSent, now).unwrap();
+        scheduler
+            .handle_input(RtcpInput::RtcpReceived { ssrc: 1, size: 100 }, now)
+            .unwrap();
+        assert_eq!(scheduler.get_members(), 2);
+        assert_eq!(scheduler.get_senders(), 1);
+        let (now, output) = run(&mut scheduler, now);
+        assert_eq!(output, RtcpOutput::Send { sender: true });
+
+        // no RTP for 2 intervals, and no RTCP of 1 for 5.
+        let (now, _) = run(&mut scheduler, now + Duration::from_secs(11));
+        assert!(!scheduler.is_sender());
+        assert_eq!(scheduler.get_members(), 2);
+        run(&mut scheduler, now + Duration::from_secs(26));
+        assert_eq!(scheduler.get_members(), 1);
+
+        let config = RtcpSchedulerConfig {
+            reduced_minimum: true,
+            ..RtcpSchedulerConfig::default()
+        };
+        let scheduler = RtcpScheduler::new(config, start);
+        assert!(randomized(0.18).contains(&(scheduler.get_next_time() - start)));
+    }
+
+    #[test]
+    fn reconsideration_test() {
+        // 3200bps of RTCP, 3/4 of it for the receivers.
+        let config = RtcpSchedulerConfig {
+            session_bandwidth: 64_000,
+            ..RtcpSchedulerConfig::default()
+        };
+        let now = Instant::now();
+        let mut scheduler = RtcpScheduler::new(config, now);
+        let first = scheduler.get_next_time();
+        for ssrc in 0..999 {
+            let input = RtcpInput::RtcpReceived {
+                ssrc,
+                size: INITIAL_RTCP_SIZE as usize,
+            };
+            scheduler.handle_input(input, now).unwrap();
+        }
+        assert_eq!(scheduler.get_members(), 1000);
+        assert_eq!(
+            scheduler.get_interval(false),
+            Duration::from_secs_f64(128.0 * 1000.0 / 300.0)
+        );
+
+        // reconsidered when it is due.
+        assert_eq!(scheduler.poll_output(first), None);
+        let later = scheduler.get_next_time();
+        assert!(later - now >= *randomized(128.0 * 1000.0 / 300.0).start());
+
+        for ssrc in 0..900 {
+            scheduler
+                .handle_input(RtcpInput::Bye { ssrc }, now)
+                .unwrap();
+        }
+        assert_eq!(scheduler.get_members(), 100);
+        assert!(scheduler.get_next_time() - now <= (later - now) / 5);
+    }
+}
@@ -4,6 +4,8 @@ use crate::rtcp::{Result, RtcpError};
 //use crate::{Result,Error};
 use crate::octets;
 
+use alloc::vec::Vec;
+
 const RTCP_HEADER_LENGTH: usize = 4; // ssrc = 32bit = 4bytes
 const RTCP_SR_INFO_LENGTH: usize = 20;
 const RTCP_REPORT_BLOCK_LENGTH: usize = 24;
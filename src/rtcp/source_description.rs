@@ -22,18 +22,60 @@ chunk  |                          SSRC/CSRC_2                          |
 
 use crate::octets;
 use crate::rtcp::{Result, RtcpError};
+use crate::OctetsError;
+
+use core::fmt;
+use alloc::vec::Vec;
+
+/// The longest data of an SDES item, its length is an octet.
+pub const MAX_SDES_ITEM_LENGTH: usize = 255;
 
 fn get_padding(len: usize) -> usize {
-    if len % 4 == 0 {
+    if len.is_multiple_of(4) {
         return 0;
     }
-    return 4 - (len % 4);
+    4 - (len % 4)
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+// dataはheapに置かない．lengthより後ろは0．
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
 pub struct RtcpSourceDescriptionItem {
     pub item_type: u8,
-    pub data: Vec<u8>,
+    length: u8,
+    data: [u8; MAX_SDES_ITEM_LENGTH],
+}
+
+impl RtcpSourceDescriptionItem {
+    pub fn new(item_type: u8, data: &[u8]) -> Result<Self> {
+        if data.len() > MAX_SDES_ITEM_LENGTH {
+            return Err(RtcpError::InvalidSdesItemLength);
+        }
+        Ok(RtcpSourceDescriptionItem::with_data(item_type, data))
+    }
+
+    // dataの長さは確かめてある．
+    fn with_data(item_type: u8, data: &[u8]) -> Self {
+        let mut item = RtcpSourceDescriptionItem {
+            item_type,
+            length: data.len() as u8,
+            data: [0; MAX_SDES_ITEM_LENGTH],
+        };
+        item.data[..data.len()].copy_from_slice(data);
+        item
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data[..self.length as usize]
+    }
+}
+
+impl fmt::Debug for RtcpSourceDescriptionItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RtcpSourceDescriptionItem")
+            .field("item_type", &self.item_type)
+            .field("data", &self.get_data())
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -49,7 +91,7 @@ impl RtcpSourceDescriptionChunk {
 
     pub fn get_length(&self) -> u32 {
         let mut b_length = 4;
-        b_length += self.items.iter().fold(0, |sum, a| sum + 2 + a.data.len());
+        b_length += self.items.iter().fold(0, |sum, a| sum + 2 + a.length as usize);
         b_length += 1;
         b_length += get_padding(b_length);
 
@@ -60,8 +102,8 @@ impl RtcpSourceDescriptionChunk {
         out.put_u32(self.ssrc)?;
         for item in &self.items {
             out.put_u8(item.item_type)?;
-            out.put_u8(item.data.len() as u8)?;
-            out.put_bytes(&item.data)?;
+            out.put_u8(item.length)?;
+            out.put_bytes(item.get_data())?;
         }
         // add END flag
         out.put_u8(0)?;
@@ -100,9 +142,11 @@ impl RtcpSourceDescriptionChunk {
                 break;
             }
             let length = bytes.get_u8()?;
-            let data = bytes.get_bytes(length as usize)?.to_vec();
-
-            items.push(RtcpSourceDescriptionItem { item_type, data });
+            let data = bytes.get_bytes(length as usize)?;
+            items.push(RtcpSourceDescriptionItem::with_data(
+                item_type,
+                data.as_ref(),
+            ));
         }
         Ok(RtcpSourceDescriptionChunk { ssrc, items })
     }
@@ -147,3 +191,120 @@ impl RtcpSourceDescriptionPacket {
         Ok(RtcpSourceDescriptionPacket { chunks })
     }
 }
+
+// SDES packetのpayloadを参照する．itemのdataをcopyしない．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtcpSourceDescriptionRef<'a> {
+    count: u8,
+    payload: &'a [u8],
+}
+
+impl<'a> RtcpSourceDescriptionRef<'a> {
+    /// The payload of an SDES packet of `count` chunks, without padding.
+    pub fn new(payload: &'a [u8], count: u8) -> Self {
+        RtcpSourceDescriptionRef { count, payload }
+    }
+
+    pub fn get_chunks(&self) -> RtcpSourceDescriptionChunks<'a> {
+        RtcpSourceDescriptionChunks {
+            remaining: self.count,
+            payload: self.payload,
+            off: 0,
+        }
+    }
+
+    pub fn to_owned(&self) -> Result<RtcpSourceDescriptionPacket> {
+        let chunks = self
+            .get_chunks()
+            .map(|v| v.map(|v| v.to_owned()))
+            .collect::<Result<_>>()?;
+        Ok(RtcpSourceDescriptionPacket { chunks })
+    }
+}
+
+/// The chunks of an SDES packet, until one is broken.
+#[derive(Debug, Clone)]
+pub struct RtcpSourceDescriptionChunks<'a> {
+    remaining: u8,
+    payload: &'a [u8],
+    off: usize,
+}
+
+impl<'a> RtcpSourceDescriptionChunks<'a> {
+    fn parse(&mut self) -> Result<RtcpSourceDescriptionChunkRef<'a>> {
+        let too_short = || RtcpError::from(OctetsError::BufferTooShort);
+        let rest = &self.payload[self.off..];
+        if rest.len() < 4 {
+            return Err(too_short());
+        }
+        let ssrc = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let mut length = 4;
+        // END check.
+        while *rest.get(length).ok_or_else(too_short)? != 0 {
+            let item_length = *rest.get(length + 1).ok_or_else(too_short)? as usize;
+            length += 2 + item_length;
+            if rest.len() < length {
+                return Err(too_short());
+            }
+        }
+        let items = &rest[4..length];
+        length += 1;
+        length += get_padding(self.off + length);
+        if rest.len() < length {
+            return Err(too_short());
+        }
+        self.off += length;
+        Ok(RtcpSourceDescriptionChunkRef { ssrc, items })
+    }
+}
+
+impl<'a> Iterator for RtcpSourceDescriptionChunks<'a> {
+    type Item = Result<RtcpSourceDescriptionChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let chunk = self.parse();
+        if chunk.is_err() {
+            self.remaining = 0;
+        }
+        Some(chunk)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct RtcpSourceDescriptionChunkRef<'a> {
+    ssrc: u32,
+    /// The items before END.
+    items: &'a [u8],
+}
+
+impl<'a> RtcpSourceDescriptionChunkRef<'a> {
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// The type and the data of the items.
+    pub fn get_items(&self) -> impl Iterator<Item = (u8, &'a [u8])> + 'a {
+        let mut items = self.items;
+        core::iter::from_fn(move || {
+            let (item_type, length) = (*items.first()?, *items.get(1)? as usize);
+            let data = &items[2..2 + length];
+            items = &items[2 + length..];
+            Some((item_type, data))
+        })
+    }
+
+    pub fn to_owned(self) -> RtcpSourceDescriptionChunk {
+        let items = self
+            .get_items()
+            .map(|(item_type, data)| RtcpSourceDescriptionItem::with_data(item_type, data))
+            .collect();
+        RtcpSourceDescriptionChunk {
+            ssrc: self.ssrc,
+            items,
+        }
+    }
+}
@@ -0,0 +1,382 @@
+// https://tools.ietf.org/html/draft-holmer-rmcat-transport-wide-cc-extensions-01#section-3.1
+
+/*
+    0                   1                   2                   3
+    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |V=2|P|  FMT=15 |    PT=205     |           length              |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                     SSRC of packet sender                     |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                      SSRC of media source                     |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |      base sequence number     |      packet status count      |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                 reference time                | fb pkt. count |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |          packet chunk         |         packet chunk          |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   .                                                               .
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |         packet chunk          |  recv delta   |  recv delta   |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   .                                                               .
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |           recv delta          |  recv delta   | zero padding  |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+   reference time is a signed 24bit value in multiples of 64ms,
+   recv deltas are in multiples of 250us.
+*/
+
+use crate::octets;
+use crate::rtcp::rtp_feedback::{RtcpRtpFeedbackPacket, RTPFB_TWCC};
+use crate::rtcp::{Result, RtcpError};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const REFERENCE_TIME_UNIT_US: i64 = 64_000;
+pub const DELTA_UNIT_US: i64 = 250;
+
+const SYMBOL_NOT_RECEIVED: u8 = 0;
+const SYMBOL_SMALL_DELTA: u8 = 1;
+const SYMBOL_LARGE_DELTA: u8 = 2;
+
+const MAX_RUN_LENGTH: usize = 0x1fff;
+const ONE_BIT_CAPACITY: usize = 14;
+const TWO_BIT_CAPACITY: usize = 7;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum PacketStatus {
+    NotReceived,
+    /// Receive delta from the previous received packet (or the reference
+    /// time for the first one), in multiples of 250us.
+    Received(i32),
+}
+
+impl PacketStatus {
+    fn symbol(self) -> u8 {
+        match self {
+            PacketStatus::NotReceived => SYMBOL_NOT_RECEIVED,
+            PacketStatus::Received(v) if (0..=255).contains(&v) => SYMBOL_SMALL_DELTA,
+            PacketStatus::Received(_) => SYMBOL_LARGE_DELTA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtcpTransportFeedbackPacket {
+    ssrc: u32,
+    media_ssrc: u32,
+    base_sequence_number: u16,
+    reference_time: i32, // 24bit
+    feedback_count: u8,
+    statuses: Vec<PacketStatus>,
+}
+
+impl RtcpTransportFeedbackPacket {
+    pub fn new(
+        ssrc: u32,
+        media_ssrc: u32,
+        base_sequence_number: u16,
+        reference_time: i32,
+        feedback_count: u8,
+        statuses: Vec<PacketStatus>,
+    ) -> Self {
+        RtcpTransportFeedbackPacket {
+            ssrc,
+            media_ssrc,
+            base_sequence_number,
+            reference_time,
+            feedback_count,
+            statuses,
+        }
+    }
+
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn get_media_ssrc(&self) -> u32 {
+        self.media_ssrc
+    }
+
+    pub fn get_base_sequence_number(&self) -> u16 {
+        self.base_sequence_number
+    }
+
+    pub fn get_reference_time(&self) -> i32 {
+        self.reference_time
+    }
+
+    pub fn get_feedback_count(&self) -> u8 {
+        self.feedback_count
+    }
+
+    pub fn get_statuses(&self) -> &[PacketStatus] {
+        &self.statuses
+    }
+
+    /// Returns each reported sequence number with its arrival time in
+    /// microseconds on the receiver's clock, `None` if not received.
+    pub fn get_arrivals(&self) -> Vec<(u16, Option<i64>)> {
+        let mut time = i64::from(self.reference_time) * REFERENCE_TIME_UNIT_US;
+        self.statuses
+            .iter()
+            .enumerate()
+            .map(|(i, status)| {
+                let seq = self.base_sequence_number.wrapping_add(i as u16);
+                match status {
+                    PacketStatus::NotReceived => (seq, None),
+                    PacketStatus::Received(delta) => {
+                        time += i64::from(*delta) * DELTA_UNIT_US;
+                        (seq, Some(time))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// FCI length in bytes, including the zero padding.
+    pub fn get_length(&self) -> u32 {
+        let chunks = encode_chunks(&self.statuses).len() * 2;
+        let deltas: usize = self
+            .statuses
+            .iter()
+            .map(|v| match v.symbol() {
+                SYMBOL_SMALL_DELTA => 1,
+                SYMBOL_LARGE_DELTA => 2,
+                _ => 0,
+            })
+            .sum();
+        let length = 8 + chunks + deltas;
+        ((length + 3) & !3) as u32
+    }
+
+    pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
+        if self.statuses.len() > usize::from(u16::MAX) {
+            return Err(RtcpError::InvalidTransportFeedbackPacket);
+        }
+
+        let start = out.off();
+        out.put_u16(self.base_sequence_number)?;
+        out.put_u16(self.statuses.len() as u16)?;
+        out.put_u24((self.reference_time as u32) & 0x00ff_ffff)?;
+        out.put_u8(self.feedback_count)?;
+
+        for chunk in encode_chunks(&self.statuses) {
+            out.put_u16(chunk)?;
+        }
+
+        for status in &self.statuses {
+            if let PacketStatus::Received(delta) = status {
+                match status.symbol() {
+                    SYMBOL_SMALL_DELTA => {
+                        out.put_u8(*delta as u8)?;
+                    }
+                    _ => {
+                        if *delta < i32::from(i16::MIN) || *delta > i32::from(i16::MAX) {
+                            return Err(RtcpError::InvalidTransportFeedbackPacket);
+                        }
+                        out.put_u16(*delta as i16 as u16)?;
+                    }
+                }
+            }
+        }
+
+        while !(out.off() - start).is_multiple_of(4) {
+            out.put_u8(0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses the FCI of a transport-cc feedback packet.
+    pub fn from_bytes(
+        bytes: &mut octets::Octets,
+        ssrc: u32,
+        media_ssrc: u32,
+    ) -> Result<RtcpTransportFeedbackPacket> {
+        if bytes.cap() < 8 {
+            return Err(RtcpError::InvalidTransportFeedbackPacket);
+        }
+
+        let base_sequence_number = bytes.get_u16()?;
+        let status_count = usize::from(bytes.get_u16()?);
+        let reference_time = bytes.get_u24()?;
+        // sign extension of the 24bit value.
+        let reference_time = ((reference_time << 8) as i32) >> 8;
+        let feedback_count = bytes.get_u8()?;
+
+        let mut symbols = Vec::with_capacity(status_count);
+        while symbols.len() < status_count {
+            let chunk = bytes.get_u16()?;
+            let remaining = status_count - symbols.len();
+
+            if chunk & 0x8000 == 0 {
+                let symbol = ((chunk >> 13) & 0x03) as u8;
+                let run = usize::from(chunk & 0x1fff);
+                symbols.extend(core::iter::repeat_n(symbol, run.min(remaining)));
+            } else if chunk & 0x4000 == 0 {
+                for i in 0..ONE_BIT_CAPACITY.min(remaining) {
+                    symbols.push(((chunk >> (13 - i)) & 0x01) as u8);
+                }
+            } else {
+                for i in 0..TWO_BIT_CAPACITY.min(remaining) {
+                    symbols.push(((chunk >> (12 - i * 2)) & 0x03) as u8);
+                }
+            }
+        }
+
+        let mut statuses = Vec::with_capacity(status_count);
+        for symbol in symbols {
+            let status = match symbol {
+                SYMBOL_NOT_RECEIVED => PacketStatus::NotReceived,
+                SYMBOL_SMALL_DELTA => PacketStatus::Received(i32::from(bytes.get_u8()?)),
+                SYMBOL_LARGE_DELTA => PacketStatus::Received(i32::from(bytes.get_u16()? as i16)),
+                _ => return Err(RtcpError::InvalidTransportFeedbackPacket),
+            };
+            statuses.push(status);
+        }
+
+        Ok(RtcpTransportFeedbackPacket {
+            ssrc,
+            media_ssrc,
+            base_sequence_number,
+            reference_time,
+            feedback_count,
+            statuses,
+        })
+    }
+
+    pub fn from_rtpfb(packet: &RtcpRtpFeedbackPacket) -> Result<RtcpTransportFeedbackPacket> {
+        if packet.get_format() != RTPFB_TWCC {
+            return Err(RtcpError::InvalidTransportFeedbackPacket);
+        }
+        let mut fci = packet.get_fci().to_vec();
+        let mut bytes = octets::Octets::with_slice(&mut fci);
+        RtcpTransportFeedbackPacket::from_bytes(
+            &mut bytes,
+            packet.get_ssrc(),
+            packet.get_media_ssrc(),
+        )
+    }
+
+    pub fn to_rtpfb(&self) -> Result<RtcpRtpFeedbackPacket> {
+        let mut fci = vec![0; self.get_length() as usize];
+        let mut out = octets::Octets::with_slice(&mut fci);
+        self.to_bytes(&mut out)?;
+        Ok(RtcpRtpFeedbackPacket::with_fci(
+            RTPFB_TWCC,
+            self.ssrc,
+            self.media_ssrc,
+            fci,
+        ))
+    }
+}
+
+// 同じsymbolが続く間はrun length chunk，それ以外はstatus vector chunkを使う．
+fn encode_chunks(statuses: &[PacketStatus]) -> Vec<u16> {
+    let symbols: Vec<u8> = statuses.iter().map(|v| v.symbol()).collect();
+    let mut chunks = Vec::new();
+
+    let mut i = 0;
+    while i < symbols.len() {
+        let run = symbols[i..]
+            .iter()
+            .take(MAX_RUN_LENGTH)
+            .take_while(|v| **v == symbols[i])
+            .count();
+
+        if run >= ONE_BIT_CAPACITY || run == symbols.len() - i {
+            chunks.push((u16::from(symbols[i]) << 13) | run as u16);
+            i += run;
+            continue;
+        }
+
+        let one_bit = symbols[i..]
+            .iter()
+            .take(ONE_BIT_CAPACITY)
+            .all(|v| *v != SYMBOL_LARGE_DELTA);
+        if one_bit {
+            let mut chunk = 0x8000;
+            for (n, symbol) in symbols[i..].iter().take(ONE_BIT_CAPACITY).enumerate() {
+                chunk |= u16::from(*symbol) << (13 - n);
+            }
+            chunks.push(chunk);
+            i += ONE_BIT_CAPACITY;
+        } else {
+            let mut chunk = 0xc000;
+            for (n, symbol) in symbols[i..].iter().take(TWO_BIT_CAPACITY).enumerate() {
+                chunk |= u16::from(*symbol) << (12 - n * 2);
+            }
+            chunks.push(chunk);
+            i += TWO_BIT_CAPACITY;
+        }
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtcp::packet::{RtcpPacket, RtcpPacketType};
+
+    #[test]
+    fn transport_feedback_round_trip_test() {
+        let mut statuses = vec![
+            PacketStatus::Received(4),
+            PacketStatus::NotReceived,
+            PacketStatus::Received(300),
+            PacketStatus::Received(-8),
+        ];
+        statuses.extend(std::iter::repeat_n(PacketStatus::Received(1), 20));
+
+        let feedback = RtcpTransportFeedbackPacket::new(1, 2, 65530, -3, 7, statuses);
+        let packet = RtcpPacket::new(RtcpPacketType::RTPFeedback(feedback.to_rtpfb().unwrap()));
+
+        let mut buf = [0; 128];
+        let len = {
+            let mut out = octets::Octets::with_slice(&mut buf);
+            packet.to_bytes(&mut out).unwrap();
+            out.off()
+        };
+        assert_eq!(len % 4, 0);
+
+        let mut bytes = octets::Octets::with_slice(&mut buf[..len]);
+        let parsed = RtcpPacket::from_bytes(&mut bytes).unwrap();
+        let parsed = match parsed.get_packet() {
+            RtcpPacketType::RTPFeedback(v) => RtcpTransportFeedbackPacket::from_rtpfb(v).unwrap(),
+            _ => panic!("not a rtpfb packet"),
+        };
+        assert_eq!(parsed, feedback);
+
+        let arrivals = parsed.get_arrivals();
+        assert_eq!(arrivals[0], (65530, Some(-3 * 64_000 + 1000)));
+        assert_eq!(arrivals[1], (65531, None));
+        assert_eq!(arrivals[6].0, 0);
+    }
+
+    #[test]
+    fn parse_chunks_test() {
+        // two-bit vector chunk : small, large, not received.
+        let mut fci = [
+            0x00, 0x0a, 0x00, 0x03, 0x00, 0x00, 0x01, 0x00, 0xd8, 0x00, 0x10, 0x00, 0x20, 0x00,
+            0x00, 0x00,
+        ];
+        let mut bytes = octets::Octets::with_slice(&mut fci);
+        let parsed = RtcpTransportFeedbackPacket::from_bytes(&mut bytes, 1, 2).unwrap();
+        assert_eq!(parsed.get_base_sequence_number(), 10);
+        assert_eq!(parsed.get_reference_time(), 1);
+        assert_eq!(
+            parsed.get_statuses(),
+            &[
+                PacketStatus::Received(16),
+                PacketStatus::Received(32),
+                PacketStatus::NotReceived
+            ]
+        );
+    }
+}
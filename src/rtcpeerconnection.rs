@@ -1,87 +1,1064 @@
-use std::net::*;
-use webrtc_sdp::address::ExplicitlyTypedAddress;
-use webrtc_sdp::*;
+// https://www.w3.org/TR/webrtc/#interface-definition
+// https://tools.ietf.org/html/rfc8835#section-3
 
-//https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/iceConnectionState
+/*
+    RtcPeerConnection
+      JsepSession      offers and answers, the transceivers
+      IceAgent         one transport for all the m= sections (max-bundle,
+                       rtcp-mux), controlling on the side of the first offer
+      DtlsTransport    made with the backend given to new once the setups
+                       of the answer tell the role
+      DtlsSrtpSession  keyed when DTLS is connected
+      SctpTransport    when an application m= section is negotiated, the
+                       data channels created before wait for it
+      LocalTrack       the frames of a transceiver that sends, packetized
+      RemoteTrack      the frames of one that receives, announced by Track
 
-enum IceConnectionState {
+    sans-IO like the layers under it: the datagrams of the sockets go to
+    handle_receive, and poll_transmit, poll_timeout, process and poll_event
+    drive it. a task of an async runtime waits on poll_next_event, the
+    sockets and a timer of poll_timeout:
+
+      loop {
+          select {
+              event = poll_next_event   -> handle it
+              datagram = recv_from      -> handle_receive
+              sleep(poll_timeout)       -> process
+          }
+          send everything of poll_transmit
+      }
+
+    a packet received on the selected pair (RFC 7983)
+      20..63    DTLS, the application data of it to SCTP
+      128..191  SRTP, SRTCP when the second byte is 192..223 (RFC 5761)
+                the RTP packets no remote track takes are the Rtp event
+
+    connectionState
+      failed        ICE or DTLS failed
+      disconnected  ICE disconnected
+      new           ICE and DTLS new
+      connected     ICE connected or completed, DTLS connected
+      connecting    otherwise
+*/
+
+pub mod driver;
+pub mod track;
+
+use crate::datachannel::channel::{DataChannel, DataChannelEvent, DataChannelInit};
+use crate::datachannel::transport::SctpTransport;
+use crate::datachannel::DataChannelError;
+use crate::dtls::fingerprint::CertificateFingerprint;
+use crate::dtls::transport::{is_dtls_packet, DtlsBackend, DtlsEvent, DtlsState, DtlsTransport};
+use crate::dtls::DtlsError;
+use crate::ice::agent::{IceAgent, IceConfig, IceConnectionState, IceEvent, IceRole};
+use crate::ice::network::{NetworkProvider, Transmit};
+use crate::jsep::bundle::BundlePolicy;
+use crate::jsep::ice::apply_remote_transport;
+use crate::jsep::rtcp_mux::RtcpMuxPolicy;
+use crate::jsep::session::{JsepEvent, JsepSession};
+use crate::jsep::signaling::IceCandidateInit;
+use crate::jsep::track::MediaStreamTrack;
+use crate::jsep::{Description, JsepError, SdpType, SignalingState};
+use crate::octets::pool::BufferPool;
+use crate::rtcpeerconnection::track::{
+    select_codec, LocalTrack, RemoteTrack, TrackReceiver, TrackSender,
+};
+use crate::rtp::demuxer::RtpDemuxer;
+use crate::rtp::frame_transformer::FrameTransformPipeline;
+use crate::rtp::packet::RtpPacketRef;
+use crate::sctp::association::SctpConfig;
+use crate::sdp::media::{Direction, MediaKind};
+use crate::srtp::context::ContextConfig;
+use crate::srtp::dtls_srtp::DtlsSrtpSession;
+use crate::time::Instant;
+use crate::WebrtcError;
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::task::{Context, Poll, Waker};
+
+pub type Result<T> = std::result::Result<T, WebrtcError>;
+
+pub(crate) fn is_rtcp_packet(data: &[u8]) -> bool {
+    data.get(1).is_some_and(|v| (192..=223).contains(v))
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum PeerConnectionState {
     New,
-    Checking,
+    Connecting,
     Connected,
-    Completed,
-    Failed,
     Disconnected,
+    Failed,
     Closed,
 }
 
-//https://developer.mozilla.org/en-US/docs/Web/API/RTCPeerConnection/iceGatheringState
-
-enum IceGatheringState {
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum IceGatheringState {
     New,
     Gathering,
-    Completed,
+    Complete,
 }
 
-enum RTCSessionDescription {
-    Offer(String),
-    Answer(String),
-    Pranswer(String), //http://iwashi.co/2016/04/03/webrtc-pranswer
-    Rollback,
+#[derive(Debug, PartialEq)]
+pub enum PeerConnectionEvent {
+    NegotiationNeeded,
+    /// A local candidate to signal, an empty one at the end of candidates.
+    IceCandidate(IceCandidateInit),
+    IceGatheringStateChange(IceGatheringState),
+    IceConnectionStateChange(IceConnectionState),
+    ConnectionStateChange(PeerConnectionState),
+    SignalingStateChange(SignalingState),
+    /// `IceEvent::TcpConnect`, the result goes to the ICE agent.
+    TcpConnect {
+        local: SocketAddr,
+        remote: SocketAddr,
+    },
+    DataChannel(DataChannelEvent),
+    /// The remote track of the transceiver receives, get_remote_track.
+    Track {
+        index: usize,
+    },
+    /// A decrypted RTP packet of the m= section of `mid`.
+    Rtp {
+        mid: String,
+        packet: Vec<u8>,
+    },
+    /// A decrypted compound RTCP packet.
+    Rtcp(Vec<u8>),
 }
 
-enum RTCSignalingState {
-    Stable,
-    HaveLocalOffer,
-    HaveRemoteOffer,
-    HaveLocalPranswer,
-    HaveRemotePranwer,
-    Closed,
+// RTCConfiguration．
+#[derive(Debug, Clone, Default)]
+pub struct RtcConfiguration {
+    pub ice: IceConfig,
+    /// The remote port is the one of the answer.
+    pub sctp: SctpConfig,
+    pub srtp: ContextConfig,
+}
+
+// eventと，poll_next_eventで待つtask．積まれた時だけ起こす．
+#[derive(Default)]
+struct EventQueue {
+    queue: VecDeque<PeerConnectionEvent>,
+    waker: Option<Waker>,
 }
 
-struct IceConnection;
+impl EventQueue {
+    fn push(&mut self, event: PeerConnectionEvent) {
+        self.queue.push_back(event);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct RtcPeerConnection<B: DtlsBackend> {
+    config: RtcConfiguration,
+    jsep: JsepSession,
+    ice: IceAgent,
+    /// Until the DTLS role is negotiated.
+    backend: Option<B>,
+    dtls: Option<DtlsTransport<B>>,
+    srtp: Option<DtlsSrtpSession>,
+    sctp: Option<SctpTransport>,
+    /// The data channels created before the SCTP transport.
+    pending_channels: Vec<(String, DataChannelInit)>,
+    demuxer: RtpDemuxer,
+    /// By the index of the transceiver.
+    senders: HashMap<usize, TrackSender>,
+    receivers: HashMap<usize, TrackReceiver>,
+    /// The transforms of the tracks, kept while they are made again.
+    transforms: HashMap<usize, FrameTransformPipeline>,
+    gathering_state: IceGatheringState,
+    connection_state: PeerConnectionState,
+    events: EventQueue,
+    /// The RTP packets written before SRTP.
+    pool: BufferPool,
+}
+
+impl<B: DtlsBackend> RtcPeerConnection<B> {
+    /// `fingerprints` are the ones of the certificate of `backend`.
+    pub fn new(
+        config: RtcConfiguration,
+        backend: B,
+        fingerprints: Vec<CertificateFingerprint>,
+    ) -> Self {
+        let ice = IceAgent::new(config.ice.clone(), IceRole::Controlled);
+        let mut jsep = JsepSession::new(ice.get_local_credentials().clone(), fingerprints);
+        jsep.set_bundle_policy(BundlePolicy::MaxBundle);
+        jsep.set_rtcp_mux_policy(RtcpMuxPolicy::Require);
+        jsep.set_ice_lite(config.ice.lite);
+        RtcPeerConnection {
+            config,
+            jsep,
+            ice,
+            backend: Some(backend),
+            dtls: None,
+            srtp: None,
+            sctp: None,
+            pending_channels: vec![],
+            demuxer: RtpDemuxer::new(),
+            senders: HashMap::new(),
+            receivers: HashMap::new(),
+            transforms: HashMap::new(),
+            gathering_state: IceGatheringState::New,
+            connection_state: PeerConnectionState::New,
+            events: EventQueue::default(),
+            pool: BufferPool::default(),
+        }
+    }
+
+    pub fn get_session(&self) -> &JsepSession {
+        &self.jsep
+    }
+
+    pub fn get_buffer_pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    /// For the transceivers. The operations that need negotiation are
+    /// told by the next poll_event.
+    pub fn get_session_mut(&mut self) -> &mut JsepSession {
+        &mut self.jsep
+    }
+
+    pub fn get_ice_agent(&self) -> &IceAgent {
+        &self.ice
+    }
+
+    pub fn get_ice_agent_mut(&mut self) -> &mut IceAgent {
+        &mut self.ice
+    }
+
+    pub fn get_dtls_transport(&self) -> Option<&DtlsTransport<B>> {
+        self.dtls.as_ref()
+    }
+
+    pub fn get_signaling_state(&self) -> SignalingState {
+        self.jsep.get_signaling_state()
+    }
+
+    pub fn get_ice_gathering_state(&self) -> IceGatheringState {
+        self.gathering_state
+    }
+
+    pub fn get_ice_connection_state(&self) -> IceConnectionState {
+        self.ice.get_state()
+    }
+
+    pub fn get_connection_state(&self) -> PeerConnectionState {
+        self.connection_state
+    }
+
+    pub fn add_transceiver(&mut self, kind: MediaKind, direction: Direction) -> usize {
+        let index = self.jsep.add_transceiver(kind, direction);
+        self.queue_negotiation_needed();
+        index
+    }
+
+    /// The index of the transceiver sending the track.
+    pub fn add_track(&mut self, track: MediaStreamTrack, stream_ids: Vec<String>) -> Result<usize> {
+        let index = self.jsep.add_track(track, stream_ids)?;
+        self.queue_negotiation_needed();
+        Ok(index)
+    }
+
+    /// The id of the channel, None while the SCTP transport is not
+    /// negotiated: it is created then, and its id told by the Open event.
+    pub fn create_data_channel(
+        &mut self,
+        label: &str,
+        init: &DataChannelInit,
+        now: Instant,
+    ) -> Result<Option<u16>> {
+        if self.jsep.get_signaling_state() == SignalingState::Closed {
+            return Err(DataChannelError::InvalidState.into());
+        }
+        self.jsep.add_data_channel();
+        self.queue_negotiation_needed();
+        match self.sctp.as_mut() {
+            Some(sctp) => Ok(Some(sctp.create_data_channel(label, init, now)?)),
+            None => {
+                init.get_reliability()?;
+                self.pending_channels
+                    .push((label.to_string(), init.clone()));
+                Ok(None)
+            }
+        }
+    }
+
+    /// The messages sent on it go out with the next process.
+    pub fn get_data_channel(&mut self, id: u16) -> Option<DataChannel<'_>> {
+        self.sctp.as_mut()?.get_data_channel(id)
+    }
+
+    /// The track of the transceiver, while it is negotiated to send it.
+    pub fn get_local_track(&mut self, index: usize) -> Option<LocalTrack<'_, B>> {
+        if self.senders.contains_key(&index) {
+            Some(LocalTrack {
+                connection: self,
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The track of the transceiver, while it is negotiated to receive.
+    pub fn get_remote_track(&mut self, index: usize) -> Option<RemoteTrack<'_, B>> {
+        if self.receivers.contains_key(&index) {
+            Some(RemoteTrack {
+                connection: self,
+                index,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn create_offer(&mut self) -> Result<Description> {
+        Ok(self.jsep.create_offer()?)
+    }
+
+    pub fn create_answer(&mut self) -> Result<Description> {
+        Ok(self.jsep.create_answer()?)
+    }
+
+    /// Gathering starts with the first description, on `network`.
+    pub fn set_local_description<P: NetworkProvider + ?Sized>(
+        &mut self,
+        description: &Description,
+        network: &mut P,
+        now: Instant,
+    ) -> Result<()> {
+        let state = self.jsep.get_signaling_state();
+        let initial = self.jsep.get_local_description().is_none()
+            && self.jsep.get_remote_description().is_none();
+        self.jsep.set_local_description(description)?;
+        if initial && description.sdp_type == SdpType::Offer {
+            self.ice.set_role(IceRole::Controlling);
+        }
+        if self.gathering_state == IceGatheringState::New && self.get_transport_mid().is_some() {
+            self.set_gathering_state(IceGatheringState::Gathering);
+            self.ice.gather(network, now)?;
+        }
+        self.handle_negotiation(state, now);
+        self.update(now);
+        Ok(())
+    }
+
+    pub fn set_remote_description(
+        &mut self,
+        description: &Description,
+        now: Instant,
+    ) -> Result<()> {
+        let state = self.jsep.get_signaling_state();
+        self.jsep.set_remote_description(description)?;
+        self.apply_remote_transport(now);
+        self.handle_negotiation(state, now);
+        self.update(now);
+        Ok(())
+    }
+
+    /// A trickled candidate of the peer, of the m= section of its mid or
+    /// else of its index.
+    pub fn add_ice_candidate(&mut self, init: &IceCandidateInit, now: Instant) -> Result<()> {
+        let mid = match (&init.sdp_mid, init.sdp_mline_index) {
+            (Some(mid), _) => Some(mid.clone()),
+            (None, Some(index)) => self
+                .jsep
+                .get_remote_description()
+                .and_then(|v| v.media.get(usize::from(index)))
+                .and_then(|v| v.get_mid())
+                .map(|v| v.to_string()),
+            (None, None) => None,
+        };
+        let mid = mid.ok_or_else(|| JsepError::InvalidDescription {
+            reason: "no m= section of the candidate".to_string(),
+        })?;
+        let candidate = init.get_candidate()?;
+        self.jsep.add_remote_candidate(&mid, candidate)?;
+        self.apply_remote_transport(now);
+        self.update(now);
+        Ok(())
+    }
+
+    /// Sends an RTP packet of a negotiated m= section, once DTLS is
+    /// connected.
+    pub fn send_rtp(&mut self, packet: &[u8], now: Instant) -> Result<()> {
+        let srtp = self.srtp.as_mut().ok_or(DtlsError::NotConnected)?;
+        let packet = srtp.get_send_context().protect_rtp(packet)?;
+        self.ice.send(&packet, now)?;
+        Ok(())
+    }
+
+    pub fn send_rtcp(&mut self, packet: &[u8], now: Instant) -> Result<()> {
+        let srtp = self.srtp.as_mut().ok_or(DtlsError::NotConnected)?;
+        let packet = srtp.get_send_context().protect_rtcp(packet)?;
+        self.ice.send(&packet, now)?;
+        Ok(())
+    }
+
+    /// Handles a datagram received on the socket bound to `local`.
+    pub fn handle_receive(
+        &mut self,
+        local: SocketAddr,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) {
+        self.ice.handle_receive(local, source, data, now);
+        self.update(now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.ice.poll_transmit()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        [
+            self.ice.poll_timeout(),
+            self.dtls.as_ref().and_then(|v| v.poll_timeout()),
+            self.sctp.as_ref().and_then(|v| v.poll_timeout()),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        self.ice.process(now);
+        if let Some(dtls) = self.dtls.as_mut() {
+            // a failure is told by the state.
+            let _ = dtls.process(now);
+        }
+        if let Some(sctp) = self.sctp.as_mut() {
+            sctp.process(now);
+        }
+        if let Some(srtp) = self.srtp.as_mut() {
+            srtp.get_send_context().process(now);
+            srtp.get_receive_context().process(now);
+        }
+        self.update(now);
+    }
+
+    /// Checks for negotiation first, like JsepSession::poll_event.
+    pub fn poll_event(&mut self) -> Option<PeerConnectionEvent> {
+        self.queue_negotiation_needed();
+        self.events.queue.pop_front()
+    }
+
+    /// `Ready` with the next event, otherwise `cx` is woken when one is
+    /// queued by a call on the connection.
+    pub fn poll_next_event(&mut self, cx: &mut Context<'_>) -> Poll<PeerConnectionEvent> {
+        match self.poll_event() {
+            Some(v) => Poll::Ready(v),
+            None => {
+                self.events.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Sends the close_notify of DTLS and stops everything, without events.
+    pub fn close(&mut self, now: Instant) {
+        if self.connection_state == PeerConnectionState::Closed {
+            return;
+        }
+        self.jsep.close();
+        if let Some(dtls) = self.dtls.as_mut() {
+            dtls.close();
+            dtls.flush(&mut self.ice, now);
+        }
+        self.ice.close();
+        self.pending_channels.clear();
+        self.connection_state = PeerConnectionState::Closed;
+    }
+
+    // BUNDLEの全てのm= sectionが使うtransportのmid．
+    fn get_transport_mid(&self) -> Option<String> {
+        self.jsep
+            .get_transport_mids()
+            .first()
+            .map(|v| v.to_string())
+    }
+
+    fn apply_remote_transport(&mut self, now: Instant) {
+        let mid = match self.get_transport_mid() {
+            Some(v) => v,
+            None => return,
+        };
+        if let Some(transport) = self.jsep.get_remote_transport(&mid) {
+            apply_remote_transport(&mut self.ice, transport, now);
+        }
+    }
+
+    // Stableになったら，決まったroleでDTLSとSCTPを作る．
+    fn handle_negotiation(&mut self, previous: SignalingState, now: Instant) {
+        let state = self.jsep.get_signaling_state();
+        if state != previous {
+            self.events
+                .push(PeerConnectionEvent::SignalingStateChange(state));
+        }
+        if state != SignalingState::Stable {
+            return;
+        }
+        let mid = match self.get_transport_mid() {
+            Some(v) => v,
+            None => return,
+        };
+        self.demuxer = self.jsep.create_demuxer(&mid);
+        self.update_tracks();
+        if self.dtls.is_none() {
+            let role = self.jsep.get_dtls_role(&mid);
+            let transport = self.jsep.get_remote_transport(&mid);
+            if let (Some(role), Some(transport), Some(backend)) =
+                (role, transport, self.backend.take())
+            {
+                let mut dtls = DtlsTransport::new(backend, role);
+                dtls.set_remote_fingerprints(transport.fingerprints.clone());
+                let _ = dtls.start(now);
+                self.dtls = Some(dtls);
+            }
+        }
+        self.create_sctp_transport(now);
+    }
+
+    // 交渉したdirectionとcodecでtrackを作り直す．
+    fn update_tracks(&mut self) {
+        for (index, transceiver) in self.jsep.get_transceivers().iter().enumerate() {
+            let (mid, direction) =
+                match (transceiver.get_mid(), transceiver.get_current_direction()) {
+                    (Some(mid), Some(direction))
+                        if !transceiver.is_stopped()
+                            && transceiver.get_kind() != MediaKind::Application =>
+                    {
+                        (mid, direction)
+                    }
+                    _ => {
+                        self.senders.remove(&index);
+                        self.receivers.remove(&index);
+                        continue;
+                    }
+                };
+            let codecs = transceiver.get_negotiated_codecs();
+            let sender = transceiver.get_sender();
+            let codec = select_codec(codecs)
+                .filter(|_| direction.is_send() && sender.get_track().is_some());
+            match (codec, sender.get_ssrcs().first()) {
+                (Some(codec), Some(layer)) => match self.senders.get_mut(&index) {
+                    Some(v) => v.set_codec(codec),
+                    None => {
+                        if let Some(v) = TrackSender::new(mid, codec, layer.ssrc) {
+                            self.senders.insert(index, v);
+                        }
+                    }
+                },
+                _ => {
+                    self.senders.remove(&index);
+                }
+            }
+            if !direction.is_recv() {
+                self.receivers.remove(&index);
+            } else if let Some(receiver) = self.receivers.get_mut(&index) {
+                receiver.set_codecs(codecs);
+            } else {
+                self.receivers
+                    .insert(index, TrackReceiver::new(mid, codecs));
+                self.events.push(PeerConnectionEvent::Track { index });
+            }
+        }
+    }
+
+    fn create_sctp_transport(&mut self, now: Instant) {
+        let dtls = match self.dtls.as_ref() {
+            Some(v) if self.sctp.is_none() => v,
+            _ => return,
+        };
+        let parameters = match self.jsep.get_remote_sctp_parameters() {
+            Some(v) => v,
+            None => return,
+        };
+        let config = SctpConfig {
+            remote_port: parameters.port,
+            ..self.config.sctp.clone()
+        };
+        let mut sctp = SctpTransport::new(dtls.get_role(), config, now);
+        for (label, init) in self.pending_channels.drain(..) {
+            // checked when they were created.
+            let _ = sctp.create_data_channel(&label, &init, now);
+        }
+        if dtls.get_state() == DtlsState::Connected {
+            sctp.start(now);
+        }
+        self.sctp = Some(sctp);
+    }
+
+    fn set_gathering_state(&mut self, state: IceGatheringState) {
+        if self.gathering_state != state {
+            self.gathering_state = state;
+            self.events
+                .push(PeerConnectionEvent::IceGatheringStateChange(state));
+        }
+    }
+
+    fn handle_ice_event(&mut self, event: IceEvent) {
+        let line = self.get_transport_mid().and_then(|mid| {
+            let index = self
+                .jsep
+                .get_local_description()?
+                .media
+                .iter()
+                .position(|v| v.get_mid() == Some(mid.as_str()))?;
+            Some((mid, index as u16))
+        });
+        match event {
+            IceEvent::LocalCandidate(candidate) => {
+                if let Some((mid, index)) = line {
+                    self.jsep.add_local_candidate(&mid, Some(candidate.clone()));
+                    self.events
+                        .push(PeerConnectionEvent::IceCandidate(IceCandidateInit::new(
+                            Some(&candidate),
+                            &mid,
+                            index,
+                        )));
+                }
+            }
+            IceEvent::GatheringComplete => {
+                if let Some((mid, index)) = line {
+                    self.jsep.add_local_candidate(&mid, None);
+                    self.events
+                        .push(PeerConnectionEvent::IceCandidate(IceCandidateInit::new(
+                            None, &mid, index,
+                        )));
+                }
+                self.set_gathering_state(IceGatheringState::Complete);
+            }
+            IceEvent::StateChanged(state) => {
+                self.events
+                    .push(PeerConnectionEvent::IceConnectionStateChange(state));
+            }
+            IceEvent::TcpConnect { local, remote } => {
+                self.events
+                    .push(PeerConnectionEvent::TcpConnect { local, remote });
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_packet(&mut self, data: &[u8], now: Instant) {
+        if is_dtls_packet(data) {
+            if let Some(dtls) = self.dtls.as_mut() {
+                let _ = dtls.handle_packet(data, now);
+            }
+            return;
+        }
+        let srtp = match self.srtp.as_mut() {
+            Some(v) if data.first().is_some_and(|v| (128..=191).contains(v)) => v,
+            _ => return,
+        };
+        if is_rtcp_packet(data) {
+            if let Ok(packet) = srtp.get_receive_context().unprotect_rtcp(data) {
+                self.events.push(PeerConnectionEvent::Rtcp(packet));
+            }
+            return;
+        }
+        let packet = match srtp.get_receive_context().unprotect_rtp(data) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let parsed = match RtpPacketRef::from_slice(&packet) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        let mid = match self.demuxer.demux_ref(&parsed) {
+            Some(v) => v.to_string(),
+            None => return,
+        };
+        let taken = self
+            .receivers
+            .values_mut()
+            .find(|v| v.get_mid() == mid)
+            .is_some_and(|v| v.handle_packet(&parsed));
+        if !taken {
+            self.events.push(PeerConnectionEvent::Rtp { mid, packet });
+        }
+    }
 
-struct RTCPeerConnection {
-    ice_gathering_state: IceGatheringState,
-    ice_connection: Option<IceConnection>,
+    fn handle_dtls_connected(&mut self, now: Instant) {
+        let dtls = match self.dtls.as_ref() {
+            Some(v) => v,
+            None => return,
+        };
+        self.srtp = DtlsSrtpSession::new(dtls, dtls.get_role(), self.config.srtp).ok();
+        if let Some(sctp) = self.sctp.as_mut() {
+            sctp.start(now);
+        }
+    }
+
+    fn update(&mut self, now: Instant) {
+        while let Some(event) = self.ice.poll_event() {
+            self.handle_ice_event(event);
+        }
+        while let Some(data) = self.ice.poll_receive() {
+            self.handle_packet(&data, now);
+        }
+        let mut connected = false;
+        if let Some(dtls) = self.dtls.as_mut() {
+            while let Some(DtlsEvent::StateChanged(state)) = dtls.poll_event() {
+                connected |= state == DtlsState::Connected;
+            }
+        }
+        if connected {
+            self.handle_dtls_connected(now);
+        }
+        if let (Some(dtls), Some(sctp)) = (self.dtls.as_mut(), self.sctp.as_mut()) {
+            while let Some(data) = dtls.poll_data() {
+                // a broken packet is dropped.
+                let _ = sctp.handle_packet(&data, now);
+            }
+            while let Some(event) = sctp.poll_event() {
+                self.events.push(PeerConnectionEvent::DataChannel(event));
+            }
+            if dtls.get_state() == DtlsState::Connected {
+                sctp.flush(dtls);
+            }
+        }
+        if let Some(dtls) = self.dtls.as_mut() {
+            dtls.flush(&mut self.ice, now);
+        }
+        self.update_connection_state();
+    }
+
+    fn queue_negotiation_needed(&mut self) {
+        while let Some(JsepEvent::NegotiationNeeded) = self.jsep.poll_event() {
+            self.events.push(PeerConnectionEvent::NegotiationNeeded);
+        }
+    }
+
+    fn update_connection_state(&mut self) {
+        if self.connection_state == PeerConnectionState::Closed {
+            return;
+        }
+        let dtls = self
+            .dtls
+            .as_ref()
+            .map(|v| v.get_state())
+            .unwrap_or(DtlsState::New);
+        let state = match (self.ice.get_state(), dtls) {
+            (IceConnectionState::Failed, _) | (_, DtlsState::Failed) => PeerConnectionState::Failed,
+            (IceConnectionState::Disconnected, _) => PeerConnectionState::Disconnected,
+            (IceConnectionState::New, DtlsState::New) => PeerConnectionState::New,
+            (IceConnectionState::Connected, DtlsState::Connected)
+            | (IceConnectionState::Completed, DtlsState::Connected) => {
+                PeerConnectionState::Connected
+            }
+            _ => PeerConnectionState::Connecting,
+        };
+        if state != self.connection_state {
+            self.connection_state = state;
+            self.events
+                .push(PeerConnectionEvent::ConnectionStateChange(state));
+        }
+    }
 }
 
-impl RTCPeerConnection {
-    pub fn new() -> RTCPeerConnection {
-        RTCPeerConnection {
-            ice_connection: None,
-            ice_gathering_state: IceGatheringState::New,
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::datachannel::channel::DataChannelMessage;
+    use crate::dtls::fingerprint::HashFunction;
+    use crate::dtls::transport::test::FakeBackend;
+    use crate::dtls::transport::DtlsRole;
+    use crate::ice::gatherer::test::FakeNetwork;
+    use crate::ice::network::NetworkInterface;
+    use crate::octets::Octets;
+    use crate::rtp::frame_transformer::{EncodedFrame, FrameTransformer};
+    use crate::rtp::packet::{RtpHeader, RtpPacket};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::time::Duration;
+
+    type Peer = RtcPeerConnection<FakeBackend>;
+
+    // 送受信どちらでも同じ，全byteの反転．
+    struct XorTransformer;
+
+    impl FrameTransformer for XorTransformer {
+        fn transform(&mut self, mut frame: EncodedFrame) -> Option<EncodedFrame> {
+            frame.data.iter_mut().for_each(|v| *v ^= 0xff);
+            Some(frame)
         }
     }
 
-    pub fn create_answer(&self) -> RTCSessionDescription {
-        let sdp = self.create_sdp();
-        RTCSessionDescription::Answer(sdp.to_string())
+    pub(crate) fn new_peer(certificate: &[u8]) -> Peer {
+        let fingerprint =
+            CertificateFingerprint::from_certificate(HashFunction::Sha256, certificate);
+        RtcPeerConnection::new(
+            RtcConfiguration::default(),
+            FakeBackend::new(certificate),
+            vec![fingerprint],
+        )
     }
 
-    pub fn create_offer(&mut self) -> RTCSessionDescription {
-        self.ice_gathering_state = IceGatheringState::Gathering;
-        //self.gather_candidates();
-        self.ice_gathering_state = IceGatheringState::Completed;
+    pub(crate) fn new_network(address: &str) -> FakeNetwork {
+        FakeNetwork::new(vec![NetworkInterface::new(
+            "eth0",
+            2,
+            vec![address.parse().unwrap()],
+        )])
+    }
+
+    /// Delivers the packets and trickles the candidates of `from`.
+    fn pump(
+        from: &mut Peer,
+        to: &mut Peer,
+        events: &mut Vec<PeerConnectionEvent>,
+        now: Instant,
+    ) -> bool {
+        let mut busy = false;
+        while let Some(v) = from.poll_transmit() {
+            to.handle_receive(v.destination, v.source, &v.data, now);
+            busy = true;
+        }
+        while let Some(event) = from.poll_event() {
+            match event {
+                PeerConnectionEvent::IceCandidate(init) => {
+                    to.add_ice_candidate(&init, now).unwrap()
+                }
+                event => events.push(event),
+            }
+            busy = true;
+        }
+        busy
+    }
 
-        let sdp = self.create_sdp();
-        RTCSessionDescription::Offer(sdp.to_string())
+    fn run_until<F: Fn(&[PeerConnectionEvent], &[PeerConnectionEvent]) -> bool>(
+        a: &mut Peer,
+        b: &mut Peer,
+        events: &mut [Vec<PeerConnectionEvent>; 2],
+        mut now: Instant,
+        done: F,
+    ) -> Instant {
+        for _ in 0..10_000 {
+            let [a_events, b_events] = events;
+            while pump(a, b, a_events, now) | pump(b, a, b_events, now) {}
+            if done(a_events, b_events) {
+                return now;
+            }
+            let next = [a.poll_timeout(), b.poll_timeout()]
+                .iter()
+                .flatten()
+                .min()
+                .copied();
+            match next {
+                Some(v) => now = now.max(v),
+                None => return now,
+            }
+            a.process(now);
+            b.process(now);
+        }
+        panic!("not done");
     }
 
-    pub fn create_pranswer(&self) -> RTCSessionDescription {
-        //http://iwashi.co/2016/04/03/webrtc-pranswer
-        let sdp = self.create_sdp();
-        RTCSessionDescription::Pranswer(sdp.to_string().replace("a=sendrecv", "a=inactive"))
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn poll_next_event_test() {
+        let now = Instant::now();
+        let mut a = new_peer(b"a");
+        let mut b = new_peer(b"b");
+        let count = Arc::new(CountWaker(AtomicUsize::new(0)));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(a.poll_next_event(&mut cx), Poll::Pending);
+        assert_eq!(b.poll_next_event(&mut cx), Poll::Pending);
+
+        a.add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            a.poll_next_event(&mut cx),
+            Poll::Ready(PeerConnectionEvent::NegotiationNeeded)
+        );
+        assert_eq!(a.poll_next_event(&mut cx), Poll::Pending);
+        // negotiation is still needed, nothing is queued.
+        a.add_transceiver(MediaKind::Video, Direction::SendRecv);
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert_eq!(a.poll_next_event(&mut cx), Poll::Pending);
+
+        let offer = a.create_offer().unwrap();
+        b.set_remote_description(&offer, now).unwrap();
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            b.poll_next_event(&mut cx),
+            Poll::Ready(PeerConnectionEvent::SignalingStateChange(
+                SignalingState::HaveRemoteOffer
+            ))
+        );
+        // woken once for each wait.
+        b.process(now);
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
     }
 
-    //fn gather_candidates(&mut self)
+    #[test]
+    fn peer_connection_test() {
+        let now = Instant::now();
+        let mut a = new_peer(b"a");
+        let mut b = new_peer(b"b");
+        let mut events = [vec![], vec![]];
+        a.add_track(
+            MediaStreamTrack::new(MediaKind::Audio),
+            vec!["s".to_string()],
+        )
+        .unwrap();
+        let init = DataChannelInit::default();
+        assert_eq!(a.create_data_channel("chat", &init, now).unwrap(), None);
+        assert_eq!(a.poll_event(), Some(PeerConnectionEvent::NegotiationNeeded));
+        assert_eq!(a.poll_event(), None);
+
+        let offer = a.create_offer().unwrap();
+        a.set_local_description(&offer, &mut new_network("10.0.0.1"), now)
+            .unwrap();
+        assert_eq!(a.get_ice_gathering_state(), IceGatheringState::Complete);
+        b.set_remote_description(&offer, now).unwrap();
+        let answer = b.create_answer().unwrap();
+        b.set_local_description(&answer, &mut new_network("10.0.0.2"), now)
+            .unwrap();
+        a.set_remote_description(&answer, now).unwrap();
+        assert_eq!(a.get_signaling_state(), SignalingState::Stable);
+        assert_eq!(a.get_ice_agent().get_role(), IceRole::Controlling);
+        assert_eq!(b.get_ice_agent().get_role(), IceRole::Controlled);
+        assert_eq!(
+            b.get_dtls_transport().map(|v| v.get_role()),
+            Some(DtlsRole::Client)
+        );
+
+        let opened = |v: &[PeerConnectionEvent]| {
+            v.iter().any(|v| {
+                matches!(
+                    v,
+                    PeerConnectionEvent::DataChannel(DataChannelEvent::Open { .. })
+                )
+            })
+        };
+        let mut now = run_until(&mut a, &mut b, &mut events, now, |a, b| {
+            opened(a) && opened(b)
+        });
+        assert_eq!(a.get_connection_state(), PeerConnectionState::Connected);
+        assert_eq!(b.get_connection_state(), PeerConnectionState::Connected);
+        for v in &events {
+            assert!(v.contains(&PeerConnectionEvent::SignalingStateChange(
+                SignalingState::Stable
+            )));
+            assert!(v.contains(&PeerConnectionEvent::IceGatheringStateChange(
+                IceGatheringState::Complete
+            )));
+            let states: Vec<PeerConnectionState> = v
+                .iter()
+                .filter_map(|v| match v {
+                    PeerConnectionEvent::ConnectionStateChange(v) => Some(*v),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(
+                states,
+                vec![
+                    PeerConnectionState::Connecting,
+                    PeerConnectionState::Connected
+                ]
+            );
+        }
 
-    fn create_sdp(&self) -> SdpSession {
-        let sdp_origin = SdpOrigin {
-            username: "Test".to_string(),
-            session_id: 0,
-            session_version: 0,
-            unicast_addr: ExplicitlyTypedAddress::Ip("127.0.0.1".parse().unwrap()),
+        // the channel created before the negotiation.
+        let id = events[0]
+            .iter()
+            .find_map(|v| match v {
+                PeerConnectionEvent::DataChannel(DataChannelEvent::Open { id }) => Some(*id),
+                _ => None,
+            })
+            .unwrap();
+        let mut channel = a.get_data_channel(id).unwrap();
+        assert_eq!(channel.get_label(), "chat");
+        channel.send_text("hello", now).unwrap();
+        a.process(now);
+        let received = |v: &[PeerConnectionEvent]| {
+            v.iter().any(|v| match v {
+                PeerConnectionEvent::DataChannel(DataChannelEvent::Message { message, .. }) => {
+                    *message == DataChannelMessage::Text("hello".to_string())
+                }
+                _ => false,
+            })
         };
-        SdpSession::new(0, sdp_origin, "".to_string())
+        now = run_until(&mut a, &mut b, &mut events, now, |_, b| received(b));
+
+        // opus on the SSRC of the sender.
+        assert!(events[1].contains(&PeerConnectionEvent::Track { index: 0 }));
+        assert!(a.get_remote_track(0).is_none());
+        let mut track = a.get_local_track(0).unwrap();
+        assert_eq!(track.get_mid(), "0");
+        assert_eq!(track.get_codec().name, "opus");
+        let ssrc = track.get_ssrc();
+        for frame in &[[1; 10], [2; 10]] {
+            track
+                .write_frame(frame, Duration::from_millis(20), now)
+                .unwrap();
+        }
+        let stats = a.get_buffer_pool().get_stats();
+        assert_eq!((stats.allocated, stats.reused, stats.in_use), (1, 1, 0));
+        now = run_until(&mut a, &mut b, &mut events, now, |_, _| true);
+        let mut track = b.get_remote_track(0).unwrap();
+        let first = track.poll_frame().unwrap();
+        assert_eq!(first.data, vec![1; 10]);
+        assert_eq!(first.ssrc, ssrc);
+        assert_eq!(first.payload_type, 111);
+        let second = track.poll_frame().unwrap();
+        assert_eq!(second.timestamp, first.timestamp.wrapping_add(960));
+        assert_eq!(track.poll_frame(), None);
+
+        // the frames through the transforms of the tracks, the one of the
+        // receiver set after the first.
+        a.get_local_track(0)
+            .unwrap()
+            .set_send_transform(Some(Box::new(XorTransformer)));
+        for (frame, received) in &[([3; 10], [0xfc; 10]), ([4; 10], [4; 10])] {
+            a.get_local_track(0)
+                .unwrap()
+                .write_frame(frame, Duration::from_millis(20), now)
+                .unwrap();
+            now = run_until(&mut a, &mut b, &mut events, now, |_, _| true);
+            let mut track = b.get_remote_track(0).unwrap();
+            assert_eq!(track.poll_frame().unwrap().data, received.to_vec());
+            track.set_receive_transform(Some(Box::new(XorTransformer)));
+        }
+        a.get_local_track(0).unwrap().set_send_transform(None);
+        b.get_remote_track(0).unwrap().set_receive_transform(None);
+
+        // a payload type no track takes, in the sequence of the track for
+        // SRTP.
+        let sequence = a.get_local_track(0).unwrap().get_sequence_number();
+        let packet = RtpPacket::new(RtpHeader::new(126, sequence, 960, ssrc), vec![1; 10]);
+        let mut data = vec![0; packet.get_length()];
+        packet.to_bytes(&mut Octets::with_slice(&mut data)).unwrap();
+        a.send_rtp(&data, now).unwrap();
+        run_until(&mut a, &mut b, &mut events, now, |_, b| {
+            b.iter().any(|v| match v {
+                PeerConnectionEvent::Rtp { mid, packet } => mid == "0" && *packet == data,
+                _ => false,
+            })
+        });
+
+        a.close(now);
+        assert_eq!(a.get_connection_state(), PeerConnectionState::Closed);
+        assert_eq!(a.get_signaling_state(), SignalingState::Closed);
+        assert!(a.create_offer().is_err());
     }
 }
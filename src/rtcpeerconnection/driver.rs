@@ -0,0 +1,252 @@
+// https://www.w3.org/TR/webrtc/#rtcpeerconnection-interface
+
+/*
+    runs an RtcPeerConnection over a Transport and a Timer of a runtime.
+    poll_event does, until nothing is ready:
+
+      poll_recv_batch -> handle_receive, and recycle
+      poll_expired    -> process
+      poll_transmit   -> poll_send_batch, MAX_BATCH at once, kept while
+                         the transport is pending
+      poll_timeout    -> reset
+
+    and then returns the next event of the connection. a task awaits it
+    in a loop, calling the connection between the events:
+
+      let event = poll_fn(|cx| driver.poll_event(&mut connection, cx)).await?;
+
+    the transport also binds the sockets, given to set_local_description
+    by get_transport_mut.
+*/
+
+use crate::dtls::transport::DtlsBackend;
+use crate::ice::network::Transmit;
+use crate::rtcpeerconnection::{PeerConnectionEvent, RtcPeerConnection};
+use crate::runtime::udp::MAX_BATCH;
+use crate::runtime::{Timer, Transport};
+
+use std::collections::VecDeque;
+use std::io;
+use std::task::{Context, Poll};
+
+pub struct PeerConnectionDriver<T: Transport, M: Timer> {
+    transport: T,
+    timer: M,
+    // sendできずに残ったtransmit．
+    pending: VecDeque<Transmit>,
+    received: Vec<Transmit>,
+}
+
+impl<T: Transport, M: Timer> PeerConnectionDriver<T, M> {
+    pub fn new(transport: T, timer: M) -> Self {
+        PeerConnectionDriver {
+            transport,
+            timer,
+            pending: VecDeque::new(),
+            received: Vec::new(),
+        }
+    }
+
+    pub fn get_transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn get_timer(&self) -> &M {
+        &self.timer
+    }
+
+    /// Moves the I/O of `connection` and returns its next event, or an
+    /// error of the transport.
+    pub fn poll_event<B: DtlsBackend>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<PeerConnectionEvent>> {
+        loop {
+            let mut busy = false;
+            while let Poll::Ready(v) = self.transport.poll_recv_batch(cx, &mut self.received) {
+                v?;
+                let now = self.timer.now();
+                for v in self.received.drain(..) {
+                    connection.handle_receive(v.destination, v.source, &v.data, now);
+                    self.transport.recycle(v.data);
+                }
+                busy = true;
+            }
+            if let Some(deadline) = connection.poll_timeout() {
+                self.timer.reset(deadline);
+                if self.timer.poll_expired(cx).is_ready() {
+                    connection.process(self.timer.now());
+                    busy = true;
+                }
+            }
+            loop {
+                while self.pending.len() < MAX_BATCH {
+                    match connection.poll_transmit() {
+                        Some(v) => self.pending.push_back(v),
+                        None => break,
+                    }
+                }
+                if self.pending.is_empty() {
+                    break;
+                }
+                match self
+                    .transport
+                    .poll_send_batch(cx, self.pending.make_contiguous())
+                {
+                    Poll::Ready(Ok(sent)) => {
+                        self.pending.drain(..sent);
+                    }
+                    Poll::Ready(Err(e)) => {
+                        self.pending.pop_front();
+                        return Poll::Ready(Err(e));
+                    }
+                    Poll::Pending => break,
+                }
+            }
+            if !busy {
+                break;
+            }
+        }
+        connection.poll_next_event(cx).map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ice::gatherer::test::FakeNetwork;
+    use crate::rtcpeerconnection::test::{new_network, new_peer};
+    use crate::rtcpeerconnection::PeerConnectionState;
+    use crate::sdp::media::{Direction, MediaKind};
+    use std::cell::{Cell, RefCell};
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::time::{Duration, Instant};
+
+    type Queue = Rc<RefCell<VecDeque<Transmit>>>;
+
+    // 相手のqueueに送るtransport．
+    struct MemoryTransport {
+        network: FakeNetwork,
+        inbox: Queue,
+        outbox: Queue,
+        // 一度だけsendをPendingにする．
+        blocked: bool,
+    }
+
+    impl Transport for MemoryTransport {
+        fn poll_send(&mut self, cx: &mut Context<'_>, transmit: &Transmit) -> Poll<io::Result<()>> {
+            if self.blocked {
+                self.blocked = false;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            self.outbox.borrow_mut().push_back(transmit.clone());
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_recv(&mut self, _: &mut Context<'_>) -> Poll<io::Result<Transmit>> {
+            match self.inbox.borrow_mut().pop_front() {
+                Some(v) => Poll::Ready(Ok(v)),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    struct ManualTimer {
+        now: Rc<Cell<Instant>>,
+        deadline: Option<Instant>,
+    }
+
+    impl Timer for ManualTimer {
+        fn now(&self) -> Instant {
+            self.now.get()
+        }
+
+        fn reset(&mut self, deadline: Instant) {
+            self.deadline = Some(deadline);
+        }
+
+        fn poll_expired(&mut self, _: &mut Context<'_>) -> Poll<()> {
+            match self.deadline {
+                Some(v) if v <= self.now.get() => {
+                    self.deadline = None;
+                    Poll::Ready(())
+                }
+                _ => Poll::Pending,
+            }
+        }
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn driver_test() {
+        let now = Rc::new(Cell::new(Instant::now()));
+        let queues: [Queue; 2] = Default::default();
+        let mut drivers: Vec<PeerConnectionDriver<MemoryTransport, ManualTimer>> = (0..2)
+            .map(|i| {
+                let transport = MemoryTransport {
+                    network: new_network(&format!("10.0.0.{}", i + 1)),
+                    inbox: queues[i].clone(),
+                    outbox: queues[1 - i].clone(),
+                    blocked: i == 0,
+                };
+                let timer = ManualTimer {
+                    now: now.clone(),
+                    deadline: None,
+                };
+                PeerConnectionDriver::new(transport, timer)
+            })
+            .collect();
+        let mut peers = [new_peer(b"a"), new_peer(b"b")];
+
+        peers[0].add_transceiver(MediaKind::Audio, Direction::SendRecv);
+        let offer = peers[0].create_offer().unwrap();
+        let network = &mut drivers[0].get_transport_mut().network;
+        peers[0]
+            .set_local_description(&offer, network, now.get())
+            .unwrap();
+        peers[1].set_remote_description(&offer, now.get()).unwrap();
+        let answer = peers[1].create_answer().unwrap();
+        let network = &mut drivers[1].get_transport_mut().network;
+        peers[1]
+            .set_local_description(&answer, network, now.get())
+            .unwrap();
+        peers[0].set_remote_description(&answer, now.get()).unwrap();
+
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..10_000 {
+            for i in 0..2 {
+                while let Poll::Ready(event) = drivers[i].poll_event(&mut peers[i], &mut cx) {
+                    if let PeerConnectionEvent::IceCandidate(init) = event.unwrap() {
+                        peers[1 - i].add_ice_candidate(&init, now.get()).unwrap();
+                    }
+                }
+            }
+            if peers
+                .iter()
+                .all(|v| v.get_connection_state() == PeerConnectionState::Connected)
+            {
+                return;
+            }
+            if queues.iter().all(|v| v.borrow().is_empty()) {
+                let next = drivers.iter().filter_map(|v| v.get_timer().deadline).min();
+                now.set(next.unwrap_or_else(|| now.get() + Duration::from_millis(10)));
+            }
+        }
+        panic!("not connected");
+    }
+}
@@ -0,0 +1,422 @@
+// https://www.w3.org/TR/webrtc/#rtcrtpsender-interface
+// https://www.w3.org/TR/webrtc/#rtcrtpreceiver-interface
+
+/*
+    LocalTrack    write_frame -> the packetizer of the first codec of the
+                                 answer it can write, on the SSRC of the
+                                 sender -> SRTP
+    RemoteTrack   poll_frame  <- the packets of a timestamp in order up to
+                                 the marker, a packet each for audio
+                              <- demuxed by mid <- SRTP
+
+    both are there while the m= section is negotiated to send or receive.
+    a send transform takes the frames before the packetizer, a receive
+    transform the assembled ones, kept for the transceiver as its tracks
+    come and go.
+    a frame missing a packet is dropped, and the one after a loss too as
+    where it starts is not known. reordered packets are taken as lost.
+*/
+
+use crate::dtls::transport::DtlsBackend;
+use crate::jsep::codec::Codec;
+use crate::jsep::track::MediaStreamTrack;
+use crate::rtcpeerconnection::{Result, RtcPeerConnection};
+use crate::rtp::frame_transformer::{EncodedFrame, FrameTransformPipeline, FrameTransformer};
+use crate::rtp::packet::{RtpPacket, RtpPacketRef};
+use crate::rtp::packetizer::RtpPacketizer;
+use crate::rtp::payloader::{new_depacketizer, new_payloader, Depacketizer};
+use crate::time::Instant;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// The RTP packets written, what is left of the path MTU after SRTP.
+pub const RTP_MTU: usize = 1200 - 16;
+
+/// The first codec of `codecs` with a payloader.
+pub(crate) fn select_codec(codecs: &[Codec]) -> Option<&Codec> {
+    codecs.iter().find(|v| new_payloader(&v.name).is_some())
+}
+
+/// The packets of a frame after the send transform, none when it drops
+/// the frame.
+pub(crate) fn pack_frame(
+    packetizer: &mut RtpPacketizer,
+    transforms: &mut FrameTransformPipeline,
+    frame: &[u8],
+    duration: Duration,
+) -> Vec<RtpPacket> {
+    if !transforms.has_send_transform() {
+        return packetizer.pack(frame, duration);
+    }
+    let frame = EncodedFrame::new(
+        packetizer.get_ssrc(),
+        packetizer.get_payload_type(),
+        packetizer.get_timestamp(),
+        frame.to_vec(),
+    );
+    match transforms.on_outgoing_frame(frame) {
+        Some(v) => packetizer.pack(&v.data, duration),
+        None => {
+            packetizer.skip(duration);
+            vec![]
+        }
+    }
+}
+
+// 送るtransceiverの，codecとpacketizer．
+pub(crate) struct TrackSender {
+    mid: String,
+    codec: Codec,
+    packetizer: RtpPacketizer,
+}
+
+impl TrackSender {
+    pub(crate) fn new(mid: &str, codec: &Codec, ssrc: u32) -> Option<Self> {
+        let payloader = new_payloader(&codec.name)?;
+        Some(TrackSender {
+            mid: mid.to_string(),
+            codec: codec.clone(),
+            packetizer: RtpPacketizer::new(
+                RTP_MTU,
+                codec.payload_type,
+                ssrc,
+                codec.clock_rate,
+                payloader,
+            ),
+        })
+    }
+
+    /// The codec of a new answer, the stream goes on.
+    pub(crate) fn set_codec(&mut self, codec: &Codec) {
+        if self.codec == *codec {
+            return;
+        }
+        if let Some(payloader) = new_payloader(&codec.name) {
+            self.packetizer
+                .set_codec(codec.payload_type, codec.clock_rate, payloader);
+            self.codec = codec.clone();
+        }
+    }
+}
+
+// 組み立て中のframe．
+struct PartialFrame {
+    ssrc: u32,
+    payload_type: u8,
+    timestamp: u32,
+    key_frame: bool,
+    broken: bool,
+    data: Vec<u8>,
+}
+
+// 受けるtransceiverの，payload typeごとのdepacketizerと組み立てたframe．
+pub(crate) struct TrackReceiver {
+    mid: String,
+    depacketizers: HashMap<u8, Box<dyn Depacketizer>>,
+    last_sequence: Option<u16>,
+    current: Option<PartialFrame>,
+    frames: VecDeque<EncodedFrame>,
+}
+
+impl TrackReceiver {
+    pub(crate) fn new(mid: &str, codecs: &[Codec]) -> Self {
+        let mut receiver = TrackReceiver {
+            mid: mid.to_string(),
+            depacketizers: HashMap::new(),
+            last_sequence: None,
+            current: None,
+            frames: VecDeque::new(),
+        };
+        receiver.set_codecs(codecs);
+        receiver
+    }
+
+    pub(crate) fn get_mid(&self) -> &str {
+        &self.mid
+    }
+
+    pub(crate) fn set_codecs(&mut self, codecs: &[Codec]) {
+        self.depacketizers = codecs
+            .iter()
+            .filter_map(|v| Some((v.payload_type, new_depacketizer(&v.name)?)))
+            .collect();
+    }
+
+    /// The next frame the receive transform passes on.
+    pub(crate) fn poll_frame(
+        &mut self,
+        transforms: &mut FrameTransformPipeline,
+    ) -> Option<EncodedFrame> {
+        while let Some(frame) = self.frames.pop_front() {
+            if let Some(v) = transforms.on_incoming_frame(frame) {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    /// Whether the packet is of a codec of the track.
+    pub(crate) fn handle_packet(&mut self, header: &RtpPacketRef) -> bool {
+        let depacketizer = match self.depacketizers.get_mut(&header.get_payload_type()) {
+            Some(v) => v,
+            None => return false,
+        };
+        let sequence = header.get_sequence_number();
+        let lost = self
+            .last_sequence
+            .is_some_and(|v| v.wrapping_add(1) != sequence);
+        self.last_sequence = Some(sequence);
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|v| v.timestamp != header.get_timestamp() || v.ssrc != header.get_ssrc())
+        {
+            // the marker of it was lost.
+            self.current = None;
+        }
+        let payload = header.get_payload();
+        let current = self.current.get_or_insert_with(|| PartialFrame {
+            ssrc: header.get_ssrc(),
+            payload_type: header.get_payload_type(),
+            timestamp: header.get_timestamp(),
+            key_frame: depacketizer.is_key_frame(payload),
+            broken: false,
+            data: vec![],
+        });
+        match depacketizer.depacketize(payload) {
+            Ok(v) if !lost || depacketizer.is_frame_per_packet() => current.data.extend(v),
+            _ => current.broken = true,
+        }
+        if depacketizer.is_frame_per_packet() || header.get_marker() {
+            if let Some(frame) = self.current.take().filter(|v| !v.broken) {
+                self.frames.push_back(EncodedFrame {
+                    ssrc: frame.ssrc,
+                    payload_type: frame.payload_type,
+                    timestamp: frame.timestamp,
+                    key_frame: frame.key_frame,
+                    data: frame.data,
+                });
+            }
+        }
+        true
+    }
+}
+
+// RtcPeerConnectionの送るtrack．
+pub struct LocalTrack<'a, B: DtlsBackend> {
+    pub(crate) connection: &'a mut RtcPeerConnection<B>,
+    pub(crate) index: usize,
+}
+
+impl<'a, B: DtlsBackend> LocalTrack<'a, B> {
+    fn get_sender(&self) -> &TrackSender {
+        &self.connection.senders[&self.index]
+    }
+
+    /// The index of the transceiver.
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_mid(&self) -> &str {
+        &self.get_sender().mid
+    }
+
+    pub fn get_track(&self) -> Option<&MediaStreamTrack> {
+        self.connection.jsep.get_transceivers()[self.index]
+            .get_sender()
+            .get_track()
+    }
+
+    /// The codec the frames are written in.
+    pub fn get_codec(&self) -> &Codec {
+        &self.get_sender().codec
+    }
+
+    pub fn get_ssrc(&self) -> u32 {
+        self.get_sender().packetizer.get_ssrc()
+    }
+
+    /// The sequence number of the next packet.
+    pub fn get_sequence_number(&self) -> u16 {
+        self.get_sender().packetizer.get_sequence_number()
+    }
+
+    /// The transform of the frames before they are packetized, e.g. an
+    /// SFrame encryptor. It stays on the transceiver.
+    pub fn set_send_transform(&mut self, transformer: Option<Box<dyn FrameTransformer>>) {
+        self.connection
+            .transforms
+            .entry(self.index)
+            .or_default()
+            .set_send_transform(transformer);
+    }
+
+    /// Sends an encoded frame lasting `duration`, in packets of at most
+    /// RTP_MTU bytes.
+    pub fn write_frame(&mut self, frame: &[u8], duration: Duration, now: Instant) -> Result<()> {
+        let sender = self.connection.senders.get_mut(&self.index).unwrap();
+        let transforms = self.connection.transforms.entry(self.index).or_default();
+        let packets = pack_frame(&mut sender.packetizer, transforms, frame, duration);
+        for packet in packets {
+            let data = self
+                .connection
+                .pool
+                .write(packet.get_length(), |out| packet.to_bytes(out))?;
+            self.connection.send_rtp(&data, now)?;
+        }
+        Ok(())
+    }
+}
+
+// RtcPeerConnectionの受けるtrack．
+pub struct RemoteTrack<'a, B: DtlsBackend> {
+    pub(crate) connection: &'a mut RtcPeerConnection<B>,
+    pub(crate) index: usize,
+}
+
+impl<'a, B: DtlsBackend> RemoteTrack<'a, B> {
+    /// The index of the transceiver.
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_mid(&self) -> &str {
+        self.connection.receivers[&self.index].get_mid()
+    }
+
+    pub fn get_track(&self) -> &MediaStreamTrack {
+        self.connection.jsep.get_transceivers()[self.index]
+            .get_receiver()
+            .get_track()
+    }
+
+    /// The transform of the assembled frames, e.g. an SFrame decryptor.
+    /// It stays on the transceiver.
+    pub fn set_receive_transform(&mut self, transformer: Option<Box<dyn FrameTransformer>>) {
+        self.connection
+            .transforms
+            .entry(self.index)
+            .or_default()
+            .set_receive_transform(transformer);
+    }
+
+    /// A whole frame received, in the order of the packets.
+    pub fn poll_frame(&mut self) -> Option<EncodedFrame> {
+        let transforms = self.connection.transforms.entry(self.index).or_default();
+        self.connection
+            .receivers
+            .get_mut(&self.index)?
+            .poll_frame(transforms)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::octets::pool::BufferPool;
+    use crate::rtp::packet::{RtpHeader, RtpPacket};
+
+    fn new_packet(sequence: u16, timestamp: u32, marker: bool, payload: &[u8]) -> RtpPacket {
+        let mut header = RtpHeader::new(96, sequence, timestamp, 1);
+        header.set_marker(marker);
+        RtpPacket::new(header, payload.to_vec())
+    }
+
+    fn handle(receiver: &mut TrackReceiver, packet: &RtpPacket) -> bool {
+        let data = BufferPool::default()
+            .write(packet.get_length(), |out| packet.to_bytes(out))
+            .unwrap();
+        receiver.handle_packet(&RtpPacketRef::from_slice(&data).unwrap())
+    }
+
+    // 偶数番目のframeを落とす．
+    struct DropTransformer(usize);
+
+    impl FrameTransformer for DropTransformer {
+        fn transform(&mut self, frame: EncodedFrame) -> Option<EncodedFrame> {
+            self.0 += 1;
+            Some(frame).filter(|_| self.0 % 2 == 1)
+        }
+    }
+
+    #[test]
+    fn pack_frame_test() {
+        let payloader = new_payloader("opus").unwrap();
+        let mut packetizer = RtpPacketizer::new(RTP_MTU, 111, 1, 48000, payloader);
+        let mut transforms = FrameTransformPipeline::new();
+        transforms.set_send_transform(Some(Box::new(DropTransformer(0))));
+        let timestamp = packetizer.get_timestamp();
+        let duration = Duration::from_millis(20);
+        let packets = pack_frame(&mut packetizer, &mut transforms, &[1; 10], duration);
+        assert_eq!(packets.len(), 1);
+        let sequence = packets[0].get_header().get_sequence_number();
+        assert_eq!(packets[0].get_header().get_timestamp(), timestamp);
+
+        // the dropped frame still takes its time.
+        assert!(pack_frame(&mut packetizer, &mut transforms, &[2; 10], duration).is_empty());
+        let packets = pack_frame(&mut packetizer, &mut transforms, &[3; 10], duration);
+        assert_eq!(
+            packets[0].get_header().get_timestamp(),
+            timestamp.wrapping_add(1920)
+        );
+        assert_eq!(
+            packets[0].get_header().get_sequence_number(),
+            sequence.wrapping_add(1)
+        );
+    }
+
+    #[test]
+    fn track_receiver_test() {
+        let codecs = vec![
+            Codec::new(96, "VP8", 90000, None),
+            Codec::new(97, "rtx", 90000, None).with_parameters("apt=96"),
+        ];
+        let mut receiver = TrackReceiver::new("0", &codecs);
+        assert!(handle(
+            &mut receiver,
+            &new_packet(1, 100, false, &[0x10, 0x00, 1])
+        ));
+        assert!(handle(&mut receiver, &new_packet(2, 100, true, &[0x00, 2])));
+        let frame = receiver.frames.pop_front().unwrap();
+        assert_eq!(frame.data, vec![0x00, 1, 2]);
+        assert!(frame.key_frame);
+        assert_eq!(frame.timestamp, 100);
+
+        // 4 is lost in the frame.
+        assert!(handle(
+            &mut receiver,
+            &new_packet(3, 200, false, &[0x10, 0x01, 1])
+        ));
+        assert!(handle(&mut receiver, &new_packet(5, 200, true, &[0x00, 2])));
+        assert!(receiver.frames.is_empty());
+        assert!(handle(
+            &mut receiver,
+            &new_packet(6, 300, true, &[0x10, 0x01, 3])
+        ));
+        let frame = receiver.frames.pop_front().unwrap();
+        assert_eq!(frame.data, vec![0x01, 3]);
+        assert!(!frame.key_frame);
+
+        // 8 with the marker is lost, so the frame after it is dropped too.
+        assert!(handle(
+            &mut receiver,
+            &new_packet(7, 400, false, &[0x10, 0x01, 1])
+        ));
+        assert!(handle(
+            &mut receiver,
+            &new_packet(9, 500, true, &[0x10, 0x01, 2])
+        ));
+        assert!(receiver.frames.is_empty());
+        assert!(handle(
+            &mut receiver,
+            &new_packet(10, 600, true, &[0x10, 0x01, 3])
+        ));
+        assert_eq!(receiver.frames.len(), 1);
+
+        let mut rtx = new_packet(11, 600, false, &[0, 8]);
+        rtx.get_header_mut().set_payload_type(97);
+        assert!(!handle(&mut receiver, &rtx));
+    }
+}
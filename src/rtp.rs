@@ -1,44 +1,73 @@
+#[cfg(feature = "std")]
+pub mod audio_level;
+#[cfg(feature = "std")]
+pub mod demuxer;
+#[cfg(feature = "std")]
+pub mod frame_transformer;
+#[cfg(feature = "std")]
+pub mod header_extension;
 pub mod packet;
+#[cfg(feature = "std")]
+pub mod packet_history;
+#[cfg(feature = "std")]
 pub mod packetizer;
+#[cfg(feature = "std")]
+pub mod payloader;
+#[cfg(feature = "std")]
+pub mod rtx;
+#[cfg(feature = "std")]
+pub mod transport_wide;
 
 use crate::OctetsError;
+#[cfg(feature = "std")]
 use failure::Fail;
 
-pub type Result<T> = std::result::Result<T, RtpError>;
+pub type Result<T> = core::result::Result<T, RtpError>;
 
-#[derive(Fail, Debug, PartialEq)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "std", derive(Fail))]
 pub enum RtpError {
-    #[fail(display = "Octets manipulate failed: {:?}", error)]
+    #[cfg_attr(feature = "std", fail(display = "Octets manipulate failed: {:?}", error))]
     OctetsError { error: OctetsError },
 
     /// The provided packet cannot be parsed because its version is unknown.
-    #[fail(display = "This RTP packet version is not 2.")]
+    #[cfg_attr(feature = "std", fail(display = "This RTP packet version is not 2."))]
     UnknownVersion,
 
     /// InvalidPacketHeader
-    #[fail(display = "Invalid packet header")]
+    #[cfg_attr(feature = "std", fail(display = "Invalid packet header"))]
     InvalidPacketHeader,
 
-    #[fail(display = "Packet header size is too short.")]
+    #[cfg_attr(feature = "std", fail(display = "Packet header size is too short."))]
     PacketHeaderTooShort,
 
-    #[fail(display = "This RTP packet version is not 2.")]
+    #[cfg_attr(feature = "std", fail(display = "This RTP packet version is not 2."))]
     InvalidPacketVersion,
 
-    #[fail(display = "Packet extension is broken.")]
+    #[cfg_attr(feature = "std", fail(display = "Packet extension is broken."))]
     InvalidPacketHeaderExtensionSize,
 
-    #[fail(display = "RTP packet padding length is invalid.")]
+    #[cfg_attr(feature = "std", fail(display = "RTP packet padding length is invalid."))]
     InvalidPacketPaddingLength,
 
-    #[fail(display = "rtp header extension profile is wrong.")]
+    #[cfg_attr(feature = "std", fail(display = "rtp header extension profile is wrong."))]
     InvalidHeaderExtensionProfile,
 
-    #[fail(display = "rtp header extension value is truncated.")]
+    #[cfg_attr(feature = "std", fail(display = "rtp header extension value is truncated."))]
     TruncatedHeaderExtensionValue,
 
-    #[fail(display = "rtp two-byte header extension is truncated.")]
+    #[cfg_attr(feature = "std", fail(display = "rtp two-byte header extension is truncated."))]
     TruncatedTwoByteHeaderExtension,
+
+    /// The id 0 is the padding of the header extension.
+    #[cfg_attr(feature = "std", fail(display = "rtp header extension id is 0."))]
+    InvalidHeaderExtensionId,
+
+    #[cfg_attr(feature = "std", fail(display = "rtp header extension value is over 255 bytes."))]
+    HeaderExtensionValueTooLong,
+
+    #[cfg_attr(feature = "std", fail(display = "RTX packet has no original sequence number."))]
+    InvalidRtxPacket,
 }
 
 impl From<OctetsError> for RtpError {
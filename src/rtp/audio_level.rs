@@ -0,0 +1,190 @@
+// https://tools.ietf.org/html/rfc6464
+
+/*
+    Client-to-Mixer Audio Level Indication (one-byte header form)
+
+     0                   1
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |  ID   | len=0 |V| level       |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    level is expressed in -dBov, 0 is the loudest and 127 is silence.
+*/
+
+use crate::rtp::packet::RtpPacket;
+use crate::time::Instant;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+pub const AUDIO_LEVEL_URI: &str = "urn:ietf:params:rtp-hdrext:ssrc-audio-level";
+
+const SILENCE_LEVEL: u8 = 127;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct AudioLevel {
+    pub voice_activity: bool,
+    pub level: u8,
+}
+
+impl AudioLevel {
+    pub fn from_extension(value: &[u8]) -> Option<AudioLevel> {
+        let b = *value.first()?;
+        Some(AudioLevel {
+            voice_activity: (b & 0x80) > 0,
+            level: b & 0x7f,
+        })
+    }
+
+    pub fn from_packet(packet: &RtpPacket, id: u8) -> Option<AudioLevel> {
+        let value = packet.get_header().get_extension(id)?;
+        AudioLevel::from_extension(&value)
+    }
+
+    pub fn to_extension(&self) -> u8 {
+        let mut b = self.level.min(SILENCE_LEVEL);
+        if self.voice_activity {
+            b |= 0x80;
+        }
+        b
+    }
+}
+
+// 各SSRCの直近のaudio levelを保持し，窓の中で大きい順に並べる．
+#[derive(Debug)]
+pub struct AudioLevelObserver {
+    window: Duration,
+    sources: HashMap<u32, VecDeque<(Instant, u8)>>,
+}
+
+impl AudioLevelObserver {
+    pub fn new(window: Duration) -> Self {
+        AudioLevelObserver {
+            window,
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, ssrc: u32, level: AudioLevel, now: Instant) {
+        let samples = self.sources.entry(ssrc).or_default();
+        samples.push_back((now, level.level.min(SILENCE_LEVEL)));
+    }
+
+    pub fn observe_packet(&mut self, packet: &RtpPacket, id: u8, now: Instant) {
+        if let Some(level) = AudioLevel::from_packet(packet, id) {
+            self.observe(packet.get_header().get_ssrc(), level, now);
+        }
+    }
+
+    pub fn remove_source(&mut self, ssrc: u32) {
+        self.sources.remove(&ssrc);
+    }
+
+    /// Returns the mean loudness (`127 - level`) of `ssrc` over the window,
+    /// or `None` if nothing was heard from it recently.
+    pub fn get_loudness(&mut self, ssrc: u32, now: Instant) -> Option<u32> {
+        self.expire(now);
+        self.sources.get(&ssrc).and_then(mean_loudness)
+    }
+
+    /// Returns up to `n` sources ordered from loudest to quietest. A source
+    /// that only sends silence is not audible and is left out.
+    pub fn get_loudest(&mut self, n: usize, now: Instant) -> Vec<u32> {
+        self.expire(now);
+
+        let mut ranking: Vec<(u32, u32)> = self
+            .sources
+            .iter()
+            .filter_map(|(ssrc, v)| mean_loudness(v).map(|l| (*ssrc, l)))
+            .filter(|(_, loudness)| *loudness > 0)
+            .collect();
+
+        // louder first, lower ssrc first on ties so the order is stable.
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        ranking.into_iter().take(n).map(|(ssrc, _)| ssrc).collect()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let window = self.window;
+        for samples in self.sources.values_mut() {
+            while let Some((t, _)) = samples.front() {
+                if now.duration_since(*t) > window {
+                    samples.pop_front();
+                } else {
+                    break;
+                }
+            }
+        }
+        self.sources.retain(|_, v| !v.is_empty());
+    }
+}
+
+fn mean_loudness(samples: &VecDeque<(Instant, u8)>) -> Option<u32> {
+    if samples.is_empty() {
+        return None;
+    }
+    let sum: u32 = samples
+        .iter()
+        .map(|(_, level)| u32::from(SILENCE_LEVEL - level))
+        .sum();
+    Some(sum / samples.len() as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn audio_level_extension_test() {
+        let level = AudioLevel::from_extension(&[0x9e]).unwrap();
+        assert_eq!(
+            level,
+            AudioLevel {
+                voice_activity: true,
+                level: 30
+            }
+        );
+        assert_eq!(level.to_extension(), 0x9e);
+        assert_eq!(AudioLevel::from_extension(&[]), None);
+    }
+
+    #[test]
+    fn observer_ranking_test() {
+        let now = Instant::now();
+        let mut observer = AudioLevelObserver::new(Duration::from_millis(500));
+
+        let loud = AudioLevel {
+            voice_activity: true,
+            level: 10,
+        };
+        let quiet = AudioLevel {
+            voice_activity: true,
+            level: 90,
+        };
+
+        observer.observe(1, quiet, now);
+        observer.observe(2, loud, now);
+        observer.observe(3, quiet, now);
+        observer.observe(3, loud, now);
+
+        assert_eq!(observer.get_loudest(3, now), vec![2, 3, 1]);
+        assert_eq!(observer.get_loudest(1, now), vec![2]);
+
+        // source 2 stops talking, its samples fall out of the window.
+        let later = now + Duration::from_millis(600);
+        observer.observe(1, quiet, later);
+        assert_eq!(observer.get_loudest(3, later), vec![1]);
+        assert_eq!(observer.get_loudness(2, later), None);
+
+        // a source sending only silence is heard but not audible.
+        let silence = AudioLevel {
+            voice_activity: false,
+            level: 127,
+        };
+        observer.observe(4, silence, later);
+        assert_eq!(observer.get_loudness(4, later), Some(0));
+        assert_eq!(observer.get_loudest(3, later), vec![1]);
+    }
+}
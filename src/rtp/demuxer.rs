@@ -0,0 +1,148 @@
+// https://tools.ietf.org/html/rfc8843#section-9.2
+
+/*
+    the m= section of a packet on a bundled transport, tried in order:
+      1. the MID header extension, which binds the SSRC to it
+      2. an SSRC signaled with a=ssrc or bound before
+      3. a payload type only one m= section has, which binds the SSRC
+
+    the RID or repaired RID header extension binds the SSRC to a layer.
+*/
+
+use crate::rtp::header_extension::{HeaderExtensionMap, MID_URI, REPAIRED_RID_URI, RID_URI};
+use crate::rtp::packet::{RtpHeader, RtpPacketRef};
+
+use std::collections::HashMap;
+use std::str;
+
+#[derive(Debug, Clone, Default)]
+pub struct RtpDemuxer {
+    extensions: HeaderExtensionMap,
+    ssrcs: HashMap<u32, String>,
+    rids: HashMap<u32, String>,
+    payload_types: HashMap<u8, Option<String>>,
+}
+
+impl RtpDemuxer {
+    pub fn new() -> Self {
+        RtpDemuxer::default()
+    }
+
+    pub fn set_header_extension_map(&mut self, extensions: HeaderExtensionMap) {
+        self.extensions = extensions;
+    }
+
+    pub fn add_ssrc(&mut self, ssrc: u32, mid: &str) {
+        self.ssrcs.insert(ssrc, mid.to_string());
+    }
+
+    /// A payload type of two m= sections tells neither.
+    pub fn add_payload_type(&mut self, payload_type: u8, mid: &str) {
+        let entry = self
+            .payload_types
+            .entry(payload_type)
+            .or_insert_with(|| Some(mid.to_string()));
+        if entry.as_deref() != Some(mid) {
+            *entry = None;
+        }
+    }
+
+    pub fn get_mid(&self, ssrc: u32) -> Option<&str> {
+        self.ssrcs.get(&ssrc).map(|v| v.as_str())
+    }
+
+    /// The layer of the SSRC, from the RID header extension.
+    pub fn get_rid(&self, ssrc: u32) -> Option<&str> {
+        self.rids.get(&ssrc).map(|v| v.as_str())
+    }
+
+    /// The mid of the packet, None when it can't be told.
+    pub fn demux(&mut self, header: &RtpHeader) -> Option<&str> {
+        let get = |uri| self.extensions.get_extension(header, uri);
+        let rid = get(RID_URI).or_else(|| get(REPAIRED_RID_URI));
+        let mid = get(MID_URI);
+        let (ssrc, payload_type) = (header.get_ssrc(), header.get_payload_type());
+        self.bind(ssrc, payload_type, mid.as_deref(), rid.as_deref())
+    }
+
+    /// The mid of a packet in the buffer received, as demux.
+    pub fn demux_ref(&mut self, packet: &RtpPacketRef) -> Option<&str> {
+        let extensions = &self.extensions;
+        let get = |uri| packet.get_extension(extensions.get_id(uri)?);
+        let rid = get(RID_URI).or_else(|| get(REPAIRED_RID_URI));
+        let mid = get(MID_URI);
+        let (ssrc, payload_type) = (packet.get_ssrc(), packet.get_payload_type());
+        self.bind(ssrc, payload_type, mid, rid)
+    }
+
+    // 変わった時だけStringを作る．
+    fn bind(
+        &mut self,
+        ssrc: u32,
+        payload_type: u8,
+        mid: Option<&[u8]>,
+        rid: Option<&[u8]>,
+    ) -> Option<&str> {
+        if let Some(rid) = rid.and_then(|v| str::from_utf8(v).ok()) {
+            if self.rids.get(&ssrc).map(String::as_str) != Some(rid) {
+                self.rids.insert(ssrc, rid.to_string());
+            }
+        }
+        let mid = match mid.and_then(|v| str::from_utf8(v).ok()) {
+            Some(v) => Some(v),
+            None if self.ssrcs.contains_key(&ssrc) => None,
+            None => self
+                .payload_types
+                .get(&payload_type)
+                .and_then(|v| v.as_deref()),
+        };
+        if let Some(mid) = mid {
+            if self.ssrcs.get(&ssrc).map(String::as_str) != Some(mid) {
+                self.ssrcs.insert(ssrc, mid.to_string());
+            }
+        }
+        self.get_mid(ssrc)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::octets::Octets;
+    use crate::rtp::packet::RtpPacket;
+
+    #[test]
+    fn demux_test() {
+        let mut map = HeaderExtensionMap::new();
+        map.insert(4, MID_URI);
+        map.insert(10, RID_URI);
+        let mut demuxer = RtpDemuxer::new();
+        demuxer.set_header_extension_map(map);
+        demuxer.add_ssrc(1111, "0");
+        demuxer.add_payload_type(111, "0");
+        demuxer.add_payload_type(96, "1");
+        demuxer.add_payload_type(96, "2");
+        demuxer.add_payload_type(100, "2");
+
+        assert_eq!(demuxer.demux(&RtpHeader::new(111, 1, 0, 1111)), Some("0"));
+        // the payload type is of both 1 and 2.
+        assert_eq!(demuxer.demux(&RtpHeader::new(96, 1, 0, 2222)), None);
+        let mut header = RtpHeader::new(96, 2, 0, 2222);
+        header.set_extension(4, b"1").unwrap();
+        header.set_extension(10, b"q").unwrap();
+        assert_eq!(demuxer.demux(&header), Some("1"));
+        assert_eq!(demuxer.demux(&RtpHeader::new(96, 3, 0, 2222)), Some("1"));
+        assert_eq!(demuxer.get_rid(2222), Some("q"));
+        assert_eq!(demuxer.demux(&RtpHeader::new(100, 1, 0, 3333)), Some("2"));
+        assert_eq!(demuxer.get_mid(3333), Some("2"));
+
+        let mut header = RtpHeader::new(96, 1, 0, 4444);
+        header.set_extension(4, b"2").unwrap();
+        let packet = RtpPacket::new(header, vec![0]);
+        let mut buf = vec![0; packet.get_length()];
+        packet.to_bytes(&mut Octets::with_slice(&mut buf)).unwrap();
+        let packet = RtpPacketRef::from_slice(&buf).unwrap();
+        assert_eq!(demuxer.demux_ref(&packet), Some("2"));
+        assert_eq!(demuxer.get_mid(4444), Some("2"));
+    }
+}
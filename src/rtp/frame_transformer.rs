@@ -0,0 +1,152 @@
+// https://w3c.github.io/webrtc-encoded-transform/
+
+/*
+    sender:   encoder -> [send transform] -> packetizer -> SRTP
+    receiver: SRTP -> frame assembler -> [receive transform] -> decoder
+*/
+
+/// A whole encoded frame as seen by a transform.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct EncodedFrame {
+    pub ssrc: u32,
+    pub payload_type: u8,
+    pub timestamp: u32,
+    pub key_frame: bool,
+    pub data: Vec<u8>,
+}
+
+impl EncodedFrame {
+    pub fn new(ssrc: u32, payload_type: u8, timestamp: u32, data: Vec<u8>) -> Self {
+        EncodedFrame {
+            ssrc,
+            payload_type,
+            timestamp,
+            key_frame: false,
+            data,
+        }
+    }
+}
+
+// encoded transformと同じく，パイプラインを変えずにframe単位のE2EEやmetadataの付与を行うためのhook．
+pub trait FrameTransformer {
+    /// Returns the frame to pass on, or `None` to drop it (e.g. on a
+    /// decryption failure).
+    fn transform(&mut self, frame: EncodedFrame) -> Option<EncodedFrame>;
+}
+
+/// Passes every frame through unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassThroughTransformer;
+
+impl FrameTransformer for PassThroughTransformer {
+    fn transform(&mut self, frame: EncodedFrame) -> Option<EncodedFrame> {
+        Some(frame)
+    }
+}
+
+// 一つのstreamの送信側と受信側のtransform．設定されていなければそのまま通す．
+#[derive(Default)]
+pub struct FrameTransformPipeline {
+    send: Option<Box<dyn FrameTransformer>>,
+    receive: Option<Box<dyn FrameTransformer>>,
+}
+
+impl FrameTransformPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_send_transform(&mut self, transformer: Option<Box<dyn FrameTransformer>>) {
+        self.send = transformer;
+    }
+
+    pub fn set_receive_transform(&mut self, transformer: Option<Box<dyn FrameTransformer>>) {
+        self.receive = transformer;
+    }
+
+    pub fn has_send_transform(&self) -> bool {
+        self.send.is_some()
+    }
+
+    pub fn has_receive_transform(&self) -> bool {
+        self.receive.is_some()
+    }
+
+    /// Called with a frame from the encoder before it is packetized.
+    pub fn on_outgoing_frame(&mut self, frame: EncodedFrame) -> Option<EncodedFrame> {
+        match self.send {
+            Some(ref mut v) => v.transform(frame),
+            None => Some(frame),
+        }
+    }
+
+    /// Called with a frame from the frame assembler before it is decoded.
+    pub fn on_incoming_frame(&mut self, frame: EncodedFrame) -> Option<EncodedFrame> {
+        match self.receive {
+            Some(ref mut v) => v.transform(frame),
+            None => Some(frame),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 先頭にframe番号を付け，受信側で取り除く．
+    struct FrameNumberInjector {
+        next: u8,
+    }
+
+    impl FrameTransformer for FrameNumberInjector {
+        fn transform(&mut self, mut frame: EncodedFrame) -> Option<EncodedFrame> {
+            frame.data.insert(0, self.next);
+            self.next = self.next.wrapping_add(1);
+            Some(frame)
+        }
+    }
+
+    struct FrameNumberStripper {
+        received: Vec<u8>,
+    }
+
+    impl FrameTransformer for FrameNumberStripper {
+        fn transform(&mut self, mut frame: EncodedFrame) -> Option<EncodedFrame> {
+            if frame.data.is_empty() {
+                return None;
+            }
+            self.received.push(frame.data.remove(0));
+            Some(frame)
+        }
+    }
+
+    #[test]
+    fn frame_transform_pipeline_test() {
+        let frame = EncodedFrame::new(1234, 96, 3000, vec![1, 2, 3]);
+
+        let mut pipeline = FrameTransformPipeline::new();
+        assert_eq!(
+            pipeline.on_outgoing_frame(frame.clone()),
+            Some(frame.clone())
+        );
+        assert_eq!(
+            pipeline.on_incoming_frame(frame.clone()),
+            Some(frame.clone())
+        );
+
+        pipeline.set_send_transform(Some(Box::new(FrameNumberInjector { next: 7 })));
+        pipeline.set_receive_transform(Some(Box::new(FrameNumberStripper { received: vec![] })));
+        assert!(pipeline.has_send_transform() && pipeline.has_receive_transform());
+
+        let sent = pipeline.on_outgoing_frame(frame.clone()).unwrap();
+        assert_eq!(sent.data, vec![7, 1, 2, 3]);
+        assert_eq!(pipeline.on_incoming_frame(sent), Some(frame.clone()));
+
+        // the receive transform may drop a frame.
+        let empty = EncodedFrame::new(1234, 96, 3000, vec![]);
+        assert_eq!(pipeline.on_incoming_frame(empty), None);
+
+        pipeline.set_send_transform(Some(Box::new(PassThroughTransformer)));
+        assert_eq!(pipeline.on_outgoing_frame(frame.clone()), Some(frame));
+    }
+}
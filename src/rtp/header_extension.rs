@@ -0,0 +1,119 @@
+// https://tools.ietf.org/html/rfc8285#section-5
+
+/*
+    the ids of the header extensions are negotiated with a=extmap, the
+    packets only carry the id. the map turns the URI into the id to write
+    and the id back into the URI when parsing.
+
+      1 - 14     one-byte or two-byte header
+      15 - 255   two-byte header only
+*/
+
+use crate::rtp::packet::RtpHeader;
+use crate::rtp::Result;
+
+use std::collections::HashMap;
+
+pub const MID_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:mid";
+pub const RID_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id";
+pub const REPAIRED_RID_URI: &str = "urn:ietf:params:rtp-hdrext:sdes:repaired-rtp-stream-id";
+pub const ABS_SEND_TIME_URI: &str = "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time";
+pub const TOFFSET_URI: &str = "urn:ietf:params:rtp-hdrext:toffset";
+
+// 交渉済みのid <-> URI．
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HeaderExtensionMap {
+    uris: HashMap<u8, String>,
+}
+
+impl HeaderExtensionMap {
+    pub fn new() -> Self {
+        HeaderExtensionMap::default()
+    }
+
+    /// Maps `id` to `uri`, replacing the URI the id had.
+    pub fn insert(&mut self, id: u8, uri: &str) {
+        self.uris.insert(id, uri.to_string());
+    }
+
+    pub fn get_id(&self, uri: &str) -> Option<u8> {
+        self.uris
+            .iter()
+            .filter(|(_, v)| v.as_str() == uri)
+            .map(|(id, _)| *id)
+            .min()
+    }
+
+    pub fn get_uri(&self, id: u8) -> Option<&str> {
+        self.uris.get(&id).map(|v| v.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uris.is_empty()
+    }
+
+    pub fn get_extension(&self, header: &RtpHeader, uri: &str) -> Option<Vec<u8>> {
+        header.get_extension(self.get_id(uri)?)
+    }
+
+    /// Writes the element of `uri`, false when it is not negotiated.
+    pub fn set_extension(&self, header: &mut RtpHeader, uri: &str, value: &[u8]) -> Result<bool> {
+        match self.get_id(uri) {
+            Some(id) => {
+                header.set_extension(id, value)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The elements with a negotiated id, by URI.
+    pub fn get_extensions<'a>(&'a self, header: &RtpHeader) -> Vec<(&'a str, Vec<u8>)> {
+        header
+            .get_extensions()
+            .into_iter()
+            .filter_map(|(id, value)| Some((self.get_uri(id)?, value)))
+            .collect()
+    }
+
+    /// Drops the elements the peer did not negotiate.
+    pub fn retain_known(&self, header: &mut RtpHeader) {
+        for (id, _) in header.get_extensions() {
+            if !self.uris.contains_key(&id) {
+                header.remove_extension(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::transport_wide::TRANSPORT_WIDE_CC_URI;
+    use crate::rtp::RtpError;
+
+    #[test]
+    fn header_extension_map_test() {
+        let mut map = HeaderExtensionMap::new();
+        map.insert(3, TRANSPORT_WIDE_CC_URI);
+        map.insert(20, MID_URI);
+        assert_eq!(map.get_id(MID_URI), Some(20));
+        assert_eq!(map.get_uri(3), Some(TRANSPORT_WIDE_CC_URI));
+        assert_eq!(map.get_id(RID_URI), None);
+
+        let mut header = RtpHeader::new(96, 1, 0, 0x1234);
+        assert_eq!(map.set_extension(&mut header, MID_URI, b"0"), Ok(true));
+        assert_eq!(map.set_extension(&mut header, RID_URI, b"hi"), Ok(false));
+        assert_eq!(
+            map.set_extension(&mut header, MID_URI, &[0; 256]),
+            Err(RtpError::HeaderExtensionValueTooLong)
+        );
+        header.set_extension(5, &[1]).unwrap();
+        assert_eq!(map.get_extension(&header, MID_URI), Some(b"0".to_vec()));
+        assert_eq!(map.get_extensions(&header), vec![(MID_URI, b"0".to_vec())]);
+
+        map.retain_known(&mut header);
+        assert_eq!(header.get_extension(5), None);
+        assert_eq!(header.get_extension(20), Some(b"0".to_vec()));
+    }
+}
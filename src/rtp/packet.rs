@@ -3,6 +3,9 @@
 
 use crate::octets;
 use crate::rtp::{Result, RtpError};
+
+use alloc::vec;
+use alloc::vec::Vec;
 /*
     The RTP header has the following format:
 
@@ -42,10 +45,10 @@ use crate::rtp::{Result, RtpError};
     |                          data                                 |
     +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 */
-fn unpack_header_extension(
+pub fn unpack_header_extension(
     bytes: &mut octets::Octets,
     profile: u16,
-) -> Result<(Vec<(u8, Vec<u8>)>)> {
+) -> Result<Vec<(u8, Vec<u8>)>> {
     // OctetsはRTP Header Extension で作り直されたものを使用する予定
     // こうすることで，RTP Header Extensionのサイズを超過しないようにする
     // TODO : Vec<(u8, octets::Octets)>で返せるようにする．．
@@ -88,6 +91,45 @@ fn unpack_header_extension(
     Ok(extensions)
 }
 
+// one-byte header(0xBEDE)に収まらない場合はtwo-byte header(0x1000)を使う．
+// idは0以外，長さはtwo-byte headerの1byteに収まるものだけを受け付ける．
+fn pack_header_extension(elements: &[(u8, Vec<u8>)]) -> Result<RtpHeaderExtension> {
+    for (id, value) in elements {
+        if *id == 0 {
+            return Err(RtpError::InvalidHeaderExtensionId);
+        }
+        if value.len() > u8::MAX as usize {
+            return Err(RtpError::HeaderExtensionValueTooLong);
+        }
+    }
+
+    let one_byte = elements
+        .iter()
+        .all(|(id, value)| *id >= 1 && *id <= 14 && !value.is_empty() && value.len() <= 16);
+
+    let mut raw = Vec::new();
+    for (id, value) in elements {
+        if one_byte {
+            raw.push((id << 4) | (value.len() as u8 - 1));
+        } else {
+            raw.push(*id);
+            raw.push(value.len() as u8);
+        }
+        raw.extend_from_slice(value);
+    }
+    while raw.len() % 4 != 0 {
+        raw.push(0);
+    }
+
+    Ok(RtpHeaderExtension {
+        profile: if one_byte { 0xBEDE } else { 0x1000 },
+        payload: raw
+            .chunks(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+            .collect(),
+    })
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct RtpHeaderExtension {
     profile: u16,
@@ -144,6 +186,144 @@ pub struct RtpHeader {
 }
 
 impl RtpHeader {
+    pub fn new(payload_type: u8, sequence_number: u16, timestamp: u32, ssrc: u32) -> Self {
+        RtpHeader {
+            version: 2,
+            padding: None,
+            extension: None,
+            marker: false,
+            payload_type,
+            sequence_number,
+            timestamp,
+            ssrc,
+            csrc: Vec::new(),
+        }
+    }
+
+    pub fn get_padding(&self) -> Option<u8> {
+        self.padding
+    }
+
+    /// The padding length counts its last byte, so it is not 0.
+    pub fn set_padding(&mut self, padding: Option<u8>) -> Result<()> {
+        if padding == Some(0) {
+            return Err(RtpError::InvalidPacketPaddingLength);
+        }
+        self.padding = padding;
+        Ok(())
+    }
+
+    pub fn get_marker(&self) -> bool {
+        self.marker
+    }
+
+    pub fn set_marker(&mut self, marker: bool) {
+        self.marker = marker;
+    }
+
+    pub fn get_payload_type(&self) -> u8 {
+        self.payload_type
+    }
+
+    pub fn set_payload_type(&mut self, payload_type: u8) {
+        self.payload_type = payload_type;
+    }
+
+    pub fn get_sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+
+    pub fn set_sequence_number(&mut self, sequence_number: u16) {
+        self.sequence_number = sequence_number;
+    }
+
+    pub fn get_timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: u32) {
+        self.timestamp = timestamp;
+    }
+
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn set_ssrc(&mut self, ssrc: u32) {
+        self.ssrc = ssrc;
+    }
+
+    pub fn get_csrc(&self) -> &[u32] {
+        &self.csrc
+    }
+
+    /// Returns the value of the one-byte or two-byte header extension element
+    /// with the given id (RFC 8285), if present.
+    pub fn get_extension(&self, id: u8) -> Option<Vec<u8>> {
+        self.get_extensions()
+            .into_iter()
+            .find(|(ext_id, _)| *ext_id == id)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_extensions(&self) -> Vec<(u8, Vec<u8>)> {
+        let ext = match self.extension {
+            Some(ref v) => v,
+            None => return Vec::new(),
+        };
+
+        let mut raw = Vec::with_capacity(ext.payload.len() * 4);
+        for word in &ext.payload {
+            raw.extend_from_slice(&word.to_be_bytes());
+        }
+
+        let mut b = octets::Octets::with_slice(&mut raw);
+        unpack_header_extension(&mut b, ext.profile).unwrap_or_default()
+    }
+
+    /// Adds or replaces the header extension element with the given id.
+    /// The id is not 0 and the value is at most 255 bytes.
+    pub fn set_extension(&mut self, id: u8, value: &[u8]) -> Result<()> {
+        let mut elements = self.get_extensions();
+        match elements.iter_mut().find(|(ext_id, _)| *ext_id == id) {
+            Some(v) => v.1 = value.to_vec(),
+            None => elements.push((id, value.to_vec())),
+        }
+        self.extension = Some(pack_header_extension(&elements)?);
+        Ok(())
+    }
+
+    pub fn remove_extension(&mut self, id: u8) {
+        let elements: Vec<_> = self
+            .get_extensions()
+            .into_iter()
+            .filter(|(ext_id, _)| *ext_id != id)
+            .collect();
+        self.extension = if elements.is_empty() {
+            None
+        } else {
+            // 残りの要素はこのheaderから読み出したものなので詰め直せる．
+            Some(
+                pack_header_extension(&elements)
+                    .expect("the elements unpacked from the header are valid"),
+            )
+        };
+    }
+
+    /// Returns the serialized header length in bytes.
+    pub fn get_length(&self) -> usize {
+        let extension = match self.extension {
+            Some(ref v) => 4 + v.payload.len() * 4,
+            None => 0,
+        };
+        12 + self.csrc.len() * 4 + extension
+    }
+
+    /// The bytes to_bytes writes.
+    pub fn serialized_len(&self) -> usize {
+        self.get_length()
+    }
+
     // 構造体に代入されたデータをBinaryに変換
     pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
         let csrc_count = self.csrc.len() as u8;
@@ -271,6 +451,34 @@ pub struct RtpPacket {
 }
 
 impl RtpPacket {
+    pub fn new(header: RtpHeader, payload: Vec<u8>) -> Self {
+        RtpPacket { header, payload }
+    }
+
+    /// Returns the serialized packet length in bytes, padding included.
+    pub fn get_length(&self) -> usize {
+        self.header.get_length()
+            + self.payload.len()
+            + self.header.padding.map(|v| v as usize).unwrap_or(0)
+    }
+
+    /// The bytes to_bytes writes, get_length.
+    pub fn serialized_len(&self) -> usize {
+        self.get_length()
+    }
+
+    pub fn get_header(&self) -> &RtpHeader {
+        &self.header
+    }
+
+    pub fn get_header_mut(&mut self) -> &mut RtpHeader {
+        &mut self.header
+    }
+
+    pub fn get_payload(&self) -> &[u8] {
+        &self.payload
+    }
+
     pub fn to_bytes(&self, out: &mut octets::Octets) -> Result<()> {
         self.header.to_bytes(out)?;
 
@@ -298,7 +506,7 @@ impl RtpPacket {
 
         let payload = match header.padding {
             Some(v) => {
-                if v as usize > bytes.cap() {
+                if v == 0 || v as usize > bytes.cap() {
                     return Err(RtpError::InvalidPacketPaddingLength);
                 }
                 bytes.get_bytes(bytes.cap() - v as usize)?.to_vec()
@@ -310,7 +518,187 @@ impl RtpPacket {
     }
 }
 
+// 受けたbufferを参照するRtpPacket．転送するだけならpayloadもheader extensionもcopyしない．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RtpPacketRef<'a> {
+    buf: &'a [u8],
+    padding: Option<u8>,
+    /// The profile and the elements of the header extension.
+    extension: Option<(u16, &'a [u8])>,
+    payload: &'a [u8],
+}
+
+impl<'a> RtpPacketRef<'a> {
+    pub fn from_slice(buf: &'a [u8]) -> Result<RtpPacketRef<'a>> {
+        if buf.len() < 12 {
+            return Err(RtpError::PacketHeaderTooShort);
+        }
+        if buf[0] >> 6 != 2 {
+            return Err(RtpError::InvalidPacketVersion);
+        }
+        let mut header_length = 12 + (buf[0] & 0x0f) as usize * 4;
+        if buf.len() < header_length {
+            return Err(RtpError::PacketHeaderTooShort);
+        }
+
+        let extension = if buf[0] & 0b00010000 > 0 {
+            if buf.len() < header_length + 4 {
+                return Err(RtpError::InvalidPacketHeaderExtensionSize);
+            }
+            let profile = u16::from_be_bytes([buf[header_length], buf[header_length + 1]]);
+            let length =
+                u16::from_be_bytes([buf[header_length + 2], buf[header_length + 3]]) as usize * 4;
+            let start = header_length + 4;
+            header_length = start + length;
+            if buf.len() < header_length {
+                return Err(RtpError::InvalidPacketHeaderExtensionSize);
+            }
+            Some((profile, &buf[start..header_length]))
+        } else {
+            None
+        };
+
+        let mut end = buf.len();
+        let padding = if buf[0] & 0b00100000 > 0 {
+            let padding = buf[end - 1];
+            if padding == 0 || padding as usize > end - header_length {
+                return Err(RtpError::InvalidPacketPaddingLength);
+            }
+            end -= padding as usize;
+            Some(padding)
+        } else {
+            None
+        };
+
+        Ok(RtpPacketRef {
+            buf,
+            padding,
+            extension,
+            payload: &buf[header_length..end],
+        })
+    }
+
+    /// The whole packet.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    pub fn get_length(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn get_padding(&self) -> Option<u8> {
+        self.padding
+    }
+
+    pub fn get_marker(&self) -> bool {
+        self.buf[1] & 0b10000000 > 0
+    }
+
+    pub fn get_payload_type(&self) -> u8 {
+        self.buf[1] & 0b01111111
+    }
+
+    pub fn get_sequence_number(&self) -> u16 {
+        u16::from_be_bytes([self.buf[2], self.buf[3]])
+    }
+
+    pub fn get_timestamp(&self) -> u32 {
+        u32::from_be_bytes([self.buf[4], self.buf[5], self.buf[6], self.buf[7]])
+    }
+
+    pub fn get_ssrc(&self) -> u32 {
+        u32::from_be_bytes([self.buf[8], self.buf[9], self.buf[10], self.buf[11]])
+    }
+
+    pub fn get_csrc(&self) -> impl Iterator<Item = u32> + 'a {
+        let count = (self.buf[0] & 0x0f) as usize;
+        self.buf[12..12 + count * 4]
+            .chunks(4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    /// The value of the one-byte or two-byte header extension element with
+    /// the given id, in the packet.
+    pub fn get_extension(&self, id: u8) -> Option<&'a [u8]> {
+        self.get_extensions()
+            .find(|(ext_id, _)| *ext_id == id)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_extensions(&self) -> RtpExtensionElements<'a> {
+        let (profile, data) = self.extension.unwrap_or((0, &[]));
+        RtpExtensionElements { profile, data }
+    }
+
+    pub fn get_payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    /// A copy of it the RtpPacket parsed from the buffer would be.
+    pub fn to_owned(&self) -> RtpPacket {
+        let header = RtpHeader {
+            version: 2,
+            padding: self.padding,
+            extension: self.extension.map(|(profile, data)| RtpHeaderExtension {
+                profile,
+                payload: data
+                    .chunks(4)
+                    .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+                    .collect(),
+            }),
+            marker: self.get_marker(),
+            payload_type: self.get_payload_type(),
+            sequence_number: self.get_sequence_number(),
+            timestamp: self.get_timestamp(),
+            ssrc: self.get_ssrc(),
+            csrc: self.get_csrc().collect(),
+        };
+        RtpPacket::new(header, self.payload.to_vec())
+    }
+}
+
+/// The elements of a header extension as unpack_header_extension reads
+/// them, until one is truncated.
+#[derive(Debug, Clone)]
+pub struct RtpExtensionElements<'a> {
+    profile: u16,
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for RtpExtensionElements<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        loop {
+            let (&octet, rest) = self.data.split_first()?;
+            self.data = rest;
+            if octet == 0x00 {
+                // padding
+                continue;
+            }
+            let (id, length) = match self.profile {
+                0xBEDE => ((octet & 0xf0) >> 4, (octet & 0x0f) as usize + 1),
+                0x1000 => {
+                    let (&length, rest) = self.data.split_first()?;
+                    self.data = rest;
+                    (octet, length as usize)
+                }
+                _ => return None,
+            };
+            if self.data.len() < length {
+                self.data = &[];
+                return None;
+            }
+            let (value, rest) = self.data.split_at(length);
+            self.data = rest;
+            return Some((id, value));
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(unused_variables, unused_assignments, unused_must_use, unused_mut)]
 mod test {
     use super::*;
     use crate::octets;
@@ -441,4 +829,106 @@ mod test {
 
         assert!(RtpPacket::from_bytes(&mut invalid_length_octets).is_err());
     }
+
+    #[test]
+    fn rtp_header_set_extension_test() {
+        let mut header = RtpHeader::new(96, 1, 2, 3);
+        header.set_extension(1, &[0x9e]).unwrap();
+        header.set_extension(3, &[0x12, 0x34]).unwrap();
+        assert_eq!(header.get_extension(1), Some(vec![0x9e]));
+        assert_eq!(header.get_extension(3), Some(vec![0x12, 0x34]));
+        assert_eq!(header.get_length(), 12 + 4 + 8);
+
+        let mut buf = [0; 64];
+        let len = {
+            let mut b = octets::Octets::with_slice(&mut buf);
+            header.to_bytes(&mut b).unwrap();
+            b.off()
+        };
+        assert_eq!(len, header.get_length());
+        assert_eq!(&buf[12..16], &[0xbe, 0xde, 0x00, 0x02]);
+
+        let parsed = RtpHeader::from_slice(&mut buf[..len]).unwrap();
+        assert_eq!(parsed.get_extension(3), Some(vec![0x12, 0x34]));
+
+        header.remove_extension(1);
+        header.remove_extension(3);
+        assert_eq!(header.get_length(), 12);
+
+        // id 15 does not fit in the one-byte form.
+        header.set_extension(15, &[1]).unwrap();
+        assert_eq!(header.get_extension(15), Some(vec![1]));
+
+        header.set_extension(16, &[2; 255]).unwrap();
+        assert_eq!(header.get_extension(16), Some(vec![2; 255]));
+        assert_eq!(
+            header.set_extension(16, &[2; 256]),
+            Err(RtpError::HeaderExtensionValueTooLong)
+        );
+        assert_eq!(
+            header.set_extension(0, &[1]),
+            Err(RtpError::InvalidHeaderExtensionId)
+        );
+        assert_eq!(header.get_extension(16), Some(vec![2; 255]));
+    }
+
+    #[test]
+    fn rtp_packet_ref_test() {
+        let raw_packet = [
+            0x90, 0xe0, 0x69, 0x8f, 0xd9, 0xc2, 0x93, 0xda, 0x1c, 0x64, 0x27, 0x82, 0x00, 0x01,
+            0x00, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x98, 0x36, 0xbe, 0x88, 0x9e,
+        ];
+        let packet = RtpPacketRef::from_slice(&raw_packet).unwrap();
+        assert!(packet.get_marker());
+        assert_eq!(packet.get_payload_type(), 96);
+        assert_eq!(packet.get_sequence_number(), 27023);
+        assert_eq!(packet.get_timestamp(), 3653407706);
+        assert_eq!(packet.get_ssrc(), 476325762);
+        assert_eq!(packet.get_csrc().count(), 0);
+        assert_eq!(packet.get_payload(), &raw_packet[20..]);
+        assert_eq!(packet.get_payload().as_ptr(), raw_packet[20..].as_ptr());
+        let mut buf = raw_packet;
+        assert_eq!(packet.to_owned(), RtpPacket::from_slice(&mut buf).unwrap());
+
+        let mut header = RtpHeader::new(96, 1, 2, 3);
+        header.set_extension(1, &[0x9e]).unwrap();
+        header.set_extension(3, &[0x12, 0x34]).unwrap();
+        header.set_padding(Some(4)).unwrap();
+        assert_eq!(
+            header.set_padding(Some(0)),
+            Err(RtpError::InvalidPacketPaddingLength)
+        );
+        let owned = RtpPacket::new(header, vec![1, 2, 3]);
+        let mut buf = Vec::new();
+        owned
+            .to_bytes(&mut octets::Octets::with_vec(&mut buf))
+            .unwrap();
+        assert_eq!(buf.len(), owned.serialized_len());
+        let packet = RtpPacketRef::from_slice(&buf).unwrap();
+        assert_eq!(packet.get_extension(3), Some(&[0x12, 0x34][..]));
+        assert_eq!(packet.get_extensions().count(), 2);
+        assert_eq!(packet.get_payload(), &[1, 2, 3]);
+        assert_eq!(packet.get_padding(), Some(4));
+        assert_eq!(packet.to_owned(), owned);
+
+        let mut zero_padding = buf.clone();
+        *zero_padding.last_mut().unwrap() = 0;
+        assert_eq!(
+            RtpPacketRef::from_slice(&zero_padding),
+            Err(RtpError::InvalidPacketPaddingLength)
+        );
+        assert_eq!(
+            RtpPacket::from_slice(&mut zero_padding),
+            Err(RtpError::InvalidPacketPaddingLength)
+        );
+
+        assert_eq!(
+            RtpPacketRef::from_slice(&raw_packet[..11]),
+            Err(RtpError::PacketHeaderTooShort)
+        );
+        assert_eq!(
+            RtpPacketRef::from_slice(&raw_packet[..18]),
+            Err(RtpError::InvalidPacketHeaderExtensionSize)
+        );
+    }
 }
@@ -0,0 +1,160 @@
+use crate::rtp::packet::RtpPacket;
+use crate::time::Instant;
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct StoredPacket {
+    pub packet: RtpPacket,
+    pub sent_at: Instant,
+    pub last_resent: Option<Instant>,
+    pub resend_count: u32,
+}
+
+// 送信済みのRTP packetを再送用に保持する．容量と経過時間の両方で古いものから捨てる．
+#[derive(Debug, Clone)]
+pub struct RtpPacketHistory {
+    capacity: usize,
+    max_age: Duration,
+    order: VecDeque<u16>,
+    packets: HashMap<u16, StoredPacket>,
+}
+
+impl RtpPacketHistory {
+    pub fn new(capacity: usize, max_age: Duration) -> Self {
+        // 容量0では挿入したpacketを置く場所がないので，最低1つは保持する．
+        let capacity = capacity.max(1);
+        RtpPacketHistory {
+            capacity,
+            max_age,
+            order: VecDeque::with_capacity(capacity),
+            packets: HashMap::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    pub fn insert(&mut self, packet: RtpPacket, now: Instant) {
+        self.expire(now);
+
+        let sequence = packet.get_header().get_sequence_number();
+        if self.packets.contains_key(&sequence) {
+            self.order.retain(|v| *v != sequence);
+        }
+        while self.order.len() >= self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.packets.remove(&old);
+            }
+        }
+
+        self.order.push_back(sequence);
+        self.packets.insert(
+            sequence,
+            StoredPacket {
+                packet,
+                sent_at: now,
+                last_resent: None,
+                resend_count: 0,
+            },
+        );
+    }
+
+    pub fn get(&self, sequence: u16) -> Option<&StoredPacket> {
+        self.packets.get(&sequence)
+    }
+
+    /// Returns the packet for retransmission unless it was already resent
+    /// within `min_interval`.
+    pub fn get_for_resend(
+        &mut self,
+        sequence: u16,
+        min_interval: Duration,
+        now: Instant,
+    ) -> Option<&RtpPacket> {
+        self.expire(now);
+
+        let stored = self.packets.get_mut(&sequence)?;
+        if let Some(t) = stored.last_resent {
+            if now.duration_since(t) < min_interval {
+                return None;
+            }
+        }
+        stored.last_resent = Some(now);
+        stored.resend_count += 1;
+        Some(&stored.packet)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(sequence) = self.order.front() {
+            let expired = self
+                .packets
+                .get(sequence)
+                .map(|v| now.duration_since(v.sent_at) > self.max_age)
+                .unwrap_or(true);
+            if !expired {
+                break;
+            }
+            self.packets.remove(sequence);
+            self.order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::packet::RtpHeader;
+
+    fn packet(sequence: u16) -> RtpPacket {
+        RtpPacket::new(RtpHeader::new(96, sequence, 0, 1), vec![1, 2, 3])
+    }
+
+    #[test]
+    fn history_eviction_test() {
+        let now = Instant::now();
+        let mut history = RtpPacketHistory::new(2, Duration::from_secs(1));
+
+        history.insert(packet(1), now);
+        history.insert(packet(2), now);
+        history.insert(packet(3), now);
+        assert_eq!(history.len(), 2);
+        assert!(history.get(1).is_none());
+        assert!(history.get(3).is_some());
+
+        let later = now + Duration::from_secs(2);
+        history.insert(packet(4), later);
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn zero_capacity_test() {
+        let now = Instant::now();
+        let mut history = RtpPacketHistory::new(0, Duration::from_secs(1));
+
+        history.insert(packet(1), now);
+        history.insert(packet(2), now);
+        assert_eq!(history.len(), 1);
+        assert!(history.get(2).is_some());
+    }
+
+    #[test]
+    fn resend_interval_test() {
+        let now = Instant::now();
+        let mut history = RtpPacketHistory::new(16, Duration::from_secs(1));
+        history.insert(packet(7), now);
+
+        let interval = Duration::from_millis(100);
+        assert!(history.get_for_resend(7, interval, now).is_some());
+        assert!(history.get_for_resend(7, interval, now).is_none());
+        let later = now + Duration::from_millis(150);
+        assert!(history.get_for_resend(7, interval, later).is_some());
+        assert_eq!(history.get(7).unwrap().resend_count, 2);
+    }
+}
@@ -1,37 +1,101 @@
-use crate::octets;
-use crate::rtp::packet::RtpHeader;
+use crate::rtp::packet::{RtpHeader, RtpPacket};
+use crate::rtp::payloader::Payloader;
+
 use rand::Rng;
+use std::time::Duration;
+
+const RTP_HEADER_LENGTH: usize = 12;
 
 // Payloadの詰め込みと新規StreamのSSRC発行などを行う．
-struct RtpPacketizer {
+pub struct RtpPacketizer {
     mtu: usize,
     payload_type: u8,
     ssrc: u32,
+    sequence_number: u16,
     timestamp: u32,
     clock_rate: u32,
+    payloader: Box<dyn Payloader>,
 }
 
 impl RtpPacketizer {
-    // NewPacketizer returns a new instance of a Packetizer for a specific payloader
-    pub fn new(mtu: usize, payload_type: u8, ssrc: u32, clock_rate: u32) -> RtpPacketizer {
+    /// `mtu` is the size of the whole RTP packet. The sequence number and
+    /// the timestamp start at random.
+    pub fn new(
+        mtu: usize,
+        payload_type: u8,
+        ssrc: u32,
+        clock_rate: u32,
+        payloader: Box<dyn Payloader>,
+    ) -> RtpPacketizer {
         let mut rng = rand::thread_rng();
         RtpPacketizer {
             mtu,
             payload_type,
             ssrc,
+            sequence_number: rng.gen(),
             timestamp: rng.gen(),
             clock_rate,
+            payloader,
         }
     }
 
-    pub fn pack() -> () {
-        ()
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    pub fn get_payload_type(&self) -> u8 {
+        self.payload_type
+    }
+
+    pub fn get_sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+
+    pub fn get_timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    /// Switches the codec, the sequence numbers and timestamps go on.
+    pub fn set_codec(&mut self, payload_type: u8, clock_rate: u32, payloader: Box<dyn Payloader>) {
+        self.payload_type = payload_type;
+        self.clock_rate = clock_rate;
+        self.payloader = payloader;
+    }
+
+    /// The packets of a frame lasting `duration`, the last one with the
+    /// marker unless it is audio. The timestamp moves on by the duration.
+    pub fn pack(&mut self, frame: &[u8], duration: Duration) -> Vec<RtpPacket> {
+        let mtu = self.mtu.saturating_sub(RTP_HEADER_LENGTH);
+        let payloads = self.payloader.payload(mtu, frame);
+        let marker = !self.payloader.is_frame_per_packet();
+        let count = payloads.len();
+        let mut packets = Vec::with_capacity(count);
+        for (i, payload) in payloads.into_iter().enumerate() {
+            let mut header = RtpHeader::new(
+                self.payload_type,
+                self.sequence_number,
+                self.timestamp,
+                self.ssrc,
+            );
+            header.set_marker(marker && i + 1 == count);
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            packets.push(RtpPacket::new(header, payload));
+        }
+        self.skip(duration);
+        packets
+    }
+
+    /// Moves the timestamp on by a frame lasting `duration` not sent.
+    pub fn skip(&mut self, duration: Duration) {
+        let samples = duration.as_micros() * u128::from(self.clock_rate) / 1_000_000;
+        self.timestamp = self.timestamp.wrapping_add(samples as u32);
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::rtp::payloader::{AudioPayloader, Vp8Payloader};
 
     #[test]
     fn rand() {
@@ -41,4 +105,40 @@ mod test {
 
         assert_ne!(rand1, rand2)
     }
+
+    #[test]
+    fn pack_test() {
+        let mut packetizer = RtpPacketizer::new(100, 96, 1234, 90000, Box::new(Vp8Payloader));
+        let sequence_number = packetizer.get_sequence_number();
+        let timestamp = packetizer.get_timestamp();
+        let packets = packetizer.pack(&[0; 200], Duration::from_millis(33));
+        assert_eq!(packets.len(), 3);
+        assert!(packets.iter().all(|v| v.get_length() <= 100));
+        let headers: Vec<&RtpHeader> = packets.iter().map(|v| v.get_header()).collect();
+        assert_eq!(headers[0].get_sequence_number(), sequence_number);
+        assert_eq!(
+            headers[2].get_sequence_number(),
+            sequence_number.wrapping_add(2)
+        );
+        assert!(headers.iter().all(|v| v.get_timestamp() == timestamp));
+        assert_eq!(
+            headers.iter().map(|v| v.get_marker()).collect::<Vec<_>>(),
+            vec![false, false, true]
+        );
+        assert_eq!(packetizer.get_timestamp(), timestamp.wrapping_add(2970));
+
+        packetizer.set_codec(111, 48000, Box::new(AudioPayloader));
+        let packets = packetizer.pack(&[1; 50], Duration::from_millis(20));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].get_header().get_payload_type(), 111);
+        assert!(!packets[0].get_header().get_marker());
+        assert_eq!(
+            packets[0].get_header().get_sequence_number(),
+            sequence_number.wrapping_add(3)
+        );
+        assert_eq!(
+            packetizer.get_timestamp(),
+            timestamp.wrapping_add(2970 + 960)
+        );
+    }
 }
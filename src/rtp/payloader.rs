@@ -0,0 +1,350 @@
+// https://tools.ietf.org/html/rfc7587#section-4.2
+// https://tools.ietf.org/html/rfc7741#section-4.2
+// https://tools.ietf.org/html/rfc6184#section-5.6
+
+/*
+    the payloads of a frame, and the frame back from them.
+
+    audio     one frame in one packet
+    VP8       a 1-byte descriptor, S on the first packet of the frame
+                  0 1 2 3 4 5 6 7
+                 |X|R|N|S|R| PID |
+    H264      the NAL units of Annex B, each in one packet or split in FU-A
+                 FU indicator  |F|NRI|  28  |
+                 FU header     |S|E|R| type |
+              STAP-A (24) is taken too.
+
+    the H264 frames received are written in Annex B.
+*/
+
+use crate::rtp::{Result, RtpError};
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+const NAL_IDR: u8 = 5;
+const NAL_SPS: u8 = 7;
+const NAL_STAP_A: u8 = 24;
+const NAL_FU_A: u8 = 28;
+
+fn truncated() -> RtpError {
+    RtpError::InvalidPacketHeader
+}
+
+pub trait Payloader {
+    /// The payloads of a frame, at most `mtu` bytes each.
+    fn payload(&mut self, mtu: usize, frame: &[u8]) -> Vec<Vec<u8>>;
+
+    /// Audio packets go without the marker of the end of a frame.
+    fn is_frame_per_packet(&self) -> bool {
+        false
+    }
+}
+
+pub trait Depacketizer {
+    /// The part of the frame in the payload.
+    fn depacketize(&mut self, payload: &[u8]) -> Result<Vec<u8>>;
+
+    /// Whether the payload starts a key frame.
+    fn is_key_frame(&self, payload: &[u8]) -> bool;
+
+    /// Audio frames are a packet each, video ones end with the marker.
+    fn is_frame_per_packet(&self) -> bool {
+        false
+    }
+}
+
+/// The payloader of the encoding name, None for one not known.
+pub fn new_payloader(name: &str) -> Option<Box<dyn Payloader>> {
+    match name.to_ascii_lowercase().as_str() {
+        "opus" | "pcmu" | "pcma" | "g722" => Some(Box::new(AudioPayloader)),
+        "vp8" => Some(Box::new(Vp8Payloader)),
+        "h264" => Some(Box::new(H264Payloader)),
+        _ => None,
+    }
+}
+
+pub fn new_depacketizer(name: &str) -> Option<Box<dyn Depacketizer>> {
+    match name.to_ascii_lowercase().as_str() {
+        "opus" | "pcmu" | "pcma" | "g722" => Some(Box::new(AudioPayloader)),
+        "vp8" => Some(Box::new(Vp8Payloader)),
+        "h264" => Some(Box::new(H264Payloader)),
+        _ => None,
+    }
+}
+
+// opus, G.711やG.722のaudio．1 packetに1 frame．
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AudioPayloader;
+
+impl Payloader for AudioPayloader {
+    fn payload(&mut self, _mtu: usize, frame: &[u8]) -> Vec<Vec<u8>> {
+        vec![frame.to_vec()]
+    }
+
+    fn is_frame_per_packet(&self) -> bool {
+        true
+    }
+}
+
+impl Depacketizer for AudioPayloader {
+    fn depacketize(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+        Ok(payload.to_vec())
+    }
+
+    fn is_key_frame(&self, _payload: &[u8]) -> bool {
+        true
+    }
+
+    fn is_frame_per_packet(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Vp8Payloader;
+
+impl Vp8Payloader {
+    // descriptorの長さ．X, I, M, L, T/Kを見る．
+    fn get_descriptor_length(payload: &[u8]) -> Result<usize> {
+        let first = *payload.first().ok_or_else(truncated)?;
+        let mut length = 1;
+        if first & 0x80 != 0 {
+            let extension = *payload.get(length).ok_or_else(truncated)?;
+            length += 1;
+            if extension & 0x80 != 0 {
+                let picture_id = *payload.get(length).ok_or_else(truncated)?;
+                length += if picture_id & 0x80 != 0 { 2 } else { 1 };
+            }
+            if extension & 0x40 != 0 {
+                length += 1;
+            }
+            if extension & 0x30 != 0 {
+                length += 1;
+            }
+        }
+        if length > payload.len() {
+            return Err(truncated());
+        }
+        Ok(length)
+    }
+}
+
+impl Payloader for Vp8Payloader {
+    fn payload(&mut self, mtu: usize, frame: &[u8]) -> Vec<Vec<u8>> {
+        let size = mtu.saturating_sub(1).max(1);
+        frame
+            .chunks(size)
+            .enumerate()
+            .map(|(i, v)| {
+                let mut payload = vec![if i == 0 { 0x10 } else { 0 }];
+                payload.extend_from_slice(v);
+                payload
+            })
+            .collect()
+    }
+}
+
+impl Depacketizer for Vp8Payloader {
+    fn depacketize(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+        let length = Vp8Payloader::get_descriptor_length(payload)?;
+        Ok(payload[length..].to_vec())
+    }
+
+    /// The start of partition 0 with the P bit of the payload header 0.
+    fn is_key_frame(&self, payload: &[u8]) -> bool {
+        let length = match Vp8Payloader::get_descriptor_length(payload) {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+        payload[0] & 0x17 == 0x10 && payload.get(length).is_some_and(|v| v & 0x01 == 0)
+    }
+}
+
+/// The NAL units of an Annex B stream, without the start codes.
+pub fn split_annex_b(stream: &[u8]) -> Vec<&[u8]> {
+    let mut starts = vec![];
+    let mut i = 0;
+    while i + 3 <= stream.len() {
+        if stream[i..i + 3] == [0, 0, 1] {
+            starts.push((i, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    let mut units = vec![];
+    for (n, (_, begin)) in starts.iter().enumerate() {
+        let mut end = starts.get(n + 1).map(|v| v.0).unwrap_or(stream.len());
+        // the zero of a 4-byte start code.
+        while end > *begin && stream[end - 1] == 0 && n + 1 < starts.len() {
+            end -= 1;
+        }
+        if end > *begin {
+            units.push(&stream[*begin..end]);
+        }
+    }
+    units
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct H264Payloader;
+
+impl Payloader for H264Payloader {
+    fn payload(&mut self, mtu: usize, frame: &[u8]) -> Vec<Vec<u8>> {
+        let mut payloads = vec![];
+        for unit in split_annex_b(frame) {
+            if unit.len() <= mtu {
+                payloads.push(unit.to_vec());
+                continue;
+            }
+            let indicator = (unit[0] & 0xe0) | NAL_FU_A;
+            let size = mtu.saturating_sub(2).max(1);
+            let fragments: Vec<&[u8]> = unit[1..].chunks(size).collect();
+            for (i, fragment) in fragments.iter().enumerate() {
+                let mut header = unit[0] & 0x1f;
+                if i == 0 {
+                    header |= 0x80;
+                }
+                if i + 1 == fragments.len() {
+                    header |= 0x40;
+                }
+                let mut payload = vec![indicator, header];
+                payload.extend_from_slice(fragment);
+                payloads.push(payload);
+            }
+        }
+        payloads
+    }
+}
+
+impl Depacketizer for H264Payloader {
+    fn depacketize(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+        let first = *payload.first().ok_or_else(truncated)?;
+        let mut frame = vec![];
+        match first & 0x1f {
+            NAL_STAP_A => {
+                let mut rest = &payload[1..];
+                while rest.len() >= 2 {
+                    let size = usize::from(u16::from_be_bytes([rest[0], rest[1]]));
+                    let unit = rest.get(2..2 + size).ok_or_else(truncated)?;
+                    frame.extend_from_slice(&START_CODE);
+                    frame.extend_from_slice(unit);
+                    rest = &rest[2 + size..];
+                }
+            }
+            NAL_FU_A => {
+                let header = *payload.get(1).ok_or_else(truncated)?;
+                if header & 0x80 != 0 {
+                    frame.extend_from_slice(&START_CODE);
+                    frame.push((first & 0xe0) | (header & 0x1f));
+                }
+                frame.extend_from_slice(&payload[2..]);
+            }
+            1..=23 => {
+                frame.extend_from_slice(&START_CODE);
+                frame.extend_from_slice(payload);
+            }
+            _ => return Err(RtpError::InvalidPacketHeader),
+        }
+        Ok(frame)
+    }
+
+    /// An IDR slice or an SPS, the start of a fragmented one too.
+    fn is_key_frame(&self, payload: &[u8]) -> bool {
+        let first = match payload.first() {
+            Some(v) => *v,
+            None => return false,
+        };
+        let is_key = |v: u8| v == NAL_IDR || v == NAL_SPS;
+        match first & 0x1f {
+            NAL_STAP_A => {
+                let mut rest = &payload[1..];
+                while rest.len() > 2 {
+                    let size = usize::from(u16::from_be_bytes([rest[0], rest[1]]));
+                    if is_key(rest[2] & 0x1f) {
+                        return true;
+                    }
+                    rest = rest.get(2 + size..).unwrap_or_default();
+                }
+                false
+            }
+            NAL_FU_A => payload
+                .get(1)
+                .is_some_and(|v| v & 0x80 != 0 && is_key(v & 0x1f)),
+            v => is_key(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vp8_test() {
+        let frame: Vec<u8> = (0..25).collect();
+        let payloads = Vp8Payloader.payload(10, &frame);
+        assert_eq!(payloads.len(), 3);
+        assert_eq!(payloads[0][..2], [0x10, 0]);
+        assert_eq!(payloads[1][0], 0);
+        assert!(payloads.iter().all(|v| v.len() <= 10));
+
+        let mut depacketizer = Vp8Payloader;
+        let received: Vec<u8> = payloads
+            .iter()
+            .flat_map(|v| depacketizer.depacketize(v).unwrap())
+            .collect();
+        assert_eq!(received, frame);
+        // the P bit of 0 is a key frame.
+        assert!(depacketizer.is_key_frame(&payloads[0]));
+        assert!(!depacketizer.is_key_frame(&payloads[1]));
+
+        // X, I with a 15-bit PictureID, L and T.
+        let payload = [0x90, 0xe0, 0x80, 0x01, 0x02, 0x03, 0xaa];
+        assert_eq!(depacketizer.depacketize(&payload).unwrap(), vec![0xaa]);
+        assert!(depacketizer.depacketize(&[0x90, 0x80]).is_err());
+    }
+
+    #[test]
+    fn h264_test() {
+        let sps = [0x67, 0x42, 0x00, 0x1f];
+        let idr: Vec<u8> = std::iter::once(0x65).chain(0..20).collect();
+        let mut frame = vec![0, 0, 0, 1];
+        frame.extend_from_slice(&sps);
+        frame.extend_from_slice(&[0, 0, 1]);
+        frame.extend_from_slice(&idr);
+        assert_eq!(split_annex_b(&frame), vec![&sps[..], &idr[..]]);
+
+        let payloads = H264Payloader.payload(10, &frame);
+        assert_eq!(payloads[0], sps);
+        // FU-A of the IDR slice.
+        assert_eq!(payloads[1][..2], [0x7c, 0x85]);
+        assert_eq!(payloads.last().unwrap()[1], 0x45);
+        assert!(payloads.iter().all(|v| v.len() <= 10));
+
+        let mut depacketizer = H264Payloader;
+        assert!(depacketizer.is_key_frame(&payloads[0]));
+        assert!(depacketizer.is_key_frame(&payloads[1]));
+        assert!(!depacketizer.is_key_frame(&payloads[2]));
+        let received: Vec<u8> = payloads
+            .iter()
+            .flat_map(|v| depacketizer.depacketize(v).unwrap())
+            .collect();
+        let mut expected = START_CODE.to_vec();
+        expected.extend_from_slice(&sps);
+        expected.extend_from_slice(&START_CODE);
+        expected.extend_from_slice(&idr);
+        assert_eq!(received, expected);
+
+        // STAP-A of the SPS and a PPS.
+        let stap = [
+            0x78, 0x00, 0x04, 0x67, 0x42, 0x00, 0x1f, 0x00, 0x02, 0x68, 0xce,
+        ];
+        assert!(depacketizer.is_key_frame(&stap));
+        assert_eq!(
+            depacketizer.depacketize(&stap).unwrap(),
+            vec![0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1f, 0, 0, 0, 1, 0x68, 0xce]
+        );
+        assert!(depacketizer.depacketize(&[0x78, 0x00, 0x09, 0x67]).is_err());
+    }
+}
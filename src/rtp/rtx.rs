@@ -0,0 +1,115 @@
+// https://tools.ietf.org/html/rfc4588
+
+/*
+    0                   1                   2                   3
+    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |                         RTP Header                            |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |            OSN                |                               |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+   |                  Original RTP Packet Payload                  |
+   |                                                               |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::rtp::packet::RtpPacket;
+use crate::rtp::{Result, RtpError};
+
+use std::collections::HashMap;
+
+// apt=で対応付けられたRTX用のpayload typeとSSRCで元のpacketを包む．
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RtxEncoder {
+    ssrc: u32,
+    payload_types: HashMap<u8, u8>, // media payload type -> rtx payload type
+    sequence_number: u16,
+}
+
+impl RtxEncoder {
+    pub fn new(ssrc: u32, payload_types: HashMap<u8, u8>, sequence_number: u16) -> Self {
+        RtxEncoder {
+            ssrc,
+            payload_types,
+            sequence_number,
+        }
+    }
+
+    pub fn get_ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Returns the RTX packet for `packet`, or `None` when its payload type
+    /// has no associated RTX payload type.
+    pub fn encapsulate(&mut self, packet: &RtpPacket) -> Option<RtpPacket> {
+        let header = packet.get_header();
+        let rtx_payload_type = *self.payload_types.get(&header.get_payload_type())?;
+
+        let mut payload = Vec::with_capacity(packet.get_payload().len() + 2);
+        payload.extend_from_slice(&header.get_sequence_number().to_be_bytes());
+        payload.extend_from_slice(packet.get_payload());
+
+        let mut rtx_header = header.clone();
+        rtx_header.set_ssrc(self.ssrc);
+        rtx_header.set_payload_type(rtx_payload_type);
+        rtx_header.set_sequence_number(self.sequence_number);
+        rtx_header
+            .set_padding(None)
+            .expect("no padding is always valid");
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+
+        Some(RtpPacket::new(rtx_header, payload))
+    }
+}
+
+/// Restores the original packet from an RTX packet.
+pub fn decapsulate(
+    packet: &RtpPacket,
+    media_ssrc: u32,
+    media_payload_type: u8,
+) -> Result<RtpPacket> {
+    let payload = packet.get_payload();
+    if payload.len() < 2 {
+        return Err(RtpError::InvalidRtxPacket);
+    }
+
+    let mut header = packet.get_header().clone();
+    header.set_ssrc(media_ssrc);
+    header.set_payload_type(media_payload_type);
+    header.set_sequence_number(u16::from_be_bytes([payload[0], payload[1]]));
+
+    Ok(RtpPacket::new(header, payload[2..].to_vec()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::packet::RtpHeader;
+
+    #[test]
+    fn rtx_round_trip_test() {
+        let mut payload_types = HashMap::new();
+        payload_types.insert(96, 97);
+        let mut encoder = RtxEncoder::new(2000, payload_types, 100);
+
+        let original = RtpPacket::new(RtpHeader::new(96, 4321, 90000, 1000), vec![1, 2, 3]);
+        let rtx = encoder.encapsulate(&original).unwrap();
+
+        assert_eq!(rtx.get_header().get_ssrc(), 2000);
+        assert_eq!(rtx.get_header().get_payload_type(), 97);
+        assert_eq!(rtx.get_header().get_sequence_number(), 100);
+        assert_eq!(rtx.get_payload(), &[0x10, 0xe1, 1, 2, 3]);
+
+        let restored = decapsulate(&rtx, 1000, 96).unwrap();
+        assert_eq!(restored, original);
+
+        let unknown = RtpPacket::new(RtpHeader::new(111, 1, 0, 1000), vec![]);
+        assert!(encoder.encapsulate(&unknown).is_none());
+
+        let empty = RtpPacket::new(RtpHeader::new(97, 1, 0, 2000), vec![0]);
+        assert_eq!(
+            decapsulate(&empty, 1000, 96),
+            Err(RtpError::InvalidRtxPacket)
+        );
+    }
+}
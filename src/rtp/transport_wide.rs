@@ -0,0 +1,88 @@
+// https://tools.ietf.org/html/draft-holmer-rmcat-transport-wide-cc-extensions-01
+
+/*
+    0                   1                   2
+    0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+   |  ID   | L=1   |transport-wide sequence number |
+   +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::rtp::packet::RtpHeader;
+use crate::rtp::Result;
+
+pub const TRANSPORT_WIDE_CC_URI: &str =
+    "http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01";
+
+pub fn get_transport_sequence_number(header: &RtpHeader, id: u8) -> Option<u16> {
+    let value = header.get_extension(id)?;
+    if value.len() < 2 {
+        return None;
+    }
+    Some(u16::from_be_bytes([value[0], value[1]]))
+}
+
+pub fn set_transport_sequence_number(header: &mut RtpHeader, id: u8, sequence: u16) -> Result<()> {
+    header.set_extension(id, &sequence.to_be_bytes())
+}
+
+// transport-wide sequence numberは同じtransport上の全SSRCで共有する．
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct TransportSequencer {
+    next: u16,
+}
+
+impl TransportSequencer {
+    pub fn new(initial: u16) -> Self {
+        TransportSequencer { next: initial }
+    }
+
+    /// Allocates the next sequence number and writes it into `header`.
+    pub fn assign(&mut self, header: &mut RtpHeader, id: u8) -> Result<u16> {
+        let sequence = self.next;
+        set_transport_sequence_number(header, id, sequence)?;
+        self.next = self.next.wrapping_add(1);
+        Ok(sequence)
+    }
+}
+
+// 16bitのsequence numberを巻き戻りのない値に展開する．
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct SequenceUnwrapper {
+    last: Option<i64>,
+}
+
+impl SequenceUnwrapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unwraps `sequence` relative to the last unwrapped value without
+    /// updating it.
+    pub fn get_unwrapped(&self, sequence: u16) -> i64 {
+        match self.last {
+            Some(last) => last + i64::from(sequence.wrapping_sub(last as u16) as i16),
+            None => i64::from(sequence),
+        }
+    }
+
+    pub fn unwrap(&mut self, sequence: u16) -> i64 {
+        let unwrapped = self.get_unwrapped(sequence);
+        self.last = Some(unwrapped);
+        unwrapped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sequence_unwrapper_test() {
+        let mut unwrapper = SequenceUnwrapper::new();
+        assert_eq!(unwrapper.unwrap(65534), 65534);
+        assert_eq!(unwrapper.unwrap(1), 65537);
+        assert_eq!(unwrapper.get_unwrapped(65535), 65535);
+        assert_eq!(unwrapper.unwrap(0), 65536);
+    }
+}
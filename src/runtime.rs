@@ -0,0 +1,260 @@
+// https://doc.rust-lang.org/std/task/index.html
+
+/*
+    what a runtime gives the sans-IO state machines, so the same code runs
+    on tokio, async-std, smol or an executor of its own:
+
+      Transport   the sockets and streams of a NetworkProvider, sending the
+                  transmits and receiving datagrams as transmits to the
+                  local address, a batch of them at once where it can,
+                  the data of a BufferPool recycled
+      Readiness   the reactor watching a non-blocking socket, for
+                  BatchUdpTransport
+      Timer       one deadline, the poll_timeout of a state machine
+      Spawner     runs a task, the one driving a connection
+
+    ThreadTimer, ThreadSpawner and block_on need only std, a thread for
+    each of them. QueueTransport and HostTimer of queue need neither
+    sockets nor threads, the host moves them, for wasm32. the ones of
+    tokio, and a connection with async calls, are in the tokio feature.
+*/
+
+pub mod queue;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod udp;
+
+use crate::ice::network::Transmit;
+use crate::time::Instant;
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// A task of a Spawner.
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+pub trait Transport {
+    /// Sends from the socket or the stream `transmit.source` names, Pending
+    /// while it cannot take more.
+    fn poll_send(&mut self, cx: &mut Context<'_>, transmit: &Transmit) -> Poll<io::Result<()>>;
+
+    /// The next datagram, or data of a stream, received. Its destination
+    /// is the local address.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Transmit>>;
+
+    /// Sends the first transmits it can take and returns how many, Pending
+    /// when none. The one that failed is the first of the next call.
+    fn poll_send_batch(
+        &mut self,
+        cx: &mut Context<'_>,
+        transmits: &[Transmit],
+    ) -> Poll<io::Result<usize>> {
+        for (i, transmit) in transmits.iter().enumerate() {
+            match self.poll_send(cx, transmit) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) if i == 0 => return Poll::Ready(Err(e)),
+                Poll::Pending if i == 0 => return Poll::Pending,
+                _ => return Poll::Ready(Ok(i)),
+            }
+        }
+        Poll::Ready(Ok(transmits.len()))
+    }
+
+    /// Appends the datagrams received to `received` and returns how many,
+    /// one at least.
+    fn poll_recv_batch(
+        &mut self,
+        cx: &mut Context<'_>,
+        received: &mut Vec<Transmit>,
+    ) -> Poll<io::Result<usize>> {
+        self.poll_recv(cx).map_ok(|v| {
+            received.push(v);
+            1
+        })
+    }
+
+    /// Gives back the data of a transmit received once it is handled, for
+    /// the next ones.
+    fn recycle(&mut self, _data: Vec<u8>) {}
+}
+
+/// When a non-blocking socket can be used again, like the AsyncFd of tokio.
+pub trait Readiness {
+    /// `Ready` while it may be readable, otherwise `cx` is woken then.
+    fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    fn poll_write_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// The socket would block after all.
+    fn clear_read_ready(&mut self);
+
+    fn clear_write_ready(&mut self);
+}
+
+pub trait Timer {
+    /// What the deadlines are compared with.
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    /// Replaces the deadline.
+    fn reset(&mut self, deadline: Instant);
+
+    /// `Ready` once when the deadline is reached, otherwise `cx` is woken
+    /// then.
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()>;
+}
+
+pub trait Spawner {
+    fn spawn(&self, task: Task);
+}
+
+// 期限とそれを待つtask．
+#[derive(Default)]
+struct TimerState {
+    deadline: Option<Instant>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+// 期限まで眠るthreadによるTimer．
+pub struct ThreadTimer {
+    shared: Arc<(Mutex<TimerState>, Condvar)>,
+}
+
+impl ThreadTimer {
+    pub fn new() -> Self {
+        let shared = Arc::new((Mutex::new(TimerState::default()), Condvar::new()));
+        let thread_shared = shared.clone();
+        thread::spawn(move || {
+            let (state, condvar) = &*thread_shared;
+            let mut state = state.lock().unwrap();
+            while !state.closed {
+                let now = Instant::now();
+                state = match state.deadline {
+                    Some(v) if state.waker.is_some() && v > now => {
+                        condvar.wait_timeout(state, v - now).unwrap().0
+                    }
+                    Some(_) if state.waker.is_some() => {
+                        if let Some(waker) = state.waker.take() {
+                            waker.wake();
+                        }
+                        state
+                    }
+                    _ => condvar.wait(state).unwrap(),
+                };
+            }
+        });
+        ThreadTimer { shared }
+    }
+}
+
+impl Default for ThreadTimer {
+    fn default() -> Self {
+        ThreadTimer::new()
+    }
+}
+
+impl Drop for ThreadTimer {
+    fn drop(&mut self) {
+        let (state, condvar) = &*self.shared;
+        state.lock().unwrap().closed = true;
+        condvar.notify_one();
+    }
+}
+
+impl Timer for ThreadTimer {
+    fn reset(&mut self, deadline: Instant) {
+        let (state, condvar) = &*self.shared;
+        state.lock().unwrap().deadline = Some(deadline);
+        condvar.notify_one();
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let (state, condvar) = &*self.shared;
+        let mut state = state.lock().unwrap();
+        if state.deadline.is_some_and(|v| v <= Instant::now()) {
+            state.deadline = None;
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        condvar.notify_one();
+        Poll::Pending
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `future` on the current thread, parked while it is pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(v) = future.as_mut().poll(&mut cx) {
+            return v;
+        }
+        thread::park();
+    }
+}
+
+// taskごとにthreadを立ててblock_onする．
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadSpawner;
+
+impl Spawner for ThreadSpawner {
+    fn spawn(&self, task: Task) {
+        thread::spawn(move || block_on(task));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    struct Sleep<'a> {
+        timer: &'a mut ThreadTimer,
+    }
+
+    impl<'a> Future for Sleep<'a> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.timer.poll_expired(cx)
+        }
+    }
+
+    #[test]
+    fn thread_runtime_test() {
+        let (sender, receiver) = mpsc::channel();
+        ThreadSpawner.spawn(Box::pin(async move {
+            let mut timer = ThreadTimer::new();
+            let start = timer.now();
+            timer.reset(start + Duration::from_millis(20));
+            Sleep { timer: &mut timer }.await;
+            sender.send(timer.now() - start).unwrap();
+        }));
+        let elapsed = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(elapsed >= Duration::from_millis(20));
+
+        // an expired deadline is ready once.
+        let mut timer = ThreadTimer::new();
+        timer.reset(Instant::now());
+        block_on(Sleep { timer: &mut timer });
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(timer.poll_expired(&mut cx), Poll::Pending);
+    }
+}
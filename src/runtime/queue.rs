@@ -0,0 +1,268 @@
+/*
+    a Transport and a Timer moved by the host, for the targets without
+    sockets or threads: wasm32 in a browser worker, where the datagrams
+    come and go through a WebSocket or WebTransport of the page, or a
+    WASI edge runtime.
+
+      QueueTransport   push_received  a datagram the host received
+                       pop_transmit   the next one the host is to send,
+                                      max_transmits of them queued at most
+      HostTimer        advance        the time of the host, waking the
+                                      task when the deadline is reached
+
+    a QueueTransport and its clones share the queues, one given to the
+    PeerConnectionDriver and one kept by the host. the tasks are woken
+    by the calls of the host.
+*/
+
+use crate::ice::network::Transmit;
+use crate::runtime::{Timer, Transport};
+use crate::time::Instant;
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+pub const DEFAULT_MAX_TRANSMITS: usize = 256;
+
+// 受信したもの，送るもの，それぞれを待つtask．
+#[derive(Debug, Default)]
+struct Queues {
+    received: VecDeque<Transmit>,
+    transmits: VecDeque<Transmit>,
+    recv_waker: Option<Waker>,
+    send_waker: Option<Waker>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueTransport {
+    max_transmits: usize,
+    queues: Arc<Mutex<Queues>>,
+}
+
+impl QueueTransport {
+    pub fn new(max_transmits: usize) -> Self {
+        QueueTransport {
+            max_transmits,
+            queues: Arc::new(Mutex::new(Queues::default())),
+        }
+    }
+
+    pub fn get_max_transmits(&self) -> usize {
+        self.max_transmits
+    }
+
+    /// A datagram the host received, `destination` the local address.
+    pub fn push_received(&self, transmit: Transmit) {
+        let mut queues = self.queues.lock().unwrap();
+        queues.received.push_back(transmit);
+        if let Some(waker) = queues.recv_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// The next datagram to send.
+    pub fn pop_transmit(&self) -> Option<Transmit> {
+        let mut queues = self.queues.lock().unwrap();
+        let transmit = queues.transmits.pop_front();
+        if let Some(waker) = queues.send_waker.take() {
+            waker.wake();
+        }
+        transmit
+    }
+
+    /// The datagrams to send, not popped yet.
+    pub fn get_pending(&self) -> usize {
+        self.queues.lock().unwrap().transmits.len()
+    }
+}
+
+impl Default for QueueTransport {
+    fn default() -> Self {
+        QueueTransport::new(DEFAULT_MAX_TRANSMITS)
+    }
+}
+
+impl Transport for QueueTransport {
+    fn poll_send(&mut self, cx: &mut Context<'_>, transmit: &Transmit) -> Poll<io::Result<()>> {
+        let mut queues = self.queues.lock().unwrap();
+        if queues.transmits.len() >= self.max_transmits {
+            queues.send_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        queues.transmits.push_back(transmit.clone());
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Transmit>> {
+        let mut queues = self.queues.lock().unwrap();
+        match queues.received.pop_front() {
+            Some(v) => Poll::Ready(Ok(v)),
+            None => {
+                queues.recv_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn poll_recv_batch(
+        &mut self,
+        cx: &mut Context<'_>,
+        received: &mut Vec<Transmit>,
+    ) -> Poll<io::Result<usize>> {
+        let mut queues = self.queues.lock().unwrap();
+        if queues.received.is_empty() {
+            queues.recv_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let count = queues.received.len();
+        received.extend(queues.received.drain(..));
+        Poll::Ready(Ok(count))
+    }
+}
+
+// hostの時計と期限，それを待つtask．
+#[derive(Debug)]
+struct Clock {
+    now: Instant,
+    deadline: Option<Instant>,
+    waker: Option<Waker>,
+}
+
+/// A Timer of the time the host gives, shared by its clones.
+#[derive(Debug, Clone)]
+pub struct HostTimer {
+    clock: Arc<Mutex<Clock>>,
+}
+
+impl HostTimer {
+    pub fn new(now: Instant) -> Self {
+        HostTimer {
+            clock: Arc::new(Mutex::new(Clock {
+                now,
+                deadline: None,
+                waker: None,
+            })),
+        }
+    }
+
+    /// Moves the time to `now`, never back.
+    pub fn advance(&self, now: Instant) {
+        let mut clock = self.clock.lock().unwrap();
+        clock.now = clock.now.max(now);
+        if clock.deadline.is_some_and(|v| v <= clock.now) {
+            if let Some(waker) = clock.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// The deadline the host is to call advance at, the latest.
+    pub fn get_deadline(&self) -> Option<Instant> {
+        self.clock.lock().unwrap().deadline
+    }
+}
+
+impl Timer for HostTimer {
+    fn now(&self) -> Instant {
+        self.clock.lock().unwrap().now
+    }
+
+    fn reset(&mut self, deadline: Instant) {
+        self.clock.lock().unwrap().deadline = Some(deadline);
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut clock = self.clock.lock().unwrap();
+        if clock.deadline.is_some_and(|v| v <= clock.now) {
+            clock.deadline = None;
+            return Poll::Ready(());
+        }
+        clock.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct CountWaker(AtomicUsize);
+
+    impl Wake for CountWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn new_transmit(data: &[u8]) -> Transmit {
+        let local = "10.0.0.1:5000".parse().unwrap();
+        let remote = "10.0.0.2:5000".parse().unwrap();
+        Transmit::new(local, remote, data.to_vec())
+    }
+
+    #[test]
+    fn queue_transport_test() {
+        let count = Arc::new(CountWaker::default());
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+        let host = QueueTransport::new(2);
+        let mut transport = host.clone();
+
+        let mut received = vec![];
+        assert!(transport.poll_recv_batch(&mut cx, &mut received).is_pending());
+        host.push_received(new_transmit(b"a"));
+        host.push_received(new_transmit(b"b"));
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        match transport.poll_recv_batch(&mut cx, &mut received) {
+            Poll::Ready(Ok(2)) => {}
+            _ => panic!(),
+        }
+        assert_eq!(received[1].data, b"b");
+
+        // two of them queued at most.
+        let transmits = [new_transmit(b"1"), new_transmit(b"2"), new_transmit(b"3")];
+        match transport.poll_send_batch(&mut cx, &transmits) {
+            Poll::Ready(Ok(2)) => {}
+            _ => panic!(),
+        }
+        assert!(transport.poll_send(&mut cx, &transmits[2]).is_pending());
+        assert_eq!(host.get_pending(), 2);
+        assert_eq!(host.pop_transmit().unwrap().data, b"1");
+        assert_eq!(count.0.load(Ordering::SeqCst), 2);
+        assert!(transport.poll_send(&mut cx, &transmits[2]).is_ready());
+        assert_eq!(host.pop_transmit().unwrap().data, b"2");
+        assert_eq!(host.pop_transmit().unwrap().data, b"3");
+        assert_eq!(host.pop_transmit(), None);
+    }
+
+    #[test]
+    fn host_timer_test() {
+        let count = Arc::new(CountWaker::default());
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+        let start = Instant::now();
+        let host = HostTimer::new(start);
+        let mut timer = host.clone();
+
+        timer.reset(start + Duration::from_millis(20));
+        assert_eq!(host.get_deadline(), Some(start + Duration::from_millis(20)));
+        assert_eq!(timer.poll_expired(&mut cx), Poll::Pending);
+        host.advance(start + Duration::from_millis(10));
+        assert_eq!(count.0.load(Ordering::SeqCst), 0);
+        host.advance(start + Duration::from_millis(20));
+        assert_eq!(count.0.load(Ordering::SeqCst), 1);
+        assert_eq!(timer.now(), start + Duration::from_millis(20));
+        assert_eq!(timer.poll_expired(&mut cx), Poll::Ready(()));
+        assert_eq!(timer.poll_expired(&mut cx), Poll::Pending);
+
+        // the time does not go back.
+        host.advance(start);
+        assert_eq!(timer.now(), start + Duration::from_millis(20));
+    }
+}
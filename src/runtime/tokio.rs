@@ -0,0 +1,681 @@
+// https://docs.rs/tokio/latest/tokio/
+
+/*
+    the runtime traits on tokio, called from a task of a tokio runtime, and
+    a connection an async task drives with them:
+
+      TokioTransport   the Transport and the NetworkProvider of the tokio
+                       UDP sockets and TCP streams, the interfaces of the
+                       system unless given
+      TokioTimer       a Sleep of tokio, paused with the time of tokio
+      TokioSpawner     tokio::spawn on a Handle
+
+      TokioPeerConnection
+        next_event           the PeerConnectionDriver until the next event,
+                             also a Stream of them
+        create_offer         awaited until gathering is done, the candidates
+        create_answer        written to the description returned
+        connect              awaited until connected
+        TcpConnect           connected by tokio, not told
+
+    the calls of RtcPeerConnection are made on get_connection_mut between
+    the events, the time of them is now().
+*/
+
+use crate::dtls::transport::DtlsBackend;
+use crate::ice::mdns::{MDNS_IPV4_GROUP, MDNS_PORT};
+use crate::ice::network::system::SystemNetwork;
+use crate::ice::network::{NetworkChange, NetworkInterface, NetworkProvider, Transmit};
+use crate::jsep::signaling::IceCandidateInit;
+use crate::jsep::Description;
+use crate::rtcpeerconnection::driver::PeerConnectionDriver;
+use crate::rtcpeerconnection::{
+    IceGatheringState, PeerConnectionEvent, PeerConnectionState, Result, RtcPeerConnection,
+};
+use crate::runtime::{Spawner, Task, Timer, Transport};
+use crate::time::Instant;
+
+use futures_core::Stream;
+use std::collections::{HashMap, VecDeque};
+use std::future::{poll_fn, Future};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::ReadBuf;
+use tokio::net::{TcpListener, TcpSocket, TcpStream, UdpSocket};
+use tokio::runtime::Handle;
+use tokio::time::Sleep;
+
+const RECEIVE_BUFFER_SIZE: usize = 65536;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// streamと，書き切れなかったdata．
+struct TcpConnection {
+    stream: TcpStream,
+    unsent: Vec<u8>,
+}
+
+impl TcpConnection {
+    /// Writes what it can of `unsent`, `Pending` while some is left.
+    fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.unsent.is_empty() {
+            match self.stream.try_write(&self.unsent) {
+                Ok(length) => {
+                    self.unsent.drain(..length);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    match self.stream.poll_write_ready(cx) {
+                        Poll::Ready(Ok(())) => continue,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+// tokioのsocketによるTransportとNetworkProvider．
+pub struct TokioTransport {
+    system: SystemNetwork,
+    interfaces: Option<Vec<NetworkInterface>>,
+    udp: HashMap<SocketAddr, UdpSocket>,
+    listeners: HashMap<SocketAddr, TcpListener>,
+    // 受け入れたstreamはlistenerのアドレスをローカルとする．
+    streams: HashMap<(SocketAddr, SocketAddr), TcpConnection>,
+    buffer: Vec<u8>,
+}
+
+impl TokioTransport {
+    /// On the interfaces of the system, watched for changes.
+    pub fn new() -> Self {
+        TokioTransport {
+            system: SystemNetwork::new(),
+            interfaces: None,
+            udp: HashMap::new(),
+            listeners: HashMap::new(),
+            streams: HashMap::new(),
+            buffer: vec![0; RECEIVE_BUFFER_SIZE],
+        }
+    }
+
+    /// On `interfaces` only, not watched.
+    pub fn with_interfaces(interfaces: Vec<NetworkInterface>) -> Self {
+        TokioTransport {
+            interfaces: Some(interfaces),
+            ..TokioTransport::new()
+        }
+    }
+
+    /// A TCP stream connected by the application, `local` the address the
+    /// transmits to `remote` come from.
+    pub fn add_stream(&mut self, local: SocketAddr, remote: SocketAddr, stream: TcpStream) {
+        let _ = stream.set_nodelay(true);
+        let connection = TcpConnection {
+            stream,
+            unsent: vec![],
+        };
+        self.streams.insert((local, remote), connection);
+    }
+
+    /// Closes the socket, the listener or the streams bound to `local`.
+    pub fn close(&mut self, local: SocketAddr) {
+        self.udp.remove(&local);
+        self.listeners.remove(&local);
+        self.streams.retain(|k, _| k.0 != local);
+    }
+
+    fn add_udp(&mut self, socket: std::net::UdpSocket) -> io::Result<SocketAddr> {
+        socket.set_nonblocking(true)?;
+        let local = socket.local_addr()?;
+        self.udp.insert(local, UdpSocket::from_std(socket)?);
+        Ok(local)
+    }
+}
+
+impl Default for TokioTransport {
+    fn default() -> Self {
+        TokioTransport::new()
+    }
+}
+
+impl NetworkProvider for TokioTransport {
+    fn get_interfaces(&mut self) -> io::Result<Vec<NetworkInterface>> {
+        match &self.interfaces {
+            Some(v) => Ok(v.clone()),
+            None => self.system.get_interfaces(),
+        }
+    }
+
+    fn bind_udp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        self.add_udp(std::net::UdpSocket::bind(addr)?)
+    }
+
+    fn connect_tcp(
+        &mut self,
+        _local: SocketAddr,
+        remote: SocketAddr,
+        tls: bool,
+    ) -> io::Result<SocketAddr> {
+        if tls {
+            return Err(io::Error::other("TLS is not supported by this provider"));
+        }
+        // the provider is not async, a TURN server connected like SystemNetwork.
+        let stream = std::net::TcpStream::connect_timeout(&remote, CONNECT_TIMEOUT)?;
+        stream.set_nonblocking(true)?;
+        let local = stream.local_addr()?;
+        self.add_stream(local, remote, TcpStream::from_std(stream)?);
+        Ok(local)
+    }
+
+    fn listen_tcp(&mut self, addr: SocketAddr) -> io::Result<SocketAddr> {
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local = listener.local_addr()?;
+        self.listeners
+            .insert(local, TcpListener::from_std(listener)?);
+        Ok(local)
+    }
+
+    fn bind_mdns(&mut self) -> io::Result<SocketAddr> {
+        let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, MDNS_PORT))?;
+        socket.join_multicast_v4(&MDNS_IPV4_GROUP, &Ipv4Addr::UNSPECIFIED)?;
+        self.add_udp(socket)
+    }
+
+    fn poll_change(&mut self) -> Option<NetworkChange> {
+        match self.interfaces {
+            Some(_) => None,
+            None => self.system.poll_change(),
+        }
+    }
+}
+
+impl Transport for TokioTransport {
+    fn poll_send(&mut self, cx: &mut Context<'_>, transmit: &Transmit) -> Poll<io::Result<()>> {
+        if let Some(connection) = self
+            .streams
+            .get_mut(&(transmit.source, transmit.destination))
+        {
+            // the data is taken whole, a partial write would break the framing.
+            if connection.poll_flush(cx)?.is_pending() {
+                return Poll::Pending;
+            }
+            connection.unsent.extend_from_slice(&transmit.data);
+            let _ = connection.poll_flush(cx)?;
+            return Poll::Ready(Ok(()));
+        }
+        match self.udp.get(&transmit.source) {
+            Some(socket) => socket
+                .poll_send_to(cx, &transmit.data, transmit.destination)
+                .map_ok(|_| ()),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no socket is bound to the source address",
+            ))),
+        }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Transmit>> {
+        for (local, listener) in &self.listeners {
+            while let Poll::Ready(v) = listener.poll_accept(cx) {
+                let (stream, remote) = v?;
+                let _ = stream.set_nodelay(true);
+                let connection = TcpConnection {
+                    stream,
+                    unsent: vec![],
+                };
+                self.streams.insert((*local, remote), connection);
+            }
+        }
+        for (local, socket) in &self.udp {
+            let mut buffer = ReadBuf::new(&mut self.buffer);
+            match socket.poll_recv_from(cx, &mut buffer) {
+                Poll::Ready(Ok(source)) => {
+                    let data = buffer.filled().to_vec();
+                    return Poll::Ready(Ok(Transmit::new(source, *local, data)));
+                }
+                // the ICMP error of an earlier datagram.
+                Poll::Ready(Err(ref e)) if e.kind() == io::ErrorKind::ConnectionReset => {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => {}
+            }
+        }
+        let mut closed = vec![];
+        let mut received = None;
+        for (key, connection) in self.streams.iter_mut() {
+            if let Poll::Ready(Err(_)) = connection.poll_flush(cx) {
+                closed.push(*key);
+                continue;
+            }
+            if received.is_some() {
+                continue;
+            }
+            while connection.stream.poll_read_ready(cx).is_ready() {
+                match connection.stream.try_read(&mut self.buffer) {
+                    Ok(0) => closed.push(*key),
+                    Ok(length) => {
+                        let data = self.buffer[..length].to_vec();
+                        received = Some(Transmit::new(key.1, key.0, data));
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    Err(_) => closed.push(*key),
+                }
+                break;
+            }
+        }
+        for key in closed {
+            self.streams.remove(&key);
+        }
+        match received {
+            Some(v) => Poll::Ready(Ok(v)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+// tokioの時計で眠るTimer．
+pub struct TokioTimer {
+    sleep: Pin<Box<Sleep>>,
+    deadline: Option<Instant>,
+}
+
+impl TokioTimer {
+    pub fn new() -> Self {
+        TokioTimer {
+            sleep: Box::pin(tokio::time::sleep(Duration::ZERO)),
+            deadline: None,
+        }
+    }
+}
+
+impl Default for TokioTimer {
+    fn default() -> Self {
+        TokioTimer::new()
+    }
+}
+
+impl Timer for TokioTimer {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn reset(&mut self, deadline: Instant) {
+        if self.deadline != Some(deadline) {
+            self.deadline = Some(deadline);
+            self.sleep.as_mut().reset(deadline.into());
+        }
+    }
+
+    fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.deadline.is_none() {
+            return Poll::Pending;
+        }
+        self.sleep.as_mut().poll(cx).map(|_| {
+            self.deadline = None;
+        })
+    }
+}
+
+// tokioのruntimeでtaskを走らせる．
+#[derive(Debug, Clone)]
+pub struct TokioSpawner {
+    handle: Handle,
+}
+
+impl TokioSpawner {
+    pub fn new(handle: Handle) -> Self {
+        TokioSpawner { handle }
+    }
+
+    /// The runtime of the calling task.
+    pub fn current() -> Self {
+        TokioSpawner::new(Handle::current())
+    }
+}
+
+impl Spawner for TokioSpawner {
+    fn spawn(&self, task: Task) {
+        self.handle.spawn(task);
+    }
+}
+
+type Connecting = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+/// Adds the candidates of `candidates` to the m= sections of `sdp` they
+/// are of, an empty one as end-of-candidates.
+fn add_candidates(sdp: &str, candidates: &[IceCandidateInit]) -> String {
+    let mut out = String::new();
+    let mut section: Option<(u16, Option<String>)> = None;
+    let flush = |out: &mut String, section: &Option<(u16, Option<String>)>| {
+        let (index, mid) = match section {
+            Some(v) => v,
+            None => return,
+        };
+        for candidate in candidates.iter().filter(|v| match &v.sdp_mid {
+            Some(v) => Some(v) == mid.as_ref(),
+            None => v.sdp_mline_index == Some(*index),
+        }) {
+            if candidate.candidate.trim().is_empty() {
+                out.push_str("a=end-of-candidates\r\n");
+            } else {
+                out.push_str(&format!("a={}\r\n", candidate.candidate));
+            }
+        }
+    };
+    for line in sdp.lines().map(|v| v.trim_end_matches('\r')) {
+        if line.starts_with("m=") {
+            flush(&mut out, &section);
+            let index = section.as_ref().map_or(0, |v| v.0 + 1);
+            section = Some((index, None));
+        } else if let (Some(v), Some(section)) = (line.strip_prefix("a=mid:"), section.as_mut()) {
+            section.1 = Some(v.to_string());
+        }
+        out.push_str(line);
+        out.push_str("\r\n");
+    }
+    flush(&mut out, &section);
+    out
+}
+
+// tokioのtaskで回すRtcPeerConnection．
+pub struct TokioPeerConnection<B: DtlsBackend> {
+    connection: RtcPeerConnection<B>,
+    driver: PeerConnectionDriver<TokioTransport, TokioTimer>,
+    // asyncな呼び出しの間に来たevent．
+    events: VecDeque<PeerConnectionEvent>,
+    connecting: Vec<(SocketAddr, SocketAddr, Connecting)>,
+}
+
+impl<B: DtlsBackend> TokioPeerConnection<B> {
+    pub fn new(connection: RtcPeerConnection<B>, transport: TokioTransport) -> Self {
+        TokioPeerConnection {
+            connection,
+            driver: PeerConnectionDriver::new(transport, TokioTimer::new()),
+            events: VecDeque::new(),
+            connecting: vec![],
+        }
+    }
+
+    pub fn get_connection(&self) -> &RtcPeerConnection<B> {
+        &self.connection
+    }
+
+    /// For the calls between the events, woken by the next poll.
+    pub fn get_connection_mut(&mut self) -> &mut RtcPeerConnection<B> {
+        &mut self.connection
+    }
+
+    pub fn get_transport_mut(&mut self) -> &mut TokioTransport {
+        self.driver.get_transport_mut()
+    }
+
+    /// The time of tokio, for the calls of the connection.
+    pub fn now(&self) -> Instant {
+        self.driver.get_timer().now()
+    }
+
+    /// Moves the I/O of the connection and returns its next event.
+    pub fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<PeerConnectionEvent>> {
+        if let Some(v) = self.events.pop_front() {
+            return Poll::Ready(Ok(v));
+        }
+        loop {
+            self.poll_connecting(cx);
+            match self.driver.poll_event(&mut self.connection, cx) {
+                Poll::Ready(Ok(PeerConnectionEvent::TcpConnect { local, remote })) => {
+                    self.start_connecting(local, remote);
+                }
+                v => return v,
+            }
+        }
+    }
+
+    pub async fn next_event(&mut self) -> io::Result<PeerConnectionEvent> {
+        poll_fn(|cx| self.poll_event(cx)).await
+    }
+
+    /// An offer set as the local description, with the candidates once
+    /// gathering is done.
+    pub async fn create_offer(&mut self) -> Result<Description> {
+        let offer = self.connection.create_offer()?;
+        self.set_local_description(&offer).await
+    }
+
+    pub async fn create_answer(&mut self) -> Result<Description> {
+        let answer = self.connection.create_answer()?;
+        self.set_local_description(&answer).await
+    }
+
+    /// Sets `description` and returns it with the candidates once gathering
+    /// is done. The events until then are kept for next_event.
+    pub async fn set_local_description(
+        &mut self,
+        description: &Description,
+    ) -> Result<Description> {
+        let now = self.now();
+        let network = self.driver.get_transport_mut();
+        self.connection
+            .set_local_description(description, network, now)?;
+        let mut candidates = vec![];
+        let mut events = vec![];
+        loop {
+            // the candidates gathered at once are queued already.
+            let event = if self.connection.get_ice_gathering_state() == IceGatheringState::Gathering
+            {
+                poll_fn(|cx| self.poll_event(cx)).await?
+            } else {
+                match self.connection.poll_event() {
+                    Some(PeerConnectionEvent::TcpConnect { local, remote }) => {
+                        self.start_connecting(local, remote);
+                        continue;
+                    }
+                    Some(v) => v,
+                    None => break,
+                }
+            };
+            if let PeerConnectionEvent::IceCandidate(init) = &event {
+                candidates.push(init.clone());
+            }
+            events.push(event);
+        }
+        self.events.extend(events);
+        Ok(Description::new(
+            description.sdp_type,
+            &add_candidates(&description.sdp, &candidates),
+        ))
+    }
+
+    /// Awaits the connected state, an error when it fails or is closed.
+    pub async fn connect(&mut self) -> Result<()> {
+        let mut events = vec![];
+        let result = loop {
+            match self.connection.get_connection_state() {
+                PeerConnectionState::Connected => break Ok(()),
+                PeerConnectionState::Failed | PeerConnectionState::Closed => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "the connection failed or is closed",
+                    )
+                    .into())
+                }
+                _ => {}
+            }
+            match poll_fn(|cx| self.poll_event(cx)).await {
+                Ok(v) => events.push(v),
+                Err(e) => break Err(e.into()),
+            }
+        };
+        self.events.extend(events);
+        result
+    }
+
+    fn start_connecting(&mut self, local: SocketAddr, remote: SocketAddr) {
+        // from the address of the candidate, the port chosen unless given.
+        let mut source = local;
+        if source.port() == 9 {
+            source.set_port(0);
+        }
+        let connect = async move {
+            let socket = match source {
+                SocketAddr::V4(_) => TcpSocket::new_v4()?,
+                SocketAddr::V6(_) => TcpSocket::new_v6()?,
+            };
+            socket.bind(source)?;
+            tokio::time::timeout(CONNECT_TIMEOUT, socket.connect(remote))
+                .await
+                .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+        };
+        self.connecting.push((local, remote, Box::pin(connect)));
+    }
+
+    fn poll_connecting(&mut self, cx: &mut Context<'_>) {
+        let now = self.now();
+        let mut i = 0;
+        while i < self.connecting.len() {
+            let (base, remote, connect) = &mut self.connecting[i];
+            let result = match connect.as_mut().poll(cx) {
+                Poll::Ready(v) => v,
+                Poll::Pending => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let (base, remote) = (*base, *remote);
+            drop(self.connecting.swap_remove(i));
+            let agent = self.connection.get_ice_agent_mut();
+            match result.and_then(|v| Ok((v.local_addr()?, v))) {
+                Ok((local, stream)) => {
+                    self.driver
+                        .get_transport_mut()
+                        .add_stream(local, remote, stream);
+                    agent.handle_tcp_connected(base, remote, local, now);
+                }
+                Err(_) => agent.handle_tcp_connect_failed(base, remote),
+            }
+        }
+    }
+}
+
+impl<B: DtlsBackend + Unpin> Stream for TokioPeerConnection<B> {
+    type Item = io::Result<PeerConnectionEvent>;
+
+    /// Ends once the connection is closed and its events are taken.
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.events.is_empty()
+            && self.connection.get_connection_state() == PeerConnectionState::Closed
+        {
+            return Poll::Ready(None);
+        }
+        self.poll_event(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtcpeerconnection::test::new_peer;
+    use crate::sdp::media::{Direction, MediaKind};
+
+    fn new_transport() -> TokioTransport {
+        TokioTransport::with_interfaces(vec![NetworkInterface::new(
+            "lo",
+            1,
+            vec!["127.0.0.1".parse().unwrap()],
+        )])
+    }
+
+    #[test]
+    fn add_candidates_test() {
+        let sdp = "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=mid:0\r\n\
+                   m=video 9 UDP/TLS/RTP/SAVPF 96\r\na=mid:1\r\n";
+        let candidates = vec![
+            IceCandidateInit {
+                candidate: "candidate:1 1 udp 2130706431 127.0.0.1 5000 typ host".to_string(),
+                sdp_mid: Some("0".to_string()),
+                sdp_mline_index: Some(0),
+                username_fragment: None,
+            },
+            IceCandidateInit {
+                candidate: String::new(),
+                sdp_mid: None,
+                sdp_mline_index: Some(0),
+                username_fragment: None,
+            },
+        ];
+        assert_eq!(
+            add_candidates(sdp, &candidates),
+            "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=mid:0\r\n\
+             a=candidate:1 1 udp 2130706431 127.0.0.1 5000 typ host\r\n\
+             a=end-of-candidates\r\n\
+             m=video 9 UDP/TLS/RTP/SAVPF 96\r\na=mid:1\r\n"
+        );
+    }
+
+    #[test]
+    fn tokio_peer_connection_test() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut a = TokioPeerConnection::new(new_peer(b"a"), new_transport());
+            let mut b = TokioPeerConnection::new(new_peer(b"b"), new_transport());
+            a.get_connection_mut()
+                .add_transceiver(MediaKind::Audio, Direction::SendRecv);
+
+            // without trickle, the candidates are in the descriptions.
+            let offer = a.create_offer().await.unwrap();
+            assert!(offer.sdp.contains("a=candidate:"));
+            assert!(offer.sdp.contains("a=end-of-candidates"));
+            // the events of gathering are kept for the stream.
+            let mut stream = Pin::new(&mut a);
+            let event = poll_fn(|cx| stream.as_mut().poll_next(cx)).await;
+            assert_eq!(
+                event.unwrap().unwrap(),
+                PeerConnectionEvent::NegotiationNeeded
+            );
+            let now = b.now();
+            b.get_connection_mut()
+                .set_remote_description(&offer, now)
+                .unwrap();
+            let answer = b.create_answer().await.unwrap();
+            assert!(answer.sdp.contains("a=candidate:"));
+            let now = a.now();
+            a.get_connection_mut()
+                .set_remote_description(&answer, now)
+                .unwrap();
+
+            let connected = poll_fn(|cx| {
+                for peer in [&mut a, &mut b] {
+                    while let Poll::Ready(v) = peer.poll_event(cx) {
+                        v.unwrap();
+                    }
+                }
+                if [&a, &b].iter().all(|v| {
+                    v.get_connection().get_connection_state() == PeerConnectionState::Connected
+                }) {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            });
+            tokio::time::timeout(Duration::from_secs(10), connected)
+                .await
+                .unwrap();
+            a.connect().await.unwrap();
+
+            // the end of the stream.
+            let now = a.now();
+            a.get_connection_mut().close(now);
+            let mut stream = Pin::new(&mut a);
+            assert!(poll_fn(|cx| stream.as_mut().poll_next(cx)).await.is_none());
+        });
+    }
+}
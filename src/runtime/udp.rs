@@ -0,0 +1,707 @@
+// https://man7.org/linux/man-pages/man2/sendmmsg.2.html
+// https://man7.org/linux/man-pages/man2/recvmmsg.2.html
+// https://man7.org/linux/man-pages/man7/udp.7.html
+
+/*
+    a non-blocking UDP socket sending and receiving up to MAX_BATCH
+    messages a system call on Linux, one elsewhere.
+
+      GSO   transmits in a row to one destination, all of the size of the
+            first but the last that may be shorter, go in one message with
+            UDP_SEGMENT, split by the kernel or the NIC
+      GRO   datagrams of one source coalesced by the kernel, split again
+            with the size UDP_GRO tells
+
+    GSO is turned off when the NIC fails with EIO. the datagrams received
+    are transmits from their source to the address the socket is bound to,
+    so bind it to an address of an interface. their data are of a
+    BufferPool, recycled once they are handled.
+*/
+
+use crate::ice::network::Transmit;
+use crate::octets::pool::BufferPool;
+use crate::runtime::{Readiness, Transport};
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::task::{ready, Context, Poll};
+
+/// Messages a system call.
+pub const MAX_BATCH: usize = 32;
+/// Segments of a GSO message, UDP_MAX_SEGMENTS of Linux.
+pub const MAX_SEGMENTS: usize = 64;
+const MAX_GSO_SIZE: usize = 65000;
+const RECEIVE_BUFFER_SIZE: usize = 2048;
+const GRO_BUFFER_SIZE: usize = 65535;
+
+/// How many datagrams and system calls, to tell how much batching saves.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct BatchStats {
+    pub send_calls: u64,
+    pub datagrams_sent: u64,
+    pub receive_calls: u64,
+    pub datagrams_received: u64,
+}
+
+#[derive(Debug)]
+pub struct BatchUdpSocket {
+    socket: UdpSocket,
+    local: SocketAddr,
+    gso_supported: bool,
+    gso: bool,
+    gro: bool,
+    buffers: Vec<Vec<u8>>,
+    pool: BufferPool,
+    stats: BatchStats,
+}
+
+impl BatchUdpSocket {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        BatchUdpSocket::from_socket(UdpSocket::bind(addr)?)
+    }
+
+    /// Makes it non-blocking, and turns on GSO and GRO where the system
+    /// has them.
+    pub fn from_socket(socket: UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        let local = socket.local_addr()?;
+        let (gso, gro) = sys::enable_offload(&socket);
+        let size = if gro {
+            GRO_BUFFER_SIZE
+        } else {
+            RECEIVE_BUFFER_SIZE
+        };
+        Ok(BatchUdpSocket {
+            socket,
+            local,
+            gso_supported: gso,
+            gso,
+            gro,
+            buffers: vec![vec![0; size]; sys::RECEIVE_BATCH],
+            pool: BufferPool::default(),
+            stats: BatchStats::default(),
+        })
+    }
+
+    pub fn get_local_addr(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub fn get_socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    pub fn is_gso_enabled(&self) -> bool {
+        self.gso
+    }
+
+    /// It stays off where the system has no GSO.
+    pub fn set_gso_enabled(&mut self, enabled: bool) {
+        self.gso = enabled && self.gso_supported;
+    }
+
+    pub fn is_gro_enabled(&self) -> bool {
+        self.gro
+    }
+
+    pub fn get_stats(&self) -> BatchStats {
+        self.stats
+    }
+
+    /// Where the data of the datagrams received come from.
+    pub fn get_pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    pub fn set_pool(&mut self, pool: BufferPool) {
+        self.pool = pool;
+    }
+
+    /// Gives back the data of a transmit received.
+    pub fn recycle(&mut self, data: Vec<u8>) {
+        self.pool.recycle(data);
+    }
+
+    /// Sends the first transmits it can and returns how many. The sources
+    /// are taken to be this socket.
+    pub fn send_batch(&mut self, transmits: &[Transmit]) -> io::Result<usize> {
+        if transmits.is_empty() {
+            return Ok(0);
+        }
+        let groups = group_segments(transmits, if self.gso { MAX_SEGMENTS } else { 1 });
+        self.stats.send_calls += 1;
+        let sent = match sys::send(&self.socket, transmits, &groups) {
+            Err(ref e) if self.gso && e.raw_os_error() == Some(sys::EIO) => {
+                // the NIC cannot segment them.
+                self.gso = false;
+                return self.send_batch(transmits);
+            }
+            v => v?,
+        };
+        self.stats.datagrams_sent += sent as u64;
+        Ok(sent)
+    }
+
+    /// Appends the datagrams received to `received` and returns how many,
+    /// WouldBlock when none.
+    pub fn recv_batch(&mut self, received: &mut Vec<Transmit>) -> io::Result<usize> {
+        self.stats.receive_calls += 1;
+        let length = received.len();
+        sys::recv(
+            &self.socket,
+            self.local,
+            &mut self.buffers,
+            &self.pool,
+            received,
+        )?;
+        let count = received.len() - length;
+        self.stats.datagrams_received += count as u64;
+        Ok(count)
+    }
+}
+
+/// Splits `transmits` into runs of at most `max_segments` that can be one
+/// GSO message.
+fn group_segments(transmits: &[Transmit], max_segments: usize) -> Vec<std::ops::Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    while start < transmits.len() && groups.len() < MAX_BATCH {
+        let first = &transmits[start];
+        let size = first.data.len();
+        let mut total = size;
+        let mut end = start + 1;
+        while size > 0 && end < transmits.len() && end - start < max_segments {
+            let next = &transmits[end];
+            if next.destination != first.destination
+                || next.data.is_empty()
+                || next.data.len() > size
+                || total + next.data.len() > MAX_GSO_SIZE
+            {
+                break;
+            }
+            total += next.data.len();
+            end += 1;
+            if next.data.len() < size {
+                break;
+            }
+        }
+        groups.push(start..end);
+        start = end;
+    }
+    groups
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::{MAX_BATCH, MAX_SEGMENTS};
+    use crate::ice::network::Transmit;
+    use crate::octets::pool::BufferPool;
+
+    use std::io;
+    use std::mem;
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
+    use std::ops::Range;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    pub const EIO: i32 = libc::EIO;
+    pub const RECEIVE_BATCH: usize = MAX_BATCH;
+
+    // cmsghdrに揃えた一つのcontrol message．
+    #[repr(C, align(8))]
+    #[derive(Clone, Copy)]
+    struct Control([u8; 32]);
+
+    fn to_sockaddr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let length = match addr {
+            SocketAddr::V4(v) => {
+                let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = v.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*v.ip()).to_be();
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(v) => {
+                let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = v.port().to_be();
+                sin6.sin6_addr.s6_addr = v.ip().octets();
+                sin6.sin6_flowinfo = v.flowinfo();
+                sin6.sin6_scope_id = v.scope_id();
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+        (storage, length as libc::socklen_t)
+    }
+
+    fn from_sockaddr(storage: &libc::sockaddr_storage) -> Option<SocketAddr> {
+        match i32::from(storage.ss_family) {
+            libc::AF_INET => {
+                let sin = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+                let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+                Some(SocketAddrV4::new(ip, u16::from_be(sin.sin_port)).into())
+            }
+            libc::AF_INET6 => {
+                let sin6 = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+                let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+                let port = u16::from_be(sin6.sin6_port);
+                let v = SocketAddrV6::new(ip, port, sin6.sin6_flowinfo, sin6.sin6_scope_id);
+                Some(v.into())
+            }
+            _ => None,
+        }
+    }
+
+    fn set_option(socket: &UdpSocket, name: libc::c_int, value: libc::c_int) -> bool {
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_UDP,
+                name,
+                &value as *const _ as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        result == 0
+    }
+
+    /// Whether GSO and GRO can be used.
+    pub fn enable_offload(socket: &UdpSocket) -> (bool, bool) {
+        // a segment size of 0 leaves the datagrams without UDP_SEGMENT as they are.
+        (
+            set_option(socket, libc::UDP_SEGMENT, 0),
+            set_option(socket, libc::UDP_GRO, 1),
+        )
+    }
+
+    pub fn send(
+        socket: &UdpSocket,
+        transmits: &[Transmit],
+        groups: &[Range<usize>],
+    ) -> io::Result<usize> {
+        let payloads: Vec<Vec<u8>> = groups
+            .iter()
+            .filter(|v| v.len() > 1)
+            .map(|v| {
+                transmits[v.clone()]
+                    .iter()
+                    .flat_map(|v| v.data.clone())
+                    .collect()
+            })
+            .collect();
+        let mut payloads = payloads.iter();
+        let mut names = Vec::with_capacity(groups.len());
+        let mut iovecs = Vec::with_capacity(groups.len());
+        let mut controls = vec![Control([0; 32]); groups.len()];
+        for group in groups {
+            let first = &transmits[group.start];
+            names.push(to_sockaddr(first.destination));
+            let data = if group.len() > 1 {
+                payloads.next().unwrap_or(&first.data)
+            } else {
+                &first.data
+            };
+            iovecs.push(libc::iovec {
+                iov_base: data.as_ptr() as *mut libc::c_void,
+                iov_len: data.len(),
+            });
+        }
+        let mut messages: Vec<libc::mmsghdr> = Vec::with_capacity(groups.len());
+        for (i, group) in groups.iter().enumerate() {
+            let mut header: libc::msghdr = unsafe { mem::zeroed() };
+            header.msg_name = &mut names[i].0 as *mut _ as *mut libc::c_void;
+            header.msg_namelen = names[i].1;
+            header.msg_iov = &mut iovecs[i];
+            header.msg_iovlen = 1;
+            if group.len() > 1 {
+                debug_assert!(group.len() <= MAX_SEGMENTS);
+                let size = transmits[group.start].data.len() as u16;
+                header.msg_control = controls[i].0.as_mut_ptr() as *mut libc::c_void;
+                header.msg_controllen =
+                    unsafe { libc::CMSG_SPACE(mem::size_of::<u16>() as u32) } as _;
+                unsafe {
+                    let cmsg = libc::CMSG_FIRSTHDR(&header);
+                    (*cmsg).cmsg_level = libc::SOL_UDP;
+                    (*cmsg).cmsg_type = libc::UDP_SEGMENT;
+                    (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<u16>() as u32) as _;
+                    ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut u16, size);
+                }
+            }
+            messages.push(libc::mmsghdr {
+                msg_hdr: header,
+                msg_len: 0,
+            });
+        }
+        let sent = unsafe {
+            libc::sendmmsg(
+                socket.as_raw_fd(),
+                messages.as_mut_ptr(),
+                messages.len() as libc::c_uint,
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(groups[..sent as usize].iter().map(|v| v.len()).sum())
+    }
+
+    pub fn recv(
+        socket: &UdpSocket,
+        local: SocketAddr,
+        buffers: &mut [Vec<u8>],
+        pool: &BufferPool,
+        received: &mut Vec<Transmit>,
+    ) -> io::Result<()> {
+        let count = buffers.len();
+        let mut names: Vec<libc::sockaddr_storage> = vec![unsafe { mem::zeroed() }; count];
+        let mut controls = vec![Control([0; 32]); count];
+        let mut iovecs: Vec<libc::iovec> = buffers
+            .iter_mut()
+            .map(|v| libc::iovec {
+                iov_base: v.as_mut_ptr() as *mut libc::c_void,
+                iov_len: v.len(),
+            })
+            .collect();
+        let mut messages: Vec<libc::mmsghdr> = (0..count)
+            .map(|i| {
+                let mut header: libc::msghdr = unsafe { mem::zeroed() };
+                header.msg_name = &mut names[i] as *mut _ as *mut libc::c_void;
+                header.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+                header.msg_iov = &mut iovecs[i];
+                header.msg_iovlen = 1;
+                header.msg_control = controls[i].0.as_mut_ptr() as *mut libc::c_void;
+                header.msg_controllen = mem::size_of::<Control>() as _;
+                libc::mmsghdr {
+                    msg_hdr: header,
+                    msg_len: 0,
+                }
+            })
+            .collect();
+        let length = unsafe {
+            libc::recvmmsg(
+                socket.as_raw_fd(),
+                messages.as_mut_ptr(),
+                count as libc::c_uint,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if length < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for (i, message) in messages[..length as usize].iter().enumerate() {
+            let source = match from_sockaddr(&names[i]) {
+                Some(v) => v,
+                None => continue,
+            };
+            let data = &buffers[i][..message.msg_len as usize];
+            let mut segment = data.len();
+            unsafe {
+                let mut cmsg = libc::CMSG_FIRSTHDR(&message.msg_hdr);
+                while !cmsg.is_null() {
+                    if (*cmsg).cmsg_level == libc::SOL_UDP && (*cmsg).cmsg_type == libc::UDP_GRO {
+                        let size = ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int);
+                        segment = size as usize;
+                    }
+                    cmsg = libc::CMSG_NXTHDR(&message.msg_hdr, cmsg);
+                }
+            }
+            if data.is_empty() {
+                received.push(Transmit::new(source, local, vec![]));
+            }
+            for chunk in data.chunks(segment.max(1)) {
+                received.push(Transmit::new(source, local, pool.take(chunk)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use crate::ice::network::Transmit;
+    use crate::octets::pool::BufferPool;
+
+    use std::io;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::ops::Range;
+
+    pub const EIO: i32 = 5;
+    pub const RECEIVE_BATCH: usize = 1;
+
+    pub fn enable_offload(_socket: &UdpSocket) -> (bool, bool) {
+        (false, false)
+    }
+
+    pub fn send(
+        socket: &UdpSocket,
+        transmits: &[Transmit],
+        groups: &[Range<usize>],
+    ) -> io::Result<usize> {
+        for (i, group) in groups.iter().enumerate() {
+            let transmit = &transmits[group.start];
+            match socket.send_to(&transmit.data, transmit.destination) {
+                Ok(_) => {}
+                Err(e) if i == 0 => return Err(e),
+                Err(_) => return Ok(i),
+            }
+        }
+        Ok(groups.len())
+    }
+
+    pub fn recv(
+        socket: &UdpSocket,
+        local: SocketAddr,
+        buffers: &mut [Vec<u8>],
+        pool: &BufferPool,
+        received: &mut Vec<Transmit>,
+    ) -> io::Result<()> {
+        let (length, source) = socket.recv_from(&mut buffers[0])?;
+        let data = pool.take(&buffers[0][..length]);
+        received.push(Transmit::new(source, local, data));
+        Ok(())
+    }
+}
+
+// runtimeのReadinessでBatchUdpSocketを待つTransport．
+pub struct BatchUdpTransport<R: Readiness> {
+    socket: BatchUdpSocket,
+    readiness: R,
+    received: VecDeque<Transmit>,
+}
+
+impl<R: Readiness> BatchUdpTransport<R> {
+    pub fn new(socket: BatchUdpSocket, readiness: R) -> Self {
+        BatchUdpTransport {
+            socket,
+            readiness,
+            received: VecDeque::new(),
+        }
+    }
+
+    pub fn get_socket(&self) -> &BatchUdpSocket {
+        &self.socket
+    }
+
+    pub fn get_socket_mut(&mut self) -> &mut BatchUdpSocket {
+        &mut self.socket
+    }
+}
+
+impl<R: Readiness> Transport for BatchUdpTransport<R> {
+    fn poll_send(&mut self, cx: &mut Context<'_>, transmit: &Transmit) -> Poll<io::Result<()>> {
+        self.poll_send_batch(cx, std::slice::from_ref(transmit))
+            .map_ok(|_| ())
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Transmit>> {
+        if self.received.is_empty() {
+            let mut received = Vec::new();
+            ready!(self.poll_recv_batch(cx, &mut received))?;
+            self.received.extend(received);
+        }
+        Poll::Ready(Ok(self.received.pop_front().unwrap()))
+    }
+
+    fn poll_send_batch(
+        &mut self,
+        cx: &mut Context<'_>,
+        transmits: &[Transmit],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            ready!(self.readiness.poll_write_ready(cx))?;
+            match self.socket.send_batch(transmits) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.readiness.clear_write_ready()
+                }
+                v => return Poll::Ready(v),
+            }
+        }
+    }
+
+    fn poll_recv_batch(
+        &mut self,
+        cx: &mut Context<'_>,
+        received: &mut Vec<Transmit>,
+    ) -> Poll<io::Result<usize>> {
+        if !self.received.is_empty() {
+            let count = self.received.len();
+            received.extend(self.received.drain(..));
+            return Poll::Ready(Ok(count));
+        }
+        loop {
+            ready!(self.readiness.poll_read_ready(cx))?;
+            match self.socket.recv_batch(received) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.readiness.clear_read_ready()
+                }
+                v => return Poll::Ready(v),
+            }
+        }
+    }
+
+    fn recycle(&mut self, data: Vec<u8>) {
+        self.socket.recycle(data);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+    use std::time::Duration;
+
+    fn new_transmits(destination: SocketAddr, sizes: &[usize]) -> Vec<Transmit> {
+        let source = "127.0.0.1:1".parse().unwrap();
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Transmit::new(source, destination, vec![i as u8; *v]))
+            .collect()
+    }
+
+    #[test]
+    fn group_segments_test() {
+        let a = "10.0.0.1:1000".parse().unwrap();
+        let b = "10.0.0.2:1000".parse().unwrap();
+        let mut transmits = new_transmits(a, &[1200, 1200, 1200, 800, 1200, 1300]);
+        transmits.extend(new_transmits(b, &[1200, 1200]));
+        assert_eq!(
+            group_segments(&transmits, MAX_SEGMENTS),
+            vec![0..4, 4..5, 5..6, 6..8]
+        );
+        assert_eq!(group_segments(&transmits, 1).len(), 8);
+
+        let transmits = new_transmits(a, &[1000; 100]);
+        assert_eq!(
+            group_segments(&transmits, MAX_SEGMENTS),
+            vec![0..64, 64..100]
+        );
+        let transmits = new_transmits(a, &vec![1; MAX_BATCH + 1]);
+        assert_eq!(group_segments(&transmits, 1).len(), MAX_BATCH);
+    }
+
+    fn receive_all(socket: &mut BatchUdpSocket, count: usize) -> Vec<Transmit> {
+        let mut received = Vec::new();
+        for _ in 0..1000 {
+            match socket.recv_batch(&mut received) {
+                Ok(_) if received.len() >= count => return received,
+                Err(ref e) if e.kind() != io::ErrorKind::WouldBlock => panic!("{}", e),
+                _ => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        panic!("received {} of {}", received.len(), count);
+    }
+
+    #[test]
+    fn batch_udp_socket_test() {
+        let loopback = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+        let mut a = BatchUdpSocket::bind(loopback).unwrap();
+        let mut b = BatchUdpSocket::bind(loopback).unwrap();
+        let mut sizes = vec![1000; 10];
+        sizes.extend(&[500, 3, 0]);
+        let transmits = new_transmits(b.get_local_addr(), &sizes);
+        let mut sent = 0;
+        while sent < transmits.len() {
+            sent += a.send_batch(&transmits[sent..]).unwrap();
+        }
+        let received = receive_all(&mut b, transmits.len());
+        assert_eq!(received.len(), transmits.len());
+        for (v, transmit) in received.iter().zip(&transmits) {
+            assert_eq!(v.source, a.get_local_addr());
+            assert_eq!(v.destination, b.get_local_addr());
+            assert_eq!(v.data, transmit.data);
+        }
+        // all but the empty one are of the pool.
+        assert_eq!(b.get_pool().get_stats().in_use, 12);
+        for v in received {
+            b.recycle(v.data);
+        }
+        assert_eq!(b.get_pool().get_stats().in_use, 0);
+        let stats = a.get_stats();
+        assert_eq!(stats.datagrams_sent, 13);
+        if cfg!(target_os = "linux") {
+            assert!(stats.send_calls < 13);
+            assert!(b.get_stats().receive_calls < 13);
+        }
+
+        a.set_gso_enabled(false);
+        assert!(!a.is_gso_enabled());
+        assert_eq!(a.send_batch(&transmits[..3]).unwrap(), 3);
+        assert_eq!(receive_all(&mut b, 3).len(), 3);
+        assert!(b.get_pool().get_stats().reused >= 2);
+        let mut received = Vec::new();
+        let e = b.recv_batch(&mut received).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+    }
+
+    // 一度clearされるまでreadyで，その後はwakeしてPending．
+    #[derive(Default)]
+    struct OnceReadiness {
+        read_cleared: bool,
+    }
+
+    impl Readiness for OnceReadiness {
+        fn poll_read_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            if self.read_cleared {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_write_ready(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn clear_read_ready(&mut self) {
+            self.read_cleared = true;
+        }
+
+        fn clear_write_ready(&mut self) {}
+    }
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[test]
+    fn batch_udp_transport_test() {
+        let loopback = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0);
+        let socket = BatchUdpSocket::bind(loopback).unwrap();
+        let mut a = BatchUdpTransport::new(socket, OnceReadiness::default());
+        let mut b = BatchUdpSocket::bind(loopback).unwrap();
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+
+        // nothing to read clears the readiness.
+        assert!(a.poll_recv(&mut cx).is_pending());
+        assert!(a.get_socket().get_stats().receive_calls >= 1);
+
+        let transmits = new_transmits(b.get_local_addr(), &[100, 100]);
+        assert_eq!(
+            a.poll_send_batch(&mut cx, &transmits).map(Result::unwrap),
+            Poll::Ready(2)
+        );
+        assert_eq!(receive_all(&mut b, 2).len(), 2);
+        let transmit = Transmit::new(b.get_local_addr(), a.get_socket().get_local_addr(), vec![7]);
+        assert_eq!(b.send_batch(&[transmit]).unwrap(), 1);
+        let mut received = None;
+        for _ in 0..1000 {
+            a.readiness.read_cleared = false;
+            if let Poll::Ready(v) = a.poll_recv(&mut cx) {
+                received = Some(v.unwrap());
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(received.unwrap().data, vec![7]);
+    }
+}
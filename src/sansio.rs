@@ -0,0 +1,36 @@
+/*
+    the shape of a protocol state machine without I/O: what is received or
+    asked for goes in, what is to be sent or told comes out, and a timer of
+    poll_timeout calls poll_output again.
+
+      handle_input(input, now)   a packet received, or a call of the user
+      poll_output(now)           runs the timers due at `now`, then returns
+                                 what is ready until None
+      poll_timeout()             when poll_output is due at the latest
+
+    now is given by the caller, so a test moves time by hand.
+
+      IceAgent                Transmit  -> IceOutput
+      DtlsTransport           record    -> DtlsOutput, the SRTP keys too
+      RtcpScheduler           RtcpInput -> RtcpOutput
+      CongestionControl       CongestionInput -> CongestionOutput
+*/
+
+use crate::time::Instant;
+
+pub trait StateMachine {
+    type Input;
+    type Output;
+    type Error;
+
+    fn handle_input(&mut self, input: Self::Input, now: Instant) -> Result<(), Self::Error>;
+
+    fn poll_output(&mut self, now: Instant) -> Option<Self::Output>;
+
+    fn poll_timeout(&self) -> Option<Instant>;
+}
+
+/// Everything `machine` has ready at `now`.
+pub fn drain_outputs<M: StateMachine + ?Sized>(machine: &mut M, now: Instant) -> Vec<M::Output> {
+    std::iter::from_fn(|| machine.poll_output(now)).collect()
+}
@@ -0,0 +1,41 @@
+pub mod association;
+pub mod chunk;
+pub mod crc32c;
+pub mod packet;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, SctpError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum SctpError {
+    #[fail(display = "SCTP packet is broken.")]
+    InvalidPacket,
+
+    #[fail(display = "SCTP checksum does not match.")]
+    InvalidChecksum,
+
+    #[fail(display = "SCTP chunk is broken.")]
+    InvalidChunk,
+
+    #[fail(display = "SCTP verification tag does not match.")]
+    InvalidVerificationTag,
+
+    #[fail(display = "State cookie is broken or stale.")]
+    InvalidCookie,
+
+    #[fail(display = "SCTP association is not established.")]
+    NotConnected,
+
+    #[fail(display = "Stream {} is not negotiated.", stream_id)]
+    InvalidStream { stream_id: u16 },
+
+    #[fail(display = "SCTP message is empty.")]
+    EmptyMessage,
+
+    #[fail(display = "SCTP message of {} bytes is too large.", size)]
+    MessageTooLarge { size: usize },
+
+    #[fail(display = "Peer does not support resetting streams.")]
+    StreamResetNotSupported,
+}
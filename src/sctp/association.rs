@@ -0,0 +1,2673 @@
+// https://tools.ietf.org/html/rfc9260
+// https://tools.ietf.org/html/rfc8261
+// https://tools.ietf.org/html/rfc6298
+// https://tools.ietf.org/html/rfc6525
+// https://tools.ietf.org/html/rfc3758
+// https://tools.ietf.org/html/rfc8260
+
+/*
+    connect                          (peer)
+      Closed      -- INIT        ->
+      CookieWait  <- INIT ACK    --  cookie, no state kept by the peer
+                  -- COOKIE ECHO ->  Established
+      CookieEchoed<- COOKIE ACK  --
+      Established
+
+    both sides may connect at once: each answers the INIT of the other
+    with its own tag, and the first COOKIE ECHO with that tag establishes.
+
+    TSNs are extended to 64 bits, starting at 2^32 + the initial TSN.
+
+    sender    cwnd and ssthresh (Section 7.2), fast retransmit on the third
+              miss indication (HTNA), T3-rtx with the RTO of RFC 6298.
+              TSNs and SSNs are given as the fragments are sent.
+    receiver  SACK every second packet or after 200ms, at once on a gap or
+              a duplicate. Ordered messages are delivered in SSN order per
+              stream.
+
+    streams   an Outgoing SSN Reset Request at a time, the peer resets its
+              inbound SSNs once the TSNs before it are received.
+
+    PR-SCTP   a message past its retransmissions or lifetime is abandoned,
+              whole, and FORWARD TSN moves the cumulative TSN of the peer
+              over it. Without the extension every message is reliable.
+
+    I-DATA    when both sides offer it, the fragments of the messages of
+              the streams are sent round robin, one stream does not wait
+              for a large message of another. MIDs take the place of SSNs.
+
+    the liveness of the path is left to ICE consent, no HEARTBEAT is sent.
+*/
+
+use crate::dtls::transport::{DtlsBackend, DtlsTransport};
+use crate::sctp::chunk::{
+    Chunk, DataChunk, InitChunk, ReconfigParameter, SackChunk, CHUNK_FORWARD_TSN,
+    CHUNK_HEADER_LENGTH, CHUNK_I_DATA, CHUNK_I_FORWARD_TSN, CHUNK_RECONFIG,
+    DATA_CHUNK_HEADER_LENGTH, I_DATA_CHUNK_HEADER_LENGTH,
+};
+use crate::sctp::packet::{Packet, COMMON_HEADER_LENGTH};
+use crate::sctp::{Result, SctpError};
+use crate::time::Instant;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+/// The port both sides use unless a=sctp-port says otherwise (RFC 8841).
+pub const DEFAULT_SCTP_PORT: u16 = 5000;
+
+const COOKIE_LENGTH: usize = 16;
+const COOKIE_MAC_LENGTH: usize = 32;
+const COOKIE_LIFETIME: Duration = Duration::from_secs(60);
+const MAX_DUPLICATE_TSNS: usize = 16;
+const FAST_RETRANSMIT_THRESHOLD: u32 = 3;
+
+const CAUSE_INVALID_STREAM: u16 = 1;
+const CAUSE_UNRECOGNIZED_CHUNK: u16 = 6;
+
+const SUPPORTED_EXTENSIONS: [u8; 2] = [CHUNK_RECONFIG, CHUNK_FORWARD_TSN];
+const INTERLEAVING_EXTENSIONS: [u8; 2] = [CHUNK_I_DATA, CHUNK_I_FORWARD_TSN];
+
+// https://tools.ietf.org/html/rfc6525#section-4.4
+const RECONFIG_SUCCESS_NOTHING_TO_DO: u32 = 0;
+const RECONFIG_SUCCESS_PERFORMED: u32 = 1;
+const RECONFIG_BAD_SEQUENCE_NUMBER: u32 = 5;
+const RECONFIG_IN_PROGRESS: u32 = 6;
+
+#[derive(Debug, Clone)]
+pub struct SctpConfig {
+    pub local_port: u16,
+    pub remote_port: u16,
+    pub outbound_streams: u16,
+    pub inbound_streams: u16,
+    /// The bytes this side buffers for the peer.
+    pub receive_window: usize,
+    /// The largest SCTP packet, what is left of the path MTU after DTLS.
+    pub mtu: usize,
+    /// What a=max-message-size signals.
+    pub max_message_size: usize,
+    pub rto_initial: Duration,
+    pub rto_min: Duration,
+    pub rto_max: Duration,
+    pub max_init_retransmits: u32,
+    pub max_retransmits: u32,
+    pub delayed_ack: Duration,
+    /// Offers I-DATA (RFC 8260), which browsers do not yet.
+    pub interleaving: bool,
+}
+
+impl Default for SctpConfig {
+    fn default() -> Self {
+        SctpConfig {
+            local_port: DEFAULT_SCTP_PORT,
+            remote_port: DEFAULT_SCTP_PORT,
+            outbound_streams: u16::MAX,
+            inbound_streams: u16::MAX,
+            receive_window: 1024 * 1024,
+            mtu: 1200,
+            max_message_size: 256 * 1024,
+            rto_initial: Duration::from_secs(1),
+            rto_min: Duration::from_secs(1),
+            rto_max: Duration::from_secs(60),
+            max_init_retransmits: 8,
+            max_retransmits: 10,
+            delayed_ack: Duration::from_millis(200),
+            interleaving: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SctpState {
+    Closed,
+    CookieWait,
+    CookieEchoed,
+    Established,
+    ShutdownPending,
+    ShutdownSent,
+    ShutdownReceived,
+    ShutdownAckSent,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum SctpEvent {
+    StateChanged(SctpState),
+    /// The peer reset these streams, all of them when empty.
+    IncomingStreamsReset(Vec<u16>),
+    /// The peer performed a reset this side asked for.
+    OutgoingStreamsReset(Vec<u16>),
+}
+
+// 送受信するuser message．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SctpMessage {
+    pub stream_id: u16,
+    pub ppid: u32,
+    pub data: Vec<u8>,
+    pub unordered: bool,
+}
+
+/// When the messages of a stream may be abandoned (RFC 3758).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SctpReliability {
+    Reliable,
+    /// Sent at most once more than this.
+    MaxRetransmits(u32),
+    /// From when the message is queued.
+    MaxLifetime(Duration),
+}
+
+// 送ったがcumulative ackされていないDATA．
+#[derive(Debug)]
+struct SentChunk {
+    chunk: DataChunk,
+    sent_at: Instant,
+    transmissions: u32,
+    misses: u32,
+    in_flight: bool,
+    gap_acked: bool,
+    retransmit: bool,
+    abandoned: bool,
+    message: u64,
+    limit: Option<MessageLimit>,
+}
+
+// partially reliableなmessageの制限．
+#[derive(Debug, Clone, Copy)]
+struct MessageLimit {
+    reliability: SctpReliability,
+    queued_at: Instant,
+}
+
+// 送信を待つmessage．TSNとSSNは送るときに振る．
+#[derive(Debug)]
+struct OutgoingMessage {
+    id: u64,
+    message: SctpMessage,
+    /// The SSN, or the MID with I-DATA.
+    sequence: u32,
+    sent: usize,
+    fsn: u32,
+    limit: Option<MessageLimit>,
+}
+
+#[derive(Debug, Default)]
+struct InboundStream {
+    /// The SSN, or the MID with I-DATA.
+    next_sequence: u32,
+    messages: HashMap<u32, SctpMessage>,
+}
+
+// 送信するpacketにchunkを詰める．
+struct PacketBuilder {
+    mtu: usize,
+    size: usize,
+    chunks: Vec<Chunk>,
+    packets: Vec<Vec<Chunk>>,
+}
+
+impl PacketBuilder {
+    fn new(mtu: usize) -> Self {
+        PacketBuilder {
+            mtu,
+            size: COMMON_HEADER_LENGTH,
+            chunks: Vec::new(),
+            packets: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, chunk: Chunk) {
+        let length = chunk.get_length();
+        if self.size + length > self.mtu && !self.chunks.is_empty() {
+            self.packets.push(std::mem::take(&mut self.chunks));
+            self.size = COMMON_HEADER_LENGTH;
+        }
+        self.size += length;
+        self.chunks.push(chunk);
+    }
+
+    fn finish(mut self) -> Vec<Vec<Chunk>> {
+        if !self.chunks.is_empty() {
+            self.packets.push(self.chunks);
+        }
+        self.packets
+    }
+}
+
+fn extend_tsn(reference: u64, tsn: u32) -> u64 {
+    let diff = tsn.wrapping_sub(reference as u32) as i32;
+    (reference as i64 + i64::from(diff)) as u64
+}
+
+fn next_sequence(interleaving: bool, sequence: u32) -> u32 {
+    if interleaving {
+        sequence.wrapping_add(1)
+    } else {
+        u32::from((sequence as u16).wrapping_add(1))
+    }
+}
+
+// SSNs wrap at 16 bits, MIDs at 32.
+fn get_sequence_distance(interleaving: bool, from: u32, to: u32) -> i32 {
+    if interleaving {
+        to.wrapping_sub(from) as i32
+    } else {
+        i32::from((to as u16).wrapping_sub(from as u16) as i16)
+    }
+}
+
+fn wrap_data_chunk(interleaving: bool, chunk: DataChunk) -> Chunk {
+    if interleaving {
+        Chunk::IData(chunk)
+    } else {
+        Chunk::Data(chunk)
+    }
+}
+
+fn random_tag() -> u32 {
+    loop {
+        let tag: u32 = rand::random();
+        if tag != 0 {
+            return tag;
+        }
+    }
+}
+
+// https://tools.ietf.org/html/rfc9260#section-3.3.10
+fn error_cause(code: u16, info: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(CHUNK_HEADER_LENGTH + info.len());
+    out.extend_from_slice(&code.to_be_bytes());
+    out.extend_from_slice(&((CHUNK_HEADER_LENGTH + info.len()) as u16).to_be_bytes());
+    out.extend_from_slice(info);
+    out.resize((out.len() + 3) & !3, 0);
+    out
+}
+
+// DTLSの上のSCTP association．socketは持たず，packetを受け渡す．
+#[derive(Debug)]
+pub struct SctpAssociation {
+    config: SctpConfig,
+    state: SctpState,
+    epoch: Instant,
+    now: Instant,
+    cookie_secret: [u8; 32],
+    local_tag: u32,
+    peer_tag: u32,
+    init: Option<InitChunk>,
+    cookie_echo: Option<Vec<u8>>,
+    control_timer: Option<Instant>,
+    error_count: u32,
+
+    outbound_streams: u16,
+    inbound_streams: u16,
+    next_tsn: u64,
+    cumulative_tsn_acked: u64,
+    sequences: HashMap<(u16, bool), u32>,
+    outgoing: VecDeque<OutgoingMessage>,
+    next_message: u64,
+    last_stream: Option<u16>,
+    sent: BTreeMap<u64, SentChunk>,
+    flight_size: usize,
+    peer_rwnd: usize,
+    cwnd: usize,
+    ssthresh: usize,
+    partial_bytes_acked: usize,
+    fast_recovery_exit: Option<u64>,
+    fast_retransmit: bool,
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+    t3_rtx: Option<Instant>,
+
+    peer_cumulative_tsn: u64,
+    received_tsns: BTreeSet<u64>,
+    duplicate_tsns: Vec<u32>,
+    reassembly: BTreeMap<u64, DataChunk>,
+    fragments: HashMap<(u16, bool, u32), BTreeMap<u32, DataChunk>>,
+    inbound: HashMap<u16, InboundStream>,
+    messages: VecDeque<SctpMessage>,
+    buffered: usize,
+    advertised_rwnd: usize,
+    ack_now: bool,
+    ack_timer: Option<Instant>,
+    unacked_packets: u32,
+
+    peer_extensions: Vec<u8>,
+    buffered_amounts: HashMap<u16, usize>,
+    reconfig_sequence: u32,
+    reconfig_request: Option<ReconfigParameter>,
+    reconfig_timer: Option<Instant>,
+    queued_resets: Vec<u16>,
+    held: VecDeque<SctpMessage>,
+    peer_reconfig_sequence: u32,
+    deferred_reset: Option<(u32, u64, Vec<u16>)>,
+    last_reconfig_response: Option<(u32, u32)>,
+
+    peer_forward_tsn: bool,
+    reliabilities: HashMap<u16, SctpReliability>,
+    interleaving: bool,
+
+    control: VecDeque<Chunk>,
+    transmits: VecDeque<Vec<u8>>,
+    events: VecDeque<SctpEvent>,
+}
+
+impl SctpAssociation {
+    pub fn new(config: SctpConfig, now: Instant) -> Self {
+        let rto = config.rto_initial;
+        let mtu = config.mtu;
+        SctpAssociation {
+            state: SctpState::Closed,
+            epoch: now,
+            now,
+            cookie_secret: rand::random(),
+            local_tag: 0,
+            peer_tag: 0,
+            init: None,
+            cookie_echo: None,
+            control_timer: None,
+            error_count: 0,
+            outbound_streams: config.outbound_streams,
+            inbound_streams: config.inbound_streams,
+            next_tsn: 0,
+            cumulative_tsn_acked: 0,
+            sequences: HashMap::new(),
+            outgoing: VecDeque::new(),
+            next_message: 0,
+            last_stream: None,
+            sent: BTreeMap::new(),
+            flight_size: 0,
+            peer_rwnd: 0,
+            cwnd: (4 * mtu).min((2 * mtu).max(4380)),
+            ssthresh: usize::MAX,
+            partial_bytes_acked: 0,
+            fast_recovery_exit: None,
+            fast_retransmit: false,
+            srtt: None,
+            rttvar: Duration::from_secs(0),
+            rto,
+            t3_rtx: None,
+            peer_cumulative_tsn: 0,
+            received_tsns: BTreeSet::new(),
+            duplicate_tsns: Vec::new(),
+            reassembly: BTreeMap::new(),
+            fragments: HashMap::new(),
+            inbound: HashMap::new(),
+            messages: VecDeque::new(),
+            buffered: 0,
+            advertised_rwnd: config.receive_window,
+            ack_now: false,
+            ack_timer: None,
+            unacked_packets: 0,
+            peer_extensions: Vec::new(),
+            buffered_amounts: HashMap::new(),
+            reconfig_sequence: 0,
+            reconfig_request: None,
+            reconfig_timer: None,
+            queued_resets: Vec::new(),
+            held: VecDeque::new(),
+            peer_reconfig_sequence: 0,
+            deferred_reset: None,
+            last_reconfig_response: None,
+            peer_forward_tsn: false,
+            reliabilities: HashMap::new(),
+            interleaving: false,
+            control: VecDeque::new(),
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+            config,
+        }
+    }
+
+    pub fn get_state(&self) -> SctpState {
+        self.state
+    }
+
+    pub fn get_config(&self) -> &SctpConfig {
+        &self.config
+    }
+
+    /// The streams negotiated with the peer, the smaller of what each side
+    /// asked for.
+    pub fn get_outbound_streams(&self) -> u16 {
+        self.outbound_streams
+    }
+
+    pub fn get_inbound_streams(&self) -> u16 {
+        self.inbound_streams
+    }
+
+    pub fn get_cwnd(&self) -> usize {
+        self.cwnd
+    }
+
+    pub fn get_ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+
+    pub fn get_rto(&self) -> Duration {
+        self.rto
+    }
+
+    /// The window the peer has left, less the bytes in flight.
+    pub fn get_peer_rwnd(&self) -> usize {
+        self.peer_rwnd
+    }
+
+    /// The bytes of user data sent and not yet acknowledged.
+    pub fn get_flight_size(&self) -> usize {
+        self.flight_size
+    }
+
+    /// The bytes of messages on `stream_id` queued and not sent yet.
+    pub fn get_buffered_amount(&self, stream_id: u16) -> usize {
+        self.buffered_amounts.get(&stream_id).copied().unwrap_or(0)
+    }
+
+    /// Whether the peer offered FORWARD TSN, without which messages are
+    /// sent reliably whatever `set_reliability` says.
+    pub fn is_partial_reliability_supported(&self) -> bool {
+        self.peer_forward_tsn
+    }
+
+    /// Whether both sides offered I-DATA.
+    pub fn is_interleaving_supported(&self) -> bool {
+        self.interleaving
+    }
+
+    /// Applies to the messages of `stream_id` sent from now on.
+    pub fn set_reliability(&mut self, stream_id: u16, reliability: SctpReliability) {
+        if reliability == SctpReliability::Reliable {
+            self.reliabilities.remove(&stream_id);
+        } else {
+            self.reliabilities.insert(stream_id, reliability);
+        }
+    }
+
+    /// Sends the INIT.
+    pub fn connect(&mut self, now: Instant) {
+        self.now = now;
+        if self.state != SctpState::Closed {
+            return;
+        }
+        self.local_tag = random_tag();
+        self.set_initial_tsn(rand::random());
+        let init = InitChunk {
+            initiate_tag: self.local_tag,
+            a_rwnd: self.config.receive_window as u32,
+            outbound_streams: self.config.outbound_streams,
+            inbound_streams: self.config.inbound_streams,
+            initial_tsn: self.next_tsn as u32,
+            state_cookie: None,
+            supported_extensions: self.get_supported_extensions(),
+            forward_tsn_supported: true,
+        };
+        self.send_packet(0, vec![Chunk::Init(init.clone())]);
+        self.init = Some(init);
+        self.error_count = 0;
+        self.control_timer = Some(now + self.rto);
+        self.set_state(SctpState::CookieWait);
+    }
+
+    /// Queues a message. It may be sent before the association is
+    /// established, once it is.
+    pub fn send(&mut self, message: SctpMessage, now: Instant) -> Result<()> {
+        self.now = now;
+        match self.state {
+            SctpState::CookieWait | SctpState::CookieEchoed | SctpState::Established => {}
+            _ => return Err(SctpError::NotConnected),
+        }
+        if message.data.is_empty() {
+            return Err(SctpError::EmptyMessage);
+        }
+        if message.data.len() > self.config.max_message_size {
+            return Err(SctpError::MessageTooLarge {
+                size: message.data.len(),
+            });
+        }
+        if message.stream_id >= self.outbound_streams {
+            return Err(SctpError::InvalidStream {
+                stream_id: message.stream_id,
+            });
+        }
+        *self.buffered_amounts.entry(message.stream_id).or_insert(0) += message.data.len();
+        // held until the peer has reset the stream (RFC 6525 Section 5.1.2).
+        if self.is_resetting(message.stream_id) {
+            self.held.push_back(message);
+        } else {
+            self.enqueue(message);
+        }
+        self.transmit(now);
+        Ok(())
+    }
+
+    fn is_resetting(&self, stream_id: u16) -> bool {
+        self.queued_resets.contains(&stream_id)
+            || matches!(
+                &self.reconfig_request,
+                Some(ReconfigParameter::OutgoingResetRequest { streams, .. })
+                    if streams.contains(&stream_id)
+            )
+    }
+
+    fn enqueue(&mut self, message: SctpMessage) {
+        let limit = match self.reliabilities.get(&message.stream_id) {
+            Some(reliability) if self.peer_forward_tsn => Some(MessageLimit {
+                reliability: *reliability,
+                queued_at: self.now,
+            }),
+            _ => None,
+        };
+        self.outgoing.push_back(OutgoingMessage {
+            id: self.next_message,
+            message,
+            sequence: 0,
+            sent: 0,
+            fsn: 0,
+            limit,
+        });
+        self.next_message += 1;
+    }
+
+    /// Resets the outgoing SSNs of `streams`, which closes their data
+    /// channels. The peer performs it after the messages already queued.
+    pub fn reset_streams(&mut self, streams: &[u16], now: Instant) -> Result<()> {
+        self.now = now;
+        if self.state != SctpState::Established {
+            return Err(SctpError::NotConnected);
+        }
+        if !self.peer_extensions.contains(&CHUNK_RECONFIG) {
+            return Err(SctpError::StreamResetNotSupported);
+        }
+        for stream in streams {
+            if !self.queued_resets.contains(stream) {
+                self.queued_resets.push(*stream);
+            }
+        }
+        self.send_reset_request(now);
+        self.transmit(now);
+        Ok(())
+    }
+
+    /// Handles an SCTP packet, the data of a DTLS record. A broken packet
+    /// or one with a wrong tag is dropped with an error, the association
+    /// goes on.
+    pub fn handle_packet(&mut self, data: &[u8], now: Instant) -> Result<()> {
+        self.now = now;
+        let packet = Packet::from_bytes(data)?;
+        if packet.source_port != self.config.remote_port
+            || packet.destination_port != self.config.local_port
+            || packet.chunks.is_empty()
+        {
+            return Err(SctpError::InvalidPacket);
+        }
+        self.validate_verification_tag(&packet)?;
+
+        let mut result = Ok(());
+        let mut packet_has_data = false;
+        for chunk in packet.chunks {
+            match chunk {
+                Chunk::Data(data) => {
+                    packet_has_data = true;
+                    self.handle_data(data, false)?;
+                }
+                Chunk::IData(data) => {
+                    packet_has_data = true;
+                    self.handle_data(data, true)?;
+                }
+                Chunk::Init(init) => self.handle_init(init, now),
+                Chunk::InitAck(init) => self.handle_init_ack(init, now),
+                Chunk::Sack(sack) => self.handle_sack(&sack, now),
+                Chunk::Heartbeat(info) => {
+                    if self.has_peer() {
+                        self.control.push_back(Chunk::HeartbeatAck(info));
+                    }
+                }
+                Chunk::HeartbeatAck(_) | Chunk::Error(_) => {}
+                Chunk::Abort { .. } => {
+                    self.close();
+                    return Ok(());
+                }
+                Chunk::Shutdown { cumulative_tsn_ack } => {
+                    self.handle_shutdown(cumulative_tsn_ack, now)
+                }
+                Chunk::ShutdownAck => self.handle_shutdown_ack(),
+                Chunk::CookieEcho(cookie) => {
+                    if let Err(e) = self.handle_cookie_echo(&cookie, packet.verification_tag, now) {
+                        result = Err(e);
+                        break;
+                    }
+                }
+                Chunk::CookieAck => {
+                    if self.state == SctpState::CookieEchoed {
+                        self.cookie_echo = None;
+                        self.control_timer = None;
+                        self.error_count = 0;
+                        self.set_state(SctpState::Established);
+                    }
+                }
+                Chunk::ShutdownComplete { .. } => {
+                    if self.state == SctpState::ShutdownAckSent {
+                        self.close();
+                        return Ok(());
+                    }
+                }
+                Chunk::Reconfig(parameters) => {
+                    if self.has_peer() {
+                        self.handle_reconfig(parameters, now);
+                    }
+                }
+                Chunk::ForwardTsn {
+                    new_cumulative_tsn,
+                    streams,
+                } => {
+                    if !self.interleaving {
+                        let streams = streams
+                            .into_iter()
+                            .map(|(stream, ssn)| (stream, false, u32::from(ssn)))
+                            .collect();
+                        self.handle_forward_tsn(new_cumulative_tsn, streams);
+                    }
+                }
+                Chunk::IForwardTsn {
+                    new_cumulative_tsn,
+                    streams,
+                } => {
+                    if self.interleaving {
+                        self.handle_forward_tsn(new_cumulative_tsn, streams);
+                    }
+                }
+                Chunk::Unknown { chunk_type, .. } => {
+                    if chunk_type & 0x40 != 0 && self.has_peer() {
+                        let cause = error_cause(CAUSE_UNRECOGNIZED_CHUNK, &chunk.to_bytes());
+                        self.control.push_back(Chunk::Error(cause));
+                    }
+                    if chunk_type & 0x80 == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        if packet_has_data {
+            self.unacked_packets += 1;
+            if self.unacked_packets >= 2 {
+                self.ack_now = true;
+            } else if self.ack_timer.is_none() {
+                self.ack_timer = Some(now + self.config.delayed_ack);
+            }
+        }
+        self.check_deferred_reset();
+        self.check_shutdown(now);
+        self.transmit(now);
+        result
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        [
+            self.control_timer,
+            self.t3_rtx,
+            self.ack_timer,
+            self.reconfig_timer,
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        self.now = now;
+        if self.ack_timer.is_some_and(|v| v <= now) {
+            self.ack_now = true;
+        }
+        if self.control_timer.is_some_and(|v| v <= now) {
+            self.on_control_timeout(now);
+        }
+        if self.t3_rtx.is_some_and(|v| v <= now) {
+            self.on_t3_rtx_timeout();
+            // the FORWARD TSN may be lost too.
+            self.queue_forward_tsn(now);
+        }
+        if self.reconfig_timer.is_some_and(|v| v <= now) {
+            if let Some(request) = self.reconfig_request.clone() {
+                self.control.push_back(Chunk::Reconfig(vec![request]));
+            }
+            self.reconfig_timer = Some(now + self.rto);
+        }
+        self.transmit(now);
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Vec<u8>> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<SctpEvent> {
+        self.events.pop_front()
+    }
+
+    /// The next message received, in order of delivery.
+    pub fn poll_message(&mut self) -> Option<SctpMessage> {
+        let message = self.messages.pop_front()?;
+        self.buffered -= message.data.len();
+        // a window that opened is reported without waiting for data.
+        if self.advertised_rwnd < self.config.receive_window / 2
+            && self.get_rwnd() >= self.config.receive_window / 2
+        {
+            self.ack_timer = Some(self.now);
+        }
+        Some(message)
+    }
+
+    /// Sends the pending packets over `dtls`. They are kept while DTLS is
+    /// not connected.
+    pub fn flush<B: DtlsBackend>(&mut self, dtls: &mut DtlsTransport<B>) {
+        while let Some(packet) = self.poll_transmit() {
+            if dtls.send(&packet).is_err() {
+                self.transmits.push_front(packet);
+                return;
+            }
+        }
+    }
+
+    /// Sends SHUTDOWN once all the data queued is acknowledged.
+    pub fn shutdown(&mut self, now: Instant) {
+        self.now = now;
+        match self.state {
+            SctpState::Established => {
+                self.set_state(SctpState::ShutdownPending);
+                self.check_shutdown(now);
+                self.transmit(now);
+            }
+            SctpState::CookieWait | SctpState::CookieEchoed => self.abort(),
+            _ => {}
+        }
+    }
+
+    /// Sends ABORT, and drops everything queued.
+    pub fn abort(&mut self) {
+        if self.has_peer() {
+            self.send_packet(
+                self.peer_tag,
+                vec![Chunk::Abort {
+                    reflected: false,
+                    causes: Vec::new(),
+                }],
+            );
+        }
+        self.close();
+    }
+
+    fn get_supported_extensions(&self) -> Vec<u8> {
+        let mut extensions = SUPPORTED_EXTENSIONS.to_vec();
+        if self.config.interleaving {
+            extensions.extend_from_slice(&INTERLEAVING_EXTENSIONS);
+        }
+        extensions
+    }
+
+    fn has_peer(&self) -> bool {
+        !matches!(self.state, SctpState::Closed | SctpState::CookieWait)
+    }
+
+    fn set_state(&mut self, state: SctpState) {
+        if self.state != state {
+            self.state = state;
+            self.events.push_back(SctpEvent::StateChanged(state));
+        }
+    }
+
+    fn close(&mut self) {
+        self.control_timer = None;
+        self.t3_rtx = None;
+        self.ack_timer = None;
+        self.ack_now = false;
+        self.outgoing.clear();
+        self.sent.clear();
+        self.flight_size = 0;
+        self.buffered_amounts.clear();
+        self.reconfig_request = None;
+        self.reconfig_timer = None;
+        self.queued_resets.clear();
+        self.held.clear();
+        self.deferred_reset = None;
+        self.control.clear();
+        self.set_state(SctpState::Closed);
+    }
+
+    fn set_initial_tsn(&mut self, tsn: u32) {
+        self.next_tsn = (1 << 32) + u64::from(tsn);
+        self.cumulative_tsn_acked = self.next_tsn - 1;
+        self.reconfig_sequence = tsn;
+    }
+
+    fn set_peer(&mut self, init: &InitChunk) {
+        self.peer_tag = init.initiate_tag;
+        self.peer_cumulative_tsn = (1 << 32) + u64::from(init.initial_tsn) - 1;
+        self.peer_rwnd = init.a_rwnd as usize;
+        self.ssthresh = init.a_rwnd as usize;
+        self.outbound_streams = self.config.outbound_streams.min(init.inbound_streams);
+        self.inbound_streams = self.config.inbound_streams.min(init.outbound_streams);
+        self.peer_extensions = init.supported_extensions.clone();
+        self.peer_reconfig_sequence = init.initial_tsn;
+        self.peer_forward_tsn =
+            init.forward_tsn_supported || init.supported_extensions.contains(&CHUNK_FORWARD_TSN);
+        self.interleaving =
+            self.config.interleaving && init.supported_extensions.contains(&CHUNK_I_DATA);
+    }
+
+    fn get_rwnd(&self) -> usize {
+        self.config.receive_window.saturating_sub(self.buffered)
+    }
+
+    // https://tools.ietf.org/html/rfc9260#section-8.5
+    fn validate_verification_tag(&self, packet: &Packet) -> Result<()> {
+        let tag = packet.verification_tag;
+        let valid = match &packet.chunks[0] {
+            Chunk::Init(_) => tag == 0 && packet.chunks.len() == 1,
+            // the tag of the cookie is checked with the cookie.
+            Chunk::CookieEcho(_) => true,
+            Chunk::Abort { reflected, .. } | Chunk::ShutdownComplete { reflected } => {
+                self.state != SctpState::Closed
+                    && (if *reflected {
+                        tag == self.peer_tag
+                    } else {
+                        tag == self.local_tag
+                    })
+            }
+            _ => self.state != SctpState::Closed && tag == self.local_tag,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(SctpError::InvalidVerificationTag)
+        }
+    }
+
+    fn send_packet(&mut self, verification_tag: u32, chunks: Vec<Chunk>) {
+        let packet = Packet {
+            source_port: self.config.local_port,
+            destination_port: self.config.remote_port,
+            verification_tag,
+            chunks,
+        };
+        self.transmits.push_back(packet.to_bytes());
+    }
+
+    fn handle_init(&mut self, init: InitChunk, now: Instant) {
+        // the tag and TSN of the INIT ACK are those of an INIT sent, or new
+        // ones kept only in the cookie. A restart is not supported.
+        let (tag, tsn) = match (&self.state, &self.init) {
+            (SctpState::Closed, _) => (random_tag(), rand::random()),
+            (SctpState::CookieWait, Some(local)) | (SctpState::CookieEchoed, Some(local)) => {
+                (local.initiate_tag, local.initial_tsn)
+            }
+            _ => return,
+        };
+        let cookie = self.make_cookie(tag, tsn, &init, now);
+        let init_ack = InitChunk {
+            initiate_tag: tag,
+            a_rwnd: self.config.receive_window as u32,
+            outbound_streams: self.config.outbound_streams,
+            inbound_streams: self.config.inbound_streams,
+            initial_tsn: tsn,
+            state_cookie: Some(cookie),
+            supported_extensions: self.get_supported_extensions(),
+            forward_tsn_supported: true,
+        };
+        self.send_packet(init.initiate_tag, vec![Chunk::InitAck(init_ack)]);
+    }
+
+    fn handle_init_ack(&mut self, init: InitChunk, now: Instant) {
+        if self.state != SctpState::CookieWait {
+            return;
+        }
+        self.set_peer(&init);
+        let cookie = init.state_cookie.unwrap_or_default();
+        self.control.push_back(Chunk::CookieEcho(cookie.clone()));
+        self.cookie_echo = Some(cookie);
+        self.error_count = 0;
+        self.control_timer = Some(now + self.rto);
+        self.set_state(SctpState::CookieEchoed);
+    }
+
+    /*
+        local tag(4) | local initial TSN(4) | created, ms since epoch(8)
+        | INIT of the peer | HMAC-SHA256
+    */
+    fn make_cookie(&self, tag: u32, tsn: u32, init: &InitChunk, now: Instant) -> Vec<u8> {
+        let mut cookie = Vec::new();
+        cookie.extend_from_slice(&tag.to_be_bytes());
+        cookie.extend_from_slice(&tsn.to_be_bytes());
+        let created = now.duration_since(self.epoch).as_millis() as u64;
+        cookie.extend_from_slice(&created.to_be_bytes());
+        cookie.extend(Chunk::Init(init.clone()).to_bytes());
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.cookie_secret).unwrap();
+        mac.update(&cookie);
+        cookie.extend_from_slice(&mac.finalize().into_bytes());
+        cookie
+    }
+
+    fn verify_cookie(&self, cookie: &[u8], now: Instant) -> Result<(u32, u32, InitChunk)> {
+        if cookie.len() < COOKIE_LENGTH + COOKIE_MAC_LENGTH {
+            return Err(SctpError::InvalidCookie);
+        }
+        let (body, tag) = cookie.split_at(cookie.len() - COOKIE_MAC_LENGTH);
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.cookie_secret).unwrap();
+        mac.update(body);
+        mac.verify_slice(tag)
+            .map_err(|_| SctpError::InvalidCookie)?;
+        let mut created = [0; 8];
+        created.copy_from_slice(&body[8..16]);
+        let created = self.epoch + Duration::from_millis(u64::from_be_bytes(created));
+        if now > created + COOKIE_LIFETIME {
+            return Err(SctpError::InvalidCookie);
+        }
+        let init = match Chunk::parse_chunks(&body[COOKIE_LENGTH..]).as_deref() {
+            Ok([Chunk::Init(init)]) => init.clone(),
+            _ => return Err(SctpError::InvalidCookie),
+        };
+        Ok((
+            u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+            u32::from_be_bytes([body[4], body[5], body[6], body[7]]),
+            init,
+        ))
+    }
+
+    fn handle_cookie_echo(&mut self, cookie: &[u8], tag: u32, now: Instant) -> Result<()> {
+        let (local_tag, local_tsn, init) = self.verify_cookie(cookie, now)?;
+        if tag != local_tag {
+            return Err(SctpError::InvalidVerificationTag);
+        }
+        match self.state {
+            SctpState::Closed => {
+                self.local_tag = local_tag;
+                self.set_initial_tsn(local_tsn);
+                self.set_peer(&init);
+                self.set_state(SctpState::Established);
+            }
+            SctpState::CookieWait | SctpState::CookieEchoed if local_tag == self.local_tag => {
+                self.set_peer(&init);
+                self.cookie_echo = None;
+                self.control_timer = None;
+                self.error_count = 0;
+                self.set_state(SctpState::Established);
+            }
+            // the COOKIE ACK was lost.
+            _ if local_tag == self.local_tag && init.initiate_tag == self.peer_tag => {}
+            _ => return Ok(()),
+        }
+        self.control.push_back(Chunk::CookieAck);
+        Ok(())
+    }
+
+    fn handle_data(&mut self, chunk: DataChunk, interleaved: bool) -> Result<()> {
+        if !self.has_peer() || self.state == SctpState::CookieEchoed {
+            return Ok(());
+        }
+        // one or the other, as negotiated (RFC 8260 Section 2.1).
+        if interleaved != self.interleaving {
+            self.abort();
+            return Err(SctpError::InvalidChunk);
+        }
+        let tsn = extend_tsn(self.peer_cumulative_tsn, chunk.tsn);
+        if tsn <= self.peer_cumulative_tsn || self.received_tsns.contains(&tsn) {
+            if self.duplicate_tsns.len() < MAX_DUPLICATE_TSNS {
+                self.duplicate_tsns.push(chunk.tsn);
+            }
+            self.ack_now = true;
+            return Ok(());
+        }
+        let highest = self
+            .received_tsns
+            .iter()
+            .next_back()
+            .copied()
+            .unwrap_or(self.peer_cumulative_tsn);
+        // no room: dropped, unless it fills a hole.
+        if self.buffered + chunk.user_data.len() > self.config.receive_window && tsn > highest {
+            self.ack_now = true;
+            return Ok(());
+        }
+        if tsn > self.peer_cumulative_tsn + 1 || chunk.immediate {
+            self.ack_now = true;
+        }
+        self.received_tsns.insert(tsn);
+        while self.received_tsns.remove(&(self.peer_cumulative_tsn + 1)) {
+            self.peer_cumulative_tsn += 1;
+        }
+        if chunk.stream_id >= self.inbound_streams {
+            // acknowledged, and dropped (Section 6.5).
+            let mut info = chunk.stream_id.to_be_bytes().to_vec();
+            info.extend_from_slice(&[0, 0]);
+            self.control
+                .push_back(Chunk::Error(error_cause(CAUSE_INVALID_STREAM, &info)));
+            return Ok(());
+        }
+        self.buffered += chunk.user_data.len();
+        if interleaved {
+            self.reassemble_interleaved(chunk);
+        } else {
+            self.reassembly.insert(tsn, chunk);
+            self.reassemble(tsn);
+        }
+        Ok(())
+    }
+
+    // the fragments of a message have consecutive TSNs, from B to E.
+    fn reassemble(&mut self, tsn: u64) {
+        let chunk = &self.reassembly[&tsn];
+        let (stream_id, unordered) = (chunk.stream_id, chunk.unordered);
+        let is_fragment = |v: &DataChunk| v.stream_id == stream_id && v.unordered == unordered;
+        let mut first = tsn;
+        while !self.reassembly[&first].beginning {
+            match self.reassembly.get(&(first - 1)) {
+                Some(v) if is_fragment(v) && !v.ending => first -= 1,
+                _ => return,
+            }
+        }
+        let mut last = tsn;
+        while !self.reassembly[&last].ending {
+            match self.reassembly.get(&(last + 1)) {
+                Some(v) if is_fragment(v) && !v.beginning => last += 1,
+                _ => return,
+            }
+        }
+        let fragments: Vec<DataChunk> = (first..=last)
+            .filter_map(|v| self.reassembly.remove(&v))
+            .collect();
+        let message = SctpMessage {
+            stream_id,
+            ppid: fragments[0].ppid,
+            data: fragments.iter().flat_map(|v| v.user_data.clone()).collect(),
+            unordered,
+        };
+        self.deliver(message, u32::from(fragments[0].ssn));
+    }
+
+    // the fragments of a message have the same MID, and FSNs from 0.
+    fn reassemble_interleaved(&mut self, chunk: DataChunk) {
+        let key = (chunk.stream_id, chunk.unordered, chunk.mid);
+        let fragments = self.fragments.entry(key).or_default();
+        fragments.insert(chunk.fsn, chunk);
+        let last = match fragments.values().next_back() {
+            Some(v) if v.ending => v.fsn,
+            _ => return,
+        };
+        if fragments.len() as u64 != u64::from(last) + 1
+            || !fragments.get(&0).is_some_and(|v| v.beginning)
+        {
+            return;
+        }
+        let fragments = self.fragments.remove(&key).unwrap();
+        let message = SctpMessage {
+            stream_id: key.0,
+            ppid: fragments[&0].ppid,
+            data: fragments
+                .values()
+                .flat_map(|v| v.user_data.clone())
+                .collect(),
+            unordered: key.1,
+        };
+        self.deliver(message, key.2);
+    }
+
+    fn deliver(&mut self, message: SctpMessage, sequence: u32) {
+        if message.unordered {
+            self.messages.push_back(message);
+            return;
+        }
+        let stream_id = message.stream_id;
+        self.inbound
+            .entry(stream_id)
+            .or_default()
+            .messages
+            .insert(sequence, message);
+        self.deliver_ordered(stream_id);
+    }
+
+    fn deliver_ordered(&mut self, stream_id: u16) {
+        let interleaving = self.interleaving;
+        let stream = self.inbound.entry(stream_id).or_default();
+        while let Some(message) = stream.messages.remove(&stream.next_sequence) {
+            stream.next_sequence = next_sequence(interleaving, stream.next_sequence);
+            self.messages.push_back(message);
+        }
+    }
+
+    fn send_reset_request(&mut self, now: Instant) {
+        if self.reconfig_request.is_some() || self.queued_resets.is_empty() {
+            return;
+        }
+        // after the TSNs of the messages queued before.
+        let queued_resets = &self.queued_resets;
+        if self
+            .outgoing
+            .iter()
+            .any(|v| queued_resets.contains(&v.message.stream_id))
+        {
+            return;
+        }
+        let streams = std::mem::take(&mut self.queued_resets);
+        for stream in &streams {
+            self.sequences.remove(&(*stream, false));
+            self.sequences.remove(&(*stream, true));
+        }
+        let request = ReconfigParameter::OutgoingResetRequest {
+            request_sequence: self.reconfig_sequence,
+            response_sequence: self.peer_reconfig_sequence.wrapping_sub(1),
+            last_tsn: (self.next_tsn - 1) as u32,
+            streams,
+        };
+        self.control
+            .push_back(Chunk::Reconfig(vec![request.clone()]));
+        self.reconfig_request = Some(request);
+        self.reconfig_timer = Some(now + self.rto);
+    }
+
+    // https://tools.ietf.org/html/rfc6525#section-5.2
+    fn handle_reconfig(&mut self, parameters: Vec<ReconfigParameter>, now: Instant) {
+        for parameter in parameters {
+            match parameter {
+                ReconfigParameter::OutgoingResetRequest {
+                    request_sequence,
+                    last_tsn,
+                    streams,
+                    ..
+                } => {
+                    if request_sequence == self.peer_reconfig_sequence {
+                        let last_tsn = extend_tsn(self.peer_cumulative_tsn, last_tsn);
+                        if self.peer_cumulative_tsn >= last_tsn {
+                            self.reset_incoming_streams(request_sequence, streams);
+                        } else {
+                            self.deferred_reset = Some((request_sequence, last_tsn, streams));
+                            self.send_reconfig_response(request_sequence, RECONFIG_IN_PROGRESS);
+                        }
+                    } else if let Some((sequence, result)) = self
+                        .last_reconfig_response
+                        .filter(|(v, _)| *v == request_sequence)
+                    {
+                        // the response was lost.
+                        self.send_reconfig_response(sequence, result);
+                    } else {
+                        self.send_reconfig_response(request_sequence, RECONFIG_BAD_SEQUENCE_NUMBER);
+                    }
+                }
+                ReconfigParameter::Response {
+                    response_sequence,
+                    result,
+                } => {
+                    let streams = match &self.reconfig_request {
+                        Some(ReconfigParameter::OutgoingResetRequest {
+                            request_sequence,
+                            streams,
+                            ..
+                        }) if *request_sequence == response_sequence => streams.clone(),
+                        _ => continue,
+                    };
+                    // asked again when the timer expires.
+                    if result == RECONFIG_IN_PROGRESS {
+                        continue;
+                    }
+                    self.reconfig_request = None;
+                    self.reconfig_timer = None;
+                    self.reconfig_sequence = self.reconfig_sequence.wrapping_add(1);
+                    if result == RECONFIG_SUCCESS_PERFORMED
+                        || result == RECONFIG_SUCCESS_NOTHING_TO_DO
+                    {
+                        self.events
+                            .push_back(SctpEvent::OutgoingStreamsReset(streams));
+                    }
+                    self.send_reset_request(now);
+                    for message in std::mem::take(&mut self.held) {
+                        if self.is_resetting(message.stream_id) {
+                            self.held.push_back(message);
+                        } else {
+                            self.enqueue(message);
+                        }
+                    }
+                }
+                ReconfigParameter::Unknown { .. } => {}
+            }
+        }
+    }
+
+    fn check_deferred_reset(&mut self) {
+        if let Some((sequence, last_tsn, _)) = &self.deferred_reset {
+            if self.peer_cumulative_tsn >= *last_tsn {
+                let sequence = *sequence;
+                let streams = self.deferred_reset.take().unwrap().2;
+                self.reset_incoming_streams(sequence, streams);
+            }
+        }
+    }
+
+    fn reset_incoming_streams(&mut self, sequence: u32, streams: Vec<u16>) {
+        self.deferred_reset = None;
+        let reset: Vec<u16> = if streams.is_empty() {
+            self.inbound.keys().copied().collect()
+        } else {
+            streams.clone()
+        };
+        // the messages of the next SSNs may already be there.
+        for stream in reset {
+            self.inbound.entry(stream).or_default().next_sequence = 0;
+            self.deliver_ordered(stream);
+        }
+        self.peer_reconfig_sequence = self.peer_reconfig_sequence.wrapping_add(1);
+        self.send_reconfig_response(sequence, RECONFIG_SUCCESS_PERFORMED);
+        self.events
+            .push_back(SctpEvent::IncomingStreamsReset(streams));
+    }
+
+    fn send_reconfig_response(&mut self, response_sequence: u32, result: u32) {
+        self.last_reconfig_response = Some((response_sequence, result));
+        self.control
+            .push_back(Chunk::Reconfig(vec![ReconfigParameter::Response {
+                response_sequence,
+                result,
+            }]));
+    }
+
+    fn make_sack(&mut self) -> Chunk {
+        let cumulative = self.peer_cumulative_tsn;
+        let max_gap_blocks = (self.config.mtu - COMMON_HEADER_LENGTH - CHUNK_HEADER_LENGTH - 12)
+            / 4
+            - MAX_DUPLICATE_TSNS;
+        let mut gap_blocks: Vec<(u16, u16)> = Vec::new();
+        for tsn in &self.received_tsns {
+            let offset = tsn - cumulative;
+            if offset > u64::from(u16::MAX) {
+                break;
+            }
+            let offset = offset as u16;
+            if let Some((_, end)) = gap_blocks.last_mut() {
+                if *end + 1 == offset {
+                    *end = offset;
+                    continue;
+                }
+            }
+            if gap_blocks.len() == max_gap_blocks {
+                break;
+            }
+            gap_blocks.push((offset, offset));
+        }
+        self.advertised_rwnd = self.get_rwnd();
+        Chunk::Sack(SackChunk {
+            cumulative_tsn_ack: cumulative as u32,
+            a_rwnd: self.advertised_rwnd as u32,
+            gap_blocks,
+            duplicate_tsns: std::mem::take(&mut self.duplicate_tsns),
+        })
+    }
+
+    // https://tools.ietf.org/html/rfc9260#section-6.2.1
+    fn handle_sack(&mut self, sack: &SackChunk, now: Instant) {
+        if !self.has_peer() || self.state == SctpState::CookieEchoed {
+            return;
+        }
+        let cumulative = extend_tsn(self.cumulative_tsn_acked, sack.cumulative_tsn_ack);
+        let highest_sent = self
+            .sent
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(self.cumulative_tsn_acked);
+        // out of order, or acking what was never sent.
+        if cumulative < self.cumulative_tsn_acked || cumulative > highest_sent {
+            return;
+        }
+        let flight_before = self.flight_size;
+        let advanced = cumulative > self.cumulative_tsn_acked;
+        let mut bytes_acked = 0;
+        let mut rtt = None;
+        let mut highest_newly_acked = None;
+        while let Some(entry) = self.sent.first_entry() {
+            if *entry.key() > cumulative {
+                break;
+            }
+            let (tsn, sent) = entry.remove_entry();
+            let length = sent.chunk.user_data.len();
+            if sent.in_flight {
+                self.flight_size -= length;
+            }
+            if !sent.gap_acked && !sent.abandoned {
+                bytes_acked += length;
+                highest_newly_acked = Some(tsn);
+                if sent.transmissions == 1 {
+                    rtt = Some(now.duration_since(sent.sent_at));
+                }
+            }
+        }
+        self.cumulative_tsn_acked = cumulative;
+
+        for (tsn, sent) in self.sent.iter_mut() {
+            if sent.abandoned {
+                continue;
+            }
+            let offset = tsn - cumulative;
+            let covered = sack
+                .gap_blocks
+                .iter()
+                .any(|(start, end)| u64::from(*start) <= offset && offset <= u64::from(*end));
+            if covered && !sent.gap_acked {
+                let length = sent.chunk.user_data.len();
+                sent.gap_acked = true;
+                sent.retransmit = false;
+                if sent.in_flight {
+                    sent.in_flight = false;
+                    self.flight_size -= length;
+                }
+                bytes_acked += length;
+                highest_newly_acked = Some(*tsn);
+            } else if !covered && sent.gap_acked {
+                // reneged, T3-rtx sends it again.
+                sent.gap_acked = false;
+            }
+        }
+
+        if let Some(highest) = highest_newly_acked {
+            let mut lost = false;
+            for (_, sent) in self.sent.range_mut(..highest) {
+                if sent.gap_acked || !sent.in_flight {
+                    continue;
+                }
+                sent.misses += 1;
+                if sent.misses == FAST_RETRANSMIT_THRESHOLD {
+                    sent.in_flight = false;
+                    sent.retransmit = true;
+                    self.flight_size -= sent.chunk.user_data.len();
+                    lost = true;
+                }
+            }
+            if lost {
+                self.fast_retransmit = true;
+                if self.fast_recovery_exit.is_none() {
+                    self.ssthresh = (self.cwnd / 2).max(4 * self.config.mtu);
+                    self.cwnd = self.ssthresh;
+                    self.partial_bytes_acked = 0;
+                    self.fast_recovery_exit = Some(highest_sent);
+                }
+            }
+        }
+
+        if let Some(rtt) = rtt {
+            self.update_rto(rtt);
+        }
+        if advanced && self.fast_recovery_exit.is_none() {
+            self.update_cwnd(bytes_acked, flight_before);
+        }
+        if self.fast_recovery_exit.is_some_and(|v| cumulative >= v) {
+            self.fast_recovery_exit = None;
+        }
+        if self.flight_size == 0 {
+            self.partial_bytes_acked = 0;
+        }
+        self.peer_rwnd = (sack.a_rwnd as usize).saturating_sub(self.flight_size);
+
+        if self.sent.is_empty() {
+            self.t3_rtx = None;
+        } else if advanced {
+            self.t3_rtx = Some(now + self.rto);
+        }
+        // a peer advertising a zero window is alive, even if the probes
+        // are dropped (Section 6.1).
+        if advanced || sack.a_rwnd == 0 {
+            self.error_count = 0;
+        }
+        // behind the advanced peer ack point (RFC 3758 Section 3.5 C3).
+        self.queue_forward_tsn(now);
+    }
+
+    // https://tools.ietf.org/html/rfc9260#section-7.2
+    fn update_cwnd(&mut self, bytes_acked: usize, flight_before: usize) {
+        let mtu = self.config.mtu;
+        if flight_before < self.cwnd {
+            // not limited by cwnd, nothing learned.
+            return;
+        }
+        if self.cwnd <= self.ssthresh {
+            self.cwnd += bytes_acked.min(mtu);
+        } else {
+            self.partial_bytes_acked += bytes_acked;
+            if self.partial_bytes_acked >= self.cwnd {
+                self.partial_bytes_acked -= self.cwnd;
+                self.cwnd += mtu;
+            }
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc6298#section-2
+    fn update_rto(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.abs_diff(rtt);
+                self.rttvar = self.rttvar * 3 / 4 + delta / 4;
+                self.srtt = Some(srtt * 7 / 8 + rtt / 8);
+            }
+        }
+        let rto = self.srtt.unwrap_or_default() + self.rttvar * 4;
+        self.rto = rto.clamp(self.config.rto_min, self.config.rto_max);
+    }
+
+    // https://tools.ietf.org/html/rfc9260#section-6.3.3
+    fn on_t3_rtx_timeout(&mut self) {
+        self.t3_rtx = None;
+        self.error_count += 1;
+        if self.error_count > self.config.max_retransmits {
+            self.abort();
+            return;
+        }
+        self.ssthresh = (self.cwnd / 2).max(4 * self.config.mtu);
+        self.cwnd = self.config.mtu;
+        self.partial_bytes_acked = 0;
+        self.fast_recovery_exit = None;
+        self.rto = (self.rto * 2).min(self.config.rto_max);
+        for sent in self.sent.values_mut() {
+            if sent.gap_acked || sent.abandoned {
+                continue;
+            }
+            if sent.in_flight {
+                sent.in_flight = false;
+                self.flight_size -= sent.chunk.user_data.len();
+            }
+            sent.retransmit = true;
+        }
+    }
+
+    fn on_control_timeout(&mut self, now: Instant) {
+        self.control_timer = None;
+        self.error_count += 1;
+        let max = match self.state {
+            SctpState::CookieWait | SctpState::CookieEchoed => self.config.max_init_retransmits,
+            _ => self.config.max_retransmits,
+        };
+        if self.error_count > max {
+            self.abort();
+            return;
+        }
+        self.rto = (self.rto * 2).min(self.config.rto_max);
+        match self.state {
+            SctpState::CookieWait => {
+                if let Some(init) = self.init.clone() {
+                    self.send_packet(0, vec![Chunk::Init(init)]);
+                }
+            }
+            SctpState::CookieEchoed => {
+                if let Some(cookie) = self.cookie_echo.clone() {
+                    self.control.push_back(Chunk::CookieEcho(cookie));
+                }
+            }
+            SctpState::ShutdownSent => self.control.push_back(Chunk::Shutdown {
+                cumulative_tsn_ack: self.peer_cumulative_tsn as u32,
+            }),
+            SctpState::ShutdownAckSent => self.control.push_back(Chunk::ShutdownAck),
+            _ => return,
+        }
+        self.control_timer = Some(now + self.rto);
+    }
+
+    // https://tools.ietf.org/html/rfc9260#section-9.2
+    fn handle_shutdown(&mut self, cumulative_tsn_ack: u32, now: Instant) {
+        match self.state {
+            SctpState::Established | SctpState::ShutdownPending => {
+                let sack = SackChunk {
+                    cumulative_tsn_ack,
+                    a_rwnd: (self.peer_rwnd + self.flight_size) as u32,
+                    gap_blocks: Vec::new(),
+                    duplicate_tsns: Vec::new(),
+                };
+                self.handle_sack(&sack, now);
+                self.set_state(SctpState::ShutdownReceived);
+            }
+            // both sides shut down at once.
+            SctpState::ShutdownSent => {
+                self.control.push_back(Chunk::ShutdownAck);
+                self.error_count = 0;
+                self.control_timer = Some(now + self.rto);
+                self.set_state(SctpState::ShutdownAckSent);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_shutdown_ack(&mut self) {
+        if self.state == SctpState::ShutdownSent || self.state == SctpState::ShutdownAckSent {
+            self.send_packet(
+                self.peer_tag,
+                vec![Chunk::ShutdownComplete { reflected: false }],
+            );
+            self.close();
+        }
+    }
+
+    fn check_shutdown(&mut self, now: Instant) {
+        if !self.outgoing.is_empty() || !self.sent.is_empty() {
+            return;
+        }
+        match self.state {
+            SctpState::ShutdownPending => {
+                self.control.push_back(Chunk::Shutdown {
+                    cumulative_tsn_ack: self.peer_cumulative_tsn as u32,
+                });
+                self.set_state(SctpState::ShutdownSent);
+            }
+            SctpState::ShutdownReceived => {
+                self.control.push_back(Chunk::ShutdownAck);
+                self.set_state(SctpState::ShutdownAckSent);
+            }
+            _ => return,
+        }
+        self.error_count = 0;
+        self.control_timer = Some(now + self.rto);
+    }
+
+    fn can_send_data(&self) -> bool {
+        matches!(
+            self.state,
+            SctpState::Established | SctpState::ShutdownPending | SctpState::ShutdownReceived
+        )
+    }
+
+    fn transmit(&mut self, now: Instant) {
+        if self.state == SctpState::Closed {
+            return;
+        }
+        if self.can_send_data() && self.abandon_messages(now) {
+            self.queue_forward_tsn(now);
+        }
+        let mut builder = PacketBuilder::new(self.config.mtu);
+        let has_data = self.can_send_data()
+            && (!self.outgoing.is_empty() || self.sent.values().any(|v| v.retransmit));
+        // a pending SACK goes with anything else sent.
+        if self.has_peer()
+            && (self.ack_now
+                || (self.ack_timer.is_some() && (has_data || !self.control.is_empty())))
+        {
+            let sack = self.make_sack();
+            builder.push(sack);
+            self.ack_now = false;
+            self.ack_timer = None;
+            self.unacked_packets = 0;
+        }
+        while let Some(chunk) = self.control.pop_front() {
+            builder.push(chunk);
+        }
+        if has_data {
+            self.transmit_data(&mut builder, now);
+            // a reset waiting for the messages queued before it.
+            self.send_reset_request(now);
+            while let Some(chunk) = self.control.pop_front() {
+                builder.push(chunk);
+            }
+        }
+        for chunks in builder.finish() {
+            self.send_packet(self.peer_tag, chunks);
+        }
+    }
+
+    fn transmit_data(&mut self, builder: &mut PacketBuilder, now: Instant) {
+        let interleaving = self.interleaving;
+        let header_length = if interleaving {
+            I_DATA_CHUNK_HEADER_LENGTH
+        } else {
+            DATA_CHUNK_HEADER_LENGTH
+        };
+        let mut sent_any = false;
+        // a fast retransmit may exceed cwnd by one packet.
+        let mut fast_retransmit_budget = if self.fast_retransmit {
+            self.config.mtu - COMMON_HEADER_LENGTH
+        } else {
+            0
+        };
+        self.fast_retransmit = false;
+        for sent in self.sent.values_mut() {
+            if !sent.retransmit {
+                continue;
+            }
+            let length = sent.chunk.user_data.len();
+            if self.flight_size >= self.cwnd {
+                if fast_retransmit_budget < header_length + length {
+                    break;
+                }
+                fast_retransmit_budget -= header_length + length;
+            }
+            sent.retransmit = false;
+            sent.in_flight = true;
+            sent.misses = 0;
+            sent.transmissions += 1;
+            sent.sent_at = now;
+            self.flight_size += length;
+            builder.push(wrap_data_chunk(interleaving, sent.chunk.clone()));
+            sent_any = true;
+        }
+        let max_payload = (self.config.mtu - COMMON_HEADER_LENGTH - header_length) & !3;
+        while let Some(index) = self.get_next_outgoing() {
+            let outgoing = &self.outgoing[index];
+            let length = (outgoing.message.data.len() - outgoing.sent).min(max_payload);
+            // one chunk probes a closed window.
+            if self.flight_size >= self.cwnd || (self.peer_rwnd < length && self.flight_size > 0) {
+                break;
+            }
+            let sent = self.take_fragment(index, length, now);
+            self.remove_buffered_amount(sent.chunk.stream_id, length);
+            self.flight_size += length;
+            self.peer_rwnd = self.peer_rwnd.saturating_sub(length);
+            builder.push(wrap_data_chunk(interleaving, sent.chunk.clone()));
+            self.sent.insert(self.next_tsn - 1, sent);
+            sent_any = true;
+        }
+        if sent_any && self.t3_rtx.is_none() {
+            self.t3_rtx = Some(now + self.rto);
+        }
+    }
+
+    // the messages go one after another with DATA, round robin over the
+    // streams with I-DATA. A stream sends its messages in order.
+    fn get_next_outgoing(&self) -> Option<usize> {
+        if !self.interleaving {
+            return if self.outgoing.is_empty() {
+                None
+            } else {
+                Some(0)
+            };
+        }
+        let mut streams = HashSet::new();
+        let mut first: Option<(u16, usize)> = None;
+        let mut next: Option<(u16, usize)> = None;
+        for (i, outgoing) in self.outgoing.iter().enumerate() {
+            let stream_id = outgoing.message.stream_id;
+            if !streams.insert(stream_id) {
+                continue;
+            }
+            if first.is_none_or(|(v, _)| stream_id < v) {
+                first = Some((stream_id, i));
+            }
+            if self.last_stream.is_none_or(|v| stream_id > v)
+                && next.is_none_or(|(v, _)| stream_id < v)
+            {
+                next = Some((stream_id, i));
+            }
+        }
+        next.or(first).map(|(_, i)| i)
+    }
+
+    fn take_fragment(&mut self, index: usize, length: usize, now: Instant) -> SentChunk {
+        let interleaving = self.interleaving;
+        let tsn = self.next_tsn;
+        self.next_tsn += 1;
+        let outgoing = &mut self.outgoing[index];
+        let stream_id = outgoing.message.stream_id;
+        let unordered = outgoing.message.unordered;
+        if outgoing.sent == 0 && (interleaving || !unordered) {
+            let next = self.sequences.entry((stream_id, unordered)).or_insert(0);
+            outgoing.sequence = *next;
+            *next = next_sequence(interleaving, *next);
+        }
+        let start = outgoing.sent;
+        let end = start + length;
+        let chunk = DataChunk {
+            tsn: tsn as u32,
+            stream_id,
+            ssn: if interleaving {
+                0
+            } else {
+                outgoing.sequence as u16
+            },
+            mid: if interleaving { outgoing.sequence } else { 0 },
+            fsn: if interleaving { outgoing.fsn } else { 0 },
+            ppid: if interleaving && start > 0 {
+                0
+            } else {
+                outgoing.message.ppid
+            },
+            unordered,
+            beginning: start == 0,
+            ending: end == outgoing.message.data.len(),
+            immediate: false,
+            user_data: outgoing.message.data[start..end].to_vec(),
+        };
+        outgoing.sent = end;
+        outgoing.fsn += 1;
+        let (message, limit) = (outgoing.id, outgoing.limit);
+        if chunk.ending {
+            self.outgoing.remove(index);
+        }
+        self.last_stream = Some(stream_id);
+        SentChunk {
+            chunk,
+            sent_at: now,
+            transmissions: 1,
+            misses: 0,
+            in_flight: true,
+            gap_acked: false,
+            retransmit: false,
+            abandoned: false,
+            message,
+            limit,
+        }
+    }
+
+    fn remove_buffered_amount(&mut self, stream_id: u16, length: usize) {
+        if let Some(amount) = self.buffered_amounts.get_mut(&stream_id) {
+            *amount -= length;
+            if *amount == 0 {
+                self.buffered_amounts.remove(&stream_id);
+            }
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc3758#section-3.5
+    fn abandon_messages(&mut self, now: Instant) -> bool {
+        let mut messages = Vec::new();
+        for sent in self.sent.values() {
+            if sent.abandoned || sent.gap_acked {
+                continue;
+            }
+            let expired = sent.limit.is_some_and(|limit| match limit.reliability {
+                SctpReliability::Reliable => false,
+                SctpReliability::MaxRetransmits(v) => sent.retransmit && sent.transmissions > v,
+                SctpReliability::MaxLifetime(v) => now >= limit.queued_at + v,
+            });
+            if expired && !messages.contains(&sent.message) {
+                messages.push(sent.message);
+            }
+        }
+        // a message not sent yet expires too.
+        for outgoing in &self.outgoing {
+            let expired = outgoing.limit.is_some_and(|limit| {
+                matches!(limit.reliability,
+                    SctpReliability::MaxLifetime(v) if now >= limit.queued_at + v)
+            });
+            if expired && !messages.contains(&outgoing.id) {
+                messages.push(outgoing.id);
+            }
+        }
+        for message in &messages {
+            self.abandon_message(*message);
+        }
+        !messages.is_empty()
+    }
+
+    // the fragments sent are skipped with FORWARD TSN, the others dropped.
+    fn abandon_message(&mut self, message: u64) {
+        for sent in self.sent.values_mut() {
+            if sent.message != message {
+                continue;
+            }
+            if sent.in_flight {
+                sent.in_flight = false;
+                self.flight_size -= sent.chunk.user_data.len();
+            }
+            sent.retransmit = false;
+            sent.abandoned = true;
+        }
+        if let Some(index) = self.outgoing.iter().position(|v| v.id == message) {
+            let outgoing = self.outgoing.remove(index).unwrap();
+            let length = outgoing.message.data.len() - outgoing.sent;
+            self.remove_buffered_amount(outgoing.message.stream_id, length);
+        }
+    }
+
+    // up to the advanced peer ack point, with the last SSN or MID of each
+    // stream.
+    fn queue_forward_tsn(&mut self, now: Instant) {
+        let mut point = self.cumulative_tsn_acked;
+        let mut streams = BTreeMap::new();
+        for (tsn, sent) in &self.sent {
+            if *tsn != point + 1 || !sent.abandoned {
+                break;
+            }
+            point = *tsn;
+            let chunk = &sent.chunk;
+            if self.interleaving {
+                streams.insert((chunk.stream_id, chunk.unordered), chunk.mid);
+            } else if !chunk.unordered {
+                streams.insert((chunk.stream_id, false), u32::from(chunk.ssn));
+            }
+        }
+        if point == self.cumulative_tsn_acked {
+            return;
+        }
+        let new_cumulative_tsn = point as u32;
+        let chunk = if self.interleaving {
+            Chunk::IForwardTsn {
+                new_cumulative_tsn,
+                streams: streams
+                    .into_iter()
+                    .map(|((stream, unordered), mid)| (stream, unordered, mid))
+                    .collect(),
+            }
+        } else {
+            Chunk::ForwardTsn {
+                new_cumulative_tsn,
+                streams: streams
+                    .into_iter()
+                    .map(|((stream, _), ssn)| (stream, ssn as u16))
+                    .collect(),
+            }
+        };
+        self.control.push_back(chunk);
+        if self.t3_rtx.is_none() {
+            self.t3_rtx = Some(now + self.rto);
+        }
+    }
+
+    // https://tools.ietf.org/html/rfc3758#section-3.6
+    // https://tools.ietf.org/html/rfc8260#section-2.3.2
+    fn handle_forward_tsn(&mut self, new_cumulative_tsn: u32, streams: Vec<(u16, bool, u32)>) {
+        if !self.has_peer() || self.state == SctpState::CookieEchoed {
+            return;
+        }
+        self.ack_now = true;
+        let tsn = extend_tsn(self.peer_cumulative_tsn, new_cumulative_tsn);
+        if tsn <= self.peer_cumulative_tsn {
+            return;
+        }
+        self.peer_cumulative_tsn = tsn;
+        self.received_tsns = self.received_tsns.split_off(&(tsn + 1));
+        while self.received_tsns.remove(&(self.peer_cumulative_tsn + 1)) {
+            self.peer_cumulative_tsn += 1;
+        }
+        // the fragments of the messages skipped never complete.
+        let kept = self.reassembly.split_off(&(tsn + 1));
+        let mut dropped: usize = std::mem::replace(&mut self.reassembly, kept)
+            .values()
+            .map(|v| v.user_data.len())
+            .sum();
+        let cumulative = self.peer_cumulative_tsn;
+        self.fragments.retain(|_, fragments| {
+            fragments.retain(|_, v| {
+                let skipped = extend_tsn(cumulative, v.tsn) <= tsn;
+                if skipped {
+                    dropped += v.user_data.len();
+                }
+                !skipped
+            });
+            !fragments.is_empty()
+        });
+        self.buffered -= dropped;
+
+        let interleaving = self.interleaving;
+        for (stream_id, unordered, sequence) in streams {
+            if unordered {
+                continue;
+            }
+            let stream = self.inbound.entry(stream_id).or_default();
+            let next = stream.next_sequence;
+            let skipped = get_sequence_distance(interleaving, next, sequence);
+            if skipped < 0 {
+                continue;
+            }
+            // the messages before the skipped one that did complete go first.
+            let mut ready: Vec<u32> = stream
+                .messages
+                .keys()
+                .copied()
+                .filter(|v| (0..=skipped).contains(&get_sequence_distance(interleaving, next, *v)))
+                .collect();
+            ready.sort_by_key(|v| get_sequence_distance(interleaving, next, *v));
+            for v in ready {
+                if let Some(message) = stream.messages.remove(&v) {
+                    self.messages.push_back(message);
+                }
+            }
+            stream.next_sequence = next_sequence(interleaving, sequence);
+            self.deliver_ordered(stream_id);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::dtls::transport::test::new_transports;
+    use crate::dtls::transport::DtlsState;
+
+    pub(crate) fn new_pair(now: Instant) -> (SctpAssociation, SctpAssociation) {
+        (
+            SctpAssociation::new(SctpConfig::default(), now),
+            SctpAssociation::new(SctpConfig::default(), now),
+        )
+    }
+
+    /// Delivers the packets both ways until there are none, dropping those
+    /// `drop` returns true for.
+    fn deliver_with(
+        a: &mut SctpAssociation,
+        b: &mut SctpAssociation,
+        now: Instant,
+        drop: &mut dyn FnMut(&[u8]) -> bool,
+    ) {
+        loop {
+            let mut delivered = false;
+            while let Some(v) = a.poll_transmit() {
+                if !drop(&v) {
+                    b.handle_packet(&v, now).unwrap();
+                }
+                delivered = true;
+            }
+            while let Some(v) = b.poll_transmit() {
+                if !drop(&v) {
+                    a.handle_packet(&v, now).unwrap();
+                }
+                delivered = true;
+            }
+            if !delivered {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn deliver(a: &mut SctpAssociation, b: &mut SctpAssociation, now: Instant) {
+        deliver_with(a, b, now, &mut |_| false);
+    }
+
+    /// Runs the timers too, until both sides are idle.
+    pub(crate) fn run(
+        a: &mut SctpAssociation,
+        b: &mut SctpAssociation,
+        mut now: Instant,
+    ) -> Instant {
+        for _ in 0..100 {
+            deliver(a, b, now);
+            let next = [a.poll_timeout(), b.poll_timeout()]
+                .iter()
+                .flatten()
+                .min()
+                .copied();
+            match next {
+                Some(v) => {
+                    now = now.max(v);
+                    a.process(now);
+                    b.process(now);
+                }
+                None => break,
+            }
+        }
+        now
+    }
+
+    pub(crate) fn connect(now: Instant) -> (SctpAssociation, SctpAssociation) {
+        let (mut a, mut b) = new_pair(now);
+        a.connect(now);
+        deliver(&mut a, &mut b, now);
+        assert_eq!(a.get_state(), SctpState::Established);
+        assert_eq!(b.get_state(), SctpState::Established);
+        (a, b)
+    }
+
+    fn message(stream_id: u16, data: &[u8], unordered: bool) -> SctpMessage {
+        SctpMessage {
+            stream_id,
+            ppid: 51,
+            data: data.to_vec(),
+            unordered,
+        }
+    }
+
+    fn iter_messages(association: &mut SctpAssociation) -> Vec<SctpMessage> {
+        std::iter::from_fn(|| association.poll_message()).collect()
+    }
+
+    fn iter_events(association: &mut SctpAssociation) -> Vec<SctpEvent> {
+        std::iter::from_fn(|| association.poll_event()).collect()
+    }
+
+    fn is_data(packet: &[u8]) -> bool {
+        Packet::from_bytes(packet)
+            .unwrap()
+            .chunks
+            .iter()
+            .any(|v| matches!(v, Chunk::Data(_) | Chunk::IData(_)))
+    }
+
+    #[test]
+    fn handshake_test() {
+        let now = Instant::now();
+        let mut a = SctpAssociation::new(
+            SctpConfig {
+                outbound_streams: 16,
+                ..SctpConfig::default()
+            },
+            now,
+        );
+        let mut b = SctpAssociation::new(
+            SctpConfig {
+                inbound_streams: 8,
+                ..SctpConfig::default()
+            },
+            now,
+        );
+        a.connect(now);
+        deliver(&mut a, &mut b, now);
+        assert_eq!(
+            iter_events(&mut a),
+            vec![
+                SctpEvent::StateChanged(SctpState::CookieWait),
+                SctpEvent::StateChanged(SctpState::CookieEchoed),
+                SctpEvent::StateChanged(SctpState::Established),
+            ]
+        );
+        assert_eq!(
+            iter_events(&mut b),
+            vec![SctpEvent::StateChanged(SctpState::Established)]
+        );
+        assert_eq!(a.get_outbound_streams(), 8);
+        assert_eq!(b.get_inbound_streams(), 8);
+        assert_eq!(b.get_outbound_streams(), u16::MAX);
+        assert_eq!(a.poll_timeout(), None);
+
+        // a packet with a tag of another association.
+        let mut other = SctpAssociation::new(SctpConfig::default(), now);
+        other.connect(now);
+        let mut c = SctpAssociation::new(SctpConfig::default(), now);
+        c.handle_packet(&other.poll_transmit().unwrap(), now)
+            .unwrap();
+        let init_ack = c.poll_transmit().unwrap();
+        assert!(c.poll_transmit().is_none());
+        assert_eq!(c.get_state(), SctpState::Closed);
+        assert_eq!(
+            a.handle_packet(&init_ack, now),
+            Err(SctpError::InvalidVerificationTag)
+        );
+    }
+
+    #[test]
+    fn init_collision_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = new_pair(now);
+        a.connect(now);
+        b.connect(now);
+        deliver(&mut a, &mut b, now);
+        assert_eq!(a.get_state(), SctpState::Established);
+        assert_eq!(b.get_state(), SctpState::Established);
+
+        a.send(message(0, b"hello", false), now).unwrap();
+        deliver(&mut a, &mut b, now);
+        assert_eq!(iter_messages(&mut b), vec![message(0, b"hello", false)]);
+    }
+
+    #[test]
+    fn handshake_retransmit_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = new_pair(now);
+        a.connect(now);
+        // the INIT and then the COOKIE ECHO are lost once.
+        a.poll_transmit().unwrap();
+        let later = now + Duration::from_secs(1);
+        assert_eq!(a.poll_timeout(), Some(later));
+        a.process(later);
+        b.handle_packet(&a.poll_transmit().unwrap(), later).unwrap();
+        a.handle_packet(&b.poll_transmit().unwrap(), later).unwrap();
+        a.poll_transmit().unwrap();
+        let later = later + Duration::from_secs(4);
+        a.process(later);
+        deliver(&mut a, &mut b, later);
+        assert_eq!(a.get_state(), SctpState::Established);
+
+        // a cookie too old is refused.
+        let (mut a, mut b) = new_pair(now);
+        a.connect(now);
+        b.handle_packet(&a.poll_transmit().unwrap(), now).unwrap();
+        a.handle_packet(&b.poll_transmit().unwrap(), now).unwrap();
+        let stale = now + Duration::from_secs(61);
+        assert_eq!(
+            b.handle_packet(&a.poll_transmit().unwrap(), stale),
+            Err(SctpError::InvalidCookie)
+        );
+        assert_eq!(b.get_state(), SctpState::Closed);
+
+        // gives up after max_init_retransmits.
+        let (mut a, _) = new_pair(now);
+        a.connect(now);
+        while let Some(timeout) = a.poll_timeout() {
+            a.process(timeout);
+        }
+        assert_eq!(a.get_state(), SctpState::Closed);
+        assert_eq!(
+            std::iter::from_fn(|| a.poll_transmit()).count(),
+            1 + a.get_config().max_init_retransmits as usize
+        );
+    }
+
+    #[test]
+    fn data_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        a.send(message(1, b"one", false), now).unwrap();
+        a.send(message(2, b"two", true), now).unwrap();
+        let large: Vec<u8> = (0..10_000).map(|v| v as u8).collect();
+        a.send(message(1, &large, false), now).unwrap();
+        a.send(message(3, b"three", false), now).unwrap();
+        let now = run(&mut a, &mut b, now);
+        assert_eq!(
+            iter_messages(&mut b),
+            vec![
+                message(1, b"one", false),
+                message(2, b"two", true),
+                message(1, &large, false),
+                message(3, b"three", false),
+            ]
+        );
+        assert_eq!(a.get_flight_size(), 0);
+        assert_eq!(a.poll_timeout(), None);
+        assert_eq!(b.poll_timeout(), None);
+
+        b.send(message(0, b"back", false), now).unwrap();
+        run(&mut a, &mut b, now);
+        assert_eq!(iter_messages(&mut a), vec![message(0, b"back", false)]);
+
+        assert_eq!(
+            a.send(message(0, b"", false), now),
+            Err(SctpError::EmptyMessage)
+        );
+        assert_eq!(
+            a.send(message(0, &vec![0; 256 * 1024 + 1], false), now),
+            Err(SctpError::MessageTooLarge {
+                size: 256 * 1024 + 1
+            })
+        );
+        let (mut c, _) = new_pair(now);
+        assert_eq!(
+            c.send(message(0, b"x", false), now),
+            Err(SctpError::NotConnected)
+        );
+    }
+
+    #[test]
+    fn stream_test() {
+        let now = Instant::now();
+        let mut a = SctpAssociation::new(SctpConfig::default(), now);
+        let mut b = SctpAssociation::new(
+            SctpConfig {
+                inbound_streams: 2,
+                ..SctpConfig::default()
+            },
+            now,
+        );
+        a.connect(now);
+        deliver(&mut a, &mut b, now);
+        assert_eq!(
+            a.send(message(2, b"x", false), now),
+            Err(SctpError::InvalidStream { stream_id: 2 })
+        );
+
+        // ordered messages wait for the one before on the same stream only.
+        a.send(message(0, b"first", false), now).unwrap();
+        a.send(message(0, b"second", false), now).unwrap();
+        a.send(message(1, b"other", false), now).unwrap();
+        a.send(message(0, b"unordered", true), now).unwrap();
+        let mut dropped = false;
+        let mut drop_first = |packet: &[u8]| {
+            let drop = !dropped && is_data(packet);
+            dropped |= drop;
+            drop
+        };
+        deliver_with(&mut a, &mut b, now, &mut drop_first);
+        assert_eq!(
+            iter_messages(&mut b),
+            vec![
+                message(1, b"other", false),
+                message(0, b"unordered", true),
+                message(0, b"first", false),
+                message(0, b"second", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn reorder_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        for data in &[&b"a"[..], b"b", b"c"] {
+            a.send(message(0, data, false), now).unwrap();
+        }
+        a.send(message(1, b"u", true), now).unwrap();
+        let packets: Vec<Vec<u8>> = std::iter::from_fn(|| a.poll_transmit()).collect();
+        assert_eq!(packets.len(), 4);
+        for i in &[3, 2, 1, 0] {
+            b.handle_packet(&packets[*i], now).unwrap();
+        }
+        assert_eq!(
+            iter_messages(&mut b),
+            vec![
+                message(1, b"u", true),
+                message(0, b"a", false),
+                message(0, b"b", false),
+                message(0, b"c", false),
+            ]
+        );
+        // a gap is acked at once, with the duplicates.
+        b.handle_packet(&packets[0], now).unwrap();
+        let sacks: Vec<Chunk> = std::iter::from_fn(|| b.poll_transmit())
+            .flat_map(|v| Packet::from_bytes(&v).unwrap().chunks)
+            .collect();
+        assert!(matches!(
+            &sacks[0],
+            Chunk::Sack(SackChunk { gap_blocks, .. }) if gap_blocks == &[(4, 4)]
+        ));
+        assert!(matches!(
+            &sacks[2],
+            Chunk::Sack(SackChunk { gap_blocks, .. }) if gap_blocks == &[(2, 4)]
+        ));
+        assert!(matches!(
+            sacks.last(),
+            Some(Chunk::Sack(SackChunk { gap_blocks, duplicate_tsns, .. }))
+                if gap_blocks.is_empty() && duplicate_tsns.len() == 1
+        ));
+    }
+
+    #[test]
+    fn delayed_ack_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        a.send(message(0, b"one", false), now).unwrap();
+        b.handle_packet(&a.poll_transmit().unwrap(), now).unwrap();
+        assert!(b.poll_transmit().is_none());
+        assert_eq!(b.poll_timeout(), Some(now + Duration::from_millis(200)));
+
+        // the second packet is acked at once.
+        a.send(message(0, b"two", false), now).unwrap();
+        b.handle_packet(&a.poll_transmit().unwrap(), now).unwrap();
+        a.handle_packet(&b.poll_transmit().unwrap(), now).unwrap();
+        assert_eq!(a.get_flight_size(), 0);
+        assert_eq!(b.poll_timeout(), None);
+
+        a.send(message(0, b"three", false), now).unwrap();
+        b.handle_packet(&a.poll_transmit().unwrap(), now).unwrap();
+        let later = now + Duration::from_millis(200);
+        b.process(later);
+        a.handle_packet(&b.poll_transmit().unwrap(), later).unwrap();
+        assert_eq!(a.get_flight_size(), 0);
+        assert_eq!(a.poll_timeout(), None);
+    }
+
+    #[test]
+    fn fast_retransmit_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        let cwnd = a.get_cwnd();
+        for i in 0..5u8 {
+            a.send(message(0, &[i; 100], false), now).unwrap();
+        }
+        let packets: Vec<Vec<u8>> = std::iter::from_fn(|| a.poll_transmit()).collect();
+        assert_eq!(packets.len(), 5);
+        for packet in &packets[1..] {
+            b.handle_packet(packet, now).unwrap();
+            while let Some(sack) = b.poll_transmit() {
+                a.handle_packet(&sack, now).unwrap();
+            }
+        }
+        // the third miss report sends it again, long before T3-rtx.
+        let retransmit = a.poll_transmit().unwrap();
+        assert_eq!(
+            Packet::from_bytes(&retransmit),
+            Packet::from_bytes(&packets[0])
+        );
+        assert!(cwnd / 2 < 4 * 1200);
+        assert_eq!(a.get_ssthresh(), 4 * 1200);
+        assert_eq!(a.get_cwnd(), 4 * 1200);
+        b.handle_packet(&retransmit, now).unwrap();
+        run(&mut a, &mut b, now);
+        assert_eq!(iter_messages(&mut b).len(), 5);
+        assert_eq!(a.get_flight_size(), 0);
+    }
+
+    #[test]
+    fn retransmit_timeout_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        a.send(message(0, b"lost", false), now).unwrap();
+        a.poll_transmit().unwrap();
+        let timeout = a.poll_timeout().unwrap();
+        assert_eq!(timeout, now + Duration::from_secs(1));
+        a.process(timeout);
+        assert_eq!(a.get_cwnd(), 1200);
+        assert_eq!(a.get_rto(), Duration::from_secs(2));
+        deliver(&mut a, &mut b, timeout);
+        assert_eq!(iter_messages(&mut b), vec![message(0, b"lost", false)]);
+        let now = run(&mut a, &mut b, timeout);
+        assert_eq!(a.get_flight_size(), 0);
+
+        // the peer gone, the association aborts after max_retransmits.
+        a.send(message(0, b"nobody", false), now).unwrap();
+        while let Some(timeout) = a.poll_timeout() {
+            a.process(timeout);
+            while a.poll_transmit().is_some() {}
+        }
+        assert_eq!(a.get_state(), SctpState::Closed);
+    }
+
+    #[test]
+    fn congestion_control_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        let initial = a.get_cwnd();
+        let large = vec![7; 100_000];
+        a.send(message(0, &large, false), now).unwrap();
+        // only cwnd is sent before a SACK.
+        let first: Vec<Vec<u8>> = std::iter::from_fn(|| a.poll_transmit()).collect();
+        assert!(first.len() <= initial / 1000 + 1);
+        for packet in &first {
+            b.handle_packet(packet, now).unwrap();
+        }
+        let now = run(&mut a, &mut b, now);
+        assert!(a.get_cwnd() > initial);
+        assert_eq!(iter_messages(&mut b), vec![message(0, &large, false)]);
+
+        // the window of the receiver limits the sender.
+        let config = SctpConfig {
+            receive_window: 4000,
+            ..SctpConfig::default()
+        };
+        let mut a = SctpAssociation::new(SctpConfig::default(), now);
+        let mut b = SctpAssociation::new(config, now);
+        a.connect(now);
+        deliver(&mut a, &mut b, now);
+        for _ in 0..4 {
+            a.send(message(0, &[1; 1000], false), now).unwrap();
+        }
+        a.send(message(0, &[2; 1000], false), now).unwrap();
+        // the last one probes the closed window, which does not abort.
+        let now = run(&mut a, &mut b, now);
+        assert_eq!(a.get_state(), SctpState::Established);
+        assert_eq!(b.buffered, 4000);
+        assert_eq!(b.poll_message().unwrap().data, vec![1; 1000]);
+        b.poll_message().unwrap();
+        run(&mut a, &mut b, now);
+        assert_eq!(iter_messages(&mut b).len(), 3);
+        assert_eq!(a.get_flight_size(), 0);
+    }
+
+    #[test]
+    fn shutdown_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        a.send(message(0, b"bye", false), now).unwrap();
+        a.shutdown(now);
+        assert_eq!(a.get_state(), SctpState::ShutdownPending);
+        assert_eq!(
+            a.send(message(0, b"x", false), now),
+            Err(SctpError::NotConnected)
+        );
+        run(&mut a, &mut b, now);
+        assert_eq!(iter_messages(&mut b), vec![message(0, b"bye", false)]);
+        assert_eq!(a.get_state(), SctpState::Closed);
+        assert_eq!(b.get_state(), SctpState::Closed);
+        assert_eq!(
+            iter_events(&mut b),
+            vec![
+                SctpEvent::StateChanged(SctpState::Established),
+                SctpEvent::StateChanged(SctpState::ShutdownReceived),
+                SctpEvent::StateChanged(SctpState::ShutdownAckSent),
+                SctpEvent::StateChanged(SctpState::Closed),
+            ]
+        );
+
+        let (mut a, mut b) = connect(now);
+        a.abort();
+        deliver(&mut a, &mut b, now);
+        assert_eq!(b.get_state(), SctpState::Closed);
+    }
+
+    #[test]
+    fn reset_streams_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        iter_events(&mut a);
+        iter_events(&mut b);
+        a.send(message(1, b"before", false), now).unwrap();
+        a.reset_streams(&[1], now).unwrap();
+        // held until the reset is done, and then SSN 0 again.
+        a.send(message(1, b"after", false), now).unwrap();
+        assert_eq!(a.get_buffered_amount(1), 5);
+        let mut dropped = false;
+        let mut drop_first = |packet: &[u8]| {
+            let drop = !dropped && is_data(packet);
+            dropped |= drop;
+            drop
+        };
+        // the request waits for the lost TSN before it.
+        deliver_with(&mut a, &mut b, now, &mut drop_first);
+        assert_eq!(iter_events(&mut b), vec![]);
+        run(&mut a, &mut b, now);
+        assert_eq!(
+            iter_messages(&mut b),
+            vec![message(1, b"before", false), message(1, b"after", false)]
+        );
+        assert_eq!(
+            iter_events(&mut b),
+            vec![SctpEvent::IncomingStreamsReset(vec![1])]
+        );
+        assert_eq!(
+            iter_events(&mut a),
+            vec![SctpEvent::OutgoingStreamsReset(vec![1])]
+        );
+        assert_eq!(a.get_buffered_amount(1), 0);
+
+        let (mut c, _) = new_pair(now);
+        assert_eq!(c.reset_streams(&[1], now), Err(SctpError::NotConnected));
+    }
+
+    #[test]
+    fn partial_reliability_test() {
+        let now = Instant::now();
+        let (mut a, mut b) = connect(now);
+        assert!(a.is_partial_reliability_supported());
+        a.set_reliability(1, SctpReliability::MaxRetransmits(0));
+        a.send(message(1, b"lost", false), now).unwrap();
+        a.send(message(1, b"next", false), now).unwrap();
+        a.send(message(2, b"reliable", false), now).unwrap();
+        let mut dropped = false;
+        let mut drop_first = |packet: &[u8]| {
+            let drop = !dropped && is_data(packet);
+            dropped |= drop;
+            drop
+        };
+        deliver_with(&mut a, &mut b, now, &mut drop_first);
+        assert_eq!(iter_messages(&mut b), vec![message(2, b"reliable", false)]);
+
+        // abandoned instead of sent again, the next one is not blocked.
+        let timeout = a.poll_timeout().unwrap();
+        a.process(timeout);
+        let packets: Vec<Vec<u8>> = std::iter::from_fn(|| a.poll_transmit()).collect();
+        let chunks: Vec<Chunk> = packets
+            .iter()
+            .flat_map(|v| Packet::from_bytes(v).unwrap().chunks)
+            .collect();
+        assert!(!chunks.iter().any(|v| matches!(v, Chunk::Data(_))));
+        assert!(chunks
+            .iter()
+            .any(|v| matches!(v, Chunk::ForwardTsn { streams, .. } if streams == &[(1, 0)])));
+        for packet in &packets {
+            b.handle_packet(packet, timeout).unwrap();
+        }
+        let now = run(&mut a, &mut b, timeout);
+        assert_eq!(iter_messages(&mut b), vec![message(1, b"next", false)]);
+        assert_eq!(a.get_flight_size(), 0);
+        assert!(a.sent.is_empty());
+
+        // the fragments in flight and those never sent expire together.
+        a.set_reliability(3, SctpReliability::MaxLifetime(Duration::from_millis(100)));
+        a.send(message(3, &vec![5; 100_000], false), now).unwrap();
+        while a.poll_transmit().is_some() {}
+        assert!(!a.outgoing.is_empty());
+        let timeout = a.poll_timeout().unwrap();
+        a.process(timeout);
+        assert!(a.outgoing.is_empty());
+        assert_eq!(a.get_buffered_amount(3), 0);
+        a.set_reliability(3, SctpReliability::Reliable);
+        a.send(message(3, b"fresh", false), timeout).unwrap();
+        run(&mut a, &mut b, timeout);
+        assert_eq!(iter_messages(&mut b), vec![message(3, b"fresh", false)]);
+        assert!(b.reassembly.is_empty());
+        assert_eq!(b.buffered, 0);
+        assert!(a.sent.is_empty());
+
+        // reliable until the peer offers the extension.
+        let (mut c, _) = new_pair(now);
+        c.connect(now);
+        c.set_reliability(1, SctpReliability::MaxRetransmits(0));
+        c.send(message(1, b"x", false), now).unwrap();
+        assert!(c.outgoing[0].limit.is_none());
+    }
+
+    #[test]
+    fn interleaving_test() {
+        let now = Instant::now();
+        let config = SctpConfig {
+            interleaving: true,
+            ..SctpConfig::default()
+        };
+        let mut a = SctpAssociation::new(config.clone(), now);
+        let mut b = SctpAssociation::new(config.clone(), now);
+        a.connect(now);
+        deliver(&mut a, &mut b, now);
+        assert!(a.is_interleaving_supported());
+        assert!(b.is_interleaving_supported());
+
+        // the small message does not wait for the large one.
+        let large: Vec<u8> = (0..10_000).map(|v| v as u8).collect();
+        a.send(message(1, &large, false), now).unwrap();
+        a.send(message(2, b"urgent", false), now).unwrap();
+        let packet = Packet::from_bytes(&a.transmits[0]).unwrap();
+        assert!(matches!(&packet.chunks[0], Chunk::IData(v) if v.beginning && v.mid == 0));
+        let now = run(&mut a, &mut b, now);
+        assert_eq!(
+            iter_messages(&mut b),
+            vec![message(2, b"urgent", false), message(1, &large, false)]
+        );
+
+        // a message abandoned is skipped by its MID.
+        a.set_reliability(3, SctpReliability::MaxRetransmits(0));
+        a.send(message(3, &large, false), now).unwrap();
+        a.send(message(3, b"after", false), now).unwrap();
+        let mut dropped = false;
+        let mut drop_first = |packet: &[u8]| {
+            let drop = !dropped && is_data(packet);
+            dropped |= drop;
+            drop
+        };
+        deliver_with(&mut a, &mut b, now, &mut drop_first);
+        run(&mut a, &mut b, now);
+        assert_eq!(iter_messages(&mut b), vec![message(3, b"after", false)]);
+        assert!(b.fragments.is_empty());
+        assert_eq!(b.buffered, 0);
+        assert!(a.sent.is_empty());
+
+        // DATA is a protocol violation once I-DATA is negotiated.
+        let data = Packet {
+            source_port: DEFAULT_SCTP_PORT,
+            destination_port: DEFAULT_SCTP_PORT,
+            verification_tag: b.local_tag,
+            chunks: vec![Chunk::Data(DataChunk {
+                tsn: b.peer_cumulative_tsn as u32 + 1,
+                stream_id: 0,
+                ssn: 0,
+                mid: 0,
+                fsn: 0,
+                ppid: 51,
+                unordered: false,
+                beginning: true,
+                ending: true,
+                immediate: false,
+                user_data: vec![1],
+            })],
+        };
+        assert_eq!(
+            b.handle_packet(&data.to_bytes(), now),
+            Err(SctpError::InvalidChunk)
+        );
+        assert_eq!(b.get_state(), SctpState::Closed);
+
+        // both sides have to offer it.
+        let mut c = SctpAssociation::new(config, now);
+        let mut d = SctpAssociation::new(SctpConfig::default(), now);
+        c.connect(now);
+        deliver(&mut c, &mut d, now);
+        assert!(!c.is_interleaving_supported());
+        c.send(message(0, b"data", false), now).unwrap();
+        assert!(is_data(&c.transmits[0]));
+        deliver(&mut c, &mut d, now);
+        assert_eq!(iter_messages(&mut d), vec![message(0, b"data", false)]);
+    }
+
+    #[test]
+    fn unknown_chunk_test() {
+        let now = Instant::now();
+        let (_, mut b) = connect(now);
+        let tag = b.local_tag;
+        let packet = |chunks| {
+            Packet {
+                source_port: DEFAULT_SCTP_PORT,
+                destination_port: DEFAULT_SCTP_PORT,
+                verification_tag: tag,
+                chunks,
+            }
+            .to_bytes()
+        };
+        let unknown = |chunk_type| Chunk::Unknown {
+            chunk_type,
+            flags: 0,
+            value: vec![1, 2, 3, 4],
+        };
+        let heartbeat = Chunk::Heartbeat(vec![0, 1, 0, 5, 9]);
+        // skipped and reported, then the heartbeat is answered.
+        let report = packet(vec![unknown(0xfd), heartbeat.clone()]);
+        // processing stops, silently.
+        let stop = packet(vec![unknown(0x3f), heartbeat]);
+        b.handle_packet(&report, now).unwrap();
+        b.handle_packet(&stop, now).unwrap();
+        let chunks = Packet::from_bytes(&b.poll_transmit().unwrap())
+            .unwrap()
+            .chunks;
+        assert!(b.poll_transmit().is_none());
+        assert!(matches!(&chunks[0], Chunk::Error(cause) if cause[..2] == [0, 6]));
+        assert_eq!(chunks[1], Chunk::HeartbeatAck(vec![0, 1, 0, 5, 9]));
+        assert_eq!(chunks.len(), 2);
+
+        let mut bytes = packet(vec![Chunk::CookieAck]);
+        bytes[12] ^= 1;
+        assert_eq!(
+            b.handle_packet(&bytes, now),
+            Err(SctpError::InvalidChecksum)
+        );
+    }
+
+    #[test]
+    fn sctp_over_dtls_test() {
+        let now = Instant::now();
+        let (mut client, mut server) = new_transports(now);
+        let (mut a, mut b) = new_pair(now);
+        // queued until DTLS connects.
+        a.connect(now);
+        a.send(message(0, b"over dtls", false), now).unwrap();
+        a.flush(&mut client);
+        assert!(a.transmits.len() == 1);
+
+        for _ in 0..10 {
+            while let Some(v) = client.poll_transmit() {
+                server.handle_packet(&v, now).unwrap();
+            }
+            while let Some(v) = server.poll_transmit() {
+                client.handle_packet(&v, now).unwrap();
+            }
+            while let Some(v) = client.poll_data() {
+                a.handle_packet(&v, now).unwrap();
+            }
+            while let Some(v) = server.poll_data() {
+                b.handle_packet(&v, now).unwrap();
+            }
+            a.flush(&mut client);
+            b.flush(&mut server);
+        }
+        assert_eq!(client.get_state(), DtlsState::Connected);
+        assert_eq!(a.get_state(), SctpState::Established);
+        assert_eq!(iter_messages(&mut b), vec![message(0, b"over dtls", false)]);
+    }
+}
@@ -0,0 +1,778 @@
+// https://tools.ietf.org/html/rfc9260#section-3.2
+// https://tools.ietf.org/html/rfc6525#section-3.1
+// https://tools.ietf.org/html/rfc3758#section-3.2
+// https://tools.ietf.org/html/rfc8260#section-2
+
+/*
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |   Chunk Type  | Chunk  Flags  |        Chunk Length           |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    \                                                               \
+    /                          Chunk Value                          /
+    \                                                               \
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    The length does not count the padding to 4 bytes. Parameters of INIT
+    and INIT ACK have the same layout with a 2 bytes type.
+
+    the upper two bits of an unknown chunk type
+      00  stop processing the packet
+      01  stop, and report it in an ERROR
+      10  skip it
+      11  skip it, and report it in an ERROR
+
+    RE-CONFIG (130) carries parameters of the same layout, of which data
+    channels use the Outgoing SSN Reset Request (13) and the Response (16).
+
+    FORWARD TSN (192)
+      New Cumulative TSN(4) | (Stream(2) | Stream Sequence(2))*
+    offered with the Forward-TSN-Supported parameter (0xC000) of INIT.
+
+    I-DATA (64), when both sides list it in the Supported Extensions
+      TSN(4) | Stream(2) | Reserved(2) | MID(4) | PPID or FSN(4) | data
+    the PPID in the first fragment, the FSN in the others.
+
+    I-FORWARD TSN (194)
+      New Cumulative TSN(4) | (Stream(2) | Reserved(15) U(1) | MID(4))*
+*/
+
+use crate::sctp::{Result, SctpError};
+
+pub const CHUNK_HEADER_LENGTH: usize = 4;
+pub const DATA_CHUNK_HEADER_LENGTH: usize = 16;
+pub const I_DATA_CHUNK_HEADER_LENGTH: usize = 20;
+
+pub const CHUNK_DATA: u8 = 0;
+pub const CHUNK_INIT: u8 = 1;
+pub const CHUNK_INIT_ACK: u8 = 2;
+pub const CHUNK_SACK: u8 = 3;
+pub const CHUNK_HEARTBEAT: u8 = 4;
+pub const CHUNK_HEARTBEAT_ACK: u8 = 5;
+pub const CHUNK_ABORT: u8 = 6;
+pub const CHUNK_SHUTDOWN: u8 = 7;
+pub const CHUNK_SHUTDOWN_ACK: u8 = 8;
+pub const CHUNK_ERROR: u8 = 9;
+pub const CHUNK_COOKIE_ECHO: u8 = 10;
+pub const CHUNK_COOKIE_ACK: u8 = 11;
+pub const CHUNK_I_DATA: u8 = 64;
+pub const CHUNK_SHUTDOWN_COMPLETE: u8 = 14;
+pub const CHUNK_RECONFIG: u8 = 130;
+pub const CHUNK_FORWARD_TSN: u8 = 192;
+pub const CHUNK_I_FORWARD_TSN: u8 = 194;
+
+const PARAMETER_STATE_COOKIE: u16 = 7;
+const PARAMETER_SUPPORTED_EXTENSIONS: u16 = 0x8008;
+const PARAMETER_FORWARD_TSN_SUPPORTED: u16 = 0xc000;
+const PARAMETER_OUTGOING_RESET_REQUEST: u16 = 13;
+const PARAMETER_RECONFIG_RESPONSE: u16 = 16;
+
+const FLAG_DATA_END: u8 = 0x01;
+const FLAG_DATA_BEGINNING: u8 = 0x02;
+const FLAG_DATA_UNORDERED: u8 = 0x04;
+const FLAG_DATA_IMMEDIATE: u8 = 0x08;
+const FLAG_T: u8 = 0x01;
+const FLAG_I_FORWARD_TSN_UNORDERED: u16 = 0x0001;
+
+pub fn get_padded_length(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+fn get_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|v| u16::from_be_bytes([v[0], v[1]]))
+        .ok_or(SctpError::InvalidChunk)
+}
+
+fn get_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+        .ok_or(SctpError::InvalidChunk)
+}
+
+/// Splits TLVs, the chunks of a packet or the parameters of an INIT.
+/// Returns `(type and flags, value)`.
+fn split_tlvs(mut bytes: &[u8]) -> Result<Vec<(u16, &[u8])>> {
+    let mut out = Vec::new();
+    while !bytes.is_empty() {
+        let kind = get_u16(bytes, 0)?;
+        let length = usize::from(get_u16(bytes, 2)?);
+        if length < CHUNK_HEADER_LENGTH || length > bytes.len() {
+            return Err(SctpError::InvalidChunk);
+        }
+        out.push((kind, &bytes[CHUNK_HEADER_LENGTH..length]));
+        // the last one may come without its padding.
+        bytes = &bytes[get_padded_length(length).min(bytes.len())..];
+    }
+    Ok(out)
+}
+
+fn put_tlv(out: &mut Vec<u8>, kind: u16, value: &[u8]) {
+    out.extend_from_slice(&kind.to_be_bytes());
+    out.extend_from_slice(&((CHUNK_HEADER_LENGTH + value.len()) as u16).to_be_bytes());
+    out.extend_from_slice(value);
+    out.resize(get_padded_length(out.len()), 0);
+}
+
+// DATAとI-DATAの中身．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DataChunk {
+    pub tsn: u32,
+    pub stream_id: u16,
+    /// Only in a DATA.
+    pub ssn: u16,
+    /// Only in an I-DATA, as is `fsn`.
+    pub mid: u32,
+    pub fsn: u32,
+    /// 0 in the fragments of an I-DATA but the first.
+    pub ppid: u32,
+    pub unordered: bool,
+    pub beginning: bool,
+    pub ending: bool,
+    /// The receiver should SACK it right away (RFC 7053).
+    pub immediate: bool,
+    pub user_data: Vec<u8>,
+}
+
+// INITとINIT ACKの中身．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InitChunk {
+    pub initiate_tag: u32,
+    pub a_rwnd: u32,
+    pub outbound_streams: u16,
+    pub inbound_streams: u16,
+    pub initial_tsn: u32,
+    /// Only in an INIT ACK.
+    pub state_cookie: Option<Vec<u8>>,
+    /// The chunk types of the extensions the sender supports (RFC 5061).
+    pub supported_extensions: Vec<u8>,
+    /// PR-SCTP (RFC 3758).
+    pub forward_tsn_supported: bool,
+}
+
+impl InitChunk {
+    fn to_value(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(16);
+        out.extend_from_slice(&self.initiate_tag.to_be_bytes());
+        out.extend_from_slice(&self.a_rwnd.to_be_bytes());
+        out.extend_from_slice(&self.outbound_streams.to_be_bytes());
+        out.extend_from_slice(&self.inbound_streams.to_be_bytes());
+        out.extend_from_slice(&self.initial_tsn.to_be_bytes());
+        if let Some(cookie) = &self.state_cookie {
+            put_tlv(&mut out, PARAMETER_STATE_COOKIE, cookie);
+        }
+        if !self.supported_extensions.is_empty() {
+            put_tlv(
+                &mut out,
+                PARAMETER_SUPPORTED_EXTENSIONS,
+                &self.supported_extensions,
+            );
+        }
+        if self.forward_tsn_supported {
+            put_tlv(&mut out, PARAMETER_FORWARD_TSN_SUPPORTED, &[]);
+        }
+        out
+    }
+
+    fn from_value(value: &[u8]) -> Result<Self> {
+        let mut init = InitChunk {
+            initiate_tag: get_u32(value, 0)?,
+            a_rwnd: get_u32(value, 4)?,
+            outbound_streams: get_u16(value, 8)?,
+            inbound_streams: get_u16(value, 10)?,
+            initial_tsn: get_u32(value, 12)?,
+            state_cookie: None,
+            supported_extensions: Vec::new(),
+            forward_tsn_supported: false,
+        };
+        // a zero tag or no streams at all must be aborted (Section 3.3.2).
+        if init.initiate_tag == 0 || init.outbound_streams == 0 || init.inbound_streams == 0 {
+            return Err(SctpError::InvalidChunk);
+        }
+        for (kind, value) in split_tlvs(&value[16..])? {
+            match kind {
+                PARAMETER_STATE_COOKIE => init.state_cookie = Some(value.to_vec()),
+                PARAMETER_SUPPORTED_EXTENSIONS => init.supported_extensions = value.to_vec(),
+                PARAMETER_FORWARD_TSN_SUPPORTED => init.forward_tsn_supported = true,
+                // the others do not matter here, whatever the upper bits ask.
+                _ => {}
+            }
+        }
+        Ok(init)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SackChunk {
+    pub cumulative_tsn_ack: u32,
+    pub a_rwnd: u32,
+    /// Offsets from the cumulative TSN ack, both ends included.
+    pub gap_blocks: Vec<(u16, u16)>,
+    pub duplicate_tsns: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ReconfigParameter {
+    /// The sender resets the SSNs of `streams`, all of them when empty,
+    /// after `last_tsn`.
+    OutgoingResetRequest {
+        request_sequence: u32,
+        response_sequence: u32,
+        last_tsn: u32,
+        streams: Vec<u16>,
+    },
+    Response {
+        response_sequence: u32,
+        result: u32,
+    },
+    Unknown {
+        kind: u16,
+        value: Vec<u8>,
+    },
+}
+
+impl ReconfigParameter {
+    fn to_bytes(&self, out: &mut Vec<u8>) {
+        match self {
+            ReconfigParameter::OutgoingResetRequest {
+                request_sequence,
+                response_sequence,
+                last_tsn,
+                streams,
+            } => {
+                let mut value = Vec::with_capacity(12 + streams.len() * 2);
+                value.extend_from_slice(&request_sequence.to_be_bytes());
+                value.extend_from_slice(&response_sequence.to_be_bytes());
+                value.extend_from_slice(&last_tsn.to_be_bytes());
+                for stream in streams {
+                    value.extend_from_slice(&stream.to_be_bytes());
+                }
+                put_tlv(out, PARAMETER_OUTGOING_RESET_REQUEST, &value);
+            }
+            ReconfigParameter::Response {
+                response_sequence,
+                result,
+            } => {
+                let mut value = response_sequence.to_be_bytes().to_vec();
+                value.extend_from_slice(&result.to_be_bytes());
+                put_tlv(out, PARAMETER_RECONFIG_RESPONSE, &value);
+            }
+            ReconfigParameter::Unknown { kind, value } => put_tlv(out, *kind, value),
+        }
+    }
+
+    fn from_value(kind: u16, value: &[u8]) -> Result<Self> {
+        let parameter = match kind {
+            PARAMETER_OUTGOING_RESET_REQUEST => {
+                if value.len() < 12 || !value.len().is_multiple_of(2) {
+                    return Err(SctpError::InvalidChunk);
+                }
+                ReconfigParameter::OutgoingResetRequest {
+                    request_sequence: get_u32(value, 0)?,
+                    response_sequence: get_u32(value, 4)?,
+                    last_tsn: get_u32(value, 8)?,
+                    streams: value[12..]
+                        .chunks(2)
+                        .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                        .collect(),
+                }
+            }
+            PARAMETER_RECONFIG_RESPONSE => ReconfigParameter::Response {
+                response_sequence: get_u32(value, 0)?,
+                result: get_u32(value, 4)?,
+            },
+            _ => ReconfigParameter::Unknown {
+                kind,
+                value: value.to_vec(),
+            },
+        };
+        Ok(parameter)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Chunk {
+    Data(DataChunk),
+    Init(InitChunk),
+    InitAck(InitChunk),
+    Sack(SackChunk),
+    /// The Heartbeat Info parameter, echoed as it is.
+    Heartbeat(Vec<u8>),
+    HeartbeatAck(Vec<u8>),
+    /// `reflected` is the T bit: the tag is the one of the receiver.
+    Abort {
+        reflected: bool,
+        causes: Vec<u8>,
+    },
+    Shutdown {
+        cumulative_tsn_ack: u32,
+    },
+    ShutdownAck,
+    Error(Vec<u8>),
+    CookieEcho(Vec<u8>),
+    CookieAck,
+    ShutdownComplete {
+        reflected: bool,
+    },
+    Reconfig(Vec<ReconfigParameter>),
+    IData(DataChunk),
+    /// The receiver moves its cumulative TSN to `new_cumulative_tsn`, and
+    /// the SSN of each `(stream, ssn)` past `ssn`.
+    ForwardTsn {
+        new_cumulative_tsn: u32,
+        streams: Vec<(u16, u16)>,
+    },
+    /// The same with `(stream, unordered, mid)`.
+    IForwardTsn {
+        new_cumulative_tsn: u32,
+        streams: Vec<(u16, bool, u32)>,
+    },
+    Unknown {
+        chunk_type: u8,
+        flags: u8,
+        value: Vec<u8>,
+    },
+}
+
+impl Chunk {
+    pub fn get_type(&self) -> u8 {
+        match self {
+            Chunk::Data(_) => CHUNK_DATA,
+            Chunk::Init(_) => CHUNK_INIT,
+            Chunk::InitAck(_) => CHUNK_INIT_ACK,
+            Chunk::Sack(_) => CHUNK_SACK,
+            Chunk::Heartbeat(_) => CHUNK_HEARTBEAT,
+            Chunk::HeartbeatAck(_) => CHUNK_HEARTBEAT_ACK,
+            Chunk::Abort { .. } => CHUNK_ABORT,
+            Chunk::Shutdown { .. } => CHUNK_SHUTDOWN,
+            Chunk::ShutdownAck => CHUNK_SHUTDOWN_ACK,
+            Chunk::Error(_) => CHUNK_ERROR,
+            Chunk::CookieEcho(_) => CHUNK_COOKIE_ECHO,
+            Chunk::CookieAck => CHUNK_COOKIE_ACK,
+            Chunk::ShutdownComplete { .. } => CHUNK_SHUTDOWN_COMPLETE,
+            Chunk::Reconfig(_) => CHUNK_RECONFIG,
+            Chunk::ForwardTsn { .. } => CHUNK_FORWARD_TSN,
+            Chunk::IData(_) => CHUNK_I_DATA,
+            Chunk::IForwardTsn { .. } => CHUNK_I_FORWARD_TSN,
+            Chunk::Unknown { chunk_type, .. } => *chunk_type,
+        }
+    }
+
+    /// The length on the wire, with the padding.
+    pub fn get_length(&self) -> usize {
+        match self {
+            Chunk::Data(data) => get_padded_length(DATA_CHUNK_HEADER_LENGTH + data.user_data.len()),
+            Chunk::IData(data) => {
+                get_padded_length(I_DATA_CHUNK_HEADER_LENGTH + data.user_data.len())
+            }
+            _ => self.to_bytes().len(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (flags, value) = match self {
+            Chunk::Data(data) | Chunk::IData(data) => {
+                let mut flags = 0;
+                if data.ending {
+                    flags |= FLAG_DATA_END;
+                }
+                if data.beginning {
+                    flags |= FLAG_DATA_BEGINNING;
+                }
+                if data.unordered {
+                    flags |= FLAG_DATA_UNORDERED;
+                }
+                if data.immediate {
+                    flags |= FLAG_DATA_IMMEDIATE;
+                }
+                let mut value = Vec::with_capacity(16 + data.user_data.len());
+                value.extend_from_slice(&data.tsn.to_be_bytes());
+                value.extend_from_slice(&data.stream_id.to_be_bytes());
+                if let Chunk::IData(_) = self {
+                    value.extend_from_slice(&[0, 0]);
+                    value.extend_from_slice(&data.mid.to_be_bytes());
+                    let ppid_or_fsn = if data.beginning { data.ppid } else { data.fsn };
+                    value.extend_from_slice(&ppid_or_fsn.to_be_bytes());
+                } else {
+                    value.extend_from_slice(&data.ssn.to_be_bytes());
+                    value.extend_from_slice(&data.ppid.to_be_bytes());
+                }
+                value.extend_from_slice(&data.user_data);
+                (flags, value)
+            }
+            Chunk::Init(init) | Chunk::InitAck(init) => (0, init.to_value()),
+            Chunk::Sack(sack) => {
+                let mut value = Vec::with_capacity(
+                    12 + sack.gap_blocks.len() * 4 + sack.duplicate_tsns.len() * 4,
+                );
+                value.extend_from_slice(&sack.cumulative_tsn_ack.to_be_bytes());
+                value.extend_from_slice(&sack.a_rwnd.to_be_bytes());
+                value.extend_from_slice(&(sack.gap_blocks.len() as u16).to_be_bytes());
+                value.extend_from_slice(&(sack.duplicate_tsns.len() as u16).to_be_bytes());
+                for (start, end) in &sack.gap_blocks {
+                    value.extend_from_slice(&start.to_be_bytes());
+                    value.extend_from_slice(&end.to_be_bytes());
+                }
+                for tsn in &sack.duplicate_tsns {
+                    value.extend_from_slice(&tsn.to_be_bytes());
+                }
+                (0, value)
+            }
+            Chunk::Heartbeat(info) | Chunk::HeartbeatAck(info) => (0, info.clone()),
+            Chunk::Abort { reflected, causes } => {
+                (if *reflected { FLAG_T } else { 0 }, causes.clone())
+            }
+            Chunk::Shutdown { cumulative_tsn_ack } => {
+                (0, cumulative_tsn_ack.to_be_bytes().to_vec())
+            }
+            Chunk::ShutdownAck | Chunk::CookieAck => (0, Vec::new()),
+            Chunk::Error(causes) => (0, causes.clone()),
+            Chunk::CookieEcho(cookie) => (0, cookie.clone()),
+            Chunk::ShutdownComplete { reflected } => {
+                (if *reflected { FLAG_T } else { 0 }, Vec::new())
+            }
+            Chunk::Reconfig(parameters) => {
+                let mut value = Vec::new();
+                parameters.iter().for_each(|v| v.to_bytes(&mut value));
+                (0, value)
+            }
+            Chunk::ForwardTsn {
+                new_cumulative_tsn,
+                streams,
+            } => {
+                let mut value = Vec::with_capacity(4 + streams.len() * 4);
+                value.extend_from_slice(&new_cumulative_tsn.to_be_bytes());
+                for (stream, ssn) in streams {
+                    value.extend_from_slice(&stream.to_be_bytes());
+                    value.extend_from_slice(&ssn.to_be_bytes());
+                }
+                (0, value)
+            }
+            Chunk::IForwardTsn {
+                new_cumulative_tsn,
+                streams,
+            } => {
+                let mut value = Vec::with_capacity(4 + streams.len() * 8);
+                value.extend_from_slice(&new_cumulative_tsn.to_be_bytes());
+                for (stream, unordered, mid) in streams {
+                    let flags = if *unordered {
+                        FLAG_I_FORWARD_TSN_UNORDERED
+                    } else {
+                        0
+                    };
+                    value.extend_from_slice(&stream.to_be_bytes());
+                    value.extend_from_slice(&flags.to_be_bytes());
+                    value.extend_from_slice(&mid.to_be_bytes());
+                }
+                (0, value)
+            }
+            Chunk::Unknown { flags, value, .. } => (*flags, value.clone()),
+        };
+        let mut out = Vec::with_capacity(get_padded_length(CHUNK_HEADER_LENGTH + value.len()));
+        put_tlv(
+            &mut out,
+            u16::from_be_bytes([self.get_type(), flags]),
+            &value,
+        );
+        out
+    }
+
+    /// Parses the chunks of a packet.
+    pub fn parse_chunks(bytes: &[u8]) -> Result<Vec<Chunk>> {
+        split_tlvs(bytes)?
+            .into_iter()
+            .map(|(kind, value)| {
+                let [chunk_type, flags] = kind.to_be_bytes();
+                Chunk::from_value(chunk_type, flags, value)
+            })
+            .collect()
+    }
+
+    fn from_value(chunk_type: u8, flags: u8, value: &[u8]) -> Result<Chunk> {
+        let chunk = match chunk_type {
+            CHUNK_DATA => {
+                // no user data is a protocol violation (Section 6.2).
+                if value.len() <= DATA_CHUNK_HEADER_LENGTH - CHUNK_HEADER_LENGTH {
+                    return Err(SctpError::InvalidChunk);
+                }
+                Chunk::Data(DataChunk {
+                    tsn: get_u32(value, 0)?,
+                    stream_id: get_u16(value, 4)?,
+                    ssn: get_u16(value, 6)?,
+                    mid: 0,
+                    fsn: 0,
+                    ppid: get_u32(value, 8)?,
+                    unordered: flags & FLAG_DATA_UNORDERED != 0,
+                    beginning: flags & FLAG_DATA_BEGINNING != 0,
+                    ending: flags & FLAG_DATA_END != 0,
+                    immediate: flags & FLAG_DATA_IMMEDIATE != 0,
+                    user_data: value[12..].to_vec(),
+                })
+            }
+            CHUNK_I_DATA => {
+                if value.len() <= I_DATA_CHUNK_HEADER_LENGTH - CHUNK_HEADER_LENGTH {
+                    return Err(SctpError::InvalidChunk);
+                }
+                let beginning = flags & FLAG_DATA_BEGINNING != 0;
+                let ppid_or_fsn = get_u32(value, 12)?;
+                Chunk::IData(DataChunk {
+                    tsn: get_u32(value, 0)?,
+                    stream_id: get_u16(value, 4)?,
+                    ssn: 0,
+                    mid: get_u32(value, 8)?,
+                    fsn: if beginning { 0 } else { ppid_or_fsn },
+                    ppid: if beginning { ppid_or_fsn } else { 0 },
+                    unordered: flags & FLAG_DATA_UNORDERED != 0,
+                    beginning,
+                    ending: flags & FLAG_DATA_END != 0,
+                    immediate: flags & FLAG_DATA_IMMEDIATE != 0,
+                    user_data: value[16..].to_vec(),
+                })
+            }
+            CHUNK_INIT => Chunk::Init(InitChunk::from_value(value)?),
+            CHUNK_INIT_ACK => {
+                let init = InitChunk::from_value(value)?;
+                if init.state_cookie.is_none() {
+                    return Err(SctpError::InvalidChunk);
+                }
+                Chunk::InitAck(init)
+            }
+            CHUNK_SACK => {
+                let gaps = usize::from(get_u16(value, 8)?);
+                let duplicates = usize::from(get_u16(value, 10)?);
+                if value.len() != 12 + gaps * 4 + duplicates * 4 {
+                    return Err(SctpError::InvalidChunk);
+                }
+                let gap_blocks = (0..gaps)
+                    .map(|i| Ok((get_u16(value, 12 + i * 4)?, get_u16(value, 14 + i * 4)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                let offset = 12 + gaps * 4;
+                let duplicate_tsns = (0..duplicates)
+                    .map(|i| get_u32(value, offset + i * 4))
+                    .collect::<Result<Vec<_>>>()?;
+                Chunk::Sack(SackChunk {
+                    cumulative_tsn_ack: get_u32(value, 0)?,
+                    a_rwnd: get_u32(value, 4)?,
+                    gap_blocks,
+                    duplicate_tsns,
+                })
+            }
+            CHUNK_HEARTBEAT => Chunk::Heartbeat(value.to_vec()),
+            CHUNK_HEARTBEAT_ACK => Chunk::HeartbeatAck(value.to_vec()),
+            CHUNK_ABORT => Chunk::Abort {
+                reflected: flags & FLAG_T != 0,
+                causes: value.to_vec(),
+            },
+            CHUNK_SHUTDOWN => Chunk::Shutdown {
+                cumulative_tsn_ack: get_u32(value, 0)?,
+            },
+            CHUNK_SHUTDOWN_ACK => Chunk::ShutdownAck,
+            CHUNK_ERROR => Chunk::Error(value.to_vec()),
+            CHUNK_COOKIE_ECHO => Chunk::CookieEcho(value.to_vec()),
+            CHUNK_COOKIE_ACK => Chunk::CookieAck,
+            CHUNK_SHUTDOWN_COMPLETE => Chunk::ShutdownComplete {
+                reflected: flags & FLAG_T != 0,
+            },
+            CHUNK_RECONFIG => Chunk::Reconfig(
+                split_tlvs(value)?
+                    .into_iter()
+                    .map(|(kind, value)| ReconfigParameter::from_value(kind, value))
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            CHUNK_FORWARD_TSN => {
+                if value.len() < 4 || !(value.len() - 4).is_multiple_of(4) {
+                    return Err(SctpError::InvalidChunk);
+                }
+                let streams = (4..value.len())
+                    .step_by(4)
+                    .map(|i| Ok((get_u16(value, i)?, get_u16(value, i + 2)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                Chunk::ForwardTsn {
+                    new_cumulative_tsn: get_u32(value, 0)?,
+                    streams,
+                }
+            }
+            CHUNK_I_FORWARD_TSN => {
+                if value.len() < 4 || !(value.len() - 4).is_multiple_of(8) {
+                    return Err(SctpError::InvalidChunk);
+                }
+                let streams = (4..value.len())
+                    .step_by(8)
+                    .map(|i| {
+                        Ok((
+                            get_u16(value, i)?,
+                            get_u16(value, i + 2)? & FLAG_I_FORWARD_TSN_UNORDERED != 0,
+                            get_u32(value, i + 4)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Chunk::IForwardTsn {
+                    new_cumulative_tsn: get_u32(value, 0)?,
+                    streams,
+                }
+            }
+            _ => Chunk::Unknown {
+                chunk_type,
+                flags,
+                value: value.to_vec(),
+            },
+        };
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn data_chunk_test() {
+        let chunk = Chunk::Data(DataChunk {
+            tsn: 0x0102_0304,
+            stream_id: 1,
+            ssn: 2,
+            mid: 0,
+            fsn: 0,
+            ppid: 51,
+            unordered: true,
+            beginning: true,
+            ending: false,
+            immediate: false,
+            user_data: b"hello".to_vec(),
+        });
+        let bytes = chunk.to_bytes();
+        assert_eq!(
+            &bytes[..8],
+            &[CHUNK_DATA, 0x06, 0x00, 21, 0x01, 0x02, 0x03, 0x04]
+        );
+        assert_eq!(bytes.len(), 24);
+        assert_eq!(chunk.get_length(), 24);
+        assert_eq!(Chunk::parse_chunks(&bytes), Ok(vec![chunk.clone()]));
+        // the padding of the last chunk may be missing.
+        assert_eq!(Chunk::parse_chunks(&bytes[..21]), Ok(vec![chunk]));
+
+        // the FSN takes the place of the PPID after the first fragment.
+        let fragment = Chunk::IData(DataChunk {
+            tsn: 9,
+            stream_id: 1,
+            ssn: 0,
+            mid: 0x0a0b_0c0d,
+            fsn: 3,
+            ppid: 0,
+            unordered: false,
+            beginning: false,
+            ending: true,
+            immediate: false,
+            user_data: b"world".to_vec(),
+        });
+        let bytes = fragment.to_bytes();
+        assert_eq!(bytes[0], CHUNK_I_DATA);
+        assert_eq!(
+            &bytes[1..20],
+            &[0x01, 0, 25, 0, 0, 0, 9, 0, 1, 0, 0, 0x0a, 0x0b, 0x0c, 0x0d, 0, 0, 0, 3]
+        );
+        assert_eq!(fragment.get_length(), 28);
+        assert_eq!(Chunk::parse_chunks(&bytes), Ok(vec![fragment]));
+
+        let empty = [CHUNK_DATA, 0x03, 0, 16, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(Chunk::parse_chunks(&empty), Err(SctpError::InvalidChunk));
+        assert_eq!(
+            Chunk::parse_chunks(&[CHUNK_COOKIE_ACK, 0, 0, 3]),
+            Err(SctpError::InvalidChunk)
+        );
+        assert_eq!(
+            Chunk::parse_chunks(&[CHUNK_COOKIE_ACK, 0, 0, 8]),
+            Err(SctpError::InvalidChunk)
+        );
+    }
+
+    #[test]
+    fn control_chunk_test() {
+        let init = InitChunk {
+            initiate_tag: 0xdead_beef,
+            a_rwnd: 131_072,
+            outbound_streams: 1024,
+            inbound_streams: 65535,
+            initial_tsn: 7,
+            state_cookie: Some(vec![1, 2, 3, 4, 5]),
+            supported_extensions: vec![130, 192],
+            forward_tsn_supported: true,
+        };
+        let chunks = vec![
+            Chunk::InitAck(init.clone()),
+            Chunk::Init(InitChunk {
+                state_cookie: None,
+                ..init.clone()
+            }),
+            Chunk::Sack(SackChunk {
+                cumulative_tsn_ack: 10,
+                a_rwnd: 1000,
+                gap_blocks: vec![(2, 3), (5, 5)],
+                duplicate_tsns: vec![9],
+            }),
+            Chunk::Heartbeat(vec![0, 1, 0, 6, 9, 9]),
+            Chunk::Abort {
+                reflected: true,
+                causes: Vec::new(),
+            },
+            Chunk::Shutdown {
+                cumulative_tsn_ack: 3,
+            },
+            Chunk::ShutdownAck,
+            Chunk::CookieEcho(vec![1, 2, 3, 4, 5]),
+            Chunk::CookieAck,
+            Chunk::ShutdownComplete { reflected: false },
+            Chunk::Reconfig(vec![
+                ReconfigParameter::OutgoingResetRequest {
+                    request_sequence: 1,
+                    response_sequence: 2,
+                    last_tsn: 3,
+                    streams: vec![4],
+                },
+                ReconfigParameter::Response {
+                    response_sequence: 5,
+                    result: 1,
+                },
+            ]),
+            Chunk::ForwardTsn {
+                new_cumulative_tsn: 9,
+                streams: vec![(1, 2), (3, 4)],
+            },
+            Chunk::IForwardTsn {
+                new_cumulative_tsn: 9,
+                streams: vec![(1, false, 2), (3, true, 0x0001_0000)],
+            },
+            Chunk::Unknown {
+                chunk_type: 0xc1,
+                flags: 0x10,
+                value: vec![1],
+            },
+        ];
+        let bytes: Vec<u8> = chunks.iter().flat_map(|v| v.to_bytes()).collect();
+        assert!(bytes.len().is_multiple_of(4));
+        assert_eq!(Chunk::parse_chunks(&bytes), Ok(chunks));
+
+        // an INIT ACK needs its cookie, an INIT a tag.
+        let mut broken = Chunk::Init(init.clone()).to_bytes();
+        broken[0] = CHUNK_INIT_ACK;
+        assert!(Chunk::parse_chunks(&broken).is_ok());
+        let no_cookie = Chunk::Init(InitChunk {
+            state_cookie: None,
+            ..init.clone()
+        });
+        let mut broken = no_cookie.to_bytes();
+        broken[0] = CHUNK_INIT_ACK;
+        assert_eq!(Chunk::parse_chunks(&broken), Err(SctpError::InvalidChunk));
+        let zero_tag = Chunk::Init(InitChunk {
+            initiate_tag: 0,
+            ..init
+        });
+        assert_eq!(
+            Chunk::parse_chunks(&zero_tag.to_bytes()),
+            Err(SctpError::InvalidChunk)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+// https://tools.ietf.org/html/rfc9260#appendix-A
+
+/*
+    CRC32c (Castagnoli), reflected polynomial 0x82f63b78.
+    The checksum is written in the packet in little endian (Appendix A).
+*/
+
+const POLYNOMIAL: u32 = 0x82f6_3b78;
+
+const fn get_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut value = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                (value >> 1) ^ POLYNOMIAL
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+const TABLE: [u32; 256] = get_table();
+
+pub fn crc32c(data: &[u8]) -> u32 {
+    let crc = data.iter().fold(!0u32, |crc, v| {
+        TABLE[usize::from((crc as u8) ^ v)] ^ (crc >> 8)
+    });
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32c_test() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(&[0; 32]), 0x8a91_36aa);
+        assert_eq!(crc32c(b""), 0);
+    }
+}
@@ -0,0 +1,97 @@
+// https://tools.ietf.org/html/rfc9260#section-3.1
+// https://tools.ietf.org/html/rfc8261#section-5
+
+/*
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |     Source Port Number        |     Destination Port Number   |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                      Verification Tag                         |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                           Checksum                            |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                            Chunks                             |
+
+    One SCTP packet is one DTLS record. The checksum is the CRC32c of the
+    packet with the checksum zeroed, and is kept over DTLS too.
+*/
+
+use crate::sctp::chunk::Chunk;
+use crate::sctp::crc32c::crc32c;
+use crate::sctp::{Result, SctpError};
+
+pub const COMMON_HEADER_LENGTH: usize = 12;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Packet {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub verification_tag: u32,
+    pub chunks: Vec<Chunk>,
+}
+
+impl Packet {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            COMMON_HEADER_LENGTH + self.chunks.iter().map(|v| v.get_length()).sum::<usize>(),
+        );
+        out.extend_from_slice(&self.source_port.to_be_bytes());
+        out.extend_from_slice(&self.destination_port.to_be_bytes());
+        out.extend_from_slice(&self.verification_tag.to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        for chunk in &self.chunks {
+            out.extend(chunk.to_bytes());
+        }
+        let checksum = crc32c(&out);
+        out[8..12].copy_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Packet> {
+        if bytes.len() < COMMON_HEADER_LENGTH + 4 {
+            return Err(SctpError::InvalidPacket);
+        }
+        let checksum = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        let mut zeroed = bytes.to_vec();
+        zeroed[8..12].copy_from_slice(&[0; 4]);
+        if crc32c(&zeroed) != checksum {
+            return Err(SctpError::InvalidChecksum);
+        }
+        Ok(Packet {
+            source_port: u16::from_be_bytes([bytes[0], bytes[1]]),
+            destination_port: u16::from_be_bytes([bytes[2], bytes[3]]),
+            verification_tag: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            chunks: Chunk::parse_chunks(&bytes[COMMON_HEADER_LENGTH..])?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn packet_test() {
+        let packet = Packet {
+            source_port: 5000,
+            destination_port: 5000,
+            verification_tag: 0x1234_5678,
+            chunks: vec![Chunk::CookieAck, Chunk::Heartbeat(vec![0, 1, 0, 5, 7])],
+        };
+        let mut bytes = packet.to_bytes();
+        assert_eq!(bytes.len(), COMMON_HEADER_LENGTH + 4 + 12);
+        assert_eq!(
+            &bytes[..8],
+            &[0x13, 0x88, 0x13, 0x88, 0x12, 0x34, 0x56, 0x78]
+        );
+        assert_eq!(Packet::from_bytes(&bytes), Ok(packet));
+
+        bytes[13] ^= 1;
+        assert_eq!(Packet::from_bytes(&bytes), Err(SctpError::InvalidChecksum));
+        assert_eq!(
+            Packet::from_bytes(&bytes[..COMMON_HEADER_LENGTH]),
+            Err(SctpError::InvalidPacket)
+        );
+    }
+}
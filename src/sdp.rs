@@ -1,34 +1,106 @@
+// https://tools.ietf.org/html/rfc8866
+
+/*
+    SessionDescription
+      v= o= s= i= u= e= p= c= b= t= r= z= k= a=
+      MediaDescription *
+        m= i= c= b= k= a=
+
+    the lines are kept in their order with their values, attributes as
+    name and value, so a description parsed from a browser writes back the
+    same lines. the attributes are interpreted by the modules using them.
+*/
+
+pub mod attribute;
+pub mod media;
+pub mod session;
+
 use failure::Fail;
 
-use std::net::IpAddr;
+pub type Result<T> = std::result::Result<T, SdpError>;
 
-/*
+#[derive(Fail, Debug, PartialEq)]
+pub enum SdpError {
+    #[fail(display = "SDP line {} is broken: {}", line, reason)]
+    InvalidLine { line: usize, reason: String },
 
-SdpSession format
-
-#[derive(Clone)]
-#[cfg_attr(feature = "serialize", derive(Serialize))]
-pub struct SdpSession {
-    pub version: u64,
-    pub origin: SdpOrigin,
-    pub session: String,
-    pub connection: Option<SdpConnection>,
-    pub bandwidth: Vec<SdpBandwidth>,
-    pub timing: Option<SdpTiming>,
-    pub attribute: Vec<SdpAttribute>,
-    pub media: Vec<SdpMedia>,
-    pub warnings: Vec<SdpParserError>, // unsupported values:
-                                       // information: Option<String>,
-                                       // uri: Option<String>,
-                                       // email: Option<String>,
-                                       // phone: Option<String>,
-                                       // repeat: Option<String>,
-                                       // zone: Option<String>,
-                                       // key: Option<String>
+    #[fail(display = "SDP has no {}= line.", line_type)]
+    MissingLine { line_type: char },
+}
+
+// 1から数えた行番号と，typeと値．
+pub(crate) struct LineReader<'a> {
+    lines: Vec<(usize, char, &'a str)>,
+    index: usize,
+}
+
+impl<'a> LineReader<'a> {
+    /// Accepts CRLF or LF, and skips empty lines.
+    pub fn new(sdp: &'a str) -> Result<Self> {
+        let mut lines = vec![];
+        for (i, line) in sdp.split('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let mut chars = line.chars();
+            match (chars.next(), chars.next()) {
+                (Some(line_type), Some('=')) if line_type.is_ascii_lowercase() => {
+                    lines.push((i + 1, line_type, &line[2..]))
+                }
+                _ => return Err(invalid_line(i + 1, "not <type>=<value>")),
+            }
+        }
+        Ok(LineReader { lines, index: 0 })
+    }
+
+    pub fn peek(&self) -> Option<(usize, char, &'a str)> {
+        self.lines.get(self.index).copied()
+    }
+
+    /// Takes the next line if it is of `line_type`.
+    pub fn take(&mut self, line_type: char) -> Option<(usize, &'a str)> {
+        match self.peek() {
+            Some((line, t, value)) if t == line_type => {
+                self.index += 1;
+                Some((line, value))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn expect(&mut self, line_type: char) -> Result<(usize, &'a str)> {
+        match self.peek() {
+            None => Err(SdpError::MissingLine { line_type }),
+            Some((line, t, _)) if t != line_type => Err(invalid_line(
+                line,
+                &format!("{}= where {}= is expected", t, line_type),
+            )),
+            _ => Ok(self.take(line_type).unwrap()),
+        }
+    }
+}
+
+impl SdpError {
+    /// Sets the line of an error from parsing a single value.
+    pub(crate) fn at_line(self, line: usize) -> Self {
+        match self {
+            SdpError::InvalidLine { reason, .. } => SdpError::InvalidLine { line, reason },
+            error => error,
+        }
+    }
+}
+
+/// `line` is 0 for an error from parsing a single value.
+pub(crate) fn invalid_line(line: usize, reason: &str) -> SdpError {
+    SdpError::InvalidLine {
+        line,
+        reason: reason.to_string(),
+    }
 }
-*/
 
 #[cfg(test)]
+#[allow(unused_imports, unused_variables)]
 mod test {
     use super::*;
     use webrtc_sdp;
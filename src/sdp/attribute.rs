@@ -0,0 +1,106 @@
+// https://tools.ietf.org/html/rfc8866#section-5.13
+
+/*
+    a=<attribute-name>                   a property, e.g. a=rtcp-mux
+    a=<attribute-name>:<attribute-value> e.g. a=mid:0
+
+    the value is kept as written, also a leading space as in
+    a=msid-semantic: WMS *
+*/
+
+use crate::sdp::{invalid_line, Result};
+
+use std::fmt;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Attribute {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl Attribute {
+    pub fn new(name: &str, value: &str) -> Self {
+        Attribute {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+        }
+    }
+
+    pub fn new_property(name: &str) -> Self {
+        Attribute {
+            name: name.to_string(),
+            value: None,
+        }
+    }
+
+    /// Parses the value of an a= line.
+    pub fn parse(value: &str) -> Result<Self> {
+        let (name, value) = match value.find(':') {
+            Some(i) => (&value[..i], Some(value[i + 1..].to_string())),
+            None => (value, None),
+        };
+        if name.is_empty() || name.chars().any(|c| c.is_ascii_whitespace()) {
+            return Err(invalid_line(0, "attribute name"));
+        }
+        Ok(Attribute {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(ref value) => write!(f, "{}:{}", self.name, value),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+pub(crate) fn find_attribute<'a>(attributes: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+    attributes.iter().find(|v| v.name == name)
+}
+
+/// The values of the attributes named `name`, in order.
+pub(crate) fn find_attribute_values<'a>(
+    attributes: &'a [Attribute],
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    attributes
+        .iter()
+        .filter(move |v| v.name == name)
+        .filter_map(|v| v.value.as_deref())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attribute_test() {
+        let attribute = Attribute::parse("rtpmap:111 opus/48000/2").unwrap();
+        assert_eq!(attribute, Attribute::new("rtpmap", "111 opus/48000/2"));
+        assert_eq!(attribute.to_string(), "rtpmap:111 opus/48000/2");
+
+        let attribute = Attribute::parse("rtcp-mux").unwrap();
+        assert_eq!(attribute, Attribute::new_property("rtcp-mux"));
+        assert_eq!(attribute.to_string(), "rtcp-mux");
+
+        let attribute = Attribute::parse("msid-semantic: WMS *").unwrap();
+        assert_eq!(attribute.value.as_deref(), Some(" WMS *"));
+        assert_eq!(attribute.to_string(), "msid-semantic: WMS *");
+
+        // the value keeps its colons.
+        let attribute = Attribute::parse("extmap:1 urn:ietf:params:rtp-hdrext:sdes:mid").unwrap();
+        assert_eq!(attribute.name, "extmap");
+        assert_eq!(
+            attribute.value.as_deref(),
+            Some("1 urn:ietf:params:rtp-hdrext:sdes:mid")
+        );
+
+        assert!(Attribute::parse("").is_err());
+        assert!(Attribute::parse(":0").is_err());
+        assert!(Attribute::parse("mid 0").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+// https://tools.ietf.org/html/rfc8866#section-5.14
+// https://tools.ietf.org/html/rfc3264#section-5.1
+
+/*
+    m=<media> <port>[/<number of ports>] <proto> <fmt> ...
+    i=*
+    c=*
+    b=*
+    k=*
+    a=*
+
+    e.g.
+    m=audio 9 UDP/TLS/RTP/SAVPF 111 0 8
+    m=application 9 UDP/DTLS/SCTP webrtc-datachannel
+*/
+
+use crate::sdp::attribute::{find_attribute, find_attribute_values, Attribute};
+use crate::sdp::session::{Bandwidth, Connection};
+use crate::sdp::{invalid_line, LineReader, Result};
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MediaKind {
+    Audio,
+    Video,
+    Text,
+    Application,
+    Message,
+}
+
+impl MediaKind {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            MediaKind::Audio => "audio",
+            MediaKind::Video => "video",
+            MediaKind::Text => "text",
+            MediaKind::Application => "application",
+            MediaKind::Message => "message",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MediaKind> {
+        match name {
+            "audio" => Some(MediaKind::Audio),
+            "video" => Some(MediaKind::Video),
+            "text" => Some(MediaKind::Text),
+            "application" => Some(MediaKind::Application),
+            "message" => Some(MediaKind::Message),
+            _ => None,
+        }
+    }
+}
+
+// a=sendrecvなど．
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Direction {
+    SendRecv,
+    SendOnly,
+    RecvOnly,
+    Inactive,
+}
+
+impl Direction {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Direction::SendRecv => "sendrecv",
+            Direction::SendOnly => "sendonly",
+            Direction::RecvOnly => "recvonly",
+            Direction::Inactive => "inactive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "sendrecv" => Some(Direction::SendRecv),
+            "sendonly" => Some(Direction::SendOnly),
+            "recvonly" => Some(Direction::RecvOnly),
+            "inactive" => Some(Direction::Inactive),
+            _ => None,
+        }
+    }
+
+    pub fn new(send: bool, recv: bool) -> Direction {
+        match (send, recv) {
+            (true, true) => Direction::SendRecv,
+            (true, false) => Direction::SendOnly,
+            (false, true) => Direction::RecvOnly,
+            (false, false) => Direction::Inactive,
+        }
+    }
+
+    pub fn is_send(self) -> bool {
+        self == Direction::SendRecv || self == Direction::SendOnly
+    }
+
+    pub fn is_recv(self) -> bool {
+        self == Direction::SendRecv || self == Direction::RecvOnly
+    }
+
+    /// The direction seen from the peer.
+    pub fn reverse(self) -> Direction {
+        match self {
+            Direction::SendOnly => Direction::RecvOnly,
+            Direction::RecvOnly => Direction::SendOnly,
+            v => v,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MediaDescription {
+    /// Kept as written, see `get_kind`.
+    pub media: String,
+    pub port: u16,
+    pub port_count: Option<u16>,
+    pub protocol: String,
+    pub formats: Vec<String>,
+    pub information: Option<String>,
+    pub connections: Vec<Connection>,
+    pub bandwidths: Vec<Bandwidth>,
+    pub key: Option<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl MediaDescription {
+    pub fn new(kind: MediaKind, port: u16, protocol: &str, formats: Vec<String>) -> Self {
+        MediaDescription {
+            media: kind.get_name().to_string(),
+            port,
+            port_count: None,
+            protocol: protocol.to_string(),
+            formats,
+            information: None,
+            connections: vec![],
+            bandwidths: vec![],
+            key: None,
+            attributes: vec![],
+        }
+    }
+
+    fn parse_media_line(value: &str) -> Result<Self> {
+        let fields: Vec<&str> = value.split(' ').collect();
+        if fields.len() < 4 || fields.iter().any(|v| v.is_empty()) {
+            return Err(invalid_line(0, "media fields"));
+        }
+        let invalid = |_| invalid_line(0, "media port");
+        let (port, port_count) = match fields[1].find('/') {
+            Some(i) => (
+                fields[1][..i].parse().map_err(invalid)?,
+                Some(fields[1][i + 1..].parse().map_err(invalid)?),
+            ),
+            None => (fields[1].parse().map_err(invalid)?, None),
+        };
+        Ok(MediaDescription {
+            media: fields[0].to_string(),
+            port,
+            port_count,
+            protocol: fields[2].to_string(),
+            formats: fields[3..].iter().map(|v| v.to_string()).collect(),
+            information: None,
+            connections: vec![],
+            bandwidths: vec![],
+            key: None,
+            attributes: vec![],
+        })
+    }
+
+    /// Reads from an m= line up to the next one.
+    pub(crate) fn read(reader: &mut LineReader) -> Result<Self> {
+        let (line, value) = reader.expect('m')?;
+        let mut media = MediaDescription::parse_media_line(value).map_err(|e| e.at_line(line))?;
+        media.information = reader.take('i').map(|(_, v)| v.to_string());
+        while let Some((line, value)) = reader.take('c') {
+            media
+                .connections
+                .push(Connection::parse(value).map_err(|e| e.at_line(line))?);
+        }
+        while let Some((line, value)) = reader.take('b') {
+            media
+                .bandwidths
+                .push(Bandwidth::parse(value).map_err(|e| e.at_line(line))?);
+        }
+        media.key = reader.take('k').map(|(_, v)| v.to_string());
+        while let Some((line, value)) = reader.take('a') {
+            media
+                .attributes
+                .push(Attribute::parse(value).map_err(|e| e.at_line(line))?);
+        }
+        match reader.peek() {
+            Some((_, 'm', _)) | None => Ok(media),
+            Some((line, t, _)) => Err(invalid_line(
+                line,
+                &format!("{}= in a media description", t),
+            )),
+        }
+    }
+
+    pub fn get_kind(&self) -> Option<MediaKind> {
+        MediaKind::from_name(&self.media)
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&Attribute> {
+        find_attribute(&self.attributes, name)
+    }
+
+    pub fn get_attribute_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        find_attribute_values(&self.attributes, name)
+    }
+
+    pub fn get_mid(&self) -> Option<&str> {
+        self.get_attribute("mid")?.value.as_deref()
+    }
+
+    /// An m= section with port 0 that is not rejected, it shares the
+    /// transport of a BUNDLE group (RFC 8843 Section 6).
+    pub fn is_bundle_only(&self) -> bool {
+        self.get_attribute("bundle-only").is_some()
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.port == 0 && !self.is_bundle_only()
+    }
+
+    /// `None` when the session direction applies.
+    pub fn get_direction(&self) -> Option<Direction> {
+        self.attributes
+            .iter()
+            .filter(|v| v.value.is_none())
+            .find_map(|v| Direction::from_name(&v.name))
+    }
+
+    /// Replaces the direction attribute, or adds one.
+    pub fn set_direction(&mut self, direction: Direction) {
+        let attribute = Attribute::new_property(direction.get_name());
+        match self
+            .attributes
+            .iter()
+            .position(|v| v.value.is_none() && Direction::from_name(&v.name).is_some())
+        {
+            Some(i) => self.attributes[i] = attribute,
+            None => self.attributes.push(attribute),
+        }
+    }
+}
+
+impl fmt::Display for MediaDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m={} {}", self.media, self.port)?;
+        if let Some(v) = self.port_count {
+            write!(f, "/{}", v)?;
+        }
+        write!(f, " {}", self.protocol)?;
+        for v in &self.formats {
+            write!(f, " {}", v)?;
+        }
+        write!(f, "\r\n")?;
+        if let Some(ref v) = self.information {
+            write!(f, "i={}\r\n", v)?;
+        }
+        for v in &self.connections {
+            write!(f, "c={}\r\n", v)?;
+        }
+        for v in &self.bandwidths {
+            write!(f, "b={}\r\n", v)?;
+        }
+        if let Some(ref v) = self.key {
+            write!(f, "k={}\r\n", v)?;
+        }
+        for v in &self.attributes {
+            write!(f, "a={}\r\n", v)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn media_line_test() {
+        let media = MediaDescription::parse_media_line("video 51372/2 RTP/AVP 99 100").unwrap();
+        assert_eq!(media.get_kind(), Some(MediaKind::Video));
+        assert_eq!(media.port, 51372);
+        assert_eq!(media.port_count, Some(2));
+        assert_eq!(media.formats, vec!["99", "100"]);
+        assert_eq!(media.to_string(), "m=video 51372/2 RTP/AVP 99 100\r\n");
+
+        // an unknown media is kept.
+        let media = MediaDescription::parse_media_line("image 0 udptl t38").unwrap();
+        assert_eq!(media.get_kind(), None);
+        assert_eq!(media.to_string(), "m=image 0 udptl t38\r\n");
+
+        assert!(MediaDescription::parse_media_line("audio 9 RTP/AVP").is_err());
+        assert!(MediaDescription::parse_media_line("audio x RTP/AVP 0").is_err());
+        assert!(MediaDescription::parse_media_line("audio 9  RTP/AVP 0").is_err());
+    }
+
+    #[test]
+    fn direction_test() {
+        let mut media = MediaDescription::new(
+            MediaKind::Audio,
+            9,
+            "UDP/TLS/RTP/SAVPF",
+            vec!["111".to_string()],
+        );
+        assert_eq!(media.get_direction(), None);
+        media.attributes.push(Attribute::new("mid", "0"));
+        media.set_direction(Direction::SendOnly);
+        media.attributes.push(Attribute::new_property("rtcp-mux"));
+        assert_eq!(media.get_direction(), Some(Direction::SendOnly));
+        media.set_direction(Direction::Inactive);
+        assert_eq!(
+            media.attributes,
+            vec![
+                Attribute::new("mid", "0"),
+                Attribute::new_property("inactive"),
+                Attribute::new_property("rtcp-mux"),
+            ]
+        );
+        assert_eq!(Direction::SendOnly.reverse(), Direction::RecvOnly);
+        assert_eq!(Direction::SendRecv.reverse(), Direction::SendRecv);
+        assert_eq!(Direction::new(false, true), Direction::RecvOnly);
+        assert!(Direction::SendOnly.is_send());
+        assert!(!Direction::SendOnly.is_recv());
+    }
+}
@@ -0,0 +1,693 @@
+// https://tools.ietf.org/html/rfc8866#section-5
+// https://tools.ietf.org/html/rfc8866#section-9
+
+/*
+    v=0
+    o=<username> <sess-id> <sess-version> <nettype> <addrtype> <unicast-address>
+    s=<session name>
+    i=* u=* e=* p=*
+    c=<nettype> <addrtype> <connection-address>                      *
+    b=<bwtype>:<bandwidth>                                           *
+    t=<start-time> <stop-time>                                   one or more
+    r=<repeat interval> <active duration> <offsets from start-time>  *
+    z=* k=*
+    a=*
+    m=                                                               *
+
+    written with CRLF line ends.
+*/
+
+use crate::sdp::attribute::{find_attribute, find_attribute_values, Attribute};
+use crate::sdp::media::{Direction, MediaDescription};
+use crate::sdp::{invalid_line, LineReader, Result};
+
+use std::fmt;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Origin {
+    pub username: String,
+    pub session_id: u64,
+    pub session_version: u64,
+    pub net_type: String,
+    pub address_type: String,
+    pub address: String,
+}
+
+impl Origin {
+    /// The origin browsers write, `- <sess-id> <sess-version> IN IP4 127.0.0.1`.
+    pub fn new(session_id: u64, session_version: u64) -> Self {
+        Origin {
+            username: "-".to_string(),
+            session_id,
+            session_version,
+            net_type: "IN".to_string(),
+            address_type: "IP4".to_string(),
+            address: "127.0.0.1".to_string(),
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        let fields: Vec<&str> = value.split(' ').collect();
+        if fields.len() != 6 {
+            return Err(invalid_line(0, "origin fields"));
+        }
+        let parse_u64 = |v: &str| {
+            v.parse::<u64>()
+                .map_err(|_| invalid_line(0, "origin session id or version"))
+        };
+        Ok(Origin {
+            username: fields[0].to_string(),
+            session_id: parse_u64(fields[1])?,
+            session_version: parse_u64(fields[2])?,
+            net_type: fields[3].to_string(),
+            address_type: fields[4].to_string(),
+            address: fields[5].to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.username,
+            self.session_id,
+            self.session_version,
+            self.net_type,
+            self.address_type,
+            self.address
+        )
+    }
+}
+
+// c=．IP4のmulticastはaddress/ttl/number，IP6はaddress/number．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Connection {
+    pub net_type: String,
+    pub address_type: String,
+    pub address: String,
+    pub ttl: Option<u8>,
+    pub address_count: Option<u32>,
+}
+
+impl Connection {
+    pub fn new(address_type: &str, address: &str) -> Self {
+        Connection {
+            net_type: "IN".to_string(),
+            address_type: address_type.to_string(),
+            address: address.to_string(),
+            ttl: None,
+            address_count: None,
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        let fields: Vec<&str> = value.split(' ').collect();
+        if fields.len() != 3 {
+            return Err(invalid_line(0, "connection fields"));
+        }
+        let mut parts = fields[2].split('/');
+        let address = parts.next().unwrap_or("");
+        let rest: Vec<&str> = parts.collect();
+        if address.is_empty() || rest.len() > 2 {
+            return Err(invalid_line(0, "connection address"));
+        }
+        let invalid = |_| invalid_line(0, "connection address");
+        let (ttl, address_count) = match (fields[1], rest.as_slice()) {
+            (_, []) => (None, None),
+            ("IP4", [ttl]) => (Some(ttl.parse().map_err(invalid)?), None),
+            ("IP4", [ttl, count]) => (
+                Some(ttl.parse().map_err(invalid)?),
+                Some(count.parse().map_err(invalid)?),
+            ),
+            (_, [count]) => (None, Some(count.parse().map_err(invalid)?)),
+            _ => return Err(invalid_line(0, "connection address")),
+        };
+        Ok(Connection {
+            net_type: fields[0].to_string(),
+            address_type: fields[1].to_string(),
+            address: address.to_string(),
+            ttl,
+            address_count,
+        })
+    }
+}
+
+impl fmt::Display for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.net_type, self.address_type, self.address
+        )?;
+        if let Some(v) = self.ttl {
+            write!(f, "/{}", v)?;
+        }
+        if let Some(v) = self.address_count {
+            write!(f, "/{}", v)?;
+        }
+        Ok(())
+    }
+}
+
+// b=．bwtypeはAS，CT，TIASなど．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Bandwidth {
+    pub bandwidth_type: String,
+    pub bandwidth: u64,
+}
+
+impl Bandwidth {
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || invalid_line(0, "bandwidth");
+        let i = value.find(':').ok_or_else(invalid)?;
+        if i == 0 {
+            return Err(invalid());
+        }
+        Ok(Bandwidth {
+            bandwidth_type: value[..i].to_string(),
+            bandwidth: value[i + 1..].parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.bandwidth_type, self.bandwidth)
+    }
+}
+
+// t=と続くr=．WebRTCでは常にt=0 0．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Timing {
+    pub start_time: u64,
+    pub stop_time: u64,
+    pub repeats: Vec<String>,
+}
+
+impl Timing {
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || invalid_line(0, "timing");
+        let fields: Vec<&str> = value.split(' ').collect();
+        if fields.len() != 2 {
+            return Err(invalid());
+        }
+        Ok(Timing {
+            start_time: fields[0].parse().map_err(|_| invalid())?,
+            stop_time: fields[1].parse().map_err(|_| invalid())?,
+            repeats: vec![],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SessionDescription {
+    pub version: u32,
+    pub origin: Origin,
+    pub session_name: String,
+    pub information: Option<String>,
+    pub uri: Option<String>,
+    pub emails: Vec<String>,
+    pub phones: Vec<String>,
+    pub connection: Option<Connection>,
+    pub bandwidths: Vec<Bandwidth>,
+    pub timings: Vec<Timing>,
+    pub time_zones: Option<String>,
+    pub key: Option<String>,
+    pub attributes: Vec<Attribute>,
+    pub media: Vec<MediaDescription>,
+}
+
+impl SessionDescription {
+    /// A session without media, with `s=-` and `t=0 0` as in RFC 8829
+    /// Section 5.2.1.
+    pub fn new(origin: Origin) -> Self {
+        SessionDescription {
+            version: 0,
+            origin,
+            session_name: "-".to_string(),
+            information: None,
+            uri: None,
+            emails: vec![],
+            phones: vec![],
+            connection: None,
+            bandwidths: vec![],
+            timings: vec![Timing {
+                start_time: 0,
+                stop_time: 0,
+                repeats: vec![],
+            }],
+            time_zones: None,
+            key: None,
+            attributes: vec![],
+            media: vec![],
+        }
+    }
+
+    pub fn parse(sdp: &str) -> Result<Self> {
+        let mut reader = LineReader::new(sdp)?;
+
+        let (line, value) = reader.expect('v')?;
+        let version = match value.parse::<u32>() {
+            Ok(0) => 0,
+            _ => return Err(invalid_line(line, "version")),
+        };
+        let (line, value) = reader.expect('o')?;
+        let origin = Origin::parse(value).map_err(|e| e.at_line(line))?;
+        let (_, session_name) = reader.expect('s')?;
+        let mut session = SessionDescription {
+            version,
+            origin,
+            session_name: session_name.to_string(),
+            information: reader.take('i').map(|(_, v)| v.to_string()),
+            uri: reader.take('u').map(|(_, v)| v.to_string()),
+            emails: vec![],
+            phones: vec![],
+            connection: None,
+            bandwidths: vec![],
+            timings: vec![],
+            time_zones: None,
+            key: None,
+            attributes: vec![],
+            media: vec![],
+        };
+        while let Some((_, value)) = reader.take('e') {
+            session.emails.push(value.to_string());
+        }
+        while let Some((_, value)) = reader.take('p') {
+            session.phones.push(value.to_string());
+        }
+        if let Some((line, value)) = reader.take('c') {
+            session.connection = Some(Connection::parse(value).map_err(|e| e.at_line(line))?);
+        }
+        while let Some((line, value)) = reader.take('b') {
+            session
+                .bandwidths
+                .push(Bandwidth::parse(value).map_err(|e| e.at_line(line))?);
+        }
+        loop {
+            let (line, value) = if session.timings.is_empty() {
+                reader.expect('t')?
+            } else {
+                match reader.take('t') {
+                    Some(v) => v,
+                    None => break,
+                }
+            };
+            let mut timing = Timing::parse(value).map_err(|e| e.at_line(line))?;
+            while let Some((_, value)) = reader.take('r') {
+                timing.repeats.push(value.to_string());
+            }
+            session.timings.push(timing);
+        }
+        session.time_zones = reader.take('z').map(|(_, v)| v.to_string());
+        session.key = reader.take('k').map(|(_, v)| v.to_string());
+        while let Some((line, value)) = reader.take('a') {
+            session
+                .attributes
+                .push(Attribute::parse(value).map_err(|e| e.at_line(line))?);
+        }
+        while reader.peek().is_some() {
+            session.media.push(MediaDescription::read(&mut reader)?);
+        }
+        Ok(session)
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&Attribute> {
+        find_attribute(&self.attributes, name)
+    }
+
+    pub fn get_attribute_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        find_attribute_values(&self.attributes, name)
+    }
+
+    /// The mids of each a=group with `semantics` (RFC 5888), e.g. BUNDLE.
+    pub fn get_groups(&self, semantics: &str) -> Vec<Vec<&str>> {
+        self.get_attribute_values("group")
+            .filter_map(|v| {
+                let mut fields = v.split(' ');
+                if fields.next() == Some(semantics) {
+                    Some(fields.filter(|v| !v.is_empty()).collect())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The direction for the media descriptions without one.
+    pub fn get_direction(&self) -> Option<Direction> {
+        self.attributes
+            .iter()
+            .filter(|v| v.value.is_none())
+            .find_map(|v| Direction::from_name(&v.name))
+    }
+
+    pub fn get_media_by_mid(&self, mid: &str) -> Option<&MediaDescription> {
+        self.media.iter().find(|v| v.get_mid() == Some(mid))
+    }
+}
+
+impl fmt::Display for SessionDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v={}\r\n", self.version)?;
+        write!(f, "o={}\r\n", self.origin)?;
+        write!(f, "s={}\r\n", self.session_name)?;
+        if let Some(ref v) = self.information {
+            write!(f, "i={}\r\n", v)?;
+        }
+        if let Some(ref v) = self.uri {
+            write!(f, "u={}\r\n", v)?;
+        }
+        for v in &self.emails {
+            write!(f, "e={}\r\n", v)?;
+        }
+        for v in &self.phones {
+            write!(f, "p={}\r\n", v)?;
+        }
+        if let Some(ref v) = self.connection {
+            write!(f, "c={}\r\n", v)?;
+        }
+        for v in &self.bandwidths {
+            write!(f, "b={}\r\n", v)?;
+        }
+        for timing in &self.timings {
+            write!(f, "t={} {}\r\n", timing.start_time, timing.stop_time)?;
+            for v in &timing.repeats {
+                write!(f, "r={}\r\n", v)?;
+            }
+        }
+        if let Some(ref v) = self.time_zones {
+            write!(f, "z={}\r\n", v)?;
+        }
+        if let Some(ref v) = self.key {
+            write!(f, "k={}\r\n", v)?;
+        }
+        for v in &self.attributes {
+            write!(f, "a={}\r\n", v)?;
+        }
+        for v in &self.media {
+            write!(f, "{}", v)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::sdp::media::MediaKind;
+    use crate::sdp::SdpError;
+
+    pub(crate) const CHROME_OFFER: &str = "v=0\r
+o=- 4215775240593850428 2 IN IP4 127.0.0.1\r
+s=-\r
+t=0 0\r
+a=group:BUNDLE 0 1 2\r
+a=extmap-allow-mixed\r
+a=msid-semantic: WMS 3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a\r
+m=audio 9 UDP/TLS/RTP/SAVPF 111 63 9 0 8 13 110 126\r
+c=IN IP4 0.0.0.0\r
+a=rtcp:9 IN IP4 0.0.0.0\r
+a=ice-ufrag:Ib4Q\r
+a=ice-pwd:6vKVOvTOf0kZgvhMAnLDRQI0\r
+a=ice-options:trickle\r
+a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC\r
+a=setup:actpass\r
+a=mid:0\r
+a=extmap:1 urn:ietf:params:rtp-hdrext:ssrc-audio-level\r
+a=extmap:2 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r
+a=extmap:3 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\r
+a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\r
+a=sendrecv\r
+a=msid:3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a 0c5f2e8b-8a4a-4b51-a0c5-1a1c3a6b5e2f\r
+a=rtcp-mux\r
+a=rtpmap:111 opus/48000/2\r
+a=rtcp-fb:111 transport-cc\r
+a=fmtp:111 minptime=10;useinbandfec=1\r
+a=rtpmap:63 red/48000/2\r
+a=fmtp:63 111/111\r
+a=rtpmap:9 G722/8000\r
+a=rtpmap:0 PCMU/8000\r
+a=rtpmap:8 PCMA/8000\r
+a=rtpmap:13 CN/8000\r
+a=rtpmap:110 telephone-event/48000\r
+a=rtpmap:126 telephone-event/8000\r
+a=ssrc:2228110218 cname:nkLsHqZl0yPvNv4B\r
+a=ssrc:2228110218 msid:3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a 0c5f2e8b-8a4a-4b51-a0c5-1a1c3a6b5e2f\r
+m=video 9 UDP/TLS/RTP/SAVPF 96 97 102 103\r
+c=IN IP4 0.0.0.0\r
+a=rtcp:9 IN IP4 0.0.0.0\r
+a=ice-ufrag:Ib4Q\r
+a=ice-pwd:6vKVOvTOf0kZgvhMAnLDRQI0\r
+a=ice-options:trickle\r
+a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC\r
+a=setup:actpass\r
+a=mid:1\r
+a=extmap:14 urn:ietf:params:rtp-hdrext:toffset\r
+a=extmap:2 http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time\r
+a=extmap:3 http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01\r
+a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\r
+a=extmap:10 urn:ietf:params:rtp-hdrext:sdes:rtp-stream-id\r
+a=extmap:11 urn:ietf:params:rtp-hdrext:sdes:repaired-rtp-stream-id\r
+a=sendrecv\r
+a=msid:3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a 7b5c1b5e-0d3a-4f0e-9d8e-4e1f0f3d2c1b\r
+a=rtcp-mux\r
+a=rtcp-rsize\r
+a=rtpmap:96 VP8/90000\r
+a=rtcp-fb:96 goog-remb\r
+a=rtcp-fb:96 transport-cc\r
+a=rtcp-fb:96 ccm fir\r
+a=rtcp-fb:96 nack\r
+a=rtcp-fb:96 nack pli\r
+a=rtpmap:97 rtx/90000\r
+a=fmtp:97 apt=96\r
+a=rtpmap:102 H264/90000\r
+a=rtcp-fb:102 nack pli\r
+a=fmtp:102 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42001f\r
+a=rtpmap:103 rtx/90000\r
+a=fmtp:103 apt=102\r
+a=ssrc-group:FID 1045175675 3822520862\r
+a=ssrc:1045175675 cname:nkLsHqZl0yPvNv4B\r
+a=ssrc:1045175675 msid:3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a 7b5c1b5e-0d3a-4f0e-9d8e-4e1f0f3d2c1b\r
+a=ssrc:3822520862 cname:nkLsHqZl0yPvNv4B\r
+a=ssrc:3822520862 msid:3bf6c5cb-3b1f-4bd1-bfd5-fd0d3fcf0e0a 7b5c1b5e-0d3a-4f0e-9d8e-4e1f0f3d2c1b\r
+m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r
+c=IN IP4 0.0.0.0\r
+a=ice-ufrag:Ib4Q\r
+a=ice-pwd:6vKVOvTOf0kZgvhMAnLDRQI0\r
+a=ice-options:trickle\r
+a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC\r
+a=setup:actpass\r
+a=mid:2\r
+a=sctp-port:5000\r
+a=max-message-size:262144\r
+";
+
+    pub(crate) const FIREFOX_OFFER: &str = "v=0\r
+o=mozilla...THIS_IS_SDPARTA-99.0 7346093441134629620 0 IN IP4 0.0.0.0\r
+s=-\r
+t=0 0\r
+a=sendrecv\r
+a=fingerprint:sha-256 A4:3B:1D:7D:91:0E:4C:7B:5F:C2:96:E7:0E:08:0F:64:58:D1:A6:91:8C:3E:23:0A:3F:1E:83:45:9B:68:2F:B5\r
+a=group:BUNDLE 0 1\r
+a=ice-options:trickle\r
+a=msid-semantic:WMS *\r
+m=audio 9 UDP/TLS/RTP/SAVPF 109 9 0 8 101\r
+c=IN IP4 0.0.0.0\r
+a=sendrecv\r
+a=extmap:1 urn:ietf:params:rtp-hdrext:ssrc-audio-level\r
+a=extmap:2/recvonly urn:ietf:params:rtp-hdrext:csrc-audio-level\r
+a=extmap:3 urn:ietf:params:rtp-hdrext:sdes:mid\r
+a=fmtp:109 maxplaybackrate=48000;stereo=1;useinbandfec=1\r
+a=fmtp:101 0-15\r
+a=ice-pwd:b0a7e5d6a2b1c4f3e6d9c8b7a6f5e4d3\r
+a=ice-ufrag:3c6e1b2a\r
+a=mid:0\r
+a=msid:{5f4b2a1c-3d6e-4f7a-8b9c-0d1e2f3a4b5c} {9a8b7c6d-5e4f-4a3b-2c1d-0e9f8a7b6c5d}\r
+a=rtcp-mux\r
+a=rtpmap:109 opus/48000/2\r
+a=rtpmap:9 G722/8000/1\r
+a=rtpmap:0 PCMU/8000\r
+a=rtpmap:8 PCMA/8000\r
+a=rtpmap:101 telephone-event/8000/1\r
+a=setup:actpass\r
+a=ssrc:3328736364 cname:{0b1c2d3e-4f5a-4b6c-8d7e-9f0a1b2c3d4e}\r
+m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r
+c=IN IP4 0.0.0.0\r
+a=sendrecv\r
+a=ice-pwd:b0a7e5d6a2b1c4f3e6d9c8b7a6f5e4d3\r
+a=ice-ufrag:3c6e1b2a\r
+a=mid:1\r
+a=setup:actpass\r
+a=sctp-port:5000\r
+a=max-message-size:1073741823\r
+";
+
+    #[test]
+    fn chrome_offer_test() {
+        let session = SessionDescription::parse(CHROME_OFFER).unwrap();
+        assert_eq!(session.to_string(), CHROME_OFFER);
+        assert_eq!(session.origin.session_id, 4215775240593850428);
+        assert_eq!(
+            session.get_attribute("group").unwrap().value.as_deref(),
+            Some("BUNDLE 0 1 2")
+        );
+        assert_eq!(session.media.len(), 3);
+        assert_eq!(session.get_groups("BUNDLE"), vec![vec!["0", "1", "2"]]);
+        assert!(session.get_groups("LS").is_empty());
+
+        let audio = &session.media[0];
+        assert_eq!(audio.get_kind(), Some(MediaKind::Audio));
+        assert_eq!(audio.port, 9);
+        assert_eq!(audio.protocol, "UDP/TLS/RTP/SAVPF");
+        assert_eq!(
+            audio.formats,
+            vec!["111", "63", "9", "0", "8", "13", "110", "126"]
+        );
+        assert_eq!(audio.connections, vec![Connection::new("IP4", "0.0.0.0")]);
+        assert_eq!(audio.get_mid(), Some("0"));
+        assert_eq!(audio.get_direction(), Some(Direction::SendRecv));
+        assert!(audio.get_attribute("rtcp-mux").is_some());
+        assert_eq!(audio.get_attribute_values("extmap").count(), 4);
+
+        let video = session.get_media_by_mid("1").unwrap();
+        assert_eq!(video.get_kind(), Some(MediaKind::Video));
+        assert_eq!(
+            video.get_attribute_values("fmtp").collect::<Vec<_>>(),
+            vec![
+                "97 apt=96",
+                "102 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42001f",
+                "103 apt=102"
+            ]
+        );
+
+        let application = &session.media[2];
+        assert_eq!(application.get_kind(), Some(MediaKind::Application));
+        assert_eq!(application.protocol, "UDP/DTLS/SCTP");
+        assert_eq!(application.formats, vec!["webrtc-datachannel"]);
+        assert_eq!(application.get_direction(), None);
+    }
+
+    #[test]
+    fn firefox_offer_test() {
+        let session = SessionDescription::parse(FIREFOX_OFFER).unwrap();
+        assert_eq!(session.to_string(), FIREFOX_OFFER);
+        assert_eq!(session.origin.username, "mozilla...THIS_IS_SDPARTA-99.0");
+        assert_eq!(session.origin.address, "0.0.0.0");
+        assert_eq!(session.get_direction(), Some(Direction::SendRecv));
+        assert!(session.get_attribute("fingerprint").is_some());
+        assert_eq!(session.media.len(), 2);
+        assert_eq!(session.media[1].get_mid(), Some("1"));
+
+        // LF only, as pasted into signaling by hand.
+        let lf = FIREFOX_OFFER.replace("\r\n", "\n");
+        assert_eq!(SessionDescription::parse(&lf).unwrap(), session);
+    }
+
+    #[test]
+    fn session_fields_test() {
+        let sdp = "v=0\r
+o=jdoe 3724394400 3724394405 IN IP4 198.51.100.1\r
+s=Call to John Smith\r
+i=SDP Offer #1\r
+u=http://www.jdoe.example.com/home.html\r
+e=Jane Doe <jane@jdoe.example.com>\r
+p=+1 617 555-6011\r
+c=IN IP4 198.51.100.1\r
+b=AS:128\r
+t=0 0\r
+t=2873397496 2873404696\r
+r=7d 1h 0 25h\r
+z=2882844526 -1h 2898848070 0\r
+k=prompt\r
+a=recvonly\r
+m=audio 49170 RTP/AVP 0\r
+i=voice\r
+c=IN IP6 ff15::101/3\r
+c=IN IP4 233.252.0.1/127/2\r
+b=TIAS:64000\r
+k=prompt\r
+m=video 51372/2 RTP/AVP 99\r
+a=rtpmap:99 h263-1998/90000\r
+";
+        let session = SessionDescription::parse(sdp).unwrap();
+        assert_eq!(session.to_string(), sdp);
+        assert_eq!(session.session_name, "Call to John Smith");
+        assert_eq!(session.emails.len(), 1);
+        assert_eq!(
+            session.bandwidths,
+            vec![Bandwidth {
+                bandwidth_type: "AS".to_string(),
+                bandwidth: 128
+            }]
+        );
+        assert_eq!(session.timings.len(), 2);
+        assert_eq!(session.timings[1].repeats, vec!["7d 1h 0 25h"]);
+        assert_eq!(session.get_direction(), Some(Direction::RecvOnly));
+
+        let audio = &session.media[0];
+        assert_eq!(audio.information.as_deref(), Some("voice"));
+        assert_eq!(audio.connections[0].ttl, None);
+        assert_eq!(audio.connections[0].address_count, Some(3));
+        assert_eq!(audio.connections[1].ttl, Some(127));
+        assert_eq!(audio.connections[1].address_count, Some(2));
+        assert_eq!(session.media[1].port_count, Some(2));
+    }
+
+    #[test]
+    fn new_test() {
+        let mut session = SessionDescription::new(Origin::new(1234, 2));
+        session.attributes.push(Attribute::new("group", "BUNDLE 0"));
+        let mut media = MediaDescription::new(
+            MediaKind::Application,
+            9,
+            "UDP/DTLS/SCTP",
+            vec!["webrtc-datachannel".to_string()],
+        );
+        media.connections.push(Connection::new("IP4", "0.0.0.0"));
+        media.attributes.push(Attribute::new("mid", "0"));
+        session.media.push(media);
+        assert_eq!(
+            session.to_string(),
+            "v=0\r
+o=- 1234 2 IN IP4 127.0.0.1\r
+s=-\r
+t=0 0\r
+a=group:BUNDLE 0\r
+m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r
+c=IN IP4 0.0.0.0\r
+a=mid:0\r
+"
+        );
+    }
+
+    #[test]
+    fn invalid_test() {
+        assert_eq!(
+            SessionDescription::parse(""),
+            Err(SdpError::MissingLine { line_type: 'v' })
+        );
+        assert_eq!(
+            SessionDescription::parse("v=0\r\no=- 1 2 IN IP4 127.0.0.1\r\ns=-\r\n"),
+            Err(SdpError::MissingLine { line_type: 't' })
+        );
+        let broken = |sdp: &str| match SessionDescription::parse(sdp) {
+            Err(SdpError::InvalidLine { line, .. }) => line,
+            v => panic!("{:?}", v),
+        };
+        let head = "v=0\r\no=- 1 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\n";
+        assert_eq!(broken("v=1\r\n"), 1);
+        assert_eq!(broken("v=0\r\no=- x 2 IN IP4 127.0.0.1\r\n"), 2);
+        assert_eq!(broken("v=0\r\ns=-\r\n"), 2);
+        assert_eq!(broken(&format!("{}a=mid\r\nt=0 0\r\n", head)), 6);
+        assert_eq!(broken(&format!("{}x\r\n", head)), 5);
+        assert_eq!(broken(&format!("{}m=audio 9\r\n", head)), 5);
+        assert_eq!(
+            broken(&format!("{}m=audio 9 RTP/AVP 0\r\nz=0 0\r\n", head)),
+            6
+        );
+        assert_eq!(broken(&format!("{}a=:x\r\n", head)), 5);
+    }
+}
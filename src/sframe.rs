@@ -0,0 +1,31 @@
+pub mod cipher_suite;
+pub mod context;
+pub mod header;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, SframeError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum SframeError {
+    #[fail(display = "SFrame header is broken.")]
+    InvalidHeader,
+
+    #[fail(display = "SFrame base key length is invalid.")]
+    InvalidKeyLength,
+
+    #[fail(display = "SFrame ciphertext uses an unknown key id {}.", kid)]
+    UnknownKeyId { kid: u64 },
+
+    #[fail(display = "SFrame encryptor has no key.")]
+    MissingKey,
+
+    #[fail(display = "SFrame key is already in use, its counter can not restart.")]
+    KeyReused,
+
+    #[fail(display = "SFrame counter exhausted, rotate the key.")]
+    CounterExhausted,
+
+    #[fail(display = "SFrame authentication failed.")]
+    AuthenticationFailed,
+}
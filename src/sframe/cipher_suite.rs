@@ -0,0 +1,183 @@
+// https://tools.ietf.org/html/rfc9605#section-4.5
+
+use crate::sframe::{Result, SframeError};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha512};
+
+/// The AEAD cipher suites. The AES-CTR with HMAC suites are not supported.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CipherSuite {
+    Aes128GcmSha256_128,
+    Aes256GcmSha512_128,
+}
+
+impl CipherSuite {
+    pub fn get_id(self) -> u16 {
+        match self {
+            CipherSuite::Aes128GcmSha256_128 => 0x0004,
+            CipherSuite::Aes256GcmSha512_128 => 0x0005,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<CipherSuite> {
+        match id {
+            0x0004 => Some(CipherSuite::Aes128GcmSha256_128),
+            0x0005 => Some(CipherSuite::Aes256GcmSha512_128),
+            _ => None,
+        }
+    }
+
+    /// Nk
+    pub fn get_key_length(self) -> usize {
+        match self {
+            CipherSuite::Aes128GcmSha256_128 => 16,
+            CipherSuite::Aes256GcmSha512_128 => 32,
+        }
+    }
+
+    /// Nn
+    pub fn get_nonce_length(self) -> usize {
+        12
+    }
+
+    /// Nt
+    pub fn get_tag_length(self) -> usize {
+        16
+    }
+}
+
+// KID毎にbase_keyから導出したsframe_keyとsframe_salt．
+#[derive(Clone)]
+pub(crate) struct SframeKey {
+    cipher: SframeAead,
+    salt: Vec<u8>,
+}
+
+#[derive(Clone)]
+enum SframeAead {
+    Aes128(Box<Aes128Gcm>),
+    Aes256(Box<Aes256Gcm>),
+}
+
+impl SframeKey {
+    /// RFC 9605 Section 4.4.2
+    pub fn derive(suite: CipherSuite, kid: u64, base_key: &[u8]) -> Result<Self> {
+        if base_key.is_empty() {
+            return Err(SframeError::InvalidKeyLength);
+        }
+
+        let mut key = vec![0; suite.get_key_length()];
+        let mut salt = vec![0; suite.get_nonce_length()];
+        let label = |name: &str| {
+            let mut v = name.as_bytes().to_vec();
+            v.extend_from_slice(&kid.to_be_bytes());
+            v.extend_from_slice(&suite.get_id().to_be_bytes());
+            v
+        };
+        let key_label = label("SFrame 1.0 Secret key ");
+        let salt_label = label("SFrame 1.0 Secret salt ");
+
+        match suite {
+            CipherSuite::Aes128GcmSha256_128 => {
+                let hkdf = Hkdf::<Sha256>::new(Some(&[]), base_key);
+                hkdf.expand(&key_label, &mut key)
+                    .and_then(|_| hkdf.expand(&salt_label, &mut salt))
+            }
+            CipherSuite::Aes256GcmSha512_128 => {
+                let hkdf = Hkdf::<Sha512>::new(Some(&[]), base_key);
+                hkdf.expand(&key_label, &mut key)
+                    .and_then(|_| hkdf.expand(&salt_label, &mut salt))
+            }
+        }
+        .map_err(|_| SframeError::InvalidKeyLength)?;
+
+        let cipher = match suite {
+            CipherSuite::Aes128GcmSha256_128 => SframeAead::Aes128(Box::new(
+                Aes128Gcm::new_from_slice(&key).map_err(|_| SframeError::InvalidKeyLength)?,
+            )),
+            CipherSuite::Aes256GcmSha512_128 => SframeAead::Aes256(Box::new(
+                Aes256Gcm::new_from_slice(&key).map_err(|_| SframeError::InvalidKeyLength)?,
+            )),
+        };
+        Ok(SframeKey { cipher, salt })
+    }
+
+    // nonce = sframe_salt XOR CTR, left padded to Nn.
+    fn get_nonce(&self, ctr: u64) -> Vec<u8> {
+        let mut nonce = self.salt.clone();
+        let offset = nonce.len() - 8;
+        for (v, c) in nonce[offset..].iter_mut().zip(&ctr.to_be_bytes()) {
+            *v ^= c;
+        }
+        nonce
+    }
+
+    pub fn seal(&self, ctr: u64, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = self.get_nonce(ctr);
+        let nonce = Nonce::from_slice(&nonce);
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        match &self.cipher {
+            SframeAead::Aes128(v) => v.encrypt(nonce, payload),
+            SframeAead::Aes256(v) => v.encrypt(nonce, payload),
+        }
+        .map_err(|_| SframeError::AuthenticationFailed)
+    }
+
+    pub fn open(&self, ctr: u64, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = self.get_nonce(ctr);
+        let nonce = Nonce::from_slice(&nonce);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        match &self.cipher {
+            SframeAead::Aes128(v) => v.decrypt(nonce, payload),
+            SframeAead::Aes256(v) => v.decrypt(nonce, payload),
+        }
+        .map_err(|_| SframeError::AuthenticationFailed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // RFC 9605 Appendix C.4, kid 0x123 and ctr 0x4567 of base_key 00..0f.
+    #[test]
+    fn sframe_key_derive_test() {
+        let base_key: Vec<u8> = (0..16).collect();
+        let cases = [
+            (
+                CipherSuite::Aes128GcmSha256_128,
+                [
+                    0x75, 0x23, 0x4e, 0xde, 0xfe, 0x07, 0x81, 0x90, 0x26, 0x75, 0x18, 0x16,
+                ],
+                [
+                    0x75, 0x23, 0x4e, 0xde, 0xfe, 0x07, 0x81, 0x90, 0x26, 0x75, 0x5d, 0x71,
+                ],
+            ),
+            (
+                CipherSuite::Aes256GcmSha512_128,
+                [
+                    0x84, 0x99, 0x1c, 0x16, 0x7b, 0x8c, 0xd2, 0x3c, 0x93, 0x70, 0x8e, 0xc7,
+                ],
+                [
+                    0x84, 0x99, 0x1c, 0x16, 0x7b, 0x8c, 0xd2, 0x3c, 0x93, 0x70, 0xcb, 0xa0,
+                ],
+            ),
+        ];
+        for (suite, salt, nonce) in &cases {
+            let key = SframeKey::derive(*suite, 0x123, &base_key).unwrap();
+            assert_eq!(key.salt, salt);
+            assert_eq!(key.get_nonce(0x4567), nonce);
+        }
+        assert!(SframeKey::derive(CipherSuite::Aes128GcmSha256_128, 0, &[]).is_err());
+    }
+}
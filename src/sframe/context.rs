@@ -0,0 +1,285 @@
+// https://tools.ietf.org/html/rfc9605#section-4.4
+
+/*
+    SFrame ciphertext
+
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |            SFrame header (not encrypted, authenticated)       |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                                                               |
+    |                   encrypted encoded frame                     |
+    |                                                               |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                      authentication tag                       |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    AAD = header || metadata. the metadata (e.g. parts of the RTP header
+    the application wants bound to the frame) is not sent.
+*/
+
+use crate::rtp::frame_transformer::{EncodedFrame, FrameTransformer};
+use crate::sframe::cipher_suite::{CipherSuite, SframeKey};
+use crate::sframe::header::SframeHeader;
+use crate::sframe::{Result, SframeError};
+
+use std::collections::HashMap;
+
+// 送信側．現在のKIDとframe毎に増えるCTRで暗号化する．base_keyはkeyの再設定を見分けるために持つ．
+pub struct SframeEncryptor {
+    suite: CipherSuite,
+    key: Option<(u64, Vec<u8>, SframeKey)>,
+    ctr: u64,
+}
+
+impl SframeEncryptor {
+    pub fn new(suite: CipherSuite) -> Self {
+        SframeEncryptor {
+            suite,
+            key: None,
+            ctr: 0,
+        }
+    }
+
+    pub fn get_kid(&self) -> Option<u64> {
+        self.key.as_ref().map(|(kid, _, _)| *kid)
+    }
+
+    /// Switches to a new key. The counter restarts since the nonce is
+    /// derived per key, so setting the current kid and key again fails
+    /// instead of reusing nonces.
+    pub fn set_key(&mut self, kid: u64, base_key: &[u8]) -> Result<()> {
+        if let Some((current_kid, current_key, _)) = &self.key {
+            if *current_kid == kid && current_key.as_slice() == base_key {
+                return Err(SframeError::KeyReused);
+            }
+        }
+        let key = SframeKey::derive(self.suite, kid, base_key)?;
+        self.key = Some((kid, base_key.to_vec(), key));
+        self.ctr = 0;
+        Ok(())
+    }
+
+    /// Encrypts a whole encoded frame before packetization.
+    pub fn encrypt(&mut self, frame: &[u8], metadata: &[u8]) -> Result<Vec<u8>> {
+        let (kid, _, key) = self.key.as_ref().ok_or(SframeError::MissingKey)?;
+        if self.ctr == u64::MAX {
+            return Err(SframeError::CounterExhausted);
+        }
+
+        let header = SframeHeader::new(*kid, self.ctr).to_bytes();
+        let mut aad = header.clone();
+        aad.extend_from_slice(metadata);
+        let encrypted = key.seal(self.ctr, &aad, frame)?;
+        self.ctr += 1;
+
+        let mut out = header;
+        out.extend_from_slice(&encrypted);
+        Ok(out)
+    }
+}
+
+// 受信側のkey ring．送信者毎のKIDで鍵を選ぶ．
+pub struct SframeDecryptor {
+    suite: CipherSuite,
+    keys: HashMap<u64, SframeKey>,
+}
+
+impl SframeDecryptor {
+    pub fn new(suite: CipherSuite) -> Self {
+        SframeDecryptor {
+            suite,
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn add_key(&mut self, kid: u64, base_key: &[u8]) -> Result<()> {
+        self.keys
+            .insert(kid, SframeKey::derive(self.suite, kid, base_key)?);
+        Ok(())
+    }
+
+    pub fn remove_key(&mut self, kid: u64) {
+        self.keys.remove(&kid);
+    }
+
+    pub fn has_key(&self, kid: u64) -> bool {
+        self.keys.contains_key(&kid)
+    }
+
+    /// Returns the header and the decrypted frame.
+    pub fn decrypt(&self, ciphertext: &[u8], metadata: &[u8]) -> Result<(SframeHeader, Vec<u8>)> {
+        let header = SframeHeader::from_bytes(ciphertext)?;
+        let header_length = header.get_length();
+        if ciphertext.len() < header_length + self.suite.get_tag_length() {
+            return Err(SframeError::InvalidHeader);
+        }
+
+        let key = self
+            .keys
+            .get(&header.kid)
+            .ok_or(SframeError::UnknownKeyId { kid: header.kid })?;
+
+        let mut aad = ciphertext[..header_length].to_vec();
+        aad.extend_from_slice(metadata);
+        let frame = key.open(header.ctr, &aad, &ciphertext[header_length..])?;
+        Ok((header, frame))
+    }
+}
+
+// send transformとして使う場合，失敗したframeは送らない．
+impl FrameTransformer for SframeEncryptor {
+    fn transform(&mut self, mut frame: EncodedFrame) -> Option<EncodedFrame> {
+        frame.data = self.encrypt(&frame.data, &[]).ok()?;
+        Some(frame)
+    }
+}
+
+impl FrameTransformer for SframeDecryptor {
+    fn transform(&mut self, mut frame: EncodedFrame) -> Option<EncodedFrame> {
+        frame.data = self.decrypt(&frame.data, &[]).ok()?.1;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sframe_round_trip_test() {
+        for suite in &[
+            CipherSuite::Aes128GcmSha256_128,
+            CipherSuite::Aes256GcmSha512_128,
+        ] {
+            let mut encryptor = SframeEncryptor::new(*suite);
+            let mut decryptor = SframeDecryptor::new(*suite);
+            assert_eq!(
+                encryptor.encrypt(b"frame", &[]),
+                Err(SframeError::MissingKey)
+            );
+
+            encryptor.set_key(3, b"base key of alice").unwrap();
+            decryptor.add_key(3, b"base key of alice").unwrap();
+
+            let frame = vec![0x42; 100];
+            let first = encryptor.encrypt(&frame, b"metadata").unwrap();
+            let second = encryptor.encrypt(&frame, b"metadata").unwrap();
+            assert_eq!(first[0], 0x30);
+            assert_eq!(second[0], 0x31);
+            assert_eq!(first.len(), 1 + frame.len() + 16);
+            assert_ne!(first[1..], second[1..]);
+
+            let (header, decrypted) = decryptor.decrypt(&second, b"metadata").unwrap();
+            assert_eq!(header, SframeHeader::new(3, 1));
+            assert_eq!(decrypted, frame);
+
+            // the metadata is authenticated.
+            assert_eq!(
+                decryptor.decrypt(&first, b"other").map(|v| v.1),
+                Err(SframeError::AuthenticationFailed)
+            );
+
+            // the counter only restarts with another kid or key.
+            assert_eq!(
+                encryptor.set_key(3, b"base key of alice"),
+                Err(SframeError::KeyReused)
+            );
+            let third = encryptor.encrypt(&frame, b"metadata").unwrap();
+            assert_eq!(third[0], 0x32);
+            encryptor.set_key(4, b"base key of alice").unwrap();
+            assert_eq!(encryptor.encrypt(&frame, &[]).unwrap()[0], 0x40);
+            encryptor.set_key(4, b"next base key").unwrap();
+            assert_eq!(encryptor.get_kid(), Some(4));
+        }
+    }
+
+    fn from_hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // RFC 9605 Appendix C.4
+    #[test]
+    fn sframe_test_vector_test() {
+        let cases = [
+            (
+                CipherSuite::Aes128GcmSha256_128,
+                "9901234567b7412c2513a1b66dbb48841bbaf17f598751176ad847681a69c6d0b091c07018ce4adb34eb",
+            ),
+            (
+                CipherSuite::Aes256GcmSha512_128,
+                "990123456794f509d36e9beacb0e261d99c7d1e972f1fed787d4049f17ca21353c1cc24d56ceabced279",
+            ),
+        ];
+        let base_key = from_hex("000102030405060708090a0b0c0d0e0f");
+        let metadata = b"IETF SFrame WG";
+        let plaintext = b"draft-ietf-sframe-enc";
+
+        for (suite, ciphertext) in &cases {
+            let ciphertext = from_hex(ciphertext);
+            let mut decryptor = SframeDecryptor::new(*suite);
+            decryptor.add_key(0x123, &base_key).unwrap();
+            let (header, frame) = decryptor.decrypt(&ciphertext, metadata).unwrap();
+            assert_eq!(header, SframeHeader::new(0x123, 0x4567));
+            assert_eq!(frame, plaintext);
+
+            let key = SframeKey::derive(*suite, 0x123, &base_key).unwrap();
+            let mut aad = header.to_bytes();
+            aad.extend_from_slice(metadata);
+            assert_eq!(
+                key.seal(0x4567, &aad, plaintext).unwrap(),
+                &ciphertext[header.get_length()..]
+            );
+        }
+    }
+
+    #[test]
+    fn sframe_key_ring_test() {
+        let suite = CipherSuite::Aes128GcmSha256_128;
+        let mut alice = SframeEncryptor::new(suite);
+        let mut bob = SframeEncryptor::new(suite);
+        alice.set_key(1, b"alice").unwrap();
+        bob.set_key(0x1234, b"bob").unwrap();
+
+        let mut decryptor = SframeDecryptor::new(suite);
+        decryptor.add_key(1, b"alice").unwrap();
+
+        let from_bob = bob.encrypt(b"hello", &[]).unwrap();
+        assert_eq!(
+            decryptor.decrypt(&from_bob, &[]).map(|v| v.1),
+            Err(SframeError::UnknownKeyId { kid: 0x1234 })
+        );
+
+        decryptor.add_key(0x1234, b"bob").unwrap();
+        assert_eq!(decryptor.decrypt(&from_bob, &[]).unwrap().1, b"hello");
+
+        let from_alice = alice.encrypt(b"hi", &[]).unwrap();
+        decryptor.remove_key(1);
+        assert!(!decryptor.has_key(1));
+        assert!(decryptor.decrypt(&from_alice, &[]).is_err());
+    }
+
+    #[test]
+    fn sframe_frame_transform_test() {
+        use crate::rtp::frame_transformer::FrameTransformPipeline;
+
+        let suite = CipherSuite::Aes128GcmSha256_128;
+        let mut encryptor = SframeEncryptor::new(suite);
+        let mut decryptor = SframeDecryptor::new(suite);
+        encryptor.set_key(1, b"key").unwrap();
+        decryptor.add_key(1, b"key").unwrap();
+
+        let mut sender = FrameTransformPipeline::new();
+        let mut receiver = FrameTransformPipeline::new();
+        sender.set_send_transform(Some(Box::new(encryptor)));
+        receiver.set_receive_transform(Some(Box::new(decryptor)));
+
+        let frame = EncodedFrame::new(1, 96, 0, vec![1, 2, 3]);
+        let sent = sender.on_outgoing_frame(frame.clone()).unwrap();
+        assert_ne!(sent.data, frame.data);
+        assert_eq!(receiver.on_incoming_frame(sent), Some(frame.clone()));
+        assert_eq!(receiver.on_incoming_frame(frame), None);
+    }
+}
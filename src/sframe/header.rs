@@ -0,0 +1,121 @@
+// https://tools.ietf.org/html/rfc9605#section-4.3
+
+/*
+    SFrame header
+
+     0 1 2 3 4 5 6 7
+    +-+-+-+-+-+-+-+-+---------------------------------+
+    |X|  K  |Y|  C  |   KID... (length=K+1 if X)      |
+    +-+-+-+-+-+-+-+-+---------------------------------+
+    |   CTR... (length=C+1 if Y)                      |
+    +-------------------------------------------------+
+
+    if X is 0, K is the KID itself, otherwise K+1 bytes of KID follow.
+    if Y is 0, C is the CTR itself, otherwise C+1 bytes of CTR follow.
+*/
+
+use crate::sframe::{Result, SframeError};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SframeHeader {
+    pub kid: u64,
+    pub ctr: u64,
+}
+
+fn get_value_length(value: u64) -> usize {
+    (8 - value.leading_zeros() as usize / 8).max(1)
+}
+
+impl SframeHeader {
+    pub fn new(kid: u64, ctr: u64) -> Self {
+        SframeHeader { kid, ctr }
+    }
+
+    pub fn get_length(&self) -> usize {
+        let mut length = 1;
+        if self.kid > 7 {
+            length += get_value_length(self.kid);
+        }
+        if self.ctr > 7 {
+            length += get_value_length(self.ctr);
+        }
+        length
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![0];
+
+        if self.kid > 7 {
+            let length = get_value_length(self.kid);
+            out[0] |= 0x80 | ((length as u8 - 1) << 4);
+            out.extend_from_slice(&self.kid.to_be_bytes()[8 - length..]);
+        } else {
+            out[0] |= (self.kid as u8) << 4;
+        }
+
+        if self.ctr > 7 {
+            let length = get_value_length(self.ctr);
+            out[0] |= 0x08 | (length as u8 - 1);
+            out.extend_from_slice(&self.ctr.to_be_bytes()[8 - length..]);
+        } else {
+            out[0] |= self.ctr as u8;
+        }
+
+        out
+    }
+
+    /// Parses the header at the start of `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SframeHeader> {
+        let config = *bytes.first().ok_or(SframeError::InvalidHeader)?;
+        let mut offset = 1;
+
+        let mut read = |extended: bool, value: u8| -> Result<u64> {
+            if !extended {
+                return Ok(u64::from(value));
+            }
+            let length = usize::from(value) + 1;
+            let field = bytes
+                .get(offset..offset + length)
+                .ok_or(SframeError::InvalidHeader)?;
+            offset += length;
+            Ok(field.iter().fold(0, |v, b| (v << 8) | u64::from(*b)))
+        };
+
+        let kid = read(config & 0x80 > 0, (config >> 4) & 0x07)?;
+        let ctr = read(config & 0x08 > 0, config & 0x07)?;
+        Ok(SframeHeader { kid, ctr })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sframe_header_test() {
+        let cases: Vec<(u64, u64, Vec<u8>)> = vec![
+            (0, 0, vec![0x00]),
+            (7, 7, vec![0x77]),
+            (8, 0, vec![0x80, 0x08]),
+            (0, 8, vec![0x08, 0x08]),
+            (0x0102, 0x030405, vec![0x9a, 0x01, 0x02, 0x03, 0x04, 0x05]),
+            (
+                u64::MAX,
+                1,
+                vec![0xf1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+        ];
+
+        for (kid, ctr, bytes) in cases {
+            let header = SframeHeader::new(kid, ctr);
+            assert_eq!(header.to_bytes(), bytes);
+            assert_eq!(header.get_length(), bytes.len());
+            assert_eq!(SframeHeader::from_bytes(&bytes).unwrap(), header);
+        }
+
+        assert_eq!(
+            SframeHeader::from_bytes(&[0x9a, 0x01]),
+            Err(SframeError::InvalidHeader)
+        );
+    }
+}
@@ -0,0 +1,4 @@
+pub mod audio_selector;
+pub mod feedback_aggregator;
+pub mod rtx_responder;
+pub mod stream_rewriter;
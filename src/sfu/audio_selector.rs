@@ -0,0 +1,251 @@
+use crate::rtp::audio_level::AudioLevelObserver;
+use crate::rtp::packet::RtpPacket;
+use crate::sfu::stream_rewriter::RtpStreamRewriter;
+use crate::time::Instant;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Reported when the speaker forwarded on an outgoing slot changes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SpeakerSwitch {
+    pub slot: usize,
+    pub out_ssrc: u32,
+    pub previous: Option<u32>,
+    pub current: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct ForwardingSlot {
+    rewriter: RtpStreamRewriter,
+    assigned_at: Option<Instant>,
+}
+
+// subscriber毎に，声の大きいN人だけを固定のN本のSSRCで転送する．
+#[derive(Debug, Clone)]
+pub struct TopNAudioSelector {
+    slots: Vec<ForwardingSlot>,
+    excluded: HashSet<u32>,
+    min_hold: Duration,
+}
+
+impl TopNAudioSelector {
+    /// `out_ssrcs` are the SSRCs announced to the subscriber, one per slot.
+    /// A speaker keeps its slot for at least `min_hold` unless it goes silent.
+    pub fn new(out_ssrcs: &[u32], clock_rate: u32, min_hold: Duration) -> Self {
+        let slots = out_ssrcs
+            .iter()
+            .map(|ssrc| ForwardingSlot {
+                rewriter: RtpStreamRewriter::new(*ssrc, clock_rate),
+                assigned_at: None,
+            })
+            .collect();
+
+        TopNAudioSelector {
+            slots,
+            excluded: HashSet::new(),
+            min_hold,
+        }
+    }
+
+    /// Never select `ssrc`, e.g. the subscriber's own microphone.
+    pub fn exclude(&mut self, ssrc: u32) {
+        self.excluded.insert(ssrc);
+    }
+
+    pub fn get_selected(&self) -> Vec<u32> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.rewriter.get_source())
+            .collect()
+    }
+
+    pub fn update(
+        &mut self,
+        observer: &mut AudioLevelObserver,
+        now: Instant,
+    ) -> Vec<SpeakerSwitch> {
+        let audible: Vec<u32> = observer
+            .get_loudest(usize::MAX, now)
+            .into_iter()
+            .filter(|ssrc| !self.excluded.contains(ssrc))
+            .collect();
+        let top = &audible[..audible.len().min(self.slots.len())];
+
+        let mut switches = Vec::new();
+
+        // speakers that went silent free their slot immediately.
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(source) = slot.rewriter.get_source() {
+                if !audible.contains(&source) {
+                    slot.rewriter.switch_source(None);
+                    slot.assigned_at = None;
+                    switches.push(SpeakerSwitch {
+                        slot: i,
+                        out_ssrc: slot.rewriter.get_out_ssrc(),
+                        previous: Some(source),
+                        current: None,
+                    });
+                }
+            }
+        }
+
+        let selected = self.get_selected();
+        for entrant in top.iter().filter(|ssrc| !selected.contains(ssrc)) {
+            let free = self
+                .slots
+                .iter()
+                .position(|s| s.rewriter.get_source().is_none());
+
+            // otherwise evict the quietest speaker that dropped out of the top N
+            // and has held its slot long enough.
+            let slot_index = free.or_else(|| {
+                self.slots
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| {
+                        let source = s.rewriter.get_source();
+                        let held = s
+                            .assigned_at
+                            .map(|t| now.duration_since(t) >= self.min_hold)
+                            .unwrap_or(true);
+                        held && source.map(|v| !top.contains(&v)).unwrap_or(false)
+                    })
+                    .max_by_key(|(_, s)| {
+                        s.rewriter
+                            .get_source()
+                            .and_then(|v| audible.iter().position(|a| *a == v))
+                    })
+                    .map(|(i, _)| i)
+            });
+
+            if let Some(i) = slot_index {
+                let slot = &mut self.slots[i];
+                let previous = slot.rewriter.get_source();
+                slot.rewriter.switch_source(Some(*entrant));
+                slot.assigned_at = Some(now);
+                switches.push(SpeakerSwitch {
+                    slot: i,
+                    out_ssrc: slot.rewriter.get_out_ssrc(),
+                    previous,
+                    current: Some(*entrant),
+                });
+            }
+        }
+
+        switches
+    }
+
+    /// Rewrites `packet` onto its slot if its source is currently selected.
+    pub fn forward(&mut self, packet: &RtpPacket, now: Instant) -> Option<RtpPacket> {
+        let ssrc = packet.get_header().get_ssrc();
+        self.slots
+            .iter_mut()
+            .find(|slot| slot.rewriter.get_source() == Some(ssrc))
+            .and_then(|slot| slot.rewriter.rewrite(packet, now))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::audio_level::AudioLevel;
+    use crate::rtp::packet::RtpHeader;
+
+    fn level(level: u8) -> AudioLevel {
+        AudioLevel {
+            voice_activity: true,
+            level,
+        }
+    }
+
+    #[test]
+    fn select_loudest_test() {
+        let now = Instant::now();
+        let mut observer = AudioLevelObserver::new(Duration::from_millis(500));
+        let mut selector = TopNAudioSelector::new(&[100, 200], 48000, Duration::from_secs(1));
+        selector.exclude(4);
+
+        observer.observe(1, level(50), now);
+        observer.observe(2, level(20), now);
+        observer.observe(3, level(90), now);
+        observer.observe(4, level(0), now);
+
+        let switches = selector.update(&mut observer, now);
+        assert_eq!(switches.len(), 2);
+        assert_eq!(selector.get_selected(), vec![2, 1]);
+
+        let packet = RtpPacket::new(RtpHeader::new(111, 1, 0, 1), vec![]);
+        let out = selector.forward(&packet, now).unwrap();
+        assert_eq!(out.get_header().get_ssrc(), 200);
+
+        let packet = RtpPacket::new(RtpHeader::new(111, 1, 0, 3), vec![]);
+        assert!(selector.forward(&packet, now).is_none());
+    }
+
+    #[test]
+    fn silent_speaker_test() {
+        let now = Instant::now();
+        let mut observer = AudioLevelObserver::new(Duration::from_millis(500));
+        let mut selector = TopNAudioSelector::new(&[100], 48000, Duration::from_secs(1));
+
+        observer.observe(1, level(50), now);
+        selector.update(&mut observer, now);
+        assert_eq!(selector.get_selected(), vec![1]);
+
+        // the speaker keeps sending packets, but only silence.
+        let later = now + Duration::from_millis(600);
+        observer.observe(
+            1,
+            AudioLevel {
+                voice_activity: false,
+                level: 127,
+            },
+            later,
+        );
+        let switches = selector.update(&mut observer, later);
+        assert_eq!(
+            switches,
+            vec![SpeakerSwitch {
+                slot: 0,
+                out_ssrc: 100,
+                previous: Some(1),
+                current: None,
+            }]
+        );
+        assert!(selector.get_selected().is_empty());
+    }
+
+    #[test]
+    fn hold_before_switch_test() {
+        let now = Instant::now();
+        let mut observer = AudioLevelObserver::new(Duration::from_millis(500));
+        let mut selector = TopNAudioSelector::new(&[100], 48000, Duration::from_secs(1));
+
+        observer.observe(1, level(50), now);
+        selector.update(&mut observer, now);
+        assert_eq!(selector.get_selected(), vec![1]);
+
+        // a louder speaker appears, but the current one has not held long enough.
+        let t1 = now + Duration::from_millis(200);
+        observer.observe(1, level(50), t1);
+        observer.observe(2, level(10), t1);
+        observer.observe(2, level(10), t1);
+        assert!(selector.update(&mut observer, t1).is_empty());
+        assert_eq!(selector.get_selected(), vec![1]);
+
+        let t2 = now + Duration::from_millis(1100);
+        observer.observe(1, level(50), t2);
+        observer.observe(2, level(10), t2);
+        let switches = selector.update(&mut observer, t2);
+        assert_eq!(
+            switches,
+            vec![SpeakerSwitch {
+                slot: 0,
+                out_ssrc: 100,
+                previous: Some(1),
+                current: Some(2),
+            }]
+        );
+    }
+}
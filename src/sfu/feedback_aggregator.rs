@@ -0,0 +1,228 @@
+use crate::rtcp::packet::{RtcpPacket, RtcpPacketType};
+use crate::rtcp::payload_specific_feedback::{
+    RtcpPayloadSpecificFeedbackPacket, PSFB_AFB, PSFB_FIR, PSFB_PLI,
+};
+use crate::rtcp::remb::RtcpRembPacket;
+use crate::time::Instant;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How the subscribers' estimates are folded into one value.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum AggregationPolicy {
+    Minimum,
+    /// The n-th percentile (0..=100) of the subscribers' estimates.
+    Percentile(u8),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct FeedbackAggregatorConfig {
+    pub policy: AggregationPolicy,
+    /// Estimates older than this are ignored.
+    pub estimate_timeout: Duration,
+    /// REMB is resent at least this often, and immediately on a decrease.
+    pub remb_interval: Duration,
+    /// Keyframe requests toward the publisher are sent at most this often.
+    pub keyframe_interval: Duration,
+}
+
+impl Default for FeedbackAggregatorConfig {
+    fn default() -> Self {
+        FeedbackAggregatorConfig {
+            policy: AggregationPolicy::Minimum,
+            estimate_timeout: Duration::from_secs(3),
+            remb_interval: Duration::from_secs(1),
+            keyframe_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+// 一つのpublisherに対する全subscriberのfeedbackをまとめて，publisher向けのfeedbackを作る．
+#[derive(Debug, Clone)]
+pub struct FeedbackAggregator {
+    config: FeedbackAggregatorConfig,
+    media_ssrcs: Vec<u32>,
+    estimates: HashMap<u32, (u64, Instant)>,
+    last_remb: Option<(u64, Instant)>,
+    keyframe_pending: bool,
+    last_keyframe_request: Option<Instant>,
+}
+
+impl FeedbackAggregator {
+    /// `media_ssrcs` are the publisher's SSRCs the feedback is about.
+    pub fn new(config: FeedbackAggregatorConfig, media_ssrcs: Vec<u32>) -> Self {
+        FeedbackAggregator {
+            config,
+            media_ssrcs,
+            estimates: HashMap::new(),
+            last_remb: None,
+            keyframe_pending: false,
+            last_keyframe_request: None,
+        }
+    }
+
+    /// Records a bandwidth estimate (bps) for the subscriber identified by
+    /// its RTCP sender SSRC, e.g. from a REMB or a local TWCC estimator.
+    pub fn on_estimate(&mut self, subscriber: u32, bitrate: u64, now: Instant) {
+        self.estimates.insert(subscriber, (bitrate, now));
+    }
+
+    pub fn on_keyframe_request(&mut self, _subscriber: u32) {
+        self.keyframe_pending = true;
+    }
+
+    pub fn remove_subscriber(&mut self, subscriber: u32) {
+        self.estimates.remove(&subscriber);
+    }
+
+    /// Consumes the feedback part of a subscriber's RTCP packet.
+    pub fn on_rtcp(&mut self, packet: &RtcpPacket, now: Instant) {
+        if let RtcpPacketType::PayloadSpecificFeedback(v) = packet.get_packet() {
+            match v.get_format() {
+                PSFB_PLI | PSFB_FIR => self.on_keyframe_request(v.get_ssrc()),
+                PSFB_AFB => {
+                    if let Ok(remb) = RtcpRembPacket::from_psfb(v) {
+                        self.on_estimate(remb.get_ssrc(), remb.get_bitrate(), now);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    pub fn get_estimate(&mut self, now: Instant) -> Option<u64> {
+        let timeout = self.config.estimate_timeout;
+        self.estimates
+            .retain(|_, (_, t)| now.duration_since(*t) <= timeout);
+
+        let mut values: Vec<u64> = self.estimates.values().map(|(v, _)| *v).collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+
+        let index = match self.config.policy {
+            AggregationPolicy::Minimum => 0,
+            AggregationPolicy::Percentile(p) => (values.len() - 1) * usize::from(p.min(100)) / 100,
+        };
+        Some(values[index])
+    }
+
+    /// Returns the feedback to send to the publisher now: at most one REMB
+    /// and at most one keyframe request however many subscribers asked.
+    pub fn poll_feedback(&mut self, sender_ssrc: u32, now: Instant) -> Vec<RtcpPacket> {
+        let mut out = Vec::new();
+
+        if let Some(bitrate) = self.get_estimate(now) {
+            let due = match self.last_remb {
+                Some((last, t)) => {
+                    bitrate < last || now.duration_since(t) >= self.config.remb_interval
+                }
+                None => true,
+            };
+            if due {
+                let remb = RtcpRembPacket::new(sender_ssrc, bitrate, self.media_ssrcs.clone());
+                if let Ok(psfb) = remb.to_psfb() {
+                    out.push(RtcpPacket::new(RtcpPacketType::PayloadSpecificFeedback(
+                        psfb,
+                    )));
+                    self.last_remb = Some((bitrate, now));
+                }
+            }
+        }
+
+        let allowed = self
+            .last_keyframe_request
+            .map(|t| now.duration_since(t) >= self.config.keyframe_interval)
+            .unwrap_or(true);
+        if self.keyframe_pending && allowed {
+            for media_ssrc in &self.media_ssrcs {
+                out.push(RtcpPacket::new(RtcpPacketType::PayloadSpecificFeedback(
+                    RtcpPayloadSpecificFeedbackPacket::new(
+                        PSFB_PLI,
+                        sender_ssrc,
+                        *media_ssrc,
+                        vec![],
+                    ),
+                )));
+            }
+            self.keyframe_pending = false;
+            self.last_keyframe_request = Some(now);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aggregate_estimates_test() {
+        let now = Instant::now();
+        let mut config = FeedbackAggregatorConfig::default();
+        let mut aggregator = FeedbackAggregator::new(config, vec![10]);
+
+        assert_eq!(aggregator.get_estimate(now), None);
+
+        aggregator.on_estimate(1, 300_000, now);
+        aggregator.on_estimate(2, 1_000_000, now);
+        aggregator.on_estimate(3, 2_000_000, now);
+        assert_eq!(aggregator.get_estimate(now), Some(300_000));
+
+        config.policy = AggregationPolicy::Percentile(50);
+        let mut aggregator = FeedbackAggregator::new(config, vec![10]);
+        aggregator.on_estimate(1, 300_000, now);
+        aggregator.on_estimate(2, 1_000_000, now);
+        aggregator.on_estimate(3, 2_000_000, now);
+        assert_eq!(aggregator.get_estimate(now), Some(1_000_000));
+
+        // stale estimates are dropped.
+        let later = now + Duration::from_secs(4);
+        aggregator.on_estimate(3, 2_000_000, later);
+        assert_eq!(aggregator.get_estimate(later), Some(2_000_000));
+    }
+
+    #[test]
+    fn deduplicate_keyframe_request_test() {
+        let now = Instant::now();
+        let mut aggregator = FeedbackAggregator::new(FeedbackAggregatorConfig::default(), vec![10]);
+
+        for subscriber in 1..5 {
+            let pli = RtcpPacket::new(RtcpPacketType::PayloadSpecificFeedback(
+                RtcpPayloadSpecificFeedbackPacket::new(PSFB_PLI, subscriber, 10, vec![]),
+            ));
+            aggregator.on_rtcp(&pli, now);
+        }
+        assert_eq!(aggregator.poll_feedback(99, now).len(), 1);
+
+        // a new request inside the interval waits.
+        aggregator.on_keyframe_request(1);
+        assert!(aggregator.poll_feedback(99, now).is_empty());
+        let later = now + Duration::from_secs(1);
+        assert_eq!(aggregator.poll_feedback(99, later).len(), 1);
+    }
+
+    #[test]
+    fn remb_on_decrease_test() {
+        let now = Instant::now();
+        let mut aggregator = FeedbackAggregator::new(FeedbackAggregatorConfig::default(), vec![10]);
+
+        aggregator.on_estimate(1, 1_000_000, now);
+        assert_eq!(aggregator.poll_feedback(99, now).len(), 1);
+        assert!(aggregator.poll_feedback(99, now).is_empty());
+
+        aggregator.on_estimate(2, 500_000, now);
+        let out = aggregator.poll_feedback(99, now);
+        match out[0].get_packet() {
+            RtcpPacketType::PayloadSpecificFeedback(v) => {
+                let remb = RtcpRembPacket::from_psfb(v).unwrap();
+                assert_eq!(remb.get_bitrate(), 500_000);
+                assert_eq!(remb.get_ssrcs(), &[10]);
+            }
+            _ => panic!("not a psfb packet"),
+        }
+    }
+}
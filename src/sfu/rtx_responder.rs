@@ -0,0 +1,154 @@
+use crate::rtcp::packet::{RtcpPacket, RtcpPacketType};
+use crate::rtcp::rtp_feedback::{RtcpRtpFeedbackPacket, RTPFB_NACK};
+use crate::rtp::packet::RtpPacket;
+use crate::rtp::packet_history::RtpPacketHistory;
+use crate::rtp::rtx::RtxEncoder;
+use crate::time::Instant;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct RtxResponderConfig {
+    pub capacity: usize,
+    pub max_age: Duration,
+    /// The same packet is not resent twice within this interval.
+    pub min_resend_interval: Duration,
+}
+
+impl Default for RtxResponderConfig {
+    fn default() -> Self {
+        RtxResponderConfig {
+            capacity: 1024,
+            max_age: Duration::from_secs(1),
+            min_resend_interval: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct RtxResponse {
+    /// Retransmissions to send to the subscriber.
+    pub packets: Vec<RtpPacket>,
+    /// Sequence numbers not in the cache, candidates for an upstream NACK.
+    pub missing: Vec<u16>,
+}
+
+#[derive(Debug, Clone)]
+struct ResponderStream {
+    history: RtpPacketHistory,
+    rtx: Option<RtxEncoder>,
+}
+
+// subscriberからのNACKに，SFUが転送済みのpacketから直接応答する．
+// 書き換え後のpacketを保持するので，sequence numberはsubscriberから見たものになる．
+#[derive(Debug, Clone)]
+pub struct RtxResponder {
+    config: RtxResponderConfig,
+    streams: HashMap<u32, ResponderStream>,
+}
+
+impl RtxResponder {
+    pub fn new(config: RtxResponderConfig) -> Self {
+        RtxResponder {
+            config,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Registers an outgoing stream. Without an RTX encoder, lost packets
+    /// are resent as-is on the media SSRC.
+    pub fn add_stream(&mut self, ssrc: u32, rtx: Option<RtxEncoder>) {
+        let history = RtpPacketHistory::new(self.config.capacity, self.config.max_age);
+        self.streams.insert(ssrc, ResponderStream { history, rtx });
+    }
+
+    pub fn remove_stream(&mut self, ssrc: u32) {
+        self.streams.remove(&ssrc);
+    }
+
+    /// Stores a packet that was forwarded to the subscriber.
+    pub fn on_forwarded(&mut self, packet: &RtpPacket, now: Instant) {
+        if let Some(stream) = self.streams.get_mut(&packet.get_header().get_ssrc()) {
+            stream.history.insert(packet.clone(), now);
+        }
+    }
+
+    pub fn on_nack(&mut self, nack: &RtcpRtpFeedbackPacket, now: Instant) -> RtxResponse {
+        let mut response = RtxResponse::default();
+
+        let stream = match self.streams.get_mut(&nack.get_media_ssrc()) {
+            Some(v) => v,
+            None => return response,
+        };
+
+        for sequence in nack.get_lost() {
+            if stream.history.get(*sequence).is_none() {
+                response.missing.push(*sequence);
+                continue;
+            }
+
+            let packet =
+                match stream
+                    .history
+                    .get_for_resend(*sequence, self.config.min_resend_interval, now)
+                {
+                    Some(v) => v,
+                    None => continue,
+                };
+
+            let out = match stream.rtx {
+                Some(ref mut rtx) => rtx.encapsulate(packet),
+                None => Some(packet.clone()),
+            };
+            if let Some(v) = out {
+                response.packets.push(v);
+            }
+        }
+
+        response
+    }
+
+    pub fn on_rtcp(&mut self, packet: &RtcpPacket, now: Instant) -> Option<RtxResponse> {
+        match packet.get_packet() {
+            RtcpPacketType::RTPFeedback(v) if v.get_format() == RTPFB_NACK => {
+                Some(self.on_nack(v, now))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::packet::RtpHeader;
+
+    #[test]
+    fn answer_nack_from_cache_test() {
+        let now = Instant::now();
+        let mut responder = RtxResponder::new(RtxResponderConfig::default());
+
+        let mut payload_types = HashMap::new();
+        payload_types.insert(96, 97);
+        responder.add_stream(1000, Some(RtxEncoder::new(2000, payload_types, 0)));
+
+        for sequence in 10..20 {
+            let packet = RtpPacket::new(RtpHeader::new(96, sequence, 0, 1000), vec![0xaa]);
+            responder.on_forwarded(&packet, now);
+        }
+
+        let nack = RtcpRtpFeedbackPacket::new(RTPFB_NACK, 1, 1000, Some(vec![12, 15, 30]));
+        let response = responder.on_nack(&nack, now);
+
+        assert_eq!(response.missing, vec![30]);
+        assert_eq!(response.packets.len(), 2);
+        assert_eq!(response.packets[0].get_header().get_ssrc(), 2000);
+        assert_eq!(response.packets[0].get_payload(), &[0x00, 12, 0xaa]);
+        assert_eq!(response.packets[1].get_header().get_sequence_number(), 1);
+
+        // repeated NACK right away is not answered again.
+        let response = responder.on_nack(&nack, now);
+        assert!(response.packets.is_empty());
+    }
+}
@@ -0,0 +1,148 @@
+use crate::rtp::packet::RtpPacket;
+use crate::time::Instant;
+
+// 複数の送信元を一つの送信ストリームにまとめるため，SSRC/sequence/timestampを書き換える．
+// 送信元が切り替わっても，受信側からは連続した一つのストリームに見える．
+#[derive(Debug, Clone)]
+pub struct RtpStreamRewriter {
+    out_ssrc: u32,
+    clock_rate: u32,
+    source: Option<u32>,
+    switching: bool,
+    first_sequence: u16,
+    sequence_offset: u16,
+    timestamp_offset: u32,
+    last_out: Option<(u16, u32, Instant)>,
+}
+
+impl RtpStreamRewriter {
+    pub fn new(out_ssrc: u32, clock_rate: u32) -> Self {
+        RtpStreamRewriter {
+            out_ssrc,
+            clock_rate,
+            source: None,
+            switching: false,
+            first_sequence: 0,
+            sequence_offset: 0,
+            timestamp_offset: 0,
+            last_out: None,
+        }
+    }
+
+    pub fn get_out_ssrc(&self) -> u32 {
+        self.out_ssrc
+    }
+
+    pub fn get_source(&self) -> Option<u32> {
+        self.source
+    }
+
+    /// Starts forwarding `source` on this stream. The offsets are computed from
+    /// the first packet received from the new source.
+    pub fn switch_source(&mut self, source: Option<u32>) {
+        if self.source != source {
+            self.source = source;
+            self.switching = source.is_some();
+        }
+    }
+
+    pub fn rewrite(&mut self, packet: &RtpPacket, now: Instant) -> Option<RtpPacket> {
+        let header = packet.get_header();
+        if self.source != Some(header.get_ssrc()) {
+            return None;
+        }
+
+        let in_sequence = header.get_sequence_number();
+        let in_timestamp = header.get_timestamp();
+        let switched = self.switching;
+
+        if self.switching {
+            let (sequence, timestamp) = match self.last_out {
+                Some((last_sequence, last_timestamp, last_time)) => {
+                    let elapsed = now.duration_since(last_time);
+                    let ticks = (elapsed.as_micros() * u128::from(self.clock_rate) / 1_000_000)
+                        .max(1) as u32;
+                    (
+                        last_sequence.wrapping_add(1),
+                        last_timestamp.wrapping_add(ticks),
+                    )
+                }
+                None => (in_sequence, in_timestamp),
+            };
+
+            self.first_sequence = in_sequence;
+            self.sequence_offset = sequence.wrapping_sub(in_sequence);
+            self.timestamp_offset = timestamp.wrapping_sub(in_timestamp);
+            self.switching = false;
+        } else if (in_sequence.wrapping_sub(self.first_sequence) as i16) < 0 {
+            // sent before the switch, these numbers belong to the previous source.
+            return None;
+        }
+
+        let out_sequence = in_sequence.wrapping_add(self.sequence_offset);
+        let out_timestamp = in_timestamp.wrapping_add(self.timestamp_offset);
+
+        let newest = match self.last_out {
+            Some((last_sequence, _, _)) => {
+                switched || (out_sequence.wrapping_sub(last_sequence) as i16) > 0
+            }
+            None => true,
+        };
+        if newest {
+            self.last_out = Some((out_sequence, out_timestamp, now));
+        }
+
+        let mut out = packet.clone();
+        let out_header = out.get_header_mut();
+        out_header.set_ssrc(self.out_ssrc);
+        out_header.set_sequence_number(out_sequence);
+        out_header.set_timestamp(out_timestamp);
+        if switched {
+            out_header.set_marker(true);
+        }
+
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rtp::packet::RtpHeader;
+    use std::time::Duration;
+
+    fn packet(ssrc: u32, sequence: u16, timestamp: u32) -> RtpPacket {
+        RtpPacket::new(RtpHeader::new(111, sequence, timestamp, ssrc), vec![0xff])
+    }
+
+    #[test]
+    fn continuous_across_switch_test() {
+        let now = Instant::now();
+        let mut rewriter = RtpStreamRewriter::new(1000, 48000);
+
+        assert!(rewriter.rewrite(&packet(1, 10, 100), now).is_none());
+
+        rewriter.switch_source(Some(1));
+        let out = rewriter.rewrite(&packet(1, 10, 100), now).unwrap();
+        assert_eq!(out.get_header().get_ssrc(), 1000);
+        assert_eq!(out.get_header().get_sequence_number(), 10);
+        assert!(out.get_header().get_marker());
+
+        let out = rewriter.rewrite(&packet(1, 11, 1060), now).unwrap();
+        assert_eq!(out.get_header().get_sequence_number(), 11);
+        assert!(!out.get_header().get_marker());
+
+        rewriter.switch_source(Some(2));
+        assert!(rewriter.rewrite(&packet(1, 12, 2020), now).is_none());
+
+        let later = now + Duration::from_millis(20);
+        let out = rewriter.rewrite(&packet(2, 60000, 7), later).unwrap();
+        assert_eq!(out.get_header().get_ssrc(), 1000);
+        assert_eq!(out.get_header().get_sequence_number(), 12);
+        assert_eq!(out.get_header().get_timestamp(), 1060 + 960);
+        assert!(out.get_header().get_marker());
+
+        // reordered packet from before the switch point is dropped.
+        assert!(rewriter.rewrite(&packet(2, 59999, 0), later).is_none());
+    }
+}
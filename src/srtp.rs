@@ -0,0 +1,80 @@
+pub mod cipher;
+pub mod context;
+pub mod cryptex;
+pub mod dtls_srtp;
+pub mod ekt;
+pub mod key_derivation;
+pub mod protection_profile;
+pub mod replay_detector;
+pub mod sdes;
+
+use crate::OctetsError;
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, SrtpError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum SrtpError {
+    #[fail(display = "Octets manipulate failed: {:?}", error)]
+    OctetsError { error: OctetsError },
+
+    #[fail(display = "SRTP master key or salt length is invalid.")]
+    InvalidKeyLength,
+
+    #[fail(display = "SRTP key derivation rate must be 0 or a power of 2 up to 2^24.")]
+    InvalidKeyDerivationRate,
+
+    #[fail(display = "DTLS did not negotiate a supported SRTP protection profile.")]
+    UnsupportedProtectionProfile,
+
+    #[fail(display = "DTLS keying material export failed.")]
+    KeyingMaterialExportFailed,
+
+    #[fail(display = "a=crypto attribute is broken.")]
+    InvalidCryptoAttribute,
+
+    #[fail(display = "SRTP MKI length does not match the context.")]
+    InvalidMki,
+
+    #[fail(display = "SRTP packet carries an unknown MKI.")]
+    UnknownMki,
+
+    #[fail(display = "EKTField is broken.")]
+    InvalidEktField,
+
+    #[fail(display = "EKTField has an unknown security parameter index.")]
+    UnknownEktSpi,
+
+    #[fail(display = "EKTPlaintext carries the SSRC of another sender.")]
+    EktSsrcMismatch,
+
+    #[fail(display = "EKTPlaintext carries a ROC behind the SRTP context.")]
+    EktRocMismatch,
+
+    #[fail(display = "No SRTP master key was received with EKT for the SSRC.")]
+    MissingEktMasterKey,
+
+    #[fail(display = "SRTP packet is too short.")]
+    PacketTooShort,
+
+    #[fail(display = "RTP header of the SRTP packet is broken.")]
+    InvalidPacketHeader,
+
+    #[fail(display = "Cryptex only supports the RFC 8285 header extension profiles.")]
+    UnsupportedHeaderExtensionProfile,
+
+    #[fail(display = "SRTP authentication failed.")]
+    AuthenticationFailed,
+
+    #[fail(display = "SRTP packet is a replay of an accepted packet.")]
+    DuplicatedPacket,
+
+    #[fail(display = "SRTP packet is behind the replay window.")]
+    PacketTooOld,
+}
+
+impl From<OctetsError> for SrtpError {
+    fn from(error: OctetsError) -> Self {
+        SrtpError::OctetsError { error }
+    }
+}
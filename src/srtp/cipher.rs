@@ -0,0 +1,129 @@
+pub mod aead_aes_gcm;
+pub mod aes_cm_hmac_sha1;
+
+use crate::srtp::cipher::aead_aes_gcm::CipherAeadAesGcm;
+use crate::srtp::cipher::aes_cm_hmac_sha1::CipherAesCmHmacSha1;
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::{Result, SrtpError};
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+
+// 各protection profileのpacket単位の暗号化と認証．indexの管理はcontextが行う．
+pub(crate) trait SrtpCipher {
+    fn get_rtp_auth_tag_length(&self) -> usize;
+
+    /// Encrypts the payload following `header_length` bytes of header and
+    /// appends the auth tag. With `cryptex` the CSRCs and the header
+    /// extension data are encrypted too.
+    fn encrypt_rtp(
+        &self,
+        packet: &[u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        roc: u32,
+        sequence: u16,
+    ) -> Result<Vec<u8>>;
+
+    /// Verifies the auth tag and decrypts, returning the plain RTP packet.
+    fn decrypt_rtp(
+        &self,
+        packet: &[u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        roc: u32,
+        sequence: u16,
+    ) -> Result<Vec<u8>>;
+
+    fn get_rtcp_auth_tag_length(&self) -> usize;
+
+    /// Returns where the E flag and SRTCP index start in a `length` bytes
+    /// SRTCP packet.
+    fn get_rtcp_index_offset(&self, length: usize) -> usize {
+        length - self.get_rtcp_auth_tag_length() - 4
+    }
+
+    /// Encrypts everything after the first 8 bytes and appends the E flag,
+    /// the 31bit SRTCP `index` and the auth tag.
+    fn encrypt_rtcp(&self, packet: &[u8], ssrc: u32, index: u32) -> Result<Vec<u8>>;
+
+    /// Verifies the auth tag and decrypts. `packet` still carries the
+    /// trailing E flag and index, which are stripped from the result.
+    fn decrypt_rtcp(
+        &self,
+        packet: &[u8],
+        ssrc: u32,
+        index: u32,
+        encrypted: bool,
+    ) -> Result<Vec<u8>>;
+}
+
+/// Derives the session keys of `profile` for `index DIV kdr`.
+pub(crate) fn new_cipher(
+    profile: ProtectionProfile,
+    master_key: &[u8],
+    master_salt: &[u8],
+    index_over_kdr: u64,
+) -> Result<Box<dyn SrtpCipher>> {
+    Ok(match profile {
+        ProtectionProfile::AeadAes128Gcm | ProtectionProfile::AeadAes256Gcm => Box::new(
+            CipherAeadAesGcm::new(profile, master_key, master_salt, index_over_kdr)?,
+        ),
+        // the null profiles share the HMAC framing and skip the keystream.
+        _ => Box::new(CipherAesCmHmacSha1::new(
+            profile,
+            master_key,
+            master_salt,
+            index_over_kdr,
+        )?),
+    })
+}
+
+/// XORs the AES counter mode keystream starting at `iv` into `data`.
+pub(crate) fn aes_cm_xor(key: &[u8], iv: &[u8; 16], data: &mut [u8]) -> Result<()> {
+    match key.len() {
+        16 => Aes128Ctr::new(key.into(), iv.into()).apply_keystream(data),
+        32 => Aes256Ctr::new(key.into(), iv.into()).apply_keystream(data),
+        _ => return Err(SrtpError::InvalidKeyLength),
+    }
+    Ok(())
+}
+
+pub(crate) fn aes_cm_keystream(key: &[u8], iv: &[u8; 16], out: &mut [u8]) -> Result<()> {
+    for b in out.iter_mut() {
+        *b = 0;
+    }
+    aes_cm_xor(key, iv, out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // https://tools.ietf.org/html/rfc3711#appendix-B.2
+    #[test]
+    fn aes_cm_keystream_test() {
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let iv = [
+            0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd,
+            0x00, 0x00,
+        ];
+        let mut out = [0; 32];
+        aes_cm_keystream(&key, &iv, &mut out).unwrap();
+        assert_eq!(
+            &out[..],
+            &[
+                0xe0, 0x3e, 0xad, 0x09, 0x35, 0xc9, 0x5e, 0x80, 0xe1, 0x66, 0xb1, 0x6d, 0xd9, 0x2b,
+                0x4e, 0xb4, 0xd2, 0x35, 0x13, 0x16, 0x2b, 0x02, 0xd0, 0xf7, 0x2a, 0x43, 0xa2, 0xfe,
+                0x4a, 0x5f, 0x97, 0xab,
+            ][..]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+// https://tools.ietf.org/html/rfc7714
+// https://tools.ietf.org/html/rfc9335#section-5.2
+
+/*
+    RTP IV formation for AES-GCM
+
+      0  0  0  0  0  0  0  0  0  0  1  1
+      0  1  2  3  4  5  6  7  8  9  0  1
+    +--+--+--+--+--+--+--+--+--+--+--+--+
+    |00|00|    SSRC   |     ROC   | SEQ |---+
+    +--+--+--+--+--+--+--+--+--+--+--+--+   |
+                                            |
+    +--+--+--+--+--+--+--+--+--+--+--+--+   |
+    |         Encryption Salt           |->(+)
+    +--+--+--+--+--+--+--+--+--+--+--+--+   |
+                                            |
+    +--+--+--+--+--+--+--+--+--+--+--+--+   |
+    |       Initialization Vector       |<--+
+    +--+--+--+--+--+--+--+--+--+--+--+--+
+
+    the RTP header is the AAD, the 16 bytes tag follows the ciphertext.
+    for SRTCP the IV carries the 31bit SRTCP index instead of ROC || SEQ,
+    and the E flag and index trail the packet after the tag.
+*/
+
+use crate::srtp::cipher::aes_cm_hmac_sha1::{
+    SRTCP_E_FLAG, SRTCP_HEADER_LENGTH, SRTCP_INDEX_LENGTH,
+};
+use crate::srtp::cipher::SrtpCipher;
+use crate::srtp::cryptex::{self, CRYPTEX_ENCRYPTION_OFFSET};
+use crate::srtp::key_derivation::*;
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::{Result, SrtpError};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+
+const AEAD_AUTH_TAG_LENGTH: usize = 16;
+
+#[derive(Clone)]
+enum AesGcm {
+    Aes128(Box<Aes128Gcm>),
+    Aes256(Box<Aes256Gcm>),
+}
+
+impl AesGcm {
+    fn new(key: &[u8]) -> Result<Self> {
+        match key.len() {
+            16 => Ok(AesGcm::Aes128(Box::new(
+                Aes128Gcm::new_from_slice(key).map_err(|_| SrtpError::InvalidKeyLength)?,
+            ))),
+            32 => Ok(AesGcm::Aes256(Box::new(
+                Aes256Gcm::new_from_slice(key).map_err(|_| SrtpError::InvalidKeyLength)?,
+            ))),
+            _ => Err(SrtpError::InvalidKeyLength),
+        }
+    }
+
+    fn encrypt(&self, iv: &[u8; 12], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(iv);
+        let payload = Payload { msg, aad };
+        match self {
+            AesGcm::Aes128(v) => v.encrypt(nonce, payload),
+            AesGcm::Aes256(v) => v.encrypt(nonce, payload),
+        }
+        .map_err(|_| SrtpError::AuthenticationFailed)
+    }
+
+    fn decrypt(&self, iv: &[u8; 12], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(iv);
+        let payload = Payload { msg, aad };
+        match self {
+            AesGcm::Aes128(v) => v.decrypt(nonce, payload),
+            AesGcm::Aes256(v) => v.decrypt(nonce, payload),
+        }
+        .map_err(|_| SrtpError::AuthenticationFailed)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CipherAeadAesGcm {
+    srtp_cipher: AesGcm,
+    srtcp_cipher: AesGcm,
+    srtp_session_salt: Vec<u8>,
+    srtcp_session_salt: Vec<u8>,
+}
+
+impl CipherAeadAesGcm {
+    pub fn new(
+        profile: ProtectionProfile,
+        master_key: &[u8],
+        master_salt: &[u8],
+        index_over_kdr: u64,
+    ) -> Result<Self> {
+        if master_key.len() != profile.get_key_length()
+            || master_salt.len() != profile.get_salt_length()
+        {
+            return Err(SrtpError::InvalidKeyLength);
+        }
+
+        let derive = |label, length| {
+            aes_cm_key_derivation(label, master_key, master_salt, index_over_kdr, length)
+        };
+
+        let key_length = profile.get_key_length();
+        let salt_length = profile.get_salt_length();
+        Ok(CipherAeadAesGcm {
+            srtp_cipher: AesGcm::new(&derive(LABEL_SRTP_ENCRYPTION, key_length)?)?,
+            srtcp_cipher: AesGcm::new(&derive(LABEL_SRTCP_ENCRYPTION, key_length)?)?,
+            srtp_session_salt: derive(LABEL_SRTP_SALT, salt_length)?,
+            srtcp_session_salt: derive(LABEL_SRTCP_SALT, salt_length)?,
+        })
+    }
+}
+
+fn rtp_iv(salt: &[u8], ssrc: u32, roc: u32, sequence: u16) -> [u8; 12] {
+    let mut iv = [0u8; 12];
+    iv[2..6].copy_from_slice(&ssrc.to_be_bytes());
+    iv[6..10].copy_from_slice(&roc.to_be_bytes());
+    iv[10..12].copy_from_slice(&sequence.to_be_bytes());
+    for (v, s) in iv.iter_mut().zip(salt) {
+        *v ^= s;
+    }
+    iv
+}
+
+fn rtcp_iv(salt: &[u8], ssrc: u32, index: u32) -> [u8; 12] {
+    let mut iv = [0u8; 12];
+    iv[2..6].copy_from_slice(&ssrc.to_be_bytes());
+    iv[8..12].copy_from_slice(&index.to_be_bytes());
+    for (v, s) in iv.iter_mut().zip(salt) {
+        *v ^= s;
+    }
+    iv
+}
+
+impl SrtpCipher for CipherAeadAesGcm {
+    fn get_rtp_auth_tag_length(&self) -> usize {
+        AEAD_AUTH_TAG_LENGTH
+    }
+
+    fn encrypt_rtp(
+        &self,
+        packet: &[u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        roc: u32,
+        sequence: u16,
+    ) -> Result<Vec<u8>> {
+        let iv = rtp_iv(&self.srtp_session_salt, ssrc, roc, sequence);
+        if cryptex {
+            let mut buf = packet.to_vec();
+            cryptex::rearrange(&mut buf);
+            let (header, payload) = buf.split_at(CRYPTEX_ENCRYPTION_OFFSET);
+            let encrypted = self.srtp_cipher.encrypt(&iv, payload, header)?;
+
+            let mut out = header.to_vec();
+            out.extend_from_slice(&encrypted);
+            cryptex::restore(&mut out);
+            return Ok(out);
+        }
+
+        let (header, payload) = packet.split_at(header_length);
+        let encrypted = self.srtp_cipher.encrypt(&iv, payload, header)?;
+
+        let mut out = Vec::with_capacity(header_length + encrypted.len());
+        out.extend_from_slice(header);
+        out.extend_from_slice(&encrypted);
+        Ok(out)
+    }
+
+    fn decrypt_rtp(
+        &self,
+        packet: &[u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        roc: u32,
+        sequence: u16,
+    ) -> Result<Vec<u8>> {
+        if packet.len() < header_length + AEAD_AUTH_TAG_LENGTH {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let iv = rtp_iv(&self.srtp_session_salt, ssrc, roc, sequence);
+        if cryptex {
+            let mut buf = packet.to_vec();
+            cryptex::rearrange(&mut buf);
+            let (header, encrypted) = buf.split_at(CRYPTEX_ENCRYPTION_OFFSET);
+            let payload = self.srtp_cipher.decrypt(&iv, encrypted, header)?;
+
+            let mut out = header.to_vec();
+            out.extend_from_slice(&payload);
+            cryptex::restore(&mut out);
+            return Ok(out);
+        }
+
+        let (header, encrypted) = packet.split_at(header_length);
+        let payload = self.srtp_cipher.decrypt(&iv, encrypted, header)?;
+
+        let mut out = Vec::with_capacity(header_length + payload.len());
+        out.extend_from_slice(header);
+        out.extend_from_slice(&payload);
+        Ok(out)
+    }
+
+    fn get_rtcp_auth_tag_length(&self) -> usize {
+        AEAD_AUTH_TAG_LENGTH
+    }
+
+    fn get_rtcp_index_offset(&self, length: usize) -> usize {
+        length - SRTCP_INDEX_LENGTH
+    }
+
+    fn encrypt_rtcp(&self, packet: &[u8], ssrc: u32, index: u32) -> Result<Vec<u8>> {
+        if packet.len() < SRTCP_HEADER_LENGTH {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let e_index = (index | SRTCP_E_FLAG).to_be_bytes();
+        let (header, payload) = packet.split_at(SRTCP_HEADER_LENGTH);
+
+        let mut aad = header.to_vec();
+        aad.extend_from_slice(&e_index);
+        let iv = rtcp_iv(&self.srtcp_session_salt, ssrc, index);
+        let encrypted = self.srtcp_cipher.encrypt(&iv, payload, &aad)?;
+
+        let mut out = header.to_vec();
+        out.extend_from_slice(&encrypted);
+        out.extend_from_slice(&e_index);
+        Ok(out)
+    }
+
+    fn decrypt_rtcp(
+        &self,
+        packet: &[u8],
+        ssrc: u32,
+        index: u32,
+        encrypted: bool,
+    ) -> Result<Vec<u8>> {
+        if packet.len() < SRTCP_HEADER_LENGTH + AEAD_AUTH_TAG_LENGTH + SRTCP_INDEX_LENGTH {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let (body, e_index) = packet.split_at(packet.len() - SRTCP_INDEX_LENGTH);
+        let iv = rtcp_iv(&self.srtcp_session_salt, ssrc, index);
+
+        if !encrypted {
+            // only the tag is the "ciphertext", the whole packet is the AAD.
+            let (plain, tag) = body.split_at(body.len() - AEAD_AUTH_TAG_LENGTH);
+            let mut aad = plain.to_vec();
+            aad.extend_from_slice(e_index);
+            self.srtcp_cipher.decrypt(&iv, tag, &aad)?;
+            return Ok(plain.to_vec());
+        }
+
+        let (header, ciphertext) = body.split_at(SRTCP_HEADER_LENGTH);
+        let mut aad = header.to_vec();
+        aad.extend_from_slice(e_index);
+        let payload = self.srtcp_cipher.decrypt(&iv, ciphertext, &aad)?;
+
+        let mut out = header.to_vec();
+        out.extend_from_slice(&payload);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rtp_iv_test() {
+        let salt = [
+            0x51, 0x75, 0x69, 0x64, 0x20, 0x70, 0x72, 0x6f, 0x20, 0x71, 0x75, 0x6f,
+        ];
+        let iv = rtp_iv(&salt, 0xcafe_babe, 0, 0xf17b);
+        assert_eq!(
+            iv,
+            [0x51, 0x75, 0xa3, 0x9a, 0x9a, 0xce, 0x72, 0x6f, 0x20, 0x71, 0x84, 0x14]
+        );
+
+        let iv = rtcp_iv(&salt, 0x4321_8765, 0x5d4);
+        assert_eq!(
+            iv,
+            [0x51, 0x75, 0x2a, 0x45, 0xa7, 0x15, 0x72, 0x6f, 0x20, 0x71, 0x70, 0xbb]
+        );
+    }
+}
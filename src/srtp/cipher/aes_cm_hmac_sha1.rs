@@ -0,0 +1,238 @@
+// https://tools.ietf.org/html/rfc3711#section-4.1.1
+// https://tools.ietf.org/html/rfc3711#section-4.2.1
+// https://tools.ietf.org/html/rfc3711#section-3.4
+// https://tools.ietf.org/html/rfc9335#section-5.1
+
+use crate::srtp::cipher::{aes_cm_xor, SrtpCipher};
+use crate::srtp::cryptex::{self, CRYPTEX_ENCRYPTION_OFFSET};
+use crate::srtp::key_derivation::*;
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::{Result, SrtpError};
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use subtle::ConstantTimeEq;
+
+type HmacSha1 = Hmac<Sha1>;
+
+pub(crate) const SRTCP_HEADER_LENGTH: usize = 8;
+pub(crate) const SRTCP_INDEX_LENGTH: usize = 4;
+pub(crate) const SRTCP_E_FLAG: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CipherAesCmHmacSha1 {
+    profile: ProtectionProfile,
+    srtp_session_key: Vec<u8>,
+    srtp_session_salt: Vec<u8>,
+    srtp_session_auth_key: Vec<u8>,
+    srtcp_session_key: Vec<u8>,
+    srtcp_session_salt: Vec<u8>,
+    srtcp_session_auth_key: Vec<u8>,
+}
+
+impl CipherAesCmHmacSha1 {
+    pub fn new(
+        profile: ProtectionProfile,
+        master_key: &[u8],
+        master_salt: &[u8],
+        index_over_kdr: u64,
+    ) -> Result<Self> {
+        if master_key.len() != profile.get_key_length()
+            || master_salt.len() != profile.get_salt_length()
+        {
+            return Err(SrtpError::InvalidKeyLength);
+        }
+
+        let derive = |label, length| {
+            aes_cm_key_derivation(label, master_key, master_salt, index_over_kdr, length)
+        };
+
+        Ok(CipherAesCmHmacSha1 {
+            profile,
+            srtp_session_key: derive(LABEL_SRTP_ENCRYPTION, profile.get_key_length())?,
+            srtp_session_salt: derive(LABEL_SRTP_SALT, profile.get_salt_length())?,
+            srtp_session_auth_key: derive(
+                LABEL_SRTP_AUTHENTICATION,
+                profile.get_auth_key_length(),
+            )?,
+            srtcp_session_key: derive(LABEL_SRTCP_ENCRYPTION, profile.get_key_length())?,
+            srtcp_session_salt: derive(LABEL_SRTCP_SALT, profile.get_salt_length())?,
+            srtcp_session_auth_key: derive(
+                LABEL_SRTCP_AUTHENTICATION,
+                profile.get_auth_key_length(),
+            )?,
+        })
+    }
+
+    fn rtp_auth_tag(&self, authenticated: &[u8], roc: u32) -> Result<Vec<u8>> {
+        let mut mac = HmacSha1::new_from_slice(&self.srtp_session_auth_key)
+            .map_err(|_| SrtpError::InvalidKeyLength)?;
+        mac.update(authenticated);
+        mac.update(&roc.to_be_bytes());
+        let tag = mac.finalize().into_bytes();
+        Ok(tag[..self.get_rtp_auth_tag_length()].to_vec())
+    }
+
+    fn xor(&self, key: &[u8], iv: &[u8; 16], data: &mut [u8]) -> Result<()> {
+        if self.profile.is_null_cipher() {
+            return Ok(());
+        }
+        aes_cm_xor(key, iv, data)
+    }
+
+    fn xor_rtp(
+        &self,
+        packet: &mut [u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        index: u64,
+    ) -> Result<()> {
+        let iv = rtp_iv(&self.srtp_session_salt, ssrc, index);
+        if !cryptex {
+            return self.xor(&self.srtp_session_key, &iv, &mut packet[header_length..]);
+        }
+
+        cryptex::rearrange(packet);
+        self.xor(
+            &self.srtp_session_key,
+            &iv,
+            &mut packet[CRYPTEX_ENCRYPTION_OFFSET..],
+        )?;
+        cryptex::restore(packet);
+        Ok(())
+    }
+
+    // the SRTCP index is part of the authenticated portion itself.
+    fn rtcp_auth_tag(&self, authenticated: &[u8]) -> Result<Vec<u8>> {
+        let mut mac = HmacSha1::new_from_slice(&self.srtcp_session_auth_key)
+            .map_err(|_| SrtpError::InvalidKeyLength)?;
+        mac.update(authenticated);
+        let tag = mac.finalize().into_bytes();
+        Ok(tag[..self.get_rtcp_auth_tag_length()].to_vec())
+    }
+}
+
+/// IV = (k_s * 2^16) XOR (SSRC * 2^64) XOR (i * 2^16)
+pub(crate) fn rtp_iv(salt: &[u8], ssrc: u32, index: u64) -> [u8; 16] {
+    let mut iv = [0u8; 16];
+    iv[..salt.len()].copy_from_slice(salt);
+    let ssrc = ssrc.to_be_bytes();
+    for i in 0..4 {
+        iv[4 + i] ^= ssrc[i];
+    }
+    let index = index.to_be_bytes();
+    for i in 0..6 {
+        iv[8 + i] ^= index[2 + i];
+    }
+    iv
+}
+
+impl SrtpCipher for CipherAesCmHmacSha1 {
+    fn get_rtp_auth_tag_length(&self) -> usize {
+        self.profile.get_rtp_auth_tag_length()
+    }
+
+    fn encrypt_rtp(
+        &self,
+        packet: &[u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        roc: u32,
+        sequence: u16,
+    ) -> Result<Vec<u8>> {
+        let index = (u64::from(roc) << 16) | u64::from(sequence);
+        let mut out = packet.to_vec();
+        self.xor_rtp(&mut out, header_length, cryptex, ssrc, index)?;
+
+        // the tag covers the packet as sent, with the extension header in place.
+        let tag = self.rtp_auth_tag(&out, roc)?;
+        out.extend_from_slice(&tag);
+        Ok(out)
+    }
+
+    fn decrypt_rtp(
+        &self,
+        packet: &[u8],
+        header_length: usize,
+        cryptex: bool,
+        ssrc: u32,
+        roc: u32,
+        sequence: u16,
+    ) -> Result<Vec<u8>> {
+        let tag_length = self.get_rtp_auth_tag_length();
+        if packet.len() < header_length + tag_length {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let (authenticated, tag) = packet.split_at(packet.len() - tag_length);
+
+        let expected = self.rtp_auth_tag(authenticated, roc)?;
+        if !bool::from(expected.ct_eq(tag)) {
+            return Err(SrtpError::AuthenticationFailed);
+        }
+
+        let index = (u64::from(roc) << 16) | u64::from(sequence);
+        let mut out = authenticated.to_vec();
+        self.xor_rtp(&mut out, header_length, cryptex, ssrc, index)?;
+        Ok(out)
+    }
+
+    fn get_rtcp_auth_tag_length(&self) -> usize {
+        self.profile.get_rtcp_auth_tag_length()
+    }
+
+    fn encrypt_rtcp(&self, packet: &[u8], ssrc: u32, index: u32) -> Result<Vec<u8>> {
+        if packet.len() < SRTCP_HEADER_LENGTH {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let mut out = packet.to_vec();
+        let iv = rtp_iv(&self.srtcp_session_salt, ssrc, u64::from(index));
+        self.xor(
+            &self.srtcp_session_key,
+            &iv,
+            &mut out[SRTCP_HEADER_LENGTH..],
+        )?;
+
+        // the E flag says whether the payload is encrypted.
+        let e_flag = if self.profile.is_null_cipher() {
+            0
+        } else {
+            SRTCP_E_FLAG
+        };
+        out.extend_from_slice(&(index | e_flag).to_be_bytes());
+        let tag = self.rtcp_auth_tag(&out)?;
+        out.extend_from_slice(&tag);
+        Ok(out)
+    }
+
+    fn decrypt_rtcp(
+        &self,
+        packet: &[u8],
+        ssrc: u32,
+        index: u32,
+        encrypted: bool,
+    ) -> Result<Vec<u8>> {
+        let tag_length = self.get_rtcp_auth_tag_length();
+        if packet.len() < SRTCP_HEADER_LENGTH + SRTCP_INDEX_LENGTH + tag_length {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let (authenticated, tag) = packet.split_at(packet.len() - tag_length);
+
+        let expected = self.rtcp_auth_tag(authenticated)?;
+        if !bool::from(expected.ct_eq(tag)) {
+            return Err(SrtpError::AuthenticationFailed);
+        }
+
+        let mut out = authenticated[..authenticated.len() - SRTCP_INDEX_LENGTH].to_vec();
+        if encrypted {
+            let iv = rtp_iv(&self.srtcp_session_salt, ssrc, u64::from(index));
+            self.xor(
+                &self.srtcp_session_key,
+                &iv,
+                &mut out[SRTCP_HEADER_LENGTH..],
+            )?;
+        }
+        Ok(out)
+    }
+}
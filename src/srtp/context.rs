@@ -0,0 +1,1157 @@
+// https://tools.ietf.org/html/rfc3711#section-3.3
+// https://tools.ietf.org/html/rfc3711#section-3.4
+
+/*
+    SRTCP packet
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |V=2|P|    RC   |   PT=SR or RR   |             length          |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                         SSRC of sender                        |
+    +>+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | |                  encrypted rest of the compound               |
+    | +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | |E|                         SRTCP index                         |
+    | +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | ~                     authentication tag                        ~
+    | +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::srtp::cipher::aes_cm_hmac_sha1::{
+    SRTCP_E_FLAG, SRTCP_HEADER_LENGTH, SRTCP_INDEX_LENGTH,
+};
+use crate::srtp::cipher::{new_cipher, SrtpCipher};
+use crate::srtp::cryptex;
+use crate::srtp::key_derivation::{get_index_over_kdr, is_valid_key_derivation_rate};
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::replay_detector::ReplayDetector;
+use crate::srtp::{Result, SrtpError};
+use crate::time::Instant;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const RTP_HEADER_LENGTH: usize = 12;
+const MAX_ROC_DISORDER: u16 = 1 << 15;
+const MAX_SRTCP_INDEX: u32 = 0x7fff_ffff;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ContextConfig {
+    /// Replay window sizes in packets, 0 disables replay protection.
+    pub srtp_replay_window: usize,
+    pub srtcp_replay_window: usize,
+    /// Session keys are re-derived every `key_derivation_rate` packets,
+    /// 0 derives them once. DTLS-SRTP always uses 0.
+    pub key_derivation_rate: u64,
+    /// How long the master key replaced by `rekey` still authenticates
+    /// received packets.
+    pub rekey_grace_period: Duration,
+    /// Encrypts CSRCs and header extensions with Cryptex, RFC 9335. Only
+    /// set this once both sides negotiated `a=cryptex`.
+    pub cryptex: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        ContextConfig {
+            srtp_replay_window: 64,
+            srtcp_replay_window: 64,
+            key_derivation_rate: 0,
+            rekey_grace_period: Duration::from_secs(5),
+            cryptex: false,
+        }
+    }
+}
+
+/// Packets rejected by the replay windows.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct ReplayCounters {
+    pub srtp_duplicated: u64,
+    pub srtp_too_old: u64,
+    pub srtcp_duplicated: u64,
+    pub srtcp_too_old: u64,
+}
+
+// SSRC毎のrollover counterと最後のsequence number．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SrtpSsrcState {
+    roc: u32,
+    last_sequence: Option<u16>,
+    replay_detector: ReplayDetector,
+}
+
+impl SrtpSsrcState {
+    fn new(replay_window: usize) -> Self {
+        SrtpSsrcState {
+            roc: 0,
+            last_sequence: None,
+            replay_detector: ReplayDetector::new(replay_window),
+        }
+    }
+
+    pub fn get_roc(&self) -> u32 {
+        self.roc
+    }
+
+    pub fn get_last_sequence(&self) -> Option<u16> {
+        self.last_sequence
+    }
+
+    pub fn get_index(&self) -> Option<u64> {
+        self.last_sequence
+            .map(|s| (u64::from(self.roc) << 16) | u64::from(s))
+    }
+
+    /// Guesses the ROC of a received `sequence`, RFC 3711 Section 3.3.1.
+    /// A packet from before the first wrap keeps ROC 0.
+    fn estimate_roc(&self, sequence: u16) -> u32 {
+        let last = match self.last_sequence {
+            Some(v) => v,
+            None => return self.roc,
+        };
+
+        if last < MAX_ROC_DISORDER {
+            if sequence > last && sequence - last > MAX_ROC_DISORDER {
+                return self.roc.saturating_sub(1);
+            }
+        } else if last - MAX_ROC_DISORDER > sequence {
+            return self.roc.wrapping_add(1);
+        }
+        self.roc
+    }
+
+    /// ROCs to try, the estimate first. The neighbours recover streams whose
+    /// ROC was lost, e.g. after more than 2^15 consecutive losses.
+    fn get_roc_candidates(&self, sequence: u16) -> Vec<u32> {
+        let roc = self.estimate_roc(sequence);
+        let mut candidates = vec![roc];
+        candidates.extend(roc.checked_add(1));
+        candidates.extend(roc.checked_sub(1));
+        candidates
+    }
+
+    fn update(&mut self, roc: u32, sequence: u16) {
+        let index = (u64::from(roc) << 16) | u64::from(sequence);
+        if self.get_index().map(|v| index > v).unwrap_or(true) {
+            self.roc = roc;
+            self.last_sequence = Some(sequence);
+        }
+    }
+}
+
+// SSRC毎のSRTCP index．送信側は次に使うindex，受信側は最後に受信したindex．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SrtcpSsrcState {
+    index: u32,
+    replay_detector: ReplayDetector,
+}
+
+impl SrtcpSsrcState {
+    fn new(replay_window: usize) -> Self {
+        SrtcpSsrcState {
+            index: 0,
+            replay_detector: ReplayDetector::new(replay_window),
+        }
+    }
+
+    pub fn get_index(&self) -> u32 {
+        self.index
+    }
+}
+
+// master keyと，直近のindex DIV kdrで導出したsession keyのcipher．
+struct SessionKeys {
+    master_key: Vec<u8>,
+    master_salt: Vec<u8>,
+    srtp_cipher: (u64, Box<dyn SrtpCipher>),
+    srtcp_cipher: (u64, Box<dyn SrtpCipher>),
+}
+
+impl SessionKeys {
+    fn new(profile: ProtectionProfile, master_key: &[u8], master_salt: &[u8]) -> Result<Self> {
+        Ok(SessionKeys {
+            master_key: master_key.to_vec(),
+            master_salt: master_salt.to_vec(),
+            srtp_cipher: (0, new_cipher(profile, master_key, master_salt, 0)?),
+            srtcp_cipher: (0, new_cipher(profile, master_key, master_salt, 0)?),
+        })
+    }
+
+    fn get_srtp_cipher(
+        &mut self,
+        profile: ProtectionProfile,
+        index_over_kdr: u64,
+    ) -> Result<&dyn SrtpCipher> {
+        if self.srtp_cipher.0 != index_over_kdr {
+            let cipher = new_cipher(profile, &self.master_key, &self.master_salt, index_over_kdr)?;
+            self.srtp_cipher = (index_over_kdr, cipher);
+        }
+        Ok(self.srtp_cipher.1.as_ref())
+    }
+
+    fn get_srtcp_cipher(
+        &mut self,
+        profile: ProtectionProfile,
+        index_over_kdr: u64,
+    ) -> Result<&dyn SrtpCipher> {
+        if self.srtcp_cipher.0 != index_over_kdr {
+            let cipher = new_cipher(profile, &self.master_key, &self.master_salt, index_over_kdr)?;
+            self.srtcp_cipher = (index_over_kdr, cipher);
+        }
+        Ok(self.srtcp_cipher.1.as_ref())
+    }
+}
+
+/// SRTP cryptographic context of one direction of a session.
+pub struct Context {
+    profile: ProtectionProfile,
+    config: ContextConfig,
+    // MKIを使わないcontextではkeyは空のMKIの一つだけ．
+    keys: HashMap<Vec<u8>, SessionKeys>,
+    active_mki: Vec<u8>,
+    // rekeyで置き換えられたkeyと，受信に使えなくなる時刻．
+    retired_keys: Vec<(Vec<u8>, SessionKeys, Instant)>,
+    replay_counters: ReplayCounters,
+    srtp_states: HashMap<u32, SrtpSsrcState>,
+    srtcp_states: HashMap<u32, SrtcpSsrcState>,
+}
+
+impl Context {
+    pub fn new(master_key: &[u8], master_salt: &[u8], profile: ProtectionProfile) -> Result<Self> {
+        Context::with_config(master_key, master_salt, profile, ContextConfig::default())
+    }
+
+    pub fn with_config(
+        master_key: &[u8],
+        master_salt: &[u8],
+        profile: ProtectionProfile,
+        config: ContextConfig,
+    ) -> Result<Self> {
+        Context::with_mki(master_key, master_salt, &[], profile, config)
+    }
+
+    /// Creates a context whose packets carry `mki`. More master keys with
+    /// MKIs of the same length can be added with `add_master_key`.
+    pub fn with_mki(
+        master_key: &[u8],
+        master_salt: &[u8],
+        mki: &[u8],
+        profile: ProtectionProfile,
+        config: ContextConfig,
+    ) -> Result<Self> {
+        if !is_valid_key_derivation_rate(config.key_derivation_rate) {
+            return Err(SrtpError::InvalidKeyDerivationRate);
+        }
+
+        let mut keys = HashMap::new();
+        keys.insert(
+            mki.to_vec(),
+            SessionKeys::new(profile, master_key, master_salt)?,
+        );
+
+        Ok(Context {
+            profile,
+            config,
+            keys,
+            active_mki: mki.to_vec(),
+            retired_keys: Vec::new(),
+            replay_counters: ReplayCounters::default(),
+            srtp_states: HashMap::new(),
+            srtcp_states: HashMap::new(),
+        })
+    }
+
+    pub fn get_profile(&self) -> ProtectionProfile {
+        self.profile
+    }
+
+    pub fn get_active_mki(&self) -> &[u8] {
+        &self.active_mki
+    }
+
+    /// Adds a master key the receiver accepts and the sender may switch to.
+    pub fn add_master_key(
+        &mut self,
+        mki: &[u8],
+        master_key: &[u8],
+        master_salt: &[u8],
+    ) -> Result<()> {
+        if self.active_mki.is_empty() || mki.len() != self.active_mki.len() {
+            return Err(SrtpError::InvalidMki);
+        }
+        let keys = SessionKeys::new(self.profile, master_key, master_salt)?;
+        self.keys.insert(mki.to_vec(), keys);
+        Ok(())
+    }
+
+    /// Selects the master key used by `protect_rtp` and `protect_rtcp`.
+    pub fn set_active_mki(&mut self, mki: &[u8]) -> Result<()> {
+        if !self.keys.contains_key(mki) {
+            return Err(SrtpError::UnknownMki);
+        }
+        self.active_mki = mki.to_vec();
+        Ok(())
+    }
+
+    /// The active master key can not be removed.
+    pub fn remove_master_key(&mut self, mki: &[u8]) -> Result<()> {
+        if mki == self.active_mki.as_slice() {
+            return Err(SrtpError::InvalidMki);
+        }
+        self.keys
+            .remove(mki)
+            .map(|_| ())
+            .ok_or(SrtpError::UnknownMki)
+    }
+
+    /// Replaces the active master key, e.g. after a DTLS renegotiation.
+    /// Packets are protected with the new key right away, while the old
+    /// one keeps authenticating received packets for the grace period.
+    /// `mki` must have the length of the context's MKI, empty without MKI.
+    pub fn rekey(
+        &mut self,
+        mki: &[u8],
+        master_key: &[u8],
+        master_salt: &[u8],
+        now: Instant,
+    ) -> Result<()> {
+        if mki.len() != self.active_mki.len() {
+            return Err(SrtpError::InvalidMki);
+        }
+        let keys = SessionKeys::new(self.profile, master_key, master_salt)?;
+
+        if let Some(old) = self.keys.remove(&self.active_mki) {
+            let expires_at = now + self.config.rekey_grace_period;
+            self.retired_keys
+                .push((self.active_mki.clone(), old, expires_at));
+        }
+        self.keys.insert(mki.to_vec(), keys);
+        self.active_mki = mki.to_vec();
+        Ok(())
+    }
+
+    pub fn get_retired_key_count(&self) -> usize {
+        self.retired_keys.len()
+    }
+
+    /// Returns when the next retired master key expires.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.retired_keys.iter().map(|(_, _, t)| *t).min()
+    }
+
+    /// Drops the retired master keys whose grace period is over.
+    pub fn process(&mut self, now: Instant) {
+        self.retired_keys.retain(|(_, _, t)| *t > now);
+    }
+
+    pub fn get_replay_counters(&self) -> ReplayCounters {
+        self.replay_counters
+    }
+
+    pub fn get_srtp_state(&self, ssrc: u32) -> Option<&SrtpSsrcState> {
+        self.srtp_states.get(&ssrc)
+    }
+
+    pub fn get_srtcp_state(&self, ssrc: u32) -> Option<&SrtcpSsrcState> {
+        self.srtcp_states.get(&ssrc)
+    }
+
+    /// Sets the ROC of `ssrc`, e.g. when joining a stream in progress.
+    pub fn set_roc(&mut self, ssrc: u32, roc: u32) {
+        let window = self.config.srtp_replay_window;
+        self.srtp_states
+            .entry(ssrc)
+            .or_insert_with(|| SrtpSsrcState::new(window))
+            .roc = roc;
+    }
+
+    /// Encrypts and authenticates a serialized RTP packet.
+    pub fn protect_rtp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        get_rtp_header_length(packet)?;
+        let use_cryptex = self.config.cryptex && cryptex::needs_cryptex(packet);
+        let packet = if use_cryptex {
+            Cow::Owned(cryptex::to_cryptex(packet)?)
+        } else {
+            Cow::Borrowed(packet)
+        };
+
+        let header_length = get_rtp_header_length(&packet)?;
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let ssrc = u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]);
+
+        let window = self.config.srtp_replay_window;
+        let state = self
+            .srtp_states
+            .entry(ssrc)
+            .or_insert_with(|| SrtpSsrcState::new(window));
+        let roc = match state.last_sequence {
+            Some(last) if sequence < last && last - sequence > MAX_ROC_DISORDER => {
+                state.roc.wrapping_add(1)
+            }
+            Some(last) if sequence > last && sequence - last > MAX_ROC_DISORDER => {
+                state.roc.saturating_sub(1)
+            }
+            _ => state.roc,
+        };
+
+        let index = (u64::from(roc) << 16) | u64::from(sequence);
+        let r = get_index_over_kdr(index, self.config.key_derivation_rate);
+        let keys = self
+            .keys
+            .get_mut(&self.active_mki)
+            .ok_or(SrtpError::UnknownMki)?;
+        let out = keys.get_srtp_cipher(self.profile, r)?.encrypt_rtp(
+            &packet,
+            header_length,
+            use_cryptex,
+            ssrc,
+            roc,
+            sequence,
+        )?;
+        state.update(roc, sequence);
+
+        let tag_length = self.profile.get_rtp_auth_tag_length();
+        Ok(insert_mki(
+            out,
+            &self.active_mki,
+            self.get_mki_suffix(tag_length),
+        ))
+    }
+
+    /// Verifies and decrypts an SRTP packet, returning the RTP packet.
+    pub fn unprotect_rtp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        let header_length = get_rtp_header_length(packet)?;
+        let tag_length = self.profile.get_rtp_auth_tag_length();
+        if packet.len() < header_length + self.active_mki.len() + tag_length {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let (mki, packet) = split_mki(
+            packet,
+            self.active_mki.len(),
+            self.get_mki_suffix(tag_length),
+        );
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let ssrc = u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]);
+        let use_cryptex = self.config.cryptex && cryptex::is_cryptex(&packet);
+
+        // a state of a new SSRC is kept once the packet is authenticated.
+        let window = self.config.srtp_replay_window;
+        let mut new_state = None;
+        let state = match self.srtp_states.get_mut(&ssrc) {
+            Some(v) => v,
+            None => new_state.get_or_insert_with(|| SrtpSsrcState::new(window)),
+        };
+        let key_sets = get_key_sets(&mut self.keys, &mut self.retired_keys, mki)?;
+
+        // the error of the estimated ROC with the active key is reported if
+        // no combination succeeds.
+        let mut error = None;
+        for keys in key_sets {
+            for roc in state.get_roc_candidates(sequence) {
+                let index = (u64::from(roc) << 16) | u64::from(sequence);
+                if let Err(e) = state.replay_detector.check(index) {
+                    error.get_or_insert(e);
+                    continue;
+                }
+
+                let r = get_index_over_kdr(index, self.config.key_derivation_rate);
+                let result = keys.get_srtp_cipher(self.profile, r)?.decrypt_rtp(
+                    &packet,
+                    header_length,
+                    use_cryptex,
+                    ssrc,
+                    roc,
+                    sequence,
+                );
+
+                // the state is only updated once the packet is authenticated.
+                match result {
+                    Ok(mut out) => {
+                        state.update(roc, sequence);
+                        state.replay_detector.accept(index);
+                        if let Some(v) = new_state.take() {
+                            self.srtp_states.insert(ssrc, v);
+                        }
+                        if use_cryptex {
+                            cryptex::from_cryptex(&mut out);
+                        }
+                        return Ok(out);
+                    }
+                    Err(SrtpError::AuthenticationFailed) => {
+                        error.get_or_insert(SrtpError::AuthenticationFailed);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        let error = error.unwrap_or(SrtpError::AuthenticationFailed);
+        match error {
+            SrtpError::DuplicatedPacket => self.replay_counters.srtp_duplicated += 1,
+            SrtpError::PacketTooOld => self.replay_counters.srtp_too_old += 1,
+            _ => {}
+        }
+        Err(error)
+    }
+
+    /// Encrypts and authenticates a serialized RTCP compound packet with the
+    /// next SRTCP index of its first SSRC.
+    pub fn protect_rtcp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        let ssrc = get_rtcp_ssrc(packet)?;
+
+        let window = self.config.srtcp_replay_window;
+        let state = self
+            .srtcp_states
+            .entry(ssrc)
+            .or_insert_with(|| SrtcpSsrcState::new(window));
+        let index = state.index;
+        let r = get_index_over_kdr(u64::from(index), self.config.key_derivation_rate);
+        let keys = self
+            .keys
+            .get_mut(&self.active_mki)
+            .ok_or(SrtpError::UnknownMki)?;
+        let out = keys
+            .get_srtcp_cipher(self.profile, r)?
+            .encrypt_rtcp(packet, ssrc, index)?;
+        state.index = (index + 1) & MAX_SRTCP_INDEX;
+
+        let tag_length = self.profile.get_rtcp_auth_tag_length();
+        Ok(insert_mki(
+            out,
+            &self.active_mki,
+            self.get_mki_suffix(tag_length),
+        ))
+    }
+
+    /// Verifies and decrypts an SRTCP packet, returning the RTCP compound
+    /// packet. Packets sent with the E flag cleared are only authenticated.
+    pub fn unprotect_rtcp(&mut self, packet: &[u8]) -> Result<Vec<u8>> {
+        let ssrc = get_rtcp_ssrc(packet)?;
+        let tag_length = self.profile.get_rtcp_auth_tag_length();
+        let mki_length = self.active_mki.len();
+        if packet.len() < SRTCP_HEADER_LENGTH + SRTCP_INDEX_LENGTH + mki_length + tag_length {
+            return Err(SrtpError::PacketTooShort);
+        }
+        let (mki, packet) = split_mki(packet, mki_length, self.get_mki_suffix(tag_length));
+        let key_sets = get_key_sets(&mut self.keys, &mut self.retired_keys, mki)?;
+
+        let offset = key_sets[0]
+            .srtcp_cipher
+            .1
+            .get_rtcp_index_offset(packet.len());
+        let e_index = u32::from_be_bytes([
+            packet[offset],
+            packet[offset + 1],
+            packet[offset + 2],
+            packet[offset + 3],
+        ]);
+        let encrypted = e_index & SRTCP_E_FLAG > 0;
+        let index = e_index & MAX_SRTCP_INDEX;
+
+        let window = self.config.srtcp_replay_window;
+        let mut new_state = None;
+        let state = match self.srtcp_states.get_mut(&ssrc) {
+            Some(v) => v,
+            None => new_state.get_or_insert_with(|| SrtcpSsrcState::new(window)),
+        };
+        if let Err(e) = state.replay_detector.check(u64::from(index)) {
+            match e {
+                SrtpError::DuplicatedPacket => self.replay_counters.srtcp_duplicated += 1,
+                _ => self.replay_counters.srtcp_too_old += 1,
+            }
+            return Err(e);
+        }
+
+        let r = get_index_over_kdr(u64::from(index), self.config.key_derivation_rate);
+        for keys in key_sets {
+            let result = keys
+                .get_srtcp_cipher(self.profile, r)?
+                .decrypt_rtcp(&packet, ssrc, index, encrypted);
+            match result {
+                Ok(out) => {
+                    state.index = state.index.max(index);
+                    state.replay_detector.accept(u64::from(index));
+                    if let Some(v) = new_state.take() {
+                        self.srtcp_states.insert(ssrc, v);
+                    }
+                    return Ok(out);
+                }
+                Err(SrtpError::AuthenticationFailed) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(SrtpError::AuthenticationFailed)
+    }
+
+    // the MKI precedes the auth tag, except for AEAD where it trails the packet.
+    fn get_mki_suffix(&self, tag_length: usize) -> usize {
+        if self.profile.is_aead() {
+            0
+        } else {
+            tag_length
+        }
+    }
+}
+
+// the keys of `mki` to try, the current one first and then the retired ones.
+fn get_key_sets<'a>(
+    keys: &'a mut HashMap<Vec<u8>, SessionKeys>,
+    retired_keys: &'a mut [(Vec<u8>, SessionKeys, Instant)],
+    mki: &[u8],
+) -> Result<Vec<&'a mut SessionKeys>> {
+    let key_sets: Vec<&mut SessionKeys> = keys
+        .get_mut(mki)
+        .into_iter()
+        .chain(
+            retired_keys
+                .iter_mut()
+                .rev()
+                .filter(|(v, _, _)| v.as_slice() == mki)
+                .map(|(_, v, _)| v),
+        )
+        .collect();
+
+    if key_sets.is_empty() {
+        return Err(SrtpError::UnknownMki);
+    }
+    Ok(key_sets)
+}
+
+fn insert_mki(mut packet: Vec<u8>, mki: &[u8], suffix_length: usize) -> Vec<u8> {
+    if !mki.is_empty() {
+        let offset = packet.len() - suffix_length;
+        packet.splice(offset..offset, mki.iter().cloned());
+    }
+    packet
+}
+
+// returns the MKI and the packet without it.
+fn split_mki(packet: &[u8], mki_length: usize, suffix_length: usize) -> (&[u8], Cow<'_, [u8]>) {
+    if mki_length == 0 {
+        return (&[], Cow::Borrowed(packet));
+    }
+    let end = packet.len() - suffix_length;
+    let start = end - mki_length;
+
+    let mut rest = packet[..start].to_vec();
+    rest.extend_from_slice(&packet[end..]);
+    (&packet[start..end], Cow::Owned(rest))
+}
+
+fn get_rtcp_ssrc(packet: &[u8]) -> Result<u32> {
+    if packet.len() < SRTCP_HEADER_LENGTH {
+        return Err(SrtpError::PacketTooShort);
+    }
+    if packet[0] >> 6 != 2 {
+        return Err(SrtpError::InvalidPacketHeader);
+    }
+    Ok(u32::from_be_bytes([
+        packet[4], packet[5], packet[6], packet[7],
+    ]))
+}
+
+/// Returns the length of the RTP header including CSRCs and extension.
+pub(crate) fn get_rtp_header_length(packet: &[u8]) -> Result<usize> {
+    if packet.len() < RTP_HEADER_LENGTH {
+        return Err(SrtpError::PacketTooShort);
+    }
+    if packet[0] >> 6 != 2 {
+        return Err(SrtpError::InvalidPacketHeader);
+    }
+
+    let mut length = RTP_HEADER_LENGTH + 4 * usize::from(packet[0] & 0x0f);
+    if packet[0] & 0x10 > 0 {
+        if packet.len() < length + 4 {
+            return Err(SrtpError::InvalidPacketHeader);
+        }
+        let words = u16::from_be_bytes([packet[length + 2], packet[length + 3]]);
+        length += 4 + 4 * usize::from(words);
+    }
+
+    if packet.len() < length {
+        return Err(SrtpError::InvalidPacketHeader);
+    }
+    Ok(length)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::octets;
+    use crate::rtcp::packet::{serialize, RtcpPacket, RtcpPacketType};
+    use crate::rtcp::receiver_report::RtcpReceiverReportPacket;
+
+    const MASTER_KEY: [u8; 16] = [
+        0xe1, 0xf9, 0x7a, 0x0d, 0x3e, 0x01, 0x8b, 0xe0, 0xd6, 0x4f, 0xa3, 0x2c, 0x06, 0xde, 0x41,
+        0x39,
+    ];
+    const MASTER_SALT: [u8; 14] = [
+        0x0e, 0xc6, 0x75, 0xad, 0x49, 0x8a, 0xfe, 0xeb, 0xb6, 0x96, 0x0b, 0x3a, 0xab, 0xe6,
+    ];
+
+    fn rtp_packet(sequence: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x80, 0x60];
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 1, 0xca, 0xfe, 0xba, 0xbe]);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn protect_unprotect_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        let packet = rtp_packet(1, &[0xab; 32]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_eq!(protected.len(), packet.len() + 10);
+        assert_eq!(&protected[..12], &packet[..12]);
+        assert_ne!(&protected[12..44], &packet[12..]);
+
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+        let profile = ProtectionProfile::Aes128CmHmacSha1_32;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_eq!(protected.len(), packet.len() + 4);
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+        assert!(Context::new(&MASTER_KEY[..15], &MASTER_SALT, profile).is_err());
+    }
+
+    #[test]
+    fn authentication_failure_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        let mut protected = sender.protect_rtp(&rtp_packet(1, &[0xab; 32])).unwrap();
+        protected[20] ^= 1;
+        assert_eq!(
+            receiver.unprotect_rtp(&protected),
+            Err(SrtpError::AuthenticationFailed)
+        );
+        // the SSRC of a forged packet is not kept.
+        assert!(receiver.get_srtp_state(0xcafe_babe).is_none());
+        for ssrc in 0..16u32 {
+            let mut forged = protected.clone();
+            forged[8..12].copy_from_slice(&ssrc.to_be_bytes());
+            assert!(receiver.unprotect_rtp(&forged).is_err());
+        }
+        assert!(receiver.srtp_states.is_empty());
+
+        assert_eq!(
+            receiver.unprotect_rtp(&protected[..20]),
+            Err(SrtpError::PacketTooShort)
+        );
+    }
+
+    #[test]
+    fn rollover_counter_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        let mut protected = Vec::new();
+        for sequence in &[65534, 65535, 0, 1] {
+            let packet = rtp_packet(*sequence, &[0x01; 8]);
+            protected.push((packet.clone(), sender.protect_rtp(&packet).unwrap()));
+        }
+        assert_eq!(sender.get_srtp_state(0xcafe_babe).unwrap().get_roc(), 1);
+
+        // 0 arrives before 65535, which still belongs to the previous ROC.
+        for i in &[0, 2, 1, 3] {
+            let (packet, srtp) = &protected[*i];
+            assert_eq!(&receiver.unprotect_rtp(srtp).unwrap(), packet);
+        }
+        let state = receiver.get_srtp_state(0xcafe_babe).unwrap();
+        assert_eq!(state.get_roc(), 1);
+        assert_eq!(state.get_last_sequence(), Some(1));
+    }
+
+    fn rtcp_packet() -> Vec<u8> {
+        let packets = vec![RtcpPacket::new(RtcpPacketType::ReceiverReport(
+            RtcpReceiverReportPacket::new(0xcafe_babe, vec![]),
+        ))];
+        let mut buf = Vec::new();
+        serialize(packets, &mut octets::Octets::with_vec(&mut buf)).unwrap();
+        buf
+    }
+
+    #[test]
+    fn protect_unprotect_rtcp_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_32;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        let packet = rtcp_packet();
+        let first = sender.protect_rtcp(&packet).unwrap();
+        let second = sender.protect_rtcp(&packet).unwrap();
+
+        // 80bit tag even with the _32 profile.
+        assert_eq!(first.len(), packet.len() + 4 + 10);
+        assert_eq!(&first[packet.len()..packet.len() + 4], &[0x80, 0, 0, 0]);
+        assert_eq!(&second[packet.len()..packet.len() + 4], &[0x80, 0, 0, 1]);
+        assert_eq!(sender.get_srtcp_state(0xcafe_babe).unwrap().get_index(), 2);
+
+        assert_eq!(receiver.unprotect_rtcp(&second).unwrap(), packet);
+        assert_eq!(receiver.unprotect_rtcp(&first).unwrap(), packet);
+
+        let mut tampered = sender.protect_rtcp(&packet).unwrap();
+        let index = packet.len();
+        tampered[index] &= 0x7f;
+        assert_eq!(
+            receiver.unprotect_rtcp(&tampered),
+            Err(SrtpError::AuthenticationFailed)
+        );
+
+        let mut forged = tampered.clone();
+        forged[4..8].copy_from_slice(&[0, 0, 0, 1]);
+        assert!(receiver.unprotect_rtcp(&forged).is_err());
+        assert!(receiver.get_srtcp_state(1).is_none());
+        assert_eq!(receiver.srtcp_states.len(), 1);
+    }
+
+    #[test]
+    fn srtp_srtcp_keys_are_separate_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        // an SRTCP packet is never accepted as SRTP.
+        let protected = sender.protect_rtcp(&rtcp_packet()).unwrap();
+        assert!(receiver.unprotect_rtp(&protected).is_err());
+    }
+
+    #[test]
+    fn aead_aes_gcm_test() {
+        for (profile, key_length) in &[
+            (ProtectionProfile::AeadAes128Gcm, 16),
+            (ProtectionProfile::AeadAes256Gcm, 32),
+        ] {
+            let key = vec![0x42; *key_length];
+            let salt = &MASTER_SALT[..12];
+            let mut sender = Context::new(&key, salt, *profile).unwrap();
+            let mut receiver = Context::new(&key, salt, *profile).unwrap();
+            assert!(Context::new(&key, &MASTER_SALT, *profile).is_err());
+
+            let packet = rtp_packet(7, &[0xab; 32]);
+            let protected = sender.protect_rtp(&packet).unwrap();
+            assert_eq!(protected.len(), packet.len() + 16);
+
+            // the header is authenticated as AAD.
+            let mut tampered = protected.clone();
+            tampered[1] ^= 1;
+            assert_eq!(
+                receiver.unprotect_rtp(&tampered),
+                Err(SrtpError::AuthenticationFailed)
+            );
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+            let packet = rtcp_packet();
+            let protected = sender.protect_rtcp(&packet).unwrap();
+            assert_eq!(protected.len(), packet.len() + 16 + 4);
+            assert_eq!(&protected[protected.len() - 4..], &[0x80, 0, 0, 0]);
+            assert_eq!(receiver.unprotect_rtcp(&protected).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn replay_protection_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let config = ContextConfig {
+            srtp_replay_window: 64,
+            srtcp_replay_window: 128,
+            ..Default::default()
+        };
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver =
+            Context::with_config(&MASTER_KEY, &MASTER_SALT, profile, config).unwrap();
+
+        let first = sender.protect_rtp(&rtp_packet(1, &[0; 4])).unwrap();
+        receiver.unprotect_rtp(&first).unwrap();
+        assert_eq!(
+            receiver.unprotect_rtp(&first),
+            Err(SrtpError::DuplicatedPacket)
+        );
+
+        let late = sender.protect_rtp(&rtp_packet(2, &[0; 4])).unwrap();
+        let latest = sender.protect_rtp(&rtp_packet(100, &[0; 4])).unwrap();
+        receiver.unprotect_rtp(&latest).unwrap();
+        assert_eq!(receiver.unprotect_rtp(&late), Err(SrtpError::PacketTooOld));
+
+        let rtcp = sender.protect_rtcp(&rtcp_packet()).unwrap();
+        receiver.unprotect_rtcp(&rtcp).unwrap();
+        assert_eq!(
+            receiver.unprotect_rtcp(&rtcp),
+            Err(SrtpError::DuplicatedPacket)
+        );
+
+        assert_eq!(
+            receiver.get_replay_counters(),
+            ReplayCounters {
+                srtp_duplicated: 1,
+                srtp_too_old: 1,
+                srtcp_duplicated: 1,
+                srtcp_too_old: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn key_derivation_rate_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let config = ContextConfig {
+            key_derivation_rate: 1 << 16,
+            ..Default::default()
+        };
+        let mut sender = Context::with_config(&MASTER_KEY, &MASTER_SALT, profile, config).unwrap();
+        let mut receiver =
+            Context::with_config(&MASTER_KEY, &MASTER_SALT, profile, config).unwrap();
+        let mut fixed = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        // the first 2^16 packets use the same keys as kdr = 0.
+        let packet = rtp_packet(65535, &[0xab; 16]);
+        assert_eq!(
+            sender.protect_rtp(&packet).unwrap(),
+            fixed.protect_rtp(&packet).unwrap()
+        );
+
+        let packet = rtp_packet(0, &[0xab; 16]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_ne!(protected, fixed.protect_rtp(&packet).unwrap());
+
+        receiver.set_roc(0xcafe_babe, 1);
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+        let config = ContextConfig {
+            key_derivation_rate: 1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            Context::with_config(&MASTER_KEY, &MASTER_SALT, profile, config).err(),
+            Some(SrtpError::InvalidKeyDerivationRate)
+        );
+    }
+
+    #[test]
+    fn master_key_identifier_test() {
+        for profile in &[
+            ProtectionProfile::Aes128CmHmacSha1_80,
+            ProtectionProfile::AeadAes128Gcm,
+        ] {
+            let key = &MASTER_KEY[..];
+            let salt = &MASTER_SALT[..profile.get_salt_length()];
+            let config = ContextConfig::default();
+            let mut sender = Context::with_mki(key, salt, &[0, 1], *profile, config).unwrap();
+            let mut receiver = Context::with_mki(key, salt, &[0, 1], *profile, config).unwrap();
+
+            let other_key = [0x11; 16];
+            assert_eq!(
+                sender.add_master_key(&[2], &other_key, salt),
+                Err(SrtpError::InvalidMki)
+            );
+            sender.add_master_key(&[0, 2], &other_key, salt).unwrap();
+            receiver.add_master_key(&[0, 2], &other_key, salt).unwrap();
+
+            let packet = rtp_packet(1, &[0xab; 16]);
+            let protected = sender.protect_rtp(&packet).unwrap();
+            let tag_length = profile.get_rtp_auth_tag_length();
+            let mki_offset = if profile.is_aead() {
+                protected.len() - 2
+            } else {
+                protected.len() - tag_length - 2
+            };
+            assert_eq!(&protected[mki_offset..mki_offset + 2], &[0, 1]);
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+            sender.set_active_mki(&[0, 2]).unwrap();
+            assert_eq!(
+                sender.remove_master_key(&[0, 2]),
+                Err(SrtpError::InvalidMki)
+            );
+            let packet = rtp_packet(2, &[0xab; 16]);
+            let protected = sender.protect_rtp(&packet).unwrap();
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+            let rtcp = rtcp_packet();
+            let protected = sender.protect_rtcp(&rtcp).unwrap();
+            assert_eq!(
+                protected.len(),
+                rtcp.len() + 4 + 2 + profile.get_rtcp_auth_tag_length()
+            );
+            assert_eq!(receiver.unprotect_rtcp(&protected).unwrap(), rtcp);
+
+            receiver.remove_master_key(&[0, 2]).unwrap();
+            let protected = sender.protect_rtp(&rtp_packet(3, &[0xab; 16])).unwrap();
+            assert_eq!(
+                receiver.unprotect_rtp(&protected),
+                Err(SrtpError::UnknownMki)
+            );
+        }
+    }
+
+    #[test]
+    fn estimate_roc_test() {
+        let mut state = SrtpSsrcState::new(64);
+        assert_eq!(state.estimate_roc(100), 0);
+
+        state.update(0, 65530);
+        assert_eq!(state.estimate_roc(65535), 0);
+        assert_eq!(state.estimate_roc(3), 1);
+
+        state.update(1, 3);
+        assert_eq!(state.estimate_roc(65533), 0);
+        assert_eq!(state.estimate_roc(10), 1);
+        assert_eq!(state.get_roc_candidates(10), vec![1, 2, 0]);
+
+        // never goes below 0.
+        let mut state = SrtpSsrcState::new(64);
+        state.update(0, 5);
+        assert_eq!(state.estimate_roc(65530), 0);
+        assert_eq!(state.get_roc_candidates(65530), vec![0, 1]);
+    }
+
+    #[test]
+    fn recover_roc_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        let packet = rtp_packet(65000, &[0x01; 8]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+        receiver.unprotect_rtp(&protected).unwrap();
+
+        // everything from 65001 to 39999 of the next cycle is lost.
+        sender.set_roc(0xcafe_babe, 1);
+        let packet = rtp_packet(40000, &[0x02; 8]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+        assert_eq!(receiver.get_srtp_state(0xcafe_babe).unwrap().get_roc(), 1);
+
+        // the recovered ROC sticks for the following packets.
+        let packet = rtp_packet(40001, &[0x03; 8]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+    }
+
+    #[test]
+    fn rekey_test() {
+        let now = Instant::now();
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        let in_flight = sender.protect_rtp(&rtp_packet(1, &[0x01; 8])).unwrap();
+        let in_flight_rtcp = sender.protect_rtcp(&rtcp_packet()).unwrap();
+
+        let new_key = [0x22; 16];
+        assert_eq!(
+            sender.rekey(&[1], &new_key, &MASTER_SALT, now),
+            Err(SrtpError::InvalidMki)
+        );
+        sender.rekey(&[], &new_key, &MASTER_SALT, now).unwrap();
+        receiver.rekey(&[], &new_key, &MASTER_SALT, now).unwrap();
+        assert_eq!(receiver.poll_timeout(), Some(now + Duration::from_secs(5)));
+
+        // the ROC, SRTCP index and replay state carry over.
+        let packet = rtp_packet(2, &[0x02; 8]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+        assert_eq!(sender.get_srtcp_state(0xcafe_babe).unwrap().get_index(), 1);
+
+        // packets protected with the old key still pass during the grace period.
+        assert_eq!(
+            receiver.unprotect_rtp(&in_flight).unwrap(),
+            rtp_packet(1, &[0x01; 8])
+        );
+        receiver.process(now + Duration::from_secs(1));
+        assert_eq!(receiver.get_retired_key_count(), 1);
+        assert_eq!(
+            receiver.unprotect_rtcp(&in_flight_rtcp).unwrap(),
+            rtcp_packet()
+        );
+
+        let late = sender.protect_rtp(&rtp_packet(3, &[0x03; 8])).unwrap();
+        let mut old = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let stale = old.protect_rtp(&rtp_packet(4, &[0x04; 8])).unwrap();
+
+        receiver.process(now + Duration::from_secs(5));
+        assert_eq!(receiver.get_retired_key_count(), 0);
+        assert_eq!(receiver.poll_timeout(), None);
+        assert_eq!(
+            receiver.unprotect_rtp(&stale),
+            Err(SrtpError::AuthenticationFailed)
+        );
+        assert!(receiver.unprotect_rtp(&late).is_ok());
+    }
+
+    #[test]
+    fn cryptex_test() {
+        // two CSRCs and a one-byte header extension.
+        let mut packet = vec![0x92, 0x60, 0x00, 0x01, 0, 0, 0, 0, 0xca, 0xfe, 0xba, 0xbe];
+        packet.extend_from_slice(&[0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22]);
+        packet.extend_from_slice(&[0xbe, 0xde, 0x00, 0x01, 0x10, 0xaa, 0x00, 0x00]);
+        packet.extend_from_slice(&[0xab; 16]);
+
+        let config = ContextConfig {
+            cryptex: true,
+            ..Default::default()
+        };
+        for profile in &[
+            ProtectionProfile::Aes128CmHmacSha1_80,
+            ProtectionProfile::AeadAes256Gcm,
+        ] {
+            let key = vec![0x42; profile.get_key_length()];
+            let salt = &MASTER_SALT[..profile.get_salt_length()];
+            let mut sender = Context::with_config(&key, salt, *profile, config).unwrap();
+            let mut receiver = Context::with_config(&key, salt, *profile, config).unwrap();
+
+            let protected = sender.protect_rtp(&packet).unwrap();
+            assert_eq!(&protected[..12], &packet[..12]);
+            assert_eq!(&protected[20..24], &[0xc0, 0xde, 0x00, 0x01]);
+            assert_ne!(&protected[12..20], &packet[12..20]);
+            assert_ne!(&protected[24..28], &packet[24..28]);
+
+            assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+            // without Cryptex only the payload is encrypted.
+            let mut plain = Context::with_config(&key, salt, *profile, Default::default()).unwrap();
+            let protected = plain.protect_rtp(&packet).unwrap();
+            assert_eq!(&protected[..28], &packet[..28]);
+        }
+    }
+
+    #[cfg(feature = "null-cipher")]
+    #[test]
+    fn null_cipher_test() {
+        let profile = ProtectionProfile::NullHmacSha1_80;
+        let mut sender = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+        let mut receiver = Context::new(&MASTER_KEY, &MASTER_SALT, profile).unwrap();
+
+        // the payload is sent in the clear, but still authenticated.
+        let packet = rtp_packet(1, &[0xab; 32]);
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_eq!(&protected[..packet.len()], &packet[..]);
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+
+        let mut tampered = sender.protect_rtp(&rtp_packet(2, &[0xab; 32])).unwrap();
+        tampered[20] ^= 1;
+        assert_eq!(
+            receiver.unprotect_rtp(&tampered),
+            Err(SrtpError::AuthenticationFailed)
+        );
+
+        let packet = rtcp_packet();
+        let protected = sender.protect_rtcp(&packet).unwrap();
+        assert_eq!(&protected[..packet.len()], &packet[..]);
+        assert_eq!(protected[packet.len()] & 0x80, 0);
+        assert_eq!(receiver.unprotect_rtcp(&protected).unwrap(), packet);
+    }
+}
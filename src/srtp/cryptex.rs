@@ -0,0 +1,159 @@
+// https://tools.ietf.org/html/rfc9335
+
+/*
+    Cryptex encrypts the CSRCs and the header extension data with the
+    payload. Only the fixed header and the 4 bytes extension header stay
+    in clear, the "defined by profile" value tells the receiver.
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |V=2|P|X|  CC   |M|     PT      |       sequence number         |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                           timestamp                           |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |           synchronization source (SSRC) identifier            |
+    +>+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | |            contributing source (CSRC) identifiers             |
+    | +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |   |   0xC0 or 0xC2    |    0xDE       |           length          |
+    | +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | |                  RFC 8285 header extensions                   |
+    | +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    | |                          payload  ...                         |
+    +>+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    the 4 bytes extension header is moved in front of the CSRCs while
+    encrypting so that the encrypted portion is contiguous.
+*/
+
+use crate::srtp::{Result, SrtpError};
+
+pub const ONE_BYTE_HEADER_PROFILE: u16 = 0xbede;
+pub const TWO_BYTE_HEADER_PROFILE: u16 = 0x1000;
+pub const CRYPTEX_ONE_BYTE_HEADER_PROFILE: u16 = 0xc0de;
+pub const CRYPTEX_TWO_BYTE_HEADER_PROFILE: u16 = 0xc2de;
+
+const RTP_HEADER_LENGTH: usize = 12;
+const EXTENSION_HEADER_LENGTH: usize = 4;
+
+/// Where the encrypted portion starts while the packet is rearranged.
+pub(crate) const CRYPTEX_ENCRYPTION_OFFSET: usize = RTP_HEADER_LENGTH + EXTENSION_HEADER_LENGTH;
+
+fn get_csrc_length(packet: &[u8]) -> usize {
+    4 * usize::from(packet[0] & 0x0f)
+}
+
+fn has_extension(packet: &[u8]) -> bool {
+    packet[0] & 0x10 > 0
+}
+
+/// Returns true if `packet` has a Cryptex header extension profile.
+pub fn is_cryptex(packet: &[u8]) -> bool {
+    if packet.len() < RTP_HEADER_LENGTH || !has_extension(packet) {
+        return false;
+    }
+    let offset = RTP_HEADER_LENGTH + get_csrc_length(packet);
+    if packet.len() < offset + EXTENSION_HEADER_LENGTH {
+        return false;
+    }
+    let profile = u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+    profile == CRYPTEX_ONE_BYTE_HEADER_PROFILE || profile == CRYPTEX_TWO_BYTE_HEADER_PROFILE
+}
+
+/// Returns true if `packet` has anything for Cryptex to encrypt.
+pub(crate) fn needs_cryptex(packet: &[u8]) -> bool {
+    get_csrc_length(packet) > 0 || has_extension(packet)
+}
+
+/// Switches the extension profile to its Cryptex value. A packet with
+/// CSRCs but no extension gets an empty one, RFC 9335 Section 5.1.
+pub(crate) fn to_cryptex(packet: &[u8]) -> Result<Vec<u8>> {
+    let offset = RTP_HEADER_LENGTH + get_csrc_length(packet);
+    let mut out = packet.to_vec();
+
+    if !has_extension(packet) {
+        out[0] |= 0x10;
+        let header = CRYPTEX_ONE_BYTE_HEADER_PROFILE.to_be_bytes();
+        out.splice(offset..offset, [header[0], header[1], 0, 0].iter().cloned());
+        return Ok(out);
+    }
+
+    let profile = match u16::from_be_bytes([packet[offset], packet[offset + 1]]) {
+        ONE_BYTE_HEADER_PROFILE => CRYPTEX_ONE_BYTE_HEADER_PROFILE,
+        TWO_BYTE_HEADER_PROFILE => CRYPTEX_TWO_BYTE_HEADER_PROFILE,
+        _ => return Err(SrtpError::UnsupportedHeaderExtensionProfile),
+    };
+    out[offset..offset + 2].copy_from_slice(&profile.to_be_bytes());
+    Ok(out)
+}
+
+/// Restores the RFC 8285 profile of a decrypted Cryptex packet.
+pub(crate) fn from_cryptex(packet: &mut [u8]) {
+    let offset = RTP_HEADER_LENGTH + get_csrc_length(packet);
+    let profile = match u16::from_be_bytes([packet[offset], packet[offset + 1]]) {
+        CRYPTEX_TWO_BYTE_HEADER_PROFILE => TWO_BYTE_HEADER_PROFILE,
+        _ => ONE_BYTE_HEADER_PROFILE,
+    };
+    packet[offset..offset + 2].copy_from_slice(&profile.to_be_bytes());
+}
+
+/// Moves the extension header in front of the CSRCs.
+pub(crate) fn rearrange(packet: &mut [u8]) {
+    let end = CRYPTEX_ENCRYPTION_OFFSET + get_csrc_length(packet);
+    packet[RTP_HEADER_LENGTH..end].rotate_right(EXTENSION_HEADER_LENGTH);
+}
+
+/// Moves the extension header back behind the CSRCs.
+pub(crate) fn restore(packet: &mut [u8]) {
+    let end = CRYPTEX_ENCRYPTION_OFFSET + get_csrc_length(packet);
+    packet[RTP_HEADER_LENGTH..end].rotate_left(EXTENSION_HEADER_LENGTH);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cryptex_layout_test() {
+        // one CSRC and a one-byte header extension with a single element.
+        let packet = vec![
+            0x91, 0x60, 0x00, 0x01, 0, 0, 0, 0, 0xca, 0xfe, 0xba, 0xbe, 0x11, 0x22, 0x33, 0x44,
+            0xbe, 0xde, 0x00, 0x01, 0x10, 0xaa, 0x00, 0x00, 0xff,
+        ];
+        assert!(!is_cryptex(&packet));
+
+        let mut cryptex = to_cryptex(&packet).unwrap();
+        assert!(is_cryptex(&cryptex));
+        assert_eq!(&cryptex[16..18], &[0xc0, 0xde]);
+
+        rearrange(&mut cryptex);
+        assert_eq!(
+            &cryptex[12..20],
+            &[0xc0, 0xde, 0x00, 0x01, 0x11, 0x22, 0x33, 0x44]
+        );
+        restore(&mut cryptex);
+
+        from_cryptex(&mut cryptex);
+        assert_eq!(cryptex, packet);
+    }
+
+    #[test]
+    fn empty_extension_test() {
+        let packet = vec![
+            0x81, 0x60, 0x00, 0x01, 0, 0, 0, 0, 0xca, 0xfe, 0xba, 0xbe, 0x11, 0x22, 0x33, 0x44,
+            0xff,
+        ];
+        let cryptex = to_cryptex(&packet).unwrap();
+        assert_eq!(cryptex[0], 0x91);
+        assert_eq!(&cryptex[16..20], &[0xc0, 0xde, 0x00, 0x00]);
+
+        let mut other = packet.clone();
+        other[0] |= 0x10;
+        other.splice(16..16, [0x12, 0x34, 0, 0].iter().cloned());
+        assert_eq!(
+            to_cryptex(&other),
+            Err(SrtpError::UnsupportedHeaderExtensionProfile)
+        );
+    }
+}
@@ -0,0 +1,271 @@
+// https://tools.ietf.org/html/rfc5764#section-4.2
+// https://tools.ietf.org/html/rfc5705
+
+/*
+    keying material = PRF(master_secret, "EXTRACTOR-dtls_srtp", ...)
+
+    +-------------------+-------------------+-------------------+-------------------+
+    | client write key  | server write key  | client write salt | server write salt |
+    +-------------------+-------------------+-------------------+-------------------+
+*/
+
+use crate::srtp::context::{Context, ContextConfig};
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::{Result, SrtpError};
+use crate::time::Instant;
+
+pub const DTLS_SRTP_EXPORTER_LABEL: &str = "EXTRACTOR-dtls_srtp";
+
+/// The part of a DTLS stack needed to key SRTP.
+pub trait KeyingMaterialExporter {
+    /// RFC 5705 exporter. Returns `None` before the handshake completes.
+    fn export_keying_material(
+        &self,
+        label: &str,
+        context: Option<&[u8]>,
+        length: usize,
+    ) -> Option<Vec<u8>>;
+
+    /// The protection profile id negotiated with the use_srtp extension.
+    fn get_selected_srtp_profile(&self) -> Option<u16>;
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum DtlsRole {
+    Client,
+    Server,
+}
+
+// 自分の書き込み鍵と相手の書き込み鍵．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DtlsSrtpKeys {
+    pub profile: ProtectionProfile,
+    pub local_master_key: Vec<u8>,
+    pub local_master_salt: Vec<u8>,
+    pub remote_master_key: Vec<u8>,
+    pub remote_master_salt: Vec<u8>,
+}
+
+impl DtlsSrtpKeys {
+    pub fn export<E: KeyingMaterialExporter + ?Sized>(
+        exporter: &E,
+        role: DtlsRole,
+    ) -> Result<Self> {
+        let profile = exporter
+            .get_selected_srtp_profile()
+            .ok_or(SrtpError::UnsupportedProtectionProfile)
+            .and_then(|id| {
+                ProtectionProfile::from_id(id).ok_or(SrtpError::UnsupportedProtectionProfile)
+            })?;
+
+        let key_length = profile.get_key_length();
+        let salt_length = profile.get_salt_length();
+        let material = exporter
+            .export_keying_material(
+                DTLS_SRTP_EXPORTER_LABEL,
+                None,
+                2 * (key_length + salt_length),
+            )
+            .ok_or(SrtpError::KeyingMaterialExportFailed)?;
+        DtlsSrtpKeys::from_keying_material(profile, &material, role)
+    }
+
+    pub fn from_keying_material(
+        profile: ProtectionProfile,
+        material: &[u8],
+        role: DtlsRole,
+    ) -> Result<Self> {
+        let key_length = profile.get_key_length();
+        let salt_length = profile.get_salt_length();
+        if material.len() != 2 * (key_length + salt_length) {
+            return Err(SrtpError::InvalidKeyLength);
+        }
+
+        let (keys, salts) = material.split_at(2 * key_length);
+        let (client_key, server_key) = keys.split_at(key_length);
+        let (client_salt, server_salt) = salts.split_at(salt_length);
+
+        let (local, remote) = match role {
+            DtlsRole::Client => ((client_key, client_salt), (server_key, server_salt)),
+            DtlsRole::Server => ((server_key, server_salt), (client_key, client_salt)),
+        };
+        Ok(DtlsSrtpKeys {
+            profile,
+            local_master_key: local.0.to_vec(),
+            local_master_salt: local.1.to_vec(),
+            remote_master_key: remote.0.to_vec(),
+            remote_master_salt: remote.1.to_vec(),
+        })
+    }
+}
+
+// DTLS handshake後に作る送信用と受信用のcontext．
+pub struct DtlsSrtpSession {
+    send: Context,
+    receive: Context,
+}
+
+impl DtlsSrtpSession {
+    pub fn new<E: KeyingMaterialExporter + ?Sized>(
+        exporter: &E,
+        role: DtlsRole,
+        config: ContextConfig,
+    ) -> Result<Self> {
+        let keys = DtlsSrtpKeys::export(exporter, role)?;
+        DtlsSrtpSession::from_keys(&keys, config)
+    }
+
+    pub fn from_keys(keys: &DtlsSrtpKeys, config: ContextConfig) -> Result<Self> {
+        Ok(DtlsSrtpSession {
+            send: Context::with_config(
+                &keys.local_master_key,
+                &keys.local_master_salt,
+                keys.profile,
+                config,
+            )?,
+            receive: Context::with_config(
+                &keys.remote_master_key,
+                &keys.remote_master_salt,
+                keys.profile,
+                config,
+            )?,
+        })
+    }
+
+    pub fn get_send_context(&mut self) -> &mut Context {
+        &mut self.send
+    }
+
+    pub fn get_receive_context(&mut self) -> &mut Context {
+        &mut self.receive
+    }
+
+    /// Installs the keys of a new handshake, keeping the old receive key for
+    /// the grace period. The profile must not change.
+    pub fn rekey<E: KeyingMaterialExporter + ?Sized>(
+        &mut self,
+        exporter: &E,
+        role: DtlsRole,
+        now: Instant,
+    ) -> Result<()> {
+        let keys = DtlsSrtpKeys::export(exporter, role)?;
+        if keys.profile != self.send.get_profile() {
+            return Err(SrtpError::UnsupportedProtectionProfile);
+        }
+        self.send
+            .rekey(&[], &keys.local_master_key, &keys.local_master_salt, now)?;
+        self.receive
+            .rekey(&[], &keys.remote_master_key, &keys.remote_master_salt, now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct FakeExporter {
+        profile: Option<u16>,
+        seed: u8,
+    }
+
+    impl KeyingMaterialExporter for FakeExporter {
+        fn export_keying_material(
+            &self,
+            label: &str,
+            context: Option<&[u8]>,
+            length: usize,
+        ) -> Option<Vec<u8>> {
+            assert_eq!(label, DTLS_SRTP_EXPORTER_LABEL);
+            assert_eq!(context, None);
+            Some(
+                (0..length)
+                    .map(|i| self.seed.wrapping_add(i as u8))
+                    .collect(),
+            )
+        }
+
+        fn get_selected_srtp_profile(&self) -> Option<u16> {
+            self.profile
+        }
+    }
+
+    #[test]
+    fn split_keying_material_test() {
+        let exporter = FakeExporter {
+            profile: Some(0x0001),
+            seed: 0,
+        };
+        let client = DtlsSrtpKeys::export(&exporter, DtlsRole::Client).unwrap();
+        assert_eq!(client.profile, ProtectionProfile::Aes128CmHmacSha1_80);
+        assert_eq!(client.local_master_key, (0..16).collect::<Vec<u8>>());
+        assert_eq!(client.remote_master_key, (16..32).collect::<Vec<u8>>());
+        assert_eq!(client.local_master_salt, (32..46).collect::<Vec<u8>>());
+        assert_eq!(client.remote_master_salt, (46..60).collect::<Vec<u8>>());
+
+        let server = DtlsSrtpKeys::export(&exporter, DtlsRole::Server).unwrap();
+        assert_eq!(server.local_master_key, client.remote_master_key);
+        assert_eq!(server.remote_master_salt, client.local_master_salt);
+
+        let gcm = FakeExporter {
+            profile: Some(0x0008),
+            seed: 0,
+        };
+        let keys = DtlsSrtpKeys::export(&gcm, DtlsRole::Client).unwrap();
+        assert_eq!(keys.local_master_key.len(), 32);
+        assert_eq!(keys.local_master_salt, (64..76).collect::<Vec<u8>>());
+
+        let unknown = FakeExporter {
+            profile: Some(0x0003),
+            seed: 0,
+        };
+        assert_eq!(
+            DtlsSrtpKeys::export(&unknown, DtlsRole::Client),
+            Err(SrtpError::UnsupportedProtectionProfile)
+        );
+    }
+
+    #[test]
+    fn dtls_srtp_session_test() {
+        let exporter = FakeExporter {
+            profile: Some(0x0007),
+            seed: 1,
+        };
+        let config = ContextConfig::default();
+        let mut client = DtlsSrtpSession::new(&exporter, DtlsRole::Client, config).unwrap();
+        let mut server = DtlsSrtpSession::new(&exporter, DtlsRole::Server, config).unwrap();
+
+        let packet = vec![0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0x04, 0xd2, 1, 2, 3];
+        let protected = client.get_send_context().protect_rtp(&packet).unwrap();
+        assert_eq!(
+            server
+                .get_receive_context()
+                .unprotect_rtp(&protected)
+                .unwrap(),
+            packet
+        );
+
+        // the client's own receive context uses the server's key.
+        assert!(client
+            .get_receive_context()
+            .unprotect_rtp(&protected)
+            .is_err());
+
+        let now = Instant::now();
+        let exporter = FakeExporter {
+            profile: Some(0x0007),
+            seed: 100,
+        };
+        client.rekey(&exporter, DtlsRole::Client, now).unwrap();
+        server.rekey(&exporter, DtlsRole::Server, now).unwrap();
+
+        let packet = vec![0x80, 0x60, 0, 2, 0, 0, 0, 0, 0, 0, 0x04, 0xd2, 4, 5, 6];
+        let protected = client.get_send_context().protect_rtp(&packet).unwrap();
+        assert_eq!(
+            server
+                .get_receive_context()
+                .unprotect_rtp(&protected)
+                .unwrap(),
+            packet
+        );
+    }
+}
@@ -0,0 +1,432 @@
+// https://tools.ietf.org/html/rfc8870
+
+/*
+    EKTField, appended to an SRTP or SRTCP packet after the auth tag.
+
+    FullEKTField
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    :                                                               :
+    :                        EKT Ciphertext                         :
+    :                                                               :
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |   Security Parameter Index    |            Length             |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |0 0 0 0 0 0 1 0|
+    +-+-+-+-+-+-+-+-+
+
+    ShortEKTField
+     0 1 2 3 4 5 6 7
+    +-+-+-+-+-+-+-+-+
+    |0 0 0 0 0 0 0 0|
+    +-+-+-+-+-+-+-+-+
+
+    EKTPlaintext = SRTPMasterKeyLength || SRTPMasterKey || SSRC || ROC,
+    encrypted with AES Key Wrap with Padding (RFC 5649) under the EKTKey.
+
+    A receiver takes the master key of a FullEKTField only once the SRTP
+    packet authenticates under it, the SSRC is the packet's own and the
+    ROC is not behind the one it knows (RFC 8870 Section 4.3.2).
+*/
+
+use crate::srtp::context::{get_rtp_header_length, Context};
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::{Result, SrtpError};
+
+use aes_kw::{KekAes128, KekAes256};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+pub const EKT_MSG_TYPE_SHORT: u8 = 0x00;
+pub const EKT_MSG_TYPE_FULL: u8 = 0x02;
+
+const FULL_EKT_FIELD_TRAILER_LENGTH: usize = 5;
+
+/// The SRTP master key of one sender, carried encrypted in a FullEKTField.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct EktPlaintext {
+    pub master_key: Vec<u8>,
+    pub ssrc: u32,
+    pub roc: u32,
+}
+
+impl EktPlaintext {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        if self.master_key.is_empty() || self.master_key.len() > usize::from(u8::MAX) {
+            return Err(SrtpError::InvalidKeyLength);
+        }
+        let mut out = Vec::with_capacity(1 + self.master_key.len() + 8);
+        out.push(self.master_key.len() as u8);
+        out.extend_from_slice(&self.master_key);
+        out.extend_from_slice(&self.ssrc.to_be_bytes());
+        out.extend_from_slice(&self.roc.to_be_bytes());
+        Ok(out)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<EktPlaintext> {
+        let length = usize::from(*bytes.first().ok_or(SrtpError::InvalidEktField)?);
+        if length == 0 || bytes.len() != 1 + length + 8 {
+            return Err(SrtpError::InvalidEktField);
+        }
+        let ssrc = &bytes[1 + length..5 + length];
+        let roc = &bytes[5 + length..];
+        Ok(EktPlaintext {
+            master_key: bytes[1..1 + length].to_vec(),
+            ssrc: u32::from_be_bytes([ssrc[0], ssrc[1], ssrc[2], ssrc[3]]),
+            roc: u32::from_be_bytes([roc[0], roc[1], roc[2], roc[3]]),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum EktField {
+    Short,
+    Full { spi: u16, ciphertext: Vec<u8> },
+}
+
+impl EktField {
+    pub fn get_length(&self) -> usize {
+        match self {
+            EktField::Short => 1,
+            EktField::Full { ciphertext, .. } => ciphertext.len() + FULL_EKT_FIELD_TRAILER_LENGTH,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            EktField::Short => Ok(vec![EKT_MSG_TYPE_SHORT]),
+            EktField::Full { spi, ciphertext } => {
+                let length = self.get_length();
+                if length > usize::from(u16::MAX) {
+                    return Err(SrtpError::InvalidEktField);
+                }
+                let mut out = ciphertext.clone();
+                out.extend_from_slice(&spi.to_be_bytes());
+                out.extend_from_slice(&(length as u16).to_be_bytes());
+                out.push(EKT_MSG_TYPE_FULL);
+                Ok(out)
+            }
+        }
+    }
+
+    /// Splits the EKTField off the end of `packet`, returning the SRTP or
+    /// SRTCP packet in front of it.
+    pub fn split(packet: &[u8]) -> Result<(&[u8], EktField)> {
+        let msg_type = *packet.last().ok_or(SrtpError::PacketTooShort)?;
+        match msg_type {
+            EKT_MSG_TYPE_SHORT => Ok((&packet[..packet.len() - 1], EktField::Short)),
+            EKT_MSG_TYPE_FULL => {
+                if packet.len() < FULL_EKT_FIELD_TRAILER_LENGTH {
+                    return Err(SrtpError::InvalidEktField);
+                }
+                let trailer = &packet[packet.len() - FULL_EKT_FIELD_TRAILER_LENGTH..];
+                let spi = u16::from_be_bytes([trailer[0], trailer[1]]);
+                let length = usize::from(u16::from_be_bytes([trailer[2], trailer[3]]));
+                if length <= FULL_EKT_FIELD_TRAILER_LENGTH || length > packet.len() {
+                    return Err(SrtpError::InvalidEktField);
+                }
+
+                let start = packet.len() - length;
+                let ciphertext =
+                    packet[start..packet.len() - FULL_EKT_FIELD_TRAILER_LENGTH].to_vec();
+                Ok((&packet[..start], EktField::Full { spi, ciphertext }))
+            }
+            _ => Err(SrtpError::InvalidEktField),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum EktCipher {
+    AesKw128(Box<KekAes128>),
+    AesKw256(Box<KekAes256>),
+}
+
+// EKTKeyとそのSPI．DTLS-SRTPのEKTKey messageで配布される．
+#[derive(Clone)]
+pub struct EktKey {
+    spi: u16,
+    cipher: EktCipher,
+}
+
+impl EktKey {
+    /// `key` is a 128 or 256 bit EKTKey.
+    pub fn new(spi: u16, key: &[u8]) -> Result<Self> {
+        let cipher = match key.len() {
+            16 => EktCipher::AesKw128(Box::new(
+                KekAes128::try_from(key).map_err(|_| SrtpError::InvalidKeyLength)?,
+            )),
+            32 => EktCipher::AesKw256(Box::new(
+                KekAes256::try_from(key).map_err(|_| SrtpError::InvalidKeyLength)?,
+            )),
+            _ => return Err(SrtpError::InvalidKeyLength),
+        };
+        Ok(EktKey { spi, cipher })
+    }
+
+    pub fn get_spi(&self) -> u16 {
+        self.spi
+    }
+
+    pub fn encrypt(&self, plaintext: &EktPlaintext) -> Result<EktField> {
+        let bytes = plaintext.to_bytes()?;
+        let ciphertext = match &self.cipher {
+            EktCipher::AesKw128(v) => v.wrap_with_padding_vec(&bytes),
+            EktCipher::AesKw256(v) => v.wrap_with_padding_vec(&bytes),
+        }
+        .map_err(|_| SrtpError::InvalidEktField)?;
+
+        Ok(EktField::Full {
+            spi: self.spi,
+            ciphertext,
+        })
+    }
+
+    /// Returns `None` for a ShortEKTField.
+    pub fn decrypt(&self, field: &EktField) -> Result<Option<EktPlaintext>> {
+        let (spi, ciphertext) = match field {
+            EktField::Short => return Ok(None),
+            EktField::Full { spi, ciphertext } => (*spi, ciphertext),
+        };
+        if spi != self.spi {
+            return Err(SrtpError::UnknownEktSpi);
+        }
+
+        let bytes = match &self.cipher {
+            EktCipher::AesKw128(v) => v.unwrap_with_padding_vec(ciphertext),
+            EktCipher::AesKw256(v) => v.unwrap_with_padding_vec(ciphertext),
+        }
+        .map_err(|_| SrtpError::AuthenticationFailed)?;
+        EktPlaintext::from_bytes(&bytes).map(Some)
+    }
+}
+
+/// Appends `field` to a protected packet.
+pub fn append_ekt_field(mut packet: Vec<u8>, field: &EktField) -> Result<Vec<u8>> {
+    packet.extend_from_slice(&field.to_bytes()?);
+    Ok(packet)
+}
+
+// 送信者のSSRCごとに，EKTで受け取ったmaster keyとそのSRTP contextを持つ．
+pub struct EktReceiver {
+    ekt_key: EktKey,
+    master_salt: Vec<u8>,
+    profile: ProtectionProfile,
+    contexts: HashMap<u32, (Vec<u8>, Context)>,
+}
+
+impl EktReceiver {
+    /// `master_salt` and `profile` are the ones DTLS-SRTP negotiated.
+    pub fn new(ekt_key: EktKey, master_salt: &[u8], profile: ProtectionProfile) -> Self {
+        EktReceiver {
+            ekt_key,
+            master_salt: master_salt.to_vec(),
+            profile,
+            contexts: HashMap::new(),
+        }
+    }
+
+    pub fn get_context(&self, ssrc: u32) -> Option<&Context> {
+        self.contexts.get(&ssrc).map(|(_, context)| context)
+    }
+
+    /// Splits the EKTField off `packet` and unprotects the SRTP packet in
+    /// front of it. Returns the EKTPlaintext of a FullEKTField whose key
+    /// the sender now uses.
+    pub fn unprotect_rtp(&mut self, packet: &[u8]) -> Result<(Vec<u8>, Option<EktPlaintext>)> {
+        let (srtp, field) = EktField::split(packet)?;
+        get_rtp_header_length(srtp)?;
+        let ssrc = u32::from_be_bytes([srtp[8], srtp[9], srtp[10], srtp[11]]);
+
+        let plaintext = match self.ekt_key.decrypt(&field)? {
+            Some(v) => v,
+            None => {
+                let (_, context) = self
+                    .contexts
+                    .get_mut(&ssrc)
+                    .ok_or(SrtpError::MissingEktMasterKey)?;
+                return Ok((context.unprotect_rtp(srtp)?, None));
+            }
+        };
+        if plaintext.ssrc != ssrc {
+            return Err(SrtpError::EktSsrcMismatch);
+        }
+
+        let roc = self
+            .contexts
+            .get(&ssrc)
+            .and_then(|(_, context)| context.get_srtp_state(ssrc))
+            .map(|v| v.get_roc());
+        if roc.map(|v| plaintext.roc < v).unwrap_or(false) {
+            return Err(SrtpError::EktRocMismatch);
+        }
+
+        // the same key is sent again and again, its replay state is kept.
+        if let Some((master_key, context)) = self.contexts.get_mut(&ssrc) {
+            if *master_key == plaintext.master_key {
+                return Ok((context.unprotect_rtp(srtp)?, Some(plaintext)));
+            }
+        }
+
+        let mut context = Context::new(&plaintext.master_key, &self.master_salt, self.profile)?;
+        context.set_roc(ssrc, plaintext.roc);
+        let payload = context.unprotect_rtp(srtp)?;
+        self.contexts
+            .insert(ssrc, (plaintext.master_key.clone(), context));
+        Ok((payload, Some(plaintext)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::srtp::context::Context;
+    use crate::srtp::protection_profile::ProtectionProfile;
+
+    #[test]
+    fn ekt_field_test() {
+        let ekt_key = EktKey::new(0x1234, &[0x5a; 16]).unwrap();
+        let plaintext = EktPlaintext {
+            master_key: vec![0x01; 16],
+            ssrc: 0xcafe_babe,
+            roc: 3,
+        };
+
+        let field = ekt_key.encrypt(&plaintext).unwrap();
+        let bytes = field.to_bytes().unwrap();
+        // 1 + 16 + 8 bytes padded to 32, plus the 8 bytes integrity block.
+        assert_eq!(bytes.len(), 40 + 5);
+        assert_eq!(&bytes[40..], &[0x12, 0x34, 0x00, 45, EKT_MSG_TYPE_FULL]);
+
+        let mut packet = vec![0xaa; 20];
+        packet.extend_from_slice(&bytes);
+        let (srtp, parsed) = EktField::split(&packet).unwrap();
+        assert_eq!(srtp, &[0xaa; 20][..]);
+        assert_eq!(parsed, field);
+        assert_eq!(ekt_key.decrypt(&parsed).unwrap(), Some(plaintext));
+
+        let other = EktKey::new(0x1234, &[0x5b; 16]).unwrap();
+        assert_eq!(other.decrypt(&parsed), Err(SrtpError::AuthenticationFailed));
+        let other = EktKey::new(0x4321, &[0x5a; 16]).unwrap();
+        assert_eq!(other.decrypt(&parsed), Err(SrtpError::UnknownEktSpi));
+
+        let (srtp, parsed) = EktField::split(&[0xaa, 0xbb, 0x00]).unwrap();
+        assert_eq!(srtp, &[0xaa, 0xbb]);
+        assert_eq!(parsed, EktField::Short);
+        assert_eq!(
+            EktField::split(&[0xaa, 0x01]),
+            Err(SrtpError::InvalidEktField)
+        );
+    }
+
+    #[test]
+    fn distribute_master_key_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let master_key = [0x77; 16];
+        let master_salt = [0x88; 14];
+        let ekt_key = EktKey::new(7, &[0x5a; 32]).unwrap();
+
+        let mut sender = Context::new(&master_key, &master_salt, profile).unwrap();
+        let mut packet = vec![0x80, 0x60, 0x00, 0x01, 0, 0, 0, 0, 0xca, 0xfe, 0xba, 0xbe];
+        packet.extend_from_slice(&[0xab; 16]);
+
+        let field = ekt_key
+            .encrypt(&EktPlaintext {
+                master_key: master_key.to_vec(),
+                ssrc: 0xcafe_babe,
+                roc: 0,
+            })
+            .unwrap();
+        let sent = append_ekt_field(sender.protect_rtp(&packet).unwrap(), &field).unwrap();
+
+        // the receiver learns the sender's key from the packet itself.
+        let (srtp, field) = EktField::split(&sent).unwrap();
+        let plaintext = ekt_key.decrypt(&field).unwrap().unwrap();
+        let mut receiver = Context::new(&plaintext.master_key, &master_salt, profile).unwrap();
+        receiver.set_roc(plaintext.ssrc, plaintext.roc);
+        assert_eq!(receiver.unprotect_rtp(srtp).unwrap(), packet);
+    }
+
+    #[test]
+    fn ekt_receiver_test() {
+        let profile = ProtectionProfile::Aes128CmHmacSha1_80;
+        let master_key = [0x77; 16];
+        let master_salt = [0x88; 14];
+        let ekt_key = EktKey::new(7, &[0x5a; 16]).unwrap();
+        let mut receiver = EktReceiver::new(ekt_key.clone(), &master_salt, profile);
+
+        let mut sender = Context::new(&master_key, &master_salt, profile).unwrap();
+        sender.set_roc(0xcafe_babe, 2);
+        let packet = |sequence: u8| {
+            let mut v = vec![
+                0x80, 0x60, 0x00, sequence, 0, 0, 0, 0, 0xca, 0xfe, 0xba, 0xbe,
+            ];
+            v.extend_from_slice(&[0xab; 16]);
+            v
+        };
+        let plaintext = |master_key: &[u8], ssrc: u32, roc: u32| EktPlaintext {
+            master_key: master_key.to_vec(),
+            ssrc,
+            roc,
+        };
+        let full = |sender: &mut Context, sequence: u8, plaintext: &EktPlaintext| {
+            let field = ekt_key.encrypt(plaintext).unwrap();
+            append_ekt_field(sender.protect_rtp(&packet(sequence)).unwrap(), &field).unwrap()
+        };
+
+        // nothing is known of the sender before its first FullEKTField.
+        let short =
+            append_ekt_field(sender.protect_rtp(&packet(1)).unwrap(), &EktField::Short).unwrap();
+        assert_eq!(
+            receiver.unprotect_rtp(&short).err(),
+            Some(SrtpError::MissingEktMasterKey)
+        );
+
+        // the EKTPlaintext of another sender is not taken.
+        let sent = full(&mut sender, 2, &plaintext(&master_key, 0x1234, 2));
+        assert_eq!(
+            receiver.unprotect_rtp(&sent).err(),
+            Some(SrtpError::EktSsrcMismatch)
+        );
+        assert!(receiver.get_context(0x1234).is_none());
+        assert!(receiver.get_context(0xcafe_babe).is_none());
+
+        // a key the packet does not authenticate under is not taken either.
+        let sent = full(&mut sender, 3, &plaintext(&[0x66; 16], 0xcafe_babe, 2));
+        assert_eq!(
+            receiver.unprotect_rtp(&sent).err(),
+            Some(SrtpError::AuthenticationFailed)
+        );
+        assert!(receiver.get_context(0xcafe_babe).is_none());
+
+        let expected = plaintext(&master_key, 0xcafe_babe, 2);
+        let sent = full(&mut sender, 4, &expected);
+        assert_eq!(
+            receiver.unprotect_rtp(&sent).unwrap(),
+            (packet(4), Some(expected.clone()))
+        );
+        assert_eq!(
+            receiver
+                .get_context(0xcafe_babe)
+                .and_then(|v| v.get_srtp_state(0xcafe_babe))
+                .map(|v| v.get_roc()),
+            Some(2)
+        );
+
+        let short =
+            append_ekt_field(sender.protect_rtp(&packet(5)).unwrap(), &EktField::Short).unwrap();
+        assert_eq!(receiver.unprotect_rtp(&short).unwrap(), (packet(5), None));
+        // the repeated key keeps the replay state.
+        assert_eq!(
+            receiver.unprotect_rtp(&sent).err(),
+            Some(SrtpError::DuplicatedPacket)
+        );
+
+        let sent = full(&mut sender, 6, &plaintext(&master_key, 0xcafe_babe, 1));
+        assert_eq!(
+            receiver.unprotect_rtp(&sent).err(),
+            Some(SrtpError::EktRocMismatch)
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// https://tools.ietf.org/html/rfc3711#section-4.3
+
+use crate::srtp::cipher::aes_cm_keystream;
+use crate::srtp::{Result, SrtpError};
+
+pub const LABEL_SRTP_ENCRYPTION: u8 = 0x00;
+pub const LABEL_SRTP_AUTHENTICATION: u8 = 0x01;
+pub const LABEL_SRTP_SALT: u8 = 0x02;
+pub const LABEL_SRTCP_ENCRYPTION: u8 = 0x03;
+pub const LABEL_SRTCP_AUTHENTICATION: u8 = 0x04;
+pub const LABEL_SRTCP_SALT: u8 = 0x05;
+
+/// Returns `r = index DIV kdr`, RFC 3711 Section 4.3.1.
+pub fn get_index_over_kdr(index: u64, key_derivation_rate: u64) -> u64 {
+    index.checked_div(key_derivation_rate).unwrap_or(0)
+}
+
+/// The key derivation rate is 0 or a power of 2 from 1 to 2^24.
+pub fn is_valid_key_derivation_rate(key_derivation_rate: u64) -> bool {
+    key_derivation_rate == 0
+        || (key_derivation_rate.is_power_of_two() && key_derivation_rate <= 1 << 24)
+}
+
+/// Derives `length` bytes of the session key `label` from the master key
+/// and salt. `index_over_kdr` is `index DIV key_derivation_rate`, 0 when
+/// the key derivation rate is 0.
+pub fn aes_cm_key_derivation(
+    label: u8,
+    master_key: &[u8],
+    master_salt: &[u8],
+    index_over_kdr: u64,
+    length: usize,
+) -> Result<Vec<u8>> {
+    if master_salt.len() > 14 {
+        return Err(SrtpError::InvalidKeyLength);
+    }
+
+    // key_id = label || r. the 96bit salt of the AEAD profiles is padded
+    // with zeros on the right, RFC 7714 Section 11.
+    let mut x = [0u8; 14];
+    x[..master_salt.len()].copy_from_slice(master_salt);
+    x[7] ^= label;
+    let r = index_over_kdr.to_be_bytes();
+    for i in 0..6 {
+        x[8 + i] ^= r[2 + i];
+    }
+
+    let mut iv = [0u8; 16];
+    iv[..14].copy_from_slice(&x);
+
+    let mut out = vec![0; length];
+    aes_cm_keystream(master_key, &iv, &mut out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // https://tools.ietf.org/html/rfc3711#appendix-B.3
+    const MASTER_KEY: [u8; 16] = [
+        0xe1, 0xf9, 0x7a, 0x0d, 0x3e, 0x01, 0x8b, 0xe0, 0xd6, 0x4f, 0xa3, 0x2c, 0x06, 0xde, 0x41,
+        0x39,
+    ];
+    const MASTER_SALT: [u8; 14] = [
+        0x0e, 0xc6, 0x75, 0xad, 0x49, 0x8a, 0xfe, 0xeb, 0xb6, 0x96, 0x0b, 0x3a, 0xab, 0xe6,
+    ];
+
+    #[test]
+    fn rfc3711_key_derivation_test() {
+        let key = aes_cm_key_derivation(LABEL_SRTP_ENCRYPTION, &MASTER_KEY, &MASTER_SALT, 0, 16);
+        assert_eq!(
+            key.unwrap(),
+            vec![
+                0xc6, 0x1e, 0x7a, 0x93, 0x74, 0x4f, 0x39, 0xee, 0x10, 0x73, 0x4a, 0xfe, 0x3f, 0xf7,
+                0xa0, 0x87,
+            ]
+        );
+
+        let salt = aes_cm_key_derivation(LABEL_SRTP_SALT, &MASTER_KEY, &MASTER_SALT, 0, 14);
+        assert_eq!(
+            salt.unwrap(),
+            vec![
+                0x30, 0xcb, 0xbc, 0x08, 0x86, 0x3d, 0x8c, 0x85, 0xd4, 0x9d, 0xb3, 0x4a, 0x9a, 0xe1,
+            ]
+        );
+
+        let auth_key =
+            aes_cm_key_derivation(LABEL_SRTP_AUTHENTICATION, &MASTER_KEY, &MASTER_SALT, 0, 20);
+        assert_eq!(
+            auth_key.unwrap(),
+            vec![
+                0xce, 0xbe, 0x32, 0x1f, 0x6f, 0xf7, 0x71, 0x6b, 0x6f, 0xd4, 0xab, 0x49, 0xaf, 0x25,
+                0x6a, 0x15, 0x6d, 0x38, 0xba, 0xa4,
+            ]
+        );
+    }
+
+    #[test]
+    fn key_derivation_rate_test() {
+        assert_eq!(get_index_over_kdr(70_000, 0), 0);
+        assert_eq!(get_index_over_kdr(70_000, 1 << 16), 1);
+        assert!(is_valid_key_derivation_rate(0));
+        assert!(is_valid_key_derivation_rate(1 << 24));
+        assert!(!is_valid_key_derivation_rate(3));
+        assert!(!is_valid_key_derivation_rate(1 << 25));
+
+        let derive = |r| {
+            aes_cm_key_derivation(LABEL_SRTP_ENCRYPTION, &MASTER_KEY, &MASTER_SALT, r, 16).unwrap()
+        };
+        assert_ne!(derive(0), derive(1));
+        assert_ne!(derive(1), derive(2));
+    }
+}
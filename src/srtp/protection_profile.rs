@@ -0,0 +1,116 @@
+// https://tools.ietf.org/html/rfc3711#section-8.2
+// https://tools.ietf.org/html/rfc5764#section-4.1.2
+// https://tools.ietf.org/html/rfc7714#section-14.2
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProtectionProfile {
+    Aes128CmHmacSha1_80,
+    Aes128CmHmacSha1_32,
+    AeadAes128Gcm,
+    AeadAes256Gcm,
+    /// Authenticated but not encrypted, so captures stay readable. Only for
+    /// development.
+    #[cfg(feature = "null-cipher")]
+    NullHmacSha1_80,
+    #[cfg(feature = "null-cipher")]
+    NullHmacSha1_32,
+}
+
+impl ProtectionProfile {
+    /// Returns the profile of a DTLS-SRTP protection profile identifier.
+    pub fn from_id(id: u16) -> Option<ProtectionProfile> {
+        match id {
+            0x0001 => Some(ProtectionProfile::Aes128CmHmacSha1_80),
+            0x0002 => Some(ProtectionProfile::Aes128CmHmacSha1_32),
+            #[cfg(feature = "null-cipher")]
+            0x0005 => Some(ProtectionProfile::NullHmacSha1_80),
+            #[cfg(feature = "null-cipher")]
+            0x0006 => Some(ProtectionProfile::NullHmacSha1_32),
+            0x0007 => Some(ProtectionProfile::AeadAes128Gcm),
+            0x0008 => Some(ProtectionProfile::AeadAes256Gcm),
+            _ => None,
+        }
+    }
+
+    pub fn get_id(self) -> u16 {
+        match self {
+            ProtectionProfile::Aes128CmHmacSha1_80 => 0x0001,
+            ProtectionProfile::Aes128CmHmacSha1_32 => 0x0002,
+            #[cfg(feature = "null-cipher")]
+            ProtectionProfile::NullHmacSha1_80 => 0x0005,
+            #[cfg(feature = "null-cipher")]
+            ProtectionProfile::NullHmacSha1_32 => 0x0006,
+            ProtectionProfile::AeadAes128Gcm => 0x0007,
+            ProtectionProfile::AeadAes256Gcm => 0x0008,
+        }
+    }
+
+    pub fn is_aead(self) -> bool {
+        matches!(
+            self,
+            ProtectionProfile::AeadAes128Gcm | ProtectionProfile::AeadAes256Gcm
+        )
+    }
+
+    pub fn is_null_cipher(self) -> bool {
+        #[cfg(feature = "null-cipher")]
+        {
+            matches!(
+                self,
+                ProtectionProfile::NullHmacSha1_80 | ProtectionProfile::NullHmacSha1_32
+            )
+        }
+        #[cfg(not(feature = "null-cipher"))]
+        {
+            false
+        }
+    }
+
+    /// The null profiles still take an AES-128 sized master key for the key
+    /// derivation.
+    pub fn get_key_length(self) -> usize {
+        match self {
+            ProtectionProfile::AeadAes256Gcm => 32,
+            _ => 16,
+        }
+    }
+
+    pub fn get_salt_length(self) -> usize {
+        if self.is_aead() {
+            12
+        } else {
+            14
+        }
+    }
+
+    /// The AEAD profiles have no separate auth key.
+    pub fn get_auth_key_length(self) -> usize {
+        if self.is_aead() {
+            0
+        } else {
+            20
+        }
+    }
+
+    pub fn get_rtp_auth_tag_length(self) -> usize {
+        match self {
+            ProtectionProfile::Aes128CmHmacSha1_80 => 10,
+            ProtectionProfile::Aes128CmHmacSha1_32 => 4,
+            #[cfg(feature = "null-cipher")]
+            ProtectionProfile::NullHmacSha1_80 => 10,
+            #[cfg(feature = "null-cipher")]
+            ProtectionProfile::NullHmacSha1_32 => 4,
+            ProtectionProfile::AeadAes128Gcm | ProtectionProfile::AeadAes256Gcm => 16,
+        }
+    }
+
+    /// SRTCP always uses the 80bit tag, even with the _32 profile.
+    pub fn get_rtcp_auth_tag_length(self) -> usize {
+        if self.is_aead() {
+            16
+        } else {
+            10
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// https://tools.ietf.org/html/rfc3711#section-3.3.2
+
+use crate::srtp::{Result, SrtpError};
+
+// 直近`window_size`個のindexの受信済みbitmap．bitの位置は index % window_size．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ReplayDetector {
+    window_size: u64,
+    latest: Option<u64>,
+    mask: Vec<u64>,
+}
+
+impl ReplayDetector {
+    /// A `window_size` of 0 disables replay protection.
+    pub fn new(window_size: usize) -> Self {
+        ReplayDetector {
+            window_size: window_size as u64,
+            latest: None,
+            mask: vec![0; window_size.div_ceil(64)],
+        }
+    }
+
+    pub fn get_window_size(&self) -> usize {
+        self.window_size as usize
+    }
+
+    pub fn get_latest(&self) -> Option<u64> {
+        self.latest
+    }
+
+    /// Fails if `index` was already accepted or is behind the window.
+    /// Packets must be authenticated before they are `accept`ed.
+    pub fn check(&self, index: u64) -> Result<()> {
+        let latest = match self.latest {
+            Some(v) if self.window_size > 0 => v,
+            _ => return Ok(()),
+        };
+
+        if index > latest {
+            return Ok(());
+        }
+        if latest - index >= self.window_size {
+            return Err(SrtpError::PacketTooOld);
+        }
+        if self.get_bit(index) {
+            return Err(SrtpError::DuplicatedPacket);
+        }
+        Ok(())
+    }
+
+    pub fn accept(&mut self, index: u64) {
+        if self.window_size == 0 {
+            return;
+        }
+
+        match self.latest {
+            Some(latest) if index <= latest => {}
+            Some(latest) => {
+                // forget the indices the window slides over.
+                if index - latest >= self.window_size {
+                    for v in self.mask.iter_mut() {
+                        *v = 0;
+                    }
+                } else {
+                    for i in latest + 1..index {
+                        self.set_bit(i, false);
+                    }
+                }
+                self.latest = Some(index);
+            }
+            None => self.latest = Some(index),
+        }
+        self.set_bit(index, true);
+    }
+
+    fn get_bit(&self, index: u64) -> bool {
+        let bit = index % self.window_size;
+        self.mask[(bit / 64) as usize] & (1 << (bit % 64)) > 0
+    }
+
+    fn set_bit(&mut self, index: u64, value: bool) {
+        let bit = index % self.window_size;
+        let word = &mut self.mask[(bit / 64) as usize];
+        if value {
+            *word |= 1 << (bit % 64);
+        } else {
+            *word &= !(1 << (bit % 64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replay_detector_test() {
+        let mut detector = ReplayDetector::new(64);
+        assert_eq!(detector.check(10), Ok(()));
+        detector.accept(10);
+        assert_eq!(detector.check(10), Err(SrtpError::DuplicatedPacket));
+
+        // late but inside the window.
+        assert_eq!(detector.check(5), Ok(()));
+        detector.accept(5);
+        assert_eq!(detector.check(5), Err(SrtpError::DuplicatedPacket));
+
+        detector.accept(100);
+        assert_eq!(detector.get_latest(), Some(100));
+        assert_eq!(detector.check(36), Err(SrtpError::PacketTooOld));
+        assert_eq!(detector.check(37), Ok(()));
+        assert_eq!(detector.check(100), Err(SrtpError::DuplicatedPacket));
+
+        // bits of indices the window slid over are cleared.
+        detector.accept(110);
+        assert_eq!(detector.check(74), Ok(()));
+        assert_eq!(detector.check(101), Ok(()));
+    }
+
+    #[test]
+    fn disabled_replay_detector_test() {
+        let mut detector = ReplayDetector::new(0);
+        detector.accept(1);
+        assert_eq!(detector.check(1), Ok(()));
+    }
+}
@@ -0,0 +1,354 @@
+// https://tools.ietf.org/html/rfc4568#section-9.1
+// https://tools.ietf.org/html/rfc7714#section-12
+
+/*
+    a=crypto:<tag> <crypto-suite> <key-params> [<session-params>]
+
+    key-params = "inline:" <base64(key || salt)> ["|" lifetime] ["|" MKI ":" length]
+
+    e.g.
+    a=crypto:1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:4
+*/
+
+use crate::srtp::context::{Context, ContextConfig};
+use crate::srtp::protection_profile::ProtectionProfile;
+use crate::srtp::{Result, SrtpError};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::fmt;
+
+pub const CRYPTO_ATTRIBUTE_NAME: &str = "crypto";
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CryptoKeyParams {
+    pub master_key: Vec<u8>,
+    pub master_salt: Vec<u8>,
+    /// The number of packets the key may protect.
+    pub lifetime: Option<u64>,
+    /// MKI value and its length in bytes.
+    pub mki: Option<(u64, u8)>,
+}
+
+impl CryptoKeyParams {
+    pub fn get_mki_bytes(&self) -> Vec<u8> {
+        match self.mki {
+            Some((value, length)) => {
+                let bytes = value.to_be_bytes();
+                let length = usize::from(length);
+                if length > bytes.len() {
+                    let mut out = vec![0; length - bytes.len()];
+                    out.extend_from_slice(&bytes);
+                    out
+                } else {
+                    bytes[bytes.len() - length..].to_vec()
+                }
+            }
+            None => vec![],
+        }
+    }
+
+    fn parse(value: &str, profile: ProtectionProfile) -> Result<Self> {
+        let value = value
+            .strip_prefix("inline:")
+            .ok_or(SrtpError::InvalidCryptoAttribute)?;
+        let mut fields = value.split('|');
+
+        let material = STANDARD
+            .decode(fields.next().unwrap_or(""))
+            .map_err(|_| SrtpError::InvalidCryptoAttribute)?;
+        let key_length = profile.get_key_length();
+        if material.len() != key_length + profile.get_salt_length() {
+            return Err(SrtpError::InvalidKeyLength);
+        }
+
+        let mut params = CryptoKeyParams {
+            master_key: material[..key_length].to_vec(),
+            master_salt: material[key_length..].to_vec(),
+            lifetime: None,
+            mki: None,
+        };
+
+        for field in fields {
+            if let Some((value, length)) = field.split_once(':') {
+                let value = value
+                    .parse()
+                    .map_err(|_| SrtpError::InvalidCryptoAttribute)?;
+                let length = length
+                    .parse()
+                    .map_err(|_| SrtpError::InvalidCryptoAttribute)?;
+                if length == 0 || length > 128 {
+                    return Err(SrtpError::InvalidCryptoAttribute);
+                }
+                params.mki = Some((value, length));
+            } else if let Some(exponent) = field.strip_prefix("2^") {
+                let exponent: u32 = exponent
+                    .parse()
+                    .map_err(|_| SrtpError::InvalidCryptoAttribute)?;
+                let lifetime = 1u64
+                    .checked_shl(exponent)
+                    .ok_or(SrtpError::InvalidCryptoAttribute)?;
+                params.lifetime = Some(lifetime);
+            } else {
+                let lifetime = field
+                    .parse()
+                    .map_err(|_| SrtpError::InvalidCryptoAttribute)?;
+                params.lifetime = Some(lifetime);
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+impl fmt::Display for CryptoKeyParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut material = self.master_key.clone();
+        material.extend_from_slice(&self.master_salt);
+        write!(f, "inline:{}", STANDARD.encode(&material))?;
+
+        if let Some(lifetime) = self.lifetime {
+            if lifetime.is_power_of_two() {
+                write!(f, "|2^{}", lifetime.trailing_zeros())?;
+            } else {
+                write!(f, "|{}", lifetime)?;
+            }
+        }
+        if let Some((value, length)) = self.mki {
+            write!(f, "|{}:{}", value, length)?;
+        }
+        Ok(())
+    }
+}
+
+// SDPのa=cryptoの値．DTLSが使えないSIP trunkなどとの相互接続で使う．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CryptoAttribute {
+    pub tag: u32,
+    pub profile: ProtectionProfile,
+    pub key_params: Vec<CryptoKeyParams>,
+    pub session_params: Vec<String>,
+}
+
+pub fn get_sdes_crypto_suite(profile: ProtectionProfile) -> &'static str {
+    match profile {
+        ProtectionProfile::Aes128CmHmacSha1_80 => "AES_CM_128_HMAC_SHA1_80",
+        ProtectionProfile::Aes128CmHmacSha1_32 => "AES_CM_128_HMAC_SHA1_32",
+        ProtectionProfile::AeadAes128Gcm => "AEAD_AES_128_GCM",
+        ProtectionProfile::AeadAes256Gcm => "AEAD_AES_256_GCM",
+        #[cfg(feature = "null-cipher")]
+        ProtectionProfile::NullHmacSha1_80 => "NULL_HMAC_SHA1_80",
+        #[cfg(feature = "null-cipher")]
+        ProtectionProfile::NullHmacSha1_32 => "NULL_HMAC_SHA1_32",
+    }
+}
+
+pub fn from_sdes_crypto_suite(suite: &str) -> Option<ProtectionProfile> {
+    match suite {
+        "AES_CM_128_HMAC_SHA1_80" => Some(ProtectionProfile::Aes128CmHmacSha1_80),
+        "AES_CM_128_HMAC_SHA1_32" => Some(ProtectionProfile::Aes128CmHmacSha1_32),
+        "AEAD_AES_128_GCM" => Some(ProtectionProfile::AeadAes128Gcm),
+        "AEAD_AES_256_GCM" => Some(ProtectionProfile::AeadAes256Gcm),
+        #[cfg(feature = "null-cipher")]
+        "NULL_HMAC_SHA1_80" => Some(ProtectionProfile::NullHmacSha1_80),
+        #[cfg(feature = "null-cipher")]
+        "NULL_HMAC_SHA1_32" => Some(ProtectionProfile::NullHmacSha1_32),
+        _ => None,
+    }
+}
+
+impl CryptoAttribute {
+    /// Creates an offer with a random master key and salt.
+    pub fn generate(tag: u32, profile: ProtectionProfile) -> Self {
+        let random = |length| (0..length).map(|_| rand::random()).collect();
+        CryptoAttribute {
+            tag,
+            profile,
+            key_params: vec![CryptoKeyParams {
+                master_key: random(profile.get_key_length()),
+                master_salt: random(profile.get_salt_length()),
+                lifetime: None,
+                mki: None,
+            }],
+            session_params: vec![],
+        }
+    }
+
+    /// Parses the value of an `a=crypto` line, with or without the
+    /// `a=crypto:` prefix.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix("a=").unwrap_or(value);
+        let value = value.strip_prefix("crypto:").unwrap_or(value);
+
+        let mut fields = value.split_whitespace();
+        let tag = fields
+            .next()
+            .and_then(|v| v.parse().ok())
+            .ok_or(SrtpError::InvalidCryptoAttribute)?;
+        let profile = fields
+            .next()
+            .ok_or(SrtpError::InvalidCryptoAttribute)
+            .and_then(|v| {
+                from_sdes_crypto_suite(v).ok_or(SrtpError::UnsupportedProtectionProfile)
+            })?;
+        let key_params = fields
+            .next()
+            .ok_or(SrtpError::InvalidCryptoAttribute)?
+            .split(';')
+            .map(|v| CryptoKeyParams::parse(v, profile))
+            .collect::<Result<Vec<_>>>()?;
+
+        // every key of an attribute uses the same MKI length, or none.
+        let mki_length = key_params[0].mki.map(|v| v.1);
+        if key_params.iter().any(|v| v.mki.map(|v| v.1) != mki_length)
+            || (key_params.len() > 1 && mki_length.is_none())
+        {
+            return Err(SrtpError::InvalidMki);
+        }
+
+        Ok(CryptoAttribute {
+            tag,
+            profile,
+            key_params,
+            session_params: fields.map(|v| v.to_string()).collect(),
+        })
+    }
+
+    /// Returns the rate from the KDR session parameter, 2^n.
+    pub fn get_key_derivation_rate(&self) -> Result<u64> {
+        for param in &self.session_params {
+            if let Some(v) = param.strip_prefix("KDR=") {
+                let exponent: u32 = v.parse().map_err(|_| SrtpError::InvalidCryptoAttribute)?;
+                return 1u64
+                    .checked_shl(exponent)
+                    .ok_or(SrtpError::InvalidKeyDerivationRate);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Creates the context for the keys of this attribute: the local one
+    /// to protect sent packets, or the remote one to unprotect received
+    /// packets. The first key is the active one.
+    pub fn create_context(&self, mut config: ContextConfig) -> Result<Context> {
+        config.key_derivation_rate = self.get_key_derivation_rate()?;
+
+        let first = &self.key_params[0];
+        let mut context = Context::with_mki(
+            &first.master_key,
+            &first.master_salt,
+            &first.get_mki_bytes(),
+            self.profile,
+            config,
+        )?;
+        for params in &self.key_params[1..] {
+            context.add_master_key(
+                &params.get_mki_bytes(),
+                &params.master_key,
+                &params.master_salt,
+            )?;
+        }
+        Ok(context)
+    }
+}
+
+impl fmt::Display for CryptoAttribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ", self.tag, get_sdes_crypto_suite(self.profile))?;
+        for (i, params) in self.key_params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ";")?;
+            }
+            write!(f, "{}", params)?;
+        }
+        for param in &self.session_params {
+            write!(f, " {}", param)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_crypto_attribute_test() {
+        let line = "a=crypto:1 AES_CM_128_HMAC_SHA1_80 inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|2^20|1:4 KDR=23";
+        let attribute = CryptoAttribute::parse(line).unwrap();
+        assert_eq!(attribute.tag, 1);
+        assert_eq!(attribute.profile, ProtectionProfile::Aes128CmHmacSha1_80);
+        assert_eq!(attribute.key_params.len(), 1);
+
+        let params = &attribute.key_params[0];
+        assert_eq!(params.master_key.len(), 16);
+        assert_eq!(params.master_salt.len(), 14);
+        assert_eq!(&params.master_key[..4], b"=-n@");
+        assert_eq!(params.lifetime, Some(1 << 20));
+        assert_eq!(params.mki, Some((1, 4)));
+        assert_eq!(params.get_mki_bytes(), vec![0, 0, 0, 1]);
+        assert_eq!(attribute.get_key_derivation_rate(), Ok(1 << 23));
+
+        assert_eq!(attribute.to_string(), &line["a=crypto:".len()..]);
+        assert_eq!(
+            CryptoAttribute::parse(&attribute.to_string()).unwrap(),
+            attribute
+        );
+
+        let errors = vec![
+            (
+                "1 AES_CM_128_HMAC_SHA1_80",
+                SrtpError::InvalidCryptoAttribute,
+            ),
+            (
+                "1 F8_128_HMAC_SHA1_80 inline:AAAA",
+                SrtpError::UnsupportedProtectionProfile,
+            ),
+            (
+                "1 AES_CM_128_HMAC_SHA1_32 inline:AAAA",
+                SrtpError::InvalidKeyLength,
+            ),
+            (
+                "1 AES_CM_128_HMAC_SHA1_80 PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR",
+                SrtpError::InvalidCryptoAttribute,
+            ),
+        ];
+        for (value, error) in errors {
+            assert_eq!(CryptoAttribute::parse(value), Err(error));
+        }
+    }
+
+    #[test]
+    fn sdes_context_test() {
+        let offer = CryptoAttribute::generate(1, ProtectionProfile::AeadAes128Gcm);
+        let answer = CryptoAttribute::parse(&offer.to_string()).unwrap();
+        assert_eq!(answer, offer);
+        assert!(offer.to_string().starts_with("1 AEAD_AES_128_GCM inline:"));
+
+        let config = ContextConfig::default();
+        let mut sender = offer.create_context(config).unwrap();
+        let mut receiver = answer.create_context(config).unwrap();
+
+        let packet = vec![0x80, 0x60, 0, 1, 0, 0, 0, 0, 0, 0, 0x04, 0xd2, 1, 2, 3];
+        let protected = sender.protect_rtp(&packet).unwrap();
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+    }
+
+    #[test]
+    fn sdes_multiple_keys_test() {
+        let line = "2 AES_CM_128_HMAC_SHA1_32 \
+            inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|1:2;\
+            inline:QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVpBQkNE|2:2";
+        let attribute = CryptoAttribute::parse(line).unwrap();
+        assert_eq!(attribute.key_params[1].get_mki_bytes(), vec![0, 2]);
+
+        let context = attribute.create_context(ContextConfig::default()).unwrap();
+        assert_eq!(context.get_active_mki(), &[0, 1]);
+
+        let mixed = "2 AES_CM_128_HMAC_SHA1_32 \
+            inline:PS1uQCVeeCFCanVmcjkpPywjNWhcYD0mXXtxaVBR|1:2;\
+            inline:QUJDREVGR0hJSktMTU5PUFFSU1RVVldYWVpBQkNE|2:4";
+        assert_eq!(CryptoAttribute::parse(mixed), Err(SrtpError::InvalidMki));
+    }
+}
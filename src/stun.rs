@@ -0,0 +1,30 @@
+pub mod attribute;
+pub mod credentials;
+pub mod integrity;
+pub mod message;
+pub mod transaction;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, StunError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum StunError {
+    #[fail(display = "This is not a STUN message.")]
+    NotStunMessage,
+
+    #[fail(display = "STUN message length is invalid.")]
+    InvalidMessageLength,
+
+    #[fail(display = "STUN attribute {:#06x} is broken.", attribute_type)]
+    InvalidAttribute { attribute_type: u16 },
+
+    #[fail(display = "STUN attribute {:#06x} is missing.", attribute_type)]
+    MissingAttribute { attribute_type: u16 },
+
+    #[fail(display = "STUN message integrity check failed.")]
+    IntegrityCheckFailed,
+
+    #[fail(display = "STUN fingerprint does not match.")]
+    FingerprintMismatch,
+}
@@ -0,0 +1,376 @@
+// https://tools.ietf.org/html/rfc8489#section-14
+// https://tools.ietf.org/html/rfc8445#section-16.1
+// https://tools.ietf.org/html/rfc8656#section-18
+
+/*
+    XOR-MAPPED-ADDRESS
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |0 0 0 0 0 0 0 0|    Family     |         X-Port                |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                X-Address (Variable)
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    X-Port is the port XOR the upper 16 bits of the magic cookie, and
+    X-Address is the address XOR the magic cookie (|| transaction ID for IPv6).
+
+    ERROR-CODE
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |           Reserved, should be 0         |Class|     Number    |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |      Reason Phrase (variable)                                ..
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::stun::message::{StunMessage, TransactionId, MAGIC_COOKIE};
+use crate::stun::{Result, StunError};
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+pub const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+pub const ATTR_USERNAME: u16 = 0x0006;
+pub const ATTR_MESSAGE_INTEGRITY: u16 = 0x0008;
+pub const ATTR_ERROR_CODE: u16 = 0x0009;
+pub const ATTR_UNKNOWN_ATTRIBUTES: u16 = 0x000a;
+pub const ATTR_CHANNEL_NUMBER: u16 = 0x000c;
+pub const ATTR_LIFETIME: u16 = 0x000d;
+pub const ATTR_XOR_PEER_ADDRESS: u16 = 0x0012;
+pub const ATTR_DATA: u16 = 0x0013;
+pub const ATTR_REALM: u16 = 0x0014;
+pub const ATTR_NONCE: u16 = 0x0015;
+pub const ATTR_XOR_RELAYED_ADDRESS: u16 = 0x0016;
+pub const ATTR_REQUESTED_ADDRESS_FAMILY: u16 = 0x0017;
+pub const ATTR_EVEN_PORT: u16 = 0x0018;
+pub const ATTR_REQUESTED_TRANSPORT: u16 = 0x0019;
+pub const ATTR_DONT_FRAGMENT: u16 = 0x001a;
+pub const ATTR_MESSAGE_INTEGRITY_SHA256: u16 = 0x001c;
+pub const ATTR_PASSWORD_ALGORITHM: u16 = 0x001d;
+pub const ATTR_USERHASH: u16 = 0x001e;
+pub const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+pub const ATTR_RESERVATION_TOKEN: u16 = 0x0022;
+pub const ATTR_PRIORITY: u16 = 0x0024;
+pub const ATTR_USE_CANDIDATE: u16 = 0x0025;
+pub const ATTR_PASSWORD_ALGORITHMS: u16 = 0x8002;
+pub const ATTR_ALTERNATE_DOMAIN: u16 = 0x8003;
+pub const ATTR_SOFTWARE: u16 = 0x8022;
+pub const ATTR_ALTERNATE_SERVER: u16 = 0x8023;
+pub const ATTR_FINGERPRINT: u16 = 0x8028;
+pub const ATTR_ICE_CONTROLLED: u16 = 0x8029;
+pub const ATTR_ICE_CONTROLLING: u16 = 0x802a;
+// https://tools.ietf.org/html/draft-thatcher-ice-renomination-01
+pub const ATTR_NOMINATION: u16 = 0xc001;
+
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+
+pub fn encode_address(addr: &SocketAddr) -> Vec<u8> {
+    let mut out = vec![0];
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            out.push(FAMILY_IPV4);
+            out.extend_from_slice(&addr.port().to_be_bytes());
+            out.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            out.push(FAMILY_IPV6);
+            out.extend_from_slice(&addr.port().to_be_bytes());
+            out.extend_from_slice(&ip.octets());
+        }
+    }
+    out
+}
+
+pub fn decode_address(attribute_type: u16, value: &[u8]) -> Result<SocketAddr> {
+    let error = StunError::InvalidAttribute { attribute_type };
+    if value.len() < 4 {
+        return Err(error);
+    }
+    let port = u16::from_be_bytes([value[2], value[3]]);
+    let ip = match (value[1], value.len()) {
+        (FAMILY_IPV4, 8) => {
+            let mut octets = [0; 4];
+            octets.copy_from_slice(&value[4..8]);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        (FAMILY_IPV6, 20) => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&value[4..20]);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return Err(error),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+// XOR-MAPPED-ADDRESSのX-Address等に使うmask．
+fn get_xor_mask(transaction_id: &TransactionId) -> [u8; 16] {
+    let mut mask = [0; 16];
+    mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    mask[4..].copy_from_slice(&transaction_id.0);
+    mask
+}
+
+pub fn encode_xor_address(addr: &SocketAddr, transaction_id: &TransactionId) -> Vec<u8> {
+    let mut out = encode_address(addr);
+    let mask = get_xor_mask(transaction_id);
+    for (i, v) in out[2..].iter_mut().enumerate() {
+        // the port is XORed with the first 16 bits of the cookie, the
+        // address with the cookie and the transaction ID.
+        *v ^= if i < 2 { mask[i] } else { mask[i - 2] };
+    }
+    out
+}
+
+pub fn decode_xor_address(
+    attribute_type: u16,
+    value: &[u8],
+    transaction_id: &TransactionId,
+) -> Result<SocketAddr> {
+    // the mask covers the address of the length of its family and no more.
+    match (value.get(1), value.len()) {
+        (Some(&FAMILY_IPV4), 8) | (Some(&FAMILY_IPV6), 20) => {}
+        _ => return Err(StunError::InvalidAttribute { attribute_type }),
+    }
+    let mut value = value.to_vec();
+    let mask = get_xor_mask(transaction_id);
+    for (i, v) in value.iter_mut().enumerate().skip(2) {
+        let i = i - 2;
+        *v ^= if i < 2 { mask[i] } else { mask[i - 2] };
+    }
+    decode_address(attribute_type, &value)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ErrorCode {
+    pub code: u16,
+    pub reason: String,
+}
+
+pub const ERROR_TRY_ALTERNATE: u16 = 300;
+pub const ERROR_BAD_REQUEST: u16 = 400;
+pub const ERROR_UNAUTHENTICATED: u16 = 401;
+pub const ERROR_FORBIDDEN: u16 = 403;
+pub const ERROR_UNKNOWN_ATTRIBUTE: u16 = 420;
+pub const ERROR_ALLOCATION_MISMATCH: u16 = 437;
+pub const ERROR_STALE_NONCE: u16 = 438;
+pub const ERROR_ADDRESS_FAMILY_NOT_SUPPORTED: u16 = 440;
+pub const ERROR_WRONG_CREDENTIALS: u16 = 441;
+pub const ERROR_UNSUPPORTED_TRANSPORT_PROTOCOL: u16 = 442;
+pub const ERROR_ALLOCATION_QUOTA_REACHED: u16 = 486;
+pub const ERROR_ROLE_CONFLICT: u16 = 487;
+pub const ERROR_SERVER_ERROR: u16 = 500;
+pub const ERROR_INSUFFICIENT_CAPACITY: u16 = 508;
+
+impl ErrorCode {
+    pub fn new(code: u16, reason: &str) -> Self {
+        ErrorCode {
+            code,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![0, 0, (self.code / 100) as u8, (self.code % 100) as u8];
+        out.extend_from_slice(self.reason.as_bytes());
+        out
+    }
+
+    pub fn from_bytes(value: &[u8]) -> Result<ErrorCode> {
+        let error = StunError::InvalidAttribute {
+            attribute_type: ATTR_ERROR_CODE,
+        };
+        if value.len() < 4 || !(3..=6).contains(&(value[2] & 0x07)) || value[3] > 99 {
+            return Err(error);
+        }
+        let code = u16::from(value[2] & 0x07) * 100 + u16::from(value[3]);
+        let reason = String::from_utf8(value[4..].to_vec()).map_err(|_| error)?;
+        Ok(ErrorCode { code, reason })
+    }
+}
+
+// よく使う属性の型付きaccessor．
+impl StunMessage {
+    pub fn get_string_attribute(&self, attribute_type: u16) -> Result<Option<String>> {
+        match self.get_attribute(attribute_type) {
+            Some(v) => String::from_utf8(v.to_vec())
+                .map(Some)
+                .map_err(|_| StunError::InvalidAttribute { attribute_type }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_u32_attribute(&self, attribute_type: u16) -> Result<Option<u32>> {
+        match self.get_attribute(attribute_type) {
+            Some(v) if v.len() == 4 => Ok(Some(u32::from_be_bytes([v[0], v[1], v[2], v[3]]))),
+            Some(_) => Err(StunError::InvalidAttribute { attribute_type }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_u64_attribute(&self, attribute_type: u16) -> Result<Option<u64>> {
+        match self.get_attribute(attribute_type) {
+            Some(v) if v.len() == 8 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(v);
+                Ok(Some(u64::from_be_bytes(bytes)))
+            }
+            Some(_) => Err(StunError::InvalidAttribute { attribute_type }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_xor_address(&self, attribute_type: u16) -> Result<Option<SocketAddr>> {
+        match self.get_attribute(attribute_type) {
+            Some(v) => decode_xor_address(attribute_type, v, &self.get_transaction_id()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn add_xor_address(&mut self, attribute_type: u16, addr: &SocketAddr) {
+        let value = encode_xor_address(addr, &self.get_transaction_id());
+        self.add_attribute(attribute_type, value);
+    }
+
+    /// XOR-MAPPED-ADDRESS, falling back to MAPPED-ADDRESS of old servers.
+    pub fn get_mapped_address(&self) -> Result<Option<SocketAddr>> {
+        if let Some(v) = self.get_xor_address(ATTR_XOR_MAPPED_ADDRESS)? {
+            return Ok(Some(v));
+        }
+        match self.get_attribute(ATTR_MAPPED_ADDRESS) {
+            Some(v) => decode_address(ATTR_MAPPED_ADDRESS, v).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_error_code(&self) -> Result<Option<ErrorCode>> {
+        match self.get_attribute(ATTR_ERROR_CODE) {
+            Some(v) => ErrorCode::from_bytes(v).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn add_error_code(&mut self, code: u16, reason: &str) {
+        self.add_attribute(ATTR_ERROR_CODE, ErrorCode::new(code, reason).to_bytes());
+    }
+
+    pub fn get_unknown_attributes(&self) -> Vec<u16> {
+        self.get_attribute(ATTR_UNKNOWN_ATTRIBUTES)
+            .map(|v| {
+                v.chunks_exact(2)
+                    .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Comprehension-required attribute types not in `known`, to be
+    /// reported with a 420 response.
+    pub fn find_unknown_attributes(&self, known: &[u16]) -> Vec<u16> {
+        let mut out: Vec<u16> = self
+            .get_attributes()
+            .iter()
+            .filter(|v| v.is_comprehension_required() && !known.contains(&v.attribute_type))
+            .map(|v| v.attribute_type)
+            .collect();
+        out.dedup();
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stun::message::{StunClass, METHOD_BINDING};
+
+    #[test]
+    fn xor_mapped_address_test() {
+        // RFC 5769 Section 2.2 and 2.3
+        let id = TransactionId([
+            0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+        ]);
+        let v4: SocketAddr = "192.0.2.1:32853".parse().unwrap();
+        let value = encode_xor_address(&v4, &id);
+        assert_eq!(value, vec![0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43]);
+        assert_eq!(
+            decode_xor_address(ATTR_XOR_MAPPED_ADDRESS, &value, &id),
+            Ok(v4)
+        );
+
+        let v6: SocketAddr = "[2001:db8:1234:5678:11:2233:4455:6677]:32853"
+            .parse()
+            .unwrap();
+        let value = encode_xor_address(&v6, &id);
+        assert_eq!(
+            value,
+            vec![
+                0x00, 0x02, 0xa1, 0x47, 0x01, 0x13, 0xa9, 0xfa, 0xa5, 0xd3, 0xf1, 0x79, 0xbc, 0x25,
+                0xf4, 0xb5, 0xbe, 0xd2, 0xb9, 0xd9,
+            ]
+        );
+        assert_eq!(
+            decode_xor_address(ATTR_XOR_MAPPED_ADDRESS, &value, &id),
+            Ok(v6)
+        );
+
+        assert!(decode_address(ATTR_MAPPED_ADDRESS, &[0, 1, 0, 0]).is_err());
+
+        // longer than the address of its family.
+        let mut oversized = value.clone();
+        oversized.extend_from_slice(&[0; 8]);
+        assert_eq!(
+            decode_xor_address(ATTR_XOR_MAPPED_ADDRESS, &oversized, &id),
+            Err(StunError::InvalidAttribute {
+                attribute_type: ATTR_XOR_MAPPED_ADDRESS
+            })
+        );
+        let mut oversized = encode_xor_address(&v4, &id);
+        oversized.push(0);
+        assert!(decode_xor_address(ATTR_XOR_MAPPED_ADDRESS, &oversized, &id).is_err());
+        assert!(decode_xor_address(ATTR_XOR_MAPPED_ADDRESS, &[0], &id).is_err());
+    }
+
+    #[test]
+    fn typed_attribute_test() {
+        let request = StunMessage::request(METHOD_BINDING);
+        let mut response = StunMessage::response(&request, StunClass::ErrorResponse);
+        let addr: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        response.add_xor_address(ATTR_XOR_MAPPED_ADDRESS, &addr);
+        response.add_error_code(ERROR_ROLE_CONFLICT, "Role Conflict");
+        response.add_attribute(ATTR_SOFTWARE, b"webrtc-rs".to_vec());
+        response.add_attribute(ATTR_PRIORITY, 0x6e00_1eff_u32.to_be_bytes().to_vec());
+        response.add_attribute(0x7777, vec![]);
+
+        let parsed = StunMessage::from_bytes(&response.to_bytes()).unwrap();
+        assert_eq!(parsed.get_mapped_address(), Ok(Some(addr)));
+        assert_eq!(
+            parsed.get_error_code(),
+            Ok(Some(ErrorCode::new(487, "Role Conflict")))
+        );
+        assert_eq!(
+            parsed.get_string_attribute(ATTR_SOFTWARE),
+            Ok(Some("webrtc-rs".to_string()))
+        );
+        assert_eq!(
+            parsed.get_u32_attribute(ATTR_PRIORITY),
+            Ok(Some(0x6e00_1eff))
+        );
+        assert_eq!(
+            parsed.get_u64_attribute(ATTR_PRIORITY),
+            Err(StunError::InvalidAttribute {
+                attribute_type: ATTR_PRIORITY
+            })
+        );
+        assert_eq!(parsed.get_u32_attribute(ATTR_USERNAME), Ok(None));
+        assert_eq!(
+            parsed.find_unknown_attributes(&[ATTR_XOR_MAPPED_ADDRESS, ATTR_ERROR_CODE]),
+            vec![ATTR_PRIORITY, 0x7777]
+        );
+
+        assert!(ErrorCode::from_bytes(&[0, 0, 7, 0]).is_err());
+    }
+}
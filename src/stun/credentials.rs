@@ -0,0 +1,375 @@
+// https://tools.ietf.org/html/rfc8489#section-9.2
+
+/*
+    client                                    server
+      | request                                  |
+      |----------------------------------------->|
+      |       401 (REALM, NONCE [, PASSWORD-ALGORITHMS])
+      |<-----------------------------------------|
+      | request (USERNAME, REALM, NONCE,          |
+      |  [PASSWORD-ALGORITHMS, PASSWORD-ALGORITHM,]
+      |  MESSAGE-INTEGRITY)                       |
+      |----------------------------------------->|
+      |                       success, or 438 when the nonce went stale
+      |<-----------------------------------------|
+
+    A nonce starting with "obMatJos2" carries 24 bits of security features
+    (4 base64 characters). Bit 0 announces PASSWORD-ALGORITHMS.
+*/
+
+use crate::stun::attribute::{
+    ATTR_NONCE, ATTR_PASSWORD_ALGORITHM, ATTR_PASSWORD_ALGORITHMS, ATTR_REALM, ATTR_USERNAME,
+    ERROR_STALE_NONCE, ERROR_UNAUTHENTICATED,
+};
+use crate::stun::integrity::{
+    get_long_term_key, get_long_term_key_sha256, verify_message_integrity,
+};
+use crate::stun::message::{get_padded_length, StunClass, StunMessage};
+use crate::stun::{Result, StunError};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+pub const NONCE_COOKIE: &str = "obMatJos2";
+const SECURITY_FEATURE_PASSWORD_ALGORITHMS: u32 = 0x80_0000;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum PasswordAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl PasswordAlgorithm {
+    pub fn get_id(self) -> u16 {
+        match self {
+            PasswordAlgorithm::Md5 => 0x0001,
+            PasswordAlgorithm::Sha256 => 0x0002,
+        }
+    }
+
+    pub fn from_id(id: u16) -> Option<PasswordAlgorithm> {
+        match id {
+            0x0001 => Some(PasswordAlgorithm::Md5),
+            0x0002 => Some(PasswordAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn get_key(self, username: &str, realm: &str, password: &str) -> Vec<u8> {
+        match self {
+            PasswordAlgorithm::Md5 => get_long_term_key(username, realm, password),
+            PasswordAlgorithm::Sha256 => get_long_term_key_sha256(username, realm, password),
+        }
+    }
+
+    /// The PASSWORD-ALGORITHM value, the algorithm without parameters.
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut out = self.get_id().to_be_bytes().to_vec();
+        out.extend_from_slice(&[0, 0]);
+        out
+    }
+}
+
+/// Parses PASSWORD-ALGORITHMS, skipping algorithms we do not know.
+pub fn parse_password_algorithms(value: &[u8]) -> Result<Vec<PasswordAlgorithm>> {
+    let mut out = vec![];
+    let mut offset = 0;
+    while offset < value.len() {
+        let header = value
+            .get(offset..offset + 4)
+            .ok_or(StunError::InvalidAttribute {
+                attribute_type: ATTR_PASSWORD_ALGORITHMS,
+            })?;
+        let id = u16::from_be_bytes([header[0], header[1]]);
+        let length = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        if let Some(v) = PasswordAlgorithm::from_id(id) {
+            out.push(v);
+        }
+        offset += 4 + get_padded_length(length);
+    }
+    Ok(out)
+}
+
+/// Returns the security feature bits of a nonce with the nonce cookie.
+pub fn get_security_features(nonce: &str) -> Option<u32> {
+    let features = nonce.strip_prefix(NONCE_COOKIE)?.get(..4)?;
+    let bytes = STANDARD.decode(features).ok()?;
+    Some(u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]))
+}
+
+// TURN等で使うlong-term credentialのclient側．REALMとNONCEを覚えておき，以降のrequestに使う．
+#[derive(Debug, Clone)]
+pub struct LongTermCredentials {
+    username: String,
+    password: String,
+    realm: Option<String>,
+    nonce: Option<String>,
+    algorithm: PasswordAlgorithm,
+    // 401で受け取ったPASSWORD-ALGORITHMSの値．以降のrequestにそのまま返す．
+    password_algorithms: Option<Vec<u8>>,
+    key: Vec<u8>,
+}
+
+impl LongTermCredentials {
+    pub fn new(username: &str, password: &str) -> Self {
+        LongTermCredentials {
+            username: username.to_string(),
+            password: password.to_string(),
+            realm: None,
+            nonce: None,
+            algorithm: PasswordAlgorithm::Md5,
+            password_algorithms: None,
+            key: vec![],
+        }
+    }
+
+    pub fn get_username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn get_realm(&self) -> Option<&str> {
+        self.realm.as_deref()
+    }
+
+    pub fn get_nonce(&self) -> Option<&str> {
+        self.nonce.as_deref()
+    }
+
+    pub fn get_algorithm(&self) -> PasswordAlgorithm {
+        self.algorithm
+    }
+
+    /// The key for MESSAGE-INTEGRITY, once the realm is known.
+    pub fn get_key(&self) -> Option<&[u8]> {
+        self.realm.as_ref().map(|_| self.key.as_slice())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.realm.is_some() && self.nonce.is_some()
+    }
+
+    /// Adds the credentials to `request` if a realm and nonce are cached.
+    /// MESSAGE-INTEGRITY is added last, so call this after every other
+    /// attribute but FINGERPRINT. Returns whether the request was signed.
+    pub fn sign(&self, request: &mut StunMessage) -> bool {
+        let (realm, nonce) = match (&self.realm, &self.nonce) {
+            (Some(realm), Some(nonce)) => (realm, nonce),
+            _ => return false,
+        };
+
+        request.add_attribute(ATTR_USERNAME, self.username.as_bytes().to_vec());
+        request.add_attribute(ATTR_REALM, realm.as_bytes().to_vec());
+        request.add_attribute(ATTR_NONCE, nonce.as_bytes().to_vec());
+        // with the security feature bit both are echoed, even for MD5.
+        if let Some(ref algorithms) = self.password_algorithms {
+            request.add_attribute(ATTR_PASSWORD_ALGORITHMS, algorithms.clone());
+            request.add_attribute(ATTR_PASSWORD_ALGORITHM, self.algorithm.to_bytes());
+        }
+        match self.algorithm {
+            PasswordAlgorithm::Md5 => request.add_message_integrity(&self.key),
+            PasswordAlgorithm::Sha256 => request.add_message_integrity_sha256(&self.key),
+        }
+        true
+    }
+
+    /// Handles an error response to a request signed with `sign` (or sent
+    /// unsigned). Returns whether the request should be retried with new
+    /// credentials: the first 401, or a 438 with a fresh nonce. A 401 to a
+    /// signed request means the credentials are wrong.
+    pub fn handle_error_response(
+        &mut self,
+        response: &StunMessage,
+        was_signed: bool,
+    ) -> Result<bool> {
+        if response.get_class() != StunClass::ErrorResponse {
+            return Ok(false);
+        }
+        let code = match response.get_error_code()? {
+            Some(v) => v.code,
+            None => return Ok(false),
+        };
+
+        let nonce = response.get_string_attribute(ATTR_NONCE)?;
+        match code {
+            ERROR_UNAUTHENTICATED if !was_signed => {
+                let realm = response.get_string_attribute(ATTR_REALM)?.ok_or(
+                    StunError::MissingAttribute {
+                        attribute_type: ATTR_REALM,
+                    },
+                )?;
+                let nonce = nonce.ok_or(StunError::MissingAttribute {
+                    attribute_type: ATTR_NONCE,
+                })?;
+                self.algorithm = self.select_algorithm(response, &nonce)?;
+                self.key = self
+                    .algorithm
+                    .get_key(&self.username, &realm, &self.password);
+                self.realm = Some(realm);
+                self.nonce = Some(nonce);
+                Ok(true)
+            }
+            ERROR_STALE_NONCE => match nonce {
+                Some(v) if self.realm.is_some() => {
+                    self.nonce = Some(v);
+                    Ok(true)
+                }
+                _ => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    fn select_algorithm(
+        &mut self,
+        response: &StunMessage,
+        nonce: &str,
+    ) -> Result<PasswordAlgorithm> {
+        self.password_algorithms = None;
+        let features = get_security_features(nonce).unwrap_or(0);
+        if features & SECURITY_FEATURE_PASSWORD_ALGORITHMS == 0 {
+            return Ok(PasswordAlgorithm::Md5);
+        }
+        let value = match response.get_attribute(ATTR_PASSWORD_ALGORITHMS) {
+            Some(v) => v,
+            None => return Ok(PasswordAlgorithm::Md5),
+        };
+        let algorithms = parse_password_algorithms(value)?;
+        self.password_algorithms = Some(value.to_vec());
+        Ok(if algorithms.contains(&PasswordAlgorithm::Sha256) {
+            PasswordAlgorithm::Sha256
+        } else {
+            PasswordAlgorithm::Md5
+        })
+    }
+
+    /// Checks the integrity of a response to a signed request.
+    pub fn verify_response(&self, bytes: &[u8]) -> Result<()> {
+        let key = self.get_key().ok_or(StunError::MissingAttribute {
+            attribute_type: ATTR_REALM,
+        })?;
+        verify_message_integrity(bytes, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stun::attribute::ATTR_MESSAGE_INTEGRITY_SHA256;
+    use crate::stun::message::METHOD_BINDING;
+
+    fn challenge(request: &StunMessage, code: u16, nonce: &str) -> StunMessage {
+        let mut response = StunMessage::response(request, StunClass::ErrorResponse);
+        response.add_error_code(code, "");
+        response.add_attribute(ATTR_REALM, b"example.org".to_vec());
+        response.add_attribute(ATTR_NONCE, nonce.as_bytes().to_vec());
+        response
+    }
+
+    #[test]
+    fn long_term_md5_test() {
+        let mut credentials = LongTermCredentials::new("user", "pass");
+        let mut request = StunMessage::request(METHOD_BINDING);
+        assert!(!credentials.sign(&mut request));
+
+        let response = challenge(&request, 401, "f//499k954d6OL34oL9FSTvy64sA");
+        assert_eq!(
+            credentials.handle_error_response(&response, false),
+            Ok(true)
+        );
+        assert_eq!(credentials.get_realm(), Some("example.org"));
+        assert_eq!(credentials.get_algorithm(), PasswordAlgorithm::Md5);
+
+        let mut request = StunMessage::request(METHOD_BINDING);
+        assert!(credentials.sign(&mut request));
+        let bytes = request.to_bytes();
+        let key = get_long_term_key("user", "example.org", "pass");
+        assert_eq!(verify_message_integrity(&bytes, &key), Ok(()));
+        assert_eq!(
+            request.get_string_attribute(ATTR_NONCE),
+            Ok(Some("f//499k954d6OL34oL9FSTvy64sA".to_string()))
+        );
+
+        // wrong credentials are not retried forever.
+        let response = challenge(&request, 401, "another");
+        assert_eq!(
+            credentials.handle_error_response(&response, true),
+            Ok(false)
+        );
+
+        let response = challenge(&request, 438, "fresh nonce");
+        assert_eq!(credentials.handle_error_response(&response, true), Ok(true));
+        assert_eq!(credentials.get_nonce(), Some("fresh nonce"));
+
+        let mut response = StunMessage::response(&request, StunClass::SuccessResponse);
+        response.add_message_integrity(&key);
+        assert_eq!(credentials.verify_response(&response.to_bytes()), Ok(()));
+    }
+
+    #[test]
+    fn long_term_sha256_test() {
+        // "gAAA" is 0x800000, only the password algorithms bit.
+        assert_eq!(get_security_features("obMatJos2gAAAnonce"), Some(0x80_0000));
+        assert_eq!(get_security_features("plain"), None);
+
+        let mut credentials = LongTermCredentials::new("user", "pass");
+        let request = StunMessage::request(METHOD_BINDING);
+        let mut response = challenge(&request, 401, "obMatJos2gAAAnonce");
+        let mut algorithms = PasswordAlgorithm::Md5.to_bytes();
+        algorithms.extend_from_slice(&[0x00, 0x09, 0x00, 0x00]);
+        algorithms.extend_from_slice(&PasswordAlgorithm::Sha256.to_bytes());
+        response.add_attribute(ATTR_PASSWORD_ALGORITHMS, algorithms.clone());
+
+        assert_eq!(
+            credentials.handle_error_response(&response, false),
+            Ok(true)
+        );
+        assert_eq!(credentials.get_algorithm(), PasswordAlgorithm::Sha256);
+
+        let mut request = StunMessage::request(METHOD_BINDING);
+        credentials.sign(&mut request);
+        assert!(request.has_attribute(ATTR_MESSAGE_INTEGRITY_SHA256));
+        assert_eq!(
+            request.get_attribute(ATTR_PASSWORD_ALGORITHM),
+            Some(&[0, 2, 0, 0][..])
+        );
+        assert_eq!(
+            request.get_attribute(ATTR_PASSWORD_ALGORITHMS),
+            Some(&algorithms[..])
+        );
+        let key = get_long_term_key_sha256("user", "example.org", "pass");
+        assert_eq!(verify_message_integrity(&request.to_bytes(), &key), Ok(()));
+
+        // MD5 picked from the list is echoed as well.
+        let mut credentials = LongTermCredentials::new("user", "pass");
+        let mut response = challenge(&request, 401, "obMatJos2gAAAnonce");
+        response.add_attribute(ATTR_PASSWORD_ALGORITHMS, PasswordAlgorithm::Md5.to_bytes());
+        credentials.handle_error_response(&response, false).unwrap();
+        assert_eq!(credentials.get_algorithm(), PasswordAlgorithm::Md5);
+        let mut request = StunMessage::request(METHOD_BINDING);
+        credentials.sign(&mut request);
+        assert_eq!(
+            request.get_attribute(ATTR_PASSWORD_ALGORITHM),
+            Some(&[0, 1, 0, 0][..])
+        );
+        assert_eq!(
+            request.get_attribute(ATTR_PASSWORD_ALGORITHMS),
+            Some(&[0, 1, 0, 0][..])
+        );
+        let key = get_long_term_key("user", "example.org", "pass");
+        assert_eq!(verify_message_integrity(&request.to_bytes(), &key), Ok(()));
+
+        // without the security feature bit the list is ignored.
+        let mut credentials = LongTermCredentials::new("user", "pass");
+        let mut response = challenge(&request, 401, "nonce");
+        response.add_attribute(
+            ATTR_PASSWORD_ALGORITHMS,
+            PasswordAlgorithm::Sha256.to_bytes(),
+        );
+        credentials.handle_error_response(&response, false).unwrap();
+        assert_eq!(credentials.get_algorithm(), PasswordAlgorithm::Md5);
+        let mut request = StunMessage::request(METHOD_BINDING);
+        credentials.sign(&mut request);
+        assert!(!request.has_attribute(ATTR_PASSWORD_ALGORITHMS));
+        assert!(!request.has_attribute(ATTR_PASSWORD_ALGORITHM));
+    }
+}
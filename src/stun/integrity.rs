@@ -0,0 +1,343 @@
+// https://tools.ietf.org/html/rfc8489#section-14.5
+// https://tools.ietf.org/html/rfc8489#section-14.6
+// https://tools.ietf.org/html/rfc8489#section-14.7
+// https://tools.ietf.org/html/rfc8489#section-9.2.2
+// https://tools.ietf.org/html/rfc8265#section-4.2
+
+/*
+    MESSAGE-INTEGRITY:        HMAC-SHA1(key, message up to the attribute)
+    MESSAGE-INTEGRITY-SHA256: HMAC-SHA256(key, message up to the attribute)
+    FINGERPRINT:              CRC-32(message up to the attribute) XOR 0x5354554e
+
+    The length in the header covered by the HMAC or CRC is the length as if
+    the attribute were the last one, so attributes after it are excluded.
+
+    short-term key = OpaqueString(password)
+    long-term key  = MD5(username ":" OpaqueString(realm) ":" OpaqueString(password))
+                   or SHA-256 of the same string
+
+    OpaqueString maps the spaces other than U+0020 to it and normalizes to
+    NFC, its disallowed characters are not rejected. the username is sent
+    as the client prepared it.
+*/
+
+use crate::stun::attribute::{
+    ATTR_FINGERPRINT, ATTR_MESSAGE_INTEGRITY, ATTR_MESSAGE_INTEGRITY_SHA256,
+};
+use crate::stun::message::{
+    get_padded_length, StunMessage, ATTRIBUTE_HEADER_LENGTH, STUN_HEADER_LENGTH,
+};
+use crate::stun::{Result, StunError};
+
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use unicode_normalization::UnicodeNormalization;
+
+const FINGERPRINT_XOR: u32 = 0x5354_554e;
+const MESSAGE_INTEGRITY_LENGTH: usize = 20;
+const MESSAGE_INTEGRITY_SHA256_LENGTH: usize = 32;
+
+// Zs，U+0020以外のspace．
+fn is_non_ascii_space(c: char) -> bool {
+    matches!(
+        c,
+        '\u{a0}' | '\u{1680}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+    )
+}
+
+/// The preparation of the OpaqueString profile of PRECIS.
+pub fn prepare_opaque_string(value: &str) -> String {
+    value
+        .chars()
+        .map(|v| if is_non_ascii_space(v) { ' ' } else { v })
+        .nfc()
+        .collect()
+}
+
+fn get_long_term_input(username: &str, realm: &str, password: &str) -> String {
+    format!(
+        "{}:{}:{}",
+        username,
+        prepare_opaque_string(realm),
+        prepare_opaque_string(password)
+    )
+}
+
+/// The key of the short-term credential mechanism, used by ICE.
+pub fn get_short_term_key(password: &str) -> Vec<u8> {
+    prepare_opaque_string(password).into_bytes()
+}
+
+/// The key of the long-term credential mechanism with MD5.
+pub fn get_long_term_key(username: &str, realm: &str, password: &str) -> Vec<u8> {
+    Md5::digest(get_long_term_input(username, realm, password).as_bytes()).to_vec()
+}
+
+/// The key of the long-term credential mechanism with SHA-256.
+pub fn get_long_term_key_sha256(username: &str, realm: &str, password: &str) -> Vec<u8> {
+    Sha256::digest(get_long_term_input(username, realm, password).as_bytes()).to_vec()
+}
+
+fn hmac_sha1(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn get_fingerprint(data: &[u8]) -> u32 {
+    crc32fast::hash(data) ^ FINGERPRINT_XOR
+}
+
+// 属性を最後に置いた場合の長さでheaderを書き換えた，属性の直前までのbytes．
+fn get_covered_bytes(bytes: &[u8], offset: usize, value_length: usize) -> Vec<u8> {
+    let mut covered = bytes[..offset].to_vec();
+    let length = offset + ATTRIBUTE_HEADER_LENGTH + value_length - STUN_HEADER_LENGTH;
+    covered[2..4].copy_from_slice(&(length as u16).to_be_bytes());
+    covered
+}
+
+/// Returns the offset and value length of the first `attribute_type` of a
+/// raw message.
+fn find_attribute(bytes: &[u8], attribute_type: u16) -> Option<(usize, usize)> {
+    let mut offset = STUN_HEADER_LENGTH;
+    while offset + ATTRIBUTE_HEADER_LENGTH <= bytes.len() {
+        let t = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+        let length = usize::from(u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]));
+        if t == attribute_type {
+            return Some((offset, length));
+        }
+        offset += ATTRIBUTE_HEADER_LENGTH + get_padded_length(length);
+    }
+    None
+}
+
+fn get_value(bytes: &[u8], offset: usize, length: usize, attribute_type: u16) -> Result<&[u8]> {
+    let start = offset + ATTRIBUTE_HEADER_LENGTH;
+    bytes
+        .get(start..start + length)
+        .ok_or(StunError::InvalidAttribute { attribute_type })
+}
+
+impl StunMessage {
+    /// Appends MESSAGE-INTEGRITY over the attributes added so far.
+    pub fn add_message_integrity(&mut self, key: &[u8]) {
+        let bytes = self.to_bytes();
+        let covered = get_covered_bytes(&bytes, bytes.len(), MESSAGE_INTEGRITY_LENGTH);
+        self.add_attribute(ATTR_MESSAGE_INTEGRITY, hmac_sha1(key, &covered));
+    }
+
+    /// Appends MESSAGE-INTEGRITY-SHA256 over the attributes added so far.
+    pub fn add_message_integrity_sha256(&mut self, key: &[u8]) {
+        let bytes = self.to_bytes();
+        let covered = get_covered_bytes(&bytes, bytes.len(), MESSAGE_INTEGRITY_SHA256_LENGTH);
+        self.add_attribute(ATTR_MESSAGE_INTEGRITY_SHA256, hmac_sha256(key, &covered));
+    }
+
+    /// Appends FINGERPRINT, which must be the last attribute.
+    pub fn add_fingerprint(&mut self) {
+        let bytes = self.to_bytes();
+        let covered = get_covered_bytes(&bytes, bytes.len(), 4);
+        self.add_attribute(
+            ATTR_FINGERPRINT,
+            get_fingerprint(&covered).to_be_bytes().to_vec(),
+        );
+    }
+}
+
+/// Validates MESSAGE-INTEGRITY-SHA256 if present, or MESSAGE-INTEGRITY, on
+/// the message as received.
+pub fn verify_message_integrity(bytes: &[u8], key: &[u8]) -> Result<()> {
+    if let Some((offset, length)) = find_attribute(bytes, ATTR_MESSAGE_INTEGRITY_SHA256) {
+        let value = get_value(bytes, offset, length, ATTR_MESSAGE_INTEGRITY_SHA256)?;
+        // the value may be truncated down to 16 bytes.
+        if !(16..=MESSAGE_INTEGRITY_SHA256_LENGTH).contains(&length) || length % 4 != 0 {
+            return Err(StunError::InvalidAttribute {
+                attribute_type: ATTR_MESSAGE_INTEGRITY_SHA256,
+            });
+        }
+        let expected = hmac_sha256(key, &get_covered_bytes(bytes, offset, length));
+        return if bool::from(expected[..length].ct_eq(value)) {
+            Ok(())
+        } else {
+            Err(StunError::IntegrityCheckFailed)
+        };
+    }
+
+    let (offset, length) =
+        find_attribute(bytes, ATTR_MESSAGE_INTEGRITY).ok_or(StunError::MissingAttribute {
+            attribute_type: ATTR_MESSAGE_INTEGRITY,
+        })?;
+    let value = get_value(bytes, offset, length, ATTR_MESSAGE_INTEGRITY)?;
+    if length != MESSAGE_INTEGRITY_LENGTH {
+        return Err(StunError::InvalidAttribute {
+            attribute_type: ATTR_MESSAGE_INTEGRITY,
+        });
+    }
+    let expected = hmac_sha1(key, &get_covered_bytes(bytes, offset, length));
+    if bool::from(expected.ct_eq(value)) {
+        Ok(())
+    } else {
+        Err(StunError::IntegrityCheckFailed)
+    }
+}
+
+/// Validates FINGERPRINT, which must be the last attribute.
+pub fn verify_fingerprint(bytes: &[u8]) -> Result<()> {
+    let (offset, length) =
+        find_attribute(bytes, ATTR_FINGERPRINT).ok_or(StunError::MissingAttribute {
+            attribute_type: ATTR_FINGERPRINT,
+        })?;
+    if length != 4 || offset + 8 != bytes.len() {
+        return Err(StunError::InvalidAttribute {
+            attribute_type: ATTR_FINGERPRINT,
+        });
+    }
+    let value = get_value(bytes, offset, length, ATTR_FINGERPRINT)?;
+    let expected = get_fingerprint(&get_covered_bytes(bytes, offset, length));
+    if expected.to_be_bytes() == value {
+        Ok(())
+    } else {
+        Err(StunError::FingerprintMismatch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stun::attribute::{ATTR_SOFTWARE, ATTR_USERNAME};
+    use crate::stun::message::METHOD_BINDING;
+
+    // RFC 5769 Section 2.1
+    const SAMPLE_REQUEST: [u8; 108] = [
+        0x00, 0x01, 0x00, 0x58, 0x21, 0x12, 0xa4, 0x42, 0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6,
+        0x86, 0xfa, 0x87, 0xdf, 0xae, 0x80, 0x22, 0x00, 0x10, 0x53, 0x54, 0x55, 0x4e, 0x20, 0x74,
+        0x65, 0x73, 0x74, 0x20, 0x63, 0x6c, 0x69, 0x65, 0x6e, 0x74, 0x00, 0x24, 0x00, 0x04, 0x6e,
+        0x00, 0x01, 0xff, 0x80, 0x29, 0x00, 0x08, 0x93, 0x2f, 0xf9, 0xb1, 0x51, 0x26, 0x3b, 0x36,
+        0x00, 0x06, 0x00, 0x09, 0x65, 0x76, 0x74, 0x6a, 0x3a, 0x68, 0x36, 0x76, 0x59, 0x20, 0x20,
+        0x20, 0x00, 0x08, 0x00, 0x14, 0x9a, 0xea, 0xa7, 0x0c, 0xbf, 0xd8, 0xcb, 0x56, 0x78, 0x1e,
+        0xf2, 0xb5, 0xb2, 0xd3, 0xf2, 0x49, 0xc1, 0xb5, 0x71, 0xa2, 0x80, 0x28, 0x00, 0x04, 0xe5,
+        0x7a, 0x3b, 0xcf,
+    ];
+
+    #[test]
+    fn rfc5769_sample_request_test() {
+        let key = get_short_term_key("VOkJxbRl1RmTxUk/WvJxBt");
+        assert_eq!(verify_message_integrity(&SAMPLE_REQUEST, &key), Ok(()));
+        assert_eq!(verify_fingerprint(&SAMPLE_REQUEST), Ok(()));
+        assert_eq!(
+            verify_message_integrity(&SAMPLE_REQUEST, b"wrong"),
+            Err(StunError::IntegrityCheckFailed)
+        );
+
+        let message = StunMessage::from_bytes(&SAMPLE_REQUEST).unwrap();
+        assert_eq!(
+            message.get_string_attribute(ATTR_USERNAME),
+            Ok(Some("evtj:h6vY".to_string()))
+        );
+
+        // the space padding is covered too, so messages are verified as received.
+        let mut rebuilt = message.clone();
+        rebuilt.remove_attribute(ATTR_MESSAGE_INTEGRITY);
+        rebuilt.remove_attribute(ATTR_FINGERPRINT);
+        rebuilt.add_message_integrity(&key);
+        assert_ne!(
+            rebuilt.get_attribute(ATTR_MESSAGE_INTEGRITY),
+            message.get_attribute(ATTR_MESSAGE_INTEGRITY)
+        );
+        assert_eq!(verify_message_integrity(&rebuilt.to_bytes(), &key), Ok(()));
+    }
+
+    #[test]
+    fn long_term_key_test() {
+        // the SASLprep'ed credentials of RFC 5769 Section 2.4
+        let key = get_long_term_key(
+            "\u{30de}\u{30c8}\u{30ea}\u{30c3}\u{30af}\u{30b9}",
+            "example.org",
+            "TheMatrIX",
+        );
+        assert_eq!(
+            key,
+            vec![
+                0xe8, 0xca, 0x7a, 0xd5, 0x9d, 0x5e, 0xb0, 0x51, 0x8e, 0x31, 0x29, 0x11, 0xd2, 0xda,
+                0xb2, 0xa9
+            ]
+        );
+        assert_eq!(get_long_term_key_sha256("u", "r", "p").len(), 32);
+    }
+
+    #[test]
+    fn opaque_string_test() {
+        // a decomposed e with an acute accent is the composed one, and an
+        // ideographic space is U+0020.
+        assert_eq!(prepare_opaque_string("caf\u{65}\u{301}"), "caf\u{e9}");
+        assert_eq!(
+            get_long_term_key("user", "example.org", "caf\u{65}\u{301}\u{3000}bar"),
+            get_long_term_key("user", "example.org", "caf\u{e9} bar")
+        );
+        assert_ne!(
+            get_long_term_key("user", "example.org", "caf\u{e9} bar"),
+            get_long_term_key("user", "example.org", "cafe bar")
+        );
+        assert_eq!(
+            get_short_term_key("\u{212b}\u{a0}"),
+            "\u{c5} ".as_bytes().to_vec()
+        );
+        // compatibility characters are kept, unlike SASLprep.
+        assert_eq!(prepare_opaque_string("\u{2168}"), "\u{2168}");
+    }
+
+    #[test]
+    fn integrity_round_trip_test() {
+        let key = get_long_term_key_sha256("user", "realm", "pass");
+        let mut message = StunMessage::request(METHOD_BINDING);
+        message.add_attribute(ATTR_USERNAME, b"user".to_vec());
+        message.add_message_integrity(&key);
+        message.add_message_integrity_sha256(&key);
+        message.add_fingerprint();
+        // attributes after the integrity are not covered.
+        message.add_attribute(ATTR_SOFTWARE, b"x".to_vec());
+
+        let bytes = message.to_bytes();
+        assert_eq!(verify_message_integrity(&bytes, &key), Ok(()));
+        assert_eq!(
+            verify_fingerprint(&bytes),
+            Err(StunError::InvalidAttribute {
+                attribute_type: ATTR_FINGERPRINT
+            })
+        );
+
+        let mut message = StunMessage::request(METHOD_BINDING);
+        message.add_message_integrity_sha256(&key);
+        message.add_fingerprint();
+        let mut bytes = message.to_bytes();
+        assert_eq!(verify_message_integrity(&bytes, &key), Ok(()));
+        assert_eq!(verify_fingerprint(&bytes), Ok(()));
+
+        bytes[10] ^= 1;
+        assert_eq!(
+            verify_message_integrity(&bytes, &key),
+            Err(StunError::IntegrityCheckFailed)
+        );
+        assert_eq!(
+            verify_fingerprint(&bytes),
+            Err(StunError::FingerprintMismatch)
+        );
+
+        let message = StunMessage::request(METHOD_BINDING);
+        assert_eq!(
+            verify_message_integrity(&message.to_bytes(), &key),
+            Err(StunError::MissingAttribute {
+                attribute_type: ATTR_MESSAGE_INTEGRITY
+            })
+        );
+    }
+}
@@ -0,0 +1,341 @@
+// https://tools.ietf.org/html/rfc8489#section-5
+
+/*
+    STUN Message Header
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |0 0|     STUN Message Type     |         Message Length        |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                         Magic Cookie                          |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                                                               |
+    |                     Transaction ID (96 bits)                  |
+    |                                                               |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    Message Type
+
+      0                 1
+      2  3  4 5 6 7 8 9 0 1 2 3 4 5
+     +--+--+-+-+-+-+-+-+-+-+-+-+-+-+
+     |M |M |M|M|M|C|M|M|M|C|M|M|M|M|
+     |11|10|9|8|7|1|6|5|4|0|3|2|1|0|
+     +--+--+-+-+-+-+-+-+-+-+-+-+-+-+
+
+    Attribute (padded to a multiple of 4 bytes)
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |         Type                  |            Length             |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                         Value (variable)                ....
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+use crate::stun::{Result, StunError};
+
+pub const MAGIC_COOKIE: u32 = 0x2112_a442;
+pub const STUN_HEADER_LENGTH: usize = 20;
+pub const ATTRIBUTE_HEADER_LENGTH: usize = 4;
+
+pub const METHOD_BINDING: u16 = 0x0001;
+pub const METHOD_ALLOCATE: u16 = 0x0003;
+pub const METHOD_REFRESH: u16 = 0x0004;
+pub const METHOD_SEND: u16 = 0x0006;
+pub const METHOD_DATA: u16 = 0x0007;
+pub const METHOD_CREATE_PERMISSION: u16 = 0x0008;
+pub const METHOD_CHANNEL_BIND: u16 = 0x0009;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum StunClass {
+    Request,
+    Indication,
+    SuccessResponse,
+    ErrorResponse,
+}
+
+impl StunClass {
+    fn get_bits(self) -> u16 {
+        match self {
+            StunClass::Request => 0x0000,
+            StunClass::Indication => 0x0010,
+            StunClass::SuccessResponse => 0x0100,
+            StunClass::ErrorResponse => 0x0110,
+        }
+    }
+
+    fn from_bits(message_type: u16) -> StunClass {
+        match message_type & 0x0110 {
+            0x0000 => StunClass::Request,
+            0x0010 => StunClass::Indication,
+            0x0100 => StunClass::SuccessResponse,
+            _ => StunClass::ErrorResponse,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TransactionId(pub [u8; 12]);
+
+impl TransactionId {
+    pub fn new() -> Self {
+        TransactionId(rand::random())
+    }
+}
+
+impl Default for TransactionId {
+    fn default() -> Self {
+        TransactionId::new()
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct StunAttribute {
+    pub attribute_type: u16,
+    pub value: Vec<u8>,
+}
+
+impl StunAttribute {
+    pub fn new(attribute_type: u16, value: Vec<u8>) -> Self {
+        StunAttribute {
+            attribute_type,
+            value,
+        }
+    }
+
+    /// The encoded length including the header and padding.
+    pub fn get_length(&self) -> usize {
+        ATTRIBUTE_HEADER_LENGTH + get_padded_length(self.value.len())
+    }
+
+    /// Types below 0x8000 must be understood by the receiver.
+    pub fn is_comprehension_required(&self) -> bool {
+        self.attribute_type < 0x8000
+    }
+}
+
+pub(crate) fn get_padded_length(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct StunMessage {
+    method: u16,
+    class: StunClass,
+    transaction_id: TransactionId,
+    attributes: Vec<StunAttribute>,
+}
+
+impl StunMessage {
+    pub fn new(method: u16, class: StunClass, transaction_id: TransactionId) -> Self {
+        StunMessage {
+            method,
+            class,
+            transaction_id,
+            attributes: vec![],
+        }
+    }
+
+    /// A request with a new random transaction ID.
+    pub fn request(method: u16) -> Self {
+        StunMessage::new(method, StunClass::Request, TransactionId::new())
+    }
+
+    /// A response to `request`, with the same method and transaction ID.
+    pub fn response(request: &StunMessage, class: StunClass) -> Self {
+        StunMessage::new(request.method, class, request.transaction_id)
+    }
+
+    pub fn get_method(&self) -> u16 {
+        self.method
+    }
+
+    pub fn get_class(&self) -> StunClass {
+        self.class
+    }
+
+    pub fn get_transaction_id(&self) -> TransactionId {
+        self.transaction_id
+    }
+
+    pub fn get_attributes(&self) -> &[StunAttribute] {
+        &self.attributes
+    }
+
+    /// Returns the value of the first attribute of `attribute_type`.
+    pub fn get_attribute(&self, attribute_type: u16) -> Option<&[u8]> {
+        self.attributes
+            .iter()
+            .find(|v| v.attribute_type == attribute_type)
+            .map(|v| v.value.as_slice())
+    }
+
+    pub fn has_attribute(&self, attribute_type: u16) -> bool {
+        self.get_attribute(attribute_type).is_some()
+    }
+
+    pub fn add_attribute(&mut self, attribute_type: u16, value: Vec<u8>) {
+        self.attributes
+            .push(StunAttribute::new(attribute_type, value));
+    }
+
+    pub fn remove_attribute(&mut self, attribute_type: u16) {
+        self.attributes
+            .retain(|v| v.attribute_type != attribute_type);
+    }
+
+    pub fn get_length(&self) -> usize {
+        STUN_HEADER_LENGTH + self.get_attributes_length()
+    }
+
+    fn get_attributes_length(&self) -> usize {
+        self.attributes.iter().map(|v| v.get_length()).sum()
+    }
+
+    pub fn get_message_type(&self) -> u16 {
+        let m = self.method;
+        (m & 0x000f) | ((m & 0x0070) << 1) | ((m & 0x0f80) << 2) | self.class.get_bits()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.get_length());
+        out.extend_from_slice(&self.get_message_type().to_be_bytes());
+        out.extend_from_slice(&(self.get_attributes_length() as u16).to_be_bytes());
+        out.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        out.extend_from_slice(&self.transaction_id.0);
+
+        for attribute in &self.attributes {
+            out.extend_from_slice(&attribute.attribute_type.to_be_bytes());
+            out.extend_from_slice(&(attribute.value.len() as u16).to_be_bytes());
+            out.extend_from_slice(&attribute.value);
+            out.resize(get_padded_length(out.len()), 0);
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<StunMessage> {
+        if !is_stun_message(bytes) {
+            return Err(StunError::NotStunMessage);
+        }
+        let message_type = u16::from_be_bytes([bytes[0], bytes[1]]);
+        let length = usize::from(u16::from_be_bytes([bytes[2], bytes[3]]));
+        if length % 4 != 0 || bytes.len() != STUN_HEADER_LENGTH + length {
+            return Err(StunError::InvalidMessageLength);
+        }
+
+        let m = message_type & 0x3eef;
+        let method = (m & 0x000f) | ((m >> 1) & 0x0070) | ((m >> 2) & 0x0f80);
+        let class = StunClass::from_bits(message_type);
+        let mut transaction_id = [0; 12];
+        transaction_id.copy_from_slice(&bytes[8..20]);
+
+        let mut attributes = vec![];
+        let mut offset = STUN_HEADER_LENGTH;
+        while offset < bytes.len() {
+            if bytes.len() - offset < ATTRIBUTE_HEADER_LENGTH {
+                return Err(StunError::InvalidMessageLength);
+            }
+            let attribute_type = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+            let value_length =
+                usize::from(u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]));
+            let start = offset + ATTRIBUTE_HEADER_LENGTH;
+            let value = bytes
+                .get(start..start + value_length)
+                .ok_or(StunError::InvalidAttribute { attribute_type })?;
+            attributes.push(StunAttribute::new(attribute_type, value.to_vec()));
+            offset = start + get_padded_length(value_length);
+        }
+
+        Ok(StunMessage {
+            method,
+            class,
+            transaction_id: TransactionId(transaction_id),
+            attributes,
+        })
+    }
+}
+
+/// Tells a STUN message apart from DTLS, RTP and RTCP on a shared socket
+/// (RFC 7983).
+pub fn is_stun_message(bytes: &[u8]) -> bool {
+    bytes.len() >= STUN_HEADER_LENGTH
+        && bytes[0] & 0xc0 == 0
+        && bytes[4..8] == MAGIC_COOKIE.to_be_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn message_type_test() {
+        let id = TransactionId([1; 12]);
+        let cases = vec![
+            (METHOD_BINDING, StunClass::Request, 0x0001),
+            (METHOD_BINDING, StunClass::Indication, 0x0011),
+            (METHOD_BINDING, StunClass::SuccessResponse, 0x0101),
+            (METHOD_BINDING, StunClass::ErrorResponse, 0x0111),
+            (0x0003, StunClass::Request, 0x0003),
+            (0x0fff, StunClass::ErrorResponse, 0x3fff),
+            (0x0080, StunClass::SuccessResponse, 0x0300),
+        ];
+
+        for (method, class, message_type) in cases {
+            let message = StunMessage::new(method, class, id);
+            assert_eq!(message.get_message_type(), message_type);
+
+            let parsed = StunMessage::from_bytes(&message.to_bytes()).unwrap();
+            assert_eq!(parsed.get_method(), method);
+            assert_eq!(parsed.get_class(), class);
+        }
+    }
+
+    #[test]
+    fn stun_message_test() {
+        let mut request = StunMessage::request(METHOD_BINDING);
+        request.add_attribute(0x8022, b"test".to_vec());
+        request.add_attribute(0x0006, b"user:name".to_vec());
+
+        let bytes = request.to_bytes();
+        assert_eq!(bytes.len(), 20 + 8 + 16);
+        assert_eq!(request.get_length(), bytes.len());
+        assert_eq!(&bytes[2..4], &[0, 24]);
+        assert_eq!(&bytes[4..8], &[0x21, 0x12, 0xa4, 0x42]);
+        assert_eq!(&bytes[41..44], &[0, 0, 0]);
+        assert!(is_stun_message(&bytes));
+
+        let parsed = StunMessage::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed, request);
+        assert_eq!(parsed.get_attribute(0x0006), Some(&b"user:name"[..]));
+        assert!(!parsed.get_attributes()[0].is_comprehension_required());
+        assert!(parsed.get_attributes()[1].is_comprehension_required());
+
+        let response = StunMessage::response(&parsed, StunClass::SuccessResponse);
+        assert_eq!(response.get_transaction_id(), request.get_transaction_id());
+
+        // truncated, and an attribute running past the end.
+        assert_eq!(
+            StunMessage::from_bytes(&bytes[..40]),
+            Err(StunError::InvalidMessageLength)
+        );
+        let mut broken = bytes.clone();
+        broken[31] = 0xff;
+        assert_eq!(
+            StunMessage::from_bytes(&broken),
+            Err(StunError::InvalidAttribute {
+                attribute_type: 0x0006
+            })
+        );
+
+        let mut rtp = bytes;
+        rtp[0] = 0x80;
+        assert!(!is_stun_message(&rtp));
+        assert_eq!(
+            StunMessage::from_bytes(&rtp),
+            Err(StunError::NotStunMessage)
+        );
+    }
+}
@@ -0,0 +1,308 @@
+// https://tools.ietf.org/html/rfc8489#section-6.2.1
+
+/*
+    Retransmission over UDP with RTO = 500ms, Rc = 7, Rm = 16
+
+    send:   0ms, 500ms, 1500ms, 3500ms, 7500ms, 15500ms, 31500ms
+    fail:   39500ms (Rm * RTO after the last send)
+*/
+
+use crate::stun::message::{StunClass, StunMessage, TransactionId};
+use crate::time::Instant;
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+
+/// Where the transactions send their requests.
+pub trait DatagramTransport {
+    fn send_to(&mut self, data: &[u8], destination: SocketAddr) -> io::Result<usize>;
+}
+
+impl DatagramTransport for UdpSocket {
+    fn send_to(&mut self, data: &[u8], destination: SocketAddr) -> io::Result<usize> {
+        UdpSocket::send_to(self, data, destination)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TransactionConfig {
+    /// The initial retransmission timeout, doubled on every retransmission.
+    pub rto: Duration,
+    /// Rc, the number of requests sent in total.
+    pub max_requests: u32,
+    /// Rm, how many RTOs to wait for a response after the last request.
+    pub last_wait_multiplier: u32,
+}
+
+impl Default for TransactionConfig {
+    fn default() -> Self {
+        TransactionConfig {
+            rto: Duration::from_millis(500),
+            max_requests: 7,
+            last_wait_multiplier: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TransactionEvent {
+    Response {
+        transaction_id: TransactionId,
+        source: SocketAddr,
+        message: StunMessage,
+        /// Only measured when the request was not retransmitted (Karn's
+        /// algorithm).
+        rtt: Option<Duration>,
+    },
+    TimedOut {
+        transaction_id: TransactionId,
+        destination: SocketAddr,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct ClientTransaction {
+    request: Vec<u8>,
+    destination: SocketAddr,
+    started_at: Instant,
+    requests_sent: u32,
+    rto: Duration,
+    next_at: Instant,
+}
+
+// 送信したrequestの再送とresponseの照合を行う．keepaliveやICEのcheckで共通に使う．
+#[derive(Debug, Clone)]
+pub struct TransactionManager {
+    config: TransactionConfig,
+    transactions: HashMap<TransactionId, ClientTransaction>,
+}
+
+impl TransactionManager {
+    pub fn new(config: TransactionConfig) -> Self {
+        TransactionManager {
+            config,
+            transactions: HashMap::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> TransactionConfig {
+        self.config
+    }
+
+    pub fn get_pending_count(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_pending(&self, transaction_id: &TransactionId) -> bool {
+        self.transactions.contains_key(transaction_id)
+    }
+
+    /// Sends `request` and keeps retransmitting it until a response arrives
+    /// or the transaction times out. A send error is returned as is and the
+    /// transaction is not started.
+    pub fn start<T: DatagramTransport + ?Sized>(
+        &mut self,
+        request: &StunMessage,
+        destination: SocketAddr,
+        now: Instant,
+        transport: &mut T,
+    ) -> io::Result<TransactionId> {
+        let bytes = request.to_bytes();
+        transport.send_to(&bytes, destination)?;
+
+        let transaction_id = request.get_transaction_id();
+        self.transactions.insert(
+            transaction_id,
+            ClientTransaction {
+                request: bytes,
+                destination,
+                started_at: now,
+                requests_sent: 1,
+                rto: self.config.rto,
+                next_at: now + self.config.rto,
+            },
+        );
+        Ok(transaction_id)
+    }
+
+    pub fn cancel(&mut self, transaction_id: &TransactionId) {
+        self.transactions.remove(transaction_id);
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.transactions.values().map(|v| v.next_at).min()
+    }
+
+    /// Retransmits the requests that are due, and reports the transactions
+    /// that timed out.
+    pub fn process<T: DatagramTransport + ?Sized>(
+        &mut self,
+        now: Instant,
+        transport: &mut T,
+    ) -> Vec<TransactionEvent> {
+        let config = self.config;
+        let mut events = Vec::new();
+
+        self.transactions.retain(|transaction_id, v| {
+            if v.next_at > now {
+                return true;
+            }
+            if v.requests_sent >= config.max_requests.max(1) {
+                events.push(TransactionEvent::TimedOut {
+                    transaction_id: *transaction_id,
+                    destination: v.destination,
+                });
+                return false;
+            }
+
+            // a failed retransmission is like a lost one.
+            let _ = transport.send_to(&v.request, v.destination);
+            v.requests_sent += 1;
+            v.rto *= 2;
+            v.next_at = if v.requests_sent == config.max_requests {
+                now + config.rto * config.last_wait_multiplier
+            } else {
+                now + v.rto
+            };
+            true
+        });
+
+        events
+    }
+
+    /// Matches a received response. Returns `None` for requests,
+    /// indications and responses to unknown transactions.
+    pub fn handle_response(
+        &mut self,
+        message: StunMessage,
+        source: SocketAddr,
+        now: Instant,
+    ) -> Option<TransactionEvent> {
+        match message.get_class() {
+            StunClass::SuccessResponse | StunClass::ErrorResponse => {}
+            _ => return None,
+        }
+        let transaction_id = message.get_transaction_id();
+        let transaction = self.transactions.remove(&transaction_id)?;
+
+        let rtt = if transaction.requests_sent == 1 {
+            Some(now.saturating_duration_since(transaction.started_at))
+        } else {
+            None
+        };
+        Some(TransactionEvent::Response {
+            transaction_id,
+            source,
+            message,
+            rtt,
+        })
+    }
+}
+
+impl Default for TransactionManager {
+    fn default() -> Self {
+        TransactionManager::new(TransactionConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stun::message::METHOD_BINDING;
+
+    #[derive(Default)]
+    struct RecordingTransport {
+        sent: Vec<(Vec<u8>, SocketAddr)>,
+    }
+
+    impl DatagramTransport for RecordingTransport {
+        fn send_to(&mut self, data: &[u8], destination: SocketAddr) -> io::Result<usize> {
+            self.sent.push((data.to_vec(), destination));
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn retransmission_test() {
+        let now = Instant::now();
+        let server: SocketAddr = "192.0.2.1:3478".parse().unwrap();
+        let mut transport = RecordingTransport::default();
+        let mut manager = TransactionManager::default();
+
+        let request = StunMessage::request(METHOD_BINDING);
+        let id = manager
+            .start(&request, server, now, &mut transport)
+            .unwrap();
+        assert_eq!(transport.sent.len(), 1);
+
+        let mut sent_at = vec![Duration::from_millis(0)];
+        let mut events = vec![];
+        while let Some(t) = manager.poll_timeout() {
+            let sent = transport.sent.len();
+            events.extend(manager.process(t, &mut transport));
+            if transport.sent.len() > sent {
+                sent_at.push(t - now);
+            }
+        }
+
+        let expected: Vec<Duration> = [0, 500, 1500, 3500, 7500, 15500, 31500]
+            .iter()
+            .map(|v| Duration::from_millis(*v))
+            .collect();
+        assert_eq!(sent_at, expected);
+        assert!(transport.sent.iter().all(|v| v.0 == request.to_bytes()));
+        assert_eq!(
+            events,
+            vec![TransactionEvent::TimedOut {
+                transaction_id: id,
+                destination: server,
+            }]
+        );
+        assert_eq!(manager.get_pending_count(), 0);
+    }
+
+    #[test]
+    fn match_response_test() {
+        let now = Instant::now();
+        let server: SocketAddr = "192.0.2.1:3478".parse().unwrap();
+        let mut transport = RecordingTransport::default();
+        let mut manager = TransactionManager::default();
+
+        let first = StunMessage::request(METHOD_BINDING);
+        let second = StunMessage::request(METHOD_BINDING);
+        manager.start(&first, server, now, &mut transport).unwrap();
+        manager.start(&second, server, now, &mut transport).unwrap();
+
+        // the second one is retransmitted, so its RTT is unknown.
+        manager.process(now + Duration::from_millis(500), &mut transport);
+        manager.cancel(&first.get_transaction_id());
+        assert_eq!(manager.get_pending_count(), 1);
+
+        let unknown = StunMessage::response(&first, StunClass::SuccessResponse);
+        assert_eq!(manager.handle_response(unknown, server, now), None);
+        assert_eq!(manager.handle_response(second.clone(), server, now), None);
+
+        let response = StunMessage::response(&second, StunClass::ErrorResponse);
+        let t = now + Duration::from_millis(600);
+        match manager.handle_response(response.clone(), server, t) {
+            Some(TransactionEvent::Response { message, rtt, .. }) => {
+                assert_eq!(message, response);
+                assert_eq!(rtt, None);
+            }
+            v => panic!("unexpected {:?}", v),
+        }
+        assert_eq!(manager.poll_timeout(), None);
+
+        let third = StunMessage::request(METHOD_BINDING);
+        manager.start(&third, server, now, &mut transport).unwrap();
+        let response = StunMessage::response(&third, StunClass::SuccessResponse);
+        match manager.handle_response(response, server, now + Duration::from_millis(40)) {
+            Some(TransactionEvent::Response { rtt, .. }) => {
+                assert_eq!(rtt, Some(Duration::from_millis(40)))
+            }
+            v => panic!("unexpected {:?}", v),
+        }
+    }
+}
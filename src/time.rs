@@ -0,0 +1,25 @@
+// https://doc.rust-lang.org/std/time/struct.Instant.html
+
+/*
+    the Instant of the state machines. it is the one of std, but on
+    wasm32-unknown-unknown, where std has no clock and Instant::now
+    panics, it is a Duration since an origin of the host:
+
+      set_clock(clock)   the monotonic time of the host, performance.now()
+                         of a browser worker or the clock of a WASI edge
+                         runtime, before the first now()
+
+    Duration is the one of core on every target. the random numbers need
+    nothing of the host, getrandom takes them from crypto.getRandomValues
+    there.
+*/
+
+pub use std::time::Duration;
+
+#[cfg(any(test, all(target_arch = "wasm32", target_os = "unknown")))]
+mod host;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use host::{set_clock, Instant};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::Instant;
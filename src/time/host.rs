@@ -0,0 +1,128 @@
+/*
+    an Instant of the clock the host sets, with the API of the one of std.
+    it overflows and panics where the one of std does.
+*/
+
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::sync::Mutex;
+use std::time::Duration;
+
+static CLOCK: Mutex<Option<fn() -> Duration>> = Mutex::new(None);
+
+/// Sets the clock of `Instant::now`, the time since any origin, never
+/// going back.
+pub fn set_clock(clock: fn() -> Duration) {
+    *CLOCK.lock().unwrap() = Some(clock);
+}
+
+// hostの時計の原点からの時間．
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Instant(Duration);
+
+impl Instant {
+    /// Panics before set_clock.
+    pub fn now() -> Instant {
+        let clock = CLOCK
+            .lock()
+            .unwrap()
+            .expect("no clock of the host, see webrtc::time::set_clock");
+        Instant(clock())
+    }
+
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0)
+    }
+
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().saturating_duration_since(*self)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration).map(Instant)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Instant)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, other: Duration) -> Instant {
+        self.checked_add(other)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, other: Duration) -> Instant {
+        self.checked_sub(other)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, other: Instant) -> Duration {
+        self.duration_since(other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static MILLIS: AtomicU64 = AtomicU64::new(10_000);
+
+    fn clock() -> Duration {
+        Duration::from_millis(MILLIS.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn host_instant_test() {
+        set_clock(clock);
+        let start = Instant::now();
+        MILLIS.fetch_add(250, Ordering::SeqCst);
+        let now = Instant::now();
+        assert_eq!(now - start, Duration::from_millis(250));
+        assert_eq!(start.saturating_duration_since(now), Duration::ZERO);
+        assert_eq!(start.checked_duration_since(now), None);
+        assert_eq!(start.elapsed(), Duration::from_millis(250));
+
+        let mut later = start + Duration::from_millis(250);
+        assert_eq!(later, now);
+        later += Duration::from_secs(1);
+        later -= Duration::from_millis(500);
+        assert!(later > now);
+        assert_eq!(later - Duration::from_millis(500), now);
+        assert_eq!(
+            Instant(Duration::ZERO).checked_sub(Duration::from_nanos(1)),
+            None
+        );
+    }
+}
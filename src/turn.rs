@@ -0,0 +1,27 @@
+pub mod channel_data;
+pub mod client;
+pub mod framing;
+pub mod rest;
+pub mod uri;
+
+use failure::Fail;
+
+pub type Result<T> = std::result::Result<T, TurnError>;
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum TurnError {
+    #[fail(display = "TURN allocation is not ready.")]
+    NoAllocation,
+
+    #[fail(display = "TURN ChannelData message is broken.")]
+    InvalidChannelData,
+
+    #[fail(display = "TURN channel numbers are exhausted.")]
+    ChannelNumbersExhausted,
+
+    #[fail(display = "TURN URI is invalid.")]
+    InvalidUri,
+
+    #[fail(display = "TURN REST credentials are invalid.")]
+    InvalidCredentials,
+}
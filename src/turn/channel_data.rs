@@ -0,0 +1,106 @@
+// https://tools.ietf.org/html/rfc8656#section-12.4
+
+/*
+    ChannelData Message
+
+     0                   1                   2                   3
+     0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |         Channel Number        |            Length             |
+    +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+    |                                                               |
+    /                       Application Data                        /
+    /                                                               /
+    |                                                               |
+    |                               +-------------------------------+
+    |                               |
+    +-------------------------------+
+
+    Channel numbers are 0x4000 through 0x4fff. Over TCP and TLS the message
+    is padded to a multiple of 4 bytes, which Length does not count.
+*/
+
+use crate::turn::{Result, TurnError};
+
+pub const MIN_CHANNEL_NUMBER: u16 = 0x4000;
+pub const MAX_CHANNEL_NUMBER: u16 = 0x4fff;
+pub const CHANNEL_DATA_HEADER_LENGTH: usize = 4;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ChannelData {
+    pub channel: u16,
+    pub data: Vec<u8>,
+}
+
+impl ChannelData {
+    pub fn new(channel: u16, data: Vec<u8>) -> Self {
+        ChannelData { channel, data }
+    }
+
+    pub fn get_length(&self, padded: bool) -> usize {
+        let length = CHANNEL_DATA_HEADER_LENGTH + self.data.len();
+        if padded {
+            (length + 3) & !3
+        } else {
+            length
+        }
+    }
+
+    pub fn to_bytes(&self, padded: bool) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.get_length(padded));
+        out.extend_from_slice(&self.channel.to_be_bytes());
+        out.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.data);
+        out.resize(self.get_length(padded), 0);
+        out
+    }
+
+    /// Parses a ChannelData message. Trailing padding is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ChannelData> {
+        if bytes.len() < CHANNEL_DATA_HEADER_LENGTH {
+            return Err(TurnError::InvalidChannelData);
+        }
+        let channel = u16::from_be_bytes([bytes[0], bytes[1]]);
+        if !(MIN_CHANNEL_NUMBER..=MAX_CHANNEL_NUMBER).contains(&channel) {
+            return Err(TurnError::InvalidChannelData);
+        }
+        let length = usize::from(u16::from_be_bytes([bytes[2], bytes[3]]));
+        let data = bytes
+            .get(CHANNEL_DATA_HEADER_LENGTH..CHANNEL_DATA_HEADER_LENGTH + length)
+            .ok_or(TurnError::InvalidChannelData)?;
+        Ok(ChannelData::new(channel, data.to_vec()))
+    }
+}
+
+/// ChannelData starts with 0b01, STUN messages with 0b00.
+pub fn is_channel_data(bytes: &[u8]) -> bool {
+    bytes.len() >= CHANNEL_DATA_HEADER_LENGTH && bytes[0] & 0xc0 == 0x40
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn channel_data_test() {
+        let message = ChannelData::new(0x4001, b"hello".to_vec());
+        let bytes = message.to_bytes(false);
+        assert_eq!(bytes, b"\x40\x01\x00\x05hello".to_vec());
+        assert!(is_channel_data(&bytes));
+        assert_eq!(ChannelData::from_bytes(&bytes), Ok(message.clone()));
+
+        let padded = message.to_bytes(true);
+        assert_eq!(padded.len(), 12);
+        assert_eq!(ChannelData::from_bytes(&padded), Ok(message));
+
+        assert!(!is_channel_data(&[0x00, 0x01, 0x00, 0x00]));
+        assert_eq!(
+            ChannelData::from_bytes(&[0x50, 0x00, 0x00, 0x00]),
+            Err(TurnError::InvalidChannelData)
+        );
+        assert_eq!(
+            ChannelData::from_bytes(&[0x40, 0x00, 0x00, 0x04, 0x00]),
+            Err(TurnError::InvalidChannelData)
+        );
+    }
+}
@@ -0,0 +1,920 @@
+// https://tools.ietf.org/html/rfc8656
+
+/*
+    client                                  server
+      | Allocate (REQUESTED-TRANSPORT)          |
+      |---------------------------------------->|
+      |            401 (REALM, NONCE)           |
+      |<----------------------------------------|
+      | Allocate + long-term credentials        |
+      |---------------------------------------->|
+      |   XOR-RELAYED-ADDRESS, LIFETIME, ...    |
+      |<----------------------------------------|
+      | CreatePermission (XOR-PEER-ADDRESS)     |
+      |---------------------------------------->|
+      | Send indication (XOR-PEER-ADDRESS, DATA)|   peer
+      |---------------------------------------->|---->
+      | Data indication (XOR-PEER-ADDRESS, DATA)|
+      |<----------------------------------------|<----
+      | ChannelBind (CHANNEL-NUMBER, PEER)      |
+      |---------------------------------------->|
+      | ChannelData (4 bytes header + data)     |   peer
+      |<--------------------------------------->|<--->
+
+    Refresh at LIFETIME - 60s, permissions expire after 300s and are
+    refreshed after 240s, channel bindings expire after 600s and are
+    refreshed after 540s.
+
+    Over TCP and TLS the requests are not retransmitted, and time out after
+    39.5s.
+*/
+
+use crate::ice::network::{Transmit, TransmitQueue};
+use crate::stun::attribute::{
+    ATTR_CHANNEL_NUMBER, ATTR_DATA, ATTR_LIFETIME, ATTR_REQUESTED_TRANSPORT,
+    ATTR_XOR_MAPPED_ADDRESS, ATTR_XOR_PEER_ADDRESS, ATTR_XOR_RELAYED_ADDRESS,
+};
+use crate::stun::credentials::LongTermCredentials;
+use crate::stun::message::{
+    StunClass, StunMessage, TransactionId, METHOD_ALLOCATE, METHOD_CHANNEL_BIND,
+    METHOD_CREATE_PERMISSION, METHOD_DATA, METHOD_REFRESH, METHOD_SEND,
+};
+use crate::stun::transaction::{TransactionConfig, TransactionEvent, TransactionManager};
+use crate::time::Instant;
+use crate::turn::channel_data::{
+    is_channel_data, ChannelData, MAX_CHANNEL_NUMBER, MIN_CHANNEL_NUMBER,
+};
+use crate::turn::framing::StreamFramer;
+use crate::turn::uri::TurnTransport;
+use crate::turn::{Result, TurnError};
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+const PROTOCOL_UDP: u8 = 17;
+const PERMISSION_REFRESH_INTERVAL: Duration = Duration::from_secs(240);
+const CHANNEL_REFRESH_INTERVAL: Duration = Duration::from_secs(540);
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+const STREAM_TRANSACTION_TIMEOUT: Duration = Duration::from_millis(39500);
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TurnConfig {
+    pub server: SocketAddr,
+    pub transport: TurnTransport,
+    pub username: String,
+    pub password: String,
+    /// The requested allocation lifetime. The server may choose another one.
+    pub lifetime: Duration,
+    /// Binds a channel to every peer data is sent to, and sends ChannelData
+    /// instead of Send indications once it is bound.
+    pub use_channels: bool,
+    pub transaction: TransactionConfig,
+}
+
+impl TurnConfig {
+    pub fn new(server: SocketAddr, username: &str, password: &str) -> Self {
+        TurnConfig {
+            server,
+            transport: TurnTransport::Udp,
+            username: username.to_string(),
+            password: password.to_string(),
+            lifetime: Duration::from_secs(600),
+            use_channels: true,
+            transaction: TransactionConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum TurnState {
+    New,
+    Allocating,
+    Allocated,
+    Closing,
+    Closed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TurnEvent {
+    Allocated {
+        relayed: SocketAddr,
+        mapped: Option<SocketAddr>,
+    },
+    /// `code` is `None` when the server did not answer.
+    AllocationFailed {
+        code: Option<u16>,
+    },
+    PermissionCreated {
+        peer: IpAddr,
+    },
+    PermissionFailed {
+        peer: IpAddr,
+    },
+    ChannelBound {
+        peer: SocketAddr,
+        channel: u16,
+    },
+    ChannelBindFailed {
+        peer: SocketAddr,
+        channel: u16,
+    },
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum RequestKind {
+    Allocate,
+    Refresh { lifetime: u32 },
+    CreatePermission { peer: IpAddr },
+    ChannelBind { peer: SocketAddr, channel: u16 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingRequest {
+    kind: RequestKind,
+    signed: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Permission {
+    installed: bool,
+    refresh_at: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    number: u16,
+    bound: bool,
+    refresh_at: Option<Instant>,
+}
+
+// 1つのallocationを管理する．socketは持たず，localから送るTransmitを返す．
+#[derive(Debug, Clone)]
+pub struct TurnClient {
+    local: SocketAddr,
+    config: TurnConfig,
+    state: TurnState,
+    credentials: LongTermCredentials,
+    transactions: TransactionManager,
+    framer: StreamFramer,
+    requests: HashMap<TransactionId, PendingRequest>,
+    relayed: Option<SocketAddr>,
+    mapped: Option<SocketAddr>,
+    refresh_at: Option<Instant>,
+    permissions: HashMap<IpAddr, Permission>,
+    channels: HashMap<SocketAddr, Channel>,
+    next_channel: u16,
+    transmits: VecDeque<Transmit>,
+    events: VecDeque<TurnEvent>,
+}
+
+impl TurnClient {
+    /// `local` is the address of the socket (or the local end of the
+    /// stream) the server is reached from.
+    pub fn new(local: SocketAddr, config: TurnConfig) -> Self {
+        TurnClient {
+            local,
+            credentials: LongTermCredentials::new(&config.username, &config.password),
+            transactions: TransactionManager::new(get_transaction_config(&config)),
+            framer: StreamFramer::new(),
+            config,
+            state: TurnState::New,
+            requests: HashMap::new(),
+            relayed: None,
+            mapped: None,
+            refresh_at: None,
+            permissions: HashMap::new(),
+            channels: HashMap::new(),
+            next_channel: MIN_CHANNEL_NUMBER,
+            transmits: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn get_local_address(&self) -> SocketAddr {
+        self.local
+    }
+
+    pub fn get_config(&self) -> &TurnConfig {
+        &self.config
+    }
+
+    pub fn get_server(&self) -> SocketAddr {
+        self.config.server
+    }
+
+    pub fn get_state(&self) -> TurnState {
+        self.state
+    }
+
+    pub fn get_relayed_address(&self) -> Option<SocketAddr> {
+        self.relayed
+    }
+
+    pub fn get_mapped_address(&self) -> Option<SocketAddr> {
+        self.mapped
+    }
+
+    pub fn has_permission(&self, peer: IpAddr) -> bool {
+        self.permissions.get(&peer).is_some_and(|v| v.installed)
+    }
+
+    /// The channel number bound to `peer`, once the server confirmed it.
+    pub fn get_channel(&self, peer: SocketAddr) -> Option<u16> {
+        self.channels
+            .get(&peer)
+            .filter(|v| v.bound)
+            .map(|v| v.number)
+    }
+
+    pub fn allocate(&mut self, now: Instant) {
+        if self.state == TurnState::New {
+            self.state = TurnState::Allocating;
+            self.send_request(RequestKind::Allocate, now);
+        }
+    }
+
+    /// Installs a permission for `peer`, kept refreshed until the
+    /// allocation is closed.
+    pub fn create_permission(&mut self, peer: IpAddr, now: Instant) {
+        if self.permissions.contains_key(&peer) {
+            return;
+        }
+        self.permissions.insert(
+            peer,
+            Permission {
+                installed: false,
+                refresh_at: None,
+            },
+        );
+        if self.state == TurnState::Allocated {
+            self.send_request(RequestKind::CreatePermission { peer }, now);
+        }
+    }
+
+    /// Binds a channel to `peer`, which also installs a permission for it.
+    /// The binding is kept refreshed until the allocation is closed.
+    pub fn bind_channel(&mut self, peer: SocketAddr, now: Instant) -> Result<u16> {
+        if self.state != TurnState::Allocated {
+            return Err(TurnError::NoAllocation);
+        }
+        if let Some(v) = self.channels.get(&peer) {
+            return Ok(v.number);
+        }
+        if self.next_channel > MAX_CHANNEL_NUMBER {
+            return Err(TurnError::ChannelNumbersExhausted);
+        }
+        let channel = self.next_channel;
+        self.next_channel += 1;
+        self.channels.insert(
+            peer,
+            Channel {
+                number: channel,
+                bound: false,
+                refresh_at: None,
+            },
+        );
+        self.send_request(RequestKind::ChannelBind { peer, channel }, now);
+        Ok(channel)
+    }
+
+    /// Sends `data` to `peer` in ChannelData once a channel is bound, or in
+    /// a Send indication. A permission (or a channel with `use_channels`)
+    /// is requested if there is none yet; until it is installed the server
+    /// drops the data.
+    pub fn send_to(&mut self, peer: SocketAddr, data: &[u8], now: Instant) -> Result<()> {
+        if self.state != TurnState::Allocated {
+            return Err(TurnError::NoAllocation);
+        }
+        if let Some(channel) = self.get_channel(peer) {
+            let message = ChannelData::new(channel, data.to_vec());
+            self.transmits.push_back(Transmit::new(
+                self.local,
+                self.config.server,
+                message.to_bytes(self.config.transport.is_stream()),
+            ));
+            return Ok(());
+        }
+        if !self.config.use_channels || self.bind_channel(peer, now).is_err() {
+            self.create_permission(peer.ip(), now);
+        }
+
+        let mut message =
+            StunMessage::new(METHOD_SEND, StunClass::Indication, TransactionId::new());
+        message.add_xor_address(ATTR_XOR_PEER_ADDRESS, &peer);
+        message.add_attribute(ATTR_DATA, data.to_vec());
+        self.transmits.push_back(Transmit::new(
+            self.local,
+            self.config.server,
+            message.to_bytes(),
+        ));
+        Ok(())
+    }
+
+    /// Deletes the allocation with a zero lifetime Refresh.
+    pub fn close(&mut self, now: Instant) {
+        match self.state {
+            TurnState::Allocated => {
+                self.state = TurnState::Closing;
+                self.refresh_at = None;
+                self.send_request(RequestKind::Refresh { lifetime: 0 }, now);
+            }
+            TurnState::New | TurnState::Allocating => {
+                self.state = TurnState::Closed;
+                self.requests.clear();
+                self.transactions = TransactionManager::new(get_transaction_config(&self.config));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        self.transmits.pop_front()
+    }
+
+    pub fn poll_event(&mut self) -> Option<TurnEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        let permissions = self.permissions.values().filter_map(|v| v.refresh_at);
+        let channels = self.channels.values().filter_map(|v| v.refresh_at);
+        self.transactions
+            .poll_timeout()
+            .into_iter()
+            .chain(self.refresh_at)
+            .chain(permissions)
+            .chain(channels)
+            .min()
+    }
+
+    pub fn process(&mut self, now: Instant) {
+        let mut transport = TransmitQueue {
+            source: self.local,
+            queue: &mut self.transmits,
+        };
+        for event in self.transactions.process(now, &mut transport) {
+            if let TransactionEvent::TimedOut { transaction_id, .. } = event {
+                if let Some(request) = self.requests.remove(&transaction_id) {
+                    self.handle_failure(request.kind, None);
+                }
+            }
+        }
+
+        if self.refresh_at.is_some_and(|v| v <= now) {
+            self.refresh_at = None;
+            let lifetime = self.config.lifetime.as_secs() as u32;
+            self.send_request(RequestKind::Refresh { lifetime }, now);
+        }
+
+        let due: Vec<IpAddr> = self
+            .permissions
+            .iter()
+            .filter(|(_, v)| v.refresh_at.is_some_and(|v| v <= now))
+            .map(|(k, _)| *k)
+            .collect();
+        for peer in due {
+            if let Some(v) = self.permissions.get_mut(&peer) {
+                v.refresh_at = None;
+            }
+            self.send_request(RequestKind::CreatePermission { peer }, now);
+        }
+
+        let due: Vec<(SocketAddr, u16)> = self
+            .channels
+            .iter()
+            .filter(|(_, v)| v.refresh_at.is_some_and(|v| v <= now))
+            .map(|(k, v)| (*k, v.number))
+            .collect();
+        for (peer, channel) in due {
+            if let Some(v) = self.channels.get_mut(&peer) {
+                v.refresh_at = None;
+            }
+            self.send_request(RequestKind::ChannelBind { peer, channel }, now);
+        }
+    }
+
+    /// Handles a datagram received from the server. Returns the peer and
+    /// the data of a Data indication or ChannelData; responses are handled
+    /// internally.
+    pub fn handle_receive(
+        &mut self,
+        source: SocketAddr,
+        data: &[u8],
+        now: Instant,
+    ) -> Option<(SocketAddr, Vec<u8>)> {
+        if source != self.config.server {
+            return None;
+        }
+        if is_channel_data(data) {
+            let message = ChannelData::from_bytes(data).ok()?;
+            let peer = self
+                .channels
+                .iter()
+                .find(|(_, v)| v.bound && v.number == message.channel)
+                .map(|(k, _)| *k)?;
+            return Some((peer, message.data));
+        }
+        let message = StunMessage::from_bytes(data).ok()?;
+
+        if message.get_class() == StunClass::Indication {
+            if message.get_method() != METHOD_DATA {
+                return None;
+            }
+            let peer = message.get_xor_address(ATTR_XOR_PEER_ADDRESS).ok()??;
+            let data = message.get_attribute(ATTR_DATA)?.to_vec();
+            return Some((peer, data));
+        }
+
+        let transaction_id = message.get_transaction_id();
+        let request = *self.requests.get(&transaction_id)?;
+        if request.signed
+            && message.get_class() == StunClass::SuccessResponse
+            && self.credentials.verify_response(data).is_err()
+        {
+            return None;
+        }
+        let message = match self.transactions.handle_response(message, source, now) {
+            Some(TransactionEvent::Response { message, .. }) => message,
+            _ => return None,
+        };
+        self.requests.remove(&transaction_id);
+
+        if message.get_class() == StunClass::SuccessResponse {
+            self.handle_success(request.kind, &message, now);
+        } else if let Ok(true) = self
+            .credentials
+            .handle_error_response(&message, request.signed)
+        {
+            self.send_request(request.kind, now);
+        } else {
+            let code = message.get_error_code().ok().flatten().map(|v| v.code);
+            self.handle_failure(request.kind, code);
+        }
+        None
+    }
+
+    /// Handles data read from the TCP or TLS stream to the server, which
+    /// may hold any number of frames. An error means the stream is broken.
+    pub fn handle_stream_data(
+        &mut self,
+        data: &[u8],
+        now: Instant,
+    ) -> Result<Vec<(SocketAddr, Vec<u8>)>> {
+        self.framer.push(data);
+        let mut received = Vec::new();
+        while let Some(frame) = self.framer.pop()? {
+            let server = self.config.server;
+            received.extend(self.handle_receive(server, &frame, now));
+        }
+        Ok(received)
+    }
+
+    fn send_request(&mut self, kind: RequestKind, now: Instant) {
+        let mut message = match kind {
+            RequestKind::Allocate => {
+                let mut message = StunMessage::request(METHOD_ALLOCATE);
+                message.add_attribute(ATTR_REQUESTED_TRANSPORT, vec![PROTOCOL_UDP, 0, 0, 0]);
+                let lifetime = self.config.lifetime.as_secs() as u32;
+                message.add_attribute(ATTR_LIFETIME, lifetime.to_be_bytes().to_vec());
+                message
+            }
+            RequestKind::Refresh { lifetime } => {
+                let mut message = StunMessage::request(METHOD_REFRESH);
+                message.add_attribute(ATTR_LIFETIME, lifetime.to_be_bytes().to_vec());
+                message
+            }
+            RequestKind::CreatePermission { peer } => {
+                let mut message = StunMessage::request(METHOD_CREATE_PERMISSION);
+                message.add_xor_address(ATTR_XOR_PEER_ADDRESS, &SocketAddr::new(peer, 0));
+                message
+            }
+            RequestKind::ChannelBind { peer, channel } => {
+                let mut message = StunMessage::request(METHOD_CHANNEL_BIND);
+                let mut value = channel.to_be_bytes().to_vec();
+                value.extend_from_slice(&[0, 0]);
+                message.add_attribute(ATTR_CHANNEL_NUMBER, value);
+                message.add_xor_address(ATTR_XOR_PEER_ADDRESS, &peer);
+                message
+            }
+        };
+        let signed = self.credentials.sign(&mut message);
+
+        let mut transport = TransmitQueue {
+            source: self.local,
+            queue: &mut self.transmits,
+        };
+        if let Ok(transaction_id) =
+            self.transactions
+                .start(&message, self.config.server, now, &mut transport)
+        {
+            self.requests
+                .insert(transaction_id, PendingRequest { kind, signed });
+        }
+    }
+
+    fn handle_success(&mut self, kind: RequestKind, message: &StunMessage, now: Instant) {
+        let lifetime = message
+            .get_u32_attribute(ATTR_LIFETIME)
+            .ok()
+            .flatten()
+            .map(|v| Duration::from_secs(u64::from(v)));
+
+        match kind {
+            RequestKind::Allocate => {
+                let relayed = match message.get_xor_address(ATTR_XOR_RELAYED_ADDRESS) {
+                    Ok(Some(v)) => v,
+                    _ => return self.handle_failure(kind, None),
+                };
+                self.relayed = Some(relayed);
+                self.mapped = message
+                    .get_xor_address(ATTR_XOR_MAPPED_ADDRESS)
+                    .ok()
+                    .flatten();
+                self.state = TurnState::Allocated;
+                self.schedule_refresh(lifetime.unwrap_or(self.config.lifetime), now);
+                self.events.push_back(TurnEvent::Allocated {
+                    relayed,
+                    mapped: self.mapped,
+                });
+
+                let peers: Vec<IpAddr> = self.permissions.keys().copied().collect();
+                for peer in peers {
+                    self.send_request(RequestKind::CreatePermission { peer }, now);
+                }
+            }
+            RequestKind::Refresh { lifetime: 0 } => self.set_closed(),
+            RequestKind::Refresh { .. } => {
+                self.schedule_refresh(lifetime.unwrap_or(self.config.lifetime), now)
+            }
+            RequestKind::CreatePermission { peer } => {
+                if let Some(v) = self.permissions.get_mut(&peer) {
+                    let first = !v.installed;
+                    v.installed = true;
+                    v.refresh_at = Some(now + PERMISSION_REFRESH_INTERVAL);
+                    if first {
+                        self.events.push_back(TurnEvent::PermissionCreated { peer });
+                    }
+                }
+            }
+            RequestKind::ChannelBind { peer, channel } => {
+                if let Some(v) = self.channels.get_mut(&peer) {
+                    let first = !v.bound;
+                    v.bound = true;
+                    v.refresh_at = Some(now + CHANNEL_REFRESH_INTERVAL);
+                    if first {
+                        self.events
+                            .push_back(TurnEvent::ChannelBound { peer, channel });
+                    }
+                }
+                // a channel binding also installs or refreshes the
+                // permission.
+                let permission = self.permissions.entry(peer.ip()).or_insert(Permission {
+                    installed: false,
+                    refresh_at: None,
+                });
+                if !permission.installed {
+                    permission.installed = true;
+                    self.events
+                        .push_back(TurnEvent::PermissionCreated { peer: peer.ip() });
+                }
+                permission.refresh_at = Some(now + PERMISSION_REFRESH_INTERVAL);
+            }
+        }
+    }
+
+    fn handle_failure(&mut self, kind: RequestKind, code: Option<u16>) {
+        match kind {
+            RequestKind::Allocate => {
+                self.state = TurnState::Failed;
+                self.events.push_back(TurnEvent::AllocationFailed { code });
+            }
+            RequestKind::Refresh { lifetime: 0 } => self.set_closed(),
+            RequestKind::Refresh { .. } => {
+                self.state = TurnState::Failed;
+                self.refresh_at = None;
+                self.events.push_back(TurnEvent::Closed);
+            }
+            RequestKind::CreatePermission { peer } => {
+                self.permissions.remove(&peer);
+                self.events.push_back(TurnEvent::PermissionFailed { peer });
+            }
+            RequestKind::ChannelBind { peer, channel } => {
+                self.channels.remove(&peer);
+                self.events
+                    .push_back(TurnEvent::ChannelBindFailed { peer, channel });
+            }
+        }
+    }
+
+    fn schedule_refresh(&mut self, lifetime: Duration, now: Instant) {
+        let wait = if lifetime > REFRESH_MARGIN * 2 {
+            lifetime - REFRESH_MARGIN
+        } else {
+            lifetime / 2
+        };
+        self.refresh_at = Some(now + wait);
+    }
+
+    fn set_closed(&mut self) {
+        self.state = TurnState::Closed;
+        self.refresh_at = None;
+        self.permissions.clear();
+        self.channels.clear();
+        self.events.push_back(TurnEvent::Closed);
+    }
+}
+
+fn get_transaction_config(config: &TurnConfig) -> TransactionConfig {
+    if config.transport.is_stream() {
+        TransactionConfig {
+            rto: STREAM_TRANSACTION_TIMEOUT,
+            max_requests: 1,
+            ..config.transaction
+        }
+    } else {
+        config.transaction
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::stun::attribute::{ATTR_NONCE, ATTR_REALM, ERROR_UNAUTHENTICATED};
+    use crate::stun::integrity::get_long_term_key;
+
+    pub(crate) const RELAYED: &str = "198.51.100.1:49152";
+    pub(crate) const MAPPED: &str = "203.0.113.7:60769";
+
+    // 認証付きでallocateに応答するTURN serverの代わり．
+    pub(crate) fn serve(request: &Transmit) -> Vec<u8> {
+        let request = StunMessage::from_bytes(&request.data).unwrap();
+        if !request.has_attribute(crate::stun::attribute::ATTR_MESSAGE_INTEGRITY) {
+            let mut response = StunMessage::response(&request, StunClass::ErrorResponse);
+            response.add_error_code(ERROR_UNAUTHENTICATED, "Unauthorized");
+            response.add_attribute(ATTR_REALM, b"example.org".to_vec());
+            response.add_attribute(ATTR_NONCE, b"nonce".to_vec());
+            return response.to_bytes();
+        }
+        let mut response = StunMessage::response(&request, StunClass::SuccessResponse);
+        if request.get_method() == METHOD_ALLOCATE {
+            response.add_xor_address(ATTR_XOR_RELAYED_ADDRESS, &RELAYED.parse().unwrap());
+            response.add_xor_address(ATTR_XOR_MAPPED_ADDRESS, &MAPPED.parse().unwrap());
+            response.add_attribute(ATTR_LIFETIME, 600u32.to_be_bytes().to_vec());
+        }
+        response.add_message_integrity(&get_long_term_key("user", "example.org", "pass"));
+        response.to_bytes()
+    }
+
+    pub(crate) fn allocate(client: &mut TurnClient, now: Instant) {
+        client.allocate(now);
+        while let Some(request) = client.poll_transmit() {
+            let response = serve(&request);
+            client.handle_receive(request.destination, &response, now);
+        }
+    }
+
+    fn config() -> TurnConfig {
+        TurnConfig {
+            use_channels: false,
+            ..TurnConfig::new("192.0.2.1:3478".parse().unwrap(), "user", "pass")
+        }
+    }
+
+    #[test]
+    fn allocate_test() {
+        let now = Instant::now();
+        let local = "192.168.1.10:50000".parse().unwrap();
+        let mut client = TurnClient::new(local, config());
+        allocate(&mut client, now);
+
+        assert_eq!(client.get_state(), TurnState::Allocated);
+        assert_eq!(client.get_relayed_address(), Some(RELAYED.parse().unwrap()));
+        assert_eq!(
+            client.poll_event(),
+            Some(TurnEvent::Allocated {
+                relayed: RELAYED.parse().unwrap(),
+                mapped: Some(MAPPED.parse().unwrap()),
+            })
+        );
+        assert_eq!(client.poll_timeout(), Some(now + Duration::from_secs(540)));
+
+        // refreshed before the lifetime ends.
+        let t = now + Duration::from_secs(540);
+        client.process(t);
+        let refresh = client.poll_transmit().unwrap();
+        let message = StunMessage::from_bytes(&refresh.data).unwrap();
+        assert_eq!(message.get_method(), METHOD_REFRESH);
+        client.handle_receive(refresh.destination, &serve(&refresh), t);
+        assert_eq!(client.poll_timeout(), Some(t + Duration::from_secs(540)));
+
+        client.close(t);
+        let refresh = client.poll_transmit().unwrap();
+        client.handle_receive(refresh.destination, &serve(&refresh), t);
+        assert_eq!(client.get_state(), TurnState::Closed);
+        assert_eq!(client.poll_event(), Some(TurnEvent::Closed));
+    }
+
+    #[test]
+    fn permission_and_data_test() {
+        let now = Instant::now();
+        let local = "192.168.1.10:50000".parse().unwrap();
+        let peer: SocketAddr = "203.0.113.50:40000".parse().unwrap();
+        let mut client = TurnClient::new(local, config());
+        assert_eq!(
+            client.send_to(peer, b"hello", now),
+            Err(TurnError::NoAllocation)
+        );
+        allocate(&mut client, now);
+        client.poll_event();
+
+        client.send_to(peer, b"hello", now).unwrap();
+        let permission = client.poll_transmit().unwrap();
+        let message = StunMessage::from_bytes(&permission.data).unwrap();
+        assert_eq!(message.get_method(), METHOD_CREATE_PERMISSION);
+        let send = client.poll_transmit().unwrap();
+        let message = StunMessage::from_bytes(&send.data).unwrap();
+        assert_eq!(message.get_class(), StunClass::Indication);
+        assert_eq!(
+            message.get_xor_address(ATTR_XOR_PEER_ADDRESS),
+            Ok(Some(peer))
+        );
+        assert_eq!(message.get_attribute(ATTR_DATA), Some(&b"hello"[..]));
+
+        client.handle_receive(permission.destination, &serve(&permission), now);
+        assert!(client.has_permission(peer.ip()));
+        assert_eq!(
+            client.poll_event(),
+            Some(TurnEvent::PermissionCreated { peer: peer.ip() })
+        );
+
+        let mut indication =
+            StunMessage::new(METHOD_DATA, StunClass::Indication, TransactionId::new());
+        indication.add_xor_address(ATTR_XOR_PEER_ADDRESS, &peer);
+        indication.add_attribute(ATTR_DATA, b"world".to_vec());
+        let server = client.get_server();
+        assert_eq!(
+            client.handle_receive(server, &indication.to_bytes(), now),
+            Some((peer, b"world".to_vec()))
+        );
+        assert_eq!(
+            client.handle_receive(peer, &indication.to_bytes(), now),
+            None
+        );
+
+        // the permission is refreshed.
+        let t = now + PERMISSION_REFRESH_INTERVAL;
+        client.process(t);
+        let refresh = client.poll_transmit().unwrap();
+        let message = StunMessage::from_bytes(&refresh.data).unwrap();
+        assert_eq!(message.get_method(), METHOD_CREATE_PERMISSION);
+    }
+
+    #[test]
+    fn allocation_failure_test() {
+        let now = Instant::now();
+        let mut client = TurnClient::new("192.168.1.10:50000".parse().unwrap(), config());
+        client.allocate(now);
+        while let Some(t) = client.poll_timeout() {
+            client.process(t);
+        }
+        assert_eq!(client.get_state(), TurnState::Failed);
+        assert_eq!(
+            client.poll_event(),
+            Some(TurnEvent::AllocationFailed { code: None })
+        );
+    }
+
+    #[test]
+    fn channel_bind_test() {
+        let now = Instant::now();
+        let local = "192.168.1.10:50000".parse().unwrap();
+        let peer: SocketAddr = "203.0.113.50:40000".parse().unwrap();
+        let mut client = TurnClient::new(
+            local,
+            TurnConfig::new("192.0.2.1:3478".parse().unwrap(), "user", "pass"),
+        );
+        allocate(&mut client, now);
+        client.poll_event();
+
+        // the first packet goes in a Send indication while the channel is
+        // being bound.
+        client.send_to(peer, b"first", now).unwrap();
+        let bind = client.poll_transmit().unwrap();
+        let message = StunMessage::from_bytes(&bind.data).unwrap();
+        assert_eq!(message.get_method(), METHOD_CHANNEL_BIND);
+        assert_eq!(
+            message.get_attribute(ATTR_CHANNEL_NUMBER),
+            Some(&[0x40, 0x00, 0x00, 0x00][..])
+        );
+        let send = client.poll_transmit().unwrap();
+        assert_eq!(
+            StunMessage::from_bytes(&send.data).unwrap().get_method(),
+            METHOD_SEND
+        );
+        assert_eq!(client.get_channel(peer), None);
+
+        client.handle_receive(bind.destination, &serve(&bind), now);
+        assert_eq!(client.get_channel(peer), Some(MIN_CHANNEL_NUMBER));
+        assert!(client.has_permission(peer.ip()));
+        assert_eq!(
+            client.poll_event(),
+            Some(TurnEvent::ChannelBound {
+                peer,
+                channel: MIN_CHANNEL_NUMBER
+            })
+        );
+
+        client.send_to(peer, b"second", now).unwrap();
+        let data = client.poll_transmit().unwrap();
+        assert_eq!(data.data, b"\x40\x00\x00\x06second".to_vec());
+
+        let server = client.get_server();
+        let incoming = ChannelData::new(MIN_CHANNEL_NUMBER, b"reply".to_vec()).to_bytes(false);
+        assert_eq!(
+            client.handle_receive(server, &incoming, now),
+            Some((peer, b"reply".to_vec()))
+        );
+        let unknown = ChannelData::new(MIN_CHANNEL_NUMBER + 1, b"reply".to_vec()).to_bytes(false);
+        assert_eq!(client.handle_receive(server, &unknown, now), None);
+
+        // the binding is refreshed with the same channel number.
+        let t = now + CHANNEL_REFRESH_INTERVAL;
+        client.process(t);
+        let refresh = std::iter::from_fn(|| client.poll_transmit())
+            .map(|v| StunMessage::from_bytes(&v.data).unwrap())
+            .find(|v| v.get_method() == METHOD_CHANNEL_BIND)
+            .unwrap();
+        assert_eq!(
+            refresh.get_attribute(ATTR_CHANNEL_NUMBER),
+            Some(&[0x40, 0x00, 0x00, 0x00][..])
+        );
+    }
+
+    #[test]
+    fn allocate_over_tcp_test() {
+        let now = Instant::now();
+        let local = "192.168.1.10:51000".parse().unwrap();
+        let peer: SocketAddr = "203.0.113.50:40000".parse().unwrap();
+        let mut client = TurnClient::new(
+            local,
+            TurnConfig {
+                transport: TurnTransport::Tls,
+                ..TurnConfig::new("192.0.2.1:5349".parse().unwrap(), "user", "pass")
+            },
+        );
+
+        // the responses arrive in arbitrary chunks of the stream.
+        client.allocate(now);
+        while let Some(request) = client.poll_transmit() {
+            let response = serve(&request);
+            let (head, tail) = response.split_at(7);
+            assert_eq!(client.handle_stream_data(head, now), Ok(vec![]));
+            assert_eq!(client.handle_stream_data(tail, now), Ok(vec![]));
+        }
+        assert_eq!(client.get_state(), TurnState::Allocated);
+
+        client.bind_channel(peer, now).unwrap();
+        let bind = client.poll_transmit().unwrap();
+        client.handle_stream_data(&serve(&bind), now).unwrap();
+        client.send_to(peer, b"hello", now).unwrap();
+        assert_eq!(
+            client.poll_transmit().unwrap().data,
+            b"\x40\x00\x00\x05hello\x00\x00\x00".to_vec()
+        );
+
+        let mut stream = ChannelData::new(MIN_CHANNEL_NUMBER, b"a".to_vec()).to_bytes(true);
+        stream.extend(ChannelData::new(MIN_CHANNEL_NUMBER, b"b".to_vec()).to_bytes(true));
+        assert_eq!(
+            client.handle_stream_data(&stream, now),
+            Ok(vec![(peer, b"a".to_vec()), (peer, b"b".to_vec())])
+        );
+        assert_eq!(
+            client.handle_stream_data(&[0xff, 0, 0, 0], now),
+            Err(TurnError::InvalidChannelData)
+        );
+    }
+
+    #[test]
+    fn stream_timeout_test() {
+        let now = Instant::now();
+        let mut client = TurnClient::new(
+            "192.168.1.10:51000".parse().unwrap(),
+            TurnConfig {
+                transport: TurnTransport::Tcp,
+                ..TurnConfig::new("192.0.2.1:3478".parse().unwrap(), "user", "pass")
+            },
+        );
+        client.allocate(now);
+        assert!(client.poll_transmit().is_some());
+        assert_eq!(
+            client.poll_timeout(),
+            Some(now + STREAM_TRANSACTION_TIMEOUT)
+        );
+        client.process(now + STREAM_TRANSACTION_TIMEOUT);
+        assert_eq!(client.poll_transmit(), None);
+        assert_eq!(client.get_state(), TurnState::Failed);
+    }
+}
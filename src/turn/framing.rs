@@ -0,0 +1,89 @@
+// https://tools.ietf.org/html/rfc8656#section-12.5
+
+/*
+    Over TCP and TLS the client-server leg is a byte stream. Unlike RFC 4571
+    there is no length prefix: every frame is delimited by its own header.
+
+    0b00 STUN:        20 bytes header + Message Length
+    0b01 ChannelData:  4 bytes header + Length, padded to a multiple of 4
+*/
+
+use crate::stun::message::STUN_HEADER_LENGTH;
+use crate::turn::channel_data::CHANNEL_DATA_HEADER_LENGTH;
+use crate::turn::{Result, TurnError};
+
+// TCPで受け取ったbyte列をSTUNとChannelDataのframeに切り分ける．
+#[derive(Debug, Clone, Default)]
+pub struct StreamFramer {
+    buffer: Vec<u8>,
+}
+
+impl StreamFramer {
+    pub fn new() -> Self {
+        StreamFramer::default()
+    }
+
+    pub fn get_buffered_length(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete frame, with the ChannelData padding
+    /// removed. An error means the stream is out of sync and has to be
+    /// closed.
+    pub fn pop(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.buffer.len() < CHANNEL_DATA_HEADER_LENGTH {
+            return Ok(None);
+        }
+        let length = usize::from(u16::from_be_bytes([self.buffer[2], self.buffer[3]]));
+        let (frame_length, consumed) = match self.buffer[0] & 0xc0 {
+            0x00 => (STUN_HEADER_LENGTH + length, STUN_HEADER_LENGTH + length),
+            0x40 => {
+                let frame_length = CHANNEL_DATA_HEADER_LENGTH + length;
+                (frame_length, (frame_length + 3) & !3)
+            }
+            _ => return Err(TurnError::InvalidChannelData),
+        };
+        if self.buffer.len() < consumed {
+            return Ok(None);
+        }
+        let frame = self.buffer[..frame_length].to_vec();
+        self.buffer.drain(..consumed);
+        Ok(Some(frame))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stun::message::{StunMessage, METHOD_BINDING};
+    use crate::turn::channel_data::ChannelData;
+
+    #[test]
+    fn stream_framer_test() {
+        let stun = StunMessage::request(METHOD_BINDING).to_bytes();
+        let channel = ChannelData::new(0x4000, b"hello".to_vec());
+
+        let mut stream = stun.clone();
+        stream.extend_from_slice(&channel.to_bytes(true));
+        stream.extend_from_slice(&stun);
+
+        // fed one byte at a time, as TCP may deliver it.
+        let mut framer = StreamFramer::new();
+        let mut frames = vec![];
+        for byte in &stream {
+            framer.push(&[*byte]);
+            while let Some(frame) = framer.pop().unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert_eq!(frames, vec![stun.clone(), channel.to_bytes(false), stun]);
+        assert_eq!(framer.get_buffered_length(), 0);
+
+        framer.push(&[0x80, 0, 0, 0]);
+        assert_eq!(framer.pop(), Err(TurnError::InvalidChannelData));
+    }
+}
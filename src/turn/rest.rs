@@ -0,0 +1,134 @@
+// https://tools.ietf.org/html/draft-uberti-behave-turn-rest-00
+
+/*
+    username = expiry timestamp (UNIX seconds) [":" user id]
+    password = base64(HMAC-SHA1(shared secret, username))
+
+    The web service and the TURN server (e.g. coturn with use-auth-secret)
+    share the secret, so the server checks the credentials without a
+    database and refuses them after the expiry.
+*/
+
+use crate::turn::client::TurnConfig;
+use crate::turn::{Result, TurnError};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+
+pub const REST_USERNAME_SEPARATOR: char = ':';
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RestCredentials {
+    pub username: String,
+    pub password: String,
+    /// UNIX seconds.
+    pub expires_at: u64,
+}
+
+impl RestCredentials {
+    /// Generates credentials valid for `ttl` from `now`.
+    pub fn generate(secret: &[u8], user_id: Option<&str>, ttl: Duration, now: SystemTime) -> Self {
+        let expires_at = get_unix_time(now) + ttl.as_secs();
+        let username = match user_id {
+            Some(v) => format!("{}{}{}", expires_at, REST_USERNAME_SEPARATOR, v),
+            None => expires_at.to_string(),
+        };
+        RestCredentials {
+            password: get_rest_password(secret, &username),
+            username,
+            expires_at,
+        }
+    }
+
+    /// Parses credentials handed out by a REST API.
+    pub fn parse(username: &str, password: &str) -> Result<Self> {
+        let (expires_at, _) = parse_rest_username(username)?;
+        Ok(RestCredentials {
+            username: username.to_string(),
+            password: password.to_string(),
+            expires_at,
+        })
+    }
+
+    pub fn get_user_id(&self) -> Option<&str> {
+        parse_rest_username(&self.username).ok().and_then(|v| v.1)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        get_unix_time(now) >= self.expires_at
+    }
+
+    /// What the server checks: the password matches the secret and the
+    /// credentials have not expired.
+    pub fn verify(&self, secret: &[u8], now: SystemTime) -> bool {
+        let expected = get_rest_password(secret, &self.username);
+        !self.is_expired(now) && bool::from(expected.as_bytes().ct_eq(self.password.as_bytes()))
+    }
+
+    pub fn to_turn_config(&self, server: SocketAddr) -> TurnConfig {
+        TurnConfig::new(server, &self.username, &self.password)
+    }
+}
+
+/// Splits a REST username into the expiry and the optional user id.
+pub fn parse_rest_username(username: &str) -> Result<(u64, Option<&str>)> {
+    let (timestamp, user_id) = match username.find(REST_USERNAME_SEPARATOR) {
+        Some(i) => (&username[..i], Some(&username[i + 1..])),
+        None => (username, None),
+    };
+    let expires_at = timestamp
+        .parse()
+        .map_err(|_| TurnError::InvalidCredentials)?;
+    Ok((expires_at, user_id))
+}
+
+pub fn get_rest_password(secret: &[u8], username: &str) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(username.as_bytes());
+    STANDARD.encode(mac.finalize().into_bytes())
+}
+
+fn get_unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|v| v.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rest_credentials_test() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_735_689_600);
+        let credentials =
+            RestCredentials::generate(b"north", Some("alice"), Duration::from_secs(3600), now);
+        assert_eq!(credentials.username, "1735693200:alice");
+        assert_eq!(credentials.password, "gJrx9uItvwHmahU43r92XE/ydKs=");
+        assert_eq!(credentials.get_user_id(), Some("alice"));
+
+        assert!(credentials.verify(b"north", now));
+        assert!(!credentials.verify(b"south", now));
+        assert!(!credentials.verify(b"north", now + Duration::from_secs(3600)));
+
+        let parsed = RestCredentials::parse(&credentials.username, &credentials.password).unwrap();
+        assert_eq!(parsed, credentials);
+        let config = parsed.to_turn_config("192.0.2.1:3478".parse().unwrap());
+        assert_eq!(config.username, "1735693200:alice");
+        assert_eq!(config.password, "gJrx9uItvwHmahU43r92XE/ydKs=");
+
+        let anonymous = RestCredentials::generate(b"north", None, Duration::from_secs(60), now);
+        assert_eq!(anonymous.username, "1735689660");
+        assert_eq!(anonymous.get_user_id(), None);
+
+        assert_eq!(
+            RestCredentials::parse("alice:1735693200", "x"),
+            Err(TurnError::InvalidCredentials)
+        );
+    }
+}
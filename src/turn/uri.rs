@@ -0,0 +1,163 @@
+// https://tools.ietf.org/html/rfc7065#section-3.1
+
+/*
+    turnURI   = scheme ":" host [ ":" port ] [ "?transport=" transport ]
+    scheme    = "turn" / "turns"
+    transport = "udp" / "tcp"
+
+    turn:  UDP by default on 3478
+    turns: TLS over TCP on 5349
+*/
+
+use crate::turn::{Result, TurnError};
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+pub const DEFAULT_TURN_PORT: u16 = 3478;
+pub const DEFAULT_TURNS_PORT: u16 = 5349;
+
+/// How the client reaches the TURN server. The relayed leg is always UDP.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum TurnTransport {
+    Udp,
+    Tcp,
+    Tls,
+}
+
+impl TurnTransport {
+    pub fn is_stream(self) -> bool {
+        self != TurnTransport::Udp
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TurnUri {
+    pub host: String,
+    pub port: u16,
+    pub transport: TurnTransport,
+}
+
+impl TurnUri {
+    pub fn parse(value: &str) -> Result<TurnUri> {
+        let (secure, rest) = if let Some(v) = value.strip_prefix("turns:") {
+            (true, v)
+        } else if let Some(v) = value.strip_prefix("turn:") {
+            (false, v)
+        } else {
+            return Err(TurnError::InvalidUri);
+        };
+
+        let (authority, query) = match rest.find('?') {
+            Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+            None => (rest, None),
+        };
+        let transport = match (secure, query) {
+            (true, None) | (true, Some("transport=tcp")) => TurnTransport::Tls,
+            (false, None) | (false, Some("transport=udp")) => TurnTransport::Udp,
+            (false, Some("transport=tcp")) => TurnTransport::Tcp,
+            _ => return Err(TurnError::InvalidUri),
+        };
+
+        // an IPv6 literal is in brackets.
+        let (host, port) = if let Some(v) = authority.strip_prefix('[') {
+            let end = v.find(']').ok_or(TurnError::InvalidUri)?;
+            let port = match &v[end + 1..] {
+                "" => None,
+                p => Some(p.strip_prefix(':').ok_or(TurnError::InvalidUri)?),
+            };
+            (&v[..end], port)
+        } else {
+            match authority.rfind(':') {
+                Some(i) => (&authority[..i], Some(&authority[i + 1..])),
+                None => (authority, None),
+            }
+        };
+        if host.is_empty() {
+            return Err(TurnError::InvalidUri);
+        }
+        let port = match port {
+            Some(v) => v.parse().map_err(|_| TurnError::InvalidUri)?,
+            None if secure => DEFAULT_TURNS_PORT,
+            None => DEFAULT_TURN_PORT,
+        };
+
+        Ok(TurnUri {
+            host: host.to_string(),
+            port,
+            transport,
+        })
+    }
+
+    /// The server address when the host is an IP literal; a hostname has
+    /// to be resolved by the caller.
+    pub fn get_socket_addr(&self) -> Option<SocketAddr> {
+        let ip = self.host.parse::<IpAddr>().ok()?;
+        Some(SocketAddr::new(ip, self.port))
+    }
+}
+
+impl fmt::Display for TurnUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scheme = match self.transport {
+            TurnTransport::Tls => "turns",
+            _ => "turn",
+        };
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        write!(f, "{}:{}:{}", scheme, host, self.port)?;
+        if self.transport == TurnTransport::Tcp {
+            write!(f, "?transport=tcp")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_uri_test() {
+        let uri = TurnUri::parse("turn:turn.example.org").unwrap();
+        assert_eq!(uri.host, "turn.example.org");
+        assert_eq!(uri.port, DEFAULT_TURN_PORT);
+        assert_eq!(uri.transport, TurnTransport::Udp);
+        assert_eq!(uri.get_socket_addr(), None);
+
+        let uri = TurnUri::parse("turn:192.0.2.1:443?transport=tcp").unwrap();
+        assert_eq!(uri.transport, TurnTransport::Tcp);
+        assert_eq!(
+            uri.get_socket_addr(),
+            Some("192.0.2.1:443".parse().unwrap())
+        );
+        assert_eq!(uri.to_string(), "turn:192.0.2.1:443?transport=tcp");
+
+        let uri = TurnUri::parse("turns:[2001:db8::1]").unwrap();
+        assert_eq!(uri.transport, TurnTransport::Tls);
+        assert_eq!(
+            uri.get_socket_addr(),
+            Some("[2001:db8::1]:5349".parse().unwrap())
+        );
+        assert_eq!(uri.to_string(), "turns:[2001:db8::1]:5349");
+
+        for value in &[
+            "stun:192.0.2.1",
+            "turn:",
+            "turn:192.0.2.1:port",
+            "turns:192.0.2.1?transport=udp",
+            "turn:192.0.2.1?transport=sctp",
+            "turn:[2001:db8::1",
+        ] {
+            assert_eq!(
+                TurnUri::parse(value),
+                Err(TurnError::InvalidUri),
+                "{}",
+                value
+            );
+        }
+    }
+}
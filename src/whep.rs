@@ -0,0 +1,182 @@
+// https://datatracker.ietf.org/doc/draft-ietf-wish-whep/
+
+/*
+    a WHEP client, the playback side of WHIP: the same POST of the offer,
+    trickle by PATCH and DELETE of the session URL, with the requests and
+    errors of whip. the offer receives, a recvonly audio and video
+    transceiver unless the connection has transceivers of its own:
+
+      whep.connect(&mut connection, &mut network, now)
+      loop {
+          request = whep.poll_request()   -> the HTTP client
+              -> whep.handle_response(&mut connection, request.id, ...)
+          IceCandidate event              -> whep.add_local_candidate
+          Track event                     -> RemoteTrack::poll_frame
+      }
+      whep.disconnect(&mut connection, now)
+*/
+
+use crate::dtls::transport::DtlsBackend;
+use crate::ice::network::NetworkProvider;
+use crate::jsep::signaling::IceCandidateInit;
+use crate::rtcpeerconnection::{Result, RtcPeerConnection};
+use crate::sdp::media::{Direction, MediaKind};
+use crate::time::Instant;
+use crate::whip::client::{WhipClient, WhipState};
+use crate::whip::{HttpRequest, HttpResponse, RtcIceServer};
+
+// WHIPと同じsessionで受ける側．
+pub struct WhepClient {
+    client: WhipClient,
+}
+
+impl WhepClient {
+    /// `token` is sent as the Bearer of an Authorization header.
+    pub fn new(endpoint: &str, token: Option<&str>) -> Self {
+        WhepClient {
+            client: WhipClient::new(endpoint, token),
+        }
+    }
+
+    pub fn get_state(&self) -> WhipState {
+        self.client.get_state()
+    }
+
+    /// The URL of the session, of the Location of the answer.
+    pub fn get_session_url(&self) -> Option<&str> {
+        self.client.get_session_url()
+    }
+
+    pub fn get_etag(&self) -> Option<&str> {
+        self.client.get_etag()
+    }
+
+    /// The ICE servers of the Link headers of the answer.
+    pub fn get_ice_servers(&self) -> &[RtcIceServer] {
+        self.client.get_ice_servers()
+    }
+
+    /// Posts an offer to receive, set as the local description of
+    /// `connection`.
+    pub fn connect<B: DtlsBackend, P: NetworkProvider + ?Sized>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        network: &mut P,
+        now: Instant,
+    ) -> Result<()> {
+        if self.client.get_state() == WhipState::New
+            && connection.get_session().get_transceivers().is_empty()
+        {
+            connection.add_transceiver(MediaKind::Audio, Direction::RecvOnly);
+            connection.add_transceiver(MediaKind::Video, Direction::RecvOnly);
+        }
+        self.client.connect(connection, network, now)
+    }
+
+    /// A local candidate of the IceCandidate event of `connection`, sent
+    /// once the session URL is known.
+    pub fn add_local_candidate(&mut self, init: &IceCandidateInit) {
+        self.client.add_local_candidate(init);
+    }
+
+    /// Deletes the session and closes `connection`.
+    pub fn disconnect<B: DtlsBackend>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        now: Instant,
+    ) {
+        self.client.disconnect(connection, now);
+    }
+
+    pub fn poll_request(&mut self) -> Option<HttpRequest> {
+        self.client.poll_request()
+    }
+
+    /// Handles the response to the request `id`.
+    pub fn handle_response<B: DtlsBackend>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        id: u64,
+        response: &HttpResponse,
+        now: Instant,
+    ) -> Result<()> {
+        self.client.handle_response(connection, id, response, now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jsep::track::MediaStreamTrack;
+    use crate::jsep::{Description, SdpType};
+    use crate::rtcpeerconnection::test::{new_network, new_peer};
+    use crate::rtcpeerconnection::PeerConnectionEvent;
+    use crate::whip::{HttpMethod, SDP_CONTENT_TYPE};
+
+    #[test]
+    fn whep_client_test() {
+        let now = Instant::now();
+        let mut player = new_peer(b"a");
+        let mut server = new_peer(b"b");
+        let mut whep = WhepClient::new("https://cdn.example.com/whep/stream", None);
+        whep.connect(&mut player, &mut new_network("10.0.0.1"), now)
+            .unwrap();
+        assert_eq!(player.get_session().get_transceivers().len(), 2);
+
+        let post = whep.poll_request().unwrap();
+        assert_eq!(post.method, HttpMethod::Post);
+        assert_eq!(post.get_header("Content-Type"), Some(SDP_CONTENT_TYPE));
+        assert_eq!(post.body.matches("a=recvonly").count(), 2);
+
+        // the server sends a track on each m= section.
+        let offer = Description::new(SdpType::Offer, &post.body);
+        server.set_remote_description(&offer, now).unwrap();
+        server
+            .add_track(MediaStreamTrack::new(MediaKind::Audio), vec![])
+            .unwrap();
+        server
+            .add_track(MediaStreamTrack::new(MediaKind::Video), vec![])
+            .unwrap();
+        let answer = server.create_answer().unwrap();
+        assert_eq!(answer.sdp.matches("a=sendonly").count(), 2);
+        server
+            .set_local_description(&answer, &mut new_network("10.0.0.2"), now)
+            .unwrap();
+        let response = HttpResponse::new(201)
+            .with_header("Location", "https://edge.example.com/whep/session/7")
+            .with_body(&answer.sdp);
+        whep.handle_response(&mut player, post.id, &response, now)
+            .unwrap();
+        assert_eq!(whep.get_state(), WhipState::Connected);
+        assert_eq!(
+            whep.get_session_url(),
+            Some("https://edge.example.com/whep/session/7")
+        );
+        let mut tracks = 0;
+        while let Some(event) = player.poll_event() {
+            match event {
+                PeerConnectionEvent::IceCandidate(init) => whep.add_local_candidate(&init),
+                PeerConnectionEvent::Track { .. } => tracks += 1,
+                _ => {}
+            }
+        }
+        assert_eq!(tracks, 2);
+
+        // a PATCH for each candidate after the answer.
+        let mut candidates = 0;
+        while let Some(patch) = whep.poll_request() {
+            assert_eq!(patch.method, HttpMethod::Patch);
+            assert_eq!(patch.get_header("If-Match"), None);
+            candidates += patch.body.matches("a=candidate:").count();
+            whep.handle_response(&mut player, patch.id, &HttpResponse::new(204), now)
+                .unwrap();
+        }
+        assert!(candidates > 0);
+
+        whep.disconnect(&mut player, now);
+        let delete = whep.poll_request().unwrap();
+        assert_eq!(delete.method, HttpMethod::Delete);
+        assert_eq!(delete.url, "https://edge.example.com/whep/session/7");
+        assert_eq!(whep.get_state(), WhipState::Closed);
+    }
+}
@@ -0,0 +1,285 @@
+// https://www.rfc-editor.org/rfc/rfc9725
+// https://www.rfc-editor.org/rfc/rfc8840#section-9
+
+/*
+    a WHIP client over RtcPeerConnection, sans-IO: the HTTP requests come
+    out of poll_request, sent by the HTTP client of the application, and
+    their responses go to handle_response.
+
+      POST   endpoint       the offer, application/sdp
+             <- 201 Created, Location: the session URL, the answer, the
+                ICE servers in the Link headers
+      PATCH  session URL    the local candidates, trickled as an
+                            application/trickle-ice-sdpfrag
+             <- 204 No Content
+      DELETE session URL    the teardown
+
+    the candidates before the answer are sent in one PATCH after it. a
+    server answering the PATCH with 405 or 501 does not trickle, the ones
+    after that are not sent. an ICE restart is a session of its own.
+
+    the ICE servers of the Link headers are RtcIceServers, the TURN ones a
+    TurnConfig for a GatherConfig, the host resolved by the application.
+*/
+
+pub mod client;
+
+use crate::turn::client::TurnConfig;
+use crate::turn::uri::TurnUri;
+
+use failure::Fail;
+use std::net::SocketAddr;
+
+pub const SDP_CONTENT_TYPE: &str = "application/sdp";
+pub const TRICKLE_CONTENT_TYPE: &str = "application/trickle-ice-sdpfrag";
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum WhipError {
+    #[fail(display = "WHIP response is {}.", status)]
+    UnexpectedStatus { status: u16 },
+
+    #[fail(display = "WHIP response is invalid: {}", reason)]
+    InvalidResponse { reason: String },
+
+    #[fail(display = "WHIP session can't be used in {:?}.", state)]
+    InvalidState { state: client::WhipState },
+
+    #[fail(display = "WHIP request {} is not waiting for a response.", id)]
+    UnknownRequest { id: u64 },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum HttpMethod {
+    Post,
+    Patch,
+    Delete,
+}
+
+impl HttpMethod {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            HttpMethod::Post => "POST",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Delete => "DELETE",
+        }
+    }
+}
+
+// アプリケーションのHTTP clientで送るrequest．
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct HttpRequest {
+    /// Given back to handle_response with the response.
+    pub id: u64,
+    pub method: HttpMethod,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpRequest {
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        get_header(&self.headers, name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct HttpResponse {
+    pub status: u16,
+    /// In the order received, a header given more than once each time.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16) -> Self {
+        HttpResponse {
+            status,
+            ..HttpResponse::default()
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: &str) -> Self {
+        self.body = body.to_string();
+        self
+    }
+
+    /// The first one, the name case-insensitive.
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        get_header(&self.headers, name)
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+fn get_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(v, _)| v.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+// Link headerのrel="ice-server"．RTCIceServerのurlが一つのもの．
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct RtcIceServer {
+    /// A stun:, stuns:, turn: or turns: URI.
+    pub url: String,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+impl RtcIceServer {
+    pub fn is_turn(&self) -> bool {
+        self.url.starts_with("turn:") || self.url.starts_with("turns:")
+    }
+
+    /// The TURN server of the URI, at `server` resolved from its host.
+    pub fn get_turn_config(&self, server: SocketAddr) -> Option<TurnConfig> {
+        let uri = TurnUri::parse(&self.url).ok()?;
+        let mut config = TurnConfig::new(
+            server,
+            self.username.as_deref()?,
+            self.credential.as_deref()?,
+        );
+        config.transport = uri.transport;
+        Some(config)
+    }
+}
+
+/// Splits `value` at `separator` outside of quotes and angle brackets.
+fn split_outside(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut quoted, mut bracketed, mut start) = (false, false, 0);
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '<' if !quoted => bracketed = true,
+            '>' if !quoted => bracketed = false,
+            c if c == separator && !quoted && !bracketed => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+/// The ICE servers of a Link header (RFC 8288), the links of other
+/// relations left out.
+pub fn parse_link_header(value: &str) -> Vec<RtcIceServer> {
+    let mut servers = vec![];
+    for link in split_outside(value, ',') {
+        let link = link.trim();
+        let end = match link.find('>') {
+            Some(v) if link.starts_with('<') => v,
+            _ => continue,
+        };
+        let mut server = RtcIceServer {
+            url: link[1..end].trim().to_string(),
+            ..RtcIceServer::default()
+        };
+        let mut is_ice_server = false;
+        for parameter in split_outside(&link[end + 1..], ';') {
+            let (name, value) = match parameter.find('=') {
+                Some(i) => (parameter[..i].trim(), parameter[i + 1..].trim()),
+                None => continue,
+            };
+            let value = value.trim_matches('"').to_string();
+            match name.to_ascii_lowercase().as_str() {
+                "rel" => is_ice_server = value.split_whitespace().any(|v| v == "ice-server"),
+                "username" => server.username = Some(value),
+                "credential" => server.credential = Some(value),
+                _ => {}
+            }
+        }
+        if is_ice_server {
+            servers.push(server);
+        }
+    }
+    servers
+}
+
+/// `reference`, a Location header, resolved against the URL it came from.
+pub fn resolve_url(base: &str, reference: &str) -> String {
+    if reference.contains("://") {
+        return reference.to_string();
+    }
+    let scheme_end = base.find("://").map(|v| v + 3).unwrap_or(0);
+    if reference.starts_with("//") {
+        let scheme = base.find("://").map(|v| &base[..v]).unwrap_or("https");
+        return format!("{}:{}", scheme, reference);
+    }
+    let path_start = base[scheme_end..]
+        .find('/')
+        .map(|v| scheme_end + v)
+        .unwrap_or(base.len());
+    if reference.starts_with('/') {
+        return format!("{}{}", &base[..path_start], reference);
+    }
+    let path = base[path_start..].split(['?', '#']).next().unwrap_or("");
+    let directory = path.rfind('/').map(|v| &path[..=v]).unwrap_or("/");
+    format!("{}{}{}", &base[..path_start], directory, reference)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::turn::uri::TurnTransport;
+
+    #[test]
+    fn parse_link_header_test() {
+        let servers = parse_link_header(
+            "<stun:stun.example.net>; rel=\"ice-server\", \
+             <turn:turn.example.net?transport=tcp>; rel=\"ice-server\"; \
+             username=\"user\"; credential=\"my,Password\"; credential-type=\"password\", \
+             <https://example.net/docs>; rel=\"help\"",
+        );
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].url, "stun:stun.example.net");
+        assert!(!servers[0].is_turn());
+        assert_eq!(
+            servers[0].get_turn_config("192.0.2.1:3478".parse().unwrap()),
+            None
+        );
+        assert_eq!(servers[1].username.as_deref(), Some("user"));
+        assert_eq!(servers[1].credential.as_deref(), Some("my,Password"));
+        let config = servers[1]
+            .get_turn_config("192.0.2.1:3478".parse().unwrap())
+            .unwrap();
+        assert_eq!(config.transport, TurnTransport::Tcp);
+        assert_eq!(config.password, "my,Password");
+    }
+
+    #[test]
+    fn resolve_url_test() {
+        let base = "https://example.com/whip/endpoint?token=1";
+        assert_eq!(
+            resolve_url(base, "https://other.example.com/session"),
+            "https://other.example.com/session"
+        );
+        assert_eq!(
+            resolve_url(base, "//cdn.example.com/s/1"),
+            "https://cdn.example.com/s/1"
+        );
+        assert_eq!(
+            resolve_url(base, "/whip/resource/1"),
+            "https://example.com/whip/resource/1"
+        );
+        assert_eq!(
+            resolve_url(base, "resource/1"),
+            "https://example.com/whip/resource/1"
+        );
+        assert_eq!(
+            resolve_url("https://example.com", "resource/1"),
+            "https://example.com/resource/1"
+        );
+    }
+}
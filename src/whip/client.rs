@@ -0,0 +1,456 @@
+use crate::dtls::transport::DtlsBackend;
+use crate::ice::network::NetworkProvider;
+use crate::jsep::signaling::IceCandidateInit;
+use crate::jsep::{Description, SdpType};
+use crate::rtcpeerconnection::{Result, RtcPeerConnection};
+use crate::time::Instant;
+use crate::whip::{
+    parse_link_header, resolve_url, HttpMethod, HttpRequest, HttpResponse, RtcIceServer, WhipError,
+    SDP_CONTENT_TYPE, TRICKLE_CONTENT_TYPE,
+};
+
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WhipState {
+    New,
+    /// The offer is posted.
+    Connecting,
+    /// The answer is set and the session URL known.
+    Connected,
+    Closed,
+}
+
+// offerのICE credentialsと，m= sectionごとのm= lineとmid．
+#[derive(Debug, Clone, Default)]
+struct OfferSections {
+    ufrag: Option<String>,
+    pwd: Option<String>,
+    sections: Vec<(String, Option<String>)>,
+}
+
+impl OfferSections {
+    fn parse(sdp: &str) -> Self {
+        let mut offer = OfferSections::default();
+        for line in sdp.lines().map(|v| v.trim_end_matches('\r')) {
+            if line.starts_with("m=") {
+                offer.sections.push((line.to_string(), None));
+            } else if let Some(v) = line.strip_prefix("a=mid:") {
+                if let Some(section) = offer.sections.last_mut() {
+                    section.1 = Some(v.to_string());
+                }
+            } else if let Some(v) = line.strip_prefix("a=ice-ufrag:") {
+                offer.ufrag.get_or_insert_with(|| v.to_string());
+            } else if let Some(v) = line.strip_prefix("a=ice-pwd:") {
+                offer.pwd.get_or_insert_with(|| v.to_string());
+            }
+        }
+        offer
+    }
+
+    /// The m= section of the candidate, by the mid or else the index.
+    fn find(&self, init: &IceCandidateInit) -> usize {
+        init.sdp_mid
+            .as_ref()
+            .and_then(|mid| {
+                self.sections
+                    .iter()
+                    .position(|(_, v)| v.as_ref() == Some(mid))
+            })
+            .or_else(|| init.sdp_mline_index.map(usize::from))
+            .filter(|v| *v < self.sections.len())
+            .unwrap_or(0)
+    }
+
+    /// The application/trickle-ice-sdpfrag of `candidates`, by m= section.
+    fn get_fragment(&self, candidates: &[IceCandidateInit]) -> String {
+        let mut lines = vec![];
+        if let (Some(ufrag), Some(pwd)) = (&self.ufrag, &self.pwd) {
+            lines.push(format!("a=ice-ufrag:{}", ufrag));
+            lines.push(format!("a=ice-pwd:{}", pwd));
+        }
+        for (index, (mline, mid)) in self.sections.iter().enumerate() {
+            let mut section = candidates
+                .iter()
+                .filter(|v| self.find(v) == index)
+                .peekable();
+            if section.peek().is_none() {
+                continue;
+            }
+            lines.push(mline.clone());
+            if let Some(mid) = mid {
+                lines.push(format!("a=mid:{}", mid));
+            }
+            for candidate in section {
+                if candidate.candidate.trim().is_empty() {
+                    lines.push("a=end-of-candidates".to_string());
+                } else {
+                    lines.push(format!("a={}", candidate.candidate));
+                }
+            }
+        }
+        lines.iter().map(|v| format!("{}\r\n", v)).collect()
+    }
+}
+
+// 一つのWHIP session．requestのidと，答えを待つmethod．
+pub struct WhipClient {
+    endpoint: String,
+    token: Option<String>,
+    state: WhipState,
+    session_url: Option<String>,
+    etag: Option<String>,
+    ice_servers: Vec<RtcIceServer>,
+    trickle: bool,
+    offer: OfferSections,
+    candidates: Vec<IceCandidateInit>,
+    next_id: u64,
+    waiting: HashMap<u64, HttpMethod>,
+    requests: VecDeque<HttpRequest>,
+}
+
+impl WhipClient {
+    /// `token` is sent as the Bearer of an Authorization header.
+    pub fn new(endpoint: &str, token: Option<&str>) -> Self {
+        WhipClient {
+            endpoint: endpoint.to_string(),
+            token: token.map(|v| v.to_string()),
+            state: WhipState::New,
+            session_url: None,
+            etag: None,
+            ice_servers: vec![],
+            trickle: true,
+            offer: OfferSections::default(),
+            candidates: vec![],
+            next_id: 0,
+            waiting: HashMap::new(),
+            requests: VecDeque::new(),
+        }
+    }
+
+    pub fn get_state(&self) -> WhipState {
+        self.state
+    }
+
+    /// The URL of the session, of the Location of the answer.
+    pub fn get_session_url(&self) -> Option<&str> {
+        self.session_url.as_deref()
+    }
+
+    pub fn get_etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// The ICE servers of the Link headers of the answer.
+    pub fn get_ice_servers(&self) -> &[RtcIceServer] {
+        &self.ice_servers
+    }
+
+    /// Posts an offer of the transceivers of `connection`, set as its local
+    /// description.
+    pub fn connect<B: DtlsBackend, P: NetworkProvider + ?Sized>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        network: &mut P,
+        now: Instant,
+    ) -> Result<()> {
+        if self.state != WhipState::New {
+            return Err(WhipError::InvalidState { state: self.state }.into());
+        }
+        let offer = connection.create_offer()?;
+        connection.set_local_description(&offer, network, now)?;
+        self.offer = OfferSections::parse(&offer.sdp);
+        let url = self.endpoint.clone();
+        self.push_request(HttpMethod::Post, url, Some(SDP_CONTENT_TYPE), offer.sdp);
+        self.state = WhipState::Connecting;
+        Ok(())
+    }
+
+    /// A local candidate of the IceCandidate event of `connection`, sent
+    /// once the session URL is known.
+    pub fn add_local_candidate(&mut self, init: &IceCandidateInit) {
+        if !self.trickle || self.state == WhipState::Closed {
+            return;
+        }
+        self.candidates.push(init.clone());
+        self.flush_candidates();
+    }
+
+    /// Deletes the session and closes `connection`.
+    pub fn disconnect<B: DtlsBackend>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        now: Instant,
+    ) {
+        if self.state == WhipState::Closed {
+            return;
+        }
+        if let Some(url) = self.session_url.clone() {
+            self.push_request(HttpMethod::Delete, url, None, String::new());
+        }
+        connection.close(now);
+        self.state = WhipState::Closed;
+        self.candidates.clear();
+    }
+
+    pub fn poll_request(&mut self) -> Option<HttpRequest> {
+        self.requests.pop_front()
+    }
+
+    /// Handles the response to the request `id`.
+    pub fn handle_response<B: DtlsBackend>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        id: u64,
+        response: &HttpResponse,
+        now: Instant,
+    ) -> Result<()> {
+        let method = self
+            .waiting
+            .remove(&id)
+            .ok_or(WhipError::UnknownRequest { id })?;
+        if let Some(etag) = response.get_header("ETag") {
+            self.etag = Some(etag.to_string());
+        }
+        match method {
+            HttpMethod::Post => self.handle_answer(connection, response, now),
+            HttpMethod::Patch if response.status == 405 || response.status == 501 => {
+                // the server does not trickle.
+                self.trickle = false;
+                self.candidates.clear();
+                Ok(())
+            }
+            _ if !response.is_success() => Err(WhipError::UnexpectedStatus {
+                status: response.status,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_answer<B: DtlsBackend>(
+        &mut self,
+        connection: &mut RtcPeerConnection<B>,
+        response: &HttpResponse,
+        now: Instant,
+    ) -> Result<()> {
+        if response.status != 201 {
+            self.state = WhipState::Closed;
+            return Err(WhipError::UnexpectedStatus {
+                status: response.status,
+            }
+            .into());
+        }
+        let location =
+            response
+                .get_header("Location")
+                .ok_or_else(|| WhipError::InvalidResponse {
+                    reason: "no Location".to_string(),
+                })?;
+        let url = resolve_url(&self.endpoint, location);
+        if self.state == WhipState::Closed {
+            // disconnected while it was posted.
+            self.push_request(HttpMethod::Delete, url, None, String::new());
+            return Ok(());
+        }
+        self.session_url = Some(url);
+        self.ice_servers = response
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Link"))
+            .flat_map(|(_, value)| parse_link_header(value))
+            .collect();
+        let answer = Description::new(SdpType::Answer, &response.body);
+        if let Err(error) = connection.set_remote_description(&answer, now) {
+            self.state = WhipState::Closed;
+            return Err(error);
+        }
+        self.state = WhipState::Connected;
+        self.flush_candidates();
+        Ok(())
+    }
+
+    /// The candidates waiting in one PATCH.
+    fn flush_candidates(&mut self) {
+        let url = match self.session_url.clone() {
+            Some(v) if !self.candidates.is_empty() => v,
+            _ => return,
+        };
+        let fragment = self.offer.get_fragment(&self.candidates);
+        self.candidates.clear();
+        self.push_request(HttpMethod::Patch, url, Some(TRICKLE_CONTENT_TYPE), fragment);
+    }
+
+    fn push_request(
+        &mut self,
+        method: HttpMethod,
+        url: String,
+        content_type: Option<&str>,
+        body: String,
+    ) {
+        let mut headers = vec![];
+        if let Some(v) = content_type {
+            headers.push(("Content-Type".to_string(), v.to_string()));
+        }
+        if let (HttpMethod::Patch, Some(etag)) = (method, &self.etag) {
+            headers.push(("If-Match".to_string(), etag.clone()));
+        }
+        if let Some(token) = &self.token {
+            headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.waiting.insert(id, method);
+        self.requests.push_back(HttpRequest {
+            id,
+            method,
+            url,
+            headers,
+            body,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jsep::track::MediaStreamTrack;
+    use crate::rtcpeerconnection::test::{new_network, new_peer};
+    use crate::rtcpeerconnection::{PeerConnectionEvent, PeerConnectionState};
+    use crate::sdp::media::MediaKind;
+    use crate::WebrtcError;
+
+    const ENDPOINT: &str = "https://ingest.example.com/whip/live";
+
+    #[test]
+    fn whip_client_test() {
+        let now = Instant::now();
+        let mut client = new_peer(b"a");
+        let mut server = new_peer(b"b");
+        client
+            .add_track(MediaStreamTrack::new(MediaKind::Video), vec![])
+            .unwrap();
+        let mut whip = WhipClient::new(ENDPOINT, Some("secret"));
+        whip.connect(&mut client, &mut new_network("10.0.0.1"), now)
+            .unwrap();
+        assert_eq!(whip.get_state(), WhipState::Connecting);
+        assert!(whip
+            .connect(&mut client, &mut new_network("10.0.0.1"), now)
+            .is_err());
+
+        let post = whip.poll_request().unwrap();
+        assert_eq!(post.method, HttpMethod::Post);
+        assert_eq!(post.url, ENDPOINT);
+        assert_eq!(post.get_header("content-type"), Some(SDP_CONTENT_TYPE));
+        assert_eq!(post.get_header("Authorization"), Some("Bearer secret"));
+        assert_eq!(whip.poll_request(), None);
+
+        // the candidates wait for the session URL.
+        while let Some(event) = client.poll_event() {
+            if let PeerConnectionEvent::IceCandidate(init) = event {
+                whip.add_local_candidate(&init);
+            }
+        }
+        assert_eq!(whip.poll_request(), None);
+
+        let offer = Description::new(SdpType::Offer, &post.body);
+        server.set_remote_description(&offer, now).unwrap();
+        let answer = server.create_answer().unwrap();
+        server
+            .set_local_description(&answer, &mut new_network("10.0.0.2"), now)
+            .unwrap();
+        let response = HttpResponse::new(201)
+            .with_header("Location", "/whip/session/1")
+            .with_header("ETag", "\"1\"")
+            .with_header("Link", "<stun:stun.example.net>; rel=\"ice-server\"")
+            .with_header(
+                "link",
+                "<turn:turn.example.net>; rel=\"ice-server\"; username=\"u\"; credential=\"p\"",
+            )
+            .with_body(&answer.sdp);
+        whip.handle_response(&mut client, post.id, &response, now)
+            .unwrap();
+        assert_eq!(whip.get_state(), WhipState::Connected);
+        assert_eq!(
+            whip.get_session_url(),
+            Some("https://ingest.example.com/whip/session/1")
+        );
+        assert_eq!(whip.get_ice_servers().len(), 2);
+        assert!(whip.get_ice_servers()[1].is_turn());
+        assert_eq!(
+            whip.handle_response(&mut client, post.id, &response, now)
+                .unwrap_err()
+                .to_string(),
+            WebrtcError::from(WhipError::UnknownRequest { id: post.id }).to_string()
+        );
+
+        let patch = whip.poll_request().unwrap();
+        assert_eq!(patch.method, HttpMethod::Patch);
+        assert_eq!(patch.url, "https://ingest.example.com/whip/session/1");
+        assert_eq!(patch.get_header("If-Match"), Some("\"1\""));
+        assert_eq!(patch.get_header("Content-Type"), Some(TRICKLE_CONTENT_TYPE));
+        let lines: Vec<&str> = patch.body.lines().collect();
+        assert!(lines[0].starts_with("a=ice-ufrag:"));
+        assert!(lines[1].starts_with("a=ice-pwd:"));
+        assert!(lines[2].starts_with("m=video 9 "));
+        assert_eq!(lines[3], "a=mid:0");
+        assert!(lines[4].starts_with("a=candidate:"));
+        assert_eq!(lines.last(), Some(&"a=end-of-candidates"));
+        for line in &lines[4..lines.len() - 1] {
+            let init = IceCandidateInit {
+                candidate: line[2..].to_string(),
+                sdp_mid: Some("0".to_string()),
+                ..IceCandidateInit::default()
+            };
+            server.add_ice_candidate(&init, now).unwrap();
+        }
+
+        // the server does not trickle.
+        whip.handle_response(&mut client, patch.id, &HttpResponse::new(405), now)
+            .unwrap();
+        whip.add_local_candidate(&IceCandidateInit::default());
+        assert_eq!(whip.poll_request(), None);
+
+        whip.disconnect(&mut client, now);
+        assert_eq!(whip.get_state(), WhipState::Closed);
+        assert_eq!(client.get_connection_state(), PeerConnectionState::Closed);
+        let delete = whip.poll_request().unwrap();
+        assert_eq!(delete.method, HttpMethod::Delete);
+        assert_eq!(delete.url, "https://ingest.example.com/whip/session/1");
+        whip.handle_response(&mut client, delete.id, &HttpResponse::new(200), now)
+            .unwrap();
+    }
+
+    #[test]
+    fn whip_disconnect_while_posting_test() {
+        let now = Instant::now();
+        let mut client = new_peer(b"a");
+        client.add_transceiver(MediaKind::Audio, crate::sdp::media::Direction::SendOnly);
+        let mut whip = WhipClient::new(ENDPOINT, None);
+        whip.connect(&mut client, &mut new_network("10.0.0.1"), now)
+            .unwrap();
+        let post = whip.poll_request().unwrap();
+        assert_eq!(post.get_header("Authorization"), None);
+        whip.disconnect(&mut client, now);
+        assert_eq!(whip.poll_request(), None);
+
+        // the session made anyway is deleted.
+        let response = HttpResponse::new(201).with_header("Location", "session/2");
+        whip.handle_response(&mut client, post.id, &response, now)
+            .unwrap();
+        let delete = whip.poll_request().unwrap();
+        assert_eq!(delete.method, HttpMethod::Delete);
+        assert_eq!(delete.url, "https://ingest.example.com/whip/session/2");
+
+        let mut whip = WhipClient::new(ENDPOINT, None);
+        let mut client = new_peer(b"a");
+        client.add_transceiver(MediaKind::Audio, crate::sdp::media::Direction::SendOnly);
+        whip.connect(&mut client, &mut new_network("10.0.0.1"), now)
+            .unwrap();
+        let post = whip.poll_request().unwrap();
+        assert!(whip
+            .handle_response(&mut client, post.id, &HttpResponse::new(403), now)
+            .is_err());
+        assert_eq!(whip.get_state(), WhipState::Closed);
+    }
+}